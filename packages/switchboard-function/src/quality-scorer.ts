@@ -9,20 +9,35 @@
  * - Completeness: 40% (expected criteria coverage)
  * - Freshness: 20% (data recency)
  *
- * Returns quality score (0-100) and refund percentage (0-100)
+ * Returns quality score (0-100) and refund percentage (0-100), packed into
+ * the feed value as quality_score * 1000 + refund_percentage so the escrow
+ * program can verify both
  *
  * Note: This uses Jaccard similarity with keyword boosting as a heuristic
  * approximation of the Python verifier's ML-based semantic matching
- * (SentenceTransformer all-MiniLM-L6-v2). Refund and freshness formulas
- * match the Python verifier exactly.
+ * (SentenceTransformer all-MiniLM-L6-v2). The freshness formula matches the
+ * Python verifier exactly; refunds follow the escrow program's refund curves.
  */
 
 import {
   QualityScoringParams,
   QualityScoringResult,
   APIResponse,
+  RefundStep,
 } from './types';
 
+/** Feed values carry the quality score in the thousands */
+export const FEED_SCORE_SCALE = 1000;
+
+/** The escrow program's `DEFAULT_REFUND_CURVE`, used by new namespaces */
+export const DEFAULT_REFUND_CURVE: RefundStep[] = [
+  { maxQualityScore: 20, refundPercentage: 100 },
+  { maxQualityScore: 40, refundPercentage: 75 },
+  { maxQualityScore: 60, refundPercentage: 50 },
+  { maxQualityScore: 80, refundPercentage: 25 },
+  { maxQualityScore: 100, refundPercentage: 0 },
+];
+
 /**
  * Main entry point for Switchboard Function
  */
@@ -53,8 +68,12 @@ export default async function qualityScorer(
         100
     );
 
-    // Determine refund percentage based on quality
-    const refundPercentage = calculateRefundPercentage(qualityScore);
+    // Determine refund percentage based on quality, on the curve the program
+    // settles by: the escrow's own, else its namespace's
+    const refundPercentage = calculateRefundPercentage(
+      qualityScore,
+      params.escrowRefundCurve ?? params.refundCurve
+    );
 
     // Generate reasoning
     const reasoning = generateReasoning(
@@ -68,6 +87,7 @@ export default async function qualityScorer(
     return {
       quality_score: qualityScore,
       refund_percentage: refundPercentage,
+      feed_value: packFeedValue(qualityScore, refundPercentage),
       reasoning,
       timestamp: Date.now(),
       breakdown: {
@@ -83,6 +103,7 @@ export default async function qualityScorer(
     return {
       quality_score: 50,
       refund_percentage: 50,
+      feed_value: packFeedValue(50, 50),
       reasoning: `Error during scoring: ${error}. Conservative 50% refund applied.`,
       timestamp: Date.now(),
       breakdown: {
@@ -383,28 +404,53 @@ function extractTimestamps(data: any): Date[] {
 
 /**
  * Calculate refund percentage based on quality score
- * Matches Python verifier formula exactly
  *
- * Thresholds:
- * - 80-100: No refund (0%)
- * - 50-79: Sliding scale refund
- * - 0-49: Full refund (100%)
+ * Same lookup as the program: the first step covering the score, else no
+ * refund. Without a curve this is the program's default:
+ * - 0-20: Full refund (100%)
+ * - 21-40: 75%
+ * - 41-60: 50%
+ * - 61-80: 25%
+ * - 81-100: No refund (0%)
  *
  * @param qualityScore Quality score (0-100)
+ * @param refundCurve Curve the escrow settles by
  * @returns Refund percentage (0-100)
  */
-function calculateRefundPercentage(qualityScore: number): number {
-  if (qualityScore >= 80) {
-    return 0; // Good quality - no refund
-  }
+function calculateRefundPercentage(
+  qualityScore: number,
+  refundCurve: RefundStep[] = DEFAULT_REFUND_CURVE
+): number {
+  const step = refundCurve.find((s) => qualityScore <= s.maxQualityScore);
+  return step ? step.refundPercentage : 0;
+}
 
-  if (qualityScore >= 50) {
-    // Sliding scale matching Python: ((80 - score) / 80) * 100
-    return Math.round(((80 - qualityScore) / 80) * 100);
-  }
+/**
+ * Pack a result into the value reported to the feed
+ */
+export function packFeedValue(qualityScore: number, refundPercentage: number): number {
+  return qualityScore * FEED_SCORE_SCALE + refundPercentage;
+}
 
-  // Poor quality - full refund
-  return 100;
+/**
+ * Split a feed value back into its two percentages, rejecting values the
+ * program would reject
+ */
+export function unpackFeedValue(feedValue: number): {
+  qualityScore: number;
+  refundPercentage: number;
+} {
+  const qualityScore = Math.floor(feedValue / FEED_SCORE_SCALE);
+  const refundPercentage = feedValue % FEED_SCORE_SCALE;
+  if (
+    !Number.isInteger(feedValue) ||
+    feedValue < 0 ||
+    qualityScore > 100 ||
+    refundPercentage > 100
+  ) {
+    throw new Error(`Invalid quality feed value: ${feedValue}`);
+  }
+  return { qualityScore, refundPercentage };
 }
 
 /**
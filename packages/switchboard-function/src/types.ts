@@ -20,6 +20,28 @@ export interface QualityScoringParams {
 
   /** Transaction ID for tracking */
   transactionId?: string;
+
+  /**
+   * Refund curve of the escrow's namespace. The feed attests to the refund
+   * it implies, so it must match the curve the program settles by.
+   * Defaults to the program's default curve.
+   */
+  refundCurve?: RefundStep[];
+
+  /**
+   * Refund curve the escrow carries itself, e.g. from provider terms. The
+   * program prefers it to the namespace's, and so does the scorer.
+   */
+  escrowRefundCurve?: RefundStep[];
+}
+
+/**
+ * One step of a namespace refund curve: scores up to `maxQualityScore`
+ * are refunded `refundPercentage`
+ */
+export interface RefundStep {
+  maxQualityScore: number;
+  refundPercentage: number;
 }
 
 /**
@@ -32,6 +54,9 @@ export interface QualityScoringResult {
   /** Recommended refund percentage (0-100) */
   refund_percentage: number;
 
+  /** Value reported to the feed: quality_score * 1000 + refund_percentage */
+  feed_value: number;
+
   /** Human-readable reasoning for the score */
   reasoning: string;
 
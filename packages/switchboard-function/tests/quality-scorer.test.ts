@@ -0,0 +1,83 @@
+/**
+ * Unit Tests: refund percentages and feed values
+ */
+
+import qualityScorer, {
+  DEFAULT_REFUND_CURVE,
+  calculateRefundPercentage,
+  packFeedValue,
+  unpackFeedValue,
+} from '../src';
+
+describe('calculateRefundPercentage', () => {
+  it('should follow the program default curve without a curve', () => {
+    const testCases = [
+      { score: 0, expectedRefund: 100 },
+      { score: 20, expectedRefund: 100 },
+      { score: 21, expectedRefund: 75 },
+      { score: 45, expectedRefund: 50 },
+      { score: 65, expectedRefund: 25 },
+      { score: 80, expectedRefund: 25 },
+      { score: 81, expectedRefund: 0 },
+      { score: 100, expectedRefund: 0 },
+    ];
+
+    for (const testCase of testCases) {
+      expect(calculateRefundPercentage(testCase.score)).toBe(testCase.expectedRefund);
+    }
+  });
+
+  it('should refund nothing past the last step', () => {
+    const curve = [{ maxQualityScore: 50, refundPercentage: 100 }];
+
+    expect(calculateRefundPercentage(50, curve)).toBe(100);
+    expect(calculateRefundPercentage(51, curve)).toBe(0);
+  });
+});
+
+describe('feed values', () => {
+  it('should round-trip a score and its default refund', () => {
+    const refundPercentage = calculateRefundPercentage(65, DEFAULT_REFUND_CURVE);
+    const feedValue = packFeedValue(65, refundPercentage);
+
+    expect(feedValue).toBe(65025);
+    expect(unpackFeedValue(feedValue)).toEqual({ qualityScore: 65, refundPercentage: 25 });
+  });
+
+  it('should reject values the program rejects', () => {
+    expect(() => unpackFeedValue(101000)).toThrow();
+    expect(() => unpackFeedValue(65101)).toThrow();
+    expect(() => unpackFeedValue(-1)).toThrow();
+  });
+});
+
+describe('qualityScorer', () => {
+  const params = {
+    originalQuery: 'exploits',
+    dataReceived: {},
+    expectedCriteria: ['tx_hash'],
+  };
+
+  it('should prefer the escrow curve to the namespace curve', async () => {
+    const result = await qualityScorer({
+      ...params,
+      refundCurve: [{ maxQualityScore: 100, refundPercentage: 10 }],
+      escrowRefundCurve: [{ maxQualityScore: 100, refundPercentage: 90 }],
+    });
+
+    expect(result.refund_percentage).toBe(90);
+    expect(unpackFeedValue(result.feed_value)).toEqual({
+      qualityScore: result.quality_score,
+      refundPercentage: 90,
+    });
+  });
+
+  it('should fall back to the namespace curve', async () => {
+    const result = await qualityScorer({
+      ...params,
+      refundCurve: [{ maxQualityScore: 100, refundPercentage: 10 }],
+    });
+
+    expect(result.refund_percentage).toBe(10);
+  });
+});
@@ -131,7 +131,7 @@ async function main() {
 
     console.log('2⃣  Agent marks escrow as disputed...');
     const tx4 = await program.methods
-      .markDisputed()
+      .markDisputed(new anchor.BN(0), { lowQuality: {} }, new Array(32).fill(0))
       .accounts({
         escrow: disputeEscrowPda,
         agent: agent.publicKey,
@@ -0,0 +1,48 @@
+//! Byte layout of `Escrow` for indexers and memcmp filters
+//!
+//! Offsets include the 8-byte account discriminator. The fields up to and
+//! including `flags` keep these offsets in every layout version; flag bits
+//! are only ever added, never moved.
+
+/// Layout written by this program; `migrate_escrow` upgrades older accounts
+pub const ESCROW_VERSION: u8 = 1;
+
+pub const ESCROW_AGENT_OFFSET: usize = 8;
+pub const ESCROW_API_OFFSET: usize = 40;
+pub const ESCROW_NAMESPACE_OFFSET: usize = 72;
+pub const ESCROW_VERSION_OFFSET: usize = 104;
+/// Little-endian `u16`
+pub const ESCROW_FLAGS_OFFSET: usize = 105;
+
+/// Bits 0-1 of `flags` hold the `EscrowStatus`; bits 2-3 are reserved so the
+/// status can grow without moving the boolean flags
+pub const STATUS_MASK: u16 = 0b11;
+pub const STATUS_ACTIVE: u16 = 0;
+pub const STATUS_RELEASED: u16 = 1;
+pub const STATUS_DISPUTED: u16 = 2;
+pub const STATUS_RESOLVED: u16 = 3;
+
+/// Escrow amount is counted in both parties' `locked_value`
+pub const FLAG_EXPOSURE_TRACKED: u16 = 1 << 4;
+/// Arbitration opted out; release-only escrow
+pub const FLAG_NO_DISPUTE: u16 = 1 << 5;
+/// Dispute was split by default after oracle failure
+pub const FLAG_SETTLED_STALE: u16 = 1 << 6;
+/// Covered by the API's service bond at creation
+pub const FLAG_SERVICE_BONDED: u16 = 1 << 7;
+/// Bound to a `WorkAgreement` that resolutions must reference
+pub const FLAG_WORK_AGREEMENT: u16 = 1 << 8;
+/// Settles milestone by milestone through a `MilestonePlan`
+pub const FLAG_MILESTONES: u16 = 1 << 9;
+/// Held resolution was appealed to the namespace's verifier set
+pub const FLAG_APPEALED: u16 = 1 << 10;
+/// Vests to the API over the time lock through `withdraw_streamed`
+pub const FLAG_STREAMING: u16 = 1 << 11;
+/// Held resolution awaits a ruling from a human arbitrator
+pub const FLAG_ESCALATED: u16 = 1 << 12;
+/// Time lock counts from delivery instead of creation
+pub const FLAG_DELIVERY_CLOCK: u16 = 1 << 13;
+/// Compressed NFT receipt minted to the agent
+pub const FLAG_RECEIPT_MINTED: u16 = 1 << 14;
+/// Appended to its agent's and API's escrow index
+pub const FLAG_INDEXED: u16 = 1 << 15;
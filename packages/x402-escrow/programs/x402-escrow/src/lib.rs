@@ -6,9 +6,29 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
     ed25519_program,
-    sysvar::instructions::{load_instruction_at_checked, ID as INSTRUCTIONS_ID},
+    instruction::Instruction,
+    sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked, ID as INSTRUCTIONS_ID,
+    },
 };
 use switchboard_on_demand::on_demand::accounts::pull_feed::PullFeedAccountData;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, TokenAccount, TokenInterface,
+};
+
+pub mod layout;
+pub mod receipts;
+pub mod remaining_accounts;
+mod settlement;
+mod tokens;
+pub mod wormhole;
+
+use receipts::{mint_receipt, ReceiptAccounts};
+use remaining_accounts::{AccountExpectation, RemainingAccountsParser};
+use settlement::{apply_resolution, Resolution, SettlementAccounts};
+use tokens::{check_mint_extensions, TokenTransfer};
+use wormhole::WormholeAccounts;
 
 declare_id!("E5EiaJhbg6Bav1v3P211LNv1tAqa4fHVeuGgRBHsEu6n");
 
@@ -17,30 +37,141 @@ const MIN_TIME_LOCK: i64 = 3600;                    // 1 hour
 const MAX_TIME_LOCK: i64 = 2_592_000;               // 30 days
 const MAX_ESCROW_AMOUNT: u64 = 1_000_000_000_000;   // 1000 SOL
 const MIN_ESCROW_AMOUNT: u64 = 1_000_000;           // 0.001 SOL
-// Dispute window constant - currently handled per-escrow
-// const DISPUTE_WINDOW: i64 = 172_800;                // 48 hours
+const DISPUTE_WINDOW: i64 = 172_800;                // 48 hours from delivery, or creation until then
 const BASE_DISPUTE_COST: u64 = 1_000_000;           // 0.001 SOL
+const DISPUTE_BOND_REFUND_THRESHOLD: u8 = 75;       // Refund % at which the agent gets the dispute cost back
+const DEFAULT_DISPUTE_BOND_BPS: u16 = 100;          // 1% of the escrowed amount
+const MAX_DISPUTE_BOND_BPS: u16 = 2_000;            // 20%
+const MAX_QUEUE_VERIFIERS: usize = 32;
+const MAX_VERIFIER_SET_SIZE: usize = 16;
+const MAX_REGISTERED_VERIFIERS: usize = 32;
+const POOR_QUALITY_THRESHOLD: u8 = 30;              // Resolutions below this are a provider strike
+const NEUTRAL_REPUTATION_SCORE: u16 = 500;
+const REPUTATION_HALF_LIFE: i64 = 2_592_000;        // 30 days idle halves a score's lead over neutral
+const REPUTATION_HISTORY_LEN: usize = 30;
+const REPUTATION_WEIGHT_UNIT: u64 = MIN_ESCROW_AMOUNT; // Each 0.001 SOL escrowed adds one unit of quality weight
+const REPUTATION_WEIGHT_CAP: u64 = 100_000_000_000; // 100 SOL; larger escrows count no more
+const RECENT_COUNTERPARTIES: usize = 8;             // Window for spotting repeat dealing between two keys
+const STRIKES_BEFORE_SUSPENSION: u8 = 3;
+const PROVIDER_SUSPENSION_DURATION: i64 = 2_592_000; // 30 days
+const VERIFICATION_STAKE_LAMPORTS: u64 = 1_000_000_000; // 1 SOL for `VerificationLevel::Staked`
+const SPAM_FREE_ESCROWS_PER_HOUR: u16 = 2;          // 3rd escrow in an hour pays a deposit
+const SPAM_WINDOW: i64 = 3600;                      // Rolling 1 hour
+const SPAM_DEPOSIT_RETENTION: i64 = 86_400;         // Deposit of an unaccepted cancel is held 24 hours
+const MAX_BLOCKLIST_ENTRIES: usize = 32;
+const MAX_ALLOWLIST_ENTRIES: usize = 64;
+const MAX_CHANNEL_ENTRIES: usize = 32;              // unsettled debits per payment channel
+const MAX_METADATA_URI_LEN: usize = 200;
+const MAX_COMMENT_URI_LEN: usize = 200;
+const PROVIDER_RESPONSE_WINDOW: i64 = 86_400;       // 24 hours from the dispute
+const PRICE_FEED_MAX_AGE: i64 = 300;                // 5 minutes
+const PRICE_FEED_DECIMALS: u32 = 18;                // Switchboard fixed-point scale
+const QUALITY_FEED_SCALE: i128 = 1000;              // quality feeds report score * 1000 + refund
+const DEFAULT_ATTESTATION_MAX_AGE: i64 = 300;       // 5 minutes
+const MIN_ATTESTATION_MAX_AGE: i64 = 30;
+const MAX_ATTESTATION_MAX_AGE: i64 = 3_600;         // 1 hour
+const PYTH_PRICE_MAX_AGE: u64 = 60;                 // seconds
+const LAMPORTS_PER_SOL: u128 = 1_000_000_000;
+const MAX_RATE_LIMIT_OVERRIDE: i64 = 86_400;        // 24 hours
+const MAX_BATCH_RESOLUTIONS: usize = 8;             // Bounded by transaction size in practice
+const BATCH_ACCOUNTS_PER_RESOLUTION: usize = 5;
+const CRANK_ACCOUNTS_PER_ESCROW: usize = 4;
+const CRANK_TIP_LAMPORTS: u64 = 10_000;             // Two signature fees per escrow released
+const RELAYER_FEE_LAMPORTS: u64 = 10_000;           // Transaction and Ed25519 signature fees of a relayed resolution
+const MAX_FEE_BPS: u16 = 1_000;                     // 10%
+const SECONDS_PER_DAY: i64 = 86_400;
+const USED_NONCE_WORDS: usize = 4;                  // 256-nonce sliding window
+const DEFAULT_MAX_DISPUTE_WINDOW_EXTENSIONS: u8 = 2;
+const STALE_DISPUTE_TIMEOUT: i64 = 604_800;         // 7 days past expiry with no resolution
+const STALE_DISPUTE_REFUND_PERCENTAGE: u8 = 50;
+const MIN_VERIFIER_TIMEOUT: i64 = 86_400;           // 1 day for escrows with their own stale terms
+const REFUND_CURVE_STEPS: usize = 5;
+const SCORE_DIMENSIONS: usize = 4;                    // Weighted sub-scores a work agreement may ask for
+// Full refund at 20 or below, nothing above 80
+const DEFAULT_REFUND_CURVE: [RefundStep; REFUND_CURVE_STEPS] = [
+    RefundStep { max_quality_score: 20, refund_percentage: 100 },
+    RefundStep { max_quality_score: 40, refund_percentage: 75 },
+    RefundStep { max_quality_score: 60, refund_percentage: 50 },
+    RefundStep { max_quality_score: 80, refund_percentage: 25 },
+    RefundStep { max_quality_score: 100, refund_percentage: 0 },
+];
+const AMOUNT_TIERS: usize = 3;
+// New agents escrow up to 1 SOL; a score above 800 unlocks the full cap
+const DEFAULT_AMOUNT_TIERS: [AmountTier; AMOUNT_TIERS] = [
+    AmountTier { min_reputation_score: 0, max_amount: 1_000_000_000 },
+    AmountTier { min_reputation_score: 650, max_amount: 100_000_000_000 },
+    AmountTier { min_reputation_score: 801, max_amount: MAX_ESCROW_AMOUNT },
+];
+const MAX_BPS: u16 = 10_000;
+const DEFAULT_MAX_INSURANCE_PAYOUT_BPS: u16 = 5_000; // 50% of the escrowed amount
+const MAX_RELEASE_INSURANCE_BPS: u16 = 100;         // 1% of each release
+const MAX_ORACLE_FEE_BPS: u16 = 500;                // 5% of the disputed amount
+const MAX_VERIFIER_PAUSE: i64 = 2_592_000;          // 30 days
+const SERVICE_BOND_WITHDRAWAL_COOLDOWN: i64 = 1_209_600; // 14 days, outlasts the stale-dispute timeout
+const SEVERE_REFUND_PERCENTAGE: u8 = 90;            // Refunds this large slash covered bonds whatever their terms
+const DEFAULT_SEVERE_REFUND_SLASH_BPS: u16 = 1_000; // 10% of the bond
+const DEFAULT_CANCEL_GRACE_PERIOD: i64 = 1_800;     // 30 minutes, inside the minimum time lock
+const ESCROW_CLOSE_COOLDOWN: i64 = 604_800;         // 7 days after settlement for audits and claims
+const MAX_APPEAL_WINDOW: i64 = 604_800;             // 7 days
+const MAX_MILESTONES: usize = 8;
+const ARBITRATION_BOND: u64 = 50_000_000;           // 0.05 SOL, posted by the escalating party
+const ARBITRATION_PERIOD: i64 = 604_800;            // 7 days for the arbitrator to rule
+const MAX_ARBITRATOR_DEVIATION: u8 = 25;            // Refund points a ruling may move from the oracle's
+const MAX_SESSION_KEY_DURATION: i64 = 2_592_000;    // 30 days
+
+/// Namespace used by integrators that predate multi-tenancy
+pub const DEFAULT_NAMESPACE: Pubkey = Pubkey::new_from_array([0; 32]);
+
+/// Schema version carried by every event; bumped whenever an event's
+/// fields change so indexers can pick a decoder per version
+pub const EVENT_VERSION: u8 = 4;
+
+/// `SessionKey` scope: open SOL escrows for the agent
+pub const SESSION_SCOPE_ESCROW: u8 = 1 << 0;
+/// `SessionKey` scope: dispute the agent's escrows
+pub const SESSION_SCOPE_DISPUTE: u8 = 1 << 1;
+const SESSION_SCOPES: u8 = SESSION_SCOPE_ESCROW | SESSION_SCOPE_DISPUTE;
 
 #[event]
 pub struct EscrowInitialized {
+    pub version: u8,
     pub escrow: Pubkey,
     pub agent: Pubkey,
     pub api: Pubkey,
     pub amount: u64,
     pub expires_at: i64,
     pub transaction_id: String,
+    pub service_bond: u64,                // API's bond covering the escrow, 0 if uncovered
+    pub payment_reference: Option<PaymentReference>,
+    pub mint: Option<Pubkey>,             // token escrowed by SPL escrows
+    pub metadata_uri: Option<String>,     // off-chain description of the API call
+    pub stale_dispute_terms: Option<StaleDisputeTerms>,
 }
 
 #[event]
 pub struct DisputeMarked {
+    pub version: u8,
     pub escrow: Pubkey,
     pub agent: Pubkey,
     pub transaction_id: String,
     pub timestamp: i64,
+    pub priority_fee: u64,
+    pub reason_code: DisputeReason,
+    pub evidence_hash: [u8; 32],
+}
+
+#[event]
+pub struct ProviderResponseSubmitted {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub api: Pubkey,
+    pub evidence_hash: [u8; 32],
+    pub comment_uri: Option<String>,
 }
 
 #[event]
 pub struct DisputeResolved {
+    pub version: u8,
     pub escrow: Pubkey,
     pub transaction_id: String,
     pub quality_score: u8,
@@ -48,1084 +179,17129 @@ pub struct DisputeResolved {
     pub refund_amount: u64,
     pub payment_amount: u64,
     pub verifier: Pubkey,
+    pub content_hash: Option<[u8; 32]>,
+    pub agent: Pubkey,
+    pub api: Pubkey,
+    pub method: ResolutionMethod,
+    pub dispute_duration: Option<i64>,    // seconds since `mark_disputed`, None if never marked
 }
 
 #[event]
-pub struct FundsReleased {
+pub struct ReputationUpdated {
+    pub version: u8,
+    pub entity: Pubkey,
+    pub old_score: u16,
+    pub new_score: u16,
+    pub reason: ReputationChangeReason,
+    pub current_win_streak: u16,
+    pub longest_win_streak: u16,
+    pub worst_quality_received: u8,
+    pub best_quality_received: u8,
+    pub total_refunds_received_lamports: u64,
+    pub total_payments_made_lamports: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeAutoAssigned {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub verifier: Pubkey,
+    pub queue_index: u16,
+}
+
+#[event]
+pub struct SpamProtectionDepositCharged {
+    pub version: u8,
+    pub agent: Pubkey,
     pub escrow: Pubkey,
-    pub transaction_id: String,
     pub amount: u64,
-    pub api: Pubkey,
-    pub timestamp: i64,
+    pub escrows_in_window: u16,
 }
 
-/// Verify Ed25519 signature instruction
-///
-/// Checks that an Ed25519 signature verification instruction exists in the transaction
-/// and validates the signature against the expected message format
-pub fn verify_ed25519_signature(
-    instructions_sysvar: &AccountInfo,
-    signature: &[u8; 64],
-    verifier_pubkey: &Pubkey,
-    message: &[u8],
-) -> Result<()> {
-        // Load the Ed25519 instruction from the sysvar
-        // Expected to be at index 0 (before the current instruction)
-        let ix = load_instruction_at_checked(0, instructions_sysvar)
-            .map_err(|_| error!(EscrowError::InvalidSignature))?;
+#[event]
+pub struct SpamProtectionDepositRefunded {
+    pub version: u8,
+    pub agent: Pubkey,
+    /// Default key when deposits retained from cancelled escrows are refunded
+    pub escrow: Pubkey,
+    pub amount: u64,
+}
 
-        // Verify it's the Ed25519 program
-        require!(
-            ix.program_id == ed25519_program::ID,
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct SpamProtectionDepositRetained {
+    pub version: u8,
+    pub agent: Pubkey,
+    pub escrow: Pubkey,
+    pub amount: u64,
+    pub retained_until: i64,
+}
 
-        // Ed25519 instruction data layout:
-        // [0]: num_signatures (should be 1)
-        // [1]: padding
-        // [2..4]: signature_offset (u16)
-        // [4..6]: signature_instruction_index (u16)
-        // [6..8]: public_key_offset (u16)
-        // [8..10]: public_key_instruction_index (u16)
-        // [10..12]: message_data_offset (u16)
-        // [12..14]: message_data_size (u16)
-        // [14..16]: message_instruction_index (u16)
-        // [16..]: data (signature + pubkey + message)
+#[event]
+pub struct DeliveryAcknowledged {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub api: Pubkey,
+    pub response_hash: [u8; 32],
+    pub challenge_nonce: Option<[u8; 16]>,
+    pub timestamp: i64,
+    pub expires_at: i64,
+}
 
-        require!(
-            ix.data.len() >= 16,
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct RateLimitOverrideGranted {
+    pub version: u8,
+    pub entity: Pubkey,
+    pub authority: Pubkey,
+    pub until_timestamp: i64,
+}
 
-        // Verify we have exactly 1 signature
-        require!(
-            ix.data[0] == 1,
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct RateLimitOverrideExpired {
+    pub version: u8,
+    pub entity: Pubkey,
+    pub expired_at: i64,
+}
 
-        // Parse offsets
-        let sig_offset = u16::from_le_bytes([ix.data[2], ix.data[3]]) as usize;
-        let pubkey_offset = u16::from_le_bytes([ix.data[6], ix.data[7]]) as usize;
-        let message_offset = u16::from_le_bytes([ix.data[10], ix.data[11]]) as usize;
-        let message_size = u16::from_le_bytes([ix.data[12], ix.data[13]]) as usize;
+#[event]
+pub struct EscrowClosed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub transaction_id: String,
+    pub agent: Pubkey,
+    pub rent_returned: u64,
+    pub timestamp: i64,
+}
 
-        // Verify signature matches
-        let ix_signature = &ix.data[sig_offset..sig_offset + 64];
-        require!(
-            ix_signature == signature,
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct EscrowRentToppedUp {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+}
 
-        // Verify public key matches
-        let ix_pubkey = &ix.data[pubkey_offset..pubkey_offset + 32];
-        require!(
-            ix_pubkey == verifier_pubkey.as_ref(),
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct EscrowToppedUp {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub new_amount: u64,
+}
 
-        // Verify message matches
-        let ix_message = &ix.data[message_offset..message_offset + message_size];
-        require!(
-            ix_message == message,
-            EscrowError::InvalidSignature
-        );
+#[event]
+pub struct ProtocolFeeCollected {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub namespace: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+}
 
-        Ok(())
+#[event]
+pub struct VerifierBondPosted {
+    pub version: u8,
+    pub verifier: Pubkey,
+    pub amount: u64,
+    pub bonded_lamports: u64,
 }
 
-/// x402Resolve Escrow Program
-///
-/// Holds payments in escrow with time-lock and dispute resolution.
-/// Enables automated refunds based on verifier oracle signatures.
-#[program]
-pub mod x402_escrow {
-    use super::*;
+#[event]
+pub struct VerifierDeregisteredUnderbonded {
+    pub version: u8,
+    pub verifier: Pubkey,
+    pub namespace: Pubkey,
+    pub bonded_lamports: u64,
+    pub required_lamports: u64,
+    pub timestamp: i64,
+}
 
-    /// Initialize a new escrow for agent-to-API payment
-    ///
-    /// # Arguments
-    /// * `amount` - Amount to escrow (lamports)
-    /// * `time_lock` - Duration before auto-release (seconds)
-    /// * `transaction_id` - Unique transaction identifier
-    pub fn initialize_escrow(
-        ctx: Context<InitializeEscrow>,
-        amount: u64,
-        time_lock: i64,
-        transaction_id: String,
-    ) -> Result<()> {
-        // Validate inputs
-        require!(
-            amount >= MIN_ESCROW_AMOUNT,
-            EscrowError::InvalidAmount
-        );
-        require!(
-            amount <= MAX_ESCROW_AMOUNT,
-            EscrowError::AmountTooLarge
-        );
-        require!(
-            time_lock >= MIN_TIME_LOCK && time_lock <= MAX_TIME_LOCK,
-            EscrowError::InvalidTimeLock
-        );
-        require!(
-            !transaction_id.is_empty() && transaction_id.len() <= 64,
-            EscrowError::InvalidTransactionId
-        );
+#[event]
+pub struct ReputationEntityTypeCorrected {
+    pub version: u8,
+    pub entity: Pubkey,
+    pub previous: EntityType,
+    pub entity_type: EntityType,
+}
 
-        let clock = Clock::get()?;
+#[event]
+pub struct ReputationUpdateSkipped {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub account: Pubkey,
+    pub entity: Pubkey,
+    pub error_code: u32,
+}
 
-        // Initialize escrow state
-        {
-            let escrow = &mut ctx.accounts.escrow;
-            escrow.agent = ctx.accounts.agent.key();
-            escrow.api = ctx.accounts.api.key();
-            escrow.amount = amount;
-            escrow.status = EscrowStatus::Active;
-            escrow.created_at = clock.unix_timestamp;
-            escrow.expires_at = clock.unix_timestamp + time_lock;
-            escrow.transaction_id = transaction_id.clone();
-            escrow.bump = ctx.bumps.escrow;
-        }
+#[event]
+pub struct DisputeWindowExtended {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub new_window: i64,
+    pub approver: Pubkey,
+}
 
-        // Verify transfer amount covers rent before executing
-        let rent = Rent::get()?;
-        let min_rent = rent.minimum_balance(8 + Escrow::INIT_SPACE);
-        require!(
-            amount >= min_rent,
-            EscrowError::InsufficientRentReserve
-        );
+#[event]
+pub struct TimeLockExtended {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub new_expires_at: i64,
+    pub api_consented: bool,
+}
 
-        // Transfer SOL to escrow PDA
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.agent.to_account_info(),
-                to: ctx.accounts.escrow.to_account_info(),
-            },
-        );
-        anchor_lang::system_program::transfer(cpi_context, amount)?;
+#[event]
+pub struct UsdEscrowPriced {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub usd_target_cents: u64,
+    pub cents_per_sol: u64,
+    pub amount: u64,
+}
 
-        let expires_at = clock.unix_timestamp + time_lock;
-        msg!("Escrow initialized: {} SOL locked", amount as f64 / 1_000_000_000.0);
-        msg!("Expires at: {}", expires_at);
+#[event]
+pub struct ResolutionSubmitted {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub verifier: Pubkey,
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    pub appeal_deadline: i64,
+}
 
-        let escrow = &ctx.accounts.escrow;
-        emit!(EscrowInitialized {
-            escrow: escrow.key(),
-            agent: escrow.agent,
-            api: escrow.api,
-            amount: escrow.amount,
-            expires_at: escrow.expires_at,
-            transaction_id: transaction_id,
-        });
+#[event]
+pub struct ResolutionAppealed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub appellant: Pubkey,
+    pub first_verifier: Pubkey,
+}
 
-        Ok(())
-    }
+#[event]
+pub struct EscalatedToArbitrator {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub escalator: Pubkey,
+    pub arbitrator: Pubkey,
+    pub bond: u64,
+    pub ruling_deadline: i64,
+}
 
-    /// Release funds to API (happy path - no dispute)
-    ///
-    /// Can be called by:
-    /// - Agent (explicitly releasing)
-    /// - Anyone after time_lock expires (auto-release)
-    pub fn release_funds(ctx: Context<ReleaseFunds>) -> Result<()> {
-        let escrow = &mut ctx.accounts.escrow;
-        let clock = Clock::get()?;
+#[event]
+pub struct ArbitratorRuled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub arbitrator: Pubkey,
+    pub oracle_refund_percentage: u8,
+    pub refund_percentage: u8,
+}
 
-        require!(
-            escrow.status == EscrowStatus::Active,
-            EscrowError::InvalidStatus
-        );
+#[event]
+pub struct ArbitrationBondSettled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub returned: bool,
+}
 
-        // Check if caller is agent OR time_lock expired
-        let is_agent = ctx.accounts.agent.key() == escrow.agent;
-        let time_lock_expired = clock.unix_timestamp >= escrow.expires_at;
+#[event]
+pub struct VerifierPaused {
+    pub version: u8,
+    pub verifier: Pubkey,
+    pub namespace: Pubkey,
+    pub reason_hash: [u8; 32],
+    pub pause_until: i64,
+    pub released_assignments: u8,
+}
 
-        // If not agent, time lock must have expired
-        if !is_agent {
-            require!(time_lock_expired, EscrowError::TimeLockNotExpired);
-        }
+#[event]
+pub struct VerifierResumed {
+    pub version: u8,
+    pub verifier: Pubkey,
+    pub namespace: Pubkey,
+    pub timestamp: i64,
+}
 
-        require!(is_agent || time_lock_expired, EscrowError::Unauthorized);
+#[event]
+pub struct EscrowMigrated {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub layout_version: u8,
+}
 
-        // Copy values before PDA signing
-        let transfer_amount = escrow.amount;
-        let transaction_id = escrow.transaction_id.clone();
-        let bump = escrow.bump;
-
-        // Transfer full amount to API
-        let seeds = &[
-            b"escrow",
-            transaction_id.as_bytes(),
-            &[bump],
-        ];
-        let signer = &[&seeds[..]];
+#[event]
+pub struct ReputationMigrated {
+    pub version: u8,
+    pub reputation: Pubkey,
+    pub entity: Pubkey,
+    pub quality_sum: u64,
+    pub quality_count: u64,
+}
 
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.escrow.to_account_info(),
-                to: ctx.accounts.api.to_account_info(),
-            },
-            signer,
-        );
-        anchor_lang::system_program::transfer(cpi_context, transfer_amount)?;
+#[event]
+pub struct OracleFeePaid {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub verifier: Pubkey,
+    pub amount: u64,
+}
 
-        let escrow = &mut ctx.accounts.escrow;
-        escrow.status = EscrowStatus::Released;
+#[event]
+pub struct RelayerReimbursed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub relayer: Pubkey,
+    pub amount: u64,
+}
 
-        msg!("Funds released to API: {} SOL", escrow.amount as f64 / 1_000_000_000.0);
+#[event]
+pub struct PriorityFeeDisbursed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
 
-        let clock = Clock::get()?;
-        emit!(FundsReleased {
-            escrow: escrow.key(),
-            transaction_id: escrow.transaction_id.clone(),
-            amount: escrow.amount,
-            api: escrow.api,
-            timestamp: clock.unix_timestamp,
-        });
+#[event]
+pub struct EscrowSettledMutually {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub transaction_id: String,
+    pub refund_percentage: u8,
+    pub refund_amount: u64,
+    pub payment_amount: u64,
+    pub timestamp: i64,
+}
 
-        Ok(())
-    }
+#[event]
+pub struct StaleDisputeSettled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub transaction_id: String,
+    pub refund_percentage: u8,
+    pub refund_amount: u64,
+    pub payment_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceFundContributed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub fund_balance: u64,
+    pub from_release: bool,               // cut of the API's payment rather than the agent's deposit
+}
+
+#[event]
+pub struct InsuranceClaimFiled {
+    pub version: u8,
+    pub claim: Pubkey,
+    pub escrow: Pubkey,
+    pub claimant: Pubkey,
+    pub claim_evidence_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceClaimPaid {
+    pub version: u8,
+    pub claim: Pubkey,
+    pub escrow: Pubkey,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub fund_balance: u64,
+}
+
+#[event]
+pub struct ServiceBondPosted {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub bonded_lamports: u64,
+    pub terms_hash: [u8; 32],
+}
+
+#[event]
+pub struct ServiceBondSlashed {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub escrow: Pubkey,
+    pub agent: Pubkey,
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    pub amount: u64,
+    pub bonded_lamports: u64,
+}
+
+#[event]
+pub struct ServiceBondWithdrawalRequested {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub available_at: i64,
+}
+
+#[event]
+pub struct ServiceBondWithdrawn {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub bonded_lamports: u64,
+}
+
+#[event]
+pub struct AuditPassed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub accounting: EscrowAccounting,
+}
+
+#[event]
+pub struct AuditFailed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub expected: EscrowAccounting,
+    pub recorded: EscrowAccounting,
+}
+
+#[event]
+pub struct WorkAgreementCreated {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub work_agreement: Pubkey,
+    pub min_records: u32,
+    pub max_age_days: u32,
+    pub min_quality_score: u8,
+}
+
+#[event]
+pub struct EscrowCancelled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub transaction_id: String,
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub api_consented: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerifierSetConfigured {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub verifiers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct QuorumResolved {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub verifier_set: Pubkey,
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct ProviderStrikeRecorded {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub escrow: Pubkey,
+    pub quality_score: u8,
+    pub strike_count: u8,
+}
+
+#[event]
+pub struct ProviderSuspensionStarted {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub suspension_end: i64,
+}
+
+#[event]
+pub struct ProviderSuspensionLifted {
+    pub version: u8,
+    pub provider: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerificationLevelChanged {
+    pub version: u8,
+    pub entity: Pubkey,
+    pub previous: VerificationLevel,
+    pub verification_level: VerificationLevel,
+    pub staked_lamports: u64,
+}
+
+#[event]
+pub struct MilestonePlanCreated {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub milestone_plan: Pubkey,
+    pub milestones: u8,
+}
+
+#[event]
+pub struct MilestoneReleased {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub index: u8,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MilestoneDisputed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub index: u8,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MilestoneResolved {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub index: u8,
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    pub refund_amount: u64,
+    pub payment_amount: u64,
+    pub verifier: Pubkey,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub agent: Pubkey,
+    pub api: Pubkey,
+    pub price_per_period: u64,
+    pub period_seconds: i64,
+    pub funded_periods: u32,
+}
+
+#[event]
+pub struct SubscriptionToppedUp {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub periods: u32,
+    pub funded_periods: u32,
+}
+
+#[event]
+pub struct SubscriptionPeriodClaimed {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub period: u32,
+    pub amount: u64,
+}
+
+#[event]
+pub struct SubscriptionPeriodDisputed {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub period: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionPeriodResolved {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub period: u32,
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    pub refund_amount: u64,
+    pub payment_amount: u64,
+    pub verifier: Pubkey,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub version: u8,
+    pub subscription: Pubkey,
+    pub refunded_periods: u32,
+    pub refund: u64,
+}
+
+#[event]
+pub struct PaymentChannelOpened {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub agent: Pubkey,
+    pub api: Pubkey,
+    pub deposit: u64,
+}
+
+#[event]
+pub struct PaymentChannelFunded {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct ChannelDebitRecorded {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub entry_id: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ChannelCreditRecorded {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub amount: u64,
+    pub carried_credit: u64,
+}
+
+#[event]
+pub struct ChannelEntryDisputed {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub entry_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ChannelEntryResolved {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub entry_id: u64,
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    pub refund_amount: u64,
+    pub payment_amount: u64,
+    pub verifier: Pubkey,
+}
+
+#[event]
+pub struct PaymentChannelSettled {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub entries: u16,
+    pub debits: u64,
+    pub credit_applied: u64,
+    pub payment: u64,
+}
+
+#[event]
+pub struct PaymentChannelClosed {
+    pub version: u8,
+    pub channel: Pubkey,
+    pub refund: u64,
+}
+
+#[event]
+pub struct DisputeBondSettled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub returned: bool,
+}
+
+#[event]
+pub struct TreasuryInitialized {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub treasury: Pubkey,
+}
+
+#[event]
+pub struct FeesWithdrawn {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub treasury: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PauseChanged {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub paused: bool,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct VerifierKeyRegistered {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub verifier: Pubkey,
+    pub replaced: Option<Pubkey>,
+}
+
+#[event]
+pub struct VerifierKeyRemoved {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub verifier: Pubkey,
+}
+
+#[event]
+pub struct ArbitratorRegistered {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub arbitrator: Pubkey,
+}
+
+#[event]
+pub struct ArbitratorRemoved {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub arbitrator: Pubkey,
+    pub cases_ruled: u32,
+}
+
+#[event]
+pub struct LockedValueDrift {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub entity: Pubkey,
+    pub recorded: u64,
+    pub released: u64,
+}
+
+#[event]
+pub struct FundsReleased {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub transaction_id: String,
+    pub amount: u64,
+    pub api: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DeliveryClockEnabled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub time_lock: i64,
+}
+
+#[event]
+pub struct DelegateSet {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub delegate: Option<Pubkey>,
+}
+
+#[event]
+pub struct ReleaseTipSet {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub release_tip_lamports: u64,
+}
+
+#[event]
+pub struct ProviderTermsPublished {
+    pub version: u8,
+    pub api: Pubkey,
+    pub provider_terms: Pubkey,
+    pub terms_id: u8,
+    pub namespace: Pubkey,
+    pub min_amount: u64,
+    pub max_amount: u64,
+    pub time_lock: i64,
+    pub dispute_window: i64,
+}
+
+#[event]
+pub struct ProviderTermsRetired {
+    pub version: u8,
+    pub api: Pubkey,
+    pub provider_terms: Pubkey,
+}
+
+#[event]
+pub struct EscrowOpenedFromTerms {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub provider_terms: Pubkey,
+}
+
+#[event]
+pub struct ReceiptMinted {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub agent: Pubkey,
+    pub merkle_tree: Pubkey,
+    pub uri: String,
+}
+
+#[event]
+pub struct SettlementPublished {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub message: Pubkey,
+    /// Sequence of the message under the program's Wormhole emitter
+    pub sequence: u64,
+}
+
+#[event]
+pub struct SessionKeyCreated {
+    pub version: u8,
+    pub agent: Pubkey,
+    pub session_key: Pubkey,
+    pub scope: u8,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct SessionKeyRevoked {
+    pub version: u8,
+    pub agent: Pubkey,
+    pub session_key: Pubkey,
+}
+
+#[event]
+pub struct EscrowIndexed {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub agent: Pubkey,
+    pub agent_position: u64,
+    pub api: Pubkey,
+    pub api_position: u64,
+}
+
+#[event]
+pub struct StreamingEnabled {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub amount: u64,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct StreamWithdrawn {
+    pub version: u8,
+    pub escrow: Pubkey,
+    pub amount: u64,
+    pub streamed_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ExpiredEscrowsCranked {
+    pub version: u8,
+    pub namespace: Pubkey,
+    pub cranker: Pubkey,
+    pub released: u8,
+    pub tips: u64,
+}
+
+/// Verify Ed25519 signature instruction
+///
+/// Scans the transaction for an Ed25519 signature verification instruction
+/// whose `signature_index`-th entry matches the expected signature, the
+/// signer owning `signer_nonces` and message. In strict mode only the
+/// instruction immediately before the current one is checked, so copies of
+/// the signature elsewhere in the transaction neither help nor hurt. The
+/// message embeds `nonce`, which is consumed so the signature cannot be
+/// submitted again.
+pub fn verify_ed25519_signature(
+    instructions_sysvar: &AccountInfo,
+    signature: &[u8; 64],
+    signer_nonces: &mut UsedNonces,
+    nonce: u64,
+    message: &[u8],
+    signature_index: u8,
+    strict_adjacency: bool,
+) -> Result<()> {
+        let verifier_pubkey = &signer_nonces.signer;
+        let matches = |ix: &Instruction| {
+            ix.program_id == ed25519_program::ID
+                && ed25519_instruction_matches(
+                    &ix.data,
+                    signature_index,
+                    signature,
+                    verifier_pubkey,
+                    message,
+                )
+        };
+
+        if strict_adjacency {
+            let current_index = load_current_index_checked(instructions_sysvar)
+                .map_err(|_| error!(EscrowError::InvalidSignature))?;
+            let previous = current_index
+                .checked_sub(1)
+                .and_then(|index| load_instruction_at_checked(index as usize, instructions_sysvar).ok())
+                .ok_or(EscrowError::SignatureInstructionMisplaced)?;
+            require!(
+                previous.program_id == ed25519_program::ID,
+                EscrowError::SignatureInstructionMisplaced
+            );
+            require!(matches(&previous), EscrowError::InvalidSignature);
+        } else {
+            // Any Ed25519 instruction covering this signature will do
+            let mut index = 0;
+            loop {
+                let ix = load_instruction_at_checked(index, instructions_sysvar)
+                    .map_err(|_| error!(EscrowError::InvalidSignature))?;
+                if matches(&ix) {
+                    break;
+                }
+                index += 1;
+            }
+        }
+
+        signer_nonces.consume(nonce)
+}
+
+/// Check one entry of Ed25519 precompile instruction data against the expected values
+///
+/// Ed25519 instruction data layout:
+/// [0]: num_signatures
+/// [1]: padding
+/// [2 + 14 * i..]: offsets entry for signature i:
+///   [+0..2]: signature_offset (u16)
+///   [+2..4]: signature_instruction_index (u16)
+///   [+4..6]: public_key_offset (u16)
+///   [+6..8]: public_key_instruction_index (u16)
+///   [+8..10]: message_data_offset (u16)
+///   [+10..12]: message_data_size (u16)
+///   [+12..14]: message_instruction_index (u16)
+/// [2 + 14 * num_signatures..]: data (signatures + pubkeys + messages)
+fn ed25519_instruction_matches(
+    data: &[u8],
+    signature_index: u8,
+    signature: &[u8; 64],
+    verifier_pubkey: &Pubkey,
+    message: &[u8],
+) -> bool {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_SIZE: usize = 14;
+
+    let num_signatures = match data.first() {
+        Some(&n) => n,
+        None => return false,
+    };
+    if signature_index >= num_signatures {
+        return false;
+    }
+
+    let entry = OFFSETS_START + signature_index as usize * OFFSETS_SIZE;
+    let read_u16 = |at: usize| {
+        data.get(entry + at..entry + at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let (
+        Some(sig_offset),
+        Some(sig_ix),
+        Some(pubkey_offset),
+        Some(pubkey_ix),
+        Some(message_offset),
+        Some(message_size),
+        Some(message_ix),
+    ) = (
+        read_u16(0),
+        read_u16(2),
+        read_u16(4),
+        read_u16(6),
+        read_u16(8),
+        read_u16(10),
+        read_u16(12),
+    ) else {
+        return false;
+    };
+
+    // All offsets must point into this instruction's own data, otherwise the
+    // precompile verified bytes other than the ones we compare below
+    if sig_ix != u16::MAX || pubkey_ix != u16::MAX || message_ix != u16::MAX {
+        return false;
+    }
+
+    let sig_offset = sig_offset as usize;
+    let pubkey_offset = pubkey_offset as usize;
+    let message_offset = message_offset as usize;
+    let message_size = message_size as usize;
+
+    data.get(sig_offset..sig_offset + 64) == Some(&signature[..])
+        && data.get(pubkey_offset..pubkey_offset + 32) == Some(verifier_pubkey.as_ref())
+        && data.get(message_offset..message_offset + message_size) == Some(message)
+}
+
+/// x402Resolve Escrow Program
+///
+/// Holds payments in escrow with time-lock and dispute resolution.
+/// Enables automated refunds based on verifier oracle signatures.
+#[program]
+pub mod x402_escrow {
+    use super::*;
+
+    /// Initialize a new escrow for agent-to-API payment
+    ///
+    /// A sponsor such as a relayer may sign as `payer` to cover the account
+    /// rent, so the agent needs no SOL beyond what it escrows. The rent goes
+    /// back to the agent when the escrow closes.
+    ///
+    /// # Arguments
+    /// * `amount` - Amount to escrow (lamports)
+    /// * `time_lock` - Duration before auto-release (seconds)
+    /// * `transaction_id` - Unique transaction identifier
+    /// * `no_dispute` - Opt out of arbitration; release-only escrow
+    /// * `namespace` - Platform whose config governs the escrow (`DEFAULT_NAMESPACE` for none)
+    /// * `payment_reference` - Optional HTTP 402 exchange the escrow pays for
+    /// * `request_hash` - Optional SHA-256 of the request payload, bound into resolutions
+    /// * `refund_recipient` - Optional wallet dispute refunds go to instead of the agent
+    /// * `metadata_uri` - Optional link, up to 200 characters, to JSON describing the
+    ///   API call (endpoint, SLA, pricing) for indexers and verifiers
+    /// * `stale_dispute_terms` - Optional split `settle_stale_dispute` applies once a
+    ///   dispute has gone unresolved for the given verifier timeout
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow(
+        ctx: Context<InitializeEscrow>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )
+    }
+
+    /// Initialize an escrow that may only pay an API on the supplied allowlist
+    ///
+    /// Takes the same arguments as `initialize_escrow`. Agents bound to an
+    /// organization with `restrict_agent` must pass its allowlist to every
+    /// escrow they open; this entry point requires one from any agent.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow_restricted(
+        ctx: Context<InitializeEscrow>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        require!(ctx.accounts.allowlist.is_some(), EscrowError::AllowlistRequired);
+
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )
+    }
+
+    /// Initialize an escrow for an agent from one of its session keys
+    ///
+    /// Takes the same arguments as `initialize_escrow`. The session key signs
+    /// and funds the escrow; everything else treats it as the agent's, from
+    /// its limits and blocklist to where refunds go. The session must be live
+    /// and scoped with `SESSION_SCOPE_ESCROW`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow_with_session(
+        ctx: Context<InitializeEscrowWithSession>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.session.authorize(SESSION_SCOPE_ESCROW, now)?;
+
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )
+    }
+
+    /// Initialize an escrow on behalf of another program (CPI entry point)
+    ///
+    /// Takes the same arguments as `initialize_escrow`. The agent only has to
+    /// sign, so a PDA can own escrows by signing with its seeds in
+    /// `x402_escrow::cpi::initialize_escrow_cpi`; a separate `payer` covers
+    /// the rent, the deposits and the escrowed lamports. Everything after
+    /// creation treats the escrow as the agent's: refunds, the spam deposit
+    /// and rent on close go to the agent, not the payer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow_cpi(
+        ctx: Context<InitializeEscrowCpi>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )
+    }
+
+    /// Initialize an escrow on standard terms the API has published
+    ///
+    /// Takes the same arguments as `initialize_escrow`. The amount must fall
+    /// within the terms' range and the time lock and namespace must be the
+    /// terms'. The escrow then takes the terms' dispute window, and its
+    /// disputes resolve on their refund curve instead of the namespace's.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow_from_terms(
+        ctx: Context<InitializeEscrow>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        let terms = ctx
+            .accounts
+            .provider_terms
+            .as_ref()
+            .ok_or(EscrowError::ProviderTermsRequired)?;
+        require!(
+            terms.admits(amount, time_lock, &namespace),
+            EscrowError::ProviderTermsMismatch
+        );
+        let (provider_terms, dispute_window, refund_curve) =
+            (terms.key(), terms.dispute_window, terms.refund_curve);
+
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.dispute_window = dispute_window;
+        escrow.refund_curve = Some(refund_curve);
+
+        emit!(EscrowOpenedFromTerms {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            provider_terms,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize a SOL escrow priced in US dollars
+    ///
+    /// The lamport amount is the USD target at the Pyth SOL/USD price named by
+    /// the namespace config. Every settlement takes a fresh price update, pays
+    /// the API its share of the target at that price, capped at its lamport
+    /// share, and refunds the surplus to the agent.
+    ///
+    /// # Arguments
+    /// * `usd_target_cents` - Value to escrow, in US cents
+    /// * `time_lock` - Duration before auto-release (seconds)
+    /// * `transaction_id` - Unique transaction identifier
+    /// * `no_dispute` - Opt out of arbitration; release-only escrow
+    /// * `namespace` - Platform whose config governs the escrow (`DEFAULT_NAMESPACE` for none)
+    /// * `payment_reference` - Optional HTTP 402 exchange the escrow pays for
+    /// * `max_amount` - Most lamports the agent accepts to lock at the current price
+    /// * `request_hash` - Optional SHA-256 of the request payload, bound into resolutions
+    /// * `refund_recipient` - Optional wallet dispute refunds go to instead of the agent
+    /// * `metadata_uri` - Optional link, up to 200 characters, to JSON describing the
+    ///   API call (endpoint, SLA, pricing) for indexers and verifiers
+    /// * `stale_dispute_terms` - Optional split `settle_stale_dispute` applies once a
+    ///   dispute has gone unresolved for the given verifier timeout
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_escrow_usd(
+        ctx: Context<InitializeEscrow>,
+        usd_target_cents: u64,
+        time_lock: i64,
+        transaction_id: String,
+        no_dispute: bool,
+        namespace: Pubkey,
+        payment_reference: Option<PaymentReference>,
+        max_amount: u64,
+        request_hash: Option<[u8; 32]>,
+        refund_recipient: Option<Pubkey>,
+        metadata_uri: Option<String>,
+        stale_dispute_terms: Option<StaleDisputeTerms>,
+    ) -> Result<()> {
+        let price_update = ctx
+            .accounts
+            .pyth_price_update
+            .as_deref()
+            .ok_or(EscrowError::InvalidPriceFeed)?;
+        let cents_per_sol =
+            read_pyth_sol_usd_cents(price_update, &ctx.accounts.config.pyth_sol_usd_feed_id)?;
+        let amount = usd_cents_to_lamports(usd_target_cents, cents_per_sol)?;
+        require!(amount <= max_amount, EscrowError::PriceSlippageExceeded);
+
+        open_sol_escrow(
+            ctx.accounts.open_accounts(&ctx.bumps),
+            amount,
+            time_lock,
+            transaction_id,
+            no_dispute,
+            namespace,
+            payment_reference,
+            request_hash,
+            refund_recipient,
+            metadata_uri,
+            stale_dispute_terms,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.usd_target_cents = Some(usd_target_cents);
+
+        msg!("Priced at {} cents per SOL", cents_per_sol);
+
+        emit!(UsdEscrowPriced {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            usd_target_cents,
+            cents_per_sol,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Release funds to API (happy path - no dispute)
+    ///
+    /// Can be called by:
+    /// - Agent or its delegate (explicitly releasing)
+    /// - Anyone after time_lock expires (auto-release), who earns the
+    ///   escrow's `release_tip_lamports` out of the API's payment
+    pub fn release_funds(ctx: Context<ReleaseFunds>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+
+        // Check if caller is agent OR time_lock expired
+        let is_agent = escrow.acts_for_agent(&ctx.accounts.agent.key());
+
+        // If not agent, time lock must have expired and the dispute window closed
+        if !is_agent {
+            require_auto_releasable(escrow, clock.unix_timestamp)?;
+        }
+
+        // Transfer full amount to API, less the namespace protocol fee and
+        // insurance cut; a USD escrow pays the target value and refunds any surplus
+        let tip_offered = escrow.release_tip_for(&ctx.accounts.agent.key());
+        // The surplus belongs to the agent, not to a delegate or cranker
+        let caller = ctx.accounts.agent.to_account_info();
+        let surplus_to = match &ctx.accounts.agent_wallet {
+            Some(wallet) => wallet.to_account_info(),
+            None if caller.key() == escrow.agent || escrow.usd_target_cents.is_none() => caller.clone(),
+            None => return err!(EscrowError::AgentWalletMissing),
+        };
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        let (transfer_amount, _) = pay_release(
+            &mut ctx.accounts.escrow,
+            &surplus_to,
+            &ctx.accounts.api,
+            &caller,
+            tip_offered,
+            ctx.accounts.pyth_price_update.as_deref(),
+            ctx.accounts.insurance_fund.as_mut(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+
+        let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+        let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+        release_exposure_accounts(escrow, agent_info.as_ref(), api_info.as_ref())?;
+
+        msg!("Funds released to API: {} SOL", transfer_amount as f64 / 1_000_000_000.0);
+
+        let clock = Clock::get()?;
+        emit!(FundsReleased {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            amount: transfer_amount,
+            api: escrow.api,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Release a long-expired escrow to the API and close it in one instruction
+    ///
+    /// Permissionless crank equivalent of `release_funds` followed by
+    /// `close_escrow`: the API is paid as `release_funds` pays it, the cranker
+    /// earns the escrow's `release_tip_lamports`, and the agent receives the
+    /// rent (and any spam protection deposit) back. Passing the receipt
+    /// accounts mints the same receipt `mint_escrow_receipt` would between
+    /// the two steps, paid by the cranker.
+    ///
+    /// Unlike `release_funds`, this does not open at expiry. Closing skips the
+    /// cooldown `close_escrow` waits out, so the crank only accepts escrows
+    /// once `Escrow::sweepable_at` has passed: `ESCROW_CLOSE_COOLDOWN` after
+    /// the later of expiry and the dispute deadline. Fresher escrows take the
+    /// two steps, leaving time for audits and claims.
+    pub fn release_and_close(ctx: Context<ReleaseAndClose>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+        require!(
+            clock.unix_timestamp >= escrow.sweepable_at(),
+            EscrowError::CloseCooldownActive
+        );
+        require_auto_releasable(escrow, clock.unix_timestamp)?;
+
+        let tip_offered = escrow.release_tip_lamports;
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        let (transfer_amount, _) = pay_release(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            &ctx.accounts.cranker.to_account_info(),
+            tip_offered,
+            ctx.accounts.pyth_price_update.as_deref(),
+            ctx.accounts.insurance_fund.as_mut(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+
+        let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+        let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+        release_exposure_accounts(escrow, agent_info.as_ref(), api_info.as_ref())?;
+
+        // Released is genuine usage, so the spam deposit goes back now
+        if escrow.spam_deposit > 0 {
+            let vault = ctx
+                .accounts
+                .spam_vault
+                .as_mut()
+                .ok_or(EscrowError::SpamVaultMissing)?;
+            return_spam_deposit(escrow, vault, &ctx.accounts.agent.to_account_info())?;
+        }
+
+        msg!("Funds released to API: {} SOL", transfer_amount as f64 / 1_000_000_000.0);
+
+        emit!(FundsReleased {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            amount: transfer_amount,
+            api: escrow.api,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // Opted-in receipts are minted from the released state, before the
+        // account is gone
+        if let Some(merkle_tree) = ctx.accounts.merkle_tree.as_ref() {
+            let missing = || error!(EscrowError::ReceiptAccountsMissing);
+            let tree_config = ctx.accounts.tree_config.as_ref().ok_or_else(missing)?;
+            let receipt_authority = ctx.accounts.receipt_authority.as_ref().ok_or_else(missing)?;
+            let log_wrapper = ctx.accounts.log_wrapper.as_ref().ok_or_else(missing)?;
+            let compression_program = ctx.accounts.compression_program.as_ref().ok_or_else(missing)?;
+            let bubblegum_program = ctx.accounts.bubblegum_program.as_ref().ok_or_else(missing)?;
+            let system_program = ctx.accounts.system_program.as_ref().ok_or_else(missing)?;
+
+            let accounts = ReceiptAccounts {
+                tree_config,
+                leaf_owner: &ctx.accounts.agent,
+                merkle_tree,
+                payer: &ctx.accounts.cranker,
+                receipt_authority,
+                log_wrapper,
+                compression_program,
+                system_program,
+                bubblegum_program,
+            };
+            let bump = [ctx.bumps.receipt_authority.ok_or_else(missing)?];
+            mint_receipt(&accounts, &ctx.accounts.escrow, &[b"receipt_authority", &bump])?;
+            ctx.accounts.escrow.set_receipt_minted(true);
+
+            msg!("Receipt minted for {}", ctx.accounts.escrow.transaction_id);
+
+            emit!(ReceiptMinted {
+                version: EVENT_VERSION,
+                escrow: ctx.accounts.escrow.key(),
+                agent: ctx.accounts.escrow.agent,
+                merkle_tree: merkle_tree.key(),
+                uri: receipts::receipt_uri(&ctx.accounts.escrow),
+            });
+        }
+
+        let escrow = &ctx.accounts.escrow;
+        emit!(EscrowClosed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            agent: escrow.agent,
+            rent_returned: ctx.accounts.escrow.to_account_info().lamports(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Start the escrow's time lock at delivery instead of creation (agent only)
+    ///
+    /// Until the API acknowledges delivery the escrow cannot be released
+    /// without the agent; `acknowledge_delivery` then restarts the full time
+    /// lock from that moment. Must be set before delivery.
+    pub fn enable_delivery_clock(ctx: Context<EnableDeliveryClock>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            escrow.delivered_at.is_none(),
+            EscrowError::DeliveryAlreadyAcknowledged
+        );
+        require!(!escrow.streaming(), EscrowError::InvalidStreamingEscrow);
+
+        escrow.set_delivery_clock(true);
+
+        msg!("Time lock starts at delivery");
+
+        emit!(DeliveryClockEnabled {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            time_lock: escrow.expires_at - escrow.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Let a second key release or dispute the escrow for the agent (agent only)
+    ///
+    /// Meant for a hot key held by an automated agent while the funding key
+    /// stays cold. Refunds still go to the agent. `None` revokes the delegate.
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+
+        escrow.delegate = delegate;
+
+        match delegate {
+            Some(delegate) => msg!("Delegate set to {}", delegate),
+            None => msg!("Delegate revoked"),
+        }
+
+        emit!(DelegateSet {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            delegate,
+        });
+
+        Ok(())
+    }
+
+    /// Stream the escrow to the API over its time lock (agent only)
+    ///
+    /// The amount vests linearly from creation to expiry and the API
+    /// withdraws the vested share with `withdraw_streamed`. A dispute freezes
+    /// whatever has not been withdrawn, and resolutions split only that.
+    /// Disputes stay open until expiry.
+    pub fn enable_streaming(ctx: Context<EnableStreaming>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(!escrow.streaming(), EscrowError::InvalidStreamingEscrow);
+        require!(
+            !escrow.has_milestones()
+                && !escrow.delivery_clock()
+                && escrow.usd_target_cents.is_none()
+                && escrow.recorded_accounting() == EscrowAccounting::default(),
+            EscrowError::InvalidStreamingEscrow
+        );
+
+        escrow.set_streaming(true);
+
+        msg!("Escrow streams until {}", escrow.expires_at);
+
+        emit!(StreamingEnabled {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            amount: escrow.amount,
+            ends_at: escrow.expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw the vested, not yet withdrawn share of a streaming escrow
+    /// (API only)
+    ///
+    /// The withdrawal that empties the stream releases the escrow.
+    pub fn withdraw_streamed(ctx: Context<WithdrawStreamed>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(escrow.streaming(), EscrowError::StreamingNotEnabled);
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+
+        let withdrawable = escrow
+            .vested_amount(clock.unix_timestamp)?
+            .saturating_sub(escrow.streamed_amount);
+        require!(withdrawable > 0, EscrowError::NothingStreamed);
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_api_with_fee(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.api.to_account_info(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            withdrawable,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.streamed_amount += withdrawable;
+
+        if escrow.open_amount() == 0 {
+            escrow.set_status(EscrowStatus::Released);
+            escrow.settled_at = Some(clock.unix_timestamp);
+
+            let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+            let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+            release_exposure_accounts(escrow, agent_info.as_ref(), api_info.as_ref())?;
+        }
+
+        msg!("Streamed {} of {} lamports", escrow.streamed_amount, escrow.amount);
+
+        emit!(StreamWithdrawn {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            amount: withdrawable,
+            streamed_amount: escrow.streamed_amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Offer a tip to whoever releases the escrow once it expires (API only)
+    ///
+    /// Paid by `release_funds` and `crank_release_expired` out of the API's
+    /// payment, within the fee headroom `crank_tip` leaves. Zero withdraws
+    /// the offer.
+    pub fn set_release_tip(ctx: Context<SetReleaseTip>, release_tip_lamports: u64) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+
+        escrow.release_tip_lamports = release_tip_lamports;
+
+        msg!("Release tip set to {} lamports", release_tip_lamports);
+
+        emit!(ReleaseTipSet {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            release_tip_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Release several expired escrows of one namespace (permissionless)
+    ///
+    /// Each escrow must be releasable by `release_funds` without the agent:
+    /// time lock expired and dispute window closed. The cranker earns up to
+    /// `CRANK_TIP_LAMPORTS`, or the escrow's larger `release_tip_lamports`,
+    /// per escrow out of the API's payment. Remaining
+    /// accounts hold, per escrow and in order:
+    /// `[escrow, api, agent_reputation, api_reputation]` (all writable).
+    pub fn crank_release_expired<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankReleaseExpired<'info>>,
+    ) -> Result<()> {
+        let remaining: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+        require!(
+            remaining.len() >= CRANK_ACCOUNTS_PER_ESCROW,
+            EscrowError::InvalidBatchSize
+        );
+
+        let clock = Clock::get()?;
+        let config = &ctx.accounts.config;
+        let cranker = ctx.accounts.cranker.to_account_info();
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        let mut count = 0;
+        let mut tips: u64 = 0;
+
+        let mut parser = RemainingAccountsParser::new(remaining);
+        while parser.remaining() > 0 {
+            count += 1;
+            require!(count <= MAX_BATCH_RESOLUTIONS, EscrowError::InvalidBatchSize);
+
+            let escrow_info = parser.next(&AccountExpectation::any().owned_by(crate::ID).writable())?;
+            let mut escrow: Account<Escrow> = Account::try_from(escrow_info)?;
+            let api = parser.next(&AccountExpectation::any().key(escrow.api).writable())?;
+            let agent_info = parser.next(
+                &AccountExpectation::any()
+                    .key(reputation_address(&escrow.agent))
+                    .writable(),
+            )?;
+            let api_info = parser.next(
+                &AccountExpectation::any()
+                    .key(reputation_address(&escrow.api))
+                    .writable(),
+            )?;
+            // Only a USD escrow can owe its agent a surplus
+            let agent = match escrow.usd_target_cents {
+                Some(_) => parser.next(&AccountExpectation::any().key(escrow.agent).writable())?,
+                None => &cranker,
+            };
+            require_keys_eq!(escrow.namespace, config.namespace, EscrowError::NamespaceMismatch);
+
+            require!(escrow.mint.is_none(), EscrowError::TokenEscrowRequiresSplPath);
+            require!(
+                escrow.status() == EscrowStatus::Active,
+                EscrowError::InvalidStatus
+            );
+            require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+            require_auto_releasable(&escrow, clock.unix_timestamp)?;
+
+            let tip_offered = escrow.release_tip_lamports.max(CRANK_TIP_LAMPORTS);
+            let (transfer_amount, tip) = pay_release(
+                &mut escrow,
+                agent,
+                api,
+                &cranker,
+                tip_offered,
+                ctx.accounts.pyth_price_update.as_deref(),
+                ctx.accounts.insurance_fund.as_mut(),
+                treasury.as_ref(),
+                config,
+                clock.unix_timestamp,
+            )?;
+            tips = tips.checked_add(tip).ok_or(EscrowError::ArithmeticOverflow)?;
+
+            // Persist before the next entry, which may share a reputation account
+            release_exposure_accounts(&mut escrow, Some(agent_info), Some(api_info))?;
+            escrow.exit(&crate::ID)?;
+
+            emit!(FundsReleased {
+                version: EVENT_VERSION,
+                escrow: escrow.key(),
+                transaction_id: escrow.transaction_id.clone(),
+                amount: transfer_amount,
+                api: escrow.api,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        parser.finish()?;
+
+        msg!("Cranked {} expired escrows for {} lamports in tips", count, tips);
+
+        emit!(ExpiredEscrowsCranked {
+            version: EVENT_VERSION,
+            namespace: config.namespace,
+            cranker: cranker.key(),
+            released: count as u8,
+            tips,
+        });
+
+        Ok(())
+    }
 
     /// Resolve dispute with verifier oracle signature
     ///
-    /// x402 Verifier Oracle assesses quality and signs a refund percentage.
-    /// This instruction validates the signature and splits funds accordingly.
+    /// x402 Verifier Oracle assesses quality and signs a quality score.
+    /// This instruction validates the signature and splits funds by the
+    /// namespace's refund curve. Namespaces with an appeal window resolve in
+    /// two phases instead, through `submit_resolution` and `settle_resolution`.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `content_hash` - SHA-256 of the response payload the verifier assessed
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn resolve_dispute(
+        ctx: Context<ResolveDispute>,
+        quality_score: u8,
+        content_hash: [u8; 32],
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        resolve_with_signature(ctx, quality_score, None, content_hash, nonce, signature, false)
+    }
+
+    /// Resolve a dispute from a verifier signature submitted by a relayer
+    ///
+    /// Verification and settlement are those of `resolve_dispute`, so the
+    /// verifier only signs the ruling and needs no funded wallet. The relayer
+    /// signs the transaction and is reimbursed `RELAYER_FEE_LAMPORTS` out of
+    /// the escrow before it is split, like the oracle fee.
+    ///
+    /// # Arguments
+    /// Same as `resolve_dispute`.
+    pub fn resolve_with_relayer(
+        ctx: Context<ResolveDispute>,
+        quality_score: u8,
+        content_hash: [u8; 32],
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(ctx.accounts.relayer.is_some(), EscrowError::RelayerMissing);
+        resolve_with_signature(ctx, quality_score, None, content_hash, nonce, signature, true)
+    }
+
+    /// Resolve a dispute from per-dimension scores (verifier signature)
+    ///
+    /// For escrows whose work agreement weights score dimensions, which no
+    /// other resolution path accepts. The verifier scores each dimension; the
+    /// quality score is their weighted aggregate, computed here, and both are
+    /// signed. Settlement is that of `resolve_dispute`.
+    ///
+    /// # Arguments
+    /// * `sub_scores` - Score of each dimension (0-100), in the agreement's order
+    /// * `content_hash` - SHA-256 of the response payload the verifier assessed
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn resolve_dispute_scored(
+        ctx: Context<ResolveDispute>,
+        sub_scores: [u8; SCORE_DIMENSIONS],
+        content_hash: [u8; 32],
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        resolve_with_signature(ctx, 0, Some(sub_scores), content_hash, nonce, signature, false)
+    }
+
+    /// Record a verifier's signed ruling on a disputed escrow without moving funds
+    ///
+    /// First phase of a two-phase resolution. The signature is checked as in
+    /// `resolve_dispute` and stored with the score; the namespace's appeal
+    /// window then starts, during which either party may `appeal_resolution`.
+    /// `settle_resolution` moves the funds once it closes.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `content_hash` - SHA-256 of the response payload the verifier assessed
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn submit_resolution(
+        ctx: Context<SubmitResolution>,
+        quality_score: u8,
+        content_hash: [u8; 32],
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+        require!(!escrow.resolution_held(), EscrowError::ResolutionHeld);
+        require!(
+            escrow.status() == EscrowStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+        if let Some(assigned) = escrow.assigned_verifier {
+            require_keys_eq!(
+                ctx.accounts.verifier.key(),
+                assigned,
+                EscrowError::VerifierNotAssigned
+            );
+        }
+        if let Some(response_hash) = escrow.response_hash {
+            require!(
+                response_hash == content_hash,
+                EscrowError::AssessedWrongContent
+            );
+        }
+
+        let work_agreement = ctx.accounts.work_agreement.as_deref();
+        let quality_score = resolution_quality(work_agreement, quality_score, None)?;
+        let refund_percentage =
+            agreed_refund_percentage(&ctx.accounts.config, escrow, work_agreement, quality_score)?;
+
+        require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+        let message = build_resolution_message(
+            &escrow.key(),
+            escrow,
+            nonce,
+            work_agreement,
+            quality_score,
+            &content_hash,
+            None,
+        );
+        verify_ed25519_signature(
+            &ctx.accounts.instructions_sysvar,
+            &signature,
+            &mut ctx.accounts.verifier_nonces,
+            nonce,
+            message.as_bytes(),
+            0,
+            ctx.accounts.config.strict_sig_adjacency,
+        )?;
+
+        let appeal_deadline = Clock::get()?
+            .unix_timestamp
+            .checked_add(ctx.accounts.config.appeal_window_seconds)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        escrow.quality_score = Some(quality_score);
+        escrow.refund_percentage = Some(refund_percentage);
+        escrow.assessed_content_hash = Some(content_hash);
+        escrow.resolution_signature = Some(signature);
+        escrow.appeal_deadline = Some(appeal_deadline);
+        escrow.first_verifier = Some(ctx.accounts.verifier.key());
+
+        msg!("Resolution submitted, settles from {}", appeal_deadline);
+
+        emit!(ResolutionSubmitted {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            verifier: ctx.accounts.verifier.key(),
+            quality_score,
+            refund_percentage,
+            appeal_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve dispute with Switchboard On-Demand oracle
+    ///
+    /// Uses Switchboard decentralized oracle network for trustless quality assessment.
+    /// The Switchboard Function calculates quality score off-chain and produces
+    /// a cryptographically verified attestation that's validated on-chain.
+    /// Settles at once, so namespaces with an appeal window cannot use it.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score from Switchboard Function (0-100)
+    pub fn resolve_dispute_switchboard(
+        ctx: Context<ResolveDisputeSwitchboard>,
+        quality_score: u8,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        require_feed_resolvable(&ctx.accounts.escrow, &ctx.accounts.config, quality_score)?;
+
+        // Verify Switchboard attestation
+        // The Switchboard Function result is stored in pull_feed account
+        // and contains the quality score signed by oracle nodes
+        let pull_feed = &ctx.accounts.switchboard_function;
+
+        // Load and verify the Switchboard attestation
+        let feed_account_info = pull_feed.to_account_info();
+        let feed_data = PullFeedAccountData::parse(feed_account_info.data.borrow())
+            .map_err(|_| EscrowError::InvalidSwitchboardAttestation)?;
+        require_trusted_switchboard_feed(
+            &feed_account_info,
+            &feed_data.queue,
+            &ctx.accounts.switchboard_queue,
+            &ctx.accounts.config.switchboard_queue,
+        )?;
+
+        // Validate timestamp freshness against the namespace's window
+        let clock = Clock::get()?;
+        let age_seconds = clock.unix_timestamp - feed_data.last_update_timestamp;
+        require_fresh_attestation(age_seconds, &ctx.accounts.config)?;
+
+        msg!("Switchboard attestation age: {} seconds", age_seconds);
+
+        // The feed attests to both the score and the refund it implies
+        let (switchboard_quality, switchboard_refund) = unpack_quality_result(feed_data.result.value)
+            .ok_or(EscrowError::InvalidSwitchboardAttestation)?;
+        require!(switchboard_quality == quality_score, EscrowError::QualityScoreMismatch);
+        let refund_percentage = ctx
+            .accounts
+            .escrow
+            .refund_percentage_for(&ctx.accounts.config, quality_score);
+        require!(
+            switchboard_refund == refund_percentage,
+            EscrowError::RefundPercentageMismatch
+        );
+
+        msg!("Switchboard Quality Score: {}", quality_score);
+        msg!("Refund: {}%", refund_percentage);
+
+        let split = settlement_split(
+            &ctx.accounts.escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        // No verifier wallet backs a feed attestation, so the agent gets its priority fee back
+        apply_resolution(
+            SettlementAccounts {
+                escrow: &mut ctx.accounts.escrow,
+                agent: &ctx.accounts.agent,
+                refund_recipient: ctx.accounts.refund_recipient.as_deref(),
+                api: &ctx.accounts.api,
+                treasury: ctx.accounts.treasury.as_ref(),
+                config: &ctx.accounts.config,
+                priority_fee_recipient: &ctx.accounts.agent,
+                agent_reputation: &ctx.accounts.agent_reputation,
+                api_reputation: &ctx.accounts.api_reputation,
+                agent_reputation_history: ctx.accounts.agent_reputation_history.as_deref_mut(),
+                api_reputation_history: ctx.accounts.api_reputation_history.as_deref_mut(),
+                verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+                service_bond: ctx.accounts.service_bond.as_mut(),
+                api_penalties: None,
+            },
+            Resolution {
+                quality_score,
+                refund_percentage,
+                split,
+                content_hash: None,
+                resolver: ctx.accounts.switchboard_function.key(),
+                method: ResolutionMethod::Switchboard,
+            },
+        )?;
+
+        msg!("Dispute resolved via Switchboard!");
+
+        Ok(())
+    }
+
+    /// Resolve dispute with a Pyth-published quality attestation
+    ///
+    /// For namespaces that publish quality results through Pyth rather than
+    /// Switchboard. The namespace's `pyth_quality_feed_id` reports
+    /// `quality_score * 1000 + refund_percentage` at exponent 0, verified
+    /// through a fully verified `PriceUpdateV2`; settlement is shared with
+    /// `resolve_dispute_switchboard`, and like it this path is closed to
+    /// namespaces with an appeal window.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score attested by the feed (0-100)
+    pub fn resolve_dispute_pyth(ctx: Context<ResolveDisputePyth>, quality_score: u8) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        require_feed_resolvable(&ctx.accounts.escrow, &ctx.accounts.config, quality_score)?;
+
+        let config = &ctx.accounts.config;
+        let (attested_quality, attested_refund) =
+            read_pyth_quality_result(&ctx.accounts.price_update, config)?;
+        require!(attested_quality == quality_score, EscrowError::QualityScoreMismatch);
+        let refund_percentage = ctx.accounts.escrow.refund_percentage_for(config, quality_score);
+        require!(attested_refund == refund_percentage, EscrowError::RefundPercentageMismatch);
+
+        msg!("Pyth Quality Score: {}", quality_score);
+        msg!("Refund: {}%", refund_percentage);
+
+        let split = settlement_split(
+            &ctx.accounts.escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        // No verifier wallet backs a feed attestation, so the agent gets its priority fee back
+        apply_resolution(
+            SettlementAccounts {
+                escrow: &mut ctx.accounts.escrow,
+                agent: &ctx.accounts.agent,
+                refund_recipient: ctx.accounts.refund_recipient.as_deref(),
+                api: &ctx.accounts.api,
+                treasury: ctx.accounts.treasury.as_ref(),
+                config: &ctx.accounts.config,
+                priority_fee_recipient: &ctx.accounts.agent,
+                agent_reputation: &ctx.accounts.agent_reputation,
+                api_reputation: &ctx.accounts.api_reputation,
+                agent_reputation_history: ctx.accounts.agent_reputation_history.as_deref_mut(),
+                api_reputation_history: ctx.accounts.api_reputation_history.as_deref_mut(),
+                verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+                service_bond: ctx.accounts.service_bond.as_mut(),
+                api_penalties: None,
+            },
+            Resolution {
+                quality_score,
+                refund_percentage,
+                split,
+                content_hash: None,
+                resolver: ctx.accounts.price_update.key(),
+                method: ResolutionMethod::Pyth,
+            },
+        )?;
+
+        msg!("Dispute resolved via Pyth!");
+
+        Ok(())
+    }
+
+    /// Resolve several disputes signed by the same verifier in one transaction
+    ///
+    /// A single Ed25519 instruction carries one signature per resolution, in
+    /// the same order as `resolutions`, so the per-transaction fee is shared.
+    /// Remaining accounts hold, per resolution and in order:
+    /// `[escrow, agent, api, agent_reputation, api_reputation]` (all writable),
+    /// followed by the API's service bond when the escrow is covered by one
+    /// and its refund recipient when it names one. Like `resolve_dispute`, it
+    /// is closed to namespaces with an appeal window.
+    ///
+    /// # Arguments
+    /// * `resolutions` - Score, content hash, nonce and signature per escrow
+    pub fn batch_resolve_disputes_ed25519<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchResolveDisputes<'info>>,
+        resolutions: Vec<BatchResolution>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+        // Every entry must belong to this namespace, so one check covers the batch
+        require_single_step_resolution(&ctx.accounts.config)?;
+
+        require!(
+            !resolutions.is_empty() && resolutions.len() <= MAX_BATCH_RESOLUTIONS,
+            EscrowError::InvalidBatchSize
+        );
+
+        let remaining: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+        require!(
+            remaining.len() >= resolutions.len() * BATCH_ACCOUNTS_PER_RESOLUTION,
+            EscrowError::InvalidBatchAccounts
+        );
+
+        let verifier = ctx.accounts.verifier.key();
+        require_registered_verifier(&ctx.accounts.verifier_registry, &verifier)?;
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+
+        let mut parser = RemainingAccountsParser::new(remaining);
+        for (index, resolution) in resolutions.iter().enumerate() {
+            let quality_score = resolution.quality_score;
+            let content_hash = resolution.content_hash;
+
+            let escrow_info = parser.next(&AccountExpectation::any().owned_by(crate::ID).writable())?;
+            let mut escrow: Account<Escrow> = Account::try_from(escrow_info)?;
+            require!(escrow.mint.is_none(), EscrowError::TokenEscrowRequiresSplPath);
+            let agent = parser.next(&AccountExpectation::any().key(escrow.agent).writable())?;
+            let api = parser.next(&AccountExpectation::any().key(escrow.api).writable())?;
+            let agent_info = parser.next(
+                &AccountExpectation::any()
+                    .key(reputation_address(&escrow.agent))
+                    .writable(),
+            )?;
+            let api_info = parser.next(
+                &AccountExpectation::any()
+                    .key(reputation_address(&escrow.api))
+                    .writable(),
+            )?;
+            require_keys_eq!(
+                escrow.namespace,
+                ctx.accounts.config.namespace,
+                EscrowError::NamespaceMismatch
+            );
+
+            require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+            require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+            require!(!escrow.resolution_held(), EscrowError::ResolutionHeld);
+            require!(
+                escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+                EscrowError::InvalidStatus
+            );
+            require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+            if let Some(assigned) = escrow.assigned_verifier {
+                require_keys_eq!(verifier, assigned, EscrowError::VerifierNotAssigned);
+            }
+            if let Some(response_hash) = escrow.response_hash {
+                require!(
+                    response_hash == content_hash,
+                    EscrowError::AssessedWrongContent
+                );
+            }
+
+            // Batch entries carry no agreement account; resolve these individually
+            require!(!escrow.has_work_agreement(), EscrowError::WorkAgreementRequired);
+            let refund_percentage = escrow.refund_percentage_for(&ctx.accounts.config, quality_score);
+
+            let message = build_resolution_message(
+                &escrow.key(),
+                &escrow,
+                resolution.nonce,
+                None,
+                quality_score,
+                &content_hash,
+                None,
+            );
+            verify_ed25519_signature(
+                &ctx.accounts.instructions_sysvar,
+                &resolution.signature,
+                &mut ctx.accounts.verifier_nonces,
+                resolution.nonce,
+                message.as_bytes(),
+                index as u8,
+                ctx.accounts.config.strict_sig_adjacency,
+            )?;
+
+            pay_oracle_fee(&mut escrow, &ctx.accounts.verifier, &ctx.accounts.config)?;
+            let split = settlement_split(
+                &escrow,
+                refund_percentage,
+                ctx.accounts.pyth_price_update.as_deref(),
+                &ctx.accounts.config,
+            )?;
+            let mut service_bond = if escrow.service_bonded() {
+                let bond_info = parser.next(
+                    &AccountExpectation::any()
+                        .key(service_bond_address(&escrow.api))
+                        .owned_by(crate::ID)
+                        .writable(),
+                )?;
+                Some(Account::<ServiceBond>::try_from(bond_info)?)
+            } else {
+                None
+            };
+            let refund_recipient = match escrow.refund_recipient {
+                Some(recipient) => Some(parser.next(&AccountExpectation::any().key(recipient).writable())?),
+                None => None,
+            };
+
+            // Reputations are stored before the next entry, which may share them
+            apply_resolution(
+                SettlementAccounts {
+                    escrow: &mut escrow,
+                    agent,
+                    refund_recipient,
+                    api,
+                    treasury: treasury.as_ref(),
+                    config: &ctx.accounts.config,
+                    priority_fee_recipient: &ctx.accounts.verifier,
+                    agent_reputation: agent_info,
+                    api_reputation: api_info,
+                    agent_reputation_history: None,
+                    api_reputation_history: None,
+                    verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+                    service_bond: service_bond.as_mut(),
+                    api_penalties: None,
+                },
+                Resolution {
+                    quality_score,
+                    refund_percentage,
+                    split,
+                    content_hash: Some(content_hash),
+                    resolver: verifier,
+                    method: ResolutionMethod::Ed25519,
+                },
+            )?;
+            if let Some(bond) = &service_bond {
+                bond.exit(&crate::ID)?;
+            }
+            escrow.exit(&crate::ID)?;
+
+            msg!("Batch dispute {} resolved: {}", index, escrow.transaction_id);
+        }
+        parser.finish()?;
+
+        Ok(())
+    }
+
+    /// Settle a dispute no oracle resolved (permissionless)
+    ///
+    /// Once `STALE_DISPUTE_TIMEOUT` has passed since expiry, the escrow is
+    /// split at the default 50% refund. Escrows created with
+    /// `StaleDisputeTerms` instead settle at their own refund once their
+    /// verifier timeout has passed since the dispute. No quality was
+    /// assessed, so reputation scores are untouched. Agents undercompensated
+    /// by the default split can file an insurance claim.
+    pub fn settle_stale_dispute(ctx: Context<SettleStaleDispute>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require_stale_dispute(escrow, clock.unix_timestamp)?;
+
+        let escrow_amount = escrow.open_amount();
+        let refund_percentage = escrow.stale_refund_percentage();
+        let (refund_amount, payment_amount) = settlement_split(
+            escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        // No verifier earned the priority fee
+        disburse_priority_fee(&ctx.accounts.escrow, &ctx.accounts.agent.to_account_info())?;
+        // No verdict was reached, so the dispute was not shown to be frivolous
+        settle_dispute_bond(
+            &ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.config,
+            true,
+        )?;
+        require_rent_exempt_after(&ctx.accounts.escrow.to_account_info(), escrow_amount)?;
+
+        let agent = ctx.accounts.agent.to_account_info();
+        let refund_recipient = ctx.accounts.refund_recipient.as_ref().map(|r| r.to_account_info());
+        let refund_to = refund_destination(&ctx.accounts.escrow, &agent, refund_recipient.as_ref())?;
+        refund_agent(&mut ctx.accounts.escrow, refund_to, refund_amount)?;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_api_with_fee(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.api,
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            payment_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_status(EscrowStatus::Resolved);
+        escrow.settled_at = Some(Clock::get()?.unix_timestamp);
+        escrow.refund_percentage = Some(refund_percentage);
+        escrow.set_settled_stale(true);
+
+        // Reputation is best-effort: corrupt analytics state never blocks settlement
+        let escrow_key = escrow.key();
+        let agent_info = ctx.accounts.agent_reputation.to_account_info();
+        let api_info = ctx.accounts.api_reputation.to_account_info();
+        let mut agent_reputation = load_reputation(&agent_info, &escrow.agent, escrow_key);
+        let mut api_reputation = load_reputation(&api_info, &escrow.api, escrow_key);
+
+        release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+        release_assignment(escrow, ctx.accounts.verifier_oracle.as_deref_mut())?;
+        store_reputation(&agent_info, agent_reputation.as_ref())?;
+        store_reputation(&api_info, api_reputation.as_ref())?;
+
+        msg!("Stale dispute settled at {}% refund", refund_percentage);
+
+        emit!(StaleDisputeSettled {
+            version: EVENT_VERSION,
+            escrow: escrow_key,
+            transaction_id: escrow.transaction_id.clone(),
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Settle an escrow on terms both parties sign
+    ///
+    /// Agent and API agree on the refund without a verifier, either before
+    /// anyone disputes or in place of a pending resolution. Any priority fee
+    /// goes back to the agent. Reputation scores are untouched since no
+    /// quality assessment was made.
+    ///
+    /// # Arguments
+    /// * `refund_percentage` - Agreed refund to the agent (0-100)
+    pub fn settle_mutual(ctx: Context<SettleMutual>, refund_percentage: u8) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+        require!(refund_percentage <= 100, EscrowError::InvalidRefundPercentage);
+
+        let escrow_amount = escrow.open_amount();
+        let (refund_amount, payment_amount) = settlement_split(
+            escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        disburse_priority_fee(&ctx.accounts.escrow, &ctx.accounts.agent.to_account_info())?;
+        settle_dispute_bond(
+            &ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api.to_account_info(),
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.config,
+            dispute_bond_returned(refund_percentage),
+        )?;
+        settle_arbitration_bond(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api.to_account_info(),
+            true,
+        )?;
+        require_rent_exempt_after(&ctx.accounts.escrow.to_account_info(), escrow_amount)?;
+
+        refund_agent(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            refund_amount,
+        )?;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_api_with_fee(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.api.to_account_info(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            payment_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_status(EscrowStatus::Resolved);
+        escrow.settled_at = Some(Clock::get()?.unix_timestamp);
+        escrow.refund_percentage = Some(refund_percentage);
+
+        // Reputation is best-effort: corrupt analytics state never blocks settlement
+        let escrow_key = escrow.key();
+        let agent_info = ctx.accounts.agent_reputation.to_account_info();
+        let api_info = ctx.accounts.api_reputation.to_account_info();
+        let mut agent_reputation = load_reputation(&agent_info, &escrow.agent, escrow_key);
+        let mut api_reputation = load_reputation(&api_info, &escrow.api, escrow_key);
+
+        release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+        release_assignment(escrow, ctx.accounts.verifier_oracle.as_deref_mut())?;
+        store_reputation(&agent_info, agent_reputation.as_ref())?;
+        store_reputation(&api_info, api_reputation.as_ref())?;
+
+        msg!("Escrow settled by agreement: {}% refund", refund_percentage);
+
+        emit!(EscrowSettledMutually {
+            version: EVENT_VERSION,
+            escrow: escrow_key,
+            transaction_id: escrow.transaction_id.clone(),
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// API commits to the response it delivered
+    ///
+    /// Once committed, a verifier resolution must attest to this exact
+    /// payload hash. The commitment can be made only once, and only before a
+    /// dispute, so the API cannot answer a dispute by committing to whatever
+    /// the verifier is about to assess. The API may also
+    /// bring the time lock forward to the end of the dispute window, which
+    /// now runs from delivery, so an undisputed escrow auto-releases then.
+    /// Acceptance marks the escrow as genuine usage and refunds its spam
+    /// protection deposit.
+    ///
+    /// # Arguments
+    /// * `response_hash` - SHA-256 of the response payload
+    /// * `challenge_nonce` - Echo of the payment reference's 402 nonce, if any
+    /// * `release_early` - Shorten the time lock to the dispute deadline
+    pub fn acknowledge_delivery(
+        ctx: Context<AcknowledgeDelivery>,
+        response_hash: [u8; 32],
+        challenge_nonce: Option<[u8; 16]>,
+        release_early: bool,
+    ) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            escrow.response_hash.is_none(),
+            EscrowError::DeliveryAlreadyAcknowledged
+        );
+        // Echoing the nonce commits the API to the same HTTP exchange as the agent
+        require!(
+            challenge_nonce == escrow.payment_reference.map(|r| r.challenge_nonce),
+            EscrowError::ChallengeNonceMismatch
+        );
+
+        escrow.response_hash = Some(response_hash);
+        escrow.delivered_at = Some(clock.unix_timestamp);
+        if escrow.delivery_clock() {
+            // Restart the countdown, keeping any extension the agent granted
+            escrow.expires_at = clock
+                .unix_timestamp
+                .checked_add(escrow.expires_at - escrow.created_at)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+        }
+        if release_early {
+            escrow.expires_at = escrow.early_release_at();
+        }
+
+        if escrow.spam_deposit > 0 {
+            let vault = ctx
+                .accounts
+                .spam_vault
+                .as_mut()
+                .ok_or(EscrowError::SpamVaultMissing)?;
+            let agent = ctx.accounts.agent.as_ref().ok_or(EscrowError::AgentWalletMissing)?;
+            return_spam_deposit(escrow, vault, &agent.to_account_info())?;
+        }
+
+        msg!("Delivery acknowledged by API");
+
+        emit!(DeliveryAcknowledged {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            api: escrow.api,
+            response_hash,
+            challenge_nonce,
+            timestamp: clock.unix_timestamp,
+            expires_at: escrow.expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Mark escrow as disputed (agent initiates dispute)
+    ///
+    /// The agent escrows a bond of `config.dispute_bond_bps` of the escrowed
+    /// amount (at least `BASE_DISPUTE_COST`), multiplied for agents that
+    /// dispute often. It comes back when the resolution refunds at least
+    /// `DISPUTE_BOND_REFUND_THRESHOLD` percent; otherwise it is forfeited to
+    /// the API, or to the treasury if the namespace says so.
+    ///
+    /// Once the namespace has an assignment queue, the next available
+    /// verifier is assigned round-robin. `remaining_accounts` must then hold
+    /// the `VerifierOracleAccount` of every queued verifier, in queue order.
+    ///
+    /// # Arguments
+    /// * `priority_fee` - Lamports escrowed for the resolving verifier so it
+    ///   can prioritize this dispute; refunded if no verifier resolves it
+    /// * `reason_code` - Category of the claim, for the verifier to check
+    ///   the evidence against
+    /// * `evidence_hash` - SHA-256 of the agent's off-chain evidence
+    pub fn mark_disputed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MarkDisputed<'info>>,
+        priority_fee: u64,
+        reason_code: DisputeReason,
+        evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let reputation = &mut ctx.accounts.reputation;
+
+        require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+
+        // The agent, its delegate or a session key scoped for disputes
+        let clock = Clock::get()?;
+        match ctx.accounts.session.as_deref() {
+            Some(session) => session.authorize(SESSION_SCOPE_DISPUTE, clock.unix_timestamp)?,
+            None => require!(
+                escrow.acts_for_agent(&ctx.accounts.agent.key()),
+                EscrowError::Unauthorized
+            ),
+        }
+
+        // Check if dispute window is still open
+        require!(
+            clock.unix_timestamp < escrow.dispute_deadline(),
+            EscrowError::DisputeWindowExpired
+        );
+
+        // Token amounts say nothing about lamport value, so SPL escrows pay the flat cost
+        let bonded_amount = if escrow.mint.is_some() { 0 } else { escrow.open_amount() };
+        let dispute_cost =
+            calculate_dispute_cost(reputation, bonded_amount, ctx.accounts.config.dispute_bond_bps);
+        require!(
+            ctx.accounts.agent.lamports() >= dispute_cost,
+            EscrowError::InsufficientDisputeFunds
+        );
+
+        require!(
+            priority_fee <= ctx.accounts.config.max_priority_fee_lamports,
+            EscrowError::PriorityFeeTooLarge
+        );
+
+        charge_rate_limit(
+            &ctx.accounts.rate_limiter,
+            &escrow.agent,
+            RateLimitedAction::Dispute,
+        )?;
+
+        // Update reputation - record dispute filed
+        reputation.disputes_filed = reputation.disputes_filed.saturating_add(1);
+
+        escrow.set_status(EscrowStatus::Disputed);
+        escrow.disputed_at = Some(clock.unix_timestamp);
+        escrow.dispute_reason = Some(reason_code);
+        escrow.evidence_hash = Some(evidence_hash);
+        escrow.priority_fee = priority_fee;
+        escrow.dispute_bond = dispute_cost;
+
+        // Round-robin verifier assignment; None lets any verifier resolve
+        escrow.assigned_verifier = None;
+        if let Some((verifier, queue_index)) = assign_from_queue(
+            &ctx.accounts.assignment_queue,
+            &escrow.namespace,
+            ctx.remaining_accounts,
+        )? {
+            escrow.assigned_verifier = Some(verifier);
+            msg!("Dispute assigned to verifier {}", verifier);
+            emit!(DisputeAutoAssigned {
+                version: EVENT_VERSION,
+                escrow: escrow.key(),
+                verifier,
+                queue_index,
+            });
+        }
+
+        if priority_fee > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.agent.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                },
+            );
+            anchor_lang::system_program::transfer(cpi_context, priority_fee)?;
+        }
+
+        // Held in the escrow until resolution decides who keeps it
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, dispute_cost)?;
+
+        let escrow = &ctx.accounts.escrow;
+        msg!("Escrow marked as disputed (cost: {} lamports)", dispute_cost);
+
+        emit!(DisputeMarked {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            agent: escrow.agent,
+            transaction_id: escrow.transaction_id.clone(),
+            timestamp: clock.unix_timestamp,
+            priority_fee,
+            reason_code,
+            evidence_hash,
+        });
+
+        Ok(())
+    }
+
+    /// API answers a dispute with its own evidence
+    ///
+    /// Accepted once, within `PROVIDER_RESPONSE_WINDOW` of the dispute and
+    /// before a verifier has ruled, so the verifier sees both sides.
+    /// Resolution does not wait for the response.
+    ///
+    /// # Arguments
+    /// * `evidence_hash` - SHA-256 of the API's off-chain evidence
+    /// * `comment_uri` - Optional link, up to 200 characters, to the API's
+    ///   account of the exchange
+    pub fn submit_provider_response(
+        ctx: Context<SubmitProviderResponse>,
+        evidence_hash: [u8; 32],
+        comment_uri: Option<String>,
+    ) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let now = Clock::get()?.unix_timestamp;
+
+        require_provider_response_open(escrow, now)?;
+        if let Some(uri) = &comment_uri {
+            require!(uri.len() <= MAX_COMMENT_URI_LEN, EscrowError::CommentUriTooLong);
+        }
+
+        escrow.provider_evidence_hash = Some(evidence_hash);
+        escrow.provider_comment_uri = comment_uri.clone();
+
+        msg!("Provider response submitted");
+
+        emit!(ProviderResponseSubmitted {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            api: escrow.api,
+            evidence_hash,
+            comment_uri,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize entity reputation
+    ///
+    /// Anyone may create an agent's reputation. Providers are scored on what
+    /// they deliver, so only the provider itself, as payer, can create one.
+    ///
+    /// # Arguments
+    /// * `entity_type` - Whether the entity pays for services or provides them
+    pub fn init_reputation(ctx: Context<InitReputation>, entity_type: EntityType) -> Result<()> {
+        require!(
+            entity_type == EntityType::Agent
+                || ctx.accounts.payer.key() == ctx.accounts.entity.key(),
+            EscrowError::Unauthorized
+        );
+
+        let reputation = &mut ctx.accounts.reputation;
+        let clock = Clock::get()?;
+
+        reputation.entity = ctx.accounts.entity.key();
+        reputation.entity_type = entity_type;
+        reputation.total_transactions = 0;
+        reputation.disputes_filed = 0;
+        reputation.disputes_won = 0;
+        reputation.disputes_partial = 0;
+        reputation.disputes_lost = 0;
+        reputation.quality_sum = 0;
+        reputation.quality_count = 0;
+        reputation.poor_quality_count = 0;
+        reputation.reputation_score = 500; // Start at medium
+        reputation.current_win_streak = 0;
+        reputation.longest_win_streak = 0;
+        reputation.worst_quality_received = 0;
+        reputation.best_quality_received = 0;
+        reputation.total_refunds_received_lamports = 0;
+        reputation.total_payments_made_lamports = 0;
+        reputation.locked_value = 0;
+        reputation.total_volume_lamports = 0;
+        reputation.volume_usd_cents = 0;
+        reputation.last_price_feed_used = None;
+        reputation.price_at_last_update = 0;
+        reputation.created_at = clock.unix_timestamp;
+        reputation.last_updated = clock.unix_timestamp;
+        reputation.bump = ctx.bumps.reputation;
+        reputation.recent_counterparties = [0; RECENT_COUNTERPARTIES];
+        reputation.repeat_transactions = 0;
+
+        msg!("Reputation initialized for {}", ctx.accounts.entity.key());
+
+        Ok(())
+    }
+
+    /// Update reputation after transaction completes
+    /// Only callable by the escrow program itself during resolve_dispute
+    pub fn update_reputation(
+        ctx: Context<UpdateReputation>,
+        quality_score: u8,
+        refund_percentage: u8,
+    ) -> Result<()> {
+        // Authorization: Only allow updates from program-owned accounts
+        // In practice, this should be called via CPI from resolve_dispute
+        let reputation = &mut ctx.accounts.reputation;
+        let clock = Clock::get()?;
+
+        reputation.total_transactions = reputation.total_transactions.saturating_add(1);
+        // No escrow to size the update by, so it weighs as little as the smallest one
+        record_quality(reputation, quality_score, 1);
+        if reputation.entity_type == EntityType::Provider && quality_score < POOR_QUALITY_THRESHOLD {
+            reputation.poor_quality_count = reputation.poor_quality_count.saturating_add(1);
+        }
+
+        // Categorize dispute outcome
+        if refund_percentage >= 75 {
+            reputation.disputes_won = reputation.disputes_won.saturating_add(1);
+        } else if refund_percentage >= 25 {
+            reputation.disputes_partial = reputation.disputes_partial.saturating_add(1);
+        } else {
+            reputation.disputes_lost = reputation.disputes_lost.saturating_add(1);
+        }
+
+        record_outcome_stats(reputation, refund_percentage >= 75, quality_score);
+
+        // Calculate new reputation score (0-1000)
+        let old_score = reputation.reputation_score;
+        reputation.last_updated = clock.unix_timestamp;
+        reputation.reputation_score = calculate_reputation_score(reputation, clock.unix_timestamp);
+
+        msg!("Reputation updated: score = {}", reputation.reputation_score);
+
+        emit_reputation_updated(
+            reputation,
+            old_score,
+            ReputationChangeReason::dispute(refund_percentage),
+            clock.unix_timestamp,
+        );
+
+        Ok(())
+    }
+
+    /// Rate limit check - ensures entity hasn't exceeded limits
+    ///
+    /// `initialize_escrow` and `mark_disputed` charge the limiter themselves;
+    /// this remains for counting other actions.
+    pub fn check_rate_limit(ctx: Context<CheckRateLimit>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        consume_rate_limit(
+            &mut ctx.accounts.rate_limiter,
+            now,
+            RateLimitedAction::Transaction,
+        )
+    }
+
+    /// Apply time decay to a stored reputation score
+    ///
+    /// Permissionless. Scores are otherwise only recomputed on activity, so
+    /// an idle entity keeps the score it farmed until someone refreshes it.
+    /// `last_updated` is left alone so the decay keeps accruing.
+    pub fn refresh_reputation(ctx: Context<RefreshReputation>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let reputation = &mut ctx.accounts.reputation;
+        let previous = reputation.reputation_score;
+        reputation.reputation_score = calculate_reputation_score(reputation, now);
+
+        msg!(
+            "Reputation refreshed: {} -> {}",
+            previous,
+            reputation.reputation_score
+        );
+
+        emit_reputation_updated(reputation, previous, ReputationChangeReason::Decay, now);
+
+        Ok(())
+    }
+
+    /// Start keeping an entity's recent score history (permissionless)
+    ///
+    /// Resolutions that supply the history append a snapshot, keeping the
+    /// last `REPUTATION_HISTORY_LEN`.
+    pub fn init_reputation_history(ctx: Context<InitReputationHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.reputation_history;
+        history.entity = ctx.accounts.reputation.entity;
+        history.snapshots = [ScoreSnapshot::default(); REPUTATION_HISTORY_LEN];
+        history.next_index = 0;
+        history.count = 0;
+        history.bump = ctx.bumps.reputation_history;
+
+        msg!("Reputation history initialized for {}", history.entity);
+
+        Ok(())
+    }
+
+    /// Record an entity's current score in its history (permissionless)
+    ///
+    /// For updates made without the history account, such as batch
+    /// resolutions. Only records a score updated since the last snapshot, so
+    /// repeated calls cannot flush older entries out of the buffer.
+    pub fn snapshot_reputation(ctx: Context<SnapshotReputation>) -> Result<()> {
+        let reputation = &ctx.accounts.reputation;
+        let history = &mut ctx.accounts.reputation_history;
+        if let Some(latest) = history.latest() {
+            require!(
+                reputation.last_updated > latest.timestamp,
+                EscrowError::SnapshotUpToDate
+            );
+        }
+
+        history.push(reputation.reputation_score, reputation.last_updated);
+
+        msg!("Reputation snapshot: {}", reputation.reputation_score);
+
+        Ok(())
+    }
+
+    /// Report an entity's reputation through return data (read-only)
+    ///
+    /// Lets other programs query a score over CPI without depending on the
+    /// account layout. The score is decayed to the current time; an entity
+    /// without a reputation account reads as neutral and uninitialized.
+    pub fn get_reputation(ctx: Context<GetReputation>) -> Result<ReputationView> {
+        let info = ctx.accounts.reputation.to_account_info();
+        let reputation = if info.owner != &crate::ID || info.data_is_empty() {
+            None
+        } else {
+            require!(
+                info.data_len() == 8 + EntityReputation::INIT_SPACE,
+                EscrowError::UnknownReputationLayout
+            );
+            let data = info.try_borrow_data()?;
+            Some(EntityReputation::try_deserialize(&mut &data[..])?)
+        };
+
+        Ok(reputation_view(
+            ctx.accounts.entity.key(),
+            reputation.as_ref(),
+            Clock::get()?.unix_timestamp,
+        ))
+    }
+
+    /// Correct the entity type recorded on a reputation account (authority only)
+    ///
+    /// Fixes up providers whose reputation someone else created as an
+    /// `Agent`. Reputation PDAs are seeded by wallet alone, so each wallet
+    /// has exactly one account and nothing needs merging.
+    ///
+    /// The entity cannot retype itself: a provider with lost disputes or
+    /// poor deliveries would switch to `Agent` to escape the provider formula.
+    ///
+    /// # Arguments
+    /// * `entity` - Wallet whose reputation is corrected
+    /// * `entity_type` - Type the entity actually acts as
+    pub fn set_reputation_entity_type(
+        ctx: Context<SetReputationEntityType>,
+        entity: Pubkey,
+        entity_type: EntityType,
+    ) -> Result<()> {
+        let reputation = &mut ctx.accounts.reputation;
+        let previous = reputation.entity_type.clone();
+        reputation.entity_type = entity_type.clone();
+        reputation.last_updated = Clock::get()?.unix_timestamp;
+
+        msg!("Entity type corrected for {}", entity);
+
+        emit!(ReputationEntityTypeCorrected {
+            version: EVENT_VERSION,
+            entity,
+            previous,
+            entity_type,
+        });
+
+        Ok(())
+    }
+
+    /// Temporarily waive rate limits for an entity (authority only)
+    ///
+    /// The override lapses on its own at `override_until`.
+    ///
+    /// # Arguments
+    /// * `entity` - Entity whose limits are waived
+    /// * `override_until` - Unix timestamp when the override ends
+    pub fn set_rate_limit_override(
+        ctx: Context<SetRateLimitOverride>,
+        entity: Pubkey,
+        override_until: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let duration = override_until.saturating_sub(clock.unix_timestamp);
+
+        require!(
+            duration > 0 && duration <= ctx.accounts.config.max_override_duration_seconds,
+            EscrowError::InvalidOverrideDuration
+        );
+
+        let rate_limiter = &mut ctx.accounts.rate_limiter;
+        rate_limiter.rate_limit_overridden_until = Some(override_until);
+
+        msg!("Rate limits overridden for {} until {}", entity, override_until);
+
+        emit!(RateLimitOverrideGranted {
+            version: EVENT_VERSION,
+            entity,
+            authority: ctx.accounts.authority.key(),
+            until_timestamp: override_until,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize the default namespace config (program upgrade authority only)
+    pub fn initialize_global_config(ctx: Context<InitializeGlobalConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        init_config_defaults(config, ctx.accounts.authority.key(), DEFAULT_NAMESPACE);
+        config.bump = ctx.bumps.config;
+
+        msg!("Global config initialized, authority: {}", config.authority);
+
+        Ok(())
+    }
+
+    /// Initialize a platform namespace config
+    ///
+    /// The signing platform authority becomes both the namespace key and the
+    /// config authority. Escrows created under the namespace use this config.
+    pub fn initialize_namespace_config(ctx: Context<InitializeNamespaceConfig>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let config = &mut ctx.accounts.config;
+        init_config_defaults(config, authority, authority);
+        config.bump = ctx.bumps.config;
+
+        msg!("Namespace config initialized: {}", authority);
+
+        Ok(())
+    }
+
+    /// Update global config settings (authority only)
+    ///
+    /// Fields left as `None` are unchanged.
+    pub fn update_global_config(
+        ctx: Context<UpdateGlobalConfig>,
+        params: UpdateGlobalConfigParams,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        if let Some(strict_sig_adjacency) = params.strict_sig_adjacency {
+            config.strict_sig_adjacency = strict_sig_adjacency;
+        }
+        if let Some(spam_protection_deposit) = params.spam_protection_deposit {
+            config.spam_protection_deposit = spam_protection_deposit;
+        }
+        if let Some(sol_usd_price_feed) = params.sol_usd_price_feed {
+            config.sol_usd_price_feed = sol_usd_price_feed;
+        }
+        if let Some(max_override_duration_seconds) = params.max_override_duration_seconds {
+            require!(
+                max_override_duration_seconds > 0
+                    && max_override_duration_seconds <= MAX_RATE_LIMIT_OVERRIDE,
+                EscrowError::InvalidOverrideDuration
+            );
+            config.max_override_duration_seconds = max_override_duration_seconds;
+        }
+        if let Some(fee_bps) = params.fee_bps {
+            require!(fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFeeBps);
+            config.fee_bps = fee_bps;
+        }
+        if let Some(treasury) = params.treasury {
+            config.treasury = treasury;
+        }
+        if let Some(min_verifier_bond_lamports) = params.min_verifier_bond_lamports {
+            // Raising the minimum restarts the grace period for existing verifiers
+            if min_verifier_bond_lamports > config.min_verifier_bond_lamports {
+                config.min_bond_raised_at = Clock::get()?.unix_timestamp;
+            }
+            config.min_verifier_bond_lamports = min_verifier_bond_lamports;
+        }
+        if let Some(bond_grace_period_days) = params.bond_grace_period_days {
+            config.bond_grace_period_days = bond_grace_period_days;
+        }
+        if let Some(max_dispute_window_extensions) = params.max_dispute_window_extensions {
+            config.max_dispute_window_extensions = max_dispute_window_extensions;
+        }
+        if let Some(insurance_fund_bps) = params.insurance_fund_bps {
+            require!(insurance_fund_bps <= MAX_FEE_BPS, EscrowError::InvalidInsuranceBps);
+            config.insurance_fund_bps = insurance_fund_bps;
+        }
+        if let Some(max_insurance_payout_bps) = params.max_insurance_payout_bps {
+            require!(max_insurance_payout_bps <= MAX_BPS, EscrowError::InvalidInsuranceBps);
+            config.max_insurance_payout_bps = max_insurance_payout_bps;
+        }
+        if let Some(max_priority_fee_lamports) = params.max_priority_fee_lamports {
+            config.max_priority_fee_lamports = max_priority_fee_lamports;
+        }
+        if let Some(usd_stablecoin_mint) = params.usd_stablecoin_mint {
+            config.usd_stablecoin_mint = usd_stablecoin_mint;
+        }
+        if let Some(cancel_grace_period_seconds) = params.cancel_grace_period_seconds {
+            require!(cancel_grace_period_seconds >= 0, EscrowError::InvalidCancelGracePeriod);
+            config.cancel_grace_period_seconds = cancel_grace_period_seconds;
+        }
+        if let Some(refund_curve) = params.refund_curve {
+            validate_refund_curve(&refund_curve)?;
+            config.refund_curve = refund_curve;
+        }
+        if let Some(appeal_window_seconds) = params.appeal_window_seconds {
+            require!(
+                (0..=MAX_APPEAL_WINDOW).contains(&appeal_window_seconds),
+                EscrowError::InvalidAppealWindow
+            );
+            config.appeal_window_seconds = appeal_window_seconds;
+        }
+        if let Some(pyth_sol_usd_feed_id) = params.pyth_sol_usd_feed_id {
+            config.pyth_sol_usd_feed_id = pyth_sol_usd_feed_id;
+        }
+        if let Some(amount_tiers) = params.amount_tiers {
+            validate_amount_tiers(&amount_tiers)?;
+            config.amount_tiers = amount_tiers;
+        }
+        if let Some(dispute_bond_bps) = params.dispute_bond_bps {
+            require!(
+                dispute_bond_bps <= MAX_DISPUTE_BOND_BPS,
+                EscrowError::InvalidDisputeBondBps
+            );
+            config.dispute_bond_bps = dispute_bond_bps;
+        }
+        if let Some(dispute_bond_to_treasury) = params.dispute_bond_to_treasury {
+            config.dispute_bond_to_treasury = dispute_bond_to_treasury;
+        }
+        if let Some(severe_refund_slash_bps) = params.severe_refund_slash_bps {
+            require!(
+                severe_refund_slash_bps <= MAX_BPS,
+                EscrowError::InvalidSevereRefundSlashBps
+            );
+            config.severe_refund_slash_bps = severe_refund_slash_bps;
+        }
+        if let Some(release_insurance_bps) = params.release_insurance_bps {
+            require!(
+                release_insurance_bps <= MAX_RELEASE_INSURANCE_BPS,
+                EscrowError::InvalidInsuranceBps
+            );
+            config.release_insurance_bps = release_insurance_bps;
+        }
+        if let Some(oracle_fee_bps) = params.oracle_fee_bps {
+            require!(oracle_fee_bps <= MAX_ORACLE_FEE_BPS, EscrowError::InvalidOracleFeeBps);
+            config.oracle_fee_bps = oracle_fee_bps;
+        }
+        if let Some(switchboard_queue) = params.switchboard_queue {
+            config.switchboard_queue = switchboard_queue;
+        }
+        if let Some(attestation_max_age_seconds) = params.attestation_max_age_seconds {
+            require!(
+                (MIN_ATTESTATION_MAX_AGE..=MAX_ATTESTATION_MAX_AGE).contains(&attestation_max_age_seconds),
+                EscrowError::InvalidAttestationMaxAge
+            );
+            config.attestation_max_age_seconds = attestation_max_age_seconds;
+        }
+        if let Some(pyth_quality_feed_id) = params.pyth_quality_feed_id {
+            config.pyth_quality_feed_id = pyth_quality_feed_id;
+        }
+        // `audit_escrow` accepts at most `MAX_FEE_BPS` of a payment as deductions
+        require!(
+            config.release_deduction_bps() <= MAX_FEE_BPS,
+            EscrowError::InvalidFeeBps
+        );
+
+        msg!("Global config updated");
+
+        Ok(())
+    }
+
+    /// Ask the counterparty to extend an active escrow's dispute window
+    ///
+    /// Callable by the agent or the API; the other party approves.
+    ///
+    /// # Arguments
+    /// * `additional_seconds` - Seconds to add to the dispute window
+    pub fn request_dispute_window_extension(
+        ctx: Context<RequestDisputeWindowExtension>,
+        additional_seconds: i64,
+    ) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let requester = ctx.accounts.requester.key();
+
+        require!(
+            requester == escrow.agent || requester == escrow.api,
+            EscrowError::Unauthorized
+        );
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(additional_seconds > 0, EscrowError::InvalidDisputeWindowExtension);
+
+        let request = &mut ctx.accounts.extension_request;
+        request.escrow = escrow.key();
+        request.requester = requester;
+        request.additional_seconds = additional_seconds;
+        request.requested_at = Clock::get()?.unix_timestamp;
+        request.bump = ctx.bumps.extension_request;
+
+        msg!("Dispute window extension of {}s requested by {}", additional_seconds, requester);
+
+        Ok(())
+    }
+
+    /// Approve a pending dispute window extension (counterparty only)
+    ///
+    /// The extended window may not exceed the longer of the default window
+    /// and the escrow's original time lock, and each escrow can be extended at most `max_dispute_window_extensions`
+    /// times. Closes the request, returning rent to the requester.
+    pub fn approve_dispute_window_extension(
+        ctx: Context<ApproveDisputeWindowExtension>,
+    ) -> Result<()> {
+        let request = &ctx.accounts.extension_request;
+        let approver = ctx.accounts.approver.key();
+        let escrow = &mut ctx.accounts.escrow;
+
+        let counterparty = if request.requester == escrow.agent {
+            escrow.api
+        } else {
+            escrow.agent
+        };
+        require_keys_eq!(approver, counterparty, EscrowError::Unauthorized);
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            escrow.dispute_window_extension_count < ctx.accounts.config.max_dispute_window_extensions,
+            EscrowError::DisputeWindowExtensionLimit
+        );
+
+        let new_window = escrow
+            .dispute_window
+            .checked_add(request.additional_seconds)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(
+            new_window <= escrow.time_lock.max(DISPUTE_WINDOW),
+            EscrowError::InvalidDisputeWindowExtension
+        );
+
+        escrow.dispute_window = new_window;
+        escrow.dispute_window_extension_count += 1;
+
+        msg!("Dispute window extended to {}s", new_window);
+
+        emit!(DisputeWindowExtended {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            new_window,
+            approver,
+        });
+
+        Ok(())
+    }
+
+    /// Push back an active escrow's expiry to give the API more time to deliver
+    ///
+    /// The agent may extend alone until the time lock expires; after that the
+    /// API can already claim the funds, so the API must co-sign. The total
+    /// lock, measured from creation, stays within `MAX_TIME_LOCK`.
+    ///
+    /// # Arguments
+    /// * `additional_seconds` - Seconds to add to `expires_at`
+    pub fn extend_time_lock(ctx: Context<ExtendTimeLock>, additional_seconds: i64) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let clock = Clock::get()?;
+        let api_consented = ctx.accounts.api.is_some();
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            api_consented || clock.unix_timestamp < escrow.expires_at,
+            EscrowError::TimeLockExtensionNeedsApi
+        );
+        require!(additional_seconds > 0, EscrowError::InvalidTimeLock);
+
+        let new_expires_at = escrow
+            .expires_at
+            .checked_add(additional_seconds)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(
+            new_expires_at - escrow.created_at <= MAX_TIME_LOCK,
+            EscrowError::InvalidTimeLock
+        );
+
+        escrow.expires_at = new_expires_at;
+
+        msg!("Time lock extended to {}", new_expires_at);
+
+        emit!(TimeLockExtended {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            new_expires_at,
+            api_consented,
+        });
+
+        Ok(())
+    }
+
+    /// Rewrite an escrow created before status and booleans were packed into `flags`
+    ///
+    /// Permissionless and idempotent. The account shrinks; the freed rent stays
+    /// in the escrow and reaches the agent when it is closed.
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        let info = ctx.accounts.escrow.to_account_info();
+        let current_len = 8 + Escrow::INIT_SPACE;
+        if info.data_len() == current_len {
+            return Ok(());
+        }
+        require!(
+            info.data_len() == 8 + LegacyEscrowV0::INIT_SPACE,
+            EscrowError::UnknownEscrowLayout
+        );
+
+        let escrow = {
+            let data = info.try_borrow_data()?;
+            require!(
+                &data[..8] == Escrow::DISCRIMINATOR,
+                EscrowError::UnknownEscrowLayout
+            );
+            Escrow::from(LegacyEscrowV0::deserialize(&mut &data[8..])?)
+        };
+
+        info.realloc(current_len, false)?;
+        escrow.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        msg!("Escrow migrated to layout v{}", escrow.version);
+
+        emit!(EscrowMigrated {
+            version: EVENT_VERSION,
+            escrow: info.key(),
+            layout_version: escrow.version,
+        });
+
+        Ok(())
+    }
+
+    /// Rewrite a reputation stored in an older layout: one that still keeps
+    /// a rounded average quality, or one without counterparty tracking
+    ///
+    /// Permissionless and idempotent. The account grows; the payer covers
+    /// the extra rent.
+    pub fn migrate_reputation(ctx: Context<MigrateReputation>) -> Result<()> {
+        let info = ctx.accounts.reputation.to_account_info();
+        let current_len = 8 + EntityReputation::INIT_SPACE;
+        if info.data_len() == current_len {
+            return Ok(());
+        }
+        let legacy_len = 8 + LegacyReputationV0::INIT_SPACE;
+        let untracked_len = current_len - EntityReputation::COUNTERPARTY_SPACE;
+        require!(
+            info.data_len() == legacy_len || info.data_len() == untracked_len,
+            EscrowError::UnknownReputationLayout
+        );
+
+        let reputation = {
+            let data = info.try_borrow_data()?;
+            require!(
+                &data[..8] == EntityReputation::DISCRIMINATOR,
+                EscrowError::UnknownReputationLayout
+            );
+            if data.len() == legacy_len {
+                EntityReputation::from(LegacyReputationV0::deserialize(&mut &data[8..])?)
+            } else {
+                // Counterparty tracking was appended and starts out empty
+                let mut padded = data.to_vec();
+                padded.resize(current_len, 0);
+                EntityReputation::try_deserialize(&mut &padded[..])?
+            }
+        };
+
+        let deficit = Rent::get()?
+            .minimum_balance(current_len)
+            .saturating_sub(info.lamports());
+        if deficit > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: info.clone(),
+                },
+            );
+            anchor_lang::system_program::transfer(cpi_context, deficit)?;
+        }
+
+        info.realloc(current_len, false)?;
+        reputation.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        msg!("Reputation migrated for {}", reputation.entity);
+
+        emit!(ReputationMigrated {
+            version: EVENT_VERSION,
+            reputation: info.key(),
+            entity: reputation.entity,
+            quality_sum: reputation.quality_sum,
+            quality_count: reputation.quality_count,
+        });
+
+        Ok(())
+    }
+
+    /// Check a settled escrow's recorded payouts against the distribution its
+    /// stored fields imply (permissionless, moves no funds)
+    ///
+    /// Emits `AuditPassed` or `AuditFailed` so monitoring can verify
+    /// accounting continuously. Priority fees sit on top of `amount` and are
+    /// reported by `PriorityFeeDisbursed` instead.
+    pub fn audit_escrow(ctx: Context<AuditEscrow>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let expected = expected_accounting(escrow)?;
+        let recorded = escrow.recorded_accounting();
+
+        if expected == recorded {
+            emit!(AuditPassed {
+                version: EVENT_VERSION,
+                escrow: escrow.key(),
+                accounting: recorded,
+            });
+        } else {
+            msg!("Escrow accounting mismatch: expected {:?}, recorded {:?}", expected, recorded);
+            emit!(AuditFailed {
+                version: EVENT_VERSION,
+                escrow: escrow.key(),
+                expected,
+                recorded,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Top up an escrow PDA that has fallen below the rent-exempt minimum
+    ///
+    /// Callable by anyone; the payer covers exactly the deficit.
+    pub fn top_up_escrow_rent(ctx: Context<TopUpEscrowRent>) -> Result<()> {
+        let escrow_info = ctx.accounts.escrow.to_account_info();
+        let min_balance = Rent::get()?.minimum_balance(escrow_info.data_len());
+        let deficit = min_balance.saturating_sub(escrow_info.lamports());
+
+        require!(deficit > 0, EscrowError::EscrowAlreadyRentExempt);
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: escrow_info,
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, deficit)?;
+
+        msg!("Escrow rent topped up: {} lamports", deficit);
+
+        emit!(EscrowRentToppedUp {
+            version: EVENT_VERSION,
+            escrow: ctx.accounts.escrow.key(),
+            payer: ctx.accounts.payer.key(),
+            amount: deficit,
+        });
+
+        Ok(())
+    }
+
+    /// Add funds to an active escrow (agent only), e.g. for expanded scope
+    ///
+    /// The addition and the new total are held to the same bounds as a new
+    /// escrow, including the agent's reputation tier. Resolutions sign the
+    /// escrowed amount, so signatures over the old amount stop verifying.
+    pub fn add_funds(ctx: Context<AddFunds>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        let escrow = &ctx.accounts.escrow;
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            !escrow.has_milestones() && escrow.usd_target_cents.is_none(),
+            EscrowError::TopUpUnsupported
+        );
+        require!(amount >= MIN_ESCROW_AMOUNT, EscrowError::InvalidAmount);
+        let new_amount = escrow
+            .amount
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(new_amount <= MAX_ESCROW_AMOUNT, EscrowError::AmountTooLarge);
+        let agent_reputation = ctx
+            .accounts
+            .agent_reputation
+            .as_ref()
+            .and_then(|info| load_reputation(info, &escrow.agent, escrow.key()));
+        check_amount_tier(
+            &ctx.accounts.config,
+            agent_reputation.as_ref(),
+            new_amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+        // Tracked exposure grows with the escrow so release subtracts the new total
+        let escrow = &mut ctx.accounts.escrow;
+        if escrow.exposure_tracked() {
+            let (Some(agent_info), Some(api_info)) = (
+                ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info()),
+                ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info()),
+            ) else {
+                return err!(EscrowError::ExposureAccountsMissing);
+            };
+            let escrow_key = escrow.key();
+            for (info, entity) in [(&agent_info, escrow.agent), (&api_info, escrow.api)] {
+                let mut reputation = load_reputation(info, &entity, escrow_key);
+                if let Some(reputation) = reputation.as_mut() {
+                    reputation.locked_value = reputation.locked_value.saturating_add(amount);
+                }
+                store_reputation(info, reputation.as_ref())?;
+            }
+        }
+        escrow.amount = new_amount;
+
+        msg!("Escrow topped up to {} SOL", new_amount as f64 / 1_000_000_000.0);
+
+        emit!(EscrowToppedUp {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            agent: escrow.agent,
+            amount,
+            new_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Create an empty counterparty blocklist for the signer
+    pub fn initialize_blocklist(ctx: Context<InitializeBlocklist>) -> Result<()> {
+        let blocklist = &mut ctx.accounts.blocklist;
+        blocklist.owner = ctx.accounts.owner.key();
+        blocklist.blocked = Vec::new();
+        blocklist.bump = ctx.bumps.blocklist;
+
+        msg!("Blocklist initialized for {}", blocklist.owner);
+
+        Ok(())
+    }
+
+    /// Block a counterparty from opening escrows with the owner
+    pub fn add_to_blocklist(ctx: Context<ModifyBlocklist>, counterparty: Pubkey) -> Result<()> {
+        let blocklist = &mut ctx.accounts.blocklist;
+
+        match blocklist.blocked.binary_search(&counterparty) {
+            Ok(_) => return err!(EscrowError::AlreadyBlocked),
+            Err(position) => {
+                require!(
+                    blocklist.blocked.len() < MAX_BLOCKLIST_ENTRIES,
+                    EscrowError::BlocklistFull
+                );
+                blocklist.blocked.insert(position, counterparty);
+            }
+        }
+
+        msg!("Blocked counterparty: {}", counterparty);
+
+        Ok(())
+    }
+
+    /// Remove a counterparty from the owner's blocklist
+    pub fn remove_from_blocklist(ctx: Context<ModifyBlocklist>, counterparty: Pubkey) -> Result<()> {
+        let blocklist = &mut ctx.accounts.blocklist;
+
+        let position = blocklist
+            .blocked
+            .binary_search(&counterparty)
+            .map_err(|_| error!(EscrowError::NotBlocked))?;
+        blocklist.blocked.remove(position);
+
+        msg!("Unblocked counterparty: {}", counterparty);
+
+        Ok(())
+    }
+
+    /// Create an empty API allowlist for the signing organization
+    pub fn initialize_allowlist(ctx: Context<InitializeAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.organization = ctx.accounts.organization.key();
+        allowlist.allowed = Vec::new();
+        allowlist.bump = ctx.bumps.allowlist;
+
+        msg!("Allowlist initialized for {}", allowlist.organization);
+
+        Ok(())
+    }
+
+    /// Allow the organization's agents to pay an API
+    pub fn add_to_allowlist(ctx: Context<ModifyAllowlist>, api: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+
+        match allowlist.allowed.binary_search(&api) {
+            Ok(_) => return err!(EscrowError::AlreadyAllowlisted),
+            Err(position) => {
+                require!(
+                    allowlist.allowed.len() < MAX_ALLOWLIST_ENTRIES,
+                    EscrowError::AllowlistFull
+                );
+                allowlist.allowed.insert(position, api);
+            }
+        }
+
+        msg!("Allowlisted API: {}", api);
+
+        Ok(())
+    }
+
+    /// Remove an API from the organization's allowlist
+    pub fn remove_from_allowlist(ctx: Context<ModifyAllowlist>, api: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+
+        let position = allowlist
+            .allowed
+            .binary_search(&api)
+            .map_err(|_| error!(EscrowError::NotAllowlisted))?;
+        allowlist.allowed.remove(position);
+
+        msg!("Removed API from allowlist: {}", api);
+
+        Ok(())
+    }
+
+    /// Bind an agent to the organization's allowlist (organization and agent sign)
+    ///
+    /// The agent can then only open escrows, subscriptions and payment
+    /// channels with allowlisted APIs, until the organization lifts it.
+    pub fn restrict_agent(ctx: Context<RestrictAgent>) -> Result<()> {
+        let restriction = &mut ctx.accounts.restriction;
+        restriction.agent = ctx.accounts.agent.key();
+        restriction.organization = ctx.accounts.organization.key();
+        restriction.bump = ctx.bumps.restriction;
+
+        msg!("Agent {} restricted to {}'s allowlist", restriction.agent, restriction.organization);
+
+        Ok(())
+    }
+
+    /// Lift an agent's restriction (organization only)
+    pub fn unrestrict_agent(ctx: Context<UnrestrictAgent>) -> Result<()> {
+        msg!("Agent {} unrestricted", ctx.accounts.restriction.agent);
+
+        Ok(())
+    }
+
+    /// Create a team account for the signing organization key
+    pub fn initialize_organization(ctx: Context<InitializeOrganization>) -> Result<()> {
+        let organization = &mut ctx.accounts.organization_account;
+        organization.organization = ctx.accounts.organization.key();
+        organization.agent_count = 0;
+        organization.bump = ctx.bumps.organization_account;
+
+        msg!("Organization initialized for {}", organization.organization);
+
+        Ok(())
+    }
+
+    /// Add an agent key to the organization (organization and agent sign)
+    ///
+    /// The agent's SOL escrows then count against `daily_limit` lamports per
+    /// UTC day until the organization removes it.
+    pub fn add_organization_agent(ctx: Context<AddOrganizationAgent>, daily_limit: u64) -> Result<()> {
+        let member = &mut ctx.accounts.member;
+        member.organization = ctx.accounts.organization.key();
+        member.agent = ctx.accounts.agent.key();
+        member.daily_limit = daily_limit;
+        member.spent_today = 0;
+        member.spend_day = 0;
+        member.bump = ctx.bumps.member;
+
+        let organization = &mut ctx.accounts.organization_account;
+        organization.agent_count = organization
+            .agent_count
+            .checked_add(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        msg!("Agent {} joined with a daily limit of {} lamports", member.agent, daily_limit);
+
+        Ok(())
+    }
+
+    /// Change an organization agent's daily spend limit (organization only)
+    pub fn set_agent_spend_limit(ctx: Context<SetAgentSpendLimit>, daily_limit: u64) -> Result<()> {
+        let member = &mut ctx.accounts.member;
+        member.daily_limit = daily_limit;
+
+        msg!("Agent {} daily limit: {} lamports", member.agent, daily_limit);
+
+        Ok(())
+    }
+
+    /// Remove an agent key from the organization (organization only)
+    pub fn remove_organization_agent(ctx: Context<RemoveOrganizationAgent>) -> Result<()> {
+        let organization = &mut ctx.accounts.organization_account;
+        organization.agent_count = organization.agent_count.saturating_sub(1);
+
+        msg!("Agent {} left the organization", ctx.accounts.member.agent);
+
+        Ok(())
+    }
+
+    /// Authorize a session key to act for the signing agent until `expires_at`
+    ///
+    /// `scope` combines `SESSION_SCOPE_*` bits: opening escrows the session
+    /// key funds through `initialize_escrow_with_session`, and disputing the
+    /// agent's escrows. Sessions last at most 30 days.
+    pub fn create_session_key(
+        ctx: Context<CreateSessionKey>,
+        session_key: Pubkey,
+        scope: u8,
+        expires_at: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            scope != 0 && scope & !SESSION_SCOPES == 0,
+            EscrowError::InvalidSessionKey
+        );
+        require!(
+            expires_at > now && expires_at - now <= MAX_SESSION_KEY_DURATION,
+            EscrowError::InvalidSessionKey
+        );
+
+        let session = &mut ctx.accounts.session;
+        session.agent = ctx.accounts.agent.key();
+        session.session_key = session_key;
+        session.scope = scope;
+        session.expires_at = expires_at;
+        session.bump = ctx.bumps.session;
+
+        msg!("Session key {} valid until {}", session_key, expires_at);
+
+        emit!(SessionKeyCreated {
+            version: EVENT_VERSION,
+            agent: session.agent,
+            session_key,
+            scope,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a session key, expired or not (agent only)
+    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
+        let session = &ctx.accounts.session;
+
+        msg!("Session key {} revoked", session.session_key);
+
+        emit!(SessionKeyRevoked {
+            version: EVENT_VERSION,
+            agent: session.agent,
+            session_key: session.session_key,
+        });
+
+        Ok(())
+    }
+
+    /// Refund an escrow's spam protection deposit to the agent
+    ///
+    /// Permissionless. Genuine usage (the escrow was released or resolved)
+    /// unlocks the deposit immediately.
+    pub fn refund_spam_deposit(ctx: Context<RefundSpamDeposit>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(escrow.spam_deposit > 0, EscrowError::NoSpamDeposit);
+        require!(
+            escrow.status() == EscrowStatus::Released || escrow.status() == EscrowStatus::Resolved,
+            EscrowError::SpamDepositLocked
+        );
+
+        return_spam_deposit(
+            escrow,
+            &mut ctx.accounts.spam_vault,
+            &ctx.accounts.agent.to_account_info(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Refund the spam protection deposits retained from cancelled escrows
+    ///
+    /// Permissionless once `SPAM_DEPOSIT_RETENTION` has passed since the
+    /// agent's latest unaccepted cancellation.
+    pub fn refund_retained_spam_deposit(ctx: Context<RefundRetainedSpamDeposit>) -> Result<()> {
+        let vault = &mut ctx.accounts.spam_vault;
+        let amount = vault.retained_lamports;
+
+        require!(amount > 0, EscrowError::NoSpamDeposit);
+        require!(
+            Clock::get()?.unix_timestamp >= vault.retained_until,
+            EscrowError::SpamDepositLocked
+        );
+
+        vault.held_lamports = vault
+            .held_lamports
+            .checked_sub(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        vault.retained_lamports = 0;
+
+        **vault.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.agent.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        msg!("Retained spam protection deposit refunded: {} lamports", amount);
+
+        emit!(SpamProtectionDepositRefunded {
+            version: EVENT_VERSION,
+            agent: vault.agent,
+            escrow: Pubkey::default(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Create the round-robin verifier assignment queue
+    pub fn initialize_assignment_queue(ctx: Context<InitializeAssignmentQueue>) -> Result<()> {
+        let queue = &mut ctx.accounts.assignment_queue;
+        queue.namespace = ctx.accounts.config.namespace;
+        queue.verifier_pubkeys = Vec::new();
+        queue.next_index = 0;
+        queue.bump = ctx.bumps.assignment_queue;
+
+        msg!("Assignment queue initialized");
+
+        Ok(())
+    }
+
+    /// Register a verifier oracle and append it to the assignment queue
+    ///
+    /// # Arguments
+    /// * `max_concurrent_assignments` - Open disputes the verifier accepts at once
+    pub fn register_verifier_oracle(
+        ctx: Context<RegisterVerifierOracle>,
+        max_concurrent_assignments: u8,
+    ) -> Result<()> {
+        require!(
+            max_concurrent_assignments > 0,
+            EscrowError::InvalidVerifierCapacity
+        );
+
+        let queue = &mut ctx.accounts.assignment_queue;
+        require!(
+            queue.verifier_pubkeys.len() < MAX_QUEUE_VERIFIERS,
+            EscrowError::AssignmentQueueFull
+        );
+
+        require!(
+            ctx.accounts.verifier_bond.bonded_lamports >= ctx.accounts.config.min_verifier_bond_lamports,
+            EscrowError::InsufficientVerifierBond
+        );
+
+        let clock = Clock::get()?;
+        let verifier = ctx.accounts.verifier.key();
+
+        let oracle = &mut ctx.accounts.verifier_oracle;
+        oracle.verifier = verifier;
+        oracle.namespace = ctx.accounts.config.namespace;
+        oracle.active = true;
+        oracle.max_concurrent_assignments = max_concurrent_assignments;
+        oracle.active_assignments = 0;
+        oracle.registered_at = clock.unix_timestamp;
+        oracle.paused_until = None;
+        oracle.bump = ctx.bumps.verifier_oracle;
+
+        queue.verifier_pubkeys.push(verifier);
+
+        msg!("Verifier oracle registered: {}", verifier);
+
+        Ok(())
+    }
+
+    /// Post (or add to) a verifier's bond
+    ///
+    /// # Arguments
+    /// * `amount` - Lamports to add to the bond
+    pub fn post_verifier_bond(ctx: Context<PostVerifierBond>, amount: u64) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.verifier.to_account_info(),
+                to: ctx.accounts.verifier_bond.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+        let bond = &mut ctx.accounts.verifier_bond;
+        if bond.verifier == Pubkey::default() {
+            bond.verifier = ctx.accounts.verifier.key();
+            bond.bump = ctx.bumps.verifier_bond;
+        }
+        bond.bonded_lamports = bond
+            .bonded_lamports
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        msg!("Verifier bond posted: {} lamports", bond.bonded_lamports);
+
+        emit!(VerifierBondPosted {
+            version: EVENT_VERSION,
+            verifier: bond.verifier,
+            amount,
+            bonded_lamports: bond.bonded_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Create the consumed-nonce registry for a quote/attestation signer
+    pub fn initialize_used_nonces(ctx: Context<InitializeUsedNonces>) -> Result<()> {
+        let used_nonces = &mut ctx.accounts.used_nonces;
+        used_nonces.signer = ctx.accounts.signer.key();
+        used_nonces.window_start = 0;
+        used_nonces.bitmap = [0; USED_NONCE_WORDS];
+        used_nonces.bump = ctx.bumps.used_nonces;
+
+        msg!("Nonce registry initialized for {}", used_nonces.signer);
+
+        Ok(())
+    }
+
+    /// Burn a nonce so any artifact signed with it can no longer be used
+    ///
+    /// # Arguments
+    /// * `nonce` - Nonce embedded in the signed quote/attestation being revoked
+    pub fn invalidate_nonce(ctx: Context<InvalidateNonce>, nonce: u64) -> Result<()> {
+        let used_nonces = &mut ctx.accounts.used_nonces;
+        used_nonces.consume(nonce)?;
+
+        msg!("Nonce {} invalidated for {}", nonce, used_nonces.signer);
+
+        Ok(())
+    }
+
+    /// Deregister a verifier whose bond fell below the namespace minimum
+    ///
+    /// Permissionless crank. Only succeeds once the grace period that
+    /// followed the last minimum increase has elapsed.
+    ///
+    /// # Arguments
+    /// * `verifier` - Verifier being checked
+    pub fn check_verifier_bond_compliance(
+        ctx: Context<CheckVerifierBondCompliance>,
+        verifier: Pubkey,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let bonded_lamports = ctx
+            .accounts
+            .verifier_bond
+            .as_ref()
+            .map_or(0, |bond| bond.bonded_lamports);
+
+        require!(
+            bonded_lamports < config.min_verifier_bond_lamports,
+            EscrowError::VerifierBondCompliant
+        );
+
+        let clock = Clock::get()?;
+        let grace_ends_at = config
+            .min_bond_raised_at
+            .saturating_add(config.bond_grace_period_days as i64 * SECONDS_PER_DAY);
+        require!(
+            clock.unix_timestamp >= grace_ends_at,
+            EscrowError::BondGracePeriodActive
+        );
+
+        // A scheduled maintenance pause shields the verifier until it ends
+        let oracle = &mut ctx.accounts.verifier_oracle;
+        match oracle.paused_until {
+            Some(until) => require!(
+                clock.unix_timestamp >= until,
+                EscrowError::VerifierPauseActive
+            ),
+            None => require!(oracle.active, EscrowError::InvalidVerifierAccount),
+        }
+        oracle.active = false;
+        oracle.paused_until = None;
+
+        remove_from_queue(&mut ctx.accounts.assignment_queue, &verifier);
+
+        msg!("Verifier deregistered for insufficient bond: {}", verifier);
+
+        emit!(VerifierDeregisteredUnderbonded {
+            version: EVENT_VERSION,
+            verifier,
+            namespace: config.namespace,
+            bonded_lamports,
+            required_lamports: config.min_verifier_bond_lamports,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Temporarily take a verifier out of rotation for maintenance
+    ///
+    /// The verifier stays registered and queued, but is skipped by assignment
+    /// until it resumes. Every disputed escrow currently assigned to the
+    /// verifier must be passed in `remaining_accounts` (writable); their
+    /// assignments are released so any verifier can resolve them.
+    ///
+    /// # Arguments
+    /// * `reason_hash` - SHA-256 of the off-chain maintenance notice
+    /// * `pause_until` - Unix timestamp before which the verifier cannot resume
+    pub fn pause_self_as_verifier<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PauseVerifier<'info>>,
+        reason_hash: [u8; 32],
+        pause_until: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            pause_until > clock.unix_timestamp
+                && pause_until - clock.unix_timestamp <= MAX_VERIFIER_PAUSE,
+            EscrowError::InvalidPauseDuration
+        );
+
+        let oracle = &mut ctx.accounts.verifier_oracle;
+        require!(oracle.active, EscrowError::InvalidVerifierAccount);
+
+        let mut released_assignments: u8 = 0;
+        let mut parser = RemainingAccountsParser::new(ctx.remaining_accounts);
+        while parser.remaining() > 0 {
+            let escrow_info = parser.next(&AccountExpectation::any().owned_by(crate::ID).writable())?;
+            let mut escrow: Account<Escrow> = Account::try_from(escrow_info)?;
+            require!(
+                escrow.status() == EscrowStatus::Disputed,
+                EscrowError::InvalidStatus
+            );
+            require!(
+                escrow.assigned_verifier == Some(oracle.verifier),
+                EscrowError::VerifierNotAssigned
+            );
+            require_keys_eq!(escrow.namespace, oracle.namespace, EscrowError::NamespaceMismatch);
+
+            escrow.assigned_verifier = None;
+            escrow.exit(&crate::ID)?;
+            oracle.active_assignments = oracle.active_assignments.saturating_sub(1);
+            released_assignments = released_assignments.saturating_add(1);
+        }
+        parser.finish()?;
+        require!(
+            oracle.active_assignments == 0,
+            EscrowError::VerifierHasAssignments
+        );
+
+        oracle.active = false;
+        oracle.paused_until = Some(pause_until);
+
+        msg!("Verifier paused until {}: {}", pause_until, oracle.verifier);
+
+        emit!(VerifierPaused {
+            version: EVENT_VERSION,
+            verifier: oracle.verifier,
+            namespace: oracle.namespace,
+            reason_hash,
+            pause_until,
+            released_assignments,
+        });
+
+        Ok(())
+    }
+
+    /// Return a paused verifier to rotation once its pause has ended
+    pub fn resume_verifier(ctx: Context<ResumeVerifier>) -> Result<()> {
+        let clock = Clock::get()?;
+        let oracle = &mut ctx.accounts.verifier_oracle;
+
+        let until = oracle.paused_until.ok_or(EscrowError::VerifierNotPaused)?;
+        require!(
+            clock.unix_timestamp >= until,
+            EscrowError::VerifierPauseActive
+        );
+
+        oracle.active = true;
+        oracle.paused_until = None;
+
+        msg!("Verifier resumed: {}", oracle.verifier);
+
+        emit!(VerifierResumed {
+            version: EVENT_VERSION,
+            verifier: oracle.verifier,
+            namespace: oracle.namespace,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create the protocol-wide insurance fund (authority only)
+    pub fn initialize_insurance_fund(ctx: Context<InitializeInsuranceFund>) -> Result<()> {
+        let fund = &mut ctx.accounts.insurance_fund;
+        fund.balance = 0;
+        fund.total_contributed = 0;
+        fund.total_paid = 0;
+        fund.bump = ctx.bumps.insurance_fund;
+
+        msg!("Insurance fund initialized");
+
+        Ok(())
+    }
+
+    /// File an insurance claim for an escrow settled by `settle_stale_dispute`
+    ///
+    /// One claim per escrow, filed by its agent. Governance reviews the
+    /// evidence off-chain before approving a payout.
+    ///
+    /// # Arguments
+    /// * `escrow` - Escrow the claim is for
+    /// * `claim_evidence_hash` - SHA-256 of the off-chain evidence bundle
+    pub fn file_insurance_claim(
+        ctx: Context<FileInsuranceClaim>,
+        escrow: Pubkey,
+        claim_evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow;
+        require_keys_eq!(
+            escrow_account.key(),
+            escrow,
+            EscrowError::InsuranceClaimNotEligible
+        );
+        require!(
+            escrow_account.settled_stale(),
+            EscrowError::InsuranceClaimNotEligible
+        );
+
+        let clock = Clock::get()?;
+        let claim = &mut ctx.accounts.claim;
+        claim.escrow = escrow;
+        claim.claimant = ctx.accounts.claimant.key();
+        claim.escrow_amount = escrow_account.amount;
+        claim.claim_evidence_hash = claim_evidence_hash;
+        claim.filed_at = clock.unix_timestamp;
+        claim.status = InsuranceClaimStatus::Pending;
+        claim.payout_lamports = 0;
+        claim.bump = ctx.bumps.claim;
+
+        msg!("Insurance claim filed for escrow {}", escrow);
+
+        emit!(InsuranceClaimFiled {
+            version: EVENT_VERSION,
+            claim: claim.key(),
+            escrow,
+            claimant: claim.claimant,
+            claim_evidence_hash,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a pending insurance claim and pay it from the fund (authority only)
+    ///
+    /// The payout is capped at `max_insurance_payout_bps` of the escrowed amount.
+    /// Paid claims are kept so the escrow cannot be claimed again.
+    ///
+    /// # Arguments
+    /// * `claim_pda` - Claim being approved
+    /// * `payout_lamports` - Amount paid to the claimant
+    pub fn approve_insurance_claim(
+        ctx: Context<ApproveInsuranceClaim>,
+        claim_pda: Pubkey,
+        payout_lamports: u64,
+    ) -> Result<()> {
+        let claim = &mut ctx.accounts.claim;
+        require_keys_eq!(claim.key(), claim_pda, EscrowError::InsuranceClaimMismatch);
+        require!(
+            claim.status == InsuranceClaimStatus::Pending,
+            EscrowError::InsuranceClaimAlreadyPaid
+        );
+        require!(payout_lamports > 0, EscrowError::InvalidAmount);
+
+        let cap = bps_of(claim.escrow_amount, ctx.accounts.config.max_insurance_payout_bps)?;
+        require!(
+            payout_lamports <= cap,
+            EscrowError::InsurancePayoutExceedsCap
+        );
+
+        // `balance` only counts contributions, so the fund's rent is never touched
+        let fund = &mut ctx.accounts.insurance_fund;
+        fund.balance = fund
+            .balance
+            .checked_sub(payout_lamports)
+            .ok_or(EscrowError::InsufficientInsuranceFunds)?;
+        fund.total_paid = fund.total_paid.saturating_add(payout_lamports);
+
+        **fund.to_account_info().try_borrow_mut_lamports()? -= payout_lamports;
+        **ctx.accounts.claimant.to_account_info().try_borrow_mut_lamports()? += payout_lamports;
+
+        claim.status = InsuranceClaimStatus::Paid;
+        claim.payout_lamports = payout_lamports;
+
+        msg!("Insurance claim paid: {} lamports", payout_lamports);
+
+        emit!(InsuranceClaimPaid {
+            version: EVENT_VERSION,
+            claim: claim_pda,
+            escrow: claim.escrow,
+            claimant: claim.claimant,
+            amount: payout_lamports,
+            fund_balance: fund.balance,
+        });
+
+        Ok(())
+    }
+
+    /// Claim insurance for a resolved escrow whose provider was suspended
+    /// mid-engagement (agent only)
+    ///
+    /// The suspension must have started after the escrow was created and no
+    /// later than its resolution, and still be in force. The on-chain record
+    /// is the evidence, so the fund pays at once: whatever the resolution did
+    /// not refund, up to `max_insurance_payout_bps` of the escrowed amount.
+    /// Uses the same claim account as `file_insurance_claim`, so an escrow
+    /// is compensated once.
+    pub fn claim_insurance(ctx: Context<ClaimInsurance>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let payout_lamports = suspension_claim_payout(
+            escrow,
+            &ctx.accounts.provider_penalties,
+            ctx.accounts.config.max_insurance_payout_bps,
+        )?;
+
+        let fund = &mut ctx.accounts.insurance_fund;
+        fund.balance = fund
+            .balance
+            .checked_sub(payout_lamports)
+            .ok_or(EscrowError::InsufficientInsuranceFunds)?;
+        fund.total_paid = fund.total_paid.saturating_add(payout_lamports);
+
+        **fund.to_account_info().try_borrow_mut_lamports()? -= payout_lamports;
+        **ctx.accounts.claimant.to_account_info().try_borrow_mut_lamports()? += payout_lamports;
+
+        let now = Clock::get()?.unix_timestamp;
+        let claim = &mut ctx.accounts.claim;
+        claim.escrow = escrow.key();
+        claim.claimant = ctx.accounts.claimant.key();
+        claim.escrow_amount = escrow.amount;
+        claim.claim_evidence_hash = [0; 32];
+        claim.filed_at = now;
+        claim.status = InsuranceClaimStatus::Paid;
+        claim.payout_lamports = payout_lamports;
+        claim.bump = ctx.bumps.claim;
+
+        msg!("Insurance claim paid: {} lamports", payout_lamports);
+
+        emit!(InsuranceClaimPaid {
+            version: EVENT_VERSION,
+            claim: claim.key(),
+            escrow: claim.escrow,
+            claimant: claim.claimant,
+            amount: payout_lamports,
+            fund_balance: fund.balance,
+        });
+
+        Ok(())
+    }
+
+    /// Post (or top up) a provider's service-level bond
+    ///
+    /// Terms are fixed while lamports are bonded; withdraw the whole bond to
+    /// change them. Whatever the terms, a covered escrow that resolves with a
+    /// refund of `SEVERE_REFUND_PERCENTAGE` or more slashes the namespace's
+    /// `severe_refund_slash_bps` of the bond.
+    ///
+    /// # Arguments
+    /// * `amount` - Lamports to add to the bond
+    /// * `terms_hash` - Hash of the off-chain terms document
+    /// * `terms` - Slashing terms enforced on dispute resolution
+    pub fn post_service_bond(
+        ctx: Context<PostServiceBond>,
+        amount: u64,
+        terms_hash: [u8; 32],
+        terms: ServiceBondTerms,
+    ) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+        require!(
+            terms.quality_threshold <= 100 && terms.slash_bps <= MAX_BPS,
+            EscrowError::InvalidServiceBondTerms
+        );
+
+        let bond = &mut ctx.accounts.service_bond;
+        require!(
+            bond.withdrawal_requested_at.is_none(),
+            EscrowError::ServiceBondWithdrawalPending
+        );
+        if bond.bonded_lamports == 0 {
+            // Escrows covered by an earlier, drained bond are not carried over
+            bond.provider = ctx.accounts.provider.key();
+            bond.terms_hash = terms_hash;
+            bond.terms = terms;
+            bond.posted_at = Clock::get()?.unix_timestamp;
+            bond.bump = ctx.bumps.service_bond;
+        } else {
+            require!(
+                bond.terms_hash == terms_hash && bond.terms == terms,
+                EscrowError::ServiceBondTermsMismatch
+            );
+        }
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.provider.to_account_info(),
+                to: ctx.accounts.service_bond.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+        let bond = &mut ctx.accounts.service_bond;
+        bond.bonded_lamports = bond
+            .bonded_lamports
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        msg!("Service bond posted: {} lamports", bond.bonded_lamports);
+
+        emit!(ServiceBondPosted {
+            version: EVENT_VERSION,
+            provider: bond.provider,
+            amount,
+            bonded_lamports: bond.bonded_lamports,
+            terms_hash: bond.terms_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Start the cooldown for withdrawing part or all of a service bond
+    ///
+    /// The bond stops covering new escrows immediately but stays slashable
+    /// for escrows created before the request until the withdrawal executes.
+    ///
+    /// # Arguments
+    /// * `amount` - Lamports to withdraw once the cooldown has elapsed
+    pub fn request_service_bond_withdrawal(
+        ctx: Context<ManageServiceBond>,
+        amount: u64,
+    ) -> Result<()> {
+        let bond = &mut ctx.accounts.service_bond;
+        require!(
+            amount > 0 && amount <= bond.bonded_lamports,
+            EscrowError::InvalidAmount
+        );
+        require!(
+            bond.withdrawal_requested_at.is_none(),
+            EscrowError::ServiceBondWithdrawalPending
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        bond.withdrawal_requested_at = Some(now);
+        bond.pending_withdrawal = amount;
+
+        msg!("Service bond withdrawal requested: {} lamports", amount);
+
+        emit!(ServiceBondWithdrawalRequested {
+            version: EVENT_VERSION,
+            provider: bond.provider,
+            amount,
+            available_at: now + SERVICE_BOND_WITHDRAWAL_COOLDOWN,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw a requested amount once the cooldown has elapsed
+    ///
+    /// Slashes during the cooldown come out of the pending amount first.
+    pub fn withdraw_service_bond(ctx: Context<ManageServiceBond>) -> Result<()> {
+        let bond = &mut ctx.accounts.service_bond;
+        let requested_at = bond
+            .withdrawal_requested_at
+            .ok_or(EscrowError::NoServiceBondWithdrawal)?;
+        require!(
+            Clock::get()?.unix_timestamp >= requested_at + SERVICE_BOND_WITHDRAWAL_COOLDOWN,
+            EscrowError::ServiceBondCooldownActive
+        );
+
+        let amount = bond.pending_withdrawal.min(bond.bonded_lamports);
+        bond.bonded_lamports -= amount;
+        bond.pending_withdrawal = 0;
+        bond.withdrawal_requested_at = None;
+
+        // `bonded_lamports` sits on top of rent, so the account stays rent-exempt
+        let bond_info = bond.to_account_info();
+        require_rent_exempt_after(&bond_info, amount)?;
+        **bond_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.provider.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        msg!("Service bond withdrawn: {} lamports", amount);
+
+        emit!(ServiceBondWithdrawn {
+            version: EVENT_VERSION,
+            provider: bond.provider,
+            amount,
+            bonded_lamports: bond.bonded_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize an escrow holding SPL tokens (e.g. USDC) instead of SOL
+    ///
+    /// Tokens are locked in a vault owned by the escrow PDA. SPL escrows
+    /// settle through `release_funds_spl` and `resolve_dispute_spl`; the
+    /// SOL-denominated insurance contribution and exposure tracking do not
+    /// apply to them.
+    ///
+    /// Token-2022 mints are accepted. The escrow holds what reached the vault
+    /// after any transfer fee, and a hooked mint's extra accounts go in
+    /// `remaining_accounts` here and on every instruction that pays out.
+    ///
+    /// # Arguments
+    /// * `amount` - Amount to escrow, in the mint's base units
+    /// * `time_lock` - Duration before auto-release (seconds)
+    /// * `transaction_id` - Unique transaction identifier
+    /// * `namespace` - Platform whose config governs the escrow (`DEFAULT_NAMESPACE` for none)
+    pub fn initialize_escrow_spl<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitializeEscrowSpl<'info>>,
+        amount: u64,
+        time_lock: i64,
+        transaction_id: String,
+        namespace: Pubkey,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        require!(amount > 0, EscrowError::InvalidAmount);
+        require!(
+            time_lock >= MIN_TIME_LOCK && time_lock <= MAX_TIME_LOCK,
+            EscrowError::InvalidTimeLock
+        );
+        require!(
+            !transaction_id.is_empty() && transaction_id.len() <= 64,
+            EscrowError::InvalidTransactionId
+        );
+
+        check_blocklists(
+            &ctx.accounts.agent_blocklist,
+            &ctx.accounts.api_blocklist,
+            &ctx.accounts.agent.key(),
+            &ctx.accounts.api.key(),
+        )?;
+        check_allowlist(
+            &ctx.accounts.agent_restriction,
+            ctx.accounts.allowlist.as_deref(),
+            &ctx.accounts.agent.key(),
+            &ctx.accounts.api.key(),
+        )?;
+        if let Some(penalties) =
+            load_provider_penalties(&ctx.accounts.api_penalties, &ctx.accounts.api.key())?
+        {
+            require!(!penalties.suspended, EscrowError::ProviderSuspended);
+        }
+        charge_rate_limit(
+            &ctx.accounts.agent_rate_limiter,
+            &ctx.accounts.agent.key(),
+            RateLimitedAction::Transaction,
+        )?;
+
+        check_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+
+        let mint = ctx.accounts.mint.key();
+        {
+            let escrow = &mut ctx.accounts.escrow;
+            init_escrow_state(
+                escrow,
+                ctx.accounts.agent.key(),
+                ctx.accounts.api.key(),
+                namespace,
+                amount,
+                time_lock,
+                transaction_id.clone(),
+            )?;
+            escrow.bump = ctx.bumps.escrow;
+            escrow.mint = Some(mint);
+        }
+
+        charge_spam_deposit(
+            ctx.accounts.spam_vault.as_mut(),
+            ctx.bumps.spam_vault,
+            &mut ctx.accounts.escrow,
+            ctx.accounts.agent.key(),
+            &ctx.accounts.agent,
+            &ctx.accounts.system_program,
+            ctx.accounts.config.spam_protection_deposit,
+        )?;
+
+        TokenTransfer {
+            token_program: &ctx.accounts.token_program.to_account_info(),
+            from: ctx.accounts.agent_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_vault.to_account_info(),
+            authority: ctx.accounts.agent.to_account_info(),
+            hook_accounts: ctx.remaining_accounts,
+        }
+        .invoke(amount, ctx.accounts.mint.decimals, &[])?;
+
+        // A transfer fee is withheld from the deposit; the escrow holds the rest
+        ctx.accounts.escrow_vault.reload()?;
+        let received = ctx.accounts.escrow_vault.amount;
+        require!(received > 0, EscrowError::InvalidAmount);
+        ctx.accounts.escrow.amount = received;
+
+        // Stablecoin volume feeds the currency-neutral reputation score of
+        // every supplied reputation
+        if mint == ctx.accounts.config.usd_stablecoin_mint {
+            let cents = stablecoin_to_usd_cents(received, ctx.accounts.mint.decimals);
+            for reputation in [
+                ctx.accounts.agent_reputation.as_deref_mut(),
+                ctx.accounts.api_reputation.as_deref_mut(),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                reputation.volume_usd_cents = reputation.volume_usd_cents.saturating_add(cents);
+            }
+        }
+
+        let escrow = &ctx.accounts.escrow;
+        msg!("SPL escrow initialized: {} tokens of {} locked", received, mint);
+
+        emit!(EscrowInitialized {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            agent: escrow.agent,
+            api: escrow.api,
+            amount: received,
+            expires_at: escrow.expires_at,
+            transaction_id,
+            service_bond: 0,
+            payment_reference: None,
+            mint: Some(mint),
+            metadata_uri: None,
+            stale_dispute_terms: None,
+        });
+
+        Ok(())
+    }
+
+    /// Release an SPL escrow's tokens to the API (happy path)
+    ///
+    /// Same rules as `release_funds`: the agent may release at any time,
+    /// anyone else once the time lock has expired.
+    pub fn release_funds_spl<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseFundsSpl<'info>>,
+    ) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        if ctx.accounts.agent.key() != escrow.agent {
+            require_auto_releasable(escrow, clock.unix_timestamp)?;
+        }
+
+        let transfer_amount = escrow.amount;
+        let vault = EscrowVault {
+            vault: &ctx.accounts.escrow_vault,
+            mint: &ctx.accounts.mint,
+            token_program: &ctx.accounts.token_program,
+            hook_accounts: ctx.remaining_accounts,
+        };
+        pay_api_with_fee_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.api_token_account.to_account_info(),
+            ctx.accounts.treasury_token_account.as_ref().map(|t| t.to_account_info()),
+            &ctx.accounts.config,
+            transfer_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_status(EscrowStatus::Released);
+        escrow.settled_at = Some(Clock::get()?.unix_timestamp);
+
+        msg!("Tokens released to API: {}", transfer_amount);
+
+        emit!(FundsReleased {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            amount: transfer_amount,
+            api: escrow.api,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve an SPL escrow's dispute with a verifier oracle signature
+    ///
+    /// Verification is identical to `resolve_dispute`, and like it this path
+    /// is closed to namespaces with an appeal window. Reputation counts the
+    /// outcome but not the token amounts, which are not lamports.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `content_hash` - SHA-256 of the payload the verifier assessed
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier
+    pub fn resolve_dispute_spl<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolveDisputeSpl<'info>>,
+        quality_score: u8,
+        content_hash: [u8; 32],
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+        require_single_step_resolution(&ctx.accounts.config)?;
+
+        let escrow = &ctx.accounts.escrow;
+
+        require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+        require!(
+            escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+        if let Some(assigned) = escrow.assigned_verifier {
+            require_keys_eq!(
+                ctx.accounts.verifier.key(),
+                assigned,
+                EscrowError::VerifierNotAssigned
+            );
+        }
+        if let Some(response_hash) = escrow.response_hash {
+            require!(
+                response_hash == content_hash,
+                EscrowError::AssessedWrongContent
+            );
+        }
+
+        let work_agreement = ctx.accounts.work_agreement.as_deref();
+        let quality_score = resolution_quality(work_agreement, quality_score, None)?;
+        let refund_percentage =
+            agreed_refund_percentage(&ctx.accounts.config, escrow, work_agreement, quality_score)?;
+
+        require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+        let message = build_resolution_message(
+            &escrow.key(),
+            escrow,
+            nonce,
+            work_agreement,
+            quality_score,
+            &content_hash,
+            None,
+        );
+        verify_ed25519_signature(
+            &ctx.accounts.instructions_sysvar,
+            &signature,
+            &mut ctx.accounts.verifier_nonces,
+            nonce,
+            message.as_bytes(),
+            0,
+            ctx.accounts.config.strict_sig_adjacency,
+        )?;
+
+        let vault = EscrowVault {
+            vault: &ctx.accounts.escrow_vault,
+            mint: &ctx.accounts.mint,
+            token_program: &ctx.accounts.token_program,
+            hook_accounts: ctx.remaining_accounts,
+        };
+        pay_oracle_fee_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.verifier.key(),
+            ctx.accounts.verifier_token_account.as_ref().map(|t| t.to_account_info()),
+            &ctx.accounts.config,
+        )?;
+        let (refund_amount, payment_amount) =
+            split_escrow_amount(ctx.accounts.escrow.open_amount(), refund_percentage)?;
+        disburse_priority_fee(&ctx.accounts.escrow, &ctx.accounts.verifier)?;
+        settle_dispute_bond(
+            &ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.config,
+            dispute_bond_returned(refund_percentage),
+        )?;
+
+        refund_agent_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.agent_token_account.to_account_info(),
+            refund_amount,
+        )?;
+        pay_api_with_fee_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.api_token_account.to_account_info(),
+            ctx.accounts.treasury_token_account.as_ref().map(|t| t.to_account_info()),
+            &ctx.accounts.config,
+            payment_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_status(EscrowStatus::Resolved);
+        escrow.settled_at = Some(Clock::get()?.unix_timestamp);
+        escrow.quality_score = Some(quality_score);
+        escrow.refund_percentage = Some(refund_percentage);
+        escrow.assessed_content_hash = Some(content_hash);
+
+        // Reputation is best-effort: corrupt analytics state never blocks settlement
+        let escrow_key = escrow.key();
+        let agent_info = ctx.accounts.agent_reputation.to_account_info();
+        let api_info = ctx.accounts.api_reputation.to_account_info();
+        let mut agent_reputation = load_reputation(&agent_info, &escrow.agent, escrow_key);
+        let mut api_reputation = load_reputation(&api_info, &escrow.api, escrow_key);
+
+        release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+        release_assignment(escrow, ctx.accounts.verifier_oracle.as_deref_mut())?;
+
+        let clock = Clock::get()?;
+        apply_dispute_reputation(
+            agent_reputation.as_mut(),
+            api_reputation.as_mut(),
+            escrow,
+            quality_score,
+            refund_percentage,
+            0,
+            0,
+            clock.unix_timestamp,
+        );
+        store_reputation(&agent_info, agent_reputation.as_ref())?;
+        store_reputation(&api_info, api_reputation.as_ref())?;
+        record_score_snapshot(
+            ctx.accounts.agent_reputation_history.as_deref_mut(),
+            agent_reputation.as_ref(),
+            clock.unix_timestamp,
+        );
+        record_score_snapshot(
+            ctx.accounts.api_reputation_history.as_deref_mut(),
+            api_reputation.as_ref(),
+            clock.unix_timestamp,
+        );
+
+        msg!("SPL dispute resolved!");
+
+        emit!(DisputeResolved {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            quality_score,
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            verifier: ctx.accounts.verifier.key(),
+            content_hash: Some(content_hash),
+            agent: escrow.agent,
+            api: escrow.api,
+            method: ResolutionMethod::Ed25519,
+            dispute_duration: escrow.dispute_duration(clock.unix_timestamp),
+        });
+
+        Ok(())
+    }
+
+    /// Settle an SPL escrow's dispute no verifier resolved (permissionless)
+    ///
+    /// The token counterpart of `settle_stale_dispute`, with the same
+    /// timing and split, so a disputed SPL escrow is never stranded once its
+    /// time lock runs out. The priority fee and dispute bond, held in
+    /// lamports, go back to the agent.
+    pub fn settle_stale_dispute_spl<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettleStaleDisputeSpl<'info>>,
+    ) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require_stale_dispute(escrow, clock.unix_timestamp)?;
+
+        let refund_percentage = escrow.stale_refund_percentage();
+        let (refund_amount, payment_amount) =
+            split_escrow_amount(escrow.open_amount(), refund_percentage)?;
+        disburse_priority_fee(&ctx.accounts.escrow, &ctx.accounts.agent.to_account_info())?;
+        settle_dispute_bond(
+            &ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.config,
+            true,
+        )?;
+
+        let vault = EscrowVault {
+            vault: &ctx.accounts.escrow_vault,
+            mint: &ctx.accounts.mint,
+            token_program: &ctx.accounts.token_program,
+            hook_accounts: ctx.remaining_accounts,
+        };
+        refund_agent_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.agent_token_account.to_account_info(),
+            refund_amount,
+        )?;
+        pay_api_with_fee_spl(
+            &mut ctx.accounts.escrow,
+            &vault,
+            ctx.accounts.api_token_account.to_account_info(),
+            ctx.accounts.treasury_token_account.as_ref().map(|t| t.to_account_info()),
+            &ctx.accounts.config,
+            payment_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_status(EscrowStatus::Resolved);
+        escrow.settled_at = Some(clock.unix_timestamp);
+        escrow.refund_percentage = Some(refund_percentage);
+        escrow.set_settled_stale(true);
+
+        let escrow_key = escrow.key();
+        let agent_info = ctx.accounts.agent_reputation.to_account_info();
+        let api_info = ctx.accounts.api_reputation.to_account_info();
+        let mut agent_reputation = load_reputation(&agent_info, &escrow.agent, escrow_key);
+        let mut api_reputation = load_reputation(&api_info, &escrow.api, escrow_key);
+
+        release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+        release_assignment(escrow, ctx.accounts.verifier_oracle.as_deref_mut())?;
+        store_reputation(&agent_info, agent_reputation.as_ref())?;
+        store_reputation(&api_info, api_reputation.as_ref())?;
+
+        msg!("Stale SPL dispute settled at {}% refund", refund_percentage);
+
+        emit!(StaleDisputeSettled {
+            version: EVENT_VERSION,
+            escrow: escrow_key,
+            transaction_id: escrow.transaction_id.clone(),
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Publish standard escrow terms for agents to open escrows on (API only)
+    ///
+    /// An API may keep several sets side by side under different `terms_id`s.
+    /// Published terms are fixed; retire them and publish again to change
+    /// them. Escrows already opened keep the terms they copied.
+    ///
+    /// # Arguments
+    /// * `terms_id` - Distinguishes this set among the API's published terms
+    /// * `namespace` - Namespace escrows on these terms must belong to
+    /// * `min_amount` / `max_amount` - Range of amounts accepted (lamports)
+    /// * `time_lock` - Time lock escrows must take (seconds)
+    /// * `dispute_window` - Seconds disputes are accepted, no longer than the
+    ///   time lock or `DISPUTE_WINDOW`, whichever is greater
+    /// * `refund_curve` - Refund by quality score, in place of the namespace's
+    #[allow(clippy::too_many_arguments)]
+    pub fn publish_provider_terms(
+        ctx: Context<PublishProviderTerms>,
+        terms_id: u8,
+        namespace: Pubkey,
+        min_amount: u64,
+        max_amount: u64,
+        time_lock: i64,
+        dispute_window: i64,
+        refund_curve: [RefundStep; REFUND_CURVE_STEPS],
+    ) -> Result<()> {
+        require!(
+            MIN_ESCROW_AMOUNT <= min_amount
+                && min_amount <= max_amount
+                && max_amount <= MAX_ESCROW_AMOUNT,
+            EscrowError::InvalidProviderTerms
+        );
+        require!(
+            (MIN_TIME_LOCK..=MAX_TIME_LOCK).contains(&time_lock)
+                && dispute_window > 0
+                && dispute_window <= time_lock.max(DISPUTE_WINDOW),
+            EscrowError::InvalidProviderTerms
+        );
+        validate_refund_curve(&refund_curve)?;
+
+        let terms = &mut ctx.accounts.provider_terms;
+        terms.api = ctx.accounts.api.key();
+        terms.namespace = namespace;
+        terms.terms_id = terms_id;
+        terms.min_amount = min_amount;
+        terms.max_amount = max_amount;
+        terms.time_lock = time_lock;
+        terms.dispute_window = dispute_window;
+        terms.refund_curve = refund_curve;
+        terms.published_at = Clock::get()?.unix_timestamp;
+        terms.bump = ctx.bumps.provider_terms;
+
+        msg!("Provider terms {} published", terms_id);
+
+        emit!(ProviderTermsPublished {
+            version: EVENT_VERSION,
+            api: terms.api,
+            provider_terms: terms.key(),
+            terms_id,
+            namespace,
+            min_amount,
+            max_amount,
+            time_lock,
+            dispute_window,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw published terms and reclaim their rent (API only)
+    pub fn retire_provider_terms(ctx: Context<RetireProviderTerms>) -> Result<()> {
+        let terms = &ctx.accounts.provider_terms;
+
+        msg!("Provider terms {} retired", terms.terms_id);
+
+        emit!(ProviderTermsRetired {
+            version: EVENT_VERSION,
+            api: terms.api,
+            provider_terms: terms.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Bind a work agreement to an escrow
+    ///
+    /// Once bound, verifier resolutions must sign over the agreed terms, and a
+    /// quality score under `min_quality_score` only settles as a full refund.
+    /// The agent must bind the agreement before the API acknowledges delivery.
+    ///
+    /// # Arguments
+    /// * `query` - Scope of the request (max 128 bytes)
+    /// * `required_fields` - Fields the response must carry
+    /// * `min_records` - Minimum number of records in the response
+    /// * `max_age_days` - Maximum age of the returned data
+    /// * `min_quality_score` - Quality score the response must reach (0-100)
+    /// * `refund_curve` - Refund by quality score for this engagement, in
+    ///   place of the namespace's; not allowed on escrows opened from the
+    ///   API's published terms, which fix their own
+    /// * `score_weights` - Percentage weight of each score dimension, e.g.
+    ///   latency, accuracy and completeness, summing to 100; all zero for a
+    ///   single score. Weighted agreements resolve through
+    ///   `resolve_dispute_scored` only.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_work_agreement(
+        ctx: Context<CreateWorkAgreement>,
+        query: String,
+        required_fields: u8,
+        min_records: u32,
+        max_age_days: u32,
+        min_quality_score: u8,
+        refund_curve: Option<[RefundStep; REFUND_CURVE_STEPS]>,
+        score_weights: [u8; SCORE_DIMENSIONS],
+    ) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            escrow.response_hash.is_none(),
+            EscrowError::WorkAgreementTooLate
+        );
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+        require!(
+            query.len() <= 128 && min_quality_score <= 100,
+            EscrowError::InvalidWorkAgreement
+        );
+        if let Some(curve) = &refund_curve {
+            require!(escrow.refund_curve.is_none(), EscrowError::InvalidWorkAgreement);
+            validate_refund_curve(curve)?;
+        }
+        let total_weight: u16 = score_weights.iter().map(|&weight| u16::from(weight)).sum();
+        require!(
+            total_weight == 0 || total_weight == 100,
+            EscrowError::InvalidWorkAgreement
+        );
+
+        escrow.set_has_work_agreement(true);
+
+        let agreement = &mut ctx.accounts.work_agreement;
+        agreement.escrow = escrow.key();
+        agreement.query = query;
+        agreement.required_fields = required_fields;
+        agreement.min_records = min_records;
+        agreement.max_age_days = max_age_days;
+        agreement.min_quality_score = min_quality_score;
+        agreement.refund_curve = refund_curve;
+        agreement.score_weights = score_weights;
+        agreement.created_at = Clock::get()?.unix_timestamp;
+        agreement.bump = ctx.bumps.work_agreement;
+
+        msg!("Work agreement bound to escrow");
+
+        emit!(WorkAgreementCreated {
+            version: EVENT_VERSION,
+            escrow: agreement.escrow,
+            work_agreement: agreement.key(),
+            min_records,
+            max_age_days,
+            min_quality_score,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel an escrow before the API delivers and close it
+    ///
+    /// The agent gets the escrowed amount and the rent back. A spam
+    /// protection deposit stays in the vault for `SPAM_DEPOSIT_RETENTION`,
+    /// since the API never accepted the work, and
+    /// `refund_retained_spam_deposit` returns it afterwards. Without the
+    /// API's co-signature the namespace's cancel grace period must have
+    /// passed since creation; a zero grace period only allows co-signed
+    /// cancellations.
+    pub fn cancel_escrow(ctx: Context<CancelEscrow>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            escrow.response_hash.is_none(),
+            EscrowError::DeliveryAlreadyAcknowledged
+        );
+        require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+
+        let api_consented = ctx.accounts.api.is_some();
+        if !api_consented {
+            let grace_period = ctx.accounts.config.cancel_grace_period_seconds;
+            let grace_ends = escrow
+                .created_at
+                .checked_add(grace_period)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            require!(
+                grace_period > 0 && clock.unix_timestamp >= grace_ends,
+                EscrowError::CancelGracePeriodActive
+            );
+        }
+
+        let refund_amount = escrow.open_amount();
+        refund_agent(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            refund_amount,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+        let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+        release_exposure_accounts(escrow, agent_info.as_ref(), api_info.as_ref())?;
+
+        // The account is closed, so the vault holds the deposit from here on
+        if escrow.spam_deposit > 0 {
+            let vault = ctx
+                .accounts
+                .spam_vault
+                .as_mut()
+                .ok_or(EscrowError::SpamVaultMissing)?;
+            retain_spam_deposit(escrow, vault, clock.unix_timestamp)?;
+        }
+
+        msg!("Escrow cancelled: {} SOL refunded", refund_amount as f64 / 1_000_000_000.0);
+
+        emit!(EscrowCancelled {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            agent: escrow.agent,
+            amount: refund_amount,
+            api_consented,
+            timestamp: clock.unix_timestamp,
+        });
+
+        emit!(EscrowClosed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            agent: escrow.agent,
+            rent_returned: ctx.accounts.escrow.to_account_info().lamports(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Mint a compressed NFT receipt of a released or resolved escrow to its agent
+    ///
+    /// Permissionless and once per escrow; the caller pays. The Bubblegum
+    /// tree must have the program's `receipt_authority` PDA as its delegate.
+    /// See `receipts` for what the receipt records.
+    pub fn mint_escrow_receipt(ctx: Context<MintEscrowReceipt>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Released || escrow.status() == EscrowStatus::Resolved,
+            EscrowError::InvalidStatus
+        );
+        require!(!escrow.receipt_minted(), EscrowError::ReceiptAlreadyMinted);
+
+        let accounts = ReceiptAccounts {
+            tree_config: &ctx.accounts.tree_config,
+            leaf_owner: &ctx.accounts.agent,
+            merkle_tree: &ctx.accounts.merkle_tree,
+            payer: &ctx.accounts.payer,
+            receipt_authority: &ctx.accounts.receipt_authority,
+            log_wrapper: &ctx.accounts.log_wrapper,
+            compression_program: &ctx.accounts.compression_program,
+            system_program: &ctx.accounts.system_program,
+            bubblegum_program: &ctx.accounts.bubblegum_program,
+        };
+        let bump = [ctx.bumps.receipt_authority];
+        mint_receipt(&accounts, escrow, &[b"receipt_authority", &bump])?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_receipt_minted(true);
+
+        msg!("Receipt minted for {}", escrow.transaction_id);
+
+        emit!(ReceiptMinted {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            agent: escrow.agent,
+            merkle_tree: ctx.accounts.merkle_tree.key(),
+            uri: receipts::receipt_uri(escrow),
+        });
+
+        Ok(())
+    }
+
+    /// Post a resolved escrow's outcome to Wormhole for other chains
+    ///
+    /// Permissionless and once per escrow; the caller pays the rent and the
+    /// bridge fee. Clients add it to the transaction that resolves the
+    /// dispute so every `DisputeResolved` has a VAA behind it. See `wormhole`
+    /// for the payload.
+    pub fn publish_settlement(ctx: Context<PublishSettlement>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Resolved,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            ctx.accounts.wormhole_message.data_is_empty(),
+            EscrowError::SettlementAlreadyPublished
+        );
+
+        let accounts = WormholeAccounts {
+            bridge: &ctx.accounts.wormhole_bridge,
+            message: &ctx.accounts.wormhole_message,
+            emitter: &ctx.accounts.wormhole_emitter,
+            sequence: &ctx.accounts.wormhole_sequence,
+            payer: &ctx.accounts.payer,
+            fee_collector: &ctx.accounts.wormhole_fee_collector,
+            clock: &ctx.accounts.clock,
+            rent: &ctx.accounts.rent,
+            system_program: &ctx.accounts.system_program,
+            wormhole_program: &ctx.accounts.wormhole_program,
+        };
+        let sequence = wormhole::next_sequence(&ctx.accounts.wormhole_sequence)?;
+        let escrow_key = escrow.key();
+        let message_bump = [ctx.bumps.wormhole_message];
+        let emitter_bump = [ctx.bumps.wormhole_emitter];
+        wormhole::post_message(
+            &accounts,
+            &wormhole::settlement_payload(&escrow_key, escrow),
+            &[b"wormhole_message", escrow_key.as_ref(), &message_bump],
+            &[b"wormhole_emitter", &emitter_bump],
+        )?;
+
+        msg!("Settlement of {} published, sequence {}", escrow.transaction_id, sequence);
+
+        emit!(SettlementPublished {
+            version: EVENT_VERSION,
+            escrow: escrow_key,
+            message: ctx.accounts.wormhole_message.key(),
+            sequence,
+        });
+
+        Ok(())
+    }
+
+    /// Append an escrow to its agent's and its API's escrow index
+    ///
+    /// Permissionless and once per escrow; the caller pays for the entries,
+    /// so clients usually add it to the transaction that opens the escrow.
+    /// Entry `n` of an agent lives at `[b"agent_escrow", agent, n]` and of an
+    /// API at `[b"api_escrow", api, n]`, with `n` below the index's `count`,
+    /// so a party's escrows can be derived and paged without scanning. An
+    /// entry outlives its escrow once the escrow is closed.
+    pub fn index_escrow(ctx: Context<IndexEscrow>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        require!(!escrow.indexed(), EscrowError::EscrowAlreadyIndexed);
+        escrow.set_indexed(true);
+
+        let agent_index = &mut ctx.accounts.agent_index;
+        agent_index.agent = escrow.agent;
+        agent_index.bump = ctx.bumps.agent_index;
+        let agent_position = agent_index.count;
+        agent_index.count = agent_position.saturating_add(1);
+        ctx.accounts.agent_entry.escrow = escrow.key();
+
+        let api_index = &mut ctx.accounts.api_index;
+        api_index.api = escrow.api;
+        api_index.bump = ctx.bumps.api_index;
+        let api_position = api_index.count;
+        api_index.count = api_position.saturating_add(1);
+        ctx.accounts.api_entry.escrow = escrow.key();
+
+        msg!(
+            "Escrow indexed at agent position {}, API position {}",
+            agent_position,
+            api_position
+        );
+
+        emit!(EscrowIndexed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            agent: escrow.agent,
+            agent_position,
+            api: escrow.api,
+            api_position,
+        });
+
+        Ok(())
+    }
+
+    /// Close a released or resolved escrow and return its rent to the agent
+    ///
+    /// Permissionless once `ESCROW_CLOSE_COOLDOWN` has passed since
+    /// settlement, which leaves time for audits and insurance claims against
+    /// the account. A spam protection deposit still held goes back to the
+    /// agent, and SPL escrows close their emptied token vault as well.
+    pub fn close_escrow(ctx: Context<CloseEscrow>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        let closable_at = escrow.closable_at().ok_or(EscrowError::InvalidStatus)?;
+        require!(
+            clock.unix_timestamp >= closable_at,
+            EscrowError::CloseCooldownActive
+        );
+
+        if escrow.spam_deposit > 0 {
+            let vault = ctx
+                .accounts
+                .spam_vault
+                .as_mut()
+                .ok_or(EscrowError::SpamVaultMissing)?;
+            return_spam_deposit(escrow, vault, &ctx.accounts.agent.to_account_info())?;
+        }
+
+        if escrow.mint.is_some() {
+            let (vault, token_program) = ctx
+                .accounts
+                .escrow_vault
+                .as_ref()
+                .zip(ctx.accounts.token_program.as_ref())
+                .ok_or(EscrowError::EscrowVaultMissing)?;
+            // Token-2022 will not close an account holding withheld transfer fees
+            if tokens::withheld_fees(&vault.to_account_info())? > 0 {
+                let mint = ctx.accounts.mint.as_ref().ok_or(EscrowError::EscrowVaultMissing)?;
+                tokens::harvest_to_mint(
+                    &token_program.to_account_info(),
+                    &mint.to_account_info(),
+                    &vault.to_account_info(),
+                )?;
+            }
+            let seeds: &[&[u8]] = &[b"escrow", escrow.transaction_id.as_bytes(), &[escrow.bump]];
+            token_interface::close_account(CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                CloseAccount {
+                    account: vault.to_account_info(),
+                    destination: ctx.accounts.agent.to_account_info(),
+                    authority: escrow.to_account_info(),
+                },
+                &[seeds],
+            ))?;
+        }
+
+        msg!("Escrow closed");
+
+        emit!(EscrowClosed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            transaction_id: escrow.transaction_id.clone(),
+            agent: escrow.agent,
+            rent_returned: escrow.to_account_info().lamports(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the namespace's M-of-N verifier set (config authority only)
+    ///
+    /// # Arguments
+    /// * `verifiers` - Distinct verifier keys, at most 16
+    /// * `threshold` - Signatures `resolve_dispute_quorum` requires
+    pub fn configure_verifier_set(
+        ctx: Context<ConfigureVerifierSet>,
+        verifiers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_verifier_set(&verifiers, threshold)?;
+
+        let set = &mut ctx.accounts.verifier_set;
+        set.namespace = ctx.accounts.config.namespace;
+        set.verifiers = verifiers;
+        set.threshold = threshold;
+        set.bump = ctx.bumps.verifier_set;
+
+        msg!("Verifier set: {}-of-{}", threshold, set.verifiers.len());
+
+        emit!(VerifierSetConfigured {
+            version: EVENT_VERSION,
+            namespace: set.namespace,
+            verifiers: set.verifiers.clone(),
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve dispute with signatures from a quorum of the verifier set
+    ///
+    /// Every signer signs the same message as `resolve_dispute`. The
+    /// signatures must sit in one Ed25519 instruction, in the order given
+    /// here. The first signer submits the resolution and receives any
+    /// priority fee; an assigned verifier must be among the signers. An
+    /// appealed resolution is decided here, without the verifier that issued
+    /// it; in a namespace with an appeal window that is the only dispute a
+    /// quorum may decide, since anything else would settle unappealable.
+    /// Remaining accounts hold each signer's `UsedNonces` (writable), in
+    /// signature order.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score agreed by the quorum (0-100)
+    /// * `content_hash` - SHA-256 of the response payload the quorum assessed
+    /// * `nonce` - Nonce embedded in the signed message, unused by every signer
+    /// * `signatures` - At least `threshold` signatures from distinct set members
+    pub fn resolve_dispute_quorum<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolveDisputeQuorum<'info>>,
+        quality_score: u8,
+        content_hash: [u8; 32],
+        nonce: u64,
+        signatures: Vec<QuorumSignature>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        let escrow = &ctx.accounts.escrow;
+        require_quorum_resolvable(escrow, &ctx.accounts.config, quality_score)?;
+
+        let signers = quorum_signers(&ctx.accounts.verifier_set, &signatures)?;
+        require_keys_eq!(
+            ctx.accounts.verifier.key(),
+            signers[0],
+            EscrowError::InvalidQuorumSigner
+        );
+        if escrow.appealed() {
+            require!(
+                !signers.iter().any(|signer| escrow.first_verifier == Some(*signer)),
+                EscrowError::AppealedVerifierSigned
+            );
+        } else if let Some(assigned) = escrow.assigned_verifier {
+            require!(signers.contains(&assigned), EscrowError::VerifierNotAssigned);
+        }
+
+        if let Some(response_hash) = escrow.response_hash {
+            require!(
+                response_hash == content_hash,
+                EscrowError::AssessedWrongContent
+            );
+        }
+
+        let work_agreement = ctx.accounts.work_agreement.as_deref();
+        let quality_score = resolution_quality(work_agreement, quality_score, None)?;
+        let refund_percentage =
+            agreed_refund_percentage(&ctx.accounts.config, escrow, work_agreement, quality_score)?;
+
+        let message = build_resolution_message(
+            &escrow.key(),
+            escrow,
+            nonce,
+            work_agreement,
+            quality_score,
+            &content_hash,
+            None,
+        );
+        let mut parser = RemainingAccountsParser::new(ctx.remaining_accounts);
+        for (index, (entry, signer)) in signatures.iter().zip(&signers).enumerate() {
+            let nonces_info = parser.next(
+                &AccountExpectation::any()
+                    .key(used_nonces_address(signer))
+                    .owned_by(crate::ID)
+                    .writable(),
+            )?;
+            let mut signer_nonces: Account<UsedNonces> = Account::try_from(nonces_info)?;
+            verify_ed25519_signature(
+                &ctx.accounts.instructions_sysvar,
+                &entry.signature,
+                &mut signer_nonces,
+                nonce,
+                message.as_bytes(),
+                index as u8,
+                ctx.accounts.config.strict_sig_adjacency,
+            )?;
+            signer_nonces.exit(&crate::ID)?;
+        }
+
+        msg!(
+            "Quorum: {} of {} verifiers",
+            signers.len(),
+            ctx.accounts.verifier_set.verifiers.len()
+        );
+        msg!("Quality Score: {}", quality_score);
+        msg!("Refund: {}%", refund_percentage);
+
+        // The first signer collects the oracle fee, as it does the priority fee
+        pay_oracle_fee(&mut ctx.accounts.escrow, &ctx.accounts.verifier, &ctx.accounts.config)?;
+        let split = settlement_split(
+            &ctx.accounts.escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        apply_resolution(
+            SettlementAccounts {
+                escrow: &mut ctx.accounts.escrow,
+                agent: &ctx.accounts.agent,
+                refund_recipient: ctx.accounts.refund_recipient.as_deref(),
+                api: &ctx.accounts.api,
+                treasury: ctx.accounts.treasury.as_ref(),
+                config: &ctx.accounts.config,
+                priority_fee_recipient: &ctx.accounts.verifier,
+                agent_reputation: &ctx.accounts.agent_reputation,
+                api_reputation: &ctx.accounts.api_reputation,
+                agent_reputation_history: ctx.accounts.agent_reputation_history.as_deref_mut(),
+                api_reputation_history: ctx.accounts.api_reputation_history.as_deref_mut(),
+                verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+                service_bond: ctx.accounts.service_bond.as_mut(),
+                api_penalties: Some(&ctx.accounts.api_penalties),
+            },
+            Resolution {
+                quality_score,
+                refund_percentage,
+                split,
+                content_hash: Some(content_hash),
+                resolver: signers[0],
+                method: ResolutionMethod::Quorum,
+            },
+        )?;
+
+        msg!("Dispute resolved by quorum!");
+
+        emit!(QuorumResolved {
+            version: EVENT_VERSION,
+            escrow: ctx.accounts.escrow.key(),
+            verifier_set: ctx.accounts.verifier_set.key(),
+            signers: signers.clone(),
+            threshold: ctx.accounts.verifier_set.threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Appeal a submitted resolution before its appeal window closes
+    ///
+    /// Callable by the agent or the API. The escrow then settles only through
+    /// `resolve_dispute_quorum` signed without the original verifier, or as a
+    /// stale dispute if no quorum decides it.
+    pub fn appeal_resolution(ctx: Context<AppealResolution>) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+        let appellant = ctx.accounts.appellant.key();
+
+        require!(
+            appellant == escrow.agent || appellant == escrow.api,
+            EscrowError::Unauthorized
+        );
+        require!(escrow.resolution_held(), EscrowError::ResolutionNotHeld);
+        require!(!escrow.appealed(), EscrowError::AlreadyAppealed);
+        require!(escrow.arbitrator.is_none(), EscrowError::AlreadyEscalated);
+        let (Some(appeal_deadline), Some(first_verifier)) =
+            (escrow.appeal_deadline, escrow.first_verifier)
+        else {
+            return err!(EscrowError::ResolutionNotHeld);
+        };
+        require!(
+            Clock::get()?.unix_timestamp < appeal_deadline,
+            EscrowError::AppealWindowClosed
+        );
+
+        escrow.set_appealed(true);
+
+        msg!("Resolution appealed by {}", appellant);
+
+        emit!(ResolutionAppealed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            appellant,
+            first_verifier,
+        });
+
+        Ok(())
+    }
+
+    /// Take a submitted resolution to a registered human arbitrator
+    ///
+    /// Callable by the agent or the API instead of an appeal, before the
+    /// appeal window closes. The escalating party posts `ARBITRATION_BOND`
+    /// and the arbitrator gets `ARBITRATION_PERIOD` to rule; if they never
+    /// do, the oracle's ruling settles and the bond is returned.
+    pub fn escalate_to_arbitrator(ctx: Context<EscalateToArbitrator>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let escalator = ctx.accounts.escalator.key();
+
+        require!(
+            escalator == escrow.agent || escalator == escrow.api,
+            EscrowError::Unauthorized
+        );
+        require!(escrow.resolution_held(), EscrowError::ResolutionNotHeld);
+        require!(!escrow.appealed(), EscrowError::AlreadyAppealed);
+        require!(escrow.arbitrator.is_none(), EscrowError::AlreadyEscalated);
+        let appeal_deadline = escrow
+            .appeal_deadline
+            .ok_or(EscrowError::ResolutionNotHeld)?;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now < appeal_deadline, EscrowError::AppealWindowClosed);
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.escalator.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, ARBITRATION_BOND)?;
+
+        let arbitrator = ctx.accounts.arbitrator.authority;
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.set_escalated(true);
+        escrow.arbitrator = Some(arbitrator);
+        escrow.escalated_by = Some(escalator);
+        escrow.arbitration_bond = ARBITRATION_BOND;
+        escrow.appeal_deadline = Some(now + ARBITRATION_PERIOD);
+
+        msg!("Resolution escalated to arbitrator {}", arbitrator);
+
+        emit!(EscalatedToArbitrator {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            escalator,
+            arbitrator,
+            bond: ARBITRATION_BOND,
+            ruling_deadline: now + ARBITRATION_PERIOD,
+        });
+
+        Ok(())
+    }
+
+    /// Rule on an escalated resolution (assigned arbitrator only)
+    ///
+    /// The ruling may move the oracle's refund by at most
+    /// `MAX_ARBITRATOR_DEVIATION` points. The escalating party gets the
+    /// bond back if the ruling moved in their favour; otherwise it goes to
+    /// the other party. The escrow then settles through `settle_resolution`
+    /// without waiting for the arbitration period to end. A held score under
+    /// a bound work agreement's minimum settles as a full refund whatever
+    /// the ruling.
+    ///
+    /// # Arguments
+    /// * `refund_percentage` - Refund to the agent (0-100)
+    pub fn arbitrator_rule(ctx: Context<ArbitratorRule>, refund_percentage: u8) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+
+        require!(
+            escrow.resolution_held() && escrow.escalated(),
+            EscrowError::NotEscalated
+        );
+        let (Some(ruling_deadline), Some(oracle_refund), Some(escalated_by)) =
+            (escrow.appeal_deadline, escrow.refund_percentage, escrow.escalated_by)
+        else {
+            return err!(EscrowError::NotEscalated);
+        };
+        let now = Clock::get()?.unix_timestamp;
+        require!(now < ruling_deadline, EscrowError::AppealWindowClosed);
+        require!(refund_percentage <= 100, EscrowError::InvalidRefundPercentage);
+        let refund_percentage = agreed_ruling(
+            escrow,
+            ctx.accounts.work_agreement.as_deref(),
+            refund_percentage,
+        )?;
+        require!(
+            oracle_refund.abs_diff(refund_percentage) <= MAX_ARBITRATOR_DEVIATION,
+            EscrowError::ArbitratorDeviationExceeded
+        );
+
+        let returned =
+            arbitration_bond_returned(escalated_by == escrow.agent, oracle_refund, refund_percentage);
+        settle_arbitration_bond(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            returned,
+        )?;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.refund_percentage = Some(refund_percentage);
+        escrow.appeal_deadline = Some(now);
+
+        let arbitrator = &mut ctx.accounts.arbitrator;
+        arbitrator.cases_ruled = arbitrator.cases_ruled.saturating_add(1);
+
+        msg!(
+            "Arbitrator ruled {}% refund (oracle: {}%)",
+            refund_percentage,
+            oracle_refund
+        );
+
+        emit!(ArbitratorRuled {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            arbitrator: arbitrator.authority,
+            oracle_refund_percentage: oracle_refund,
+            refund_percentage,
+        });
+
+        Ok(())
+    }
+
+    /// Pay out a submitted resolution once its appeal window closed unappealed
+    ///
+    /// Second phase of a two-phase resolution. Permissionless. Splits the
+    /// escrow as the verifier ruled, and pays that verifier any priority fee.
+    pub fn settle_resolution(ctx: Context<SettleResolution>) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(escrow.resolution_held(), EscrowError::ResolutionNotHeld);
+        require!(!escrow.appealed(), EscrowError::AlreadyAppealed);
+        let (Some(appeal_deadline), Some(quality_score), Some(refund_percentage)) =
+            (escrow.appeal_deadline, escrow.quality_score, escrow.refund_percentage)
+        else {
+            return err!(EscrowError::ResolutionNotHeld);
+        };
+        require!(
+            clock.unix_timestamp >= appeal_deadline,
+            EscrowError::AppealWindowOpen
+        );
+
+        pay_oracle_fee(&mut ctx.accounts.escrow, &ctx.accounts.verifier, &ctx.accounts.config)?;
+        let split = settlement_split(
+            &ctx.accounts.escrow,
+            refund_percentage,
+            ctx.accounts.pyth_price_update.as_deref(),
+            &ctx.accounts.config,
+        )?;
+        // An arbitrator ruling clears the escalation; a lapsed one leaves it set
+        let escrow = &ctx.accounts.escrow;
+        let method = if escrow.arbitrator.is_some() && !escrow.escalated() {
+            ResolutionMethod::Arbitrator
+        } else {
+            ResolutionMethod::Ed25519
+        };
+        // Only still escalated if the arbitrator let the period run out
+        settle_arbitration_bond(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            true,
+        )?;
+        let content_hash = ctx.accounts.escrow.assessed_content_hash;
+        apply_resolution(
+            SettlementAccounts {
+                escrow: &mut ctx.accounts.escrow,
+                agent: &ctx.accounts.agent,
+                refund_recipient: ctx.accounts.refund_recipient.as_deref(),
+                api: &ctx.accounts.api,
+                treasury: ctx.accounts.treasury.as_ref(),
+                config: &ctx.accounts.config,
+                priority_fee_recipient: &ctx.accounts.verifier,
+                agent_reputation: &ctx.accounts.agent_reputation,
+                api_reputation: &ctx.accounts.api_reputation,
+                agent_reputation_history: ctx.accounts.agent_reputation_history.as_deref_mut(),
+                api_reputation_history: ctx.accounts.api_reputation_history.as_deref_mut(),
+                verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+                service_bond: ctx.accounts.service_bond.as_mut(),
+                api_penalties: Some(&ctx.accounts.api_penalties),
+            },
+            Resolution {
+                quality_score,
+                refund_percentage,
+                split,
+                content_hash,
+                resolver: ctx.accounts.verifier.key(),
+                method,
+            },
+        )?;
+
+        msg!("Submitted resolution settled");
+
+        Ok(())
+    }
+
+    /// Start tracking a provider's strikes and suspensions
+    ///
+    /// Permissionless, so agents can enrol the providers they pay. Once the
+    /// account exists, every `resolve_dispute` below quality 30 is a strike
+    /// and three strikes suspend the provider from new escrows.
+    pub fn init_provider_penalties(ctx: Context<InitProviderPenalties>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let penalties = &mut ctx.accounts.provider_penalties;
+        penalties.provider = ctx.accounts.provider.key();
+        penalties.strike_count = 0;
+        penalties.suspended = false;
+        penalties.suspension_end = None;
+        penalties.total_refunds_issued = 0;
+        penalties.poor_quality_count = 0;
+        penalties.created_at = now;
+        penalties.last_updated = now;
+        penalties.bump = ctx.bumps.provider_penalties;
+
+        msg!("Provider penalties initialized for {}", penalties.provider);
+
+        Ok(())
+    }
+
+    /// Lift a provider's suspension once `suspension_end` has passed
+    ///
+    /// Permissionless; clears the strikes that caused the suspension.
+    pub fn lift_suspension(ctx: Context<LiftSuspension>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let penalties = &mut ctx.accounts.provider_penalties;
+
+        require!(penalties.suspended, EscrowError::ProviderNotSuspended);
+        require!(
+            penalties.suspension_end.is_some_and(|end| now >= end),
+            EscrowError::SuspensionActive
+        );
+
+        penalties.suspended = false;
+        penalties.suspension_end = None;
+        penalties.strike_count = 0;
+        penalties.last_updated = now;
+
+        msg!("Suspension lifted for {}", penalties.provider);
+
+        emit!(ProviderSuspensionLifted {
+            version: EVENT_VERSION,
+            provider: penalties.provider,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Create an entity's rate limiter at `VerificationLevel::Basic`
+    pub fn init_rate_limiter(ctx: Context<InitRateLimiter>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let rate_limiter = &mut ctx.accounts.rate_limiter;
+
+        rate_limiter.entity = ctx.accounts.entity.key();
+        rate_limiter.verification_level = VerificationLevel::Basic;
+        rate_limiter.transactions_last_hour = 0;
+        rate_limiter.transactions_last_day = 0;
+        rate_limiter.disputes_last_day = 0;
+        rate_limiter.last_hour_check = now / 3600;
+        rate_limiter.last_day_check = now / 86400;
+        rate_limiter.rate_limit_overridden_until = None;
+        rate_limiter.staked_lamports = 0;
+        rate_limiter.bump = ctx.bumps.rate_limiter;
+
+        msg!("Rate limiter initialized for {}", rate_limiter.entity);
+
+        Ok(())
+    }
+
+    /// Raise an entity's verification level
+    ///
+    /// With the default namespace authority co-signing, any higher level may
+    /// be granted. Otherwise the entity can only reach `Staked`, by locking
+    /// 1 SOL in its rate limiter.
+    ///
+    /// # Arguments
+    /// * `verification_level` - Level to move to; must be above the current one
+    pub fn upgrade_verification_level(
+        ctx: Context<UpgradeVerificationLevel>,
+        verification_level: VerificationLevel,
+    ) -> Result<()> {
+        let previous = ctx.accounts.rate_limiter.verification_level;
+        let authorized = ctx.accounts.authority.is_some();
+        require!(
+            verification_upgrade_allowed(previous, verification_level, authorized),
+            EscrowError::InvalidVerificationUpgrade
+        );
+
+        if !authorized {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.entity.to_account_info(),
+                    to: ctx.accounts.rate_limiter.to_account_info(),
+                },
+            );
+            anchor_lang::system_program::transfer(cpi_context, VERIFICATION_STAKE_LAMPORTS)?;
+            ctx.accounts.rate_limiter.staked_lamports = VERIFICATION_STAKE_LAMPORTS;
+        }
+
+        let rate_limiter = &mut ctx.accounts.rate_limiter;
+        rate_limiter.verification_level = verification_level;
+
+        msg!("Verification level raised for {}", rate_limiter.entity);
+
+        emit!(VerificationLevelChanged {
+            version: EVENT_VERSION,
+            entity: rate_limiter.entity,
+            previous,
+            verification_level,
+            staked_lamports: rate_limiter.staked_lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw the verification stake
+    ///
+    /// An entity at `Staked` drops back to `Basic`; levels granted by the
+    /// authority are kept.
+    pub fn withdraw_verification_stake(ctx: Context<WithdrawVerificationStake>) -> Result<()> {
+        let rate_limiter = &mut ctx.accounts.rate_limiter;
+        let stake = rate_limiter.staked_lamports;
+        require!(stake > 0, EscrowError::NoVerificationStake);
+
+        let previous = rate_limiter.verification_level;
+        if previous == VerificationLevel::Staked {
+            rate_limiter.verification_level = VerificationLevel::Basic;
+        }
+        rate_limiter.staked_lamports = 0;
+
+        let limiter_info = rate_limiter.to_account_info();
+        require_rent_exempt_after(&limiter_info, stake)?;
+        **limiter_info.try_borrow_mut_lamports()? -= stake;
+        **ctx.accounts.entity.to_account_info().try_borrow_mut_lamports()? += stake;
+
+        msg!("Verification stake withdrawn: {} lamports", stake);
+
+        emit!(VerificationLevelChanged {
+            version: EVENT_VERSION,
+            entity: rate_limiter.entity,
+            previous,
+            verification_level: rate_limiter.verification_level,
+            staked_lamports: 0,
+        });
+
+        Ok(())
+    }
+    /// Split an escrow into milestones that settle one at a time (agent only)
+    ///
+    /// The amounts must sum to the escrowed amount. From then on the escrow
+    /// only settles through `release_milestone` and `dispute_milestone`, so
+    /// one bad deliverable puts only its own tranche at risk. Not available
+    /// for escrows bound to a work agreement.
+    pub fn create_milestone_plan(
+        ctx: Context<CreateMilestonePlan>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let escrow = &mut ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            !escrow.has_work_agreement() && !escrow.streaming(),
+            EscrowError::InvalidMilestonePlan
+        );
+        validate_milestone_amounts(&amounts, escrow.amount)?;
+
+        escrow.set_has_milestones(true);
+
+        let plan = &mut ctx.accounts.milestone_plan;
+        plan.escrow = escrow.key();
+        plan.milestones = amounts
+            .iter()
+            .map(|&amount| Milestone {
+                amount,
+                status: MilestoneStatus::Pending,
+                refund_percentage: None,
+            })
+            .collect();
+        plan.bump = ctx.bumps.milestone_plan;
+
+        msg!("Escrow split into {} milestones", amounts.len());
+
+        emit!(MilestonePlanCreated {
+            version: EVENT_VERSION,
+            escrow: plan.escrow,
+            milestone_plan: plan.key(),
+            milestones: amounts.len() as u8,
+        });
+
+        Ok(())
+    }
+
+    /// Release one pending milestone to the API
+    ///
+    /// The agent can release at any time; anyone else once the escrow has
+    /// expired and its dispute window has closed, as with `release_funds`.
+    /// Settling the last milestone settles the escrow.
+    pub fn release_milestone(ctx: Context<ReleaseMilestone>, index: u8) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        if ctx.accounts.caller.key() != escrow.agent {
+            require_auto_releasable(escrow, clock.unix_timestamp)?;
+        }
+
+        let milestone = ctx
+            .accounts
+            .milestone_plan
+            .milestone_mut(index, MilestoneStatus::Pending)?;
+        milestone.status = MilestoneStatus::Released;
+        let amount = milestone.amount;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_api_with_fee(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.api,
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            amount,
+        )?;
+
+        let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+        let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+        settle_milestone_plan(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.milestone_plan,
+            agent_info.as_ref(),
+            api_info.as_ref(),
+        )?;
+
+        msg!("Milestone {} released: {} SOL", index, amount as f64 / 1_000_000_000.0);
+
+        emit!(MilestoneReleased {
+            version: EVENT_VERSION,
+            escrow: ctx.accounts.escrow.key(),
+            index,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Dispute one pending milestone (agent only)
+    ///
+    /// Holds that milestone for `resolve_milestone_dispute` while the others
+    /// can still be released. Allowed until the escrow's dispute deadline
+    /// and counted against the agent's dispute rate limit.
+    pub fn dispute_milestone(ctx: Context<DisputeMilestone>, index: u8) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        let clock = Clock::get()?;
+
+        require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            clock.unix_timestamp < escrow.dispute_deadline(),
+            EscrowError::DisputeWindowExpired
+        );
+
+        charge_rate_limit(
+            &ctx.accounts.rate_limiter,
+            &ctx.accounts.agent.key(),
+            RateLimitedAction::Dispute,
+        )?;
+
+        let milestone = ctx
+            .accounts
+            .milestone_plan
+            .milestone_mut(index, MilestoneStatus::Pending)?;
+        milestone.status = MilestoneStatus::Disputed;
+        let amount = milestone.amount;
+
+        msg!("Milestone {} disputed", index);
+
+        emit!(MilestoneDisputed {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            index,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve a disputed milestone with a verifier oracle signature
+    ///
+    /// Splits only that milestone's amount; settling the last milestone
+    /// settles the escrow.
+    ///
+    /// # Arguments
+    /// * `index` - Disputed milestone
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `refund_percentage` - Share of the milestone refunded (0-100)
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn resolve_milestone_dispute(
+        ctx: Context<ResolveMilestoneDispute>,
+        index: u8,
+        quality_score: u8,
+        refund_percentage: u8,
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        let escrow = &ctx.accounts.escrow;
+
+        require!(
+            escrow.status() == EscrowStatus::Active,
+            EscrowError::InvalidStatus
+        );
+        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+        require!(refund_percentage <= 100, EscrowError::InvalidRefundPercentage);
+
+        if let Some(assigned) = escrow.assigned_verifier {
+            require_keys_eq!(
+                ctx.accounts.verifier.key(),
+                assigned,
+                EscrowError::VerifierNotAssigned
+            );
+        }
+
+        require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+        let message = build_milestone_resolution_message(
+            &escrow.key(),
+            escrow,
+            nonce,
+            index,
+            quality_score,
+            refund_percentage,
+        );
+        verify_ed25519_signature(
+            &ctx.accounts.instructions_sysvar,
+            &signature,
+            &mut ctx.accounts.verifier_nonces,
+            nonce,
+            message.as_bytes(),
+            0,
+            ctx.accounts.config.strict_sig_adjacency,
+        )?;
+
+        let milestone = ctx
+            .accounts
+            .milestone_plan
+            .milestone_mut(index, MilestoneStatus::Disputed)?;
+        milestone.status = MilestoneStatus::Resolved;
+        milestone.refund_percentage = Some(refund_percentage);
+        let milestone_amount = milestone.amount;
+
+        let (refund_amount, payment_amount) =
+            split_escrow_amount(milestone_amount, refund_percentage)?;
+        require_rent_exempt_after(&ctx.accounts.escrow.to_account_info(), milestone_amount)?;
+
+        let agent = ctx.accounts.agent.to_account_info();
+        let refund_recipient = ctx.accounts.refund_recipient.as_ref().map(|r| r.to_account_info());
+        let refund_to = refund_destination(&ctx.accounts.escrow, &agent, refund_recipient.as_ref())?;
+        refund_agent(&mut ctx.accounts.escrow, refund_to, refund_amount)?;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_api_with_fee(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.api,
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            payment_amount,
+        )?;
+
+        let agent_info = ctx.accounts.agent_reputation.as_ref().map(|a| a.to_account_info());
+        let api_info = ctx.accounts.api_reputation.as_ref().map(|a| a.to_account_info());
+        settle_milestone_plan(
+            &mut ctx.accounts.escrow,
+            &ctx.accounts.milestone_plan,
+            agent_info.as_ref(),
+            api_info.as_ref(),
+        )?;
+
+        msg!("Milestone {} resolved: {}% refunded", index, refund_percentage);
+
+        emit!(MilestoneResolved {
+            version: EVENT_VERSION,
+            escrow: ctx.accounts.escrow.key(),
+            index,
+            quality_score,
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            verifier: ctx.accounts.verifier.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Open a subscription to an API, prepaying `periods` billing periods
+    ///
+    /// The first period starts now. The API claims each period once it has
+    /// ended and the agent's dispute window on it has closed.
+    ///
+    /// # Arguments
+    /// * `namespace` - Platform namespace whose config governs the subscription
+    /// * `price_per_period` - Lamports the API earns per billing period
+    /// * `period_seconds` - Length of a billing period
+    /// * `periods` - Periods funded up front
+    pub fn create_subscription(
+        ctx: Context<CreateSubscription>,
+        namespace: Pubkey,
+        price_per_period: u64,
+        period_seconds: i64,
+        periods: u32,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+        require!(
+            price_per_period >= MIN_ESCROW_AMOUNT && periods > 0,
+            EscrowError::InvalidAmount
+        );
+        require!(
+            (MIN_TIME_LOCK..=MAX_TIME_LOCK).contains(&period_seconds),
+            EscrowError::InvalidSubscriptionPeriod
+        );
+        let deposit = subscription_deposit(price_per_period, periods)?;
+        check_allowlist(
+            &ctx.accounts.agent_restriction,
+            ctx.accounts.allowlist.as_deref(),
+            &ctx.accounts.agent.key(),
+            &ctx.accounts.api.key(),
+        )?;
+
+        let clock = Clock::get()?;
+        let subscription = &mut ctx.accounts.subscription;
+        subscription.agent = ctx.accounts.agent.key();
+        subscription.api = ctx.accounts.api.key();
+        subscription.namespace = namespace;
+        subscription.price_per_period = price_per_period;
+        subscription.period_seconds = period_seconds;
+        subscription.period_start = clock.unix_timestamp;
+        subscription.claimable_at = subscription.period_end() + DISPUTE_WINDOW;
+        subscription.funded_periods = periods;
+        subscription.periods_settled = 0;
+        subscription.disputed = false;
+        subscription.bump = ctx.bumps.subscription;
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.subscription.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, deposit)?;
+
+        msg!("Subscription opened with {} periods funded", periods);
+
+        emit!(SubscriptionCreated {
+            version: EVENT_VERSION,
+            subscription: ctx.accounts.subscription.key(),
+            agent: ctx.accounts.agent.key(),
+            api: ctx.accounts.api.key(),
+            price_per_period,
+            period_seconds,
+            funded_periods: periods,
+        });
+
+        Ok(())
+    }
+
+    /// Prepay further billing periods (agent only)
+    ///
+    /// A subscription that ran out of funded periods restarts now.
+    pub fn top_up_subscription(ctx: Context<TopUpSubscription>, periods: u32) -> Result<()> {
+        require!(periods > 0, EscrowError::InvalidAmount);
+
+        let clock = Clock::get()?;
+        let subscription = &mut ctx.accounts.subscription;
+        let funded_periods = subscription
+            .funded_periods
+            .checked_add(periods)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        subscription_deposit(subscription.price_per_period, funded_periods)?;
+        let deposit = subscription_deposit(subscription.price_per_period, periods)?;
+
+        if subscription.funded_periods == 0 {
+            subscription.period_start = subscription.period_start.max(clock.unix_timestamp);
+            subscription.claimable_at = subscription.period_end() + DISPUTE_WINDOW;
+        }
+        subscription.funded_periods = funded_periods;
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.subscription.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, deposit)?;
+
+        emit!(SubscriptionToppedUp {
+            version: EVENT_VERSION,
+            subscription: ctx.accounts.subscription.key(),
+            periods,
+            funded_periods,
+        });
+
+        Ok(())
+    }
+
+    /// Claim the oldest unsettled billing period (API only)
+    ///
+    /// Allowed once the period has ended and the agent's dispute window on
+    /// it has closed undisputed.
+    pub fn claim_period(ctx: Context<ClaimPeriod>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+        let clock = Clock::get()?;
+
+        require!(subscription.funded_periods > 0, EscrowError::SubscriptionUnfunded);
+        require!(!subscription.disputed, EscrowError::PeriodDisputed);
+        require!(
+            clock.unix_timestamp >= subscription.claimable_at,
+            EscrowError::PeriodNotClaimable
+        );
+
+        let period = subscription.periods_settled;
+        let amount = subscription.price_per_period;
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_from_prepaid(
+            &ctx.accounts.subscription.to_account_info(),
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api.to_account_info(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            0,
+            amount,
+        )?;
+        ctx.accounts.subscription.advance_period(clock.unix_timestamp);
+
+        msg!("Subscription period {} claimed", period);
+
+        emit!(SubscriptionPeriodClaimed {
+            version: EVENT_VERSION,
+            subscription: ctx.accounts.subscription.key(),
+            period,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Dispute the oldest unsettled billing period (agent only)
+    ///
+    /// Holds its payment for `resolve_period_dispute`. Allowed from the
+    /// period's start until it becomes claimable, and counted against the
+    /// agent's dispute rate limit.
+    pub fn dispute_period(ctx: Context<DisputePeriod>) -> Result<()> {
+        let subscription = &mut ctx.accounts.subscription;
+        let clock = Clock::get()?;
+
+        require!(subscription.funded_periods > 0, EscrowError::SubscriptionUnfunded);
+        require!(!subscription.disputed, EscrowError::PeriodDisputed);
+        require!(
+            clock.unix_timestamp >= subscription.period_start,
+            EscrowError::PeriodNotStarted
+        );
+        require!(
+            clock.unix_timestamp < subscription.claimable_at,
+            EscrowError::DisputeWindowExpired
+        );
+
+        charge_rate_limit(
+            &ctx.accounts.rate_limiter,
+            &ctx.accounts.agent.key(),
+            RateLimitedAction::Dispute,
+        )?;
+
+        subscription.disputed = true;
+
+        msg!("Subscription period {} disputed", subscription.periods_settled);
+
+        emit!(SubscriptionPeriodDisputed {
+            version: EVENT_VERSION,
+            subscription: subscription.key(),
+            period: subscription.periods_settled,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve a disputed billing period with a verifier oracle signature
+    ///
+    /// Splits that period's price by the namespace's refund curve and moves
+    /// the subscription on to the next period.
+    ///
+    /// # Arguments
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn resolve_period_dispute(
+        ctx: Context<ResolvePeriodDispute>,
+        quality_score: u8,
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+
+        let subscription = &ctx.accounts.subscription;
+        require!(subscription.disputed, EscrowError::PeriodNotDisputed);
+        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+        require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+        let message = build_subscription_resolution_message(
+            &subscription.key(),
+            subscription,
+            nonce,
+            quality_score,
+        );
+        verify_ed25519_signature(
+            &ctx.accounts.instructions_sysvar,
+            &signature,
+            &mut ctx.accounts.verifier_nonces,
+            nonce,
+            message.as_bytes(),
+            0,
+            ctx.accounts.config.strict_sig_adjacency,
+        )?;
+
+        let period = subscription.periods_settled;
+        let refund_percentage = ctx.accounts.config.refund_percentage_for(quality_score);
+        let (refund_amount, payment_amount) =
+            split_escrow_amount(subscription.price_per_period, refund_percentage)?;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_from_prepaid(
+            &ctx.accounts.subscription.to_account_info(),
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api,
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            refund_amount,
+            payment_amount,
+        )?;
+        ctx.accounts.subscription.advance_period(Clock::get()?.unix_timestamp);
+
+        msg!("Subscription period {} resolved: {}% refunded", period, refund_percentage);
+
+        emit!(SubscriptionPeriodResolved {
+            version: EVENT_VERSION,
+            subscription: ctx.accounts.subscription.key(),
+            period,
+            quality_score,
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            verifier: ctx.accounts.verifier.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Stop a subscription, refunding the periods that have not started
+    /// (agent only)
+    ///
+    /// Started periods stay funded for the API to claim or the agent to
+    /// dispute as usual.
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+        let clock = Clock::get()?;
+
+        let started = subscription.started_periods(clock.unix_timestamp);
+        let refunded_periods = subscription.funded_periods - started;
+        let refund = subscription_deposit(subscription.price_per_period, refunded_periods)?;
+
+        debit_prepaid(
+            &ctx.accounts.subscription.to_account_info(),
+            &ctx.accounts.agent.to_account_info(),
+            refund,
+        )?;
+        ctx.accounts.subscription.funded_periods = started;
+
+        msg!("Subscription cancelled: {} periods refunded", refunded_periods);
+
+        emit!(SubscriptionCancelled {
+            version: EVENT_VERSION,
+            subscription: ctx.accounts.subscription.key(),
+            refunded_periods,
+            refund,
+        });
+
+        Ok(())
+    }
+
+    /// Open a payment channel to an API, depositing `deposit` lamports
+    ///
+    /// Calls between the pair are then recorded as channel entries and paid
+    /// out net, in a single `settle_channel`, instead of one escrow each.
+    ///
+    /// # Arguments
+    /// * `namespace` - Platform namespace whose config governs the channel
+    /// * `deposit` - Lamports the agent funds the channel with
+    pub fn open_payment_channel(
+        ctx: Context<OpenPaymentChannel>,
+        namespace: Pubkey,
+        deposit: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+        require!(
+            (MIN_ESCROW_AMOUNT..=MAX_ESCROW_AMOUNT).contains(&deposit),
+            EscrowError::InvalidAmount
+        );
+        check_allowlist(
+            &ctx.accounts.agent_restriction,
+            ctx.accounts.allowlist.as_deref(),
+            &ctx.accounts.agent.key(),
+            &ctx.accounts.api.key(),
+        )?;
+
+        let channel = &mut ctx.accounts.channel;
+        channel.agent = ctx.accounts.agent.key();
+        channel.api = ctx.accounts.api.key();
+        channel.namespace = namespace;
+        channel.balance = deposit;
+        channel.carried_credit = 0;
+        channel.next_entry_id = 0;
+        channel.total_settled = 0;
+        channel.entries = Vec::new();
+        channel.bump = ctx.bumps.channel;
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.channel.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, deposit)?;
+
+        emit!(PaymentChannelOpened {
+            version: EVENT_VERSION,
+            channel: ctx.accounts.channel.key(),
+            agent: ctx.accounts.agent.key(),
+            api: ctx.accounts.api.key(),
+            deposit,
+        });
+
+        Ok(())
+    }
+
+    /// Add to a payment channel's balance (agent only)
+    pub fn fund_payment_channel(ctx: Context<FundPaymentChannel>, amount: u64) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let channel = &mut ctx.accounts.channel;
+        let balance = channel
+            .balance
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(balance <= MAX_ESCROW_AMOUNT, EscrowError::AmountTooLarge);
+        channel.balance = balance;
+
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.agent.to_account_info(),
+                to: ctx.accounts.channel.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+        emit!(PaymentChannelFunded {
+            version: EVENT_VERSION,
+            channel: ctx.accounts.channel.key(),
+            amount,
+            balance,
+        });
+
+        Ok(())
+    }
+
+    /// Record a call the agent owes the API for (agent only)
+    ///
+    /// The entry stays open to dispute for `DISPUTE_WINDOW`, then
+    /// `settle_channel` pays it out with the others.
+    ///
+    /// # Arguments
+    /// * `amount` - Lamports owed for the call
+    pub fn record_channel_debit(ctx: Context<RecordChannelDebit>, amount: u64) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let channel = &mut ctx.accounts.channel;
+        require!(
+            channel.entries.len() < MAX_CHANNEL_ENTRIES,
+            EscrowError::PaymentChannelFull
+        );
+        let pending = channel
+            .pending_debits()
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(pending <= channel.balance, EscrowError::ChannelBalanceExceeded);
+
+        let entry_id = channel.next_entry_id;
+        channel.entries.push(ChannelEntry {
+            id: entry_id,
+            amount,
+            recorded_at: Clock::get()?.unix_timestamp,
+            disputed: false,
+            resolved: false,
+        });
+        channel.next_entry_id += 1;
+
+        emit!(ChannelDebitRecorded {
+            version: EVENT_VERSION,
+            channel: channel.key(),
+            entry_id,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Credit the agent against the channel's debits (API only)
+    ///
+    /// Credits offset the next settlements and are never paid out on their own.
+    pub fn record_channel_credit(ctx: Context<RecordChannelCredit>, amount: u64) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let channel = &mut ctx.accounts.channel;
+        channel.carried_credit = channel
+            .carried_credit
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        emit!(ChannelCreditRecorded {
+            version: EVENT_VERSION,
+            channel: channel.key(),
+            amount,
+            carried_credit: channel.carried_credit,
+        });
+
+        Ok(())
+    }
+
+    /// Dispute a single channel entry within its dispute window (agent only)
+    ///
+    /// Holds that entry out of settlement until `resolve_channel_entry`;
+    /// the rest of the channel keeps settling. Counted against the agent's
+    /// dispute rate limit.
+    pub fn dispute_channel_entry(ctx: Context<DisputeChannelEntry>, entry_id: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let entry = ctx
+            .accounts
+            .channel
+            .entry_mut(entry_id)
+            .ok_or(EscrowError::ChannelEntryNotFound)?;
+        require!(
+            !entry.disputed && !entry.resolved && now < entry.recorded_at + DISPUTE_WINDOW,
+            EscrowError::ChannelEntryNotDisputable
+        );
+        entry.disputed = true;
+
+        charge_rate_limit(
+            &ctx.accounts.rate_limiter,
+            &ctx.accounts.agent.key(),
+            RateLimitedAction::Dispute,
+        )?;
+
+        msg!("Channel entry {} disputed", entry_id);
+
+        emit!(ChannelEntryDisputed {
+            version: EVENT_VERSION,
+            channel: ctx.accounts.channel.key(),
+            entry_id,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve a disputed channel entry with a verifier oracle signature
+    ///
+    /// The entry is cut to the API's share under the namespace's refund
+    /// curve and settles with the rest of the channel; the refunded share
+    /// stays in the channel's balance.
+    ///
+    /// # Arguments
+    /// * `entry_id` - Disputed entry
+    /// * `quality_score` - Quality score from verifier (0-100)
+    /// * `nonce` - Unused nonce of the verifier, embedded in the signed message
+    /// * `signature` - Ed25519 signature from verifier oracle
+    pub fn resolve_channel_entry(
+        ctx: Context<ResolveChannelEntry>,
+        entry_id: u64,
+        quality_score: u8,
+        nonce: u64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+        let channel_key = ctx.accounts.channel.key();
+        let entry = *ctx
+            .accounts
+            .channel
+            .entry_mut(entry_id)
+            .ok_or(EscrowError::ChannelEntryNotFound)?;
+        require!(entry.disputed, EscrowError::ChannelEntryNotDisputed);
+
+        require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+        let message = build_channel_resolution_message(&channel_key, &entry, nonce, quality_score);
+        verify_ed25519_signature(
+            &ctx.accounts.instructions_sysvar,
+            &signature,
+            &mut ctx.accounts.verifier_nonces,
+            nonce,
+            message.as_bytes(),
+            0,
+            ctx.accounts.config.strict_sig_adjacency,
+        )?;
+
+        let refund_percentage = ctx.accounts.config.refund_percentage_for(quality_score);
+        let (refund_amount, payment_amount) = split_escrow_amount(entry.amount, refund_percentage)?;
+
+        let entry = ctx
+            .accounts
+            .channel
+            .entry_mut(entry_id)
+            .ok_or(EscrowError::ChannelEntryNotFound)?;
+        entry.amount = payment_amount;
+        entry.disputed = false;
+        entry.resolved = true;
+
+        msg!("Channel entry {} resolved: {}% refunded", entry_id, refund_percentage);
+
+        emit!(ChannelEntryResolved {
+            version: EVENT_VERSION,
+            channel: channel_key,
+            entry_id,
+            quality_score,
+            refund_percentage,
+            refund_amount,
+            payment_amount,
+            verifier: ctx.accounts.verifier.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Pay the API the net of every settleable entry (API only)
+    ///
+    /// Entries settle once their dispute window has closed undisputed, or
+    /// once resolved. Carried credits are offset first; disputed and recent
+    /// entries stay in the channel.
+    pub fn settle_channel(ctx: Context<SettleChannel>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let channel = &mut ctx.accounts.channel;
+
+        let (entries, debits) = channel.take_settleable(now);
+        let credit_applied = debits.min(channel.carried_credit);
+        let payment = debits - credit_applied;
+        channel.carried_credit -= credit_applied;
+        channel.balance -= payment;
+        channel.total_settled = channel
+            .total_settled
+            .checked_add(payment)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let treasury = ctx.accounts.treasury.as_ref().map(|t| t.to_account_info());
+        pay_from_prepaid(
+            &ctx.accounts.channel.to_account_info(),
+            &ctx.accounts.agent.to_account_info(),
+            &ctx.accounts.api.to_account_info(),
+            treasury.as_ref(),
+            &ctx.accounts.config,
+            0,
+            payment,
+        )?;
+
+        msg!("Channel settled {} entries: {} lamports", entries, payment);
+
+        emit!(PaymentChannelSettled {
+            version: EVENT_VERSION,
+            channel: ctx.accounts.channel.key(),
+            entries,
+            debits,
+            credit_applied,
+            payment,
+        });
+
+        Ok(())
+    }
+
+    /// Close a payment channel with no unsettled entries, returning its
+    /// balance and rent to the agent (agent only)
+    ///
+    /// Carried credits lapse with the channel.
+    pub fn close_payment_channel(ctx: Context<ClosePaymentChannel>) -> Result<()> {
+        emit!(PaymentChannelClosed {
+            version: EVENT_VERSION,
+            channel: ctx.accounts.channel.key(),
+            refund: ctx.accounts.channel.balance,
+        });
+
+        Ok(())
+    }
+
+    /// Create the namespace's program-owned treasury and route protocol
+    /// fees to it (config authority only)
+    ///
+    /// Fees collected by `pay_api_with_fee` then accumulate in the PDA until
+    /// the authority moves them out with `withdraw_fees`.
+    pub fn initialize_treasury(ctx: Context<InitializeTreasury>) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.namespace = ctx.accounts.config.namespace;
+        treasury.total_withdrawn = 0;
+        treasury.bump = ctx.bumps.treasury;
+
+        let config = &mut ctx.accounts.config;
+        config.treasury = treasury.key();
+
+        msg!("Protocol fees now collect in treasury {}", config.treasury);
+
+        emit!(TreasuryInitialized {
+            version: EVENT_VERSION,
+            namespace: config.namespace,
+            treasury: config.treasury,
+        });
+
+        Ok(())
+    }
+
+    /// Move collected protocol fees out of the namespace treasury (config
+    /// authority only)
+    ///
+    /// The treasury keeps its rent-exempt minimum.
+    ///
+    /// # Arguments
+    /// * `amount` - Lamports to send to `destination`
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        let treasury_info = ctx.accounts.treasury.to_account_info();
+        require_rent_exempt_after(&treasury_info, amount)?;
+
+        **treasury_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.destination.try_borrow_mut_lamports()? += amount;
+
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.total_withdrawn = treasury
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        msg!("Withdrew {} SOL of protocol fees", amount as f64 / 1_000_000_000.0);
+
+        emit!(FeesWithdrawn {
+            version: EVENT_VERSION,
+            namespace: treasury.namespace,
+            treasury: treasury.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Pause or resume a namespace (circuit breaker)
+    ///
+    /// While paused, new escrows and verifier resolutions are rejected so a
+    /// compromised verifier key can be contained without redeploying.
+    /// Releases, cancellations and mutual settlements stay open so funds can
+    /// still leave. Callable by the namespace's config authority or by the
+    /// protocol authority, either of which may be a multisig.
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == ctx.accounts.config.authority
+                || authority == ctx.accounts.protocol_config.authority,
+            EscrowError::Unauthorized
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.paused = paused;
+
+        msg!("Namespace {} {}", config.namespace, if paused { "paused" } else { "resumed" });
+
+        emit!(PauseChanged {
+            version: EVENT_VERSION,
+            namespace: config.namespace,
+            paused,
+            authority,
+        });
+
+        Ok(())
+    }
+
+    /// Create the namespace's verifier registry (config authority only)
+    ///
+    /// Only keys in the registry can sign `resolve_dispute`,
+    /// `resolve_dispute_spl`, `resolve_milestone_dispute` and batch
+    /// resolutions for the namespace's escrows.
+    pub fn initialize_verifier_registry(ctx: Context<InitializeVerifierRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        registry.namespace = ctx.accounts.config.namespace;
+        registry.verifiers = Vec::new();
+        registry.bump = ctx.bumps.verifier_registry;
+
+        msg!("Verifier registry initialized for {}", registry.namespace);
+
+        Ok(())
+    }
+
+    /// Approve a verifier signing key, or replace one (config authority only)
+    ///
+    /// Passing `replaces` rotates that key out in the same slot, so a
+    /// compromised or retired key stops verifying the moment its successor
+    /// starts. Disputes assigned to the old key need the new one assigned.
+    ///
+    /// # Arguments
+    /// * `verifier` - Key to approve
+    /// * `replaces` - Registered key to rotate out, if any
+    pub fn register_verifier_key(
+        ctx: Context<ManageVerifierRegistry>,
+        verifier: Pubkey,
+        replaces: Option<Pubkey>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        require!(
+            !registry.verifiers.contains(&verifier),
+            EscrowError::VerifierAlreadyRegistered
+        );
+
+        match replaces {
+            Some(old) => {
+                let slot = registry
+                    .verifiers
+                    .iter()
+                    .position(|key| *key == old)
+                    .ok_or(EscrowError::VerifierNotRegistered)?;
+                registry.verifiers[slot] = verifier;
+            }
+            None => {
+                require!(
+                    registry.verifiers.len() < MAX_REGISTERED_VERIFIERS,
+                    EscrowError::VerifierRegistryFull
+                );
+                registry.verifiers.push(verifier);
+            }
+        }
+
+        msg!("Verifier key registered: {}", verifier);
+
+        emit!(VerifierKeyRegistered {
+            version: EVENT_VERSION,
+            namespace: registry.namespace,
+            verifier,
+            replaced: replaces,
+        });
+
+        Ok(())
+    }
+
+    /// Remove a verifier signing key from the registry (config authority only)
+    pub fn remove_verifier_key(ctx: Context<ManageVerifierRegistry>, verifier: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        let slot = registry
+            .verifiers
+            .iter()
+            .position(|key| *key == verifier)
+            .ok_or(EscrowError::VerifierNotRegistered)?;
+        registry.verifiers.remove(slot);
+
+        msg!("Verifier key removed: {}", verifier);
+
+        emit!(VerifierKeyRemoved {
+            version: EVENT_VERSION,
+            namespace: registry.namespace,
+            verifier,
+        });
+
+        Ok(())
+    }
+
+    /// Add a human arbitrator to the namespace's registry (config authority only)
+    ///
+    /// # Arguments
+    /// * `arbitrator_authority` - Key that signs the arbitrator's rulings
+    pub fn register_arbitrator(
+        ctx: Context<RegisterArbitrator>,
+        arbitrator_authority: Pubkey,
+    ) -> Result<()> {
+        let arbitrator = &mut ctx.accounts.arbitrator;
+        arbitrator.namespace = ctx.accounts.config.namespace;
+        arbitrator.authority = arbitrator_authority;
+        arbitrator.cases_ruled = 0;
+        arbitrator.registered_at = Clock::get()?.unix_timestamp;
+        arbitrator.bump = ctx.bumps.arbitrator;
+
+        msg!("Arbitrator registered: {}", arbitrator_authority);
+
+        emit!(ArbitratorRegistered {
+            version: EVENT_VERSION,
+            namespace: arbitrator.namespace,
+            arbitrator: arbitrator_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an arbitrator from the registry (config authority only)
+    ///
+    /// Cases already escalated to them can no longer be ruled on and settle
+    /// on the oracle's ruling once their period ends.
+    pub fn remove_arbitrator(ctx: Context<RemoveArbitrator>) -> Result<()> {
+        let arbitrator = &ctx.accounts.arbitrator;
+
+        msg!("Arbitrator removed: {}", arbitrator.authority);
+
+        emit!(ArbitratorRemoved {
+            version: EVENT_VERSION,
+            namespace: arbitrator.namespace,
+            arbitrator: arbitrator.authority,
+            cases_ruled: arbitrator.cases_ruled,
+        });
+
+        Ok(())
+    }
+}
+
+// Helper functions
+
+/// Start of every message a verifier signs about `escrow`
+///
+/// Format: "{transaction_id}:{escrow}:{amount}:{resolution_nonce}:{nonce}",
+/// where `nonce` is the verifier's. The escrow address, amount and
+/// `Escrow::resolution_nonce` keep a signature from applying to another
+/// escrow that reuses the transaction ID, including one re-initialized at
+/// the same address.
+fn resolution_message_prefix(escrow_key: &Pubkey, escrow: &Escrow, nonce: u64) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        escrow.transaction_id, escrow_key, escrow.amount, escrow.resolution_nonce, nonce
+    )
+}
+
+/// Message the verifier oracle signs for `resolve_dispute`
+///
+/// Format: "{prefix}:{quality_score}:{content_hash_hex}", followed by
+/// ":{request_hash_hex}" when the agent committed to its request,
+/// ":{challenge_nonce_hex}" when the escrow carries a payment reference,
+/// ":{min_records}:{max_age_days}:{min_quality_score}" when it is bound to a
+/// work agreement and, for agreements that weight score dimensions,
+/// ":{score_1}:{score_2}:{score_3}:{score_4}" with `quality_score` their
+/// weighted aggregate
+fn build_resolution_message(
+    escrow_key: &Pubkey,
+    escrow: &Escrow,
+    nonce: u64,
+    work_agreement: Option<&WorkAgreement>,
+    quality_score: u8,
+    content_hash: &[u8; 32],
+    sub_scores: Option<&[u8; SCORE_DIMENSIONS]>,
+) -> String {
+    let content_hash_hex: String = content_hash.iter().map(|b| format!("{:02x}", b)).collect();
+    let mut message = format!(
+        "{}:{}:{}",
+        resolution_message_prefix(escrow_key, escrow, nonce),
+        quality_score,
+        content_hash_hex
+    );
+    if let Some(request_hash) = &escrow.request_hash {
+        let request_hash_hex: String = request_hash.iter().map(|b| format!("{:02x}", b)).collect();
+        message = format!("{}:{}", message, request_hash_hex);
+    }
+    if let Some(reference) = &escrow.payment_reference {
+        let nonce_hex: String = reference.challenge_nonce.iter().map(|b| format!("{:02x}", b)).collect();
+        message = format!("{}:{}", message, nonce_hex);
+    }
+    if let Some(agreement) = work_agreement {
+        message = format!(
+            "{}:{}:{}:{}",
+            message, agreement.min_records, agreement.max_age_days, agreement.min_quality_score
+        );
+    }
+    if let Some(sub_scores) = sub_scores {
+        for score in sub_scores {
+            message = format!("{}:{}", message, score);
+        }
+    }
+    message
+}
+
+/// Count an escrow against the agent's daily limit if it belongs to an
+/// organization
+fn charge_organization_spend(info: &AccountInfo, agent: &Pubkey, amount: u64, now: i64) -> Result<()> {
+    if info.owner != &crate::ID {
+        return Ok(());
+    }
+    let mut member = OrganizationMember::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(member.agent, *agent, EscrowError::Unauthorized);
+
+    member.charge(amount, now)?;
+
+    let mut data = info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    member.try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+/// Restriction stored at `info`, or `None` for agents no organization restricts
+fn load_agent_restriction(info: &AccountInfo, agent: &Pubkey) -> Result<Option<AgentRestriction>> {
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+    let restriction = AgentRestriction::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(restriction.agent, *agent, EscrowError::Unauthorized);
+    Ok(Some(restriction))
+}
+
+/// Blocklist stored at `info`, or `None` before `initialize_blocklist`
+fn load_blocklist(info: &AccountInfo, owner: &Pubkey) -> Result<Option<Blocklist>> {
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+    let blocklist = Blocklist::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(blocklist.owner, *owner, EscrowError::Unauthorized);
+    Ok(Some(blocklist))
+}
+
+/// Provider penalties stored at `info`, or `None` before `init_provider_penalties`
+fn load_provider_penalties(info: &AccountInfo, provider: &Pubkey) -> Result<Option<ProviderPenalties>> {
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+    let penalties = ProviderPenalties::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(penalties.provider, *provider, EscrowError::Unauthorized);
+    Ok(Some(penalties))
+}
+
+/// Count a resolution against the API's penalties, if it tracks them
+fn record_provider_strike(
+    escrow: &Account<Escrow>,
+    penalties_info: &AccountInfo,
+    quality_score: u8,
+    refund_amount: u64,
+) -> Result<()> {
+    let Some(mut penalties) = load_provider_penalties(penalties_info, &escrow.api)? else {
+        return Ok(());
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+    let newly_suspended = penalties.record_resolution(quality_score, refund_amount, now);
+    if quality_score < POOR_QUALITY_THRESHOLD {
+        emit!(ProviderStrikeRecorded {
+            version: EVENT_VERSION,
+            provider: penalties.provider,
+            escrow: escrow.key(),
+            quality_score,
+            strike_count: penalties.strike_count,
+        });
+    }
+    if newly_suspended {
+        msg!("Provider suspended: {}", penalties.provider);
+        emit!(ProviderSuspensionStarted {
+            version: EVENT_VERSION,
+            provider: penalties.provider,
+            suspension_end: penalties.suspension_end.unwrap_or(now),
+        });
+    }
+
+    let mut data = penalties_info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    penalties.try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+/// Whether `upgrade_verification_level` may move from `current` to `target`
+///
+/// Levels only rise. Without the authority the sole reachable level is
+/// `Staked`, which the caller pays for.
+fn verification_upgrade_allowed(
+    current: VerificationLevel,
+    target: VerificationLevel,
+    authorized: bool,
+) -> bool {
+    target > current && (authorized || target == VerificationLevel::Staked)
+}
+
+/// Check a milestone plan's amounts before storing it
+fn validate_milestone_amounts(amounts: &[u64], escrow_amount: u64) -> Result<()> {
+    require!(
+        !amounts.is_empty()
+            && amounts.len() <= MAX_MILESTONES
+            && amounts.iter().all(|&amount| amount > 0),
+        EscrowError::InvalidMilestonePlan
+    );
+    let total = amounts
+        .iter()
+        .try_fold(0u64, |total, &amount| total.checked_add(amount))
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    require!(total == escrow_amount, EscrowError::InvalidMilestonePlan);
+    Ok(())
+}
+
+/// Message the verifier oracle signs for `resolve_milestone_dispute`
+///
+/// Format: "{prefix}:milestone:{index}:{quality_score}:{refund_percentage}"
+fn build_milestone_resolution_message(
+    escrow_key: &Pubkey,
+    escrow: &Escrow,
+    nonce: u64,
+    index: u8,
+    quality_score: u8,
+    refund_percentage: u8,
+) -> String {
+    format!(
+        "{}:milestone:{}:{}:{}",
+        resolution_message_prefix(escrow_key, escrow, nonce),
+        index,
+        quality_score,
+        refund_percentage
+    )
+}
+
+/// Message the verifier oracle signs for `resolve_period_dispute`
+///
+/// Format: "subscription:{subscription}:{period}:{price_per_period}:{nonce}:{quality_score}".
+/// The period index keeps a signature from settling any other period.
+fn build_subscription_resolution_message(
+    subscription_key: &Pubkey,
+    subscription: &SubscriptionEscrow,
+    nonce: u64,
+    quality_score: u8,
+) -> String {
+    format!(
+        "subscription:{}:{}:{}:{}:{}",
+        subscription_key,
+        subscription.periods_settled,
+        subscription.price_per_period,
+        nonce,
+        quality_score
+    )
+}
+
+/// Lamports `periods` billing periods cost, within the per-escrow maximum
+fn subscription_deposit(price_per_period: u64, periods: u32) -> Result<u64> {
+    let deposit = price_per_period
+        .checked_mul(periods as u64)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    require!(deposit <= MAX_ESCROW_AMOUNT, EscrowError::AmountTooLarge);
+    Ok(deposit)
+}
+
+/// Pay out of a subscription's or payment channel's prepaid balance:
+/// `refund` back to the agent and `payment` to the API, less the
+/// namespace's protocol fee
+fn pay_from_prepaid(
+    prepaid: &AccountInfo,
+    agent: &AccountInfo,
+    api: &AccountInfo,
+    treasury: Option<&AccountInfo>,
+    config: &GlobalConfig,
+    refund: u64,
+    payment: u64,
+) -> Result<()> {
+    let fee = bps_of(payment, config.fee_bps)?;
+    require_rent_exempt_after(prepaid, refund + payment)?;
+
+    debit_prepaid(prepaid, agent, refund)?;
+    if fee > 0 {
+        let treasury = treasury.ok_or(EscrowError::TreasuryMissing)?;
+        debit_prepaid(prepaid, treasury, fee)?;
+
+        emit!(ProtocolFeeCollected {
+            version: EVENT_VERSION,
+            escrow: prepaid.key(),
+            namespace: config.namespace,
+            treasury: treasury.key(),
+            amount: fee,
+        });
+    }
+    debit_prepaid(prepaid, api, payment - fee)
+}
+
+/// Move lamports out of a program-owned prepaid balance
+fn debit_prepaid(prepaid: &AccountInfo, to: &AccountInfo, lamports: u64) -> Result<()> {
+    if lamports > 0 {
+        **prepaid.try_borrow_mut_lamports()? -= lamports;
+        **to.try_borrow_mut_lamports()? += lamports;
+    }
+    Ok(())
+}
+
+/// Message the verifier oracle signs for `resolve_channel_entry`
+///
+/// Format: "channel:{channel}:{entry_id}:{amount}:{nonce}:{quality_score}".
+/// Entry ids are never reused, so a signature settles only that entry.
+fn build_channel_resolution_message(
+    channel_key: &Pubkey,
+    entry: &ChannelEntry,
+    nonce: u64,
+    quality_score: u8,
+) -> String {
+    format!(
+        "channel:{}:{}:{}:{}:{}",
+        channel_key, entry.id, entry.amount, nonce, quality_score
+    )
+}
+
+/// Settle a milestone escrow once none of its milestones are left open
+fn settle_milestone_plan(
+    escrow: &mut Account<Escrow>,
+    plan: &MilestonePlan,
+    agent_info: Option<&AccountInfo>,
+    api_info: Option<&AccountInfo>,
+) -> Result<()> {
+    let Some(status) = plan.settled_status() else {
+        return Ok(());
+    };
+    escrow.set_status(status);
+    escrow.settled_at = Some(Clock::get()?.unix_timestamp);
+
+    release_exposure_accounts(escrow, agent_info, api_info)
+}
+
+/// Fail unless `verifier` is approved in the namespace's registry
+fn require_registered_verifier(registry: &VerifierRegistry, verifier: &Pubkey) -> Result<()> {
+    require!(
+        registry.verifiers.contains(verifier),
+        EscrowError::VerifierNotRegistered
+    );
+    Ok(())
+}
+
+/// Check a verifier set before storing it
+fn validate_verifier_set(verifiers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !verifiers.is_empty()
+            && verifiers.len() <= MAX_VERIFIER_SET_SIZE
+            && threshold > 0
+            && threshold as usize <= verifiers.len(),
+        EscrowError::InvalidVerifierSet
+    );
+    for (i, verifier) in verifiers.iter().enumerate() {
+        require!(
+            !verifiers[..i].contains(verifier),
+            EscrowError::InvalidVerifierSet
+        );
+    }
+    Ok(())
+}
+
+/// Resolve quorum signatures to distinct set members, in signature order
+///
+/// Fails unless at least `threshold` distinct members signed.
+fn quorum_signers(set: &VerifierSet, signatures: &[QuorumSignature]) -> Result<Vec<Pubkey>> {
+    let mut seen: u32 = 0;
+    let mut signers = Vec::with_capacity(signatures.len());
+    for entry in signatures {
+        let index = entry.verifier_index as usize;
+        let signer = set
+            .verifiers
+            .get(index)
+            .ok_or(EscrowError::InvalidQuorumSigner)?;
+        require!(seen & (1 << index) == 0, EscrowError::InvalidQuorumSigner);
+        seen |= 1 << index;
+        signers.push(*signer);
+    }
+    require!(
+        signers.len() >= set.threshold as usize,
+        EscrowError::QuorumNotReached
+    );
+    Ok(signers)
+}
+
+/// Refund percentage for a verifier resolution
+///
+/// Follows the escrow's refund curve, or the agreement's if it set one.
+/// Bound escrows must present their agreement; quality under the agreed
+/// minimum means the scope was not met, so it refunds in full.
+fn agreed_refund_percentage(
+    config: &GlobalConfig,
+    escrow: &Escrow,
+    work_agreement: Option<&WorkAgreement>,
+    quality_score: u8,
+) -> Result<u8> {
+    if !escrow.has_work_agreement() {
+        return Ok(escrow.refund_percentage_for(config, quality_score));
+    }
+    let agreement = work_agreement.ok_or(EscrowError::WorkAgreementRequired)?;
+    if quality_score < agreement.min_quality_score {
+        return Ok(100);
+    }
+    Ok(agreement.refund_percentage_for(escrow, config, quality_score))
+}
+
+/// Quality score of a verifier resolution
+///
+/// Agreements that weight score dimensions must be resolved from
+/// per-dimension scores, which are aggregated here; anything else takes the
+/// single score it was given.
+fn resolution_quality(
+    work_agreement: Option<&WorkAgreement>,
+    quality_score: u8,
+    sub_scores: Option<&[u8; SCORE_DIMENSIONS]>,
+) -> Result<u8> {
+    match (work_agreement.filter(|agreement| agreement.weighted()), sub_scores) {
+        (Some(agreement), Some(sub_scores)) => agreement.aggregate_score(sub_scores),
+        (None, None) => Ok(quality_score),
+        (Some(_), None) => err!(EscrowError::SubScoresRequired),
+        (None, Some(_)) => err!(EscrowError::ScoreDimensionsNotAgreed),
+    }
+}
+
+/// Refund an arbitrator's ruling settles at
+///
+/// A ruling may move the oracle's refund but cannot waive a work
+/// agreement's minimum: a held score under it refunds in full.
+fn agreed_ruling(
+    escrow: &Escrow,
+    work_agreement: Option<&WorkAgreement>,
+    refund_percentage: u8,
+) -> Result<u8> {
+    if !escrow.has_work_agreement() {
+        return Ok(refund_percentage);
+    }
+    let agreement = work_agreement.ok_or(EscrowError::WorkAgreementRequired)?;
+    match escrow.quality_score {
+        Some(score) if score < agreement.min_quality_score => Ok(100),
+        _ => Ok(refund_percentage),
+    }
+}
+
+/// Check a refund curve before storing it: scores ascend to 100 and refunds
+/// never rise with quality
+fn validate_refund_curve(curve: &[RefundStep; REFUND_CURVE_STEPS]) -> Result<()> {
+    require!(
+        curve[REFUND_CURVE_STEPS - 1].max_quality_score == 100,
+        EscrowError::InvalidRefundCurve
+    );
+    require!(curve[0].refund_percentage <= 100, EscrowError::InvalidRefundCurve);
+    for pair in curve.windows(2) {
+        require!(
+            pair[0].max_quality_score < pair[1].max_quality_score
+                && pair[0].refund_percentage >= pair[1].refund_percentage,
+            EscrowError::InvalidRefundCurve
+        );
+    }
+    Ok(())
+}
+
+/// Tiers must open at score 0 and raise both the score and the cap, within
+/// the protocol's escrow bounds
+fn validate_amount_tiers(tiers: &[AmountTier; AMOUNT_TIERS]) -> Result<()> {
+    require!(tiers[0].min_reputation_score == 0, EscrowError::InvalidAmountTiers);
+    require!(
+        tiers
+            .iter()
+            .all(|tier| (MIN_ESCROW_AMOUNT..=MAX_ESCROW_AMOUNT).contains(&tier.max_amount)),
+        EscrowError::InvalidAmountTiers
+    );
+    for pair in tiers.windows(2) {
+        require!(
+            pair[0].min_reputation_score < pair[1].min_reputation_score
+                && pair[0].max_amount <= pair[1].max_amount,
+            EscrowError::InvalidAmountTiers
+        );
+    }
+    Ok(())
+}
+
+/// Reject an escrow amount above the agent's reputation tier
+fn check_amount_tier(
+    config: &GlobalConfig,
+    agent_reputation: Option<&EntityReputation>,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    // A provider-typed account is scored on deliveries, not on paying
+    let score = agent_reputation
+        .filter(|reputation| reputation.entity_type == EntityType::Agent)
+        .map(|reputation| calculate_reputation_score(reputation, now));
+    require!(
+        amount <= config.max_escrow_amount_for(score),
+        EscrowError::AmountExceedsReputationTier
+    );
+    Ok(())
+}
+
+fn calculate_dispute_cost(reputation: &EntityReputation, amount: u64, bond_bps: u16) -> u64 {
+    let proportional = (amount as u128 * bond_bps as u128 / MAX_BPS as u128) as u64;
+    let base_cost = proportional.max(BASE_DISPUTE_COST);
+    if reputation.total_transactions == 0 {
+        return base_cost;
+    }
+
+    let dispute_rate = (reputation.disputes_filed * 100) / reputation.total_transactions;
+
+    let multiplier = match dispute_rate {
+        0..=20 => 1,     // Normal dispute rate
+        21..=40 => 2,    // High dispute rate
+        41..=60 => 5,    // Very high dispute rate
+        _ => 10,         // Abuse pattern
+    };
+
+    base_cost.saturating_mul(multiplier)
+}
+
+/// What `get_reputation` reports for `entity`; neutral when it has no account
+fn reputation_view(entity: Pubkey, reputation: Option<&EntityReputation>, now: i64) -> ReputationView {
+    match reputation {
+        Some(reputation) => ReputationView {
+            entity,
+            initialized: true,
+            entity_type: Some(reputation.entity_type.clone()),
+            reputation_score: calculate_reputation_score(reputation, now),
+            total_transactions: reputation.total_transactions,
+            disputes_filed: reputation.disputes_filed,
+            disputes_lost: reputation.disputes_lost,
+            average_quality_bps: reputation.average_quality_bps(),
+            distinct_recent_counterparties: reputation.distinct_recent_counterparties(),
+            last_updated: reputation.last_updated,
+        },
+        None => ReputationView {
+            entity,
+            initialized: false,
+            entity_type: None,
+            reputation_score: NEUTRAL_REPUTATION_SCORE,
+            total_transactions: 0,
+            disputes_filed: 0,
+            disputes_lost: 0,
+            average_quality_bps: 0,
+            distinct_recent_counterparties: 0,
+            last_updated: 0,
+        },
+    }
+}
+
+/// Score an entity's history (0-1000), decayed by the time since `last_updated`
+fn calculate_reputation_score(reputation: &EntityReputation, now: i64) -> u16 {
+    if reputation.total_transactions == 0 {
+        return NEUTRAL_REPUTATION_SCORE; // Default medium score
+    }
+
+    // Repeat dealing with the same counterparty earns no transaction credit
+    let diverse_transactions = reputation
+        .total_transactions
+        .saturating_sub(reputation.repeat_transactions);
+    let tx_score = diverse_transactions.min(100) as u16 * 5; // Max 500 from transactions
+
+    // Currency-neutral volume so SOL and USDC histories compare fairly. It
+    // is a bonus on top of the original scale, so histories without
+    // recorded volume keep the score they had before volume was tracked.
+    let volume_score = match reputation.volume_usd_cents {
+        0..=9_999 => 0,                 // < $100
+        10_000..=99_999 => 25,          // < $1k
+        100_000..=999_999 => 50,        // < $10k
+        1_000_000..=9_999_999 => 75,    // < $100k
+        _ => 100,                       // Max 100 from volume
+    };
+
+    let quality_score = (reputation.average_quality_bps() / 50).min(200); // Max 200 from quality
+
+    let outcome_score = match reputation.entity_type {
+        EntityType::Agent => {
+            if reputation.disputes_filed > 0 {
+                let win_rate = (reputation.disputes_won * 100) / reputation.disputes_filed;
+                (win_rate as u16 * 3).min(300) + quality_score // Max 300 from dispute wins
+            } else {
+                150 + quality_score // No disputes, neutral
+            }
+        }
+        EntityType::Provider => provider_outcome_score(reputation, quality_score),
+    };
+
+    let score = (tx_score + volume_score + outcome_score).min(1000);
+    decay_reputation_score(score, now.saturating_sub(reputation.last_updated))
+}
+
+/// Dispute and quality part of a provider's score (max 500)
+///
+/// Providers rarely file disputes, so the agent formula would leave them at
+/// neutral. Instead every lost dispute and every poor-quality delivery
+/// counts against them: losing a sixth of all transactions forfeits half
+/// the dispute score, and each poor-quality resolution costs 50 points.
+fn provider_outcome_score(reputation: &EntityReputation, quality_score: u16) -> u16 {
+    let loss_rate = (reputation.disputes_lost.saturating_mul(100) / reputation.total_transactions)
+        .min(100) as u16;
+    let dispute_score = 300u16.saturating_sub(loss_rate * 6); // Max 300 with no lost disputes
+    let poor_quality_penalty = reputation.poor_quality_count.min(10) as u16 * 50;
+    (dispute_score + quality_score).saturating_sub(poor_quality_penalty)
+}
+
+/// Shrink a score's lead over neutral by half every `REPUTATION_HALF_LIFE`
+/// idle, interpolating linearly within a half-life
+///
+/// Scores at or below neutral are kept, so idling never clears a bad record.
+fn decay_reputation_score(score: u16, idle: i64) -> u16 {
+    if score <= NEUTRAL_REPUTATION_SCORE || idle <= 0 {
+        return score;
+    }
+
+    let half_lives = idle / REPUTATION_HALF_LIFE;
+    if half_lives >= 16 {
+        return NEUTRAL_REPUTATION_SCORE;
+    }
+    let lead = (score - NEUTRAL_REPUTATION_SCORE) as i64 >> half_lives;
+    let partial = lead * (idle % REPUTATION_HALF_LIFE) / (2 * REPUTATION_HALF_LIFE);
+    NEUTRAL_REPUTATION_SCORE + (lead - partial) as u16
+}
+
+/// Read the configured SOL/USD Switchboard feed as US cents per SOL
+fn read_sol_usd_cents(feed: &AccountInfo, expected: &Pubkey, now: i64) -> Result<u64> {
+    require!(
+        *expected != Pubkey::default() && feed.key() == *expected,
+        EscrowError::InvalidPriceFeed
+    );
+
+    let feed_data = PullFeedAccountData::parse(feed.data.borrow())
+        .map_err(|_| EscrowError::InvalidPriceFeed)?;
+
+    let age_seconds = now - feed_data.last_update_timestamp;
+    require!(
+        (0..=PRICE_FEED_MAX_AGE).contains(&age_seconds),
+        EscrowError::StaleAttestation
+    );
+
+    require!(feed_data.result.value > 0, EscrowError::InvalidPriceFeed);
+
+    // value is USD per SOL scaled by 10^18; cents need 10^2
+    let cents = feed_data.result.value / 10i128.pow(PRICE_FEED_DECIMALS - 2);
+    u64::try_from(cents).map_err(|_| error!(EscrowError::InvalidPriceFeed))
+}
+
+/// Split a quality feed result, `quality_score * 1000 + refund_percentage`,
+/// into its two percentages
+fn unpack_quality_result(value: i128) -> Option<(u8, u8)> {
+    if !(0..=100 * QUALITY_FEED_SCALE + 100).contains(&value) {
+        return None;
+    }
+    let (quality_score, refund_percentage) = (value / QUALITY_FEED_SCALE, value % QUALITY_FEED_SCALE);
+    (refund_percentage <= 100).then_some((quality_score as u8, refund_percentage as u8))
+}
+
+/// Read the namespace's Pyth quality feed as (quality score, refund percentage)
+fn read_pyth_quality_result(price_update: &PriceUpdateV2, config: &GlobalConfig) -> Result<(u8, u8)> {
+    require!(config.pyth_quality_feed_id != [0; 32], EscrowError::InvalidPythAttestation);
+
+    let price = price_update.get_price_no_older_than(
+        &Clock::get()?,
+        config.attestation_max_age_seconds as u64,
+        &config.pyth_quality_feed_id,
+    )?;
+    pyth_quality_result(price.price, price.exponent)
+}
+
+/// Decode a Pyth quality feed price, which must be published unscaled
+fn pyth_quality_result(price: i64, exponent: i32) -> Result<(u8, u8)> {
+    require!(exponent == 0, EscrowError::InvalidPythAttestation);
+    unpack_quality_result(price as i128).ok_or(error!(EscrowError::InvalidPythAttestation))
+}
+
+/// Reject attestations older than the namespace's window, or from the future
+fn require_fresh_attestation(age_seconds: i64, config: &GlobalConfig) -> Result<()> {
+    require!(
+        (0..=config.attestation_max_age_seconds).contains(&age_seconds),
+        EscrowError::StaleAttestation
+    );
+    Ok(())
+}
+
+/// Namespaces with an appeal window resolve only through `submit_resolution`,
+/// so every path that settles a ruling at once checks this first
+fn require_single_step_resolution(config: &GlobalConfig) -> Result<()> {
+    require!(
+        config.appeal_window_seconds == 0,
+        EscrowError::TwoPhaseResolutionRequired
+    );
+    Ok(())
+}
+
+/// Checks on an escrow before a quorum decides it
+///
+/// Besides fresh disputes in single-step namespaces, a quorum decides
+/// appealed resolutions, which have already had their appeal window.
+fn require_quorum_resolvable(escrow: &Escrow, config: &GlobalConfig, quality_score: u8) -> Result<()> {
+    require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+    require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+    require!(
+        !escrow.resolution_held() || escrow.appealed(),
+        EscrowError::ResolutionHeld
+    );
+    require!(
+        escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+        EscrowError::InvalidStatus
+    );
+    if !escrow.appealed() {
+        require_single_step_resolution(config)?;
+    }
+    require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+    Ok(())
+}
+
+/// Checks shared by the feed resolution paths before reading an attestation
+fn require_feed_resolvable(escrow: &Escrow, config: &GlobalConfig, quality_score: u8) -> Result<()> {
+    require_single_step_resolution(config)?;
+    require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+    require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+    require!(!escrow.resolution_held(), EscrowError::ResolutionHeld);
+    require!(
+        escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+        EscrowError::InvalidStatus
+    );
+    // Feed attestations cannot reference the agreed terms
+    require!(!escrow.has_work_agreement(), EscrowError::WorkAgreementRequired);
+    require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+    Ok(())
+}
+
+/// Anyone can deploy an account that parses as a PullFeed, so a feed is
+/// only trusted if the Switchboard program owns it and it reports to the
+/// namespace's queue. The queue account's owner stands in for the
+/// Switchboard program id, which differs between clusters.
+fn require_trusted_switchboard_feed(
+    feed: &AccountInfo,
+    feed_queue: &Pubkey,
+    queue: &AccountInfo,
+    expected_queue: &Pubkey,
+) -> Result<()> {
+    require!(
+        *expected_queue != Pubkey::default()
+            && queue.key() == *expected_queue
+            && *feed_queue == *expected_queue
+            && feed.owner == queue.owner,
+        EscrowError::UntrustedSwitchboardFeed
+    );
+    Ok(())
+}
+
+fn lamports_to_usd_cents(lamports: u64, cents_per_sol: u64) -> u128 {
+    (lamports as u128)
+        .saturating_mul(cents_per_sol as u128)
+        / LAMPORTS_PER_SOL
+}
+
+/// Read the namespace's Pyth SOL/USD feed from a price update as US cents per SOL
+fn read_pyth_sol_usd_cents(price_update: &PriceUpdateV2, feed_id: &[u8; 32]) -> Result<u64> {
+    require!(*feed_id != [0; 32], EscrowError::InvalidPriceFeed);
+
+    let price = price_update.get_price_no_older_than(&Clock::get()?, PYTH_PRICE_MAX_AGE, feed_id)?;
+    require!(price.price > 0, EscrowError::InvalidPriceFeed);
+
+    // price is USD per SOL scaled by 10^exponent; cents need 10^2
+    let scale = price.exponent + 2;
+    let cents = if scale >= 0 {
+        (price.price as u128).checked_mul(10u128.pow(scale as u32))
+    } else {
+        Some(price.price as u128 / 10u128.pow(scale.unsigned_abs()))
+    };
+    cents
+        .and_then(|cents| u64::try_from(cents).ok())
+        .filter(|cents| *cents > 0)
+        .ok_or(error!(EscrowError::InvalidPriceFeed))
+}
+
+fn usd_cents_to_lamports(usd_cents: u64, cents_per_sol: u64) -> Result<u64> {
+    let lamports = (usd_cents as u128)
+        .checked_mul(LAMPORTS_PER_SOL)
+        .ok_or(EscrowError::ArithmeticOverflow)?
+        / cents_per_sol as u128;
+    u64::try_from(lamports).map_err(|_| error!(EscrowError::AmountTooLarge))
+}
+
+/// Split an escrow into (refund to agent, payment to API), repricing USD escrows
+///
+/// A USD escrow must come with a price update. It pays the API its share of
+/// the target at that price, never more than the plain split; the rest is
+/// refunded.
+fn settlement_split(
+    escrow: &Escrow,
+    refund_percentage: u8,
+    price_update: Option<&PriceUpdateV2>,
+    config: &GlobalConfig,
+) -> Result<(u64, u64)> {
+    let cents_per_sol = settlement_price(escrow, price_update, config)?;
+    repriced_split(escrow, refund_percentage, cents_per_sol)
+}
+
+/// SOL/USD price a USD escrow settles at; `None` for lamport escrows
+fn settlement_price(
+    escrow: &Escrow,
+    price_update: Option<&PriceUpdateV2>,
+    config: &GlobalConfig,
+) -> Result<Option<u64>> {
+    if escrow.usd_target_cents.is_none() {
+        return Ok(None);
+    }
+    let price_update = price_update.ok_or(EscrowError::PriceUpdateMissing)?;
+    read_pyth_sol_usd_cents(price_update, &config.pyth_sol_usd_feed_id).map(Some)
+}
+
+/// `settlement_split` at a price already read
+fn repriced_split(
+    escrow: &Escrow,
+    refund_percentage: u8,
+    cents_per_sol: Option<u64>,
+) -> Result<(u64, u64)> {
+    let (refund_amount, payment_amount) = split_escrow_amount(escrow.open_amount(), refund_percentage)?;
+    let (Some(usd_target_cents), Some(cents_per_sol)) = (escrow.usd_target_cents, cents_per_sol) else {
+        return Ok((refund_amount, payment_amount));
+    };
+
+    let owed_cents = (usd_target_cents as u128) * (100 - refund_percentage as u128) / 100;
+    let owed = usd_cents_to_lamports(owed_cents as u64, cents_per_sol)?;
+    let payment = owed.min(payment_amount);
+    Ok((escrow.open_amount() - payment, payment))
+}
+
+/// Lamports a release moves: (surplus refunded to the agent, API's share,
+/// tip to the cranker). The API's share is net of the tip and still owes
+/// the insurance cut and protocol fee.
+fn release_split(
+    escrow: &Escrow,
+    config: &GlobalConfig,
+    cents_per_sol: Option<u64>,
+    tip_offered: u64,
+) -> Result<(u64, u64, u64)> {
+    let (surplus, payment) = repriced_split(escrow, 0, cents_per_sol)?;
+    let tip = crank_tip(payment, config.release_deduction_bps(), tip_offered)?;
+    Ok((surplus, payment - tip, tip))
+}
+
+/// Pay out a release, shared by `release_funds`, `release_and_close` and
+/// `crank_release_expired`, and mark the escrow released.
+///
+/// Refunds a USD escrow's surplus to the agent, tips the cranker, pools the
+/// insurance cut and pays the API the rest less the protocol fee. Returns
+/// the API's share before insurance and fee, and the tip.
+#[allow(clippy::too_many_arguments)]
+fn pay_release(
+    escrow: &mut Account<Escrow>,
+    agent: &AccountInfo,
+    api: &AccountInfo,
+    cranker: &AccountInfo,
+    tip_offered: u64,
+    price_update: Option<&PriceUpdateV2>,
+    insurance_fund: Option<&mut Account<InsuranceFund>>,
+    treasury: Option<&AccountInfo>,
+    config: &GlobalConfig,
+    now: i64,
+) -> Result<(u64, u64)> {
+    let cents_per_sol = settlement_price(escrow, price_update, config)?;
+    let (surplus, transfer_amount, tip) = release_split(escrow, config, cents_per_sol, tip_offered)?;
+
+    // Direct lamport manipulation throughout: the escrow PDA carries data
+    // and system_program::transfer requires empty accounts
+    refund_agent(escrow, agent, surplus)?;
+    let insurance_cut = contribute_release_insurance(escrow, insurance_fund, config, transfer_amount)?;
+    pay_api_with_fee(escrow, api, treasury, config, transfer_amount - insurance_cut)?;
+    pay_crank_tip(escrow, cranker, tip)?;
+
+    escrow.set_status(EscrowStatus::Released);
+    escrow.settled_at = Some(now);
+
+    Ok((transfer_amount, tip))
+}
+
+/// Split an escrowed amount into (refund to agent, payment to API)
+fn split_escrow_amount(amount: u64, refund_percentage: u8) -> Result<(u64, u64)> {
+    let refund_amount = (amount as u128)
+        .checked_mul(refund_percentage as u128)
+        .ok_or(EscrowError::ArithmeticOverflow)?
+        .checked_div(100)
+        .ok_or(EscrowError::ArithmeticOverflow)? as u64;
+
+    Ok((refund_amount, amount - refund_amount))
+}
+
+/// Apply a dispute outcome to both parties' reputation and emit updates.
+///
+/// The API's outcome is the inverse of the agent's: a high refund is a win for
+/// the agent and a loss for the API. A party whose reputation could not be
+/// loaded is skipped. Quality is weighted by the escrow's size, and divided
+/// again by how often the pair has dealt recently so two keys cannot farm
+/// each other's reputation.
+#[allow(clippy::too_many_arguments)]
+fn apply_dispute_reputation(
+    agent_reputation: Option<&mut EntityReputation>,
+    api_reputation: Option<&mut EntityReputation>,
+    escrow: &Escrow,
+    quality_score: u8,
+    refund_percentage: u8,
+    refund_amount: u64,
+    payment_amount: u64,
+    now: i64,
+) {
+    let weight = reputation_weight(escrow.amount);
+
+    if let Some(agent_reputation) = agent_reputation {
+        agent_reputation.total_transactions = agent_reputation.total_transactions.saturating_add(1);
+        let repeats = agent_reputation.record_counterparty(&escrow.api);
+        record_quality(agent_reputation, quality_score, weight / (1 + repeats));
+
+        // Categorize dispute outcome for agent
+        if refund_percentage >= 75 {
+            agent_reputation.disputes_won = agent_reputation.disputes_won.saturating_add(1);
+        } else if refund_percentage >= 25 {
+            agent_reputation.disputes_partial = agent_reputation.disputes_partial.saturating_add(1);
+        } else {
+            agent_reputation.disputes_lost = agent_reputation.disputes_lost.saturating_add(1);
+        }
+
+        record_outcome_stats(agent_reputation, refund_percentage >= 75, quality_score);
+        agent_reputation.total_refunds_received_lamports = agent_reputation
+            .total_refunds_received_lamports
+            .saturating_add(refund_amount);
+
+        let old_score = agent_reputation.reputation_score;
+        agent_reputation.last_updated = now;
+        agent_reputation.reputation_score = calculate_reputation_score(agent_reputation, now);
+
+        msg!("Agent reputation: {}", agent_reputation.reputation_score);
+        emit_reputation_updated(
+            agent_reputation,
+            old_score,
+            ReputationChangeReason::dispute(refund_percentage),
+            now,
+        );
+    }
+
+    if let Some(api_reputation) = api_reputation {
+        api_reputation.total_transactions = api_reputation.total_transactions.saturating_add(1);
+
+        // Quality delivered by API (inverse of refund percentage)
+        let quality_delivered = 100 - refund_percentage;
+        let repeats = api_reputation.record_counterparty(&escrow.agent);
+        record_quality(api_reputation, quality_delivered, weight / (1 + repeats));
+        if quality_score < POOR_QUALITY_THRESHOLD {
+            api_reputation.poor_quality_count = api_reputation.poor_quality_count.saturating_add(1);
+        }
+
+        if refund_percentage <= 25 {
+            api_reputation.disputes_won = api_reputation.disputes_won.saturating_add(1);
+        } else if refund_percentage <= 75 {
+            api_reputation.disputes_partial = api_reputation.disputes_partial.saturating_add(1);
+        } else {
+            api_reputation.disputes_lost = api_reputation.disputes_lost.saturating_add(1);
+        }
+
+        record_outcome_stats(api_reputation, refund_percentage <= 25, quality_delivered);
+        api_reputation.total_payments_made_lamports = api_reputation
+            .total_payments_made_lamports
+            .saturating_add(payment_amount);
+
+        let old_score = api_reputation.reputation_score;
+        api_reputation.last_updated = now;
+        api_reputation.reputation_score = calculate_reputation_score(api_reputation, now);
+
+        msg!("API reputation: {}", api_reputation.reputation_score);
+        emit_reputation_updated(
+            api_reputation,
+            old_score,
+            ReputationChangeReason::dispute(quality_delivered),
+            now,
+        );
+    }
+}
+
+/// Reputation PDA of an entity
+fn reputation_address(entity: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"reputation", entity.as_ref()], &crate::ID).0
+}
+
+/// Nonce registry PDA of a signer
+fn used_nonces_address(signer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"used_nonces", signer.as_ref()], &crate::ID).0
+}
+
+/// Service bond PDA of a provider
+fn service_bond_address(provider: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"service_bond", provider.as_ref()], &crate::ID).0
+}
+
+/// Deserialize a reputation account for a best-effort update.
+///
+/// Returns `None` and emits `ReputationUpdateSkipped` when the account is
+/// uninitialized, corrupt or tracks a different entity. Callers are expected
+/// to have checked the address.
+fn load_reputation(info: &AccountInfo, entity: &Pubkey, escrow: Pubkey) -> Option<EntityReputation> {
+    let loaded = if info.owner != &crate::ID {
+        Err(error!(EscrowError::ReputationAccountInvalid))
+    } else if info.data_len() != 8 + EntityReputation::INIT_SPACE {
+        // Pre-migration layouts can deserialize into garbage
+        Err(error!(EscrowError::UnknownReputationLayout))
+    } else {
+        info.try_borrow_data()
+            .map_err(Into::into)
+            .and_then(|data| EntityReputation::try_deserialize(&mut &data[..]))
+            .and_then(|reputation| {
+                if reputation.entity == *entity {
+                    Ok(reputation)
+                } else {
+                    Err(error!(EscrowError::ReputationAccountInvalid))
+                }
+            })
+    };
+
+    match loaded {
+        Ok(reputation) => Some(reputation),
+        Err(err) => {
+            let error_code = match &err {
+                Error::AnchorError(anchor_error) => anchor_error.error_code_number,
+                Error::ProgramError(_) => 0,
+            };
+            msg!("Warning: skipping reputation update for {}: {}", entity, err);
+            emit!(ReputationUpdateSkipped {
+                version: EVENT_VERSION,
+                escrow,
+                account: info.key(),
+                entity: *entity,
+                error_code,
+            });
+            None
+        }
+    }
+}
+
+/// Write back a reputation loaded with `load_reputation`; no-op if it was skipped
+fn store_reputation(info: &AccountInfo, reputation: Option<&EntityReputation>) -> Result<()> {
+    if let Some(reputation) = reputation {
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        reputation.try_serialize(&mut writer)?;
+    }
+
+    Ok(())
+}
+
+/// Quality weight of a resolution on an escrow of `amount` lamports.
+///
+/// Scales with the escrow up to `REPUTATION_WEIGHT_CAP`, so a 100 SOL
+/// engagement moves the average far more than a 0.001 SOL one, but no single
+/// whale escrow can swamp an entity's history. Never below 1.
+fn reputation_weight(amount: u64) -> u64 {
+    (amount.min(REPUTATION_WEIGHT_CAP) / REPUTATION_WEIGHT_UNIT).max(1)
+}
+
+/// Short, never-zero stand-in for a counterparty in `recent_counterparties`.
+///
+/// A collision can only make two keys look like one, which discounts rather
+/// than inflates reputation.
+fn counterparty_fingerprint(counterparty: &Pubkey) -> u32 {
+    let bytes = counterparty.to_bytes();
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).max(1)
+}
+
+/// Add a resolution's quality score, weighted, to the sum behind the average.
+///
+/// Rather than saturating (which would freeze the sum while the count kept
+/// growing and drag the average down), both totals are halved until the new
+/// resolution fits. Halving together keeps the average to within rounding.
+fn record_quality(reputation: &mut EntityReputation, quality: u8, weight: u64) {
+    let weight = weight.clamp(1, REPUTATION_WEIGHT_CAP / REPUTATION_WEIGHT_UNIT);
+    let contribution = quality as u64 * weight;
+    loop {
+        if let (Some(sum), Some(count)) = (
+            reputation.quality_sum.checked_add(contribution),
+            reputation.quality_count.checked_add(weight),
+        ) {
+            reputation.quality_sum = sum;
+            reputation.quality_count = count;
+            return;
+        }
+        reputation.quality_sum /= 2;
+        reputation.quality_count /= 2;
+    }
+}
+
+/// Append a resolution's score to an entity's history, if one was supplied
+fn record_score_snapshot(
+    history: Option<&mut ReputationHistory>,
+    reputation: Option<&EntityReputation>,
+    now: i64,
+) {
+    if let (Some(history), Some(reputation)) = (history, reputation) {
+        history.push(reputation.reputation_score, now);
+    }
+}
+
+/// Update win streak and best/worst quality after a resolution.
+///
+/// Must be called after `total_transactions` has been incremented so the
+/// first recorded outcome seeds both quality extremes.
+fn record_outcome_stats(reputation: &mut EntityReputation, won: bool, quality: u8) {
+    if won {
+        reputation.current_win_streak = reputation.current_win_streak.saturating_add(1);
+        reputation.longest_win_streak = reputation
+            .longest_win_streak
+            .max(reputation.current_win_streak);
+    } else {
+        reputation.current_win_streak = 0;
+    }
+
+    if reputation.total_transactions <= 1 {
+        reputation.worst_quality_received = quality;
+        reputation.best_quality_received = quality;
+    } else {
+        reputation.worst_quality_received = reputation.worst_quality_received.min(quality);
+        reputation.best_quality_received = reputation.best_quality_received.max(quality);
+    }
+}
+
+/// Settings a freshly created namespace config starts from
+fn init_config_defaults(config: &mut GlobalConfig, authority: Pubkey, namespace: Pubkey) {
+    config.authority = authority;
+    config.namespace = namespace;
+    config.strict_sig_adjacency = false;
+    config.spam_protection_deposit = 0;
+    config.sol_usd_price_feed = Pubkey::default();
+    config.max_override_duration_seconds = MAX_RATE_LIMIT_OVERRIDE;
+    config.fee_bps = 0;
+    config.treasury = Pubkey::default();
+    config.min_verifier_bond_lamports = 0;
+    config.bond_grace_period_days = 0;
+    config.min_bond_raised_at = 0;
+    config.max_dispute_window_extensions = DEFAULT_MAX_DISPUTE_WINDOW_EXTENSIONS;
+    config.insurance_fund_bps = 0;
+    config.max_insurance_payout_bps = DEFAULT_MAX_INSURANCE_PAYOUT_BPS;
+    config.max_priority_fee_lamports = 0;
+    config.usd_stablecoin_mint = Pubkey::default();
+    config.cancel_grace_period_seconds = DEFAULT_CANCEL_GRACE_PERIOD;
+    config.paused = false;
+    config.refund_curve = DEFAULT_REFUND_CURVE;
+    config.appeal_window_seconds = 0;
+    config.pyth_sol_usd_feed_id = [0; 32];
+    config.amount_tiers = DEFAULT_AMOUNT_TIERS;
+    config.dispute_bond_bps = DEFAULT_DISPUTE_BOND_BPS;
+    config.dispute_bond_to_treasury = false;
+    config.severe_refund_slash_bps = DEFAULT_SEVERE_REFUND_SLASH_BPS;
+    config.release_insurance_bps = 0;
+    config.oracle_fee_bps = 0;
+    config.switchboard_queue = Pubkey::default();
+    config.attestation_max_age_seconds = DEFAULT_ATTESTATION_MAX_AGE;
+    config.pyth_quality_feed_id = [0; 32];
+}
+
+/// `bps` basis points of `amount`, rounded down
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let share = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(EscrowError::ArithmeticOverflow)?
+        / 10_000;
+    Ok(share as u64)
+}
+
+/// Pool the namespace's insurance cut of a release, taken from the API's
+/// payment before the protocol fee. Recorded with the escrow's fees.
+///
+/// Returns the lamports pooled.
+fn contribute_release_insurance(
+    escrow: &mut Account<Escrow>,
+    insurance_fund: Option<&mut Account<InsuranceFund>>,
+    config: &GlobalConfig,
+    payment: u64,
+) -> Result<u64> {
+    let cut = bps_of(payment, config.release_insurance_bps)?;
+    if cut == 0 {
+        return Ok(0);
+    }
+    let fund = insurance_fund.ok_or(EscrowError::InsuranceFundMissing)?;
+
+    **escrow.to_account_info().try_borrow_mut_lamports()? -= cut;
+    **fund.to_account_info().try_borrow_mut_lamports()? += cut;
+    fund.balance = fund
+        .balance
+        .checked_add(cut)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    fund.total_contributed = fund.total_contributed.saturating_add(cut);
+    escrow.fees_paid = escrow
+        .fees_paid
+        .checked_add(cut)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    emit!(InsuranceFundContributed {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        agent: escrow.agent,
+        amount: cut,
+        fund_balance: fund.balance,
+        from_release: true,
+    });
+
+    Ok(cut)
+}
+
+/// Tip for releasing an expired escrow, paid out of the API's share.
+///
+/// Capped so the tip and the protocol fee together stay within
+/// `MAX_FEE_BPS`, which is what `audit_escrow` accepts as fees.
+fn crank_tip(amount: u64, fee_bps: u16, offered: u64) -> Result<u64> {
+    let headroom = bps_of(amount, MAX_FEE_BPS.saturating_sub(fee_bps))?;
+    Ok(offered.min(headroom))
+}
+
+/// Send the cranker its tip, recording it with the escrow's fees
+fn pay_crank_tip(escrow: &mut Account<Escrow>, cranker: &AccountInfo, tip: u64) -> Result<()> {
+    if tip > 0 {
+        **escrow.to_account_info().try_borrow_mut_lamports()? -= tip;
+        **cranker.try_borrow_mut_lamports()? += tip;
+    }
+
+    escrow.fees_paid = escrow
+        .fees_paid
+        .checked_add(tip)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// Pay the API out of the escrow, sending the namespace's protocol fee to its treasury.
+///
+/// Records both amounts for `audit_escrow` and returns the fee taken.
+fn pay_api_with_fee(
+    escrow: &mut Account<Escrow>,
+    api: &AccountInfo,
+    treasury: Option<&AccountInfo>,
+    config: &GlobalConfig,
+    payment: u64,
+) -> Result<u64> {
+    let fee = (payment as u128)
+        .checked_mul(config.fee_bps as u128)
+        .ok_or(EscrowError::ArithmeticOverflow)?
+        / 10_000;
+    let fee = fee as u64;
+    let net_payment = payment - fee;
+    let escrow_info = escrow.to_account_info();
+
+    if fee > 0 {
+        let treasury = treasury.ok_or(EscrowError::TreasuryMissing)?;
+        **escrow_info.try_borrow_mut_lamports()? -= fee;
+        **treasury.try_borrow_mut_lamports()? += fee;
+
+        emit!(ProtocolFeeCollected {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            namespace: config.namespace,
+            treasury: treasury.key(),
+            amount: fee,
+        });
+    }
+
+    if net_payment > 0 {
+        **escrow_info.try_borrow_mut_lamports()? -= net_payment;
+        **api.try_borrow_mut_lamports()? += net_payment;
+    }
+
+    escrow.fees_paid = escrow
+        .fees_paid
+        .checked_add(fee)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    escrow.paid_to_api = escrow
+        .paid_to_api
+        .checked_add(net_payment)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(fee)
+}
+
+/// Fail when an agent bound to an organization pays an API off its
+/// allowlist, or when a supplied allowlist does not name the API
+fn check_allowlist(
+    agent_restriction: &AccountInfo,
+    allowlist: Option<&Allowlist>,
+    agent: &Pubkey,
+    api: &Pubkey,
+) -> Result<()> {
+    if let Some(restriction) = load_agent_restriction(agent_restriction, agent)? {
+        let allowlist = allowlist.ok_or(EscrowError::AllowlistRequired)?;
+        require_keys_eq!(
+            allowlist.organization,
+            restriction.organization,
+            EscrowError::AllowlistMismatch
+        );
+    }
+    if let Some(allowlist) = allowlist {
+        require!(allowlist.contains(api), EscrowError::ApiNotAllowlisted);
+    }
+
+    Ok(())
+}
+
+/// Fail when either party has blocked the other
+///
+/// Both blocklist PDAs are always passed, so a party cannot skip the
+/// other's blocklist by leaving it out.
+fn check_blocklists(
+    agent_blocklist: &AccountInfo,
+    api_blocklist: &AccountInfo,
+    agent: &Pubkey,
+    api: &Pubkey,
+) -> Result<()> {
+    if let Some(blocklist) = load_blocklist(agent_blocklist, agent)? {
+        require!(!blocklist.contains(api), EscrowError::CounterpartyBlocked);
+    }
+    if let Some(blocklist) = load_blocklist(api_blocklist, api)? {
+        require!(!blocklist.contains(agent), EscrowError::CounterpartyBlocked);
+    }
+
+    Ok(())
+}
+
+/// Fresh `Active` state for a new escrow; callers set `bump` and any options
+fn init_escrow_state(
+    escrow: &mut Escrow,
+    agent: Pubkey,
+    api: Pubkey,
+    namespace: Pubkey,
+    amount: u64,
+    time_lock: i64,
+    transaction_id: String,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    escrow.agent = agent;
+    escrow.api = api;
+    escrow.namespace = namespace;
+    escrow.version = layout::ESCROW_VERSION;
+    escrow.flags = 0;
+    escrow.amount = amount;
+    escrow.set_status(EscrowStatus::Active);
+    escrow.created_at = now;
+    escrow.expires_at = now + time_lock;
+    escrow.transaction_id = transaction_id;
+    escrow.assigned_verifier = None;
+    escrow.spam_deposit = 0;
+    escrow.response_hash = None;
+    escrow.delivered_at = None;
+    escrow.assessed_content_hash = None;
+    escrow.time_lock = time_lock;
+    escrow.dispute_window = DISPUTE_WINDOW;
+    escrow.dispute_window_extension_count = 0;
+    escrow.priority_fee = 0;
+    escrow.dispute_bond = 0;
+    // A closed escrow can only be re-initialized in a later slot, so old
+    // signatures never match the new escrow
+    escrow.resolution_nonce = clock.slot;
+    escrow.appeal_deadline = None;
+    escrow.first_verifier = None;
+    escrow.resolution_signature = None;
+    escrow.usd_target_cents = None;
+    escrow.streamed_amount = 0;
+    escrow.arbitrator = None;
+    escrow.escalated_by = None;
+    escrow.arbitration_bond = 0;
+    escrow.payment_reference = None;
+    escrow.request_hash = None;
+    escrow.refund_recipient = None;
+    escrow.delegate = None;
+    escrow.metadata_uri = None;
+    escrow.disputed_at = None;
+    escrow.dispute_reason = None;
+    escrow.evidence_hash = None;
+    escrow.provider_evidence_hash = None;
+    escrow.provider_comment_uri = None;
+    escrow.stale_dispute_terms = None;
+    escrow.release_tip_lamports = 0;
+    escrow.refund_curve = None;
+    escrow.quality_sub_scores = None;
+    escrow.paid_to_api = 0;
+    escrow.refunded_to_agent = 0;
+    escrow.fees_paid = 0;
+    escrow.mint = None;
+    escrow.settled_at = None;
+
+    Ok(())
+}
+
+/// Accounts a new SOL escrow is checked against and funded from; gathered
+/// from `InitializeEscrow`, `InitializeEscrowCpi` or, for session keys,
+/// `InitializeEscrowWithSession`
+struct OpenEscrowAccounts<'a, 'info> {
+    escrow: &'a mut Account<'info, Escrow>,
+    escrow_bump: u8,
+    /// Agent the escrow belongs to
+    agent: Pubkey,
+    /// Funds the escrow: the agent itself, one of its session keys or a
+    /// fee payer of a CPI caller
+    payer: &'a Signer<'info>,
+    api: &'a AccountInfo<'info>,
+    config: &'a GlobalConfig,
+    spam_vault: Option<&'a mut Account<'info, SpamProtectionVault>>,
+    spam_vault_bump: Option<u8>,
+    price_feed: Option<&'a AccountInfo<'info>>,
+    agent_blocklist: &'a AccountInfo<'info>,
+    api_blocklist: &'a AccountInfo<'info>,
+    agent_restriction: &'a AccountInfo<'info>,
+    allowlist: Option<&'a Allowlist>,
+    org_member: &'a AccountInfo<'info>,
+    agent_reputation: Option<&'a mut EntityReputation>,
+    api_reputation: Option<&'a mut EntityReputation>,
+    insurance_fund: Option<&'a mut Account<'info, InsuranceFund>>,
+    service_bond: Option<&'a ServiceBond>,
+    api_penalties: &'a AccountInfo<'info>,
+    agent_rate_limiter: &'a AccountInfo<'info>,
+    system_program: &'a Program<'info, System>,
+}
+
+impl<'info> InitializeEscrow<'info> {
+    fn open_accounts(&mut self, bumps: &InitializeEscrowBumps) -> OpenEscrowAccounts<'_, 'info> {
+        OpenEscrowAccounts {
+            escrow: &mut self.escrow,
+            escrow_bump: bumps.escrow,
+            agent: self.agent.key(),
+            payer: &self.agent,
+            api: &self.api,
+            config: &self.config,
+            spam_vault: self.spam_vault.as_mut(),
+            spam_vault_bump: bumps.spam_vault,
+            price_feed: self.price_feed.as_ref(),
+            agent_blocklist: &self.agent_blocklist,
+            api_blocklist: &self.api_blocklist,
+            agent_restriction: &self.agent_restriction,
+            allowlist: self.allowlist.as_deref(),
+            org_member: &self.org_member,
+            agent_reputation: self.agent_reputation.as_deref_mut(),
+            api_reputation: self.api_reputation.as_deref_mut(),
+            insurance_fund: self.insurance_fund.as_mut(),
+            service_bond: self.service_bond.as_deref(),
+            api_penalties: &self.api_penalties,
+            agent_rate_limiter: &self.agent_rate_limiter,
+            system_program: &self.system_program,
+        }
+    }
+}
+
+impl<'info> InitializeEscrowCpi<'info> {
+    fn open_accounts(&mut self, bumps: &InitializeEscrowCpiBumps) -> OpenEscrowAccounts<'_, 'info> {
+        OpenEscrowAccounts {
+            escrow: &mut self.escrow,
+            escrow_bump: bumps.escrow,
+            agent: self.agent.key(),
+            payer: &self.payer,
+            api: &self.api,
+            config: &self.config,
+            spam_vault: self.spam_vault.as_mut(),
+            spam_vault_bump: bumps.spam_vault,
+            price_feed: self.price_feed.as_ref(),
+            agent_blocklist: &self.agent_blocklist,
+            api_blocklist: &self.api_blocklist,
+            agent_restriction: &self.agent_restriction,
+            allowlist: self.allowlist.as_deref(),
+            org_member: &self.org_member,
+            agent_reputation: self.agent_reputation.as_deref_mut(),
+            api_reputation: self.api_reputation.as_deref_mut(),
+            insurance_fund: self.insurance_fund.as_mut(),
+            service_bond: self.service_bond.as_deref(),
+            api_penalties: &self.api_penalties,
+            agent_rate_limiter: &self.agent_rate_limiter,
+            system_program: &self.system_program,
+        }
+    }
+}
+
+impl<'info> InitializeEscrowWithSession<'info> {
+    fn open_accounts(
+        &mut self,
+        bumps: &InitializeEscrowWithSessionBumps,
+    ) -> OpenEscrowAccounts<'_, 'info> {
+        OpenEscrowAccounts {
+            escrow: &mut self.escrow,
+            escrow_bump: bumps.escrow,
+            agent: self.agent.key(),
+            payer: &self.session_signer,
+            api: &self.api,
+            config: &self.config,
+            spam_vault: self.spam_vault.as_mut(),
+            spam_vault_bump: bumps.spam_vault,
+            price_feed: self.price_feed.as_ref(),
+            agent_blocklist: &self.agent_blocklist,
+            api_blocklist: &self.api_blocklist,
+            agent_restriction: &self.agent_restriction,
+            allowlist: self.allowlist.as_deref(),
+            org_member: &self.org_member,
+            agent_reputation: self.agent_reputation.as_deref_mut(),
+            api_reputation: self.api_reputation.as_deref_mut(),
+            insurance_fund: self.insurance_fund.as_mut(),
+            service_bond: self.service_bond.as_deref(),
+            api_penalties: &self.api_penalties,
+            agent_rate_limiter: &self.agent_rate_limiter,
+            system_program: &self.system_program,
+        }
+    }
+}
+
+/// Validate and fund a new SOL escrow; shared by the lamport, USD and session entry points
+#[allow(clippy::too_many_arguments)]
+fn open_sol_escrow(
+    mut accounts: OpenEscrowAccounts,
+    amount: u64,
+    time_lock: i64,
+    transaction_id: String,
+    no_dispute: bool,
+    namespace: Pubkey,
+    payment_reference: Option<PaymentReference>,
+    request_hash: Option<[u8; 32]>,
+    refund_recipient: Option<Pubkey>,
+    metadata_uri: Option<String>,
+    stale_dispute_terms: Option<StaleDisputeTerms>,
+) -> Result<()> {
+    require!(!accounts.config.paused, EscrowError::ProtocolPaused);
+
+    // Validate inputs
+    require!(
+        amount >= MIN_ESCROW_AMOUNT,
+        EscrowError::InvalidAmount
+    );
+    require!(
+        amount <= MAX_ESCROW_AMOUNT,
+        EscrowError::AmountTooLarge
+    );
+    require!(
+        time_lock >= MIN_TIME_LOCK && time_lock <= MAX_TIME_LOCK,
+        EscrowError::InvalidTimeLock
+    );
+    require!(
+        !transaction_id.is_empty() && transaction_id.len() <= 64,
+        EscrowError::InvalidTransactionId
+    );
+    if let Some(reference) = &payment_reference {
+        require!(
+            reference.challenge_nonce != [0; 16],
+            EscrowError::InvalidChallengeNonce
+        );
+    }
+    if let Some(uri) = &metadata_uri {
+        require!(uri.len() <= MAX_METADATA_URI_LEN, EscrowError::MetadataUriTooLong);
+    }
+    if let Some(terms) = &stale_dispute_terms {
+        require!(
+            (MIN_VERIFIER_TIMEOUT..=MAX_TIME_LOCK).contains(&terms.verifier_timeout)
+                && terms.refund_percentage <= 100,
+            EscrowError::InvalidStaleDisputeTerms
+        );
+    }
+
+    // Either party may refuse the other via their blocklist
+    check_blocklists(
+        accounts.agent_blocklist,
+        accounts.api_blocklist,
+        &accounts.agent,
+        &accounts.api.key(),
+    )?;
+    check_allowlist(
+        accounts.agent_restriction,
+        accounts.allowlist,
+        &accounts.agent,
+        &accounts.api.key(),
+    )?;
+    if let Some(penalties) = load_provider_penalties(accounts.api_penalties, &accounts.api.key())? {
+        require!(!penalties.suspended, EscrowError::ProviderSuspended);
+    }
+    charge_rate_limit(
+        accounts.agent_rate_limiter,
+        &accounts.agent,
+        RateLimitedAction::Transaction,
+    )?;
+
+    let clock = Clock::get()?;
+    check_amount_tier(
+        accounts.config,
+        accounts.agent_reputation.as_deref(),
+        amount,
+        clock.unix_timestamp,
+    )?;
+    charge_organization_spend(
+        accounts.org_member,
+        &accounts.agent,
+        amount,
+        clock.unix_timestamp,
+    )?;
+
+    // Initialize escrow state
+    {
+        let escrow = &mut *accounts.escrow;
+        init_escrow_state(
+            escrow,
+            accounts.agent,
+            accounts.api.key(),
+            namespace,
+            amount,
+            time_lock,
+            transaction_id.clone(),
+        )?;
+        escrow.bump = accounts.escrow_bump;
+        escrow.set_no_dispute(no_dispute);
+        escrow.payment_reference = payment_reference;
+        escrow.request_hash = request_hash;
+        escrow.refund_recipient = refund_recipient;
+        escrow.metadata_uri = metadata_uri;
+        escrow.stale_dispute_terms = stale_dispute_terms;
+    }
+
+    // Anti-griefing: rapid escrow creation requires a refundable deposit
+    charge_spam_deposit(
+        accounts.spam_vault,
+        accounts.spam_vault_bump,
+        accounts.escrow,
+        accounts.agent,
+        accounts.payer,
+        accounts.system_program,
+        accounts.config.spam_protection_deposit,
+    )?;
+
+    // A share of every escrow is pooled to compensate agents when oracles fail
+    let contribution = bps_of(amount, accounts.config.insurance_fund_bps)?;
+    if contribution > 0 {
+        let fund = accounts
+            .insurance_fund
+            .as_deref_mut()
+            .ok_or(EscrowError::InsuranceFundMissing)?;
+        let cpi_context = CpiContext::new(
+            accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: accounts.payer.to_account_info(),
+                to: fund.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, contribution)?;
+
+        fund.balance = fund
+            .balance
+            .checked_add(contribution)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        fund.total_contributed = fund.total_contributed.saturating_add(contribution);
+
+        msg!("Insurance contribution: {} lamports", contribution);
+        emit!(InsuranceFundContributed {
+            version: EVENT_VERSION,
+            escrow: accounts.escrow.key(),
+            agent: accounts.agent,
+            amount: contribution,
+            fund_balance: fund.balance,
+            from_release: false,
+        });
+    }
+
+    // Supplying the API's service bond demands cover: it must meet the
+    // provider's declared minimum and not be on its way out
+    let mut service_bond = 0;
+    if let (false, Some(bond)) = (no_dispute, accounts.service_bond) {
+        require!(
+            bond.withdrawal_requested_at.is_none(),
+            EscrowError::ServiceBondWithdrawalPending
+        );
+        require!(
+            bond.bonded_lamports > 0 && bond.bonded_lamports >= bond.terms.min_active_bond,
+            EscrowError::ServiceBondBelowMinimum
+        );
+        accounts.escrow.set_service_bonded(true);
+        service_bond = bond.bonded_lamports;
+    }
+
+    // Volume counts for every supplied reputation, whether or not the
+    // escrow's exposure is tracked. USD cents normalize SOL and
+    // token-denominated escrows.
+    let cents_per_sol = match accounts.price_feed {
+        Some(feed) => Some(read_sol_usd_cents(
+            feed,
+            &accounts.config.sol_usd_price_feed,
+            clock.unix_timestamp,
+        )?),
+        None => None,
+    };
+    for reputation in [
+        accounts.agent_reputation.as_deref_mut(),
+        accounts.api_reputation.as_deref_mut(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        reputation.total_volume_lamports = reputation
+            .total_volume_lamports
+            .saturating_add(amount as u128);
+
+        if let (Some(cents_per_sol), Some(feed)) = (cents_per_sol, accounts.price_feed) {
+            reputation.volume_usd_cents = reputation
+                .volume_usd_cents
+                .saturating_add(lamports_to_usd_cents(amount, cents_per_sol));
+            reputation.last_price_feed_used = Some(feed.key());
+            reputation.price_at_last_update = cents_per_sol;
+        }
+    }
+
+    // Track open exposure when both reputation accounts are supplied.
+    // No-dispute escrows cannot be disputed, so they carry no exposure.
+    if let (false, Some(agent_reputation), Some(api_reputation)) = (
+        no_dispute,
+        accounts.agent_reputation,
+        accounts.api_reputation,
+    ) {
+        agent_reputation.locked_value = agent_reputation
+            .locked_value
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        api_reputation.locked_value = api_reputation
+            .locked_value
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        accounts.escrow.set_exposure_tracked(true);
+    }
+
+    // Verify transfer amount covers rent before executing
+    let rent = Rent::get()?;
+    let min_rent = rent.minimum_balance(8 + Escrow::INIT_SPACE);
+    require!(
+        amount >= min_rent,
+        EscrowError::InsufficientRentReserve
+    );
+
+    // Transfer SOL to escrow PDA
+    let cpi_context = CpiContext::new(
+        accounts.system_program.to_account_info(),
+        anchor_lang::system_program::Transfer {
+            from: accounts.payer.to_account_info(),
+            to: accounts.escrow.to_account_info(),
+        },
+    );
+    anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+    let expires_at = clock.unix_timestamp + time_lock;
+    msg!("Escrow initialized: {} SOL locked", amount as f64 / 1_000_000_000.0);
+    msg!("Expires at: {}", expires_at);
+
+    let escrow = &accounts.escrow;
+    emit!(EscrowInitialized {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        agent: escrow.agent,
+        api: escrow.api,
+        amount: escrow.amount,
+        expires_at: escrow.expires_at,
+        transaction_id: transaction_id,
+        service_bond,
+        payment_reference,
+        mint: None,
+        metadata_uri: escrow.metadata_uri.clone(),
+        stale_dispute_terms: escrow.stale_dispute_terms,
+    });
+
+    Ok(())
+}
+
+/// Count a new escrow against the agent's spam window, charging the
+/// refundable deposit once the free allowance is used up
+///
+/// Namespaces without a deposit keep no window, so their agents never pay
+/// rent for a vault.
+fn charge_spam_deposit<'info>(
+    vault: Option<&mut Account<'info, SpamProtectionVault>>,
+    vault_bump: Option<u8>,
+    escrow: &mut Account<'info, Escrow>,
+    agent: Pubkey,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    deposit: u64,
+) -> Result<()> {
+    if deposit == 0 {
+        return Ok(());
+    }
+    let vault = vault.ok_or(EscrowError::SpamVaultMissing)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    if vault.agent == Pubkey::default() {
+        vault.agent = agent;
+        vault.bump = vault_bump.ok_or(EscrowError::SpamVaultMissing)?;
+    }
+
+    if now - vault.window_start >= SPAM_WINDOW {
+        vault.window_start = now;
+        vault.escrows_in_window = 0;
+    }
+    vault.escrows_in_window = vault.escrows_in_window.saturating_add(1);
+
+    if vault.escrows_in_window <= SPAM_FREE_ESCROWS_PER_HOUR {
+        return Ok(());
+    }
+
+    let cpi_context = CpiContext::new(
+        system_program.to_account_info(),
+        anchor_lang::system_program::Transfer {
+            from: payer.to_account_info(),
+            to: vault.to_account_info(),
+        },
+    );
+    anchor_lang::system_program::transfer(cpi_context, deposit)?;
+
+    vault.held_lamports = vault
+        .held_lamports
+        .checked_add(deposit)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    vault.deposit_count = vault.deposit_count.saturating_add(1);
+    escrow.spam_deposit = deposit;
+
+    msg!("Spam protection deposit charged: {} lamports", deposit);
+    emit!(SpamProtectionDepositCharged {
+        version: EVENT_VERSION,
+        agent: vault.agent,
+        escrow: escrow.key(),
+        amount: deposit,
+        escrows_in_window: vault.escrows_in_window,
+    });
+
+    Ok(())
+}
+
+/// Token vault of an SPL escrow and what is needed to move tokens out of it
+struct EscrowVault<'a, 'info> {
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    mint: &'a InterfaceAccount<'info, Mint>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    /// The mint's transfer hook accounts, if it has a hook
+    hook_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'info> EscrowVault<'_, 'info> {
+    /// Transfer tokens out of the vault, signed by the escrow PDA
+    ///
+    /// `amount` is what leaves the vault; a Token-2022 transfer fee comes out
+    /// of what `to` receives.
+    fn transfer(&self, escrow: &Account<'info, Escrow>, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let seeds: &[&[u8]] = &[b"escrow", escrow.transaction_id.as_bytes(), &[escrow.bump]];
+        TokenTransfer {
+            token_program: &self.token_program.to_account_info(),
+            from: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to,
+            authority: escrow.to_account_info(),
+            hook_accounts: self.hook_accounts,
+        }
+        .invoke(amount, self.mint.decimals, &[seeds])
+    }
+}
+
+/// Token counterpart of `pay_api_with_fee`; the fee goes to the treasury's
+/// token account for the escrow's mint
+fn pay_api_with_fee_spl<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    vault: &EscrowVault<'_, 'info>,
+    api_token_account: AccountInfo<'info>,
+    treasury_token_account: Option<AccountInfo<'info>>,
+    config: &GlobalConfig,
+    payment: u64,
+) -> Result<u64> {
+    let fee = bps_of(payment, config.fee_bps)?;
+    let net_payment = payment - fee;
+
+    if fee > 0 {
+        let treasury = treasury_token_account.ok_or(EscrowError::TreasuryMissing)?;
+        let treasury_key = treasury.key();
+        vault.transfer(escrow, treasury, fee)?;
+
+        emit!(ProtocolFeeCollected {
+            version: EVENT_VERSION,
+            escrow: escrow.key(),
+            namespace: config.namespace,
+            treasury: treasury_key,
+            amount: fee,
+        });
+    }
+    vault.transfer(escrow, api_token_account, net_payment)?;
+
+    escrow.fees_paid = escrow
+        .fees_paid
+        .checked_add(fee)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    escrow.paid_to_api = escrow
+        .paid_to_api
+        .checked_add(net_payment)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(fee)
+}
+
+/// Token counterpart of `refund_agent`
+fn refund_agent_spl<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    vault: &EscrowVault<'_, 'info>,
+    agent_token_account: AccountInfo<'info>,
+    refund: u64,
+) -> Result<()> {
+    vault.transfer(escrow, agent_token_account, refund)?;
+
+    escrow.refunded_to_agent = escrow
+        .refunded_to_agent
+        .checked_add(refund)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// USD cents of a stablecoin amount, assuming a $1 peg
+fn stablecoin_to_usd_cents(amount: u64, decimals: u8) -> u128 {
+    (amount as u128).saturating_mul(100) / 10u128.pow(decimals as u32)
+}
+
+/// Wallet an escrow's dispute refunds are paid to: the refund recipient it
+/// names, whose account must then be supplied, or else the agent
+fn refund_destination<'a, 'info>(
+    escrow: &Escrow,
+    agent: &'a AccountInfo<'info>,
+    refund_recipient: Option<&'a AccountInfo<'info>>,
+) -> Result<&'a AccountInfo<'info>> {
+    match escrow.refund_recipient {
+        None => Ok(agent),
+        Some(recipient) => refund_recipient
+            .filter(|info| info.key() == recipient)
+            .ok_or_else(|| error!(EscrowError::RefundRecipientMismatch)),
+    }
+}
+
+/// Refund the agent out of the escrow, recording it for `audit_escrow`
+fn refund_agent(escrow: &mut Account<Escrow>, agent: &AccountInfo, refund: u64) -> Result<()> {
+    // Direct lamport manipulation: the escrow PDA carries data, so
+    // system_program::transfer cannot debit it
+    if refund > 0 {
+        **escrow.to_account_info().try_borrow_mut_lamports()? -= refund;
+        **agent.try_borrow_mut_lamports()? += refund;
+    }
+
+    escrow.refunded_to_agent = escrow
+        .refunded_to_agent
+        .checked_add(refund)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// What `claim_insurance` pays for an escrow resolved around its provider's
+/// suspension: the unrefunded amount, capped at `max_payout_bps` of it
+fn suspension_claim_payout(
+    escrow: &Escrow,
+    penalties: &ProviderPenalties,
+    max_payout_bps: u16,
+) -> Result<u64> {
+    require!(
+        escrow.status() == EscrowStatus::Resolved,
+        EscrowError::InsuranceClaimNotEligible
+    );
+    let settled_at = escrow.settled_at.ok_or(EscrowError::InsuranceClaimNotEligible)?;
+    let suspended_at = penalties
+        .suspended_at()
+        .ok_or(EscrowError::InsuranceClaimNotEligible)?;
+    require!(
+        escrow.created_at <= suspended_at && suspended_at <= settled_at,
+        EscrowError::InsuranceClaimNotEligible
+    );
+
+    let unrefunded = escrow.amount.saturating_sub(escrow.refunded_to_agent);
+    require!(unrefunded > 0, EscrowError::InsuranceClaimNotEligible);
+    Ok(unrefunded.min(bps_of(escrow.amount, max_payout_bps)?))
+}
+
+/// Payout totals a terminal escrow's stored fields imply.
+///
+/// The protocol fee rate may have changed since settlement, so the recorded
+/// fee is accepted up to `MAX_FEE_BPS` of the API's share and the API is
+/// expected to have received the rest.
+fn expected_accounting(escrow: &Escrow) -> Result<EscrowAccounting> {
+    let (refund, payment) = match escrow.status() {
+        EscrowStatus::Released => (0, escrow.amount),
+        // Milestones split individually; the refunds must leave the rest for the API
+        EscrowStatus::Resolved if escrow.has_milestones() => {
+            let splittable = escrow.amount - escrow.oracle_fee_paid;
+            let refund = escrow.refunded_to_agent.min(splittable);
+            (refund, splittable - refund)
+        }
+        // Only the balance a stream had not paid out yet was split
+        EscrowStatus::Resolved => {
+            let refund_percentage = escrow.refund_percentage.ok_or(EscrowError::InvalidStatus)?;
+            let (refund, _) = split_escrow_amount(escrow.open_amount(), refund_percentage)?;
+            (refund, escrow.amount - escrow.oracle_fee_paid - refund)
+        }
+        _ => return err!(EscrowError::InvalidStatus),
+    };
+    // Repricing a USD escrow only ever moves lamports from the API to the agent
+    let (refund, payment) = if escrow.usd_target_cents.is_some() {
+        let repriced = escrow.refunded_to_agent.clamp(refund, escrow.amount);
+        (repriced, payment - (repriced - refund))
+    } else {
+        (refund, payment)
+    };
+
+    let fees_paid = escrow.fees_paid.min(bps_of(payment, MAX_FEE_BPS)?);
+    Ok(EscrowAccounting {
+        refunded_to_agent: refund,
+        paid_to_api: payment - fees_paid,
+        fees_paid,
+    })
+}
+
+/// Shared body of `resolve_dispute`, `resolve_with_relayer` and
+/// `resolve_dispute_scored`, which alone passes `sub_scores`
+fn resolve_with_signature(
+    ctx: Context<ResolveDispute>,
+    quality_score: u8,
+    sub_scores: Option<[u8; SCORE_DIMENSIONS]>,
+    content_hash: [u8; 32],
+    nonce: u64,
+    signature: [u8; 64],
+    reimburse_relayer: bool,
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, EscrowError::ProtocolPaused);
+    require_single_step_resolution(&ctx.accounts.config)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+
+    require!(!escrow.no_dispute(), EscrowError::DisputesDisabledForEscrow);
+    require!(!escrow.has_milestones(), EscrowError::MilestonePlanActive);
+    require!(!escrow.resolution_held(), EscrowError::ResolutionHeld);
+    require!(
+        escrow.status() == EscrowStatus::Active || escrow.status() == EscrowStatus::Disputed,
+        EscrowError::InvalidStatus
+    );
+
+    require!(quality_score <= 100, EscrowError::InvalidQualityScore);
+
+    if let Some(assigned) = escrow.assigned_verifier {
+        require_keys_eq!(
+            ctx.accounts.verifier.key(),
+            assigned,
+            EscrowError::VerifierNotAssigned
+        );
+    }
+
+    // The verifier must have scored the payload the API committed to
+    if let Some(response_hash) = escrow.response_hash {
+        require!(
+            response_hash == content_hash,
+            EscrowError::AssessedWrongContent
+        );
+    }
+
+    let work_agreement = ctx.accounts.work_agreement.as_deref();
+    let quality_score = resolution_quality(work_agreement, quality_score, sub_scores.as_ref())?;
+    // The split follows the namespace's curve, not the caller
+    let refund_percentage =
+        agreed_refund_percentage(&ctx.accounts.config, escrow, work_agreement, quality_score)?;
+
+    // Only keys approved for the namespace may resolve its disputes
+    require_registered_verifier(&ctx.accounts.verifier_registry, ctx.accounts.verifier.key)?;
+
+    // Verify signature from verifier oracle
+    let message = build_resolution_message(
+        &escrow.key(),
+        escrow,
+        nonce,
+        work_agreement,
+        quality_score,
+        &content_hash,
+        sub_scores.as_ref(),
+    );
+    let message_bytes = message.as_bytes();
+
+    // Verify Ed25519 signature from the instructions sysvar
+    verify_ed25519_signature(
+        &ctx.accounts.instructions_sysvar,
+        &signature,
+        &mut ctx.accounts.verifier_nonces,
+        nonce,
+        message_bytes,
+        0,
+        ctx.accounts.config.strict_sig_adjacency,
+    )?;
+
+    msg!("Verifier: {}", ctx.accounts.verifier.key());
+    msg!("Quality Score: {}", quality_score);
+    msg!("Refund: {}%", refund_percentage);
+
+    escrow.quality_sub_scores = sub_scores;
+    pay_oracle_fee(escrow, &ctx.accounts.verifier, &ctx.accounts.config)?;
+    if let (true, Some(relayer)) = (reimburse_relayer, ctx.accounts.relayer.as_ref()) {
+        pay_relayer_fee(escrow, relayer)?;
+    }
+
+    let split = settlement_split(
+        escrow,
+        refund_percentage,
+        ctx.accounts.pyth_price_update.as_deref(),
+        &ctx.accounts.config,
+    )?;
+    apply_resolution(
+        SettlementAccounts {
+            escrow: &mut ctx.accounts.escrow,
+            agent: &ctx.accounts.agent,
+            refund_recipient: ctx.accounts.refund_recipient.as_deref(),
+            api: &ctx.accounts.api,
+            treasury: ctx.accounts.treasury.as_ref(),
+            config: &ctx.accounts.config,
+            priority_fee_recipient: &ctx.accounts.verifier,
+            agent_reputation: &ctx.accounts.agent_reputation,
+            api_reputation: &ctx.accounts.api_reputation,
+            agent_reputation_history: ctx.accounts.agent_reputation_history.as_deref_mut(),
+            api_reputation_history: ctx.accounts.api_reputation_history.as_deref_mut(),
+            verifier_oracle: ctx.accounts.verifier_oracle.as_deref_mut(),
+            service_bond: ctx.accounts.service_bond.as_mut(),
+            api_penalties: Some(&ctx.accounts.api_penalties),
+        },
+        Resolution {
+            quality_score,
+            refund_percentage,
+            split,
+            content_hash: Some(content_hash),
+            resolver: ctx.accounts.verifier.key(),
+            method: ResolutionMethod::Ed25519,
+        },
+    )?;
+
+    msg!("Dispute resolved!");
+
+    Ok(())
+}
+
+/// Pay the resolving verifier `config.oracle_fee_bps` of the open amount,
+/// before it is split, so both parties bear the fee in proportion
+fn pay_oracle_fee(escrow: &mut Account<Escrow>, verifier: &AccountInfo, config: &GlobalConfig) -> Result<()> {
+    let fee = bps_of(escrow.open_amount(), config.oracle_fee_bps)?;
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let escrow_info = escrow.to_account_info();
+    require_rent_exempt_after(&escrow_info, fee)?;
+    **escrow_info.try_borrow_mut_lamports()? -= fee;
+    **verifier.try_borrow_mut_lamports()? += fee;
+    record_oracle_fee(escrow, verifier.key(), fee)
+}
+
+/// Reimburse the relayer that submitted a resolution, out of the open amount
+/// like the oracle fee
+fn pay_relayer_fee(escrow: &mut Account<Escrow>, relayer: &AccountInfo) -> Result<()> {
+    let fee = RELAYER_FEE_LAMPORTS.min(escrow.open_amount());
+    let escrow_info = escrow.to_account_info();
+    require_rent_exempt_after(&escrow_info, fee)?;
+    **escrow_info.try_borrow_mut_lamports()? -= fee;
+    **relayer.try_borrow_mut_lamports()? += fee;
+
+    escrow.oracle_fee_paid = escrow
+        .oracle_fee_paid
+        .checked_add(fee)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    emit!(RelayerReimbursed {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        relayer: relayer.key(),
+        amount: fee,
+    });
+
+    Ok(())
+}
+
+/// Token counterpart of `pay_oracle_fee`
+fn pay_oracle_fee_spl<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    vault: &EscrowVault<'_, 'info>,
+    verifier: Pubkey,
+    verifier_token_account: Option<AccountInfo<'info>>,
+    config: &GlobalConfig,
+) -> Result<()> {
+    let fee = bps_of(escrow.open_amount(), config.oracle_fee_bps)?;
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let token_account = verifier_token_account.ok_or(EscrowError::VerifierTokenAccountMissing)?;
+    vault.transfer(escrow, token_account, fee)?;
+    record_oracle_fee(escrow, verifier, fee)
+}
+
+fn record_oracle_fee(escrow: &mut Account<Escrow>, verifier: Pubkey, fee: u64) -> Result<()> {
+    escrow.oracle_fee_paid = escrow
+        .oracle_fee_paid
+        .checked_add(fee)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    emit!(OracleFeePaid {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        verifier,
+        amount: fee,
+    });
+
+    Ok(())
+}
+
+/// Pay out an escrow's priority fee: to the verifier that resolved the
+/// dispute, or back to the agent when no verifier did
+fn disburse_priority_fee(escrow: &Account<Escrow>, recipient: &AccountInfo) -> Result<()> {
+    let fee = escrow.priority_fee;
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let escrow_info = escrow.to_account_info();
+    require_rent_exempt_after(&escrow_info, fee)?;
+    **escrow_info.try_borrow_mut_lamports()? -= fee;
+    **recipient.try_borrow_mut_lamports()? += fee;
+
+    emit!(PriorityFeeDisbursed {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        recipient: recipient.key(),
+        amount: fee,
+    });
+
+    Ok(())
+}
+
+/// Whether a resolution refunds enough to give the agent its dispute bond back
+fn dispute_bond_returned(refund_percentage: u8) -> bool {
+    refund_percentage >= DISPUTE_BOND_REFUND_THRESHOLD
+}
+
+/// Pay out the dispute cost `mark_disputed` escrowed: back to the agent when
+/// `returned`, otherwise forfeited to the API, or to the treasury when
+/// `config.dispute_bond_to_treasury` is set
+fn settle_dispute_bond<'a, 'info>(
+    escrow: &Account<Escrow>,
+    agent: &'a AccountInfo<'info>,
+    api: &'a AccountInfo<'info>,
+    treasury: Option<&'a AccountInfo<'info>>,
+    config: &GlobalConfig,
+    returned: bool,
+) -> Result<()> {
+    let bond = escrow.dispute_bond;
+    if bond == 0 {
+        return Ok(());
+    }
+
+    let recipient = if returned {
+        agent
+    } else if config.dispute_bond_to_treasury {
+        treasury.ok_or(EscrowError::TreasuryMissing)?
+    } else {
+        api
+    };
+    let escrow_info = escrow.to_account_info();
+    require_rent_exempt_after(&escrow_info, bond)?;
+    **escrow_info.try_borrow_mut_lamports()? -= bond;
+    **recipient.try_borrow_mut_lamports()? += bond;
+
+    emit!(DisputeBondSettled {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        recipient: recipient.key(),
+        amount: bond,
+        returned,
+    });
+
+    Ok(())
+}
+
+/// Whether an arbitrator's ruling moved the refund toward the party that
+/// escalated, entitling them to their arbitration bond back
+fn arbitration_bond_returned(escalator_is_agent: bool, oracle_refund: u8, ruling: u8) -> bool {
+    if escalator_is_agent {
+        ruling > oracle_refund
+    } else {
+        ruling < oracle_refund
+    }
+}
+
+/// End an escalation, paying out the bond `escalate_to_arbitrator` escrowed:
+/// back to the escalating party when `returned`, to the other party otherwise
+fn settle_arbitration_bond<'info>(
+    escrow: &mut Account<Escrow>,
+    agent: &AccountInfo<'info>,
+    api: &AccountInfo<'info>,
+    returned: bool,
+) -> Result<()> {
+    let bond = escrow.arbitration_bond;
+    let Some(escalated_by) = escrow.escalated_by else {
+        return Ok(());
+    };
+    escrow.set_escalated(false);
+    if bond == 0 {
+        return Ok(());
+    }
+
+    let recipient = if returned == (escalated_by == escrow.agent) { agent } else { api };
+    let escrow_info = escrow.to_account_info();
+    require_rent_exempt_after(&escrow_info, bond)?;
+    **escrow_info.try_borrow_mut_lamports()? -= bond;
+    **recipient.try_borrow_mut_lamports()? += bond;
+    escrow.arbitration_bond = 0;
+
+    emit!(ArbitrationBondSettled {
+        version: EVENT_VERSION,
+        escrow: escrow.key(),
+        recipient: recipient.key(),
+        amount: bond,
+        returned,
+    });
+
+    Ok(())
+}
+
+/// Slash the API's service bond to the agent when a covered escrow resolves
+/// below the bond's quality threshold, or refunds at least
+/// `SEVERE_REFUND_PERCENTAGE` percent.
+///
+/// Returns the lamports slashed.
+fn slash_service_bond(
+    escrow: &Account<Escrow>,
+    service_bond: Option<&mut Account<ServiceBond>>,
+    agent: &AccountInfo,
+    config: &GlobalConfig,
+    quality_score: u8,
+    refund_percentage: u8,
+) -> Result<u64> {
+    if !escrow.service_bonded() {
+        return Ok(0);
+    }
+    let bond = service_bond.ok_or(EscrowError::ServiceBondMissing)?;
+    if !bond.covers(escrow.created_at) {
+        return Ok(0);
+    }
+
+    let slash = bond.resolution_slash(quality_score, refund_percentage, config.severe_refund_slash_bps)?;
+    if slash == 0 {
+        return Ok(0);
+    }
+    bond.bonded_lamports -= slash;
+    bond.pending_withdrawal = bond.pending_withdrawal.min(bond.bonded_lamports);
+    bond.total_slashed = bond.total_slashed.saturating_add(slash);
+
+    let bond_info = bond.to_account_info();
+    require_rent_exempt_after(&bond_info, slash)?;
+    **bond_info.try_borrow_mut_lamports()? -= slash;
+    **agent.try_borrow_mut_lamports()? += slash;
+
+    msg!("Service bond slashed: {} lamports", slash);
+
+    emit!(ServiceBondSlashed {
+        version: EVENT_VERSION,
+        provider: bond.provider,
+        escrow: escrow.key(),
+        agent: agent.key(),
+        quality_score,
+        refund_percentage,
+        amount: slash,
+        bonded_lamports: bond.bonded_lamports,
+    });
+
+    Ok(slash)
+}
+
+/// Keep a cancelled escrow's spam protection deposit in the vault for
+/// `SPAM_DEPOSIT_RETENTION`, detached from the escrow account being closed
+fn retain_spam_deposit(
+    escrow: &mut Account<Escrow>,
+    vault: &mut Account<SpamProtectionVault>,
+    now: i64,
+) -> Result<()> {
+    let deposit = escrow.spam_deposit;
+
+    vault.retained_lamports = vault
+        .retained_lamports
+        .checked_add(deposit)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    vault.retained_until = now
+        .checked_add(SPAM_DEPOSIT_RETENTION)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    escrow.spam_deposit = 0;
+
+    msg!("Spam protection deposit retained: {} lamports", deposit);
+
+    emit!(SpamProtectionDepositRetained {
+        version: EVENT_VERSION,
+        agent: escrow.agent,
+        escrow: escrow.key(),
+        amount: deposit,
+        retained_until: vault.retained_until,
+    });
+
+    Ok(())
+}
+
+/// Move an escrow's spam protection deposit from the vault back to the agent
+fn return_spam_deposit(
+    escrow: &mut Account<Escrow>,
+    vault: &mut Account<SpamProtectionVault>,
+    agent: &AccountInfo,
+) -> Result<()> {
+    let deposit = escrow.spam_deposit;
+
+    vault.held_lamports = vault
+        .held_lamports
+        .checked_sub(deposit)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    escrow.spam_deposit = 0;
+
+    **vault.to_account_info().try_borrow_mut_lamports()? -= deposit;
+    **agent.try_borrow_mut_lamports()? += deposit;
+
+    msg!("Spam protection deposit refunded: {} lamports", deposit);
+
+    emit!(SpamProtectionDepositRefunded {
+        version: EVENT_VERSION,
+        agent: escrow.agent,
+        escrow: escrow.key(),
+        amount: deposit,
+    });
+
+    Ok(())
+}
+
+/// Ensure an active escrow may be released without the agent at `now`
+///
+/// Its time lock must have expired, counting from delivery for escrows on a
+/// delivery clock, and the dispute window must have closed.
+fn require_auto_releasable(escrow: &Escrow, now: i64) -> Result<()> {
+    require!(
+        !escrow.delivery_clock() || escrow.delivered_at.is_some(),
+        EscrowError::DeliveryNotAcknowledged
+    );
+    require!(now >= escrow.expires_at, EscrowError::TimeLockNotExpired);
+    require!(now >= escrow.dispute_deadline(), EscrowError::DisputeWindowOpen);
+    Ok(())
+}
+
+/// Checks shared by the SOL and SPL stale dispute settlements
+fn require_stale_dispute(escrow: &Escrow, now: i64) -> Result<()> {
+    require!(
+        escrow.status() == EscrowStatus::Disputed,
+        EscrowError::InvalidStatus
+    );
+    // An unappealed resolution settles through `settle_resolution`
+    require!(
+        !escrow.resolution_held() || escrow.appealed(),
+        EscrowError::ResolutionHeld
+    );
+    require!(now >= escrow.stale_at()?, EscrowError::DisputeNotStale);
+    Ok(())
+}
+
+/// Whether the API may still answer the escrow's dispute at `now`
+fn require_provider_response_open(escrow: &Escrow, now: i64) -> Result<()> {
+    require!(
+        escrow.status() == EscrowStatus::Disputed && !escrow.resolution_held(),
+        EscrowError::InvalidStatus
+    );
+    require!(
+        escrow.provider_evidence_hash.is_none(),
+        EscrowError::ProviderResponseAlreadySubmitted
+    );
+    let disputed_at = escrow.disputed_at.ok_or(EscrowError::InvalidStatus)?;
+    require!(
+        now < disputed_at.saturating_add(PROVIDER_RESPONSE_WINDOW),
+        EscrowError::ProviderResponseWindowClosed
+    );
+    Ok(())
+}
+
+/// Ensure an account stays rent-exempt after `outflow` lamports leave it.
+///
+/// Uses the account's actual data length so every `Escrow` field is covered.
+fn require_rent_exempt_after(account: &AccountInfo, outflow: u64) -> Result<()> {
+    let min_balance = Rent::get()?.minimum_balance(account.data_len());
+    require_balance_after(account.lamports(), outflow, min_balance)
+}
+
+/// Balance check behind `require_rent_exempt_after`
+fn require_balance_after(lamports: u64, outflow: u64, min_balance: u64) -> Result<()> {
+    let remaining = lamports
+        .checked_sub(outflow)
+        .ok_or(EscrowError::EscrowBelowRentExempt)?;
+
+    require!(remaining >= min_balance, EscrowError::EscrowBelowRentExempt);
+
+    Ok(())
+}
+
+/// Remove an escrow's amount from both parties' open exposure.
+///
+/// Runs at most once per escrow: the `exposure_tracked` flag is cleared on the
+/// first terminal transition so later paths (e.g. close after resolve) are no-ops.
+/// A reputation that failed to load is skipped rather than blocking settlement.
+fn release_exposure(
+    escrow: &mut Account<Escrow>,
+    agent_reputation: Option<&mut EntityReputation>,
+    api_reputation: Option<&mut EntityReputation>,
+) {
+    if !escrow.exposure_tracked() {
+        return;
+    }
+
+    let escrow_key = escrow.key();
+    if let Some(agent_reputation) = agent_reputation {
+        decrease_locked_value(escrow_key, agent_reputation, escrow.amount);
+    }
+    if let Some(api_reputation) = api_reputation {
+        decrease_locked_value(escrow_key, api_reputation, escrow.amount);
+    }
+    escrow.set_exposure_tracked(false);
+}
+
+/// Load both parties' reputation accounts, release the escrow's exposure
+/// from them and store them back
+///
+/// Reputation is best-effort: corrupt analytics state never blocks
+/// settlement, but a tracked exposure needs both accounts supplied.
+fn release_exposure_accounts(
+    escrow: &mut Account<Escrow>,
+    agent_info: Option<&AccountInfo>,
+    api_info: Option<&AccountInfo>,
+) -> Result<()> {
+    require!(
+        !escrow.exposure_tracked() || (agent_info.is_some() && api_info.is_some()),
+        EscrowError::ExposureAccountsMissing
+    );
+    let escrow_key = escrow.key();
+    let mut agent_reputation =
+        agent_info.and_then(|info| load_reputation(info, &escrow.agent, escrow_key));
+    let mut api_reputation =
+        api_info.and_then(|info| load_reputation(info, &escrow.api, escrow_key));
+
+    release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+    if let Some(info) = agent_info {
+        store_reputation(info, agent_reputation.as_ref())?;
+    }
+    if let Some(info) = api_info {
+        store_reputation(info, api_reputation.as_ref())?;
+    }
+
+    Ok(())
+}
+
+/// Subtract from `locked_value`, clamping at zero if accounting has drifted
+fn decrease_locked_value(escrow: Pubkey, reputation: &mut EntityReputation, amount: u64) {
+    match reputation.locked_value.checked_sub(amount) {
+        Some(remaining) => reputation.locked_value = remaining,
+        None => {
+            msg!("Warning: locked value drift for {}", reputation.entity);
+            emit!(LockedValueDrift {
+                version: EVENT_VERSION,
+                escrow,
+                entity: reputation.entity,
+                recorded: reputation.locked_value,
+                released: amount,
+            });
+            reputation.locked_value = 0;
+        }
+    }
+}
+
+/// Pick the next verifier with spare capacity, starting at `next_index`.
+///
+/// `candidates[i]` must be the `VerifierOracleAccount` for
+/// `queue.verifier_pubkeys[i]`. Returns the verifier and its queue index.
+fn assign_next_verifier<'info>(
+    queue: &mut AssignmentQueue,
+    candidates: &'info [AccountInfo<'info>],
+) -> Result<Option<(Pubkey, u16)>> {
+    let len = queue.verifier_pubkeys.len();
+    if len == 0 {
+        return Ok(None);
+    }
+    require!(candidates.len() == len, EscrowError::InvalidVerifierAccount);
+
+    for offset in 0..len {
+        let index = (queue.next_index as usize + offset) % len;
+        let mut oracle: Account<VerifierOracleAccount> = Account::try_from(&candidates[index])?;
+        require_keys_eq!(
+            oracle.verifier,
+            queue.verifier_pubkeys[index],
+            EscrowError::InvalidVerifierAccount
+        );
+        require_keys_eq!(oracle.namespace, queue.namespace, EscrowError::NamespaceMismatch);
+
+        // Paused verifiers are inactive until they resume
+        if !oracle.active || oracle.active_assignments >= oracle.max_concurrent_assignments {
+            continue;
+        }
+
+        oracle.active_assignments = oracle.active_assignments.saturating_add(1);
+        oracle.exit(&crate::ID)?;
+
+        queue.next_index = ((index + 1) % len) as u16;
+        return Ok(Some((queue.verifier_pubkeys[index], index as u16)));
+    }
+
+    Ok(None)
+}
+
+/// Assign a dispute from the queue stored at `info`, or `None` for
+/// namespaces that never created one
+///
+/// The queue PDA is always passed, so an agent cannot skip assignment by
+/// leaving it out.
+fn assign_from_queue<'info>(
+    info: &AccountInfo<'info>,
+    namespace: &Pubkey,
+    candidates: &'info [AccountInfo<'info>],
+) -> Result<Option<(Pubkey, u16)>> {
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+    let mut queue = AssignmentQueue::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(queue.namespace, *namespace, EscrowError::NamespaceMismatch);
+
+    let assigned = assign_next_verifier(&mut queue, candidates)?;
+
+    let mut data = info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    queue.try_serialize(&mut writer)?;
+
+    Ok(assigned)
+}
+
+/// Drop a verifier from the round-robin, keeping the cursor on the same next verifier
+fn remove_from_queue(queue: &mut AssignmentQueue, verifier: &Pubkey) {
+    let Some(index) = queue.verifier_pubkeys.iter().position(|key| key == verifier) else {
+        return;
+    };
+
+    queue.verifier_pubkeys.remove(index);
+    if (queue.next_index as usize) > index {
+        queue.next_index -= 1;
+    }
+    if queue.next_index as usize >= queue.verifier_pubkeys.len() {
+        queue.next_index = 0;
+    }
+}
+
+/// Free the assigned verifier's slot once its dispute is settled
+fn release_assignment(
+    escrow: &Escrow,
+    verifier_oracle: Option<&mut VerifierOracleAccount>,
+) -> Result<()> {
+    let Some(assigned) = escrow.assigned_verifier else {
+        return Ok(());
+    };
+
+    let oracle = verifier_oracle.ok_or(EscrowError::InvalidVerifierAccount)?;
+    require_keys_eq!(oracle.verifier, assigned, EscrowError::InvalidVerifierAccount);
+    require_keys_eq!(oracle.namespace, escrow.namespace, EscrowError::NamespaceMismatch);
+    oracle.active_assignments = oracle.active_assignments.saturating_sub(1);
+
+    Ok(())
+}
+
+fn emit_reputation_updated(
+    reputation: &EntityReputation,
+    old_score: u16,
+    reason: ReputationChangeReason,
+    timestamp: i64,
+) {
+    emit!(ReputationUpdated {
+        version: EVENT_VERSION,
+        entity: reputation.entity,
+        old_score,
+        new_score: reputation.reputation_score,
+        reason,
+        current_win_streak: reputation.current_win_streak,
+        longest_win_streak: reputation.longest_win_streak,
+        worst_quality_received: reputation.worst_quality_received,
+        best_quality_received: reputation.best_quality_received,
+        total_refunds_received_lamports: reputation.total_refunds_received_lamports,
+        total_payments_made_lamports: reputation.total_payments_made_lamports,
+        timestamp,
+    });
+}
+
+/// Whether an emergency override is in force; clears it once lapsed
+fn rate_limit_overridden(rate_limiter: &mut RateLimiter, now: i64) -> bool {
+    match rate_limiter.rate_limit_overridden_until {
+        Some(until) if now < until => true,
+        Some(until) => {
+            rate_limiter.rate_limit_overridden_until = None;
+            emit!(RateLimitOverrideExpired {
+                version: EVENT_VERSION,
+                entity: rate_limiter.entity,
+                expired_at: until,
+            });
+            false
+        }
+        None => false,
+    }
+}
+
+/// What a rate limiter is charged for
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RateLimitedAction {
+    Transaction,
+    Dispute,
+}
+
+/// Roll the limiter's windows forward, then count one action against them
+fn consume_rate_limit(
+    rate_limiter: &mut RateLimiter,
+    now: i64,
+    action: RateLimitedAction,
+) -> Result<()> {
+    let current_hour = now / 3600;
+    let current_day = now / 86400;
+
+    // Reset hourly counter if hour changed
+    if current_hour > rate_limiter.last_hour_check {
+        rate_limiter.transactions_last_hour = 0;
+        rate_limiter.last_hour_check = current_hour;
+    }
+
+    // Reset daily counters if day changed
+    if current_day > rate_limiter.last_day_check {
+        rate_limiter.transactions_last_day = 0;
+        rate_limiter.disputes_last_day = 0;
+        rate_limiter.last_day_check = current_day;
+    }
+
+    // Emergency override skips limit checks until it expires
+    let overridden = rate_limit_overridden(rate_limiter, now);
+    let (hour_limit, day_limit, dispute_day_limit) = get_rate_limits(rate_limiter.verification_level);
+
+    match action {
+        RateLimitedAction::Transaction => {
+            require!(
+                overridden
+                    || (rate_limiter.transactions_last_hour < hour_limit
+                        && rate_limiter.transactions_last_day < day_limit),
+                EscrowError::RateLimitExceeded
+            );
+            rate_limiter.transactions_last_hour = rate_limiter.transactions_last_hour.saturating_add(1);
+            rate_limiter.transactions_last_day = rate_limiter.transactions_last_day.saturating_add(1);
+        }
+        RateLimitedAction::Dispute => {
+            require!(
+                overridden || rate_limiter.disputes_last_day < dispute_day_limit,
+                EscrowError::RateLimitExceeded
+            );
+            rate_limiter.disputes_last_day = rate_limiter.disputes_last_day.saturating_add(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Charge the entity's rate limiter at `info`; entities without one are unlimited
+fn charge_rate_limit(info: &AccountInfo, entity: &Pubkey, action: RateLimitedAction) -> Result<()> {
+    if info.owner != &crate::ID {
+        return Ok(());
+    }
+    let mut rate_limiter = RateLimiter::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(rate_limiter.entity, *entity, EscrowError::Unauthorized);
+
+    consume_rate_limit(&mut rate_limiter, Clock::get()?.unix_timestamp, action)?;
+
+    let mut data = info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    rate_limiter.try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+fn get_rate_limits(verification: VerificationLevel) -> (u16, u16, u16) {
+    match verification {
+        VerificationLevel::Basic => (1, 10, 3),        // 1/hour, 10/day, 3 disputes/day
+        VerificationLevel::Staked => (10, 100, 10),    // 10/hour, 100/day, 10 disputes/day
+        VerificationLevel::Social => (50, 500, 50),    // 50/hour, 500/day, 50 disputes/day
+        VerificationLevel::KYC => (1000, 10000, 1000), // Unlimited
+    }
+}
+
+// ============================================================================
+// Account Structs
+// ============================================================================
+
+#[derive(Accounts)]
+#[instruction(amount: u64, time_lock: i64, transaction_id: String, no_dispute: bool, namespace: Pubkey)]
+pub struct InitializeEscrow<'info> {
+    #[account(
+        init,
+        payer = payer.as_ref().map_or(agent.to_account_info(), |payer| payer.to_account_info()),
+        space = 8 + Escrow::INIT_SPACE,
+        seeds = [b"escrow", transaction_id.as_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// Optional: sponsors the escrow's and spam vault's rent instead of the
+    /// agent, e.g. a relayer. The agent still funds the escrow itself.
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a spam protection deposit, since
+    /// it carries the agent's escrow-creation window
+    #[account(
+        init_if_needed,
+        payer = payer.as_ref().map_or(agent.to_account_info(), |payer| payer.to_account_info()),
+        space = 8 + SpamProtectionVault::INIT_SPACE,
+        seeds = [b"spam_vault", agent.key().as_ref()],
+        bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Optional: SOL/USD Switchboard feed for normalized volume tracking
+    /// CHECK: Must match `config.sol_usd_price_feed`, parsed as a PullFeed
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    /// Required by `initialize_escrow_usd`: Pyth update for `config.pyth_sol_usd_feed_id`
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// CHECK: Agent's blocklist PDA, checked against the API when initialized
+    #[account(seeds = [b"blocklist", agent.key().as_ref()], bump)]
+    pub agent_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: API's blocklist PDA, checked against the agent when initialized
+    #[account(seeds = [b"blocklist", api.key().as_ref()], bump)]
+    pub api_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    /// CHECK: Agent's organization membership PDA, charged against its daily limit when initialized
+    #[account(mut, seeds = [b"org_member", agent.key().as_ref()], bump)]
+    pub org_member: UncheckedAccount<'info>,
+
+    /// Optional: each supplied reputation records the escrow's volume; when
+    /// both are supplied the amount is added to each party's open exposure
+    #[account(
+        mut,
+        seeds = [b"reputation", agent.key().as_ref()],
+        bump = agent_reputation.bump
+    )]
+    pub agent_reputation: Option<Account<'info, EntityReputation>>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", api.key().as_ref()],
+        bump = api_reputation.bump
+    )]
+    pub api_reputation: Option<Account<'info, EntityReputation>>,
+
+    /// Required when the namespace pools an insurance contribution
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Optional: API's service bond, which must then cover the escrow
+    #[account(seeds = [b"service_bond", api.key().as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// CHECK: API's penalties PDA; suspended providers cannot take new escrows
+    #[account(seeds = [b"provider_penalties", api.key().as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub agent_rate_limiter: UncheckedAccount<'info>,
+
+    /// Required by `initialize_escrow_from_terms`: terms the API published
+    #[account(
+        seeds = [b"provider_terms", api.key().as_ref(), &[provider_terms.terms_id]],
+        bump = provider_terms.bump
+    )]
+    pub provider_terms: Option<Account<'info, ProviderTerms>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, time_lock: i64, transaction_id: String, no_dispute: bool, namespace: Pubkey)]
+pub struct InitializeEscrowCpi<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Escrow::INIT_SPACE,
+        seeds = [b"escrow", transaction_id.as_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Agent the escrow belongs to; may be a PDA signing for its program
+    pub agent: Signer<'info>,
+
+    /// Pays the rent and funds the escrow
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a spam protection deposit, since
+    /// it carries the agent's escrow-creation window
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + SpamProtectionVault::INIT_SPACE,
+        seeds = [b"spam_vault", agent.key().as_ref()],
+        bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Optional: SOL/USD Switchboard feed for normalized volume tracking
+    /// CHECK: Must match `config.sol_usd_price_feed`, parsed as a PullFeed
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's blocklist PDA, checked against the API when initialized
+    #[account(seeds = [b"blocklist", agent.key().as_ref()], bump)]
+    pub agent_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: API's blocklist PDA, checked against the agent when initialized
+    #[account(seeds = [b"blocklist", api.key().as_ref()], bump)]
+    pub api_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    /// CHECK: Agent's organization membership PDA, charged against its daily limit when initialized
+    #[account(mut, seeds = [b"org_member", agent.key().as_ref()], bump)]
+    pub org_member: UncheckedAccount<'info>,
+
+    /// Optional: each supplied reputation records the escrow's volume; when
+    /// both are supplied the amount is added to each party's open exposure
+    #[account(
+        mut,
+        seeds = [b"reputation", agent.key().as_ref()],
+        bump = agent_reputation.bump
+    )]
+    pub agent_reputation: Option<Account<'info, EntityReputation>>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", api.key().as_ref()],
+        bump = api_reputation.bump
+    )]
+    pub api_reputation: Option<Account<'info, EntityReputation>>,
+
+    /// Required when the namespace pools an insurance contribution
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Optional: API's service bond, which must then cover the escrow
+    #[account(seeds = [b"service_bond", api.key().as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// CHECK: API's penalties PDA; suspended providers cannot take new escrows
+    #[account(seeds = [b"provider_penalties", api.key().as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub agent_rate_limiter: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, time_lock: i64, transaction_id: String, no_dispute: bool, namespace: Pubkey)]
+pub struct InitializeEscrowWithSession<'info> {
+    #[account(
+        init,
+        payer = session_signer,
+        space = 8 + Escrow::INIT_SPACE,
+        seeds = [b"escrow", transaction_id.as_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        seeds = [b"session_key", agent.key().as_ref(), session_signer.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    /// Session key the escrow is funded from
+    #[account(mut)]
+    pub session_signer: Signer<'info>,
+
+    /// CHECK: Agent the escrow belongs to, bound to the session by its seeds
+    pub agent: UncheckedAccount<'info>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a spam protection deposit, since
+    /// it carries the agent's escrow-creation window
+    #[account(
+        init_if_needed,
+        payer = session_signer,
+        space = 8 + SpamProtectionVault::INIT_SPACE,
+        seeds = [b"spam_vault", agent.key().as_ref()],
+        bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Optional: SOL/USD Switchboard feed for normalized volume tracking
+    /// CHECK: Must match `config.sol_usd_price_feed`, parsed as a PullFeed
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    /// Required by `initialize_escrow_usd`: Pyth update for `config.pyth_sol_usd_feed_id`
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// CHECK: Agent's blocklist PDA, checked against the API when initialized
+    #[account(seeds = [b"blocklist", agent.key().as_ref()], bump)]
+    pub agent_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: API's blocklist PDA, checked against the agent when initialized
+    #[account(seeds = [b"blocklist", api.key().as_ref()], bump)]
+    pub api_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    /// CHECK: Agent's organization membership PDA, charged against its daily limit when initialized
+    #[account(mut, seeds = [b"org_member", agent.key().as_ref()], bump)]
+    pub org_member: UncheckedAccount<'info>,
+
+    /// Optional: each supplied reputation records the escrow's volume; when
+    /// both are supplied the amount is added to each party's open exposure
+    #[account(
+        mut,
+        seeds = [b"reputation", agent.key().as_ref()],
+        bump = agent_reputation.bump
+    )]
+    pub agent_reputation: Option<Account<'info, EntityReputation>>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", api.key().as_ref()],
+        bump = api_reputation.bump
+    )]
+    pub api_reputation: Option<Account<'info, EntityReputation>>,
+
+    /// Required when the namespace pools an insurance contribution
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Optional: API's service bond, which must then cover the escrow
+    #[account(seeds = [b"service_bond", api.key().as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// CHECK: API's penalties PDA; suspended providers cannot take new escrows
+    #[account(seeds = [b"provider_penalties", api.key().as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub agent_rate_limiter: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseFunds<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Required when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required when the namespace takes an insurance cut of releases
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// Required for USD escrows released by anyone but the agent: receives
+    /// the repriced surplus
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent_wallet: Option<SystemAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseAndClose<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath,
+        close = agent
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Receives the rent and any spam protection deposit
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Required when the escrow holds a spam protection deposit
+    #[account(
+        mut,
+        seeds = [b"spam_vault", escrow.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Required when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required when the namespace takes an insurance cut of releases
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// Anyone may crank a long-expired escrow; receives the release tip and
+    /// pays for any receipt
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// The receipt accounts of `MintEscrowReceipt`, all passed to mint one
+    /// CHECK: Bubblegum tree config, checked by Bubblegum
+    #[account(mut)]
+    pub tree_config: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Merkle tree delegated to `receipt_authority`, checked by Bubblegum
+    #[account(mut)]
+    pub merkle_tree: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Signs the mint as tree delegate and receipt creator
+    #[account(seeds = [b"receipt_authority"], bump)]
+    pub receipt_authority: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SPL Noop program
+    #[account(address = receipts::SPL_NOOP_PROGRAM_ID)]
+    pub log_wrapper: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SPL Account Compression program
+    #[account(address = receipts::SPL_ACCOUNT_COMPRESSION_PROGRAM_ID)]
+    pub compression_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Bubblegum program
+    #[account(address = receipts::BUBBLEGUM_PROGRAM_ID)]
+    pub bubblegum_program: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct EnableDeliveryClock<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EnableStreaming<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReleaseTip<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = api @ EscrowError::Unauthorized,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub api: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStreamed<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub api: Signer<'info>,
+
+    /// Required for the final withdrawal when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CrankReleaseExpired<'info> {
+    /// Namespace config; every escrow cranked must belong to it
+    #[account(seeds = [b"global_config", config.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required when the namespace takes an insurance cut of releases
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Required when any escrow cranked is priced in USD
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// Anyone may crank; receives the tips
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: Verifier oracle public key; receives any priority fee
+    #[account(mut)]
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", escrow.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is covered by the API's service bond
+    #[account(mut, seeds = [b"service_bond", escrow.api.as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// Required when the escrow is bound to a work agreement
+    #[account(seeds = [b"work_agreement", escrow.key().as_ref()], bump = work_agreement.bump)]
+    pub work_agreement: Option<Account<'info, WorkAgreement>>,
+
+    /// CHECK: API's penalties PDA, updated when the provider tracks penalties
+    #[account(mut, seeds = [b"provider_penalties", escrow.api.as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// Required by `resolve_with_relayer`: submits the transaction and is reimbursed
+    #[account(mut)]
+    pub relayer: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitResolution<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: Verifier oracle public key; checked against the registry
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", escrow.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the escrow is bound to a work agreement
+    #[account(seeds = [b"work_agreement", escrow.key().as_ref()], bump = work_agreement.bump)]
+    pub work_agreement: Option<Account<'info, WorkAgreement>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleStaleDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleMutual<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: Signer<'info>,
+
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: Signer<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDisputeSwitchboard<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Switchboard Function pull feed containing quality score
+    /// CHECK: Parsed as a PullFeed and bound to `switchboard_queue` in the handler
+    pub switchboard_function: AccountInfo<'info>,
+
+    /// CHECK: Must match `config.switchboard_queue`; only its key and owner are read
+    #[account(address = config.switchboard_queue @ EscrowError::UntrustedSwitchboardFeed)]
+    pub switchboard_queue: AccountInfo<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is covered by the API's service bond
+    #[account(mut, seeds = [b"service_bond", escrow.api.as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDisputePyth<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Fully verified update for `config.pyth_quality_feed_id`
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is covered by the API's service bond
+    #[account(mut, seeds = [b"service_bond", escrow.api.as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BatchResolveDisputes<'info> {
+    /// CHECK: Verifier oracle public key; signs every resolution in the batch
+    /// and receives their priority fees
+    #[account(mut)]
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", config.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config; every escrow in the batch must belong to it
+    #[account(seeds = [b"global_config", config.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required when any escrow in the batch has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when any escrow in the batch is priced in USD
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcknowledgeDelivery<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub api: Signer<'info>,
+
+    /// Required when the escrow holds a spam protection deposit
+    #[account(
+        mut,
+        seeds = [b"spam_vault", escrow.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Receives the spam protection deposit; required alongside `spam_vault`
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: Option<SystemAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct MarkDisputed<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", escrow.agent.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, EntityReputation>,
+
+    /// The agent, its delegate or a session key; pays the dispute cost
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// Required when `agent` is one of the agent's session keys
+    #[account(
+        seeds = [b"session_key", escrow.agent.as_ref(), agent.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, SessionKey>>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", escrow.agent.as_ref()], bump)]
+    pub rate_limiter: UncheckedAccount<'info>,
+
+    /// CHECK: Namespace's assignment queue PDA, assigning a verifier when initialized
+    #[account(mut, seeds = [b"assign_queue", escrow.namespace.as_ref()], bump)]
+    pub assignment_queue: UncheckedAccount<'info>,
+
+    /// Namespace config capping the priority fee
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitProviderResponse<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub api: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitReputation<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + EntityReputation::INIT_SPACE,
+        seeds = [b"reputation", entity.key().as_ref()],
+        bump
+    )]
+    pub reputation: Account<'info, EntityReputation>,
+
+    /// CHECK: Entity being tracked
+    pub entity: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateReputation<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation", reputation.entity.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, EntityReputation>,
+
+    /// Authority that can update reputation (restricted)
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshReputation<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation", reputation.entity.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, EntityReputation>,
+}
+
+#[derive(Accounts)]
+pub struct InitReputationHistory<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ReputationHistory::INIT_SPACE,
+        seeds = [b"reputation_history", reputation.entity.as_ref()],
+        bump
+    )]
+    pub reputation_history: Account<'info, ReputationHistory>,
+
+    #[account(seeds = [b"reputation", reputation.entity.as_ref()], bump = reputation.bump)]
+    pub reputation: Account<'info, EntityReputation>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotReputation<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation_history", reputation.entity.as_ref()],
+        bump = reputation_history.bump
+    )]
+    pub reputation_history: Account<'info, ReputationHistory>,
+
+    #[account(seeds = [b"reputation", reputation.entity.as_ref()], bump = reputation.bump)]
+    pub reputation: Account<'info, EntityReputation>,
+}
+
+#[derive(Accounts)]
+pub struct GetReputation<'info> {
+    /// CHECK: Only used to derive the reputation address
+    pub entity: UncheckedAccount<'info>,
+
+    /// CHECK: May be uninitialized; `get_reputation` checks owner and layout
+    #[account(seeds = [b"reputation", entity.key().as_ref()], bump)]
+    pub reputation: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CheckRateLimit<'info> {
+    #[account(
+        mut,
+        seeds = [b"rate_limit", entity.key().as_ref()],
+        bump = rate_limiter.bump
+    )]
+    pub rate_limiter: Account<'info, RateLimiter>,
+
+    pub entity: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(entity: Pubkey)]
+pub struct SetReputationEntityType<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation", entity.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, EntityReputation>,
+
+    #[account(
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(entity: Pubkey)]
+pub struct SetRateLimitOverride<'info> {
+    #[account(
+        mut,
+        seeds = [b"rate_limit", entity.as_ref()],
+        bump = rate_limiter.bump
+    )]
+    pub rate_limiter: Account<'info, RateLimiter>,
+
+    /// Rate limits are shared across namespaces, so only the default
+    /// namespace authority may override them
+    #[account(
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGlobalConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GlobalConfig::INIT_SPACE,
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::X402Escrow>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ EscrowError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeNamespaceConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GlobalConfig::INIT_SPACE,
+        seeds = [b"global_config", authority.key().as_ref()],
+        bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Platform authority; its key is the namespace
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGlobalConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestDisputeWindowExtension<'info> {
+    #[account(
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = requester,
+        space = 8 + DisputeWindowExtensionRequest::INIT_SPACE,
+        seeds = [b"window_extension", escrow.key().as_ref()],
+        bump
+    )]
+    pub extension_request: Account<'info, DisputeWindowExtensionRequest>,
+
+    /// Agent or API of the escrow
+    #[account(mut)]
+    pub requester: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveDisputeWindowExtension<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"window_extension", escrow.key().as_ref()],
+        bump = extension_request.bump,
+        has_one = escrow,
+        has_one = requester,
+        close = requester
+    )]
+    pub extension_request: Account<'info, DisputeWindowExtensionRequest>,
+
+    /// CHECK: Original requester, receives the request rent
+    #[account(mut)]
+    pub requester: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub approver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendTimeLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    pub agent: Signer<'info>,
+
+    /// Co-signature required once the time lock has expired
+    #[account(address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    /// CHECK: Escrow in any known layout; the handler checks the discriminator
+    #[account(mut, owner = crate::ID)]
+    pub escrow: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateReputation<'info> {
+    /// CHECK: Reputation in any known layout; the handler checks the discriminator
+    #[account(mut, owner = crate::ID)]
+    pub reputation: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpEscrowRent<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddFunds<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// Required when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBlocklist<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Blocklist::INIT_SPACE,
+        seeds = [b"blocklist", owner.key().as_ref()],
+        bump
+    )]
+    pub blocklist: Account<'info, Blocklist>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyBlocklist<'info> {
+    #[account(
+        mut,
+        seeds = [b"blocklist", owner.key().as_ref()],
+        bump = blocklist.bump,
+        has_one = owner @ EscrowError::Unauthorized
+    )]
+    pub blocklist: Account<'info, Blocklist>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeAllowlist<'info> {
+    #[account(
+        init,
+        payer = organization,
+        space = 8 + Allowlist::INIT_SPACE,
+        seeds = [b"allowlist", organization.key().as_ref()],
+        bump
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyAllowlist<'info> {
+    #[account(
+        mut,
+        seeds = [b"allowlist", organization.key().as_ref()],
+        bump = allowlist.bump,
+        has_one = organization @ EscrowError::Unauthorized
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    pub organization: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RestrictAgent<'info> {
+    #[account(
+        init,
+        payer = organization,
+        space = 8 + AgentRestriction::INIT_SPACE,
+        seeds = [b"agent_restriction", agent.key().as_ref()],
+        bump
+    )]
+    pub restriction: Account<'info, AgentRestriction>,
+
+    /// The organization's allowlist must exist before it can bind agents
+    #[account(
+        seeds = [b"allowlist", organization.key().as_ref()],
+        bump = allowlist.bump,
+        has_one = organization @ EscrowError::Unauthorized
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnrestrictAgent<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent_restriction", restriction.agent.as_ref()],
+        bump = restriction.bump,
+        has_one = organization @ EscrowError::Unauthorized,
+        close = organization
+    )]
+    pub restriction: Account<'info, AgentRestriction>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeOrganization<'info> {
+    #[account(
+        init,
+        payer = organization,
+        space = 8 + Organization::INIT_SPACE,
+        seeds = [b"organization", organization.key().as_ref()],
+        bump
+    )]
+    pub organization_account: Account<'info, Organization>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddOrganizationAgent<'info> {
+    #[account(
+        init,
+        payer = organization,
+        space = 8 + OrganizationMember::INIT_SPACE,
+        seeds = [b"org_member", agent.key().as_ref()],
+        bump
+    )]
+    pub member: Account<'info, OrganizationMember>,
+
+    #[account(
+        mut,
+        seeds = [b"organization", organization.key().as_ref()],
+        bump = organization_account.bump,
+        has_one = organization @ EscrowError::Unauthorized
+    )]
+    pub organization_account: Account<'info, Organization>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAgentSpendLimit<'info> {
+    #[account(
+        mut,
+        seeds = [b"org_member", member.agent.as_ref()],
+        bump = member.bump,
+        has_one = organization @ EscrowError::Unauthorized
+    )]
+    pub member: Account<'info, OrganizationMember>,
+
+    pub organization: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveOrganizationAgent<'info> {
+    #[account(
+        mut,
+        seeds = [b"org_member", member.agent.as_ref()],
+        bump = member.bump,
+        has_one = organization @ EscrowError::Unauthorized,
+        close = organization
+    )]
+    pub member: Account<'info, OrganizationMember>,
+
+    #[account(
+        mut,
+        seeds = [b"organization", organization.key().as_ref()],
+        bump = organization_account.bump,
+        has_one = organization @ EscrowError::Unauthorized
+    )]
+    pub organization_account: Account<'info, Organization>,
+
+    #[account(mut)]
+    pub organization: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSessionKey<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + SessionKey::INIT_SPACE,
+        seeds = [b"session_key", agent.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+    #[account(
+        mut,
+        seeds = [b"session_key", agent.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        close = agent
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundSpamDeposit<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"spam_vault", escrow.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Account<'info, SpamProtectionVault>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundRetainedSpamDeposit<'info> {
+    #[account(
+        mut,
+        seeds = [b"spam_vault", spam_vault.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Account<'info, SpamProtectionVault>,
+
+    #[account(mut, address = spam_vault.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeAssignmentQueue<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AssignmentQueue::INIT_SPACE,
+        seeds = [b"assign_queue", config.namespace.as_ref()],
+        bump
+    )]
+    pub assignment_queue: Account<'info, AssignmentQueue>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterVerifierOracle<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VerifierOracleAccount::INIT_SPACE,
+        seeds = [b"verifier_oracle", config.namespace.as_ref(), verifier.key().as_ref()],
+        bump
+    )]
+    pub verifier_oracle: Account<'info, VerifierOracleAccount>,
+
+    /// CHECK: Verifier oracle signing key being registered
+    pub verifier: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"verifier_bond", verifier.key().as_ref()],
+        bump = verifier_bond.bump
+    )]
+    pub verifier_bond: Account<'info, VerifierBond>,
+
+    #[account(
+        mut,
+        seeds = [b"assign_queue", config.namespace.as_ref()],
+        bump = assignment_queue.bump
+    )]
+    pub assignment_queue: Account<'info, AssignmentQueue>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PostVerifierBond<'info> {
+    #[account(
+        init_if_needed,
+        payer = verifier,
+        space = 8 + VerifierBond::INIT_SPACE,
+        seeds = [b"verifier_bond", verifier.key().as_ref()],
+        bump
+    )]
+    pub verifier_bond: Account<'info, VerifierBond>,
+
+    #[account(mut)]
+    pub verifier: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(verifier: Pubkey)]
+pub struct CheckVerifierBondCompliance<'info> {
+    #[account(seeds = [b"global_config", config.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"verifier_oracle", config.namespace.as_ref(), verifier.as_ref()],
+        bump = verifier_oracle.bump
+    )]
+    pub verifier_oracle: Account<'info, VerifierOracleAccount>,
+
+    /// Absent when the verifier never posted a bond
+    #[account(seeds = [b"verifier_bond", verifier.as_ref()], bump = verifier_bond.bump)]
+    pub verifier_bond: Option<Account<'info, VerifierBond>>,
+
+    #[account(
+        mut,
+        seeds = [b"assign_queue", config.namespace.as_ref()],
+        bump = assignment_queue.bump
+    )]
+    pub assignment_queue: Account<'info, AssignmentQueue>,
+
+    /// Anyone may crank compliance checks
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PauseVerifier<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_oracle", verifier_oracle.namespace.as_ref(), verifier.key().as_ref()],
+        bump = verifier_oracle.bump,
+        has_one = verifier @ EscrowError::Unauthorized
+    )]
+    pub verifier_oracle: Account<'info, VerifierOracleAccount>,
+
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResumeVerifier<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_oracle", verifier_oracle.namespace.as_ref(), verifier.key().as_ref()],
+        bump = verifier_oracle.bump,
+        has_one = verifier @ EscrowError::Unauthorized
+    )]
+    pub verifier_oracle: Account<'info, VerifierOracleAccount>,
+
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUsedNonces<'info> {
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + UsedNonces::INIT_SPACE,
+        seeds = [b"used_nonces", signer.key().as_ref()],
+        bump
+    )]
+    pub used_nonces: Account<'info, UsedNonces>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InvalidateNonce<'info> {
+    #[account(
+        mut,
+        seeds = [b"used_nonces", signer.key().as_ref()],
+        bump = used_nonces.bump
+    )]
+    pub used_nonces: Account<'info, UsedNonces>,
+
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeInsuranceFund<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + InsuranceFund::INIT_SPACE,
+        seeds = [b"insurance_fund"],
+        bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FileInsuranceClaim<'info> {
+    #[account(
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = claimant,
+        space = 8 + InsuranceClaim::INIT_SPACE,
+        seeds = [b"insurance_claim", escrow.key().as_ref()],
+        bump
+    )]
+    pub claim: Account<'info, InsuranceClaim>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub claimant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimInsurance<'info> {
+    #[account(
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = claimant,
+        space = 8 + InsuranceClaim::INIT_SPACE,
+        seeds = [b"insurance_claim", escrow.key().as_ref()],
+        bump
+    )]
+    pub claim: Account<'info, InsuranceClaim>,
+
+    #[account(seeds = [b"provider_penalties", escrow.api.as_ref()], bump = provider_penalties.bump)]
+    pub provider_penalties: Account<'info, ProviderPenalties>,
+
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub claimant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveInsuranceClaim<'info> {
+    #[account(
+        mut,
+        seeds = [b"insurance_claim", claim.escrow.as_ref()],
+        bump = claim.bump
+    )]
+    pub claim: Account<'info, InsuranceClaim>,
+
+    #[account(mut, seeds = [b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(mut, address = claim.claimant @ EscrowError::Unauthorized)]
+    pub claimant: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, time_lock: i64, transaction_id: String, namespace: Pubkey)]
+pub struct InitializeEscrowSpl<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + Escrow::INIT_SPACE,
+        seeds = [b"escrow", transaction_id.as_bytes()],
+        bump
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(
+        init,
+        payer = agent,
+        seeds = [b"escrow_vault", escrow.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = agent,
+        token::token_program = token_program
+    )]
+    pub agent_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, GlobalConfig>>,
+
+    /// Required when the namespace charges a spam protection deposit, since
+    /// it carries the agent's escrow-creation window
+    #[account(
+        init_if_needed,
+        payer = agent,
+        space = 8 + SpamProtectionVault::INIT_SPACE,
+        seeds = [b"spam_vault", agent.key().as_ref()],
+        bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// CHECK: Agent's blocklist PDA, checked against the API when initialized
+    #[account(seeds = [b"blocklist", agent.key().as_ref()], bump)]
+    pub agent_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: API's blocklist PDA, checked against the agent when initialized
+    #[account(seeds = [b"blocklist", api.key().as_ref()], bump)]
+    pub api_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    /// Optional: credited with USD volume when the mint is the config's stablecoin
+    #[account(
+        mut,
+        seeds = [b"reputation", agent.key().as_ref()],
+        bump = agent_reputation.bump
+    )]
+    pub agent_reputation: Option<Account<'info, EntityReputation>>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", api.key().as_ref()],
+        bump = api_reputation.bump
+    )]
+    pub api_reputation: Option<Account<'info, EntityReputation>>,
+
+    /// CHECK: API's penalties PDA; suspended providers cannot take new escrows
+    #[account(seeds = [b"provider_penalties", api.key().as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub agent_rate_limiter: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseFundsSpl<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint == Some(mint.key()) @ EscrowError::NotTokenEscrow
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mut, seeds = [b"escrow_vault", escrow.key().as_ref()], bump)]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    pub agent: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = escrow.api,
+        token::token_program = token_program
+    )]
+    pub api_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, GlobalConfig>>,
+
+    /// Required when the namespace charges a protocol fee
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config.treasury,
+        token::token_program = token_program
+    )]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDisputeSpl<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint == Some(mint.key()) @ EscrowError::NotTokenEscrow
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mut, seeds = [b"escrow_vault", escrow.key().as_ref()], bump)]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = escrow.agent,
+        token::token_program = token_program
+    )]
+    pub agent_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = escrow.api,
+        token::token_program = token_program
+    )]
+    pub api_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the dispute bond back when the agent wins
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// CHECK: API wallet address, receives a forfeited dispute bond
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: Verifier oracle public key; receives any priority fee
+    #[account(mut)]
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", escrow.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Box<Account<'info, VerifierRegistry>>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Box<Account<'info, UsedNonces>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, GlobalConfig>>,
+
+    /// Required when the namespace charges a protocol fee
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config.treasury,
+        token::token_program = token_program
+    )]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Required when the namespace charges an oracle fee
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = verifier,
+        token::token_program = token_program
+    )]
+    pub verifier_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Required when the namespace forfeits dispute bonds, paid in lamports,
+    /// to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is bound to a work agreement
+    #[account(seeds = [b"work_agreement", escrow.key().as_ref()], bump = work_agreement.bump)]
+    pub work_agreement: Option<Account<'info, WorkAgreement>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SettleStaleDisputeSpl<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint == Some(mint.key()) @ EscrowError::NotTokenEscrow
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mut, seeds = [b"escrow_vault", escrow.key().as_ref()], bump)]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = escrow.agent,
+        token::token_program = token_program
+    )]
+    pub agent_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = escrow.api,
+        token::token_program = token_program
+    )]
+    pub api_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the dispute bond and any priority fee back
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, GlobalConfig>>,
+
+    /// Required when the namespace charges a protocol fee
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config.treasury,
+        token::token_program = token_program
+    )]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Required when the namespace forfeits dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AuditEscrow<'info> {
+    #[account(seeds = [b"escrow", escrow.transaction_id.as_bytes()], bump = escrow.bump)]
+    pub escrow: Account<'info, Escrow>,
+}
+
+#[derive(Accounts)]
+#[instruction(terms_id: u8)]
+pub struct PublishProviderTerms<'info> {
+    #[account(
+        init,
+        payer = api,
+        space = 8 + ProviderTerms::INIT_SPACE,
+        seeds = [b"provider_terms", api.key().as_ref(), &[terms_id]],
+        bump
+    )]
+    pub provider_terms: Account<'info, ProviderTerms>,
+
+    #[account(mut)]
+    pub api: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RetireProviderTerms<'info> {
+    #[account(
+        mut,
+        close = api,
+        seeds = [b"provider_terms", api.key().as_ref(), &[provider_terms.terms_id]],
+        bump = provider_terms.bump
+    )]
+    pub provider_terms: Account<'info, ProviderTerms>,
+
+    #[account(mut)]
+    pub api: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PostServiceBond<'info> {
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ServiceBond::INIT_SPACE,
+        seeds = [b"service_bond", provider.key().as_ref()],
+        bump
+    )]
+    pub service_bond: Account<'info, ServiceBond>,
+
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageServiceBond<'info> {
+    #[account(
+        mut,
+        seeds = [b"service_bond", provider.key().as_ref()],
+        bump = service_bond.bump,
+        has_one = provider @ EscrowError::Unauthorized
+    )]
+    pub service_bond: Account<'info, ServiceBond>,
+
+    #[account(mut)]
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateWorkAgreement<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + WorkAgreement::INIT_SPACE,
+        seeds = [b"work_agreement", escrow.key().as_ref()],
+        bump
+    )]
+    pub work_agreement: Account<'info, WorkAgreement>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath,
+        close = agent
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Receives the escrowed amount, the rent and any spam protection deposit
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// Co-signature that waives the cancel grace period
+    #[account(address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: Option<Signer<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the escrow holds a spam protection deposit
+    #[account(
+        mut,
+        seeds = [b"spam_vault", escrow.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Required when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct MintEscrowReceipt<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: Receives the receipt
+    #[account(address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: AccountInfo<'info>,
+
+    /// CHECK: Bubblegum tree config, checked by Bubblegum
+    #[account(mut)]
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: Merkle tree delegated to `receipt_authority`, checked by Bubblegum
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: Signs the mint as tree delegate and receipt creator
+    #[account(seeds = [b"receipt_authority"], bump)]
+    pub receipt_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: SPL Noop program
+    #[account(address = receipts::SPL_NOOP_PROGRAM_ID)]
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    /// CHECK: SPL Account Compression program
+    #[account(address = receipts::SPL_ACCOUNT_COMPRESSION_PROGRAM_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: Bubblegum program
+    #[account(address = receipts::BUBBLEGUM_PROGRAM_ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PublishSettlement<'info> {
+    #[account(seeds = [b"escrow", escrow.transaction_id.as_bytes()], bump = escrow.bump)]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: Core bridge config, checked by the bridge
+    #[account(mut, seeds = [b"Bridge"], bump, seeds::program = wormhole::WORMHOLE_PROGRAM_ID)]
+    pub wormhole_bridge: UncheckedAccount<'info>,
+
+    /// CHECK: The escrow's message, created by the bridge
+    #[account(mut, seeds = [b"wormhole_message", escrow.key().as_ref()], bump)]
+    pub wormhole_message: UncheckedAccount<'info>,
+
+    /// CHECK: Signs as the program's emitter
+    #[account(seeds = [b"wormhole_emitter"], bump)]
+    pub wormhole_emitter: UncheckedAccount<'info>,
+
+    /// CHECK: Emitter's sequence tracker, checked by the bridge
+    #[account(
+        mut,
+        seeds = [b"Sequence", wormhole_emitter.key().as_ref()],
+        bump,
+        seeds::program = wormhole::WORMHOLE_PROGRAM_ID
+    )]
+    pub wormhole_sequence: UncheckedAccount<'info>,
+
+    /// CHECK: Receives the bridge fee
+    #[account(mut, seeds = [b"fee_collector"], bump, seeds::program = wormhole::WORMHOLE_PROGRAM_ID)]
+    pub wormhole_fee_collector: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Clock sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::clock::ID)]
+    pub clock: UncheckedAccount<'info>,
+
+    /// CHECK: Rent sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::rent::ID)]
+    pub rent: UncheckedAccount<'info>,
+
+    /// CHECK: Wormhole core bridge
+    #[account(address = wormhole::WORMHOLE_PROGRAM_ID)]
+    pub wormhole_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IndexEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + AgentIndex::INIT_SPACE,
+        seeds = [b"agent_index", escrow.agent.as_ref()],
+        bump
+    )]
+    pub agent_index: Account<'info, AgentIndex>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + EscrowIndexEntry::INIT_SPACE,
+        seeds = [b"agent_escrow", escrow.agent.as_ref(), &agent_index.count.to_le_bytes()],
+        bump
+    )]
+    pub agent_entry: Account<'info, EscrowIndexEntry>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ApiIndex::INIT_SPACE,
+        seeds = [b"api_index", escrow.api.as_ref()],
+        bump
+    )]
+    pub api_index: Account<'info, ApiIndex>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + EscrowIndexEntry::INIT_SPACE,
+        seeds = [b"api_escrow", escrow.api.as_ref(), &api_index.count.to_le_bytes()],
+        bump
+    )]
+    pub api_entry: Account<'info, EscrowIndexEntry>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        close = agent
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Receives the rent and any spam protection deposit
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow holds a spam protection deposit
+    #[account(
+        mut,
+        seeds = [b"spam_vault", escrow.agent.as_ref()],
+        bump = spam_vault.bump
+    )]
+    pub spam_vault: Option<Account<'info, SpamProtectionVault>>,
+
+    /// Required for SPL escrows
+    #[account(mut, seeds = [b"escrow_vault", escrow.key().as_ref()], bump)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Required for SPL escrows
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// Required for SPL escrows whose vault holds withheld transfer fees,
+    /// which are harvested to the mint before the vault closes
+    #[account(mut, address = escrow.mint.unwrap_or_default())]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Anyone may close a settled escrow
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureVerifierSet<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VerifierSet::INIT_SPACE,
+        seeds = [b"verifier_set", config.namespace.as_ref()],
+        bump
+    )]
+    pub verifier_set: Account<'info, VerifierSet>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDisputeQuorum<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: First quorum signer; receives any priority fee
+    #[account(mut)]
+    pub verifier: AccountInfo<'info>,
+
+    /// Namespace verifier set the signers belong to
+    #[account(seeds = [b"verifier_set", escrow.namespace.as_ref()], bump = verifier_set.bump)]
+    pub verifier_set: Account<'info, VerifierSet>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is covered by the API's service bond
+    #[account(mut, seeds = [b"service_bond", escrow.api.as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// Required when the escrow is bound to a work agreement
+    #[account(seeds = [b"work_agreement", escrow.key().as_ref()], bump = work_agreement.bump)]
+    pub work_agreement: Option<Account<'info, WorkAgreement>>,
+
+    /// CHECK: API's penalties PDA, updated when the provider tracks penalties
+    #[account(mut, seeds = [b"provider_penalties", escrow.api.as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppealResolution<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Agent or API of the escrow
+    pub appellant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EscalateToArbitrator<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Registered arbitrator the escalating party picks
+    #[account(
+        seeds = [b"arbitrator", escrow.namespace.as_ref(), arbitrator.authority.as_ref()],
+        bump = arbitrator.bump
+    )]
+    pub arbitrator: Account<'info, Arbitrator>,
+
+    /// Agent or API of the escrow; posts the arbitration bond
+    #[account(mut)]
+    pub escalator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ArbitratorRule<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"arbitrator", escrow.namespace.as_ref(), authority.key().as_ref()],
+        bump = arbitrator.bump,
+        constraint = escrow.arbitrator == Some(authority.key()) @ EscrowError::Unauthorized
+    )]
+    pub arbitrator: Account<'info, Arbitrator>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Required when the escrow is bound to a work agreement
+    #[account(seeds = [b"work_agreement", escrow.key().as_ref()], bump = work_agreement.bump)]
+    pub work_agreement: Option<Account<'info, WorkAgreement>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleResolution<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: Verifier of the submitted resolution; receives any priority fee
+    #[account(
+        mut,
+        constraint = escrow.first_verifier == Some(verifier.key()) @ EscrowError::InvalidVerifierAccount
+    )]
+    pub verifier: AccountInfo<'info>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee or forfeits
+    /// dispute bonds to the treasury
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required for USD escrows: Pyth update that reprices them at settlement
+    pub pyth_price_update: Option<Account<'info, PriceUpdateV2>>,
+
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: UncheckedAccount<'info>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: UncheckedAccount<'info>,
+
+    /// Optional: score histories that record this resolution
+    #[account(mut, seeds = [b"reputation_history", escrow.agent.as_ref()], bump = agent_reputation_history.bump)]
+    pub agent_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    #[account(mut, seeds = [b"reputation_history", escrow.api.as_ref()], bump = api_reputation_history.bump)]
+    pub api_reputation_history: Option<Account<'info, ReputationHistory>>,
+
+    /// Required when the escrow has an assigned verifier
+    #[account(mut)]
+    pub verifier_oracle: Option<Account<'info, VerifierOracleAccount>>,
+
+    /// Required when the escrow is covered by the API's service bond
+    #[account(mut, seeds = [b"service_bond", escrow.api.as_ref()], bump = service_bond.bump)]
+    pub service_bond: Option<Account<'info, ServiceBond>>,
+
+    /// CHECK: API's penalties PDA, updated when the provider tracks penalties
+    #[account(mut, seeds = [b"provider_penalties", escrow.api.as_ref()], bump)]
+    pub api_penalties: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitProviderPenalties<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ProviderPenalties::INIT_SPACE,
+        seeds = [b"provider_penalties", provider.key().as_ref()],
+        bump
+    )]
+    pub provider_penalties: Account<'info, ProviderPenalties>,
+
+    /// CHECK: Provider whose penalties are tracked
+    pub provider: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LiftSuspension<'info> {
+    #[account(
+        mut,
+        seeds = [b"provider_penalties", provider_penalties.provider.as_ref()],
+        bump = provider_penalties.bump
+    )]
+    pub provider_penalties: Account<'info, ProviderPenalties>,
+}
+
+#[derive(Accounts)]
+pub struct InitRateLimiter<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RateLimiter::INIT_SPACE,
+        seeds = [b"rate_limit", entity.key().as_ref()],
+        bump
+    )]
+    pub rate_limiter: Account<'info, RateLimiter>,
+
+    /// CHECK: Entity being rate limited
+    pub entity: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeVerificationLevel<'info> {
+    #[account(
+        mut,
+        seeds = [b"rate_limit", entity.key().as_ref()],
+        bump = rate_limiter.bump
+    )]
+    pub rate_limiter: Account<'info, RateLimiter>,
+
+    /// Pays the stake when upgrading without the authority
+    #[account(mut)]
+    pub entity: Signer<'info>,
+
+    /// Rate limits are shared across namespaces, so only the default
+    /// namespace authority may grant levels
+    #[account(seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required for any level other than `Staked`
+    #[account(address = config.authority @ EscrowError::Unauthorized)]
+    pub authority: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVerificationStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"rate_limit", entity.key().as_ref()],
+        bump = rate_limiter.bump
+    )]
+    pub rate_limiter: Account<'info, RateLimiter>,
+
+    #[account(mut)]
+    pub entity: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMilestonePlan<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + MilestonePlan::INIT_SPACE,
+        seeds = [b"milestones", escrow.key().as_ref()],
+        bump
+    )]
+    pub milestone_plan: Account<'info, MilestonePlan>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseMilestone<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"milestones", escrow.key().as_ref()],
+        bump = milestone_plan.bump
+    )]
+    pub milestone_plan: Account<'info, MilestonePlan>,
+
+    /// The agent, or anyone once the escrow has expired
+    pub caller: Signer<'info>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// Required for the last milestone when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct DisputeMilestone<'info> {
+    #[account(
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"milestones", escrow.key().as_ref()],
+        bump = milestone_plan.bump
+    )]
+    pub milestone_plan: Account<'info, MilestonePlan>,
+
+    pub agent: Signer<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub rate_limiter: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMilestoneDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
+        bump = escrow.bump,
+        constraint = escrow.mint.is_none() @ EscrowError::TokenEscrowRequiresSplPath
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"milestones", escrow.key().as_ref()],
+        bump = milestone_plan.bump
+    )]
+    pub milestone_plan: Account<'info, MilestonePlan>,
+
+    #[account(mut, address = escrow.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Required when the escrow names a refund recipient
+    #[account(mut)]
+    pub refund_recipient: Option<SystemAccount<'info>>,
+
+    /// CHECK: API wallet address, must match the escrow
+    #[account(mut, address = escrow.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: Verifier oracle public key
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", escrow.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config governing the escrow
+    #[account(seeds = [b"global_config", escrow.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+
+    /// Required for the last milestone when the escrow's exposure is tracked
+    /// CHECK: Agent's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.agent.as_ref()], bump)]
+    pub agent_reputation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: API's reputation PDA, deserialized best-effort in the handler
+    #[account(mut, seeds = [b"reputation", escrow.api.as_ref()], bump)]
+    pub api_reputation: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(namespace: Pubkey)]
+pub struct CreateSubscription<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + SubscriptionEscrow::INIT_SPACE,
+        seeds = [b"subscription", agent.key().as_ref(), api.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", agent.key().as_ref(), subscription.api.as_ref()],
+        bump = subscription.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPeriod<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.agent.as_ref(), api.key().as_ref()],
+        bump = subscription.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    #[account(mut)]
+    pub api: Signer<'info>,
+
+    #[account(mut, address = subscription.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Namespace config governing the subscription
+    #[account(seeds = [b"global_config", subscription.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct DisputePeriod<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", agent.key().as_ref(), subscription.api.as_ref()],
+        bump = subscription.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    pub agent: Signer<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub rate_limiter: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolvePeriodDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.agent.as_ref(), subscription.api.as_ref()],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    #[account(mut, address = subscription.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// CHECK: API wallet address, must match the subscription
+    #[account(mut, address = subscription.api @ EscrowError::Unauthorized)]
+    pub api: AccountInfo<'info>,
+
+    /// CHECK: Verifier oracle public key
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", subscription.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config governing the subscription
+    #[account(seeds = [b"global_config", subscription.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription", agent.key().as_ref(), subscription.api.as_ref()],
+        bump = subscription.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub subscription: Account<'info, SubscriptionEscrow>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(namespace: Pubkey)]
+pub struct OpenPaymentChannel<'info> {
+    #[account(
+        init,
+        payer = agent,
+        space = 8 + PaymentChannel::INIT_SPACE,
+        seeds = [b"payment_channel", agent.key().as_ref(), api.key().as_ref()],
+        bump
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    /// CHECK: API wallet address
+    pub api: AccountInfo<'info>,
+
+    #[account(seeds = [b"global_config", namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// CHECK: Agent's restriction PDA; restricted agents may only pay allowlisted APIs
+    #[account(seeds = [b"agent_restriction", agent.key().as_ref()], bump)]
+    pub agent_restriction: UncheckedAccount<'info>,
+
+    /// Required for restricted agents: their organization's allowlist
+    pub allowlist: Option<Account<'info, Allowlist>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundPaymentChannel<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", agent.key().as_ref(), channel.api.as_ref()],
+        bump = channel.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordChannelDebit<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", agent.key().as_ref(), channel.api.as_ref()],
+        bump = channel.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordChannelCredit<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", channel.agent.as_ref(), api.key().as_ref()],
+        bump = channel.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    pub api: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DisputeChannelEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", agent.key().as_ref(), channel.api.as_ref()],
+        bump = channel.bump,
+        has_one = agent @ EscrowError::Unauthorized
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    pub agent: Signer<'info>,
+
+    /// CHECK: Agent's rate limiter PDA, charged when initialized
+    #[account(mut, seeds = [b"rate_limit", agent.key().as_ref()], bump)]
+    pub rate_limiter: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveChannelEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", channel.agent.as_ref(), channel.api.as_ref()],
+        bump = channel.bump
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    /// CHECK: Verifier oracle public key
+    pub verifier: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar for Ed25519 signature verification
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Signing keys approved for the namespace; `verifier` must be one
+    #[account(
+        seeds = [b"verifier_registry", channel.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    /// Consumed nonces of `verifier`
+    #[account(
+        mut,
+        seeds = [b"used_nonces", verifier.key().as_ref()],
+        bump = verifier_nonces.bump
+    )]
+    pub verifier_nonces: Account<'info, UsedNonces>,
+
+    /// Namespace config governing the channel
+    #[account(seeds = [b"global_config", channel.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SettleChannel<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", channel.agent.as_ref(), api.key().as_ref()],
+        bump = channel.bump,
+        has_one = api @ EscrowError::Unauthorized
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    #[account(mut)]
+    pub api: Signer<'info>,
+
+    #[account(mut, address = channel.agent @ EscrowError::Unauthorized)]
+    pub agent: SystemAccount<'info>,
+
+    /// Namespace config governing the channel
+    #[account(seeds = [b"global_config", channel.namespace.as_ref()], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Required when the namespace charges a protocol fee
+    /// CHECK: Must match `config.treasury`
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePaymentChannel<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment_channel", agent.key().as_ref(), channel.api.as_ref()],
+        bump = channel.bump,
+        has_one = agent @ EscrowError::Unauthorized,
+        constraint = channel.entries.is_empty() @ EscrowError::ChannelEntriesPending,
+        close = agent
+    )]
+    pub channel: Account<'info, PaymentChannel>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProtocolTreasury::INIT_SPACE,
+        seeds = [b"treasury", config.namespace.as_ref()],
+        bump
+    )]
+    pub treasury: Account<'info, ProtocolTreasury>,
+
+    #[account(
+        mut,
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"treasury", config.namespace.as_ref()],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, ProtocolTreasury>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Any wallet chosen by the authority
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Default namespace config; its authority can pause every namespace
+    #[account(
+        seeds = [b"global_config", DEFAULT_NAMESPACE.as_ref()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVerifierRegistry<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VerifierRegistry::INIT_SPACE,
+        seeds = [b"verifier_registry", config.namespace.as_ref()],
+        bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageVerifierRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_registry", config.namespace.as_ref()],
+        bump = verifier_registry.bump
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(arbitrator_authority: Pubkey)]
+pub struct RegisterArbitrator<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Arbitrator::INIT_SPACE,
+        seeds = [b"arbitrator", config.namespace.as_ref(), arbitrator_authority.as_ref()],
+        bump
+    )]
+    pub arbitrator: Account<'info, Arbitrator>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveArbitrator<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"arbitrator", config.namespace.as_ref(), arbitrator.authority.as_ref()],
+        bump = arbitrator.bump
+    )]
+    pub arbitrator: Account<'info, Arbitrator>,
+
+    #[account(
+        seeds = [b"global_config", config.namespace.as_ref()],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+// ============================================================================
+// State
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct Escrow {
+    pub agent: Pubkey,                    // 32
+    pub api: Pubkey,                      // 32
+    pub namespace: Pubkey,                // 32 - fixed offset for memcmp filters
+    pub version: u8,                      // 1 - layout::ESCROW_VERSION
+    pub flags: u16,                       // 2 - status and booleans, see `layout`
+    pub amount: u64,                      // 8
+    pub created_at: i64,                  // 8
+    pub expires_at: i64,                  // 8
+    #[max_len(64)]
+    pub transaction_id: String,           // 4 + 64
+    pub bump: u8,                         // 1
+    pub quality_score: Option<u8>,        // 1 + 1
+    pub refund_percentage: Option<u8>,    // 1 + 1
+    pub assigned_verifier: Option<Pubkey>, // 1 + 32
+    pub spam_deposit: u64,                // 8 - held in the agent's spam vault
+    pub response_hash: Option<[u8; 32]>,  // 1 + 32 - committed by the API
+    pub delivered_at: Option<i64>,        // 1 + 8
+    pub assessed_content_hash: Option<[u8; 32]>, // 1 + 32 - attested by verifier
+    pub time_lock: i64,                   // 8 - original lock duration
+    pub dispute_window: i64,              // 8 - seconds disputes are accepted, see `dispute_deadline`
+    pub dispute_window_extension_count: u8, // 1
+    pub priority_fee: u64,                // 8 - held on top of amount for the verifier
+    pub payment_reference: Option<PaymentReference>, // 1 + 80 - originating HTTP 402 exchange
+    pub paid_to_api: u64,                 // 8 - net of protocol fees
+    pub refunded_to_agent: u64,           // 8
+    pub fees_paid: u64,                   // 8 - protocol fees and crank tips
+    pub mint: Option<Pubkey>,             // 1 + 32 - SPL escrows hold this token in their vault
+    pub settled_at: Option<i64>,          // 1 + 8 - set on release or resolution
+    pub dispute_bond: u64,                // 8 - dispute cost held on top of amount until resolution
+    pub resolution_nonce: u64,            // 8 - creation slot, signed into resolutions of this escrow
+    pub appeal_deadline: Option<i64>,     // 1 + 8 - end of a held resolution's appeal window
+    pub first_verifier: Option<Pubkey>,   // 1 + 32 - verifier of a held resolution
+    pub resolution_signature: Option<[u8; 64]>, // 1 + 64 - verifier signature of a submitted resolution
+    pub usd_target_cents: Option<u64>,    // 1 + 8 - value of a USD-priced escrow
+    pub streamed_amount: u64,             // 8 - withdrawn by the API through `withdraw_streamed`
+    pub arbitrator: Option<Pubkey>,       // 1 + 32 - arbitrator a held resolution was escalated to
+    pub escalated_by: Option<Pubkey>,     // 1 + 32 - party that posted the arbitration bond
+    pub arbitration_bond: u64,            // 8 - held on top of amount until the ruling
+    pub oracle_fee_paid: u64,             // 8 - taken out of amount for the resolving verifier and any relayer
+    pub request_hash: Option<[u8; 32]>,   // 1 + 32 - committed by the agent at creation
+    pub refund_recipient: Option<Pubkey>, // 1 + 32 - dispute refunds go here instead of the agent
+    pub delegate: Option<Pubkey>,         // 1 + 32 - may release or dispute for the agent
+    #[max_len(MAX_METADATA_URI_LEN)]
+    pub metadata_uri: Option<String>,     // 1 + 4 + 200 - off-chain description of the API call
+    pub disputed_at: Option<i64>,         // 1 + 8 - set by `mark_disputed`
+    pub dispute_reason: Option<DisputeReason>, // 1 + 1 - claim category given to `mark_disputed`
+    pub evidence_hash: Option<[u8; 32]>,  // 1 + 32 - digest of the agent's dispute evidence
+    pub provider_evidence_hash: Option<[u8; 32]>, // 1 + 32 - digest of the API's counter-evidence
+    #[max_len(MAX_COMMENT_URI_LEN)]
+    pub provider_comment_uri: Option<String>, // 1 + 4 + 200 - API's account of the exchange
+    pub stale_dispute_terms: Option<StaleDisputeTerms>, // 1 + 9 - agreed split for unresolved disputes
+    pub release_tip_lamports: u64,        // 8 - offered by the API to whoever releases after expiry
+    pub refund_curve: Option<[RefundStep; REFUND_CURVE_STEPS]>, // 1 + 10 - from the API's terms, replaces the namespace's
+    pub quality_sub_scores: Option<[u8; SCORE_DIMENSIONS]>, // 1 + 4 - per dimension, for weighted work agreements
+}
+
+impl Escrow {
+    /// Last moment `mark_disputed` accepts a dispute
+    ///
+    /// The window runs from delivery once the API acknowledges it, and from
+    /// creation until then, independently of `expires_at`. Streaming escrows
+    /// accept disputes until expiry as well. Permissionless auto-release
+    /// waits for it to close.
+    pub fn dispute_deadline(&self) -> i64 {
+        let deadline = self
+            .delivered_at
+            .unwrap_or(self.created_at)
+            .saturating_add(self.dispute_window);
+        if self.streaming() {
+            deadline.max(self.expires_at)
+        } else {
+            deadline
+        }
+    }
+
+    /// `expires_at` once the API opts into release at the dispute deadline
+    ///
+    /// Never later than the current time lock. Streaming escrows keep
+    /// theirs, since it paces the stream.
+    pub fn early_release_at(&self) -> i64 {
+        if self.streaming() {
+            self.expires_at
+        } else {
+            self.expires_at.min(self.dispute_deadline())
+        }
+    }
+
+    /// Refund owed to the agent for `quality_score`: under the curve copied
+    /// from the API's terms, else the namespace's
+    pub fn refund_percentage_for(&self, config: &GlobalConfig, quality_score: u8) -> u8 {
+        match &self.refund_curve {
+            Some(curve) => curve_refund_percentage(curve, quality_score),
+            None => config.refund_percentage_for(quality_score),
+        }
+    }
+
+    /// Escrowed amount neither streamed to the API nor paid to the
+    /// resolving verifier
+    pub fn open_amount(&self) -> u64 {
+        self.amount - self.streamed_amount - self.oracle_fee_paid
+    }
+
+    /// Share of the amount vested by `now`, linearly from creation to expiry
+    pub fn vested_amount(&self, now: i64) -> Result<u64> {
+        let duration = self.expires_at - self.created_at;
+        if duration <= 0 || now >= self.expires_at {
+            return Ok(self.amount);
+        }
+        let elapsed = (now - self.created_at).max(0) as u128;
+        let vested = (self.amount as u128)
+            .checked_mul(elapsed)
+            .ok_or(EscrowError::ArithmeticOverflow)?
+            / duration as u128;
+        Ok(vested as u64)
+    }
+
+    /// Earliest time `close_escrow` accepts this escrow, `None` while unsettled
+    ///
+    /// Escrows settled before `settled_at` was recorded count from expiry.
+    pub fn closable_at(&self) -> Option<i64> {
+        match self.status() {
+            EscrowStatus::Released | EscrowStatus::Resolved => Some(
+                self.settled_at
+                    .unwrap_or(self.expires_at)
+                    .saturating_add(ESCROW_CLOSE_COOLDOWN),
+            ),
+            EscrowStatus::Active | EscrowStatus::Disputed => None,
+        }
+    }
+
+    /// Earliest time `release_and_close` accepts this escrow: a full
+    /// `ESCROW_CLOSE_COOLDOWN` after anyone could have released it
+    pub fn sweepable_at(&self) -> i64 {
+        self.expires_at
+            .max(self.dispute_deadline())
+            .saturating_add(ESCROW_CLOSE_COOLDOWN)
+    }
+
+    /// Payouts written by every transfer site, checked by `audit_escrow`
+    pub fn recorded_accounting(&self) -> EscrowAccounting {
+        EscrowAccounting {
+            refunded_to_agent: self.refunded_to_agent,
+            paid_to_api: self.paid_to_api,
+            fees_paid: self.fees_paid,
+        }
+    }
+
+    pub fn status(&self) -> EscrowStatus {
+        EscrowStatus::from_bits(self.flags)
+    }
+
+    pub fn set_status(&mut self, status: EscrowStatus) {
+        self.flags = (self.flags & !layout::STATUS_MASK) | status.to_bits();
+    }
+
+    /// Amount counted in both parties' `locked_value`
+    pub fn exposure_tracked(&self) -> bool {
+        self.flag(layout::FLAG_EXPOSURE_TRACKED)
+    }
+
+    pub fn set_exposure_tracked(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_EXPOSURE_TRACKED, value);
+    }
+
+    /// Arbitration opted out; release-only escrow
+    pub fn no_dispute(&self) -> bool {
+        self.flag(layout::FLAG_NO_DISPUTE)
+    }
+
+    pub fn set_no_dispute(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_NO_DISPUTE, value);
+    }
+
+    /// Split by default after oracle failure
+    pub fn settled_stale(&self) -> bool {
+        self.flag(layout::FLAG_SETTLED_STALE)
+    }
+
+    pub fn set_settled_stale(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_SETTLED_STALE, value);
+    }
+
+    /// Covered by the API's service bond at creation
+    pub fn service_bonded(&self) -> bool {
+        self.flag(layout::FLAG_SERVICE_BONDED)
+    }
+
+    pub fn set_service_bonded(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_SERVICE_BONDED, value);
+    }
+
+    /// Resolutions must reference the escrow's `WorkAgreement`
+    pub fn has_work_agreement(&self) -> bool {
+        self.flag(layout::FLAG_WORK_AGREEMENT)
+    }
+
+    pub fn set_has_work_agreement(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_WORK_AGREEMENT, value);
+    }
+
+    /// Settles through its `MilestonePlan` rather than as a whole
+    pub fn has_milestones(&self) -> bool {
+        self.flag(layout::FLAG_MILESTONES)
+    }
+
+    pub fn set_has_milestones(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_MILESTONES, value);
+    }
+
+    /// The agent itself or the delegate it named
+    pub fn acts_for_agent(&self, key: &Pubkey) -> bool {
+        *key == self.agent || self.delegate == Some(*key)
+    }
+
+    /// Tip `release_funds` offers `caller`; the agent's own releases earn none
+    pub fn release_tip_for(&self, caller: &Pubkey) -> u64 {
+        if self.acts_for_agent(caller) {
+            0
+        } else {
+            self.release_tip_lamports
+        }
+    }
+
+    /// A verifier ruled on the dispute and the payout awaits its appeal window
+    pub fn resolution_held(&self) -> bool {
+        self.status() == EscrowStatus::Disputed && self.appeal_deadline.is_some()
+    }
+
+    /// Seconds the escrow has been disputed, None if it never was
+    pub fn dispute_duration(&self, now: i64) -> Option<i64> {
+        self.disputed_at.map(|disputed_at| now.saturating_sub(disputed_at))
+    }
+
+    /// When an unresolved dispute may go through `settle_stale_dispute`
+    ///
+    /// Escrows with their own terms wait their verifier timeout from the
+    /// dispute, but never past an appealed resolution's deadline. Others,
+    /// and escrows disputed before disputes were timestamped, wait
+    /// `STALE_DISPUTE_TIMEOUT` past expiry, the dispute window and any
+    /// appeal deadline.
+    pub fn stale_at(&self) -> Result<i64> {
+        let appeal_deadline = self.appeal_deadline.unwrap_or(i64::MIN);
+        let stale_at = match (self.stale_dispute_terms, self.disputed_at) {
+            (Some(terms), Some(disputed_at)) => disputed_at
+                .checked_add(terms.verifier_timeout)
+                .map(|timeout| timeout.max(appeal_deadline)),
+            _ => self
+                .expires_at
+                .max(self.dispute_deadline())
+                .max(appeal_deadline)
+                .checked_add(STALE_DISPUTE_TIMEOUT),
+        };
+        Ok(stale_at.ok_or(EscrowError::ArithmeticOverflow)?)
+    }
+
+    /// Refund `settle_stale_dispute` pays the agent
+    pub fn stale_refund_percentage(&self) -> u8 {
+        self.stale_dispute_terms
+            .map_or(STALE_DISPUTE_REFUND_PERCENTAGE, |terms| terms.refund_percentage)
+    }
+
+    /// The held resolution was appealed to the namespace's verifier set
+    pub fn appealed(&self) -> bool {
+        self.flag(layout::FLAG_APPEALED)
+    }
+
+    pub fn set_appealed(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_APPEALED, value);
+    }
+
+    /// Vests to the API over the time lock
+    pub fn streaming(&self) -> bool {
+        self.flag(layout::FLAG_STREAMING)
+    }
+
+    pub fn set_streaming(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_STREAMING, value);
+    }
+
+    /// Time lock counts from delivery; see `enable_delivery_clock`
+    pub fn delivery_clock(&self) -> bool {
+        self.flag(layout::FLAG_DELIVERY_CLOCK)
+    }
+
+    pub fn set_delivery_clock(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_DELIVERY_CLOCK, value);
+    }
+
+    /// A receipt was minted to the agent; see `mint_escrow_receipt`
+    pub fn receipt_minted(&self) -> bool {
+        self.flag(layout::FLAG_RECEIPT_MINTED)
+    }
+
+    pub fn set_receipt_minted(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_RECEIPT_MINTED, value);
+    }
+
+    /// Listed in the parties' escrow indexes; see `index_escrow`
+    pub fn indexed(&self) -> bool {
+        self.flag(layout::FLAG_INDEXED)
+    }
+
+    pub fn set_indexed(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_INDEXED, value);
+    }
+
+    /// The held resolution awaits the ruling of the arbitrator it was escalated to
+    pub fn escalated(&self) -> bool {
+        self.flag(layout::FLAG_ESCALATED)
+    }
+
+    pub fn set_escalated(&mut self, value: bool) {
+        self.set_flag(layout::FLAG_ESCALATED, value);
+    }
+
+    fn flag(&self, bit: u16) -> bool {
+        self.flags & bit != 0
+    }
+
+    fn set_flag(&mut self, bit: u16, value: bool) {
+        if value {
+            self.flags |= bit;
+        } else {
+            self.flags &= !bit;
+        }
+    }
+}
+
+/// `Escrow` as laid out before status and booleans moved into `flags`.
+/// Read only by `migrate_escrow`.
+#[derive(AnchorDeserialize, InitSpace)]
+pub struct LegacyEscrowV0 {
+    pub agent: Pubkey,
+    pub api: Pubkey,
+    pub namespace: Pubkey,
+    pub amount: u64,
+    pub status: EscrowStatus,
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[max_len(64)]
+    pub transaction_id: String,
+    pub bump: u8,
+    pub quality_score: Option<u8>,
+    pub refund_percentage: Option<u8>,
+    pub exposure_tracked: bool,
+    pub assigned_verifier: Option<Pubkey>,
+    pub spam_deposit: u64,
+    pub no_dispute: bool,
+    pub response_hash: Option<[u8; 32]>,
+    pub delivered_at: Option<i64>,
+    pub assessed_content_hash: Option<[u8; 32]>,
+    pub time_lock: i64,
+    pub dispute_window: i64,
+    pub dispute_window_extension_count: u8,
+    pub settled_stale: bool,
+    pub priority_fee: u64,
+}
+
+impl From<LegacyEscrowV0> for Escrow {
+    fn from(legacy: LegacyEscrowV0) -> Self {
+        let mut escrow = Escrow {
+            agent: legacy.agent,
+            api: legacy.api,
+            namespace: legacy.namespace,
+            version: layout::ESCROW_VERSION,
+            flags: 0,
+            amount: legacy.amount,
+            created_at: legacy.created_at,
+            expires_at: legacy.expires_at,
+            transaction_id: legacy.transaction_id,
+            bump: legacy.bump,
+            quality_score: legacy.quality_score,
+            refund_percentage: legacy.refund_percentage,
+            assigned_verifier: legacy.assigned_verifier,
+            spam_deposit: legacy.spam_deposit,
+            response_hash: legacy.response_hash,
+            delivered_at: legacy.delivered_at,
+            assessed_content_hash: legacy.assessed_content_hash,
+            time_lock: legacy.time_lock,
+            dispute_window: legacy.dispute_window,
+            dispute_window_extension_count: legacy.dispute_window_extension_count,
+            priority_fee: legacy.priority_fee,
+            payment_reference: None,
+            paid_to_api: 0,
+            refunded_to_agent: 0,
+            fees_paid: 0,
+            mint: None,
+            settled_at: None,
+            dispute_bond: 0,
+            resolution_nonce: 0,
+            appeal_deadline: None,
+            first_verifier: None,
+            resolution_signature: None,
+            usd_target_cents: None,
+            streamed_amount: 0,
+            arbitrator: None,
+            escalated_by: None,
+            arbitration_bond: 0,
+            oracle_fee_paid: 0,
+            request_hash: None,
+            refund_recipient: None,
+            delegate: None,
+            metadata_uri: None,
+            disputed_at: None,
+            dispute_reason: None,
+            evidence_hash: None,
+            provider_evidence_hash: None,
+            provider_comment_uri: None,
+            stale_dispute_terms: None,
+            release_tip_lamports: 0,
+            refund_curve: None,
+            quality_sub_scores: None,
+        };
+        escrow.set_status(legacy.status);
+        escrow.set_exposure_tracked(legacy.exposure_tracked);
+        escrow.set_no_dispute(legacy.no_dispute);
+        escrow.set_settled_stale(legacy.settled_stale);
+        escrow
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub enum EscrowStatus {
+    Active,      // Payment locked, awaiting resolution
+    Released,    // Funds released to API (happy path)
+    Disputed,    // Agent disputed quality
+    Resolved,    // Dispute resolved with refund split
+}
+
+impl EscrowStatus {
+    fn to_bits(&self) -> u16 {
+        match self {
+            EscrowStatus::Active => layout::STATUS_ACTIVE,
+            EscrowStatus::Released => layout::STATUS_RELEASED,
+            EscrowStatus::Disputed => layout::STATUS_DISPUTED,
+            EscrowStatus::Resolved => layout::STATUS_RESOLVED,
+        }
+    }
+
+    fn from_bits(flags: u16) -> Self {
+        match flags & layout::STATUS_MASK {
+            layout::STATUS_ACTIVE => EscrowStatus::Active,
+            layout::STATUS_RELEASED => EscrowStatus::Released,
+            layout::STATUS_DISPUTED => EscrowStatus::Disputed,
+            _ => EscrowStatus::Resolved,
+        }
+    }
+}
+
+/// What the agent claims went wrong, given to `mark_disputed`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum DisputeReason {
+    Other,        // Only described by the evidence
+    NotDelivered, // No response was delivered
+    Incomplete,   // Response truncated or missing requested data
+    InvalidData,  // Response malformed or not what was requested
+    LowQuality,   // Delivered but below the expected quality
+    SlaBreach,    // Latency or availability terms missed
+}
+
+/// How a dispute's ruling was attested, as reported in `DisputeResolved`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolutionMethod {
+    Ed25519,     // Signed by a registered verifier
+    Switchboard, // Switchboard quality feed
+    Pyth,        // Pyth quality feed
+    Quorum,      // Threshold of a verifier set
+    Arbitrator,  // Escalated ruling from the assigned arbitrator
+}
+
+/// Why a reputation score was recomputed, as reported in `ReputationUpdated`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReputationChangeReason {
+    DisputeWon,     // 75%+ of the disputed amount went the entity's way
+    DisputePartial,
+    DisputeLost,    // Under 25% went the entity's way
+    Decay,          // Idle time, via `refresh_reputation`
+}
+
+impl ReputationChangeReason {
+    /// Outcome of a dispute for a party that got `favourable_percentage` of
+    /// it: the refund for the agent, the remainder for the API
+    fn dispute(favourable_percentage: u8) -> Self {
+        match favourable_percentage {
+            75.. => ReputationChangeReason::DisputeWon,
+            25.. => ReputationChangeReason::DisputePartial,
+            _ => ReputationChangeReason::DisputeLost,
+        }
+    }
+}
+
+/// Entity Reputation - tracks agent/provider performance on-chain
+#[account]
+#[derive(InitSpace)]
+pub struct EntityReputation {
+    pub entity: Pubkey,                   // 32
+    pub entity_type: EntityType,          // 1 + 1
+    pub total_transactions: u64,          // 8
+    pub disputes_filed: u64,              // 8
+    pub disputes_won: u64,                // 8 - Quality <50
+    pub disputes_partial: u64,            // 8 - Quality 50-79
+    pub disputes_lost: u64,               // 8 - Quality >=80
+    pub quality_sum: u64,                 // 8 - quality scores received, each times its `reputation_weight`
+    pub quality_count: u64,               // 8 - total weight in `quality_sum`, see `average_quality_bps`
+    pub poor_quality_count: u32,          // 4 - provider resolutions below POOR_QUALITY_THRESHOLD
+    pub reputation_score: u16,            // 2 - 0-1000 score
+    pub current_win_streak: u16,          // 2
+    pub longest_win_streak: u16,          // 2
+    pub worst_quality_received: u8,       // 1
+    pub best_quality_received: u8,        // 1
+    pub total_refunds_received_lamports: u64, // 8
+    pub total_payments_made_lamports: u64,    // 8
+    pub locked_value: u64,                // 8 - value in open escrows
+    pub total_volume_lamports: u128,      // 16
+    pub volume_usd_cents: u128,           // 16 - currency-neutral volume
+    pub last_price_feed_used: Option<Pubkey>, // 1 + 32
+    pub price_at_last_update: u64,        // 8 - US cents per SOL
+    pub created_at: i64,                  // 8
+    pub last_updated: i64,                // 8
+    pub bump: u8,                         // 1
+    pub recent_counterparties: [u32; RECENT_COUNTERPARTIES], // 4 * 8 - fingerprints, newest first, 0 = empty
+    pub repeat_transactions: u64,         // 8 - resolutions with a counterparty already in `recent_counterparties`
+}
+
+impl EntityReputation {
+    /// Bytes appended for counterparty tracking, see `migrate_reputation`
+    const COUNTERPARTY_SPACE: usize = 4 * RECENT_COUNTERPARTIES + 8;
+
+    /// Note a resolution with `counterparty`, returning how many of the
+    /// recent resolutions were with it too
+    fn record_counterparty(&mut self, counterparty: &Pubkey) -> u64 {
+        let fingerprint = counterparty_fingerprint(counterparty);
+        let repeats = self
+            .recent_counterparties
+            .iter()
+            .filter(|&&seen| seen == fingerprint)
+            .count() as u64;
+        if repeats > 0 {
+            self.repeat_transactions = self.repeat_transactions.saturating_add(1);
+        }
+        self.recent_counterparties.rotate_right(1);
+        self.recent_counterparties[0] = fingerprint;
+        repeats
+    }
+
+    /// Distinct counterparties among the recent resolutions
+    pub fn distinct_recent_counterparties(&self) -> u8 {
+        let mut distinct = 0;
+        for (i, fingerprint) in self.recent_counterparties.iter().enumerate() {
+            if *fingerprint != 0 && !self.recent_counterparties[..i].contains(fingerprint) {
+                distinct += 1;
+            }
+        }
+        distinct
+    }
+
+    /// Average quality received, in hundredths of a point (0-10000)
+    pub fn average_quality_bps(&self) -> u16 {
+        if self.quality_count == 0 {
+            return 0;
+        }
+        let average = (self.quality_sum as u128 * 100 + self.quality_count as u128 / 2)
+            / self.quality_count as u128;
+        average.min(MAX_BPS as u128) as u16
+    }
+}
+
+/// Reputation History - ring buffer of an entity's most recent scores
+#[account]
+#[derive(InitSpace)]
+pub struct ReputationHistory {
+    pub entity: Pubkey,                   // 32
+    pub snapshots: [ScoreSnapshot; REPUTATION_HISTORY_LEN], // 10 * 30
+    pub next_index: u8,                   // 1 - slot the next snapshot overwrites
+    pub count: u8,                        // 1 - snapshots recorded, up to the capacity
+    pub bump: u8,                         // 1
+}
+
+impl ReputationHistory {
+    pub fn push(&mut self, score: u16, timestamp: i64) {
+        self.snapshots[self.next_index as usize] = ScoreSnapshot { score, timestamp };
+        self.next_index = ((self.next_index as usize + 1) % REPUTATION_HISTORY_LEN) as u8;
+        self.count = (self.count as usize + 1).min(REPUTATION_HISTORY_LEN) as u8;
+    }
+
+    /// Snapshots recorded so far, oldest first
+    pub fn snapshots(&self) -> impl Iterator<Item = &ScoreSnapshot> {
+        let start = (self.next_index as usize + REPUTATION_HISTORY_LEN - self.count as usize)
+            % REPUTATION_HISTORY_LEN;
+        (0..self.count as usize).map(move |i| &self.snapshots[(start + i) % REPUTATION_HISTORY_LEN])
+    }
+
+    pub fn latest(&self) -> Option<&ScoreSnapshot> {
+        self.snapshots().last()
+    }
+
+    /// How far the latest score sits below the best one recorded at or
+    /// after `since`; 0 if it has not dropped
+    pub fn drop_since(&self, since: i64) -> u16 {
+        let Some(latest) = self.latest() else {
+            return 0;
+        };
+        self.snapshots()
+            .filter(|snapshot| snapshot.timestamp >= since)
+            .map(|snapshot| snapshot.score.saturating_sub(latest.score))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct ScoreSnapshot {
+    pub score: u16,
+    pub timestamp: i64,
+}
+
+/// Reputation summary returned by `get_reputation`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct ReputationView {
+    pub entity: Pubkey,
+    pub initialized: bool,
+    pub entity_type: Option<EntityType>,
+    pub reputation_score: u16,            // decayed to the time of the call
+    pub total_transactions: u64,
+    pub disputes_filed: u64,
+    pub disputes_lost: u64,
+    pub average_quality_bps: u16,
+    pub distinct_recent_counterparties: u8,
+    pub last_updated: i64,
+}
+
+/// `EntityReputation` as laid out while it kept a rounded `u8` average.
+/// Read only by `migrate_reputation`.
+#[derive(AnchorDeserialize, InitSpace)]
+pub struct LegacyReputationV0 {
+    pub entity: Pubkey,
+    pub entity_type: EntityType,
+    pub total_transactions: u64,
+    pub disputes_filed: u64,
+    pub disputes_won: u64,
+    pub disputes_partial: u64,
+    pub disputes_lost: u64,
+    pub average_quality_received: u8,
+    pub reputation_score: u16,
+    pub current_win_streak: u16,
+    pub longest_win_streak: u16,
+    pub worst_quality_received: u8,
+    pub best_quality_received: u8,
+    pub total_refunds_received_lamports: u64,
+    pub total_payments_made_lamports: u64,
+    pub locked_value: u64,
+    pub total_volume_lamports: u128,
+    pub volume_usd_cents: u128,
+    pub last_price_feed_used: Option<Pubkey>,
+    pub price_at_last_update: u64,
+    pub created_at: i64,
+    pub last_updated: i64,
+    pub bump: u8,
+}
+
+impl From<LegacyReputationV0> for EntityReputation {
+    /// The rounding already lost cannot be recovered; the rounded average
+    /// stands in for every resolution recorded so far
+    fn from(legacy: LegacyReputationV0) -> Self {
+        EntityReputation {
+            entity: legacy.entity,
+            entity_type: legacy.entity_type,
+            total_transactions: legacy.total_transactions,
+            disputes_filed: legacy.disputes_filed,
+            disputes_won: legacy.disputes_won,
+            disputes_partial: legacy.disputes_partial,
+            disputes_lost: legacy.disputes_lost,
+            quality_sum: (legacy.average_quality_received as u64)
+                .saturating_mul(legacy.total_transactions),
+            quality_count: legacy.total_transactions,
+            poor_quality_count: 0,
+            reputation_score: legacy.reputation_score,
+            current_win_streak: legacy.current_win_streak,
+            longest_win_streak: legacy.longest_win_streak,
+            worst_quality_received: legacy.worst_quality_received,
+            best_quality_received: legacy.best_quality_received,
+            total_refunds_received_lamports: legacy.total_refunds_received_lamports,
+            total_payments_made_lamports: legacy.total_payments_made_lamports,
+            locked_value: legacy.locked_value,
+            total_volume_lamports: legacy.total_volume_lamports,
+            volume_usd_cents: legacy.volume_usd_cents,
+            last_price_feed_used: legacy.last_price_feed_used,
+            price_at_last_update: legacy.price_at_last_update,
+            created_at: legacy.created_at,
+            last_updated: legacy.last_updated,
+            bump: legacy.bump,
+            recent_counterparties: [0; RECENT_COUNTERPARTIES],
+            repeat_transactions: 0,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum EntityType {
+    Agent,
+    Provider,
+}
+
+/// Rate Limiter - prevents spam and abuse
+#[account]
+#[derive(InitSpace)]
+pub struct RateLimiter {
+    pub entity: Pubkey,                   // 32
+    pub verification_level: VerificationLevel, // 1 + 1
+    pub transactions_last_hour: u16,      // 2
+    pub transactions_last_day: u16,       // 2
+    pub disputes_last_day: u16,           // 2
+    pub last_hour_check: i64,             // 8
+    pub last_day_check: i64,              // 8
+    pub rate_limit_overridden_until: Option<i64>, // 1 + 8
+    pub staked_lamports: u64,             // 8 - held in this account for `Staked`
+    pub bump: u8,                         // 1
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, InitSpace)]
+pub enum VerificationLevel {
+    Basic,       // Just wallet (low limits)
+    Staked,      // 1+ SOL staked (medium limits)
+    Social,      // Twitter/GitHub linked (high limits)
+    KYC,         // Identity verified (unlimited)
+}
+
+/// Work Agreement - structured scope definition
+#[account]
+#[derive(InitSpace)]
+pub struct WorkAgreement {
+    pub escrow: Pubkey,                   // 32
+    #[max_len(128)]
+    pub query: String,                    // 4 + 128
+    pub required_fields: u8,              // 1 - bitmask or count
+    pub min_records: u32,                 // 4
+    pub max_age_days: u32,                // 4
+    pub min_quality_score: u8,            // 1
+    pub created_at: i64,                  // 8
+    pub bump: u8,                         // 1
+    pub refund_curve: Option<[RefundStep; REFUND_CURVE_STEPS]>, // 1 + 10 - agreed refund by quality, replaces the namespace's
+    pub score_weights: [u8; SCORE_DIMENSIONS], // 4 - percent per score dimension; all zero for a single score
+}
+
+impl WorkAgreement {
+    /// Refund owed to the agent for `quality_score` under the agreed curve,
+    /// else the escrow's
+    pub fn refund_percentage_for(&self, escrow: &Escrow, config: &GlobalConfig, quality_score: u8) -> u8 {
+        match &self.refund_curve {
+            Some(curve) => curve_refund_percentage(curve, quality_score),
+            None => escrow.refund_percentage_for(config, quality_score),
+        }
+    }
+
+    /// Whether resolutions score each dimension separately
+    pub fn weighted(&self) -> bool {
+        self.score_weights.iter().any(|&weight| weight > 0)
+    }
+
+    /// Weighted aggregate of per-dimension scores, rounded down
+    pub fn aggregate_score(&self, sub_scores: &[u8; SCORE_DIMENSIONS]) -> Result<u8> {
+        require!(
+            sub_scores.iter().all(|&score| score <= 100),
+            EscrowError::InvalidQualityScore
+        );
+        let weighted: u16 = sub_scores
+            .iter()
+            .zip(self.score_weights)
+            .map(|(&score, weight)| u16::from(score) * u16::from(weight))
+            .sum();
+        Ok((weighted / 100) as u8)
+    }
+}
+
+/// Milestone Plan - releases an escrow in tranches
+#[account]
+#[derive(InitSpace)]
+pub struct MilestonePlan {
+    pub escrow: Pubkey,                   // 32
+    #[max_len(8)]
+    pub milestones: Vec<Milestone>,       // 4 + 11 * 8
+    pub bump: u8,                         // 1
+}
+
+impl MilestonePlan {
+    /// Milestone `index`, which must currently be in `status`
+    pub fn milestone_mut(&mut self, index: u8, status: MilestoneStatus) -> Result<&mut Milestone> {
+        let milestone = self
+            .milestones
+            .get_mut(index as usize)
+            .ok_or(EscrowError::InvalidMilestone)?;
+        require!(milestone.status == status, EscrowError::InvalidMilestone);
+        Ok(milestone)
+    }
+
+    /// Escrow status once no milestone is left open: `Released` when every
+    /// milestone was released, `Resolved` when any went through a dispute
+    pub fn settled_status(&self) -> Option<EscrowStatus> {
+        let mut status = EscrowStatus::Released;
+        for milestone in &self.milestones {
+            match milestone.status {
+                MilestoneStatus::Released => {}
+                MilestoneStatus::Resolved => status = EscrowStatus::Resolved,
+                MilestoneStatus::Pending | MilestoneStatus::Disputed => return None,
+            }
+        }
+        Some(status)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct Milestone {
+    pub amount: u64,                      // 8
+    pub status: MilestoneStatus,          // 1
+    pub refund_percentage: Option<u8>,    // 1 + 1 - set when resolved
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum MilestoneStatus {
+    Pending,     // Awaiting release or dispute
+    Released,    // Paid to the API
+    Disputed,    // Held for a verifier
+    Resolved,    // Split by the verifier's refund percentage
+}
+
+/// Subscription Escrow - prepaid billing periods between an agent and an API
+#[account]
+#[derive(InitSpace)]
+pub struct SubscriptionEscrow {
+    pub agent: Pubkey,                    // 32
+    pub api: Pubkey,                      // 32
+    pub namespace: Pubkey,                // 32
+    pub price_per_period: u64,            // 8
+    pub period_seconds: i64,              // 8
+    pub period_start: i64,                // 8 - start of the oldest unsettled period
+    pub claimable_at: i64,                // 8 - end of the agent's dispute window on it
+    pub funded_periods: u32,              // 4 - prepaid and not yet settled
+    pub periods_settled: u32,             // 4 - index of the oldest unsettled period
+    pub disputed: bool,                   // 1 - oldest unsettled period held for a verifier
+    pub bump: u8,                         // 1
+}
+
+impl SubscriptionEscrow {
+    pub fn period_end(&self) -> i64 {
+        self.period_start + self.period_seconds
+    }
+
+    /// Funded periods that have started by `now`
+    pub fn started_periods(&self, now: i64) -> u32 {
+        if now < self.period_start {
+            return 0;
+        }
+        let started = (now - self.period_start) / self.period_seconds + 1;
+        started.min(self.funded_periods as i64) as u32
+    }
+
+    /// Settle the oldest period. The next one stays open to disputes for
+    /// the full window after it ends, or from `now` if it already has, so
+    /// a late claim never shortens the agent's window.
+    pub fn advance_period(&mut self, now: i64) {
+        self.period_start += self.period_seconds;
+        self.funded_periods -= 1;
+        self.periods_settled += 1;
+        self.disputed = false;
+        self.claimable_at = self.period_end().max(now) + DISPUTE_WINDOW;
+    }
+}
+
+/// Payment Channel - nets many small payments between an agent and an API
+#[account]
+#[derive(InitSpace)]
+pub struct PaymentChannel {
+    pub agent: Pubkey,                    // 32
+    pub api: Pubkey,                      // 32
+    pub namespace: Pubkey,                // 32
+    pub balance: u64,                     // 8 - deposited and not yet paid to the API
+    pub carried_credit: u64,              // 8 - API credits not yet offset against debits
+    pub next_entry_id: u64,               // 8
+    pub total_settled: u64,               // 8 - paid to the API over the channel's life
+    #[max_len(MAX_CHANNEL_ENTRIES)]
+    pub entries: Vec<ChannelEntry>,       // 4 + 32 * 26 - unsettled debits, oldest first
+    pub bump: u8,                         // 1
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct ChannelEntry {
+    pub id: u64,
+    pub amount: u64,                      // cut to the API's share once resolved
+    pub recorded_at: i64,
+    pub disputed: bool,
+    pub resolved: bool,
+}
+
+impl PaymentChannel {
+    /// Lamports owed across the unsettled entries
+    pub fn pending_debits(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.amount).sum()
+    }
+
+    pub fn entry_mut(&mut self, id: u64) -> Option<&mut ChannelEntry> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    /// Remove the entries that may settle at `now`, returning how many and
+    /// their total
+    pub fn take_settleable(&mut self, now: i64) -> (u16, u64) {
+        let settleable =
+            |entry: &ChannelEntry| !entry.disputed && (entry.resolved || now >= entry.recorded_at + DISPUTE_WINDOW);
+        let (count, total) = self
+            .entries
+            .iter()
+            .filter(|entry| settleable(entry))
+            .fold((0u16, 0u64), |(count, total), entry| (count + 1, total + entry.amount));
+        self.entries.retain(|entry| !settleable(entry));
+        (count, total)
+    }
+}
+
+/// Provider Penalties - track strikes and suspensions
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderPenalties {
+    pub provider: Pubkey,                 // 32
+    pub strike_count: u8,                 // 1
+    pub suspended: bool,                  // 1
+    pub suspension_end: Option<i64>,      // 1 + 8
+    pub total_refunds_issued: u64,        // 8
+    pub poor_quality_count: u32,          // 4 - Quality <30
+    pub created_at: i64,                  // 8
+    pub last_updated: i64,                // 8
+    pub bump: u8,                         // 1
+}
+
+impl ProviderPenalties {
+    /// When the current suspension started, if the provider is suspended
+    pub fn suspended_at(&self) -> Option<i64> {
+        if !self.suspended {
+            return None;
+        }
+        self.suspension_end
+            .map(|end| end.saturating_sub(PROVIDER_SUSPENSION_DURATION))
+    }
+
+    /// Apply one dispute resolution; returns whether it started a suspension
+    ///
+    /// Strikes keep accruing while suspended but do not extend the suspension.
+    pub fn record_resolution(&mut self, quality_score: u8, refund_amount: u64, now: i64) -> bool {
+        self.total_refunds_issued = self.total_refunds_issued.saturating_add(refund_amount);
+        self.last_updated = now;
+        if quality_score >= POOR_QUALITY_THRESHOLD {
+            return false;
+        }
+
+        self.poor_quality_count = self.poor_quality_count.saturating_add(1);
+        self.strike_count = self.strike_count.saturating_add(1);
+        if self.suspended || self.strike_count < STRIKES_BEFORE_SUSPENSION {
+            return false;
+        }
+        self.suspended = true;
+        self.suspension_end = Some(now.saturating_add(PROVIDER_SUSPENSION_DURATION));
+        true
+    }
+}
+
+/// Global Config - per-namespace settings and admin authority
+///
+/// One per namespace, seeded by the namespace key. `DEFAULT_NAMESPACE`
+/// holds the protocol-wide config.
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalConfig {
+    pub authority: Pubkey,                // 32
+    pub namespace: Pubkey,                // 32
+    pub strict_sig_adjacency: bool,       // 1 - Ed25519 ix must precede resolve
+    pub spam_protection_deposit: u64,     // 8 - 0 disables
+    pub sol_usd_price_feed: Pubkey,       // 32 - Switchboard SOL/USD feed
+    pub max_override_duration_seconds: i64, // 8 - capped at 24 hours
+    pub fee_bps: u16,                     // 2 - taken from API payouts
+    pub treasury: Pubkey,                 // 32 - receives protocol fees
+    pub min_verifier_bond_lamports: u64,  // 8
+    pub bond_grace_period_days: u16,      // 2
+    pub min_bond_raised_at: i64,          // 8 - start of the current grace period
+    pub max_dispute_window_extensions: u8, // 1
+    pub insurance_fund_bps: u16,          // 2 - of each escrow, pooled at creation
+    pub max_insurance_payout_bps: u16,    // 2 - of the escrowed amount, per claim
+    pub max_priority_fee_lamports: u64,   // 8 - 0 disables priority fees
+    pub usd_stablecoin_mint: Pubkey,      // 32 - SPL escrows in this mint count toward USD volume
+    pub cancel_grace_period_seconds: i64, // 8 - 0 requires the API to co-sign cancellations
+    pub paused: bool,                     // 1 - blocks new escrows and verifier resolutions
+    pub refund_curve: [RefundStep; REFUND_CURVE_STEPS], // 10 - refund by quality score, ascending
+    pub appeal_window_seconds: i64,       // 8 - 0 allows single-step `resolve_dispute`
+    pub pyth_sol_usd_feed_id: [u8; 32],   // 32 - Pyth SOL/USD feed for USD escrows, zero disables them
+    pub amount_tiers: [AmountTier; AMOUNT_TIERS], // 30 - escrow caps by agent reputation, ascending
+    pub dispute_bond_bps: u16,            // 2 - of the escrowed amount, before the dispute-rate multiplier
+    pub dispute_bond_to_treasury: bool,   // 1 - forfeited bonds go to the treasury instead of the API
+    pub severe_refund_slash_bps: u16,     // 2 - of a covered service bond, slashed on refunds of 90% or more
+    pub release_insurance_bps: u16,       // 2 - of each release, pooled in the insurance fund
+    pub oracle_fee_bps: u16,              // 2 - of a disputed escrow, paid to the verifier that resolves it
+    pub switchboard_queue: Pubkey,        // 32 - queue whose feeds may resolve disputes; unset disables them
+    pub attestation_max_age_seconds: i64, // 8 - oldest quality attestation the feed resolution paths accept
+    pub pyth_quality_feed_id: [u8; 32],   // 32 - Pyth feed of packed quality results, zero disables `resolve_dispute_pyth`
+    pub bump: u8,                         // 1
+}
+
+impl GlobalConfig {
+    /// Refund owed to the agent for `quality_score` under the namespace's curve
+    pub fn refund_percentage_for(&self, quality_score: u8) -> u8 {
+        curve_refund_percentage(&self.refund_curve, quality_score)
+    }
+
+    /// Share of a release kept from the API: protocol fee plus insurance cut
+    pub fn release_deduction_bps(&self) -> u16 {
+        self.fee_bps.saturating_add(self.release_insurance_bps)
+    }
+
+    /// Largest escrow an agent with `reputation_score` may hold
     ///
-    /// # Arguments
-    /// * `quality_score` - Quality score from verifier (0-100)
-    /// * `refund_percentage` - Refund percentage (0-100)
-    /// * `signature` - Ed25519 signature from verifier oracle
-    pub fn resolve_dispute(
-        ctx: Context<ResolveDispute>,
-        quality_score: u8,
-        refund_percentage: u8,
-        signature: [u8; 64],
-    ) -> Result<()> {
-        let escrow = &mut ctx.accounts.escrow;
+    /// Agents without a reputation account get the first tier.
+    pub fn max_escrow_amount_for(&self, reputation_score: Option<u16>) -> u64 {
+        tier_cap(&self.amount_tiers, reputation_score)
+    }
+}
+
+/// Cap of the highest tier `reputation_score` reaches
+fn tier_cap(tiers: &[AmountTier; AMOUNT_TIERS], reputation_score: Option<u16>) -> u64 {
+    let score = reputation_score.unwrap_or(0);
+    tiers
+        .iter()
+        .rev()
+        .find(|tier| score >= tier.min_reputation_score)
+        .unwrap_or(&tiers[0])
+        .max_amount
+}
+
+/// One tier of a namespace's escrow caps: agents scoring at least
+/// `min_reputation_score` may escrow up to `max_amount` lamports
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct AmountTier {
+    pub min_reputation_score: u16,
+    pub max_amount: u64,
+}
+
+/// Refund `curve` assigns to `quality_score`
+fn curve_refund_percentage(curve: &[RefundStep; REFUND_CURVE_STEPS], quality_score: u8) -> u8 {
+    curve
+        .iter()
+        .find(|step| quality_score <= step.max_quality_score)
+        .map_or(0, |step| step.refund_percentage)
+}
+
+/// One step of a namespace's refund curve: scores up to `max_quality_score`
+/// refund `refund_percentage` of the escrow
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct RefundStep {
+    pub max_quality_score: u8,
+    pub refund_percentage: u8,
+}
+
+/// Optional updates applied by `update_global_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct UpdateGlobalConfigParams {
+    pub strict_sig_adjacency: Option<bool>,
+    pub spam_protection_deposit: Option<u64>,
+    pub sol_usd_price_feed: Option<Pubkey>,
+    pub max_override_duration_seconds: Option<i64>,
+    pub fee_bps: Option<u16>,
+    pub treasury: Option<Pubkey>,
+    pub min_verifier_bond_lamports: Option<u64>,
+    pub bond_grace_period_days: Option<u16>,
+    pub max_dispute_window_extensions: Option<u8>,
+    pub insurance_fund_bps: Option<u16>,
+    pub max_insurance_payout_bps: Option<u16>,
+    pub max_priority_fee_lamports: Option<u64>,
+    pub usd_stablecoin_mint: Option<Pubkey>,
+    pub cancel_grace_period_seconds: Option<i64>,
+    pub refund_curve: Option<[RefundStep; REFUND_CURVE_STEPS]>,
+    pub appeal_window_seconds: Option<i64>,
+    pub pyth_sol_usd_feed_id: Option<[u8; 32]>,
+    pub amount_tiers: Option<[AmountTier; AMOUNT_TIERS]>,
+    pub dispute_bond_bps: Option<u16>,
+    pub dispute_bond_to_treasury: Option<bool>,
+    pub severe_refund_slash_bps: Option<u16>,
+    pub release_insurance_bps: Option<u16>,
+    pub oracle_fee_bps: Option<u16>,
+    pub switchboard_queue: Option<Pubkey>,
+    pub attestation_max_age_seconds: Option<i64>,
+    pub pyth_quality_feed_id: Option<[u8; 32]>,
+}
+
+/// HTTP 402 exchange an escrow pays for, committed by the agent at creation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct PaymentReference {
+    pub method_and_path_hash: [u8; 32],   // 32 - SHA-256 of "{METHOD} {path}"
+    pub challenge_nonce: [u8; 16],        // 16 - nonce the server issued in its 402 response
+    pub client_id_hash: [u8; 32],         // 32
+}
+
+/// Split agreed at creation for a dispute no verifier resolves
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct StaleDisputeTerms {
+    pub verifier_timeout: i64,            // 8 - seconds from the dispute, `MIN_VERIFIER_TIMEOUT` to `MAX_TIME_LOCK`
+    pub refund_percentage: u8,            // 1 - refund to the agent (0-100)
+}
+
+/// Where an escrow's `amount` went
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EscrowAccounting {
+    pub refunded_to_agent: u64,
+    pub paid_to_api: u64,
+    pub fees_paid: u64,
+}
+
+/// One verifier's signature for `resolve_dispute_quorum`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct QuorumSignature {
+    /// Position of the signer in `VerifierSet::verifiers`
+    pub verifier_index: u8,
+    pub signature: [u8; 64],
+}
+
+/// One entry of `batch_resolve_disputes_ed25519`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchResolution {
+    pub quality_score: u8,
+    pub content_hash: [u8; 32],
+    pub nonce: u64,
+    pub signature: [u8; 64],
+}
+
+/// Spam Protection Vault - per-agent escrow creation rate and held deposits
+#[account]
+#[derive(InitSpace)]
+pub struct SpamProtectionVault {
+    pub agent: Pubkey,                    // 32
+    pub window_start: i64,                // 8
+    pub escrows_in_window: u16,           // 2
+    pub held_lamports: u64,               // 8
+    pub deposit_count: u32,               // 4
+    pub bump: u8,                         // 1
+    pub retained_lamports: u64,           // 8 - deposits of cancelled, unaccepted escrows
+    pub retained_until: i64,              // 8
+}
+
+/// Blocklist - counterparties an entity refuses to transact with
+#[account]
+#[derive(InitSpace)]
+pub struct Blocklist {
+    pub owner: Pubkey,                    // 32
+    #[max_len(32)]
+    pub blocked: Vec<Pubkey>,             // 4 + 32 * 32 - kept sorted
+    pub bump: u8,                         // 1
+}
+
+impl Blocklist {
+    /// O(log n) lookup over the sorted entries
+    pub fn contains(&self, key: &Pubkey) -> bool {
+        self.blocked.binary_search(key).is_ok()
+    }
+}
+
+/// Allowlist - APIs an organization's restricted agents may pay
+#[account]
+#[derive(InitSpace)]
+pub struct Allowlist {
+    pub organization: Pubkey,             // 32
+    #[max_len(MAX_ALLOWLIST_ENTRIES)]
+    pub allowed: Vec<Pubkey>,             // 4 + 32 * 64 - kept sorted
+    pub bump: u8,                         // 1
+}
+
+impl Allowlist {
+    /// O(log n) lookup over the sorted entries
+    pub fn contains(&self, key: &Pubkey) -> bool {
+        self.allowed.binary_search(key).is_ok()
+    }
+}
+
+/// Organization - team account owning a set of agent keys
+#[account]
+#[derive(InitSpace)]
+pub struct Organization {
+    pub organization: Pubkey,             // 32 - admin key, also keys the allowlist
+    pub agent_count: u32,                 // 4
+    pub bump: u8,                         // 1
+}
+
+/// Organization Member - an agent key of an organization and its spend limit
+#[account]
+#[derive(InitSpace)]
+pub struct OrganizationMember {
+    pub organization: Pubkey,             // 32
+    pub agent: Pubkey,                    // 32
+    pub daily_limit: u64,                 // 8 - lamports of SOL escrows per UTC day
+    pub spent_today: u64,                 // 8
+    pub spend_day: i64,                   // 8 - day `spent_today` counts, in days since the epoch
+    pub bump: u8,                         // 1
+}
+
+impl OrganizationMember {
+    /// Add `amount` to today's spend, starting a new day's count when the
+    /// last spend was on an earlier one
+    pub fn charge(&mut self, amount: u64, now: i64) -> Result<()> {
+        let today = now / SECONDS_PER_DAY;
+        if today > self.spend_day {
+            self.spent_today = 0;
+            self.spend_day = today;
+        }
+        let spent = self
+            .spent_today
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(spent <= self.daily_limit, EscrowError::DailySpendLimitExceeded);
+        self.spent_today = spent;
+        Ok(())
+    }
+}
+
+/// Agent Restriction - binds an agent to its organization's allowlist
+#[account]
+#[derive(InitSpace)]
+pub struct AgentRestriction {
+    pub agent: Pubkey,                    // 32
+    pub organization: Pubkey,             // 32
+    pub bump: u8,                         // 1
+}
+
+/// Session Key - a hot key allowed to act for an agent until it expires
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    pub agent: Pubkey,                    // 32
+    pub session_key: Pubkey,              // 32
+    pub scope: u8,                        // 1 - `SESSION_SCOPE_*` bits
+    pub expires_at: i64,                  // 8
+    pub bump: u8,                         // 1
+}
+
+impl SessionKey {
+    /// Fail unless the session is live and its scope covers `scope`
+    pub fn authorize(&self, scope: u8, now: i64) -> Result<()> {
+        require!(now < self.expires_at, EscrowError::SessionKeyExpired);
+        require!(self.scope & scope == scope, EscrowError::SessionScopeMissing);
+        Ok(())
+    }
+}
+
+/// Agent Index - number of escrows indexed for an agent
+#[account]
+#[derive(InitSpace)]
+pub struct AgentIndex {
+    pub agent: Pubkey,                    // 32
+    pub count: u64,                       // 8 - entries at positions 0..count
+    pub bump: u8,                         // 1
+}
+
+/// API Index - number of escrows indexed for an API
+#[account]
+#[derive(InitSpace)]
+pub struct ApiIndex {
+    pub api: Pubkey,                      // 32
+    pub count: u64,                       // 8 - entries at positions 0..count
+    pub bump: u8,                         // 1
+}
+
+/// Escrow Index Entry - one position of an `AgentIndex` or `ApiIndex`
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowIndexEntry {
+    pub escrow: Pubkey,                   // 32
+}
+
+/// Verifier Oracle - a registered verifier and its assignment capacity
+#[account]
+#[derive(InitSpace)]
+pub struct VerifierOracleAccount {
+    pub verifier: Pubkey,                 // 32
+    pub namespace: Pubkey,                // 32
+    pub active: bool,                     // 1
+    pub max_concurrent_assignments: u8,   // 1
+    pub active_assignments: u8,           // 1
+    pub registered_at: i64,               // 8
+    pub bump: u8,                         // 1
+    pub paused_until: Option<i64>,        // 1 + 8 - scheduled maintenance pause
+}
+
+/// Dispute Window Extension Request - pending until the counterparty approves
+#[account]
+#[derive(InitSpace)]
+pub struct DisputeWindowExtensionRequest {
+    pub escrow: Pubkey,                   // 32
+    pub requester: Pubkey,                // 32
+    pub additional_seconds: i64,          // 8
+    pub requested_at: i64,                // 8
+    pub bump: u8,                         // 1
+}
+
+/// Protocol Treasury - program-owned account collecting a namespace's fees
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolTreasury {
+    pub namespace: Pubkey,                // 32
+    pub total_withdrawn: u64,             // 8
+    pub bump: u8,                         // 1
+}
+
+/// Insurance Fund - pooled contributions that compensate agents for oracle
+/// failures and suspended providers
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceFund {
+    pub balance: u64,                     // 8 - lamports available for payouts
+    pub total_contributed: u64,           // 8
+    pub total_paid: u64,                  // 8
+    pub bump: u8,                         // 1
+}
+
+/// Insurance Claim - one per stale-settled escrow, reviewed by governance
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceClaim {
+    pub escrow: Pubkey,                   // 32
+    pub claimant: Pubkey,                 // 32
+    pub escrow_amount: u64,               // 8 - basis for the payout cap
+    pub claim_evidence_hash: [u8; 32],    // 32
+    pub filed_at: i64,                    // 8
+    pub status: InsuranceClaimStatus,     // 1
+    pub payout_lamports: u64,             // 8
+    pub bump: u8,                         // 1
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum InsuranceClaimStatus {
+    Pending,     // Awaiting governance review
+    Paid,        // Payout transferred to the claimant
+}
+
+/// Standard escrow terms an API publishes for `initialize_escrow_from_terms`
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderTerms {
+    pub api: Pubkey,                      // 32
+    pub namespace: Pubkey,                // 32 - escrows on these terms belong to it
+    pub terms_id: u8,                     // 1 - distinguishes the API's sets of terms
+    pub min_amount: u64,                  // 8
+    pub max_amount: u64,                  // 8
+    pub time_lock: i64,                   // 8 - taken by every escrow on these terms
+    pub dispute_window: i64,              // 8 - copied to the escrow
+    pub refund_curve: [RefundStep; REFUND_CURVE_STEPS], // 10 - copied to the escrow
+    pub published_at: i64,                // 8
+    pub bump: u8,                         // 1
+}
+
+impl ProviderTerms {
+    /// Whether an escrow of `amount` locked for `time_lock` in `namespace`
+    /// is on these terms
+    pub fn admits(&self, amount: u64, time_lock: i64, namespace: &Pubkey) -> bool {
+        (self.min_amount..=self.max_amount).contains(&amount)
+            && self.time_lock == time_lock
+            && self.namespace == *namespace
+    }
+}
+
+/// Service Bond - lamports a provider stakes behind its service quality
+///
+/// Lamports above rent are exactly `bonded_lamports`.
+#[account]
+#[derive(InitSpace)]
+pub struct ServiceBond {
+    pub provider: Pubkey,                 // 32
+    pub bonded_lamports: u64,             // 8
+    pub terms_hash: [u8; 32],             // 32 - off-chain terms document
+    pub terms: ServiceBondTerms,          // 19
+    pub posted_at: i64,                   // 8 - escrows created earlier are not covered
+    pub withdrawal_requested_at: Option<i64>, // 1 + 8
+    pub pending_withdrawal: u64,          // 8
+    pub total_slashed: u64,               // 8
+    pub bump: u8,                         // 1
+}
+
+impl ServiceBond {
+    /// Whether an escrow created at `created_at` can be slashed against this bond
+    pub fn covers(&self, created_at: i64) -> bool {
+        let before_withdrawal = match self.withdrawal_requested_at {
+            Some(requested_at) => created_at < requested_at,
+            None => true,
+        };
+        created_at >= self.posted_at && before_withdrawal
+    }
+
+    /// Lamports slashed for one below-threshold resolution
+    pub fn incident_slash(&self) -> Result<u64> {
+        Ok(bps_of(self.bonded_lamports, self.terms.slash_bps)?.min(self.terms.max_slash_per_incident))
+    }
+
+    /// Lamports one resolution slashes: the provider's own terms, or the
+    /// namespace's `severe_bps` of the bond for a near-total refund if larger.
+    /// The provider's per-incident cap does not limit the severe slash.
+    pub fn resolution_slash(&self, quality_score: u8, refund_percentage: u8, severe_bps: u16) -> Result<u64> {
+        let by_terms = if quality_score < self.terms.quality_threshold {
+            self.incident_slash()?
+        } else {
+            0
+        };
+        let severe = if refund_percentage >= SEVERE_REFUND_PERCENTAGE {
+            bps_of(self.bonded_lamports, severe_bps)?
+        } else {
+            0
+        };
+        Ok(by_terms.max(severe))
+    }
+}
+
+/// Enforceable part of a provider's service bond terms
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct ServiceBondTerms {
+    pub quality_threshold: u8,            // 1 - resolutions scored below this slash the bond
+    pub slash_bps: u16,                   // 2 - share of the bond slashed per incident
+    pub max_slash_per_incident: u64,      // 8
+    pub min_active_bond: u64,             // 8 - escrows can only claim cover at or above this
+}
+
+/// Verifier Bond - lamports a verifier has staked behind its attestations
+#[account]
+#[derive(InitSpace)]
+pub struct VerifierBond {
+    pub verifier: Pubkey,                 // 32
+    pub bonded_lamports: u64,             // 8
+    pub bump: u8,                         // 1
+}
+
+/// Used Nonces - consumed nonces of one quote/attestation signer
+///
+/// Every signed quote or attestation embeds a `nonce: u64` that is marked
+/// here on use. Tracks a sliding 256-nonce window as a bitmap, so nonces may
+/// be consumed out of order; anything below `window_start` counts as used.
+#[account]
+#[derive(InitSpace)]
+pub struct UsedNonces {
+    pub signer: Pubkey,                   // 32
+    pub window_start: u64,                // 8 - always a multiple of 64
+    pub bitmap: [u64; USED_NONCE_WORDS],  // 32 - bit i = window_start + i
+    pub bump: u8,                         // 1
+}
+
+impl UsedNonces {
+    const WINDOW_BITS: u64 = 64 * USED_NONCE_WORDS as u64;
+
+    /// Mark `nonce` consumed, sliding the window forward if it lies beyond it
+    pub fn consume(&mut self, nonce: u64) -> Result<()> {
+        require!(nonce >= self.window_start, EscrowError::NonceAlreadyUsed);
+
+        if nonce - self.window_start >= Self::WINDOW_BITS {
+            self.advance_to(nonce);
+        }
+
+        let offset = nonce - self.window_start;
+        let word = (offset / 64) as usize;
+        let bit = 1u64 << (offset % 64);
+
+        require!(self.bitmap[word] & bit == 0, EscrowError::NonceAlreadyUsed);
+        self.bitmap[word] |= bit;
+
+        Ok(())
+    }
+
+    /// Slide the window by whole words so `nonce` falls in the last word
+    fn advance_to(&mut self, nonce: u64) {
+        let new_start = (nonce / 64).saturating_sub(USED_NONCE_WORDS as u64 - 1) * 64;
+        let shift = ((new_start - self.window_start) / 64) as usize;
+
+        if shift >= USED_NONCE_WORDS {
+            self.bitmap = [0; USED_NONCE_WORDS];
+        } else {
+            self.bitmap.rotate_left(shift);
+            self.bitmap[USED_NONCE_WORDS - shift..].fill(0);
+        }
+        self.window_start = new_start;
+    }
+}
+
+/// Assignment Queue - round-robin scheduler for dispute verifiers
+#[account]
+#[derive(InitSpace)]
+pub struct AssignmentQueue {
+    pub namespace: Pubkey,                // 32
+    #[max_len(32)]
+    pub verifier_pubkeys: Vec<Pubkey>,    // 4 + 32 * 32
+    pub next_index: u16,                  // 2
+    pub bump: u8,                         // 1
+}
+
+/// Verifier Registry - signing keys approved to resolve a namespace's disputes
+#[account]
+#[derive(InitSpace)]
+pub struct VerifierRegistry {
+    pub namespace: Pubkey,                // 32
+    #[max_len(32)]
+    pub verifiers: Vec<Pubkey>,           // 4 + 32 * 32
+    pub bump: u8,                         // 1
+}
+
+/// Verifier Set - M-of-N verifiers that resolve disputes by quorum
+#[account]
+#[derive(InitSpace)]
+pub struct VerifierSet {
+    pub namespace: Pubkey,                // 32
+    #[max_len(16)]
+    pub verifiers: Vec<Pubkey>,           // 4 + 32 * 16
+    pub threshold: u8,                    // 1 - signatures required
+    pub bump: u8,                         // 1
+}
+
+/// Arbitrator - human reviewer that held resolutions can be escalated to
+#[account]
+#[derive(InitSpace)]
+pub struct Arbitrator {
+    pub namespace: Pubkey,                // 32
+    pub authority: Pubkey,                // 32 - signs `arbitrator_rule`
+    pub cases_ruled: u32,                 // 4
+    pub registered_at: i64,               // 8
+    pub bump: u8,                         // 1
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Program errors, surfaced to clients as `6000 + discriminant`
+///
+/// Codes are part of the client API: add new variants at the end with the
+/// next discriminant, never reorder, renumber or reuse one, and register
+/// each in `ERROR_CODES`.
+#[error_code]
+pub enum EscrowError {
+    #[msg("Invalid escrow status for this operation")]
+    InvalidStatus = 0,
+
+    #[msg("Unauthorized: signer is not permitted to perform this operation")]
+    Unauthorized = 1,
+
+    #[msg("Invalid quality score (must be 0-100)")]
+    InvalidQualityScore = 2,
+
+    #[msg("Invalid refund percentage (must be 0-100)")]
+    InvalidRefundPercentage = 3,
+
+    #[msg("Invalid verifier signature")]
+    InvalidSignature = 4,
+
+    #[msg("Invalid time lock: must be between 1 hour and 30 days")]
+    InvalidTimeLock = 5,
+
+    #[msg("Invalid amount: below the minimum (0.001 SOL for escrows)")]
+    InvalidAmount = 6,
+
+    #[msg("Invalid transaction ID: must be non-empty and max 64 chars")]
+    InvalidTransactionId = 7,
+
+    #[msg("Time lock not expired: cannot release funds yet")]
+    TimeLockNotExpired = 8,
+
+    #[msg("Dispute window expired: the escrow no longer accepts disputes")]
+    DisputeWindowExpired = 9,
+
+    #[msg("Amount too large: exceeds maximum escrow amount")]
+    AmountTooLarge = 10,
+
+    #[msg("Insufficient funds to pay dispute cost")]
+    InsufficientDisputeFunds = 11,
+
+    #[msg("Rate limit exceeded: too many transactions")]
+    RateLimitExceeded = 12,
+
+    #[msg("Provider is suspended")]
+    ProviderSuspended = 13,
+
+    #[msg("Reputation score too low for this operation")]
+    ReputationTooLow = 14,
+
+    #[msg("Arithmetic overflow in calculation")]
+    ArithmeticOverflow = 15,
+
+    #[msg("Insufficient rent reserve in escrow account")]
+    InsufficientRentReserve = 16,
+
+    #[msg("Invalid Switchboard attestation")]
+    InvalidSwitchboardAttestation = 17,
+
+    #[msg("Switchboard data is older than the namespace accepts")]
+    StaleAttestation = 18,
+
+    #[msg("Quality score mismatch between Switchboard and submitted value")]
+    QualityScoreMismatch = 19,
+
+    #[msg("Reputation accounts required to release tracked exposure")]
+    ExposureAccountsMissing = 20,
+
+    #[msg("Verifier is not assigned to this dispute")]
+    VerifierNotAssigned = 21,
+
+    #[msg("Verifier oracle account missing or does not match")]
+    InvalidVerifierAccount = 22,
+
+    #[msg("Verifier capacity must be greater than 0")]
+    InvalidVerifierCapacity = 23,
+
+    #[msg("Assignment queue is full")]
+    AssignmentQueueFull = 24,
+
+    #[msg("Ed25519 instruction must immediately precede the resolving instruction")]
+    SignatureInstructionMisplaced = 25,
+
+    #[msg("Escrow has no spam protection deposit")]
+    NoSpamDeposit = 26,
+
+    #[msg("Spam protection deposit is still locked")]
+    SpamDepositLocked = 27,
+
+    #[msg("Counterparty is on a blocklist")]
+    CounterpartyBlocked = 28,
+
+    #[msg("Blocklist is full")]
+    BlocklistFull = 29,
+
+    #[msg("Counterparty is already blocked")]
+    AlreadyBlocked = 30,
+
+    #[msg("Counterparty is not blocked")]
+    NotBlocked = 31,
+
+    #[msg("Price feed is not the configured SOL/USD feed or is invalid")]
+    InvalidPriceFeed = 32,
+
+    #[msg("Disputes are disabled for this escrow")]
+    DisputesDisabledForEscrow = 33,
+
+    #[msg("Escrow balance would fall below the rent-exempt minimum")]
+    EscrowBelowRentExempt = 34,
+
+    #[msg("Escrow is already rent-exempt")]
+    EscrowAlreadyRentExempt = 35,
+
+    #[msg("Spam vault account required to return the escrow's deposit")]
+    SpamVaultMissing = 36,
+
+    #[msg("Rate limit override duration out of bounds")]
+    InvalidOverrideDuration = 37,
+
+    #[msg("Verifier assessed content that differs from the API's committed response")]
+    AssessedWrongContent = 38,
+
+    #[msg("Delivery has already been acknowledged")]
+    DeliveryAlreadyAcknowledged = 39,
+
+    #[msg("Batch must contain between 1 and 8 resolutions")]
+    InvalidBatchSize = 40,
+
+    #[msg("Batch accounts do not match the escrows being resolved")]
+    InvalidBatchAccounts = 41,
+
+    #[msg("Account belongs to a different namespace")]
+    NamespaceMismatch = 42,
+
+    #[msg("Fee exceeds the maximum of 1000 basis points")]
+    InvalidFeeBps = 43,
+
+    #[msg("Treasury account required to collect the protocol fee")]
+    TreasuryMissing = 44,
+
+    #[msg("Treasury does not match the namespace config")]
+    InvalidTreasury = 45,
+
+    #[msg("Verifier bond is below the required minimum")]
+    InsufficientVerifierBond = 46,
+
+    #[msg("Verifier bond meets the required minimum")]
+    VerifierBondCompliant = 47,
+
+    #[msg("Verifier is still within the bond grace period")]
+    BondGracePeriodActive = 48,
+
+    #[msg("Nonce has already been used or has fallen out of the window")]
+    NonceAlreadyUsed = 49,
+
+    #[msg("Reputation account is uninitialized, corrupt or for another entity")]
+    ReputationAccountInvalid = 50,
+
+    #[msg("Dispute window extension must be positive and stay within the time lock")]
+    InvalidDisputeWindowExtension = 51,
+
+    #[msg("Dispute window extension limit reached")]
+    DisputeWindowExtensionLimit = 52,
+
+    #[msg("Expected remaining account is missing")]
+    RemainingAccountMissing = 53,
+
+    #[msg("Remaining account has an unexpected address")]
+    RemainingAccountKeyMismatch = 54,
+
+    #[msg("Remaining account is owned by an unexpected program")]
+    RemainingAccountOwnerMismatch = 55,
+
+    #[msg("Remaining account must be writable")]
+    RemainingAccountNotWritable = 56,
+
+    #[msg("Remaining account must sign")]
+    RemainingAccountNotSigner = 57,
+
+    #[msg("Unexpected extra remaining accounts")]
+    RemainingAccountUnconsumed = 58,
+
+    #[msg("Dispute is not stale yet: oracles may still resolve it")]
+    DisputeNotStale = 59,
+
+    #[msg("Insurance fund account required to collect the insurance contribution")]
+    InsuranceFundMissing = 60,
+
+    #[msg("Insurance basis points out of bounds")]
+    InvalidInsuranceBps = 61,
+
+    #[msg("Escrow was not settled by the stale-dispute default")]
+    InsuranceClaimNotEligible = 62,
+
+    #[msg("Claim account does not match the claim being approved")]
+    InsuranceClaimMismatch = 63,
+
+    #[msg("Insurance claim has already been paid")]
+    InsuranceClaimAlreadyPaid = 64,
+
+    #[msg("Payout exceeds the per-claim insurance cap")]
+    InsurancePayoutExceedsCap = 65,
+
+    #[msg("Insurance fund balance is too low for this payout")]
+    InsufficientInsuranceFunds = 66,
+
+    #[msg("Pause must end in the future and last at most 30 days")]
+    InvalidPauseDuration = 67,
+
+    #[msg("Verifier still has assigned disputes; pass each assigned escrow to release it")]
+    VerifierHasAssignments = 68,
+
+    #[msg("Verifier is not paused")]
+    VerifierNotPaused = 69,
+
+    #[msg("Verifier is within a scheduled maintenance pause")]
+    VerifierPauseActive = 70,
 
-        require!(
-            escrow.status == EscrowStatus::Active || escrow.status == EscrowStatus::Disputed,
-            EscrowError::InvalidStatus
-        );
+    #[msg("Priority fee exceeds the namespace cap")]
+    PriorityFeeTooLarge = 71,
 
-        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
-        require!(refund_percentage <= 100, EscrowError::InvalidRefundPercentage);
+    #[msg("Escrow account layout is not recognized")]
+    UnknownEscrowLayout = 72,
 
-        // Verify signature from verifier oracle
-        // Message format: "{transaction_id}:{quality_score}"
-        let message = format!("{}:{}", escrow.transaction_id, quality_score);
-        let message_bytes = message.as_bytes();
+    #[msg("Escrow is covered by a service bond that was not supplied")]
+    ServiceBondMissing = 73,
 
-        // Verify Ed25519 signature from the instructions sysvar
-        verify_ed25519_signature(
-            &ctx.accounts.instructions_sysvar,
-            &signature,
-            ctx.accounts.verifier.key,
-            message_bytes,
-        )?;
+    #[msg("Service bond is below the provider's declared minimum")]
+    ServiceBondBelowMinimum = 74,
 
-        msg!("Verifier: {}", ctx.accounts.verifier.key());
-        msg!("Quality Score: {}", quality_score);
-        msg!("Refund: {}%", refund_percentage);
+    #[msg("Service bond has a withdrawal pending")]
+    ServiceBondWithdrawalPending = 75,
 
-        // Calculate split amounts
-        let refund_amount = (escrow.amount as u128)
-            .checked_mul(refund_percentage as u128)
-            .ok_or(EscrowError::ArithmeticOverflow)?
-            .checked_div(100)
-            .ok_or(EscrowError::ArithmeticOverflow)? as u64;
+    #[msg("Service bond terms are fixed while lamports are bonded")]
+    ServiceBondTermsMismatch = 76,
+
+    #[msg("Service bond withdrawal cooldown has not elapsed")]
+    ServiceBondCooldownActive = 77,
+
+    #[msg("No service bond withdrawal was requested")]
+    NoServiceBondWithdrawal = 78,
+
+    #[msg("Invalid service bond terms")]
+    InvalidServiceBondTerms = 79,
+
+    #[msg("Payment reference challenge nonce must be non-zero")]
+    InvalidChallengeNonce = 80,
+
+    #[msg("Challenge nonce does not match the escrow's payment reference")]
+    ChallengeNonceMismatch = 81,
+
+    #[msg("Token escrows settle through the SPL instructions")]
+    TokenEscrowRequiresSplPath = 82,
+
+    #[msg("Escrow holds SOL, not tokens")]
+    NotTokenEscrow = 83,
+
+    #[msg("Escrow is bound to a work agreement the resolution must reference")]
+    WorkAgreementRequired = 84,
+
+    #[msg("Quality below the work agreement's minimum requires a full refund")]
+    BelowAgreedQuality = 85,
+
+    #[msg("Invalid work agreement terms")]
+    InvalidWorkAgreement = 86,
+
+    #[msg("Work agreements must be created before delivery")]
+    WorkAgreementTooLate = 87,
+
+    #[msg("Cancellation needs the API's signature until the grace period elapses")]
+    CancelGracePeriodActive = 88,
+
+    #[msg("Cancel grace period must not be negative")]
+    InvalidCancelGracePeriod = 89,
+
+    #[msg("Escrow cannot be closed until the close cooldown elapses")]
+    CloseCooldownActive = 90,
+
+    #[msg("SPL escrows must close their token vault")]
+    EscrowVaultMissing = 91,
+
+    #[msg("Verifier set needs 1-16 distinct verifiers and a threshold within its size")]
+    InvalidVerifierSet = 92,
+
+    #[msg("Quorum signature from a verifier outside the set or listed twice")]
+    InvalidQuorumSigner = 93,
+
+    #[msg("Fewer distinct verifier signatures than the set's threshold")]
+    QuorumNotReached = 94,
+
+    #[msg("Provider suspension has not ended yet")]
+    SuspensionActive = 95,
+
+    #[msg("Provider is not suspended")]
+    ProviderNotSuspended = 96,
+
+    #[msg("Verification level can only rise, and only to Staked without the authority")]
+    InvalidVerificationUpgrade = 97,
+
+    #[msg("No verification stake to withdraw")]
+    NoVerificationStake = 98,
+
+    #[msg("Escrow cannot auto-release while its dispute window is open")]
+    DisputeWindowOpen = 99,
+
+    #[msg("Milestone amounts must be non-zero and sum to the escrowed amount")]
+    InvalidMilestonePlan = 100,
+
+    #[msg("Escrow settles per milestone")]
+    MilestonePlanActive = 101,
+
+    #[msg("Milestone index out of range or in the wrong state")]
+    InvalidMilestone = 102,
+
+    #[msg("Namespace is paused")]
+    ProtocolPaused = 103,
+
+    #[msg("Verifier key is not in the namespace's registry")]
+    VerifierNotRegistered = 104,
+
+    #[msg("Verifier registry is full")]
+    VerifierRegistryFull = 105,
+
+    #[msg("Verifier key is already registered")]
+    VerifierAlreadyRegistered = 106,
+
+    #[msg("Refund curve must ascend to a score of 100 with non-increasing refunds")]
+    InvalidRefundCurve = 107,
+
+    #[msg("Time lock has expired: extending it needs the API's co-signature")]
+    TimeLockExtensionNeedsApi = 108,
+
+    #[msg("Invalid appeal window: must be between 0 and 7 days")]
+    InvalidAppealWindow = 109,
+
+    #[msg("Resolution is held for appeal: appeal or settle it")]
+    ResolutionHeld = 110,
+
+    #[msg("No resolution is held for appeal")]
+    ResolutionNotHeld = 111,
+
+    #[msg("Resolution has already been appealed")]
+    AlreadyAppealed = 112,
+
+    #[msg("Appeal window is still open")]
+    AppealWindowOpen = 113,
+
+    #[msg("Appeal window has closed")]
+    AppealWindowClosed = 114,
+
+    #[msg("The verifier of an appealed resolution may not resolve the appeal")]
+    AppealedVerifierSigned = 115,
+
+    #[msg("Namespace has an appeal window: resolve through submit_resolution and settle_resolution")]
+    TwoPhaseResolutionRequired = 116,
+
+    #[msg("Escrow amount at the current price exceeds the agent's maximum")]
+    PriceSlippageExceeded = 117,
+
+    #[msg("Invalid billing period: must be between 1 hour and 30 days")]
+    InvalidSubscriptionPeriod = 118,
+
+    #[msg("Subscription has no funded period left")]
+    SubscriptionUnfunded = 119,
+
+    #[msg("Billing period has not started")]
+    PeriodNotStarted = 120,
+
+    #[msg("Billing period is still open to disputes")]
+    PeriodNotClaimable = 121,
+
+    #[msg("Billing period is held for a verifier")]
+    PeriodDisputed = 122,
+
+    #[msg("Billing period is not disputed")]
+    PeriodNotDisputed = 123,
+
+    #[msg("Escrow is not streaming")]
+    StreamingNotEnabled = 124,
+
+    #[msg("Only an active SOL escrow without milestones, USD pricing or payouts can stream")]
+    InvalidStreamingEscrow = 125,
+
+    #[msg("No streamed funds to withdraw yet")]
+    NothingStreamed = 126,
+
+    #[msg("Milestone and USD-priced escrows cannot be topped up")]
+    TopUpUnsupported = 127,
+
+    #[msg("Resolution was already escalated to an arbitrator")]
+    AlreadyEscalated = 128,
 
-        let payment_amount = escrow.amount - refund_amount;
+    #[msg("Resolution is not awaiting an arbitrator's ruling")]
+    NotEscalated = 129,
 
-        msg!("Refund to Agent: {} SOL", refund_amount as f64 / 1_000_000_000.0);
-        msg!("Payment to API: {} SOL", payment_amount as f64 / 1_000_000_000.0);
+    #[msg("Ruling deviates too far from the oracle's refund")]
+    ArbitratorDeviationExceeded = 130,
 
-        // Transfer refund to agent
-        // Note: Using direct lamport manipulation instead of system_program::transfer
-        // because escrow PDA contains data and system transfer requires empty accounts
-        if refund_amount > 0 {
-            **ctx.accounts.escrow.to_account_info().try_borrow_mut_lamports()? -= refund_amount;
-            **ctx.accounts.agent.to_account_info().try_borrow_mut_lamports()? += refund_amount;
+    #[msg("Reputation account layout is not recognized")]
+    UnknownReputationLayout = 131,
+
+    #[msg("Amount tiers must start at score 0 and rise in score and cap")]
+    InvalidAmountTiers = 132,
+
+    #[msg("Escrow amount exceeds the agent's reputation tier")]
+    AmountExceedsReputationTier = 133,
+
+    #[msg("Reputation history already has the current score")]
+    SnapshotUpToDate = 134,
+
+    #[msg("Dispute bond exceeds the maximum share of the escrow")]
+    InvalidDisputeBondBps = 135,
+
+    #[msg("Severe refund slash exceeds 100% of the bond")]
+    InvalidSevereRefundSlashBps = 136,
+
+    #[msg("Oracle fee exceeds the maximum share of the escrow")]
+    InvalidOracleFeeBps = 137,
+
+    #[msg("Verifier token account required to pay the oracle fee")]
+    VerifierTokenAccountMissing = 138,
+
+    #[msg("Switchboard feed is not on the namespace's trusted queue")]
+    UntrustedSwitchboardFeed = 139,
+
+    #[msg("Refund percentage does not match the attested value")]
+    RefundPercentageMismatch = 140,
+
+    #[msg("Attestation staleness window out of bounds")]
+    InvalidAttestationMaxAge = 141,
+
+    #[msg("Pyth quality attestation is missing or malformed")]
+    InvalidPythAttestation = 142,
+
+    #[msg("Payment channel has too many unsettled entries")]
+    PaymentChannelFull = 143,
+
+    #[msg("No unsettled channel entry with this id")]
+    ChannelEntryNotFound = 144,
+
+    #[msg("Channel debits would exceed its balance")]
+    ChannelBalanceExceeded = 145,
+
+    #[msg("Channel entry is disputed, resolved, or past its dispute window")]
+    ChannelEntryNotDisputable = 146,
+
+    #[msg("Channel entry is not disputed")]
+    ChannelEntryNotDisputed = 147,
+
+    #[msg("Payment channel still has unsettled entries")]
+    ChannelEntriesPending = 148,
+
+    #[msg("Escrow's time lock starts at delivery, which the API has not acknowledged")]
+    DeliveryNotAcknowledged = 149,
+
+    #[msg("Refund recipient account does not match the escrow")]
+    RefundRecipientMismatch = 150,
+
+    #[msg("Agent may only pay APIs on its organization's allowlist, which was not supplied")]
+    AllowlistRequired = 151,
+
+    #[msg("Allowlist belongs to another organization")]
+    AllowlistMismatch = 152,
+
+    #[msg("API is not on the allowlist")]
+    ApiNotAllowlisted = 153,
+
+    #[msg("Allowlist is full")]
+    AllowlistFull = 154,
+
+    #[msg("API is already on the allowlist")]
+    AlreadyAllowlisted = 155,
+
+    #[msg("API is not on the allowlist")]
+    NotAllowlisted = 156,
+
+    #[msg("Escrow would exceed the agent's daily spend limit set by its organization")]
+    DailySpendLimitExceeded = 157,
+
+    #[msg("Session key scope or expiry is invalid")]
+    InvalidSessionKey = 158,
+
+    #[msg("Session key has expired")]
+    SessionKeyExpired = 159,
+
+    #[msg("Session key is not authorized for this action")]
+    SessionScopeMissing = 160,
+
+    #[msg("Metadata URI exceeds 200 characters")]
+    MetadataUriTooLong = 161,
+
+    #[msg("A receipt was already minted for this escrow")]
+    ReceiptAlreadyMinted = 162,
+
+    #[msg("Escrow is already indexed")]
+    EscrowAlreadyIndexed = 163,
+
+    #[msg("Provider response window has closed")]
+    ProviderResponseWindowClosed = 164,
+
+    #[msg("Provider response already submitted")]
+    ProviderResponseAlreadySubmitted = 165,
+
+    #[msg("Comment URI exceeds 200 characters")]
+    CommentUriTooLong = 166,
+
+    #[msg("Stale dispute terms need a 1-30 day verifier timeout and a refund of at most 100%")]
+    InvalidStaleDisputeTerms = 167,
+
+    #[msg("Mint has an extension escrow vaults cannot hold")]
+    UnsupportedMintExtension = 168,
+
+    #[msg("Settlement already published to Wormhole")]
+    SettlementAlreadyPublished = 169,
+
+    #[msg("Relayer must sign a relayed resolution")]
+    RelayerMissing = 170,
+
+    #[msg("Provider terms account is required")]
+    ProviderTermsRequired = 171,
+
+    #[msg("Escrow amount, time lock or namespace is not on the provider's terms")]
+    ProviderTermsMismatch = 172,
+
+    #[msg("Provider terms have an invalid amount range, time lock, dispute window or refund curve")]
+    InvalidProviderTerms = 173,
+
+    #[msg("Work agreement weights score dimensions; resolve with per-dimension scores")]
+    SubScoresRequired = 174,
+
+    #[msg("Per-dimension scores need a work agreement that weights them")]
+    ScoreDimensionsNotAgreed = 175,
+
+    #[msg("USD escrows settle only with a Pyth SOL/USD price update")]
+    PriceUpdateMissing = 176,
+
+    #[msg("Releasing a USD escrow for the agent needs the agent's wallet for the surplus")]
+    AgentWalletMissing = 177,
+
+    #[msg("Minting a receipt needs every receipt account")]
+    ReceiptAccountsMissing = 178,
+}
+
+/// Stable `(code, name)` registry of every `EscrowError`, in code order
+pub const ERROR_CODES: &[(u32, &str)] = &[
+    (6000, "InvalidStatus"),
+    (6001, "Unauthorized"),
+    (6002, "InvalidQualityScore"),
+    (6003, "InvalidRefundPercentage"),
+    (6004, "InvalidSignature"),
+    (6005, "InvalidTimeLock"),
+    (6006, "InvalidAmount"),
+    (6007, "InvalidTransactionId"),
+    (6008, "TimeLockNotExpired"),
+    (6009, "DisputeWindowExpired"),
+    (6010, "AmountTooLarge"),
+    (6011, "InsufficientDisputeFunds"),
+    (6012, "RateLimitExceeded"),
+    (6013, "ProviderSuspended"),
+    (6014, "ReputationTooLow"),
+    (6015, "ArithmeticOverflow"),
+    (6016, "InsufficientRentReserve"),
+    (6017, "InvalidSwitchboardAttestation"),
+    (6018, "StaleAttestation"),
+    (6019, "QualityScoreMismatch"),
+    (6020, "ExposureAccountsMissing"),
+    (6021, "VerifierNotAssigned"),
+    (6022, "InvalidVerifierAccount"),
+    (6023, "InvalidVerifierCapacity"),
+    (6024, "AssignmentQueueFull"),
+    (6025, "SignatureInstructionMisplaced"),
+    (6026, "NoSpamDeposit"),
+    (6027, "SpamDepositLocked"),
+    (6028, "CounterpartyBlocked"),
+    (6029, "BlocklistFull"),
+    (6030, "AlreadyBlocked"),
+    (6031, "NotBlocked"),
+    (6032, "InvalidPriceFeed"),
+    (6033, "DisputesDisabledForEscrow"),
+    (6034, "EscrowBelowRentExempt"),
+    (6035, "EscrowAlreadyRentExempt"),
+    (6036, "SpamVaultMissing"),
+    (6037, "InvalidOverrideDuration"),
+    (6038, "AssessedWrongContent"),
+    (6039, "DeliveryAlreadyAcknowledged"),
+    (6040, "InvalidBatchSize"),
+    (6041, "InvalidBatchAccounts"),
+    (6042, "NamespaceMismatch"),
+    (6043, "InvalidFeeBps"),
+    (6044, "TreasuryMissing"),
+    (6045, "InvalidTreasury"),
+    (6046, "InsufficientVerifierBond"),
+    (6047, "VerifierBondCompliant"),
+    (6048, "BondGracePeriodActive"),
+    (6049, "NonceAlreadyUsed"),
+    (6050, "ReputationAccountInvalid"),
+    (6051, "InvalidDisputeWindowExtension"),
+    (6052, "DisputeWindowExtensionLimit"),
+    (6053, "RemainingAccountMissing"),
+    (6054, "RemainingAccountKeyMismatch"),
+    (6055, "RemainingAccountOwnerMismatch"),
+    (6056, "RemainingAccountNotWritable"),
+    (6057, "RemainingAccountNotSigner"),
+    (6058, "RemainingAccountUnconsumed"),
+    (6059, "DisputeNotStale"),
+    (6060, "InsuranceFundMissing"),
+    (6061, "InvalidInsuranceBps"),
+    (6062, "InsuranceClaimNotEligible"),
+    (6063, "InsuranceClaimMismatch"),
+    (6064, "InsuranceClaimAlreadyPaid"),
+    (6065, "InsurancePayoutExceedsCap"),
+    (6066, "InsufficientInsuranceFunds"),
+    (6067, "InvalidPauseDuration"),
+    (6068, "VerifierHasAssignments"),
+    (6069, "VerifierNotPaused"),
+    (6070, "VerifierPauseActive"),
+    (6071, "PriorityFeeTooLarge"),
+    (6072, "UnknownEscrowLayout"),
+    (6073, "ServiceBondMissing"),
+    (6074, "ServiceBondBelowMinimum"),
+    (6075, "ServiceBondWithdrawalPending"),
+    (6076, "ServiceBondTermsMismatch"),
+    (6077, "ServiceBondCooldownActive"),
+    (6078, "NoServiceBondWithdrawal"),
+    (6079, "InvalidServiceBondTerms"),
+    (6080, "InvalidChallengeNonce"),
+    (6081, "ChallengeNonceMismatch"),
+    (6082, "TokenEscrowRequiresSplPath"),
+    (6083, "NotTokenEscrow"),
+    (6084, "WorkAgreementRequired"),
+    (6085, "BelowAgreedQuality"),
+    (6086, "InvalidWorkAgreement"),
+    (6087, "WorkAgreementTooLate"),
+    (6088, "CancelGracePeriodActive"),
+    (6089, "InvalidCancelGracePeriod"),
+    (6090, "CloseCooldownActive"),
+    (6091, "EscrowVaultMissing"),
+    (6092, "InvalidVerifierSet"),
+    (6093, "InvalidQuorumSigner"),
+    (6094, "QuorumNotReached"),
+    (6095, "SuspensionActive"),
+    (6096, "ProviderNotSuspended"),
+    (6097, "InvalidVerificationUpgrade"),
+    (6098, "NoVerificationStake"),
+    (6099, "DisputeWindowOpen"),
+    (6100, "InvalidMilestonePlan"),
+    (6101, "MilestonePlanActive"),
+    (6102, "InvalidMilestone"),
+    (6103, "ProtocolPaused"),
+    (6104, "VerifierNotRegistered"),
+    (6105, "VerifierRegistryFull"),
+    (6106, "VerifierAlreadyRegistered"),
+    (6107, "InvalidRefundCurve"),
+    (6108, "TimeLockExtensionNeedsApi"),
+    (6109, "InvalidAppealWindow"),
+    (6110, "ResolutionHeld"),
+    (6111, "ResolutionNotHeld"),
+    (6112, "AlreadyAppealed"),
+    (6113, "AppealWindowOpen"),
+    (6114, "AppealWindowClosed"),
+    (6115, "AppealedVerifierSigned"),
+    (6116, "TwoPhaseResolutionRequired"),
+    (6117, "PriceSlippageExceeded"),
+    (6118, "InvalidSubscriptionPeriod"),
+    (6119, "SubscriptionUnfunded"),
+    (6120, "PeriodNotStarted"),
+    (6121, "PeriodNotClaimable"),
+    (6122, "PeriodDisputed"),
+    (6123, "PeriodNotDisputed"),
+    (6124, "StreamingNotEnabled"),
+    (6125, "InvalidStreamingEscrow"),
+    (6126, "NothingStreamed"),
+    (6127, "TopUpUnsupported"),
+    (6128, "AlreadyEscalated"),
+    (6129, "NotEscalated"),
+    (6130, "ArbitratorDeviationExceeded"),
+    (6131, "UnknownReputationLayout"),
+    (6132, "InvalidAmountTiers"),
+    (6133, "AmountExceedsReputationTier"),
+    (6134, "SnapshotUpToDate"),
+    (6135, "InvalidDisputeBondBps"),
+    (6136, "InvalidSevereRefundSlashBps"),
+    (6137, "InvalidOracleFeeBps"),
+    (6138, "VerifierTokenAccountMissing"),
+    (6139, "UntrustedSwitchboardFeed"),
+    (6140, "RefundPercentageMismatch"),
+    (6141, "InvalidAttestationMaxAge"),
+    (6142, "InvalidPythAttestation"),
+    (6143, "PaymentChannelFull"),
+    (6144, "ChannelEntryNotFound"),
+    (6145, "ChannelBalanceExceeded"),
+    (6146, "ChannelEntryNotDisputable"),
+    (6147, "ChannelEntryNotDisputed"),
+    (6148, "ChannelEntriesPending"),
+    (6149, "DeliveryNotAcknowledged"),
+    (6150, "RefundRecipientMismatch"),
+    (6151, "AllowlistRequired"),
+    (6152, "AllowlistMismatch"),
+    (6153, "ApiNotAllowlisted"),
+    (6154, "AllowlistFull"),
+    (6155, "AlreadyAllowlisted"),
+    (6156, "NotAllowlisted"),
+    (6157, "DailySpendLimitExceeded"),
+    (6158, "InvalidSessionKey"),
+    (6159, "SessionKeyExpired"),
+    (6160, "SessionScopeMissing"),
+    (6161, "MetadataUriTooLong"),
+    (6162, "ReceiptAlreadyMinted"),
+    (6163, "EscrowAlreadyIndexed"),
+    (6164, "ProviderResponseWindowClosed"),
+    (6165, "ProviderResponseAlreadySubmitted"),
+    (6166, "CommentUriTooLong"),
+    (6167, "InvalidStaleDisputeTerms"),
+    (6168, "UnsupportedMintExtension"),
+    (6169, "SettlementAlreadyPublished"),
+    (6170, "RelayerMissing"),
+    (6171, "ProviderTermsRequired"),
+    (6172, "ProviderTermsMismatch"),
+    (6173, "InvalidProviderTerms"),
+    (6174, "SubScoresRequired"),
+    (6175, "ScoreDimensionsNotAgreed"),
+    (6176, "PriceUpdateMissing"),
+    (6177, "AgentWalletMissing"),
+    (6178, "ReceiptAccountsMissing"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(name, discriminant)` of each `EscrowError` variant in declaration order,
+    /// read from this file so an unregistered variant cannot slip through
+    fn declared_error_variants() -> Vec<(String, u32)> {
+        let source = include_str!("lib.rs");
+        let start = source
+            .find("pub enum EscrowError {")
+            .expect("EscrowError enum not found");
+        let body = &source[start..];
+        let body = &body[..body.find("\n}").expect("unterminated EscrowError enum")];
+
+        body.lines()
+            .skip(1)
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"))
+            .map(|line| {
+                let (name, discriminant) = line
+                    .trim_end_matches(',')
+                    .split_once(" = ")
+                    .unwrap_or_else(|| panic!("{} needs an explicit discriminant", line));
+                (name.to_string(), discriminant.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn error_codes_are_append_only() {
+        for (index, (name, discriminant)) in declared_error_variants().iter().enumerate() {
+            assert_eq!(
+                *discriminant, index as u32,
+                "{} must be appended with the next discriminant",
+                name
+            );
         }
+    }
 
-        // Transfer payment to API
-        if payment_amount > 0 {
-            **ctx.accounts.escrow.to_account_info().try_borrow_mut_lamports()? -= payment_amount;
-            **ctx.accounts.api.to_account_info().try_borrow_mut_lamports()? += payment_amount;
+    #[test]
+    fn error_registry_matches_enum() {
+        let registered: Vec<(String, u32)> = ERROR_CODES
+            .iter()
+            .map(|(code, name)| (name.to_string(), code - anchor_lang::error::ERROR_CODE_OFFSET))
+            .collect();
+
+        assert_eq!(registered, declared_error_variants());
+    }
+
+    #[test]
+    fn error_codes_match_anchor_numbering() {
+        let samples = [
+            EscrowError::InvalidStatus,
+            EscrowError::StaleAttestation,
+            EscrowError::RemainingAccountUnconsumed,
+            EscrowError::InsufficientInsuranceFunds,
+        ];
+        for error in samples {
+            let code = u32::from(error);
+            let (_, name) = ERROR_CODES
+                .iter()
+                .find(|(registered, _)| *registered == code)
+                .expect("code missing from registry");
+            assert_eq!(*name, error.name());
         }
+        assert_eq!(u32::from(EscrowError::StaleAttestation), 6018);
+    }
 
-        let escrow = &mut ctx.accounts.escrow;
-        escrow.status = EscrowStatus::Resolved;
-        escrow.quality_score = Some(quality_score);
-        escrow.refund_percentage = Some(refund_percentage);
+    const STATUSES: [EscrowStatus; 4] = [
+        EscrowStatus::Active,
+        EscrowStatus::Released,
+        EscrowStatus::Disputed,
+        EscrowStatus::Resolved,
+    ];
 
-        // Update agent reputation
-        let agent_reputation = &mut ctx.accounts.agent_reputation;
-        let clock = Clock::get()?;
+    type FlagAccessors = (fn(&Escrow) -> bool, fn(&mut Escrow, bool), u16);
 
-        agent_reputation.total_transactions = agent_reputation.total_transactions.saturating_add(1);
+    const FLAGS: [FlagAccessors; 12] = [
+        (Escrow::exposure_tracked, Escrow::set_exposure_tracked, layout::FLAG_EXPOSURE_TRACKED),
+        (Escrow::no_dispute, Escrow::set_no_dispute, layout::FLAG_NO_DISPUTE),
+        (Escrow::settled_stale, Escrow::set_settled_stale, layout::FLAG_SETTLED_STALE),
+        (Escrow::service_bonded, Escrow::set_service_bonded, layout::FLAG_SERVICE_BONDED),
+        (Escrow::has_work_agreement, Escrow::set_has_work_agreement, layout::FLAG_WORK_AGREEMENT),
+        (Escrow::has_milestones, Escrow::set_has_milestones, layout::FLAG_MILESTONES),
+        (Escrow::appealed, Escrow::set_appealed, layout::FLAG_APPEALED),
+        (Escrow::streaming, Escrow::set_streaming, layout::FLAG_STREAMING),
+        (Escrow::escalated, Escrow::set_escalated, layout::FLAG_ESCALATED),
+        (Escrow::delivery_clock, Escrow::set_delivery_clock, layout::FLAG_DELIVERY_CLOCK),
+        (Escrow::receipt_minted, Escrow::set_receipt_minted, layout::FLAG_RECEIPT_MINTED),
+        (Escrow::indexed, Escrow::set_indexed, layout::FLAG_INDEXED),
+    ];
 
-        // Update average quality received by agent
-        let total_quality = agent_reputation.average_quality_received as u64
-            * (agent_reputation.total_transactions.saturating_sub(1)) as u64
-            + quality_score as u64;
-        agent_reputation.average_quality_received =
-            (total_quality / agent_reputation.total_transactions as u64) as u8;
+    fn legacy_reputation(average_quality_received: u8, total_transactions: u64) -> LegacyReputationV0 {
+        LegacyReputationV0 {
+            entity: Pubkey::new_unique(),
+            entity_type: EntityType::Agent,
+            total_transactions,
+            disputes_filed: 0,
+            disputes_won: 0,
+            disputes_partial: 0,
+            disputes_lost: 0,
+            average_quality_received,
+            reputation_score: 500,
+            current_win_streak: 0,
+            longest_win_streak: 0,
+            worst_quality_received: 0,
+            best_quality_received: 0,
+            total_refunds_received_lamports: 0,
+            total_payments_made_lamports: 0,
+            locked_value: 0,
+            total_volume_lamports: 0,
+            volume_usd_cents: 0,
+            last_price_feed_used: None,
+            price_at_last_update: 0,
+            created_at: 0,
+            last_updated: 0,
+            bump: 255,
+        }
+    }
 
-        // Categorize dispute outcome for agent
-        if refund_percentage >= 75 {
-            agent_reputation.disputes_won = agent_reputation.disputes_won.saturating_add(1);
-        } else if refund_percentage >= 25 {
-            agent_reputation.disputes_partial = agent_reputation.disputes_partial.saturating_add(1);
-        } else {
-            agent_reputation.disputes_lost = agent_reputation.disputes_lost.saturating_add(1);
+    fn legacy_escrow() -> LegacyEscrowV0 {
+        LegacyEscrowV0 {
+            agent: Pubkey::new_unique(),
+            api: Pubkey::new_unique(),
+            namespace: Pubkey::new_unique(),
+            amount: 42_000_000,
+            status: EscrowStatus::Disputed,
+            created_at: 1_700_000_000,
+            expires_at: 1_700_086_400,
+            transaction_id: "tx_layout".to_string(),
+            bump: 254,
+            quality_score: None,
+            refund_percentage: None,
+            exposure_tracked: true,
+            assigned_verifier: Some(Pubkey::new_unique()),
+            spam_deposit: 0,
+            no_dispute: false,
+            response_hash: None,
+            delivered_at: None,
+            assessed_content_hash: None,
+            time_lock: 86_400,
+            dispute_window: 86_400,
+            dispute_window_extension_count: 1,
+            settled_stale: true,
+            priority_fee: 5_000,
         }
+    }
 
-        // Recalculate agent reputation score
-        agent_reputation.reputation_score = calculate_reputation_score(agent_reputation);
-        agent_reputation.last_updated = clock.unix_timestamp;
+    #[test]
+    fn each_flag_sets_and_clears_independently() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        for (index, (get, set, bit)) in FLAGS.iter().enumerate() {
+            for status in STATUSES {
+                escrow.flags = 0;
+                escrow.set_status(status.clone());
 
-        // Update API reputation (inverse of agent outcome)
-        let api_reputation = &mut ctx.accounts.api_reputation;
-        api_reputation.total_transactions = api_reputation.total_transactions.saturating_add(1);
+                set(&mut escrow, true);
+                assert!(get(&escrow));
+                assert_eq!(escrow.flags & bit, *bit);
+                assert_eq!(escrow.status(), status);
+                for (other, (other_get, _, _)) in FLAGS.iter().enumerate() {
+                    if other != index {
+                        assert!(!other_get(&escrow));
+                    }
+                }
 
-        // Quality delivered by API (inverse of refund percentage)
-        let quality_delivered = 100 - refund_percentage;
-        let total_quality_api = api_reputation.average_quality_received as u64
-            * (api_reputation.total_transactions.saturating_sub(1)) as u64
-            + quality_delivered as u64;
-        api_reputation.average_quality_received =
-            (total_quality_api / api_reputation.total_transactions as u64) as u8;
+                set(&mut escrow, false);
+                assert!(!get(&escrow));
+                assert_eq!(escrow.flags & !layout::STATUS_MASK, 0);
+                assert_eq!(escrow.status(), status);
+            }
+        }
+    }
 
-        // Categorize for API (inverse)
-        if refund_percentage <= 25 {
-            // API provided good quality
-            api_reputation.disputes_won = api_reputation.disputes_won.saturating_add(1);
-        } else if refund_percentage <= 75 {
-            api_reputation.disputes_partial = api_reputation.disputes_partial.saturating_add(1);
-        } else {
-            // API provided poor quality
-            api_reputation.disputes_lost = api_reputation.disputes_lost.saturating_add(1);
+    #[test]
+    fn status_round_trips_without_touching_flags() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        let all_flags = FLAGS.iter().fold(0, |acc, (_, _, bit)| acc | bit);
+        for base in [0, all_flags] {
+            for status in STATUSES {
+                escrow.flags = base;
+                escrow.set_status(status.clone());
+                assert_eq!(escrow.status(), status);
+                assert_eq!(escrow.flags & !layout::STATUS_MASK, base);
+            }
         }
+    }
 
-        api_reputation.reputation_score = calculate_reputation_score(api_reputation);
-        api_reputation.last_updated = clock.unix_timestamp;
+    #[test]
+    fn flag_bits_do_not_overlap_status() {
+        let mut seen = layout::STATUS_MASK;
+        for (_, _, bit) in FLAGS {
+            assert_eq!(bit.count_ones(), 1);
+            assert_eq!(seen & bit, 0);
+            seen |= bit;
+        }
+    }
 
-        msg!("Dispute resolved!");
-        msg!("Agent reputation: {}", agent_reputation.reputation_score);
-        msg!("API reputation: {}", api_reputation.reputation_score);
+    #[test]
+    fn packed_layout_is_not_larger_than_unpacked() {
+        // Fields appended since v0 are not part of the comparison
+        let appended = 1 + PaymentReference::INIT_SPACE + 3 * 8 + 1 + 32 + 1 + 8 + 8 + 8 + 9 + 33 + 65 + 9 + 8 + 33 + 33 + 8 + 8 + 33 + 33 + 33 + 1 + 4 + MAX_METADATA_URI_LEN + 9 + 2 + 33 + 33 + 1 + 4 + MAX_COMMENT_URI_LEN + 1 + StaleDisputeTerms::INIT_SPACE + 8 + 1 + 2 * REFUND_CURVE_STEPS + 1 + SCORE_DIMENSIONS;
+        let (packed, unpacked) = (Escrow::INIT_SPACE - appended, LegacyEscrowV0::INIT_SPACE);
+        assert!(packed <= unpacked, "{packed} > {unpacked}");
+    }
 
-        emit!(DisputeResolved {
-            escrow: escrow.key(),
-            transaction_id: escrow.transaction_id.clone(),
-            quality_score,
-            refund_percentage,
-            refund_amount,
-            payment_amount,
-            verifier: ctx.accounts.verifier.key(),
+    #[test]
+    fn migration_preserves_status_and_flags() {
+        let legacy = legacy_escrow();
+        let (agent, priority_fee) = (legacy.agent, legacy.priority_fee);
+        let escrow = Escrow::from(legacy);
+
+        assert_eq!(escrow.version, layout::ESCROW_VERSION);
+        assert_eq!(escrow.status(), EscrowStatus::Disputed);
+        assert!(escrow.exposure_tracked());
+        assert!(!escrow.no_dispute());
+        assert!(escrow.settled_stale());
+        assert_eq!(escrow.agent, agent);
+        assert_eq!(escrow.priority_fee, priority_fee);
+    }
+
+    #[test]
+    fn exported_offsets_match_serialized_escrow() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.set_no_dispute(true);
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+
+        let read_key = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        assert_eq!(read_key(layout::ESCROW_AGENT_OFFSET), escrow.agent);
+        assert_eq!(read_key(layout::ESCROW_API_OFFSET), escrow.api);
+        assert_eq!(read_key(layout::ESCROW_NAMESPACE_OFFSET), escrow.namespace);
+        assert_eq!(data[layout::ESCROW_VERSION_OFFSET], layout::ESCROW_VERSION);
+        let flags = u16::from_le_bytes([
+            data[layout::ESCROW_FLAGS_OFFSET],
+            data[layout::ESCROW_FLAGS_OFFSET + 1],
+        ]);
+        assert_eq!(flags, escrow.flags);
+    }
+
+    fn service_bond(bonded_lamports: u64) -> ServiceBond {
+        ServiceBond {
+            provider: Pubkey::new_unique(),
+            bonded_lamports,
+            terms_hash: [0; 32],
+            terms: ServiceBondTerms {
+                quality_threshold: 50,
+                slash_bps: 2_500,
+                max_slash_per_incident: 1_000_000,
+                min_active_bond: 0,
+            },
+            posted_at: 1_000,
+            withdrawal_requested_at: None,
+            pending_withdrawal: 0,
+            total_slashed: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn service_bond_covers_escrows_created_before_withdrawal_request() {
+        let mut bond = service_bond(10_000_000);
+        assert!(!bond.covers(999));
+        assert!(bond.covers(1_000));
+
+        bond.withdrawal_requested_at = Some(2_000);
+        assert!(bond.covers(1_999));
+        assert!(!bond.covers(2_000));
+
+        // Executing the withdrawal restores cover for the remaining bond
+        bond.withdrawal_requested_at = None;
+        assert!(bond.covers(2_000));
+    }
+
+    #[test]
+    fn incident_slash_is_share_of_bond_up_to_cap() {
+        assert_eq!(service_bond(2_000_000).incident_slash().unwrap(), 500_000);
+        assert_eq!(service_bond(10_000_000).incident_slash().unwrap(), 1_000_000);
+        assert_eq!(service_bond(0).incident_slash().unwrap(), 0);
+    }
+
+    #[test]
+    fn near_total_refunds_slash_the_bond_whatever_its_terms() {
+        let bond = service_bond(10_000_000);
+        // Above the provider's quality threshold, only a severe refund slashes
+        assert_eq!(bond.resolution_slash(60, 89, 1_000).unwrap(), 0);
+        assert_eq!(bond.resolution_slash(60, 90, 1_000).unwrap(), 1_000_000);
+        assert_eq!(bond.resolution_slash(60, 100, 0).unwrap(), 0);
+
+        // The larger of the two applies, and the severe slash ignores the cap
+        assert_eq!(bond.resolution_slash(10, 50, 1_000).unwrap(), 1_000_000);
+        assert_eq!(bond.resolution_slash(10, 95, 500).unwrap(), 1_000_000);
+        assert_eq!(bond.resolution_slash(10, 95, 5_000).unwrap(), 5_000_000);
+    }
+
+    #[test]
+    fn resolution_message_appends_challenge_nonce() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_1".to_string();
+        let escrow_key = Pubkey::new_unique();
+        let content_hash = [0xab; 32];
+        let base = format!("tx_1:{}:{}:0:7:42:{}", escrow_key, escrow.amount, "ab".repeat(32));
+        assert_eq!(build_resolution_message(&escrow_key, &escrow, 7, None, 42, &content_hash, None), base);
+
+        escrow.payment_reference = Some(PaymentReference {
+            method_and_path_hash: [1; 32],
+            challenge_nonce: [0x0f; 16],
+            client_id_hash: [2; 32],
         });
+        assert_eq!(
+            build_resolution_message(&escrow_key, &escrow, 7, None, 42, &content_hash, None),
+            format!("{}:{}", base, "0f".repeat(16))
+        );
+    }
 
-        Ok(())
+    #[test]
+    fn delivery_clock_holds_auto_release_until_delivery() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.delivered_at = None;
+        let after_everything = escrow.expires_at.max(escrow.dispute_deadline()) + 1;
+        assert!(require_auto_releasable(&escrow, after_everything).is_ok());
+
+        escrow.set_delivery_clock(true);
+        assert!(require_auto_releasable(&escrow, after_everything).is_err());
+
+        escrow.delivered_at = Some(escrow.created_at);
+        assert!(require_auto_releasable(&escrow, after_everything).is_ok());
     }
 
-    /// Resolve dispute with Switchboard On-Demand oracle
-    ///
-    /// Uses Switchboard decentralized oracle network for trustless quality assessment.
-    /// The Switchboard Function calculates quality score off-chain and produces
-    /// a cryptographically verified attestation that's validated on-chain.
-    ///
-    /// # Arguments
-    /// * `quality_score` - Quality score from Switchboard Function (0-100)
-    /// * `refund_percentage` - Refund percentage from Switchboard (0-100)
-    pub fn resolve_dispute_switchboard(
-        ctx: Context<ResolveDisputeSwitchboard>,
-        quality_score: u8,
-        refund_percentage: u8,
-    ) -> Result<()> {
-        let escrow = &mut ctx.accounts.escrow;
+    #[test]
+    fn early_release_stops_at_the_dispute_deadline() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.created_at = 1_000;
+        escrow.expires_at = 1_000 + 86_400;
+        escrow.dispute_window = 3_600;
+        escrow.delivered_at = Some(2_000);
+        assert_eq!(escrow.early_release_at(), 2_000 + 3_600);
 
-        require!(
-            escrow.status == EscrowStatus::Active || escrow.status == EscrowStatus::Disputed,
-            EscrowError::InvalidStatus
+        // A window reaching past the time lock never extends it
+        escrow.dispute_window = 100_000;
+        assert_eq!(escrow.early_release_at(), escrow.expires_at);
+
+        escrow.dispute_window = 3_600;
+        escrow.set_streaming(true);
+        assert_eq!(escrow.early_release_at(), escrow.expires_at);
+    }
+
+    #[test]
+    fn resolution_message_binds_the_request() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_1".to_string();
+        let escrow_key = Pubkey::new_unique();
+        let content_hash = [0xab; 32];
+        let base = build_resolution_message(&escrow_key, &escrow, 7, None, 42, &content_hash, None);
+
+        escrow.request_hash = Some([0x5e; 32]);
+        let bound = build_resolution_message(&escrow_key, &escrow, 7, None, 42, &content_hash, None);
+        assert_eq!(bound, format!("{}:{}", base, "5e".repeat(32)));
+
+        // A response judged against another request needs another signature
+        escrow.request_hash = Some([0x5f; 32]);
+        assert_ne!(build_resolution_message(&escrow_key, &escrow, 7, None, 42, &content_hash, None), bound);
+    }
+
+    #[test]
+    fn resolution_message_binds_escrow_amount_and_nonce() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_1".to_string();
+        let escrow_key = Pubkey::new_unique();
+        let content_hash = [0xab; 32];
+        let message = |escrow: &Escrow, key: &Pubkey, nonce: u64| {
+            build_resolution_message(key, escrow, nonce, None, 42, &content_hash, None)
+        };
+        let signed = message(&escrow, &escrow_key, 7);
+
+        // Same transaction ID, but another escrow, amount or nonce
+        assert_ne!(message(&escrow, &Pubkey::new_unique(), 7), signed);
+        assert_ne!(message(&escrow, &escrow_key, 8), signed);
+        let mut reinitialized = escrow.clone();
+        reinitialized.resolution_nonce += 1;
+        assert_ne!(message(&reinitialized, &escrow_key, 7), signed);
+        escrow.amount += 1;
+        assert_ne!(message(&escrow, &escrow_key, 7), signed);
+
+        assert_eq!(
+            build_milestone_resolution_message(&escrow_key, &escrow, 7, 1, 42, 50),
+            format!("tx_1:{}:{}:0:7:milestone:1:42:50", escrow_key, escrow.amount)
+        );
+    }
+
+    fn settled_escrow(status: EscrowStatus, refund_percentage: Option<u8>) -> Escrow {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.amount = 1_000_000;
+        escrow.set_status(status);
+        escrow.refund_percentage = refund_percentage;
+        escrow
+    }
+
+    #[test]
+    fn audit_expects_full_payment_on_release() {
+        let mut escrow = settled_escrow(EscrowStatus::Released, None);
+        escrow.paid_to_api = 990_000;
+        escrow.fees_paid = 10_000;
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+
+        escrow.refunded_to_agent = 1;
+        assert_ne!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
+
+    #[test]
+    fn audit_expects_split_on_resolution() {
+        let mut escrow = settled_escrow(EscrowStatus::Resolved, Some(75));
+        escrow.refunded_to_agent = 750_000;
+        escrow.paid_to_api = 250_000;
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+
+        // Lamports missing from the API's share are caught
+        escrow.paid_to_api = 240_000;
+        assert_ne!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
+
+    #[test]
+    fn audit_leaves_the_oracle_fee_out_of_the_split() {
+        let mut escrow = settled_escrow(EscrowStatus::Resolved, Some(75));
+        escrow.oracle_fee_paid = 20_000;
+        assert_eq!(escrow.open_amount(), 980_000);
+        escrow.refunded_to_agent = 735_000;
+        escrow.paid_to_api = 245_000;
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+
+        // Splitting the full amount anyway would overpay someone
+        escrow.paid_to_api = 265_000;
+        assert_ne!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
+
+    #[test]
+    fn audit_accepts_repriced_usd_settlements() {
+        let mut escrow = settled_escrow(EscrowStatus::Resolved, Some(25));
+        escrow.usd_target_cents = Some(1_000);
+        // SOL rose, so the API's 75% of the target took fewer lamports
+        escrow.refunded_to_agent = 400_000;
+        escrow.paid_to_api = 600_000;
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+
+        // Repricing never refunds less than the plain split
+        escrow.refunded_to_agent = 200_000;
+        escrow.paid_to_api = 800_000;
+        assert_ne!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
+
+    #[test]
+    fn usd_cents_convert_at_the_feed_price() {
+        // $10 at $150/SOL
+        assert_eq!(usd_cents_to_lamports(1_000, 15_000).unwrap(), 66_666_666);
+        assert_eq!(lamports_to_usd_cents(66_666_666, 15_000), 999);
+    }
+
+    #[test]
+    fn usd_escrows_release_only_at_a_price() {
+        let config = default_config();
+        let mut escrow = settled_escrow(EscrowStatus::Active, None);
+        assert_eq!(settlement_price(&escrow, None, &config).unwrap(), None);
+
+        escrow.usd_target_cents = Some(10);
+        assert!(settlement_price(&escrow, None, &config).is_err());
+
+        // $0.10 at $200/SOL; the rest goes back to the agent
+        assert_eq!(
+            release_split(&escrow, &config, Some(20_000), 0).unwrap(),
+            (500_000, 500_000, 0)
+        );
+
+        // The tip comes out of the repriced payment, within its fee headroom
+        let headroom = bps_of(500_000, MAX_FEE_BPS - config.release_deduction_bps()).unwrap();
+        assert_eq!(
+            release_split(&escrow, &config, Some(20_000), u64::MAX).unwrap(),
+            (500_000, 500_000 - headroom, headroom)
+        );
+
+        // A falling price never pays the API more than the escrow holds
+        assert_eq!(
+            release_split(&escrow, &config, Some(100), 0).unwrap(),
+            (0, 1_000_000, 0)
         );
+    }
+
+    #[test]
+    fn audit_caps_fees_at_protocol_maximum() {
+        let mut escrow = settled_escrow(EscrowStatus::Released, None);
+        escrow.fees_paid = 200_000;
+        escrow.paid_to_api = 800_000;
+
+        let expected = expected_accounting(&escrow).unwrap();
+        assert_eq!(expected.fees_paid, 100_000);
+        assert_ne!(expected, escrow.recorded_accounting());
+    }
+
+    #[test]
+    fn crank_tips_stay_within_the_audited_fee_cap() {
+        assert_eq!(
+            crank_tip(1_000_000_000, 100, CRANK_TIP_LAMPORTS).unwrap(),
+            CRANK_TIP_LAMPORTS
+        );
+        // 0.001 SOL at the maximum fee leaves nothing to tip
+        assert_eq!(crank_tip(MIN_ESCROW_AMOUNT, MAX_FEE_BPS, CRANK_TIP_LAMPORTS).unwrap(), 0);
+        // However much the API offers
+        assert_eq!(
+            crank_tip(1_000_000_000, 100, u64::MAX).unwrap(),
+            bps_of(1_000_000_000, MAX_FEE_BPS - 100).unwrap()
+        );
+
+        let mut escrow = settled_escrow(EscrowStatus::Released, None);
+        let tip = crank_tip(escrow.amount, 900, CRANK_TIP_LAMPORTS).unwrap();
+        let fee = bps_of(escrow.amount - tip, 900).unwrap();
+        escrow.fees_paid = fee + tip;
+        escrow.paid_to_api = escrow.amount - tip - fee;
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
+
+    fn subscription(funded_periods: u32) -> SubscriptionEscrow {
+        SubscriptionEscrow {
+            agent: Pubkey::new_unique(),
+            api: Pubkey::new_unique(),
+            namespace: Pubkey::default(),
+            price_per_period: 1_000_000,
+            period_seconds: 1_000,
+            period_start: 10_000,
+            claimable_at: 11_000 + DISPUTE_WINDOW,
+            funded_periods,
+            periods_settled: 0,
+            disputed: false,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn subscriptions_count_only_funded_started_periods() {
+        let subscription = subscription(3);
+        assert_eq!(subscription.started_periods(9_999), 0);
+        assert_eq!(subscription.started_periods(10_000), 1);
+        assert_eq!(subscription.started_periods(11_000), 2);
+        assert_eq!(subscription.started_periods(50_000), 3);
+    }
+
+    #[test]
+    fn late_claims_keep_the_next_period_disputable() {
+        let mut subscription = subscription(3);
+        subscription.disputed = true;
+
+        // Resolved before the next period ends: its window runs from its end
+        subscription.advance_period(11_500);
+        assert_eq!(subscription.period_start, 11_000);
+        assert_eq!(subscription.claimable_at, 12_000 + DISPUTE_WINDOW);
+        assert!(!subscription.disputed);
 
-        require!(quality_score <= 100, EscrowError::InvalidQualityScore);
-        require!(refund_percentage <= 100, EscrowError::InvalidRefundPercentage);
+        // Claimed late: the following period gets a full window from now
+        let late = 20_000 + DISPUTE_WINDOW;
+        subscription.advance_period(late);
+        assert_eq!(subscription.claimable_at, late + DISPUTE_WINDOW);
+        assert_eq!((subscription.funded_periods, subscription.periods_settled), (1, 2));
+    }
 
-        // Verify Switchboard attestation
-        // The Switchboard Function result is stored in pull_feed account
-        // and contains the quality score signed by oracle nodes
-        let pull_feed = &ctx.accounts.switchboard_function;
+    #[test]
+    fn reputation_history_keeps_the_latest_snapshots_in_order() {
+        let mut history = ReputationHistory {
+            entity: Pubkey::new_unique(),
+            snapshots: [ScoreSnapshot::default(); REPUTATION_HISTORY_LEN],
+            next_index: 0,
+            count: 0,
+            bump: 255,
+        };
+        assert!(history.latest().is_none());
+        assert_eq!(history.drop_since(0), 0);
 
-        // Load and verify the Switchboard attestation
-        let feed_account_info = pull_feed.to_account_info();
-        let feed_data = PullFeedAccountData::parse(feed_account_info.data.borrow())
-            .map_err(|_| EscrowError::InvalidSwitchboardAttestation)?;
+        for i in 0..(REPUTATION_HISTORY_LEN as i64 + 5) {
+            history.push(900 - i as u16, i * 100);
+        }
+        assert_eq!(history.snapshots().count(), REPUTATION_HISTORY_LEN);
+        let first = history.snapshots().next().unwrap();
+        assert_eq!(first.timestamp, 500);
+        let latest = *history.latest().unwrap();
+        assert_eq!(latest, ScoreSnapshot { score: 866, timestamp: 3_400 });
 
-        // Validate timestamp freshness (attestation must be within 300 seconds)
-        let clock = Clock::get()?;
-        let age_seconds = clock.unix_timestamp - feed_data.last_update_timestamp;
+        history.push(600, 3_500);
+        // Snapshots older than the buffer are forgotten
+        assert_eq!(history.drop_since(0), 894 - 600);
+        assert_eq!(history.drop_since(3_400), 266);
+        assert_eq!(history.drop_since(3_500), 0);
+    }
 
-        require!(
-            age_seconds >= 0 && age_seconds <= 300,
-            EscrowError::StaleAttestation
-        );
+    #[test]
+    fn providers_are_scored_on_losses_and_poor_deliveries() {
+        let mut agent = EntityReputation::from(legacy_reputation(0, 0));
+        agent.total_transactions = 20;
+        agent.disputes_lost = 10;
+        for _ in 0..20 {
+            record_quality(&mut agent, 60, 1);
+        }
+        let mut provider = agent.clone();
+        provider.entity_type = EntityType::Provider;
 
-        msg!("Switchboard attestation age: {} seconds", age_seconds);
+        // Losing half its disputes leaves a provider no dispute score at all
+        assert_eq!(calculate_reputation_score(&agent, 0), 100 + 150 + 120);
+        assert_eq!(calculate_reputation_score(&provider, 0), 100 + 120);
 
-        // Extract quality score from Switchboard result
-        // The value is encoded as i128 in the feed
-        let switchboard_quality = feed_data.result.value;
+        provider.disputes_lost = 0;
+        assert_eq!(calculate_reputation_score(&provider, 0), 100 + 300 + 120);
+        provider.poor_quality_count = 3;
+        assert_eq!(calculate_reputation_score(&provider, 0), 100 + 300 + 120 - 150);
+        provider.poor_quality_count = 40;
+        assert_eq!(calculate_reputation_score(&provider, 0), 100);
+    }
 
-        // Verify the quality score matches what was submitted
-        require!(
-            switchboard_quality == quality_score as i128,
-            EscrowError::QualityScoreMismatch
-        );
+    #[test]
+    fn volume_adds_to_the_original_score_scale() {
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        reputation.total_transactions = 100;
+        for _ in 0..20 {
+            record_quality(&mut reputation, 100, 1);
+        }
 
-        msg!("Switchboard Quality Score: {}", quality_score);
-        msg!("Refund: {}%", refund_percentage);
+        // Without recorded volume the score is what it was before volume counted
+        assert_eq!(calculate_reputation_score(&reputation, 0), 500 + 150 + 200);
 
-        // Calculate split amounts (same logic as resolve_dispute)
-        let refund_amount = (escrow.amount as u128)
-            .checked_mul(refund_percentage as u128)
-            .ok_or(EscrowError::ArithmeticOverflow)?
-            .checked_div(100)
-            .ok_or(EscrowError::ArithmeticOverflow)? as u64;
+        reputation.volume_usd_cents = 10_000;
+        assert_eq!(calculate_reputation_score(&reputation, 0), 500 + 25 + 150 + 200);
+        reputation.volume_usd_cents = 10_000_000;
+        assert_eq!(calculate_reputation_score(&reputation, 0), 500 + 100 + 150 + 200);
 
-        let payment_amount = escrow.amount - refund_amount;
+        // The total still tops out at 1000
+        reputation.disputes_filed = 10;
+        reputation.disputes_won = 10;
+        assert_eq!(calculate_reputation_score(&reputation, 0), 1000);
+    }
 
-        msg!("Refund to Agent: {} SOL", refund_amount as f64 / 1_000_000_000.0);
-        msg!("Payment to API: {} SOL", payment_amount as f64 / 1_000_000_000.0);
+    #[test]
+    fn reputation_view_reports_the_decayed_score() {
+        let entity = Pubkey::new_unique();
+        let missing = reputation_view(entity, None, 1_000);
+        assert!(!missing.initialized);
+        assert!(missing.entity_type.is_none());
+        assert_eq!(missing.reputation_score, NEUTRAL_REPUTATION_SCORE);
 
-        // Transfer refund to agent
-        // Note: Using direct lamport manipulation instead of system_program::transfer
-        // because escrow PDA contains data and system transfer requires empty accounts
-        if refund_amount > 0 {
-            **ctx.accounts.escrow.to_account_info().try_borrow_mut_lamports()? -= refund_amount;
-            **ctx.accounts.agent.to_account_info().try_borrow_mut_lamports()? += refund_amount;
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        reputation.entity = entity;
+        reputation.total_transactions = 60;
+        for _ in 0..20 {
+            record_quality(&mut reputation, 90, 1);
         }
+        reputation.last_updated = 1_000;
+        reputation.reputation_score = calculate_reputation_score(&reputation, 1_000);
 
-        // Transfer payment to API
-        if payment_amount > 0 {
-            **ctx.accounts.escrow.to_account_info().try_borrow_mut_lamports()? -= payment_amount;
-            **ctx.accounts.api.to_account_info().try_borrow_mut_lamports()? += payment_amount;
-        }
+        let fresh = reputation_view(entity, Some(&reputation), 1_000);
+        assert!(fresh.initialized);
+        assert!(fresh.entity_type == Some(EntityType::Agent));
+        assert_eq!(fresh.reputation_score, reputation.reputation_score);
+        assert_eq!(fresh.average_quality_bps, 9_000);
 
-        let escrow = &mut ctx.accounts.escrow;
-        escrow.status = EscrowStatus::Resolved;
-        escrow.quality_score = Some(quality_score);
-        escrow.refund_percentage = Some(refund_percentage);
+        let idle = reputation_view(entity, Some(&reputation), 1_000 + REPUTATION_HALF_LIFE);
+        assert!(idle.reputation_score < fresh.reputation_score);
+        assert_eq!(idle.last_updated, 1_000);
+    }
 
-        // Update agent reputation (same logic as resolve_dispute)
-        let agent_reputation = &mut ctx.accounts.agent_reputation;
-        let clock = Clock::get()?;
+    #[test]
+    fn escrow_caps_follow_the_agent_reputation_tier() {
+        let tiers = DEFAULT_AMOUNT_TIERS;
+        validate_amount_tiers(&tiers).unwrap();
 
-        agent_reputation.total_transactions = agent_reputation.total_transactions.saturating_add(1);
+        assert_eq!(tier_cap(&tiers, None), 1_000_000_000);
+        assert_eq!(tier_cap(&tiers, Some(500)), 1_000_000_000);
+        assert_eq!(tier_cap(&tiers, Some(800)), 100_000_000_000);
+        assert_eq!(tier_cap(&tiers, Some(801)), MAX_ESCROW_AMOUNT);
 
-        let total_quality = agent_reputation.average_quality_received as u64
-            * (agent_reputation.total_transactions.saturating_sub(1)) as u64
-            + quality_score as u64;
-        agent_reputation.average_quality_received =
-            (total_quality / agent_reputation.total_transactions as u64) as u8;
+        let mut unsorted = tiers;
+        unsorted.swap(1, 2);
+        assert!(validate_amount_tiers(&unsorted).is_err());
+        let mut shrinking = tiers;
+        shrinking[2].max_amount = MIN_ESCROW_AMOUNT;
+        assert!(validate_amount_tiers(&shrinking).is_err());
+        let mut gated = tiers;
+        gated[0].min_reputation_score = 100;
+        assert!(validate_amount_tiers(&gated).is_err());
+    }
 
-        if refund_percentage >= 75 {
-            agent_reputation.disputes_won = agent_reputation.disputes_won.saturating_add(1);
-        } else if refund_percentage >= 25 {
-            agent_reputation.disputes_partial = agent_reputation.disputes_partial.saturating_add(1);
-        } else {
-            agent_reputation.disputes_lost = agent_reputation.disputes_lost.saturating_add(1);
+    #[test]
+    fn average_quality_keeps_fractions_across_resolutions() {
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        for quality in [100, 0, 0] {
+            record_quality(&mut reputation, quality, 1);
         }
+        // 33.33 rather than the 33 a rounded u8 would settle on
+        assert_eq!(reputation.average_quality_bps(), 3_333);
 
-        agent_reputation.reputation_score = calculate_reputation_score(agent_reputation);
-        agent_reputation.last_updated = clock.unix_timestamp;
+        // A u8 average truncated 99, 100, 100, ... down to 99 forever
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        record_quality(&mut reputation, 99, 1);
+        for _ in 0..99 {
+            record_quality(&mut reputation, 100, 1);
+        }
+        assert_eq!(reputation.average_quality_bps(), 9_999);
+    }
 
-        // Update API reputation
-        let api_reputation = &mut ctx.accounts.api_reputation;
-        api_reputation.total_transactions = api_reputation.total_transactions.saturating_add(1);
+    #[test]
+    fn quality_is_weighted_by_capped_escrow_size() {
+        assert_eq!(reputation_weight(0), 1);
+        assert_eq!(reputation_weight(MIN_ESCROW_AMOUNT), 1);
+        assert_eq!(reputation_weight(1_000_000_000), 1_000);
+        assert_eq!(reputation_weight(REPUTATION_WEIGHT_CAP), 100_000);
+        assert_eq!(reputation_weight(MAX_ESCROW_AMOUNT), 100_000);
+        assert_eq!(reputation_weight(u64::MAX), 100_000);
 
-        let quality_delivered = 100 - refund_percentage;
-        let total_quality_api = api_reputation.average_quality_received as u64
-            * (api_reputation.total_transactions.saturating_sub(1)) as u64
-            + quality_delivered as u64;
-        api_reputation.average_quality_received =
-            (total_quality_api / api_reputation.total_transactions as u64) as u8;
+        // One honest 100 SOL job outweighs a hundred botched 0.001 SOL ones
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        record_quality(&mut reputation, 100, reputation_weight(100_000_000_000));
+        for _ in 0..100 {
+            record_quality(&mut reputation, 0, reputation_weight(MIN_ESCROW_AMOUNT));
+        }
+        assert_eq!(reputation.average_quality_bps(), 9_990);
 
-        if refund_percentage <= 25 {
-            api_reputation.disputes_won = api_reputation.disputes_won.saturating_add(1);
-        } else if refund_percentage <= 75 {
-            api_reputation.disputes_partial = api_reputation.disputes_partial.saturating_add(1);
-        } else {
-            api_reputation.disputes_lost = api_reputation.disputes_lost.saturating_add(1);
+        // Past the cap a larger escrow buys no extra weight
+        let mut capped = EntityReputation::from(legacy_reputation(0, 0));
+        record_quality(&mut capped, 100, reputation_weight(MAX_ESCROW_AMOUNT));
+        for _ in 0..100 {
+            record_quality(&mut capped, 0, 1);
         }
+        assert_eq!(capped.average_quality_bps(), reputation.average_quality_bps());
 
-        api_reputation.reputation_score = calculate_reputation_score(api_reputation);
-        api_reputation.last_updated = clock.unix_timestamp;
+        // Weights passed in directly are held to the same cap
+        let mut direct = EntityReputation::from(legacy_reputation(0, 0));
+        record_quality(&mut direct, 100, u64::MAX);
+        assert_eq!(direct.quality_count, 100_000);
+        assert_eq!(direct.quality_sum, 10_000_000);
+    }
 
-        msg!("Dispute resolved via Switchboard!");
-        msg!("Agent reputation: {}", agent_reputation.reputation_score);
-        msg!("API reputation: {}", api_reputation.reputation_score);
+    #[test]
+    fn quality_totals_halve_instead_of_overflowing() {
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        reputation.quality_sum = u64::MAX - 10;
+        reputation.quality_count = u64::MAX / 100;
+        let before = reputation.average_quality_bps();
 
-        emit!(DisputeResolved {
-            escrow: escrow.key(),
-            transaction_id: escrow.transaction_id.clone(),
-            quality_score,
-            refund_percentage,
-            refund_amount,
-            payment_amount,
-            verifier: ctx.accounts.switchboard_function.key(),
-        });
+        record_quality(&mut reputation, 100, reputation_weight(REPUTATION_WEIGHT_CAP));
+        assert_eq!(reputation.quality_sum, (u64::MAX - 10) / 2 + 10_000_000);
+        assert_eq!(reputation.quality_count, u64::MAX / 100 / 2 + 100_000);
+        assert_eq!(reputation.average_quality_bps(), before);
 
-        Ok(())
+        // A saturated count halves too rather than freezing the average
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        reputation.quality_sum = 0;
+        reputation.quality_count = u64::MAX;
+        record_quality(&mut reputation, 100, 1);
+        assert_eq!(reputation.quality_count, u64::MAX / 2 + 1);
+        assert_eq!(reputation.quality_sum, 100);
     }
 
-    /// Mark escrow as disputed (agent initiates dispute)
-    pub fn mark_disputed(ctx: Context<MarkDisputed>) -> Result<()> {
-        let escrow = &mut ctx.accounts.escrow;
-        let reputation = &mut ctx.accounts.reputation;
+    #[test]
+    fn repeat_dealing_between_two_keys_is_discounted() {
+        let mut escrow = settled_escrow(EscrowStatus::Resolved, Some(0));
+        escrow.amount = 1_000_000_000;
+        escrow.agent = Pubkey::new_unique();
+        escrow.api = Pubkey::new_unique();
 
-        require!(
-            escrow.status == EscrowStatus::Active,
-            EscrowError::InvalidStatus
-        );
+        let mut agent = EntityReputation::from(legacy_reputation(0, 0));
+        agent.entity = escrow.agent;
+        let mut provider = EntityReputation::from(legacy_reputation(0, 0));
+        provider.entity = escrow.api;
+        provider.entity_type = EntityType::Provider;
+        let mut diverse = provider.clone();
 
-        require!(
-            ctx.accounts.agent.key() == escrow.agent,
-            EscrowError::Unauthorized
-        );
+        for _ in 0..10 {
+            apply_dispute_reputation(Some(&mut agent), Some(&mut provider), &escrow, 100, 0, 0, 0, 0);
+        }
+        assert_eq!(provider.total_transactions, 10);
+        assert_eq!(provider.repeat_transactions, 9);
+        assert_eq!(agent.repeat_transactions, 9);
+        assert_eq!(provider.distinct_recent_counterparties(), 1);
+        // 1000 for the first deal, then divided by 2, 3, ... up to the window
+        assert_eq!(provider.quality_count, 1_000 + 500 + 333 + 250 + 200 + 166 + 142 + 125 + 111 + 111);
 
-        // Check if dispute window is still open (before time lock expires)
-        let clock = Clock::get()?;
-        require!(
-            clock.unix_timestamp < escrow.expires_at,
-            EscrowError::DisputeWindowExpired
+        for _ in 0..10 {
+            escrow.agent = Pubkey::new_unique();
+            apply_dispute_reputation(None, Some(&mut diverse), &escrow, 100, 0, 0, 0, 0);
+        }
+        assert_eq!(diverse.repeat_transactions, 0);
+        assert_eq!(diverse.quality_count, 10_000);
+        assert_eq!(diverse.distinct_recent_counterparties(), RECENT_COUNTERPARTIES as u8);
+
+        // Only the first self-dealt transaction earns transaction credit
+        assert_eq!(
+            calculate_reputation_score(&diverse, 0) - calculate_reputation_score(&provider, 0),
+            9 * 5
         );
+    }
 
-        // Calculate dispute cost based on reputation
-        let dispute_cost = calculate_dispute_cost(reputation);
-        require!(
-            ctx.accounts.agent.lamports() >= dispute_cost,
-            EscrowError::InsufficientDisputeFunds
+    #[test]
+    fn counterparty_window_forgets_old_partners() {
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        let first = Pubkey::new_unique();
+        assert_eq!(reputation.record_counterparty(&first), 0);
+        for _ in 0..RECENT_COUNTERPARTIES {
+            reputation.record_counterparty(&Pubkey::new_unique());
+        }
+        assert_eq!(reputation.record_counterparty(&first), 0);
+        assert_eq!(reputation.repeat_transactions, 0);
+        assert_eq!(reputation.record_counterparty(&first), 1);
+        assert_eq!(reputation.repeat_transactions, 1);
+        assert_eq!(counterparty_fingerprint(&Pubkey::default()), 1);
+    }
+
+    #[test]
+    fn untracked_reputations_migrate_with_empty_counterparties() {
+        let mut reputation = EntityReputation::from(legacy_reputation(87, 12));
+        reputation.record_counterparty(&Pubkey::new_unique());
+        // Filled in so the serialized record spans the whole account
+        reputation.last_price_feed_used = Some(Pubkey::new_unique());
+        let mut data = Vec::new();
+        reputation.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + EntityReputation::INIT_SPACE);
+
+        // The layout before tracking, padded the way `migrate_reputation` does
+        data.truncate(data.len() - EntityReputation::COUNTERPARTY_SPACE);
+        data.resize(8 + EntityReputation::INIT_SPACE, 0);
+        let migrated = EntityReputation::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(migrated.quality_sum, reputation.quality_sum);
+        assert_eq!(migrated.bump, reputation.bump);
+        assert_eq!(migrated.recent_counterparties, [0; RECENT_COUNTERPARTIES]);
+        assert_eq!(migrated.distinct_recent_counterparties(), 0);
+    }
+
+    #[test]
+    fn migrated_reputations_carry_the_rounded_average() {
+        let migrated = EntityReputation::from(legacy_reputation(87, 12));
+        assert_eq!(migrated.quality_sum, 87 * 12);
+        assert_eq!(migrated.quality_count, 12);
+        assert_eq!(migrated.average_quality_bps(), 8_700);
+        assert_eq!(EntityReputation::from(legacy_reputation(0, 0)).average_quality_bps(), 0);
+    }
+
+    #[test]
+    fn reputation_leads_halve_each_idle_half_life() {
+        assert_eq!(decay_reputation_score(900, 0), 900);
+        assert_eq!(decay_reputation_score(900, REPUTATION_HALF_LIFE / 2), 800);
+        assert_eq!(decay_reputation_score(900, REPUTATION_HALF_LIFE), 700);
+        assert_eq!(decay_reputation_score(900, 2 * REPUTATION_HALF_LIFE), 600);
+        assert_eq!(decay_reputation_score(900, 40 * REPUTATION_HALF_LIFE), NEUTRAL_REPUTATION_SCORE);
+
+        // Bad records stay put
+        assert_eq!(decay_reputation_score(200, 10 * REPUTATION_HALF_LIFE), 200);
+
+        let mut previous = 1000;
+        for day in 0..120 {
+            let score = decay_reputation_score(1000, day * SECONDS_PER_DAY);
+            assert!(score <= previous && score >= NEUTRAL_REPUTATION_SCORE);
+            previous = score;
+        }
+    }
+
+    #[test]
+    fn arbitration_bonds_follow_the_direction_of_the_ruling() {
+        // Agent escalated a 40% refund
+        assert!(arbitration_bond_returned(true, 40, 65));
+        assert!(!arbitration_bond_returned(true, 40, 40));
+        assert!(!arbitration_bond_returned(true, 40, 30));
+        // API escalated the same ruling
+        assert!(arbitration_bond_returned(false, 40, 15));
+        assert!(!arbitration_bond_returned(false, 40, 40));
+        assert!(!arbitration_bond_returned(false, 40, 50));
+
+        assert!(40u8.abs_diff(65) <= MAX_ARBITRATOR_DEVIATION);
+        assert!(40u8.abs_diff(66) > MAX_ARBITRATOR_DEVIATION);
+        assert!(10u8.abs_diff(0) <= MAX_ARBITRATOR_DEVIATION);
+    }
+
+    #[test]
+    fn streams_vest_linearly_and_disputes_split_the_rest() {
+        let mut escrow = settled_escrow(EscrowStatus::Active, None);
+        escrow.set_streaming(true);
+        escrow.expires_at = escrow.created_at + 4 * escrow.dispute_window;
+        let duration = escrow.expires_at - escrow.created_at;
+        assert_eq!(escrow.vested_amount(escrow.created_at - 1).unwrap(), 0);
+        assert_eq!(
+            escrow.vested_amount(escrow.created_at + duration / 4).unwrap(),
+            escrow.amount / 4
         );
+        assert_eq!(escrow.vested_amount(escrow.expires_at).unwrap(), escrow.amount);
+        // Disputes stay open for the whole stream
+        assert_eq!(escrow.dispute_deadline(), escrow.expires_at);
 
-        // Update reputation - record dispute filed
-        reputation.disputes_filed = reputation.disputes_filed.saturating_add(1);
+        // A quarter streamed, then the rest resolved at a full refund
+        escrow.streamed_amount = escrow.amount / 4;
+        escrow.paid_to_api = escrow.amount / 4;
+        escrow.refunded_to_agent = escrow.open_amount();
+        escrow.refund_percentage = Some(100);
+        escrow.set_status(EscrowStatus::Resolved);
+        assert_eq!(expected_accounting(&escrow).unwrap(), escrow.recorded_accounting());
+    }
 
-        escrow.status = EscrowStatus::Disputed;
+    #[test]
+    fn channels_settle_only_closed_undisputed_entries() {
+        let entry = |id, recorded_at, disputed, resolved| ChannelEntry {
+            id,
+            amount: 100 + id,
+            recorded_at,
+            disputed,
+            resolved,
+        };
+        let mut channel = PaymentChannel {
+            agent: Pubkey::new_unique(),
+            api: Pubkey::new_unique(),
+            namespace: DEFAULT_NAMESPACE,
+            balance: 1_000,
+            carried_credit: 0,
+            next_entry_id: 5,
+            total_settled: 0,
+            entries: vec![
+                entry(0, 0, false, false),
+                entry(1, 0, true, false),
+                entry(2, 10, false, false),
+                entry(3, 20, false, true),
+                entry(4, 30, true, false),
+            ],
+            bump: 0,
+        };
+        assert_eq!(channel.pending_debits(), 510);
 
-        msg!("Escrow marked as disputed (cost: {} lamports)", dispute_cost);
+        // Entry 2's window is still open; resolved entry 3 settles regardless
+        assert_eq!(channel.take_settleable(DISPUTE_WINDOW + 5), (2, 100 + 103));
+        let left: Vec<u64> = channel.entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(left, vec![1, 2, 4]);
 
-        emit!(DisputeMarked {
-            escrow: escrow.key(),
-            agent: escrow.agent,
-            transaction_id: escrow.transaction_id.clone(),
-            timestamp: clock.unix_timestamp,
-        });
+        assert_eq!(channel.take_settleable(DISPUTE_WINDOW + 10), (1, 102));
+        assert!(channel.entry_mut(2).is_none());
+        assert!(channel.entry_mut(4).unwrap().disputed);
+    }
 
-        Ok(())
+    #[test]
+    fn quality_feed_results_carry_score_and_refund() {
+        assert_eq!(unpack_quality_result(35_075), Some((35, 75)));
+        assert_eq!(unpack_quality_result(100), Some((0, 100)));
+        assert_eq!(unpack_quality_result(100_000), Some((100, 0)));
+        // A bare score no longer parses as a packed result with a valid refund
+        assert!(unpack_quality_result(350).is_none());
+        assert!(unpack_quality_result(101_000).is_none());
+        assert!(unpack_quality_result(-1).is_none());
     }
 
-    /// Initialize or update entity reputation
-    pub fn init_reputation(ctx: Context<InitReputation>) -> Result<()> {
-        let reputation = &mut ctx.accounts.reputation;
-        let clock = Clock::get()?;
+    #[test]
+    fn quorums_decide_only_appealed_rulings_under_an_appeal_window() {
+        let mut config = default_config();
+        let mut escrow = Escrow::from(legacy_escrow());
+        assert!(require_quorum_resolvable(&escrow, &config, 50).is_ok());
 
-        reputation.entity = ctx.accounts.entity.key();
-        reputation.entity_type = EntityType::Agent;
-        reputation.total_transactions = 0;
-        reputation.disputes_filed = 0;
-        reputation.disputes_won = 0;
-        reputation.disputes_partial = 0;
-        reputation.disputes_lost = 0;
-        reputation.average_quality_received = 0;
-        reputation.reputation_score = 500; // Start at medium
-        reputation.created_at = clock.unix_timestamp;
-        reputation.last_updated = clock.unix_timestamp;
-        reputation.bump = ctx.bumps.reputation;
+        config.appeal_window_seconds = 3600;
+        assert!(require_quorum_resolvable(&escrow, &config, 50).is_err());
 
-        msg!("Reputation initialized for {}", ctx.accounts.entity.key());
+        // A held ruling waits out its window unless it was appealed
+        escrow.appeal_deadline = Some(escrow.created_at + 3600);
+        assert!(require_quorum_resolvable(&escrow, &config, 50).is_err());
+        escrow.set_appealed(true);
+        assert!(require_quorum_resolvable(&escrow, &config, 50).is_ok());
+        assert!(require_quorum_resolvable(&escrow, &config, 101).is_err());
+    }
 
-        Ok(())
+    #[test]
+    fn feed_resolutions_wait_for_the_appeal_window() {
+        let mut config = default_config();
+        let escrow = Escrow::from(legacy_escrow());
+        assert!(require_feed_resolvable(&escrow, &config, 50).is_ok());
+
+        // The Pyth and Switchboard paths settle at once, so an appeal window closes them
+        config.appeal_window_seconds = 3600;
+        assert!(require_single_step_resolution(&config).is_err());
+        assert!(require_feed_resolvable(&escrow, &config, 50).is_err());
     }
 
-    /// Update reputation after transaction completes
-    /// Only callable by the escrow program itself during resolve_dispute
-    pub fn update_reputation(
-        ctx: Context<UpdateReputation>,
-        quality_score: u8,
-        refund_percentage: u8,
-    ) -> Result<()> {
-        // Authorization: Only allow updates from program-owned accounts
-        // In practice, this should be called via CPI from resolve_dispute
-        let reputation = &mut ctx.accounts.reputation;
-        let clock = Clock::get()?;
+    #[test]
+    fn pyth_quality_feeds_must_be_unscaled_packed_results() {
+        assert_eq!(pyth_quality_result(35_075, 0).unwrap(), (35, 75));
+        // The same digits under a price exponent are not a score
+        assert!(pyth_quality_result(35_075, -2).is_err());
+        assert!(pyth_quality_result(3_507_500_000_000, -8).is_err());
+        assert!(pyth_quality_result(35_101, 0).is_err());
+        assert!(pyth_quality_result(-35_075, 0).is_err());
+    }
 
-        reputation.total_transactions = reputation.total_transactions.saturating_add(1);
+    #[test]
+    fn organization_spend_limits_reset_daily() {
+        let mut member = OrganizationMember {
+            organization: Pubkey::new_unique(),
+            agent: Pubkey::new_unique(),
+            daily_limit: 1_000,
+            spent_today: 0,
+            spend_day: 0,
+            bump: 0,
+        };
+        let day = 20_000 * SECONDS_PER_DAY;
 
-        // Update average quality received
-        let total_quality = reputation.average_quality_received as u64
-            * (reputation.total_transactions - 1) as u64
-            + quality_score as u64;
-        reputation.average_quality_received = (total_quality / reputation.total_transactions as u64) as u8;
+        member.charge(600, day).unwrap();
+        member.charge(400, day + 3_600).unwrap();
+        assert!(member.charge(1, day + 7_200).is_err());
+        assert_eq!(member.spent_today, 1_000);
 
-        // Categorize dispute outcome
-        if refund_percentage >= 75 {
-            reputation.disputes_won = reputation.disputes_won.saturating_add(1);
-        } else if refund_percentage >= 25 {
-            reputation.disputes_partial = reputation.disputes_partial.saturating_add(1);
-        } else {
-            reputation.disputes_lost = reputation.disputes_lost.saturating_add(1);
-        }
+        member.charge(700, day + SECONDS_PER_DAY).unwrap();
+        assert_eq!(member.spent_today, 700);
+        assert_eq!(member.spend_day, 20_001);
+    }
 
-        // Calculate new reputation score (0-1000)
-        reputation.reputation_score = calculate_reputation_score(reputation);
-        reputation.last_updated = clock.unix_timestamp;
+    #[test]
+    fn restricted_agents_pay_only_their_organizations_allowlist() {
+        let (agent, organization) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (allowed_api, other_api) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let allowlist = Allowlist { organization, allowed: vec![allowed_api], bump: 0 };
+        let foreign = Allowlist { organization: Pubkey::new_unique(), allowed: vec![allowed_api], bump: 0 };
 
-        msg!("Reputation updated: score = {}", reputation.reputation_score);
+        let restriction_key = Pubkey::new_unique();
+        let (mut lamports, mut data) = (0u64, Vec::new());
+        let system = Pubkey::default();
+        let unrestricted = AccountInfo::new(&restriction_key, false, false, &mut lamports, &mut data, &system, false, 0);
+        assert!(check_allowlist(&unrestricted, None, &agent, &other_api).is_ok());
+        // An allowlist supplied voluntarily is still enforced
+        assert!(check_allowlist(&unrestricted, Some(&allowlist), &agent, &other_api).is_err());
 
-        Ok(())
+        let mut data = Vec::new();
+        AgentRestriction { agent, organization, bump: 0 }.try_serialize(&mut data).unwrap();
+        let mut lamports = 0u64;
+        let restricted = AccountInfo::new(&restriction_key, false, false, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert!(check_allowlist(&restricted, Some(&allowlist), &agent, &allowed_api).is_ok());
+        assert!(check_allowlist(&restricted, Some(&allowlist), &agent, &other_api).is_err());
+        assert!(check_allowlist(&restricted, Some(&foreign), &agent, &allowed_api).is_err());
+        assert!(check_allowlist(&restricted, None, &agent, &allowed_api).is_err());
     }
 
-    /// Rate limit check - ensures entity hasn't exceeded limits
-    pub fn check_rate_limit(ctx: Context<CheckRateLimit>) -> Result<()> {
-        let rate_limiter = &mut ctx.accounts.rate_limiter;
-        let clock = Clock::get()?;
-        let current_hour = clock.unix_timestamp / 3600;
-        let current_day = clock.unix_timestamp / 86400;
+    #[test]
+    fn refunds_go_to_the_named_recipient() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        let (cold_key, other_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let system = Pubkey::default();
+        let (mut lamports, mut data) = ([0u64; 3], [Vec::new(), Vec::new(), Vec::new()]);
+        let [agent_lamports, cold_lamports, other_lamports] = &mut lamports;
+        let [agent_data, cold_data, other_data] = &mut data;
+        let agent = AccountInfo::new(&escrow.agent, false, true, agent_lamports, agent_data, &system, false, 0);
+        let cold = AccountInfo::new(&cold_key, false, true, cold_lamports, cold_data, &system, false, 0);
+        let other = AccountInfo::new(&other_key, false, true, other_lamports, other_data, &system, false, 0);
 
-        // Reset hourly counter if hour changed
-        if current_hour > rate_limiter.last_hour_check {
-            rate_limiter.transactions_last_hour = 0;
-            rate_limiter.last_hour_check = current_hour;
-        }
+        assert_eq!(refund_destination(&escrow, &agent, Some(&cold)).unwrap().key, agent.key);
 
-        // Reset daily counter if day changed
-        if current_day > rate_limiter.last_day_check {
-            rate_limiter.transactions_last_day = 0;
-            rate_limiter.disputes_last_day = 0;
-            rate_limiter.last_day_check = current_day;
-        }
+        escrow.refund_recipient = Some(cold_key);
+        assert_eq!(refund_destination(&escrow, &agent, Some(&cold)).unwrap().key, &cold_key);
+        assert!(refund_destination(&escrow, &agent, Some(&other)).is_err());
+        assert!(refund_destination(&escrow, &agent, None).is_err());
+    }
 
-        // Get limits based on verification level
-        let (hour_limit, day_limit, _dispute_day_limit) = get_rate_limits(rate_limiter.verification_level.clone());
+    #[test]
+    fn delegates_act_for_the_agent() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        let (agent, api, hot_key) = (escrow.agent, escrow.api, Pubkey::new_unique());
 
-        // Check limits
-        require!(
-            rate_limiter.transactions_last_hour < hour_limit,
-            EscrowError::RateLimitExceeded
+        assert!(escrow.acts_for_agent(&agent));
+        assert!(!escrow.acts_for_agent(&hot_key));
+
+        escrow.delegate = Some(hot_key);
+        assert!(escrow.acts_for_agent(&hot_key));
+        assert!(escrow.acts_for_agent(&agent));
+        assert!(!escrow.acts_for_agent(&api));
+    }
+
+    #[test]
+    fn release_tips_go_to_permissionless_releasers() {
+        let config = default_config();
+        let mut escrow = settled_escrow(EscrowStatus::Active, None);
+        let (cranker, hot_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        escrow.delegate = Some(hot_key);
+        escrow.release_tip_lamports = 20_000;
+
+        assert_eq!(escrow.release_tip_for(&escrow.agent), 0);
+        assert_eq!(escrow.release_tip_for(&hot_key), 0);
+        assert_eq!(escrow.release_tip_for(&cranker), 20_000);
+
+        // The tip comes out of the API's share, never the agent's
+        let tip = escrow.release_tip_for(&cranker);
+        assert_eq!(
+            release_split(&escrow, &config, None, tip).unwrap(),
+            (0, escrow.amount - 20_000, 20_000)
         );
-        require!(
-            rate_limiter.transactions_last_day < day_limit,
-            EscrowError::RateLimitExceeded
+        assert_eq!(
+            release_split(&escrow, &config, None, escrow.release_tip_for(&escrow.agent)).unwrap(),
+            (0, escrow.amount, 0)
         );
+    }
 
-        // Increment counters
-        rate_limiter.transactions_last_hour = rate_limiter.transactions_last_hour.saturating_add(1);
-        rate_limiter.transactions_last_day = rate_limiter.transactions_last_day.saturating_add(1);
+    #[test]
+    fn receipts_record_the_outcome() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_receipt".to_string();
+        escrow.quality_score = None;
+        escrow.refund_percentage = None;
+        escrow.paid_to_api = 1_000;
+        escrow.refunded_to_agent = 0;
+        assert_eq!(receipts::receipt_uri(&escrow), "x402:receipt:tx_receipt::0:1000:0");
 
-        Ok(())
+        escrow.quality_score = Some(40);
+        escrow.refund_percentage = Some(60);
+        escrow.paid_to_api = 400;
+        escrow.refunded_to_agent = 600;
+        assert_eq!(receipts::receipt_uri(&escrow), "x402:receipt:tx_receipt:40:60:400:600");
     }
-}
 
-// Helper functions
-fn calculate_dispute_cost(reputation: &EntityReputation) -> u64 {
-    if reputation.total_transactions == 0 {
-        return BASE_DISPUTE_COST;
+    #[test]
+    fn dispute_duration_counts_from_mark_disputed() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        assert_eq!(escrow.dispute_duration(1_000), None);
+
+        escrow.disputed_at = Some(400);
+        assert_eq!(escrow.dispute_duration(1_000), Some(600));
+        assert_eq!(escrow.dispute_duration(400), Some(0));
     }
 
-    let dispute_rate = (reputation.disputes_filed * 100) / reputation.total_transactions;
+    #[test]
+    fn expired_disputes_settle_once_stale() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.disputed_at = Some(escrow.created_at + 60);
+        let stale_at = escrow.stale_at().unwrap();
+        assert!(stale_at > escrow.expires_at);
 
-    let multiplier = match dispute_rate {
-        0..=20 => 1,     // Normal dispute rate
-        21..=40 => 2,    // High dispute rate
-        41..=60 => 5,    // Very high dispute rate
-        _ => 10,         // Abuse pattern
-    };
+        // Past the time lock the dispute waits, then nothing stands in the way
+        assert!(require_stale_dispute(&escrow, escrow.expires_at).is_err());
+        assert!(require_stale_dispute(&escrow, stale_at - 1).is_err());
+        assert!(require_stale_dispute(&escrow, stale_at).is_ok());
 
-    BASE_DISPUTE_COST.saturating_mul(multiplier)
-}
+        // A held ruling settles through `settle_resolution` unless appealed
+        escrow.appeal_deadline = Some(escrow.expires_at);
+        assert!(require_stale_dispute(&escrow, stale_at).is_err());
+        escrow.set_appealed(true);
+        assert!(require_stale_dispute(&escrow, stale_at).is_ok());
 
-fn calculate_reputation_score(reputation: &EntityReputation) -> u16 {
-    if reputation.total_transactions == 0 {
-        return 500; // Default medium score
+        escrow.appeal_deadline = None;
+        escrow.set_appealed(false);
+        escrow.set_status(EscrowStatus::Active);
+        assert!(require_stale_dispute(&escrow, stale_at).is_err());
     }
 
-    let tx_score = reputation.total_transactions.min(100) as u16 * 5; // Max 500 from transactions
+    #[test]
+    fn providers_answer_a_dispute_once_within_the_window() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.disputed_at = None;
+        assert!(require_provider_response_open(&escrow, 1_000).is_err());
 
-    let dispute_score = if reputation.disputes_filed > 0 {
-        let win_rate = (reputation.disputes_won * 100) / reputation.disputes_filed;
-        (win_rate as u16 * 3).min(300) // Max 300 from dispute wins
-    } else {
-        150 // No disputes, neutral
-    };
+        escrow.disputed_at = Some(1_000);
+        assert!(require_provider_response_open(&escrow, 1_000).is_ok());
+        assert!(require_provider_response_open(&escrow, 1_000 + PROVIDER_RESPONSE_WINDOW - 1).is_ok());
+        assert!(require_provider_response_open(&escrow, 1_000 + PROVIDER_RESPONSE_WINDOW).is_err());
 
-    let quality_score = (reputation.average_quality_received as u16 * 2).min(200); // Max 200 from quality
+        escrow.provider_evidence_hash = Some([1; 32]);
+        assert!(require_provider_response_open(&escrow, 1_000).is_err());
 
-    (tx_score + dispute_score + quality_score).min(1000)
-}
+        // Once a ruling is held the response can no longer inform it
+        escrow.provider_evidence_hash = None;
+        escrow.appeal_deadline = Some(2_000);
+        assert!(require_provider_response_open(&escrow, 1_000).is_err());
 
-fn get_rate_limits(verification: VerificationLevel) -> (u16, u16, u16) {
-    match verification {
-        VerificationLevel::Basic => (1, 10, 3),        // 1/hour, 10/day, 3 disputes/day
-        VerificationLevel::Staked => (10, 100, 10),    // 10/hour, 100/day, 10 disputes/day
-        VerificationLevel::Social => (50, 500, 50),    // 50/hour, 500/day, 50 disputes/day
-        VerificationLevel::KYC => (1000, 10000, 1000), // Unlimited
+        escrow.appeal_deadline = None;
+        escrow.set_status(EscrowStatus::Resolved);
+        assert!(require_provider_response_open(&escrow, 1_000).is_err());
     }
-}
 
-// ============================================================================
-// Account Structs
-// ============================================================================
+    #[test]
+    fn stale_disputes_settle_on_the_escrow_terms() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.disputed_at = Some(escrow.created_at + 60);
+        let default_stale_at =
+            escrow.expires_at.max(escrow.dispute_deadline()) + STALE_DISPUTE_TIMEOUT;
+        assert_eq!(escrow.stale_at().unwrap(), default_stale_at);
+        assert_eq!(escrow.stale_refund_percentage(), STALE_DISPUTE_REFUND_PERCENTAGE);
 
-#[derive(Accounts)]
-#[instruction(amount: u64, time_lock: i64, transaction_id: String)]
-pub struct InitializeEscrow<'info> {
-    #[account(
-        init,
-        payer = agent,
-        space = 8 + Escrow::INIT_SPACE,
-        seeds = [b"escrow", transaction_id.as_bytes()],
-        bump
-    )]
-    pub escrow: Account<'info, Escrow>,
+        escrow.stale_dispute_terms = Some(StaleDisputeTerms {
+            verifier_timeout: MIN_VERIFIER_TIMEOUT,
+            refund_percentage: 100,
+        });
+        assert_eq!(escrow.stale_at().unwrap(), escrow.created_at + 60 + MIN_VERIFIER_TIMEOUT);
+        assert_eq!(escrow.stale_refund_percentage(), 100);
 
-    #[account(mut)]
-    pub agent: Signer<'info>,
+        // An appealed resolution still gets its full window
+        escrow.appeal_deadline = Some(escrow.created_at + 2 * MIN_VERIFIER_TIMEOUT);
+        assert_eq!(escrow.stale_at().unwrap(), escrow.created_at + 2 * MIN_VERIFIER_TIMEOUT);
 
-    /// CHECK: API wallet address
-    pub api: AccountInfo<'info>,
+        // Untimestamped disputes fall back to the default timeout
+        escrow.disputed_at = None;
+        assert!(escrow.stale_at().unwrap() > default_stale_at);
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn settlement_payload_reports_the_outcome() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.amount = 1_000;
+        escrow.refunded_to_agent = 650;
+        escrow.paid_to_api = 350;
+        escrow.quality_score = Some(40);
+        escrow.refund_percentage = Some(65);
+        escrow.settled_at = Some(1_700_000_000);
+        let escrow_key = Pubkey::new_unique();
 
-#[derive(Accounts)]
-pub struct ReleaseFunds<'info> {
-    #[account(
-        mut,
-        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
-        bump = escrow.bump
-    )]
-    pub escrow: Account<'info, Escrow>,
+        let payload = wormhole::settlement_payload(&escrow_key, &escrow);
+        let id = escrow.transaction_id.as_bytes();
+        assert_eq!(payload.len(), 1 + 32 * 4 + 8 * 3 + 2 + 8 + 1 + id.len());
+        assert_eq!(payload[0], wormhole::PAYLOAD_ID_SETTLEMENT);
+        assert_eq!(payload[1..33], escrow_key.to_bytes());
+        assert_eq!(payload[97..129], [0; 32]); // SOL escrow
+        assert_eq!(payload[129..137], 1_000u64.to_be_bytes());
+        assert_eq!(payload[137..145], 650u64.to_be_bytes());
+        assert_eq!(payload[145..153], 350u64.to_be_bytes());
+        assert_eq!(payload[153..155], [40, 65]);
+        assert_eq!(payload[155..163], 1_700_000_000i64.to_be_bytes());
+        assert_eq!(payload[163] as usize, id.len());
+        assert_eq!(&payload[164..], id);
 
-    #[account(mut)]
-    pub agent: Signer<'info>,
+        escrow.quality_score = None;
+        assert_eq!(
+            wormhole::settlement_payload(&escrow_key, &escrow)[153],
+            wormhole::NO_QUALITY_SCORE
+        );
+    }
 
-    /// CHECK: API wallet address
-    #[account(mut)]
-    pub api: AccountInfo<'info>,
+    #[test]
+    fn reputation_reasons_mirror_dispute_categories() {
+        assert_eq!(ReputationChangeReason::dispute(100), ReputationChangeReason::DisputeWon);
+        assert_eq!(ReputationChangeReason::dispute(75), ReputationChangeReason::DisputeWon);
+        assert_eq!(ReputationChangeReason::dispute(74), ReputationChangeReason::DisputePartial);
+        assert_eq!(ReputationChangeReason::dispute(25), ReputationChangeReason::DisputePartial);
+        assert_eq!(ReputationChangeReason::dispute(24), ReputationChangeReason::DisputeLost);
+        assert_eq!(ReputationChangeReason::dispute(0), ReputationChangeReason::DisputeLost);
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn session_keys_act_within_scope_until_expiry() {
+        let session = SessionKey {
+            agent: Pubkey::new_unique(),
+            session_key: Pubkey::new_unique(),
+            scope: SESSION_SCOPE_DISPUTE,
+            expires_at: 1_000,
+            bump: 0,
+        };
 
-#[derive(Accounts)]
-pub struct ResolveDispute<'info> {
-    #[account(
-        mut,
-        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
-        bump = escrow.bump
-    )]
-    pub escrow: Account<'info, Escrow>,
+        assert!(session.authorize(SESSION_SCOPE_DISPUTE, 999).is_ok());
+        assert!(session.authorize(SESSION_SCOPE_DISPUTE, 1_000).is_err());
+        assert!(session.authorize(SESSION_SCOPE_ESCROW, 999).is_err());
+    }
+
+    #[test]
+    fn switchboard_feeds_must_share_the_trusted_queue_and_owner() {
+        let (switchboard, queue_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (feed_key, forged_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let forger = Pubkey::new_unique();
+        let (mut lamports, mut data) = ([0u64; 3], [Vec::new(), Vec::new(), Vec::new()]);
+        let [queue_lamports, feed_lamports, forged_lamports] = &mut lamports;
+        let [queue_data, feed_data, forged_data] = &mut data;
+        let queue = AccountInfo::new(&queue_key, false, false, queue_lamports, queue_data, &switchboard, false, 0);
+        let feed = AccountInfo::new(&feed_key, false, false, feed_lamports, feed_data, &switchboard, false, 0);
+        let forged = AccountInfo::new(&forged_key, false, false, forged_lamports, forged_data, &forger, false, 0);
+
+        assert!(require_trusted_switchboard_feed(&feed, &queue_key, &queue, &queue_key).is_ok());
+        // A forged feed can name the right queue but not be owned by Switchboard
+        assert!(require_trusted_switchboard_feed(&forged, &queue_key, &queue, &queue_key).is_err());
+        // A genuine feed on another queue
+        assert!(require_trusted_switchboard_feed(&feed, &forger, &queue, &queue_key).is_err());
+        // No trusted queue configured
+        let unset = Pubkey::default();
+        assert!(require_trusted_switchboard_feed(&feed, &unset, &queue, &unset).is_err());
+    }
+
+    #[test]
+    fn audit_rejects_open_escrows() {
+        assert!(expected_accounting(&settled_escrow(EscrowStatus::Active, None)).is_err());
+        assert!(expected_accounting(&settled_escrow(EscrowStatus::Disputed, None)).is_err());
+    }
+
+    fn work_agreement(min_quality_score: u8) -> WorkAgreement {
+        WorkAgreement {
+            escrow: Pubkey::new_unique(),
+            query: "btc ohlcv 2024".to_string(),
+            required_fields: 0b111,
+            min_records: 100,
+            max_age_days: 7,
+            min_quality_score,
+            created_at: 1_700_000_000,
+            bump: 255,
+            refund_curve: None,
+            score_weights: [0; SCORE_DIMENSIONS],
+        }
+    }
+
+    #[test]
+    fn resolution_message_appends_work_agreement_terms() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_1".to_string();
+        let content_hash = [0xab; 32];
+        let escrow_key = Pubkey::new_unique();
+        let agreement = work_agreement(80);
+        assert_eq!(
+            build_resolution_message(&escrow_key, &escrow, 0, Some(&agreement), 42, &content_hash, None),
+            format!("tx_1:{}:{}:0:0:42:{}:100:7:80", escrow_key, escrow.amount, "ab".repeat(32))
+        );
+    }
+
+    #[test]
+    fn weighted_agreements_aggregate_signed_sub_scores() {
+        let mut agreement = work_agreement(80);
+        assert_eq!(resolution_quality(Some(&agreement), 42, None).unwrap(), 42);
+        assert!(resolution_quality(Some(&agreement), 0, Some(&[90; SCORE_DIMENSIONS])).is_err());
+
+        // Latency, accuracy, completeness; the fourth dimension unused
+        agreement.score_weights = [20, 50, 30, 0];
+        let sub_scores = [100, 70, 51, 0];
+        assert!(resolution_quality(Some(&agreement), 42, None).is_err());
+        assert_eq!(resolution_quality(Some(&agreement), 0, Some(&sub_scores)).unwrap(), 70);
+        assert!(agreement.aggregate_score(&[101, 0, 0, 0]).is_err());
+
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.transaction_id = "tx_1".to_string();
+        let escrow_key = Pubkey::new_unique();
+        let content_hash = [0xab; 32];
+        assert_eq!(
+            build_resolution_message(
+                &escrow_key,
+                &escrow,
+                0,
+                Some(&agreement),
+                70,
+                &content_hash,
+                Some(&sub_scores),
+            ),
+            format!(
+                "tx_1:{}:{}:0:0:70:{}:100:7:80:100:70:51:0",
+                escrow_key,
+                escrow.amount,
+                "ab".repeat(32)
+            )
+        );
+    }
 
-    #[account(mut)]
-    pub agent: SystemAccount<'info>,
+    fn default_config() -> GlobalConfig {
+        let zeroed = vec![0u8; GlobalConfig::INIT_SPACE];
+        let mut config: GlobalConfig = AnchorDeserialize::deserialize(&mut zeroed.as_slice()).unwrap();
+        init_config_defaults(&mut config, Pubkey::new_unique(), DEFAULT_NAMESPACE);
+        config
+    }
 
-    /// CHECK: API wallet address
-    #[account(mut)]
-    pub api: AccountInfo<'info>,
+    #[test]
+    fn work_agreement_refunds_in_full_below_agreed_quality() {
+        let config = default_config();
+        let mut escrow = Escrow::from(legacy_escrow());
+        let agreement = work_agreement(80);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, None, 79).unwrap(), 25);
 
-    /// CHECK: Verifier oracle public key
-    pub verifier: AccountInfo<'info>,
+        escrow.set_has_work_agreement(true);
+        assert!(agreed_refund_percentage(&config, &escrow, None, 90).is_err());
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 90).unwrap(), 0);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 79).unwrap(), 100);
 
-    /// CHECK: Instructions sysvar for Ed25519 signature verification
-    #[account(address = INSTRUCTIONS_ID)]
-    pub instructions_sysvar: AccountInfo<'info>,
+        // Arbitrators cannot rule below a full refund either
+        escrow.quality_score = Some(79);
+        assert!(agreed_ruling(&escrow, None, 90).is_err());
+        assert_eq!(agreed_ruling(&escrow, Some(&agreement), 90).unwrap(), 100);
+        escrow.quality_score = Some(80);
+        assert_eq!(agreed_ruling(&escrow, Some(&agreement), 90).unwrap(), 90);
+    }
 
-    #[account(
-        mut,
-        seeds = [b"reputation", agent.key().as_ref()],
-        bump = agent_reputation.bump
-    )]
-    pub agent_reputation: Account<'info, EntityReputation>,
+    #[test]
+    fn work_agreement_curve_sets_the_split() {
+        let config = default_config();
+        let step = |max_quality_score, refund_percentage| RefundStep { max_quality_score, refund_percentage };
+        let mut agreement = work_agreement(40);
+        agreement.refund_curve = Some([step(59, 80), step(69, 60), step(89, 20), step(99, 5), step(100, 0)]);
+        validate_refund_curve(&agreement.refund_curve.unwrap()).unwrap();
 
-    #[account(
-        mut,
-        seeds = [b"reputation", api.key().as_ref()],
-        bump = api_reputation.bump
-    )]
-    pub api_reputation: Account<'info, EntityReputation>,
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.set_has_work_agreement(true);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 75).unwrap(), 20);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 95).unwrap(), 5);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 50).unwrap(), 80);
+        // The agreed minimum still overrides the curve
+        assert_eq!(agreed_refund_percentage(&config, &escrow, Some(&agreement), 39).unwrap(), 100);
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn escrows_on_provider_terms_resolve_on_their_curve() {
+        let config = default_config();
+        let mut curve = DEFAULT_REFUND_CURVE;
+        curve[0].refund_percentage = 80;
+        curve[1].refund_percentage = 80;
+        let terms = ProviderTerms {
+            api: Pubkey::new_unique(),
+            namespace: DEFAULT_NAMESPACE,
+            terms_id: 0,
+            min_amount: MIN_ESCROW_AMOUNT,
+            max_amount: 10 * MIN_ESCROW_AMOUNT,
+            time_lock: MIN_TIME_LOCK,
+            dispute_window: MIN_TIME_LOCK,
+            refund_curve: curve,
+            published_at: 0,
+            bump: 255,
+        };
+        assert!(terms.admits(MIN_ESCROW_AMOUNT, MIN_TIME_LOCK, &DEFAULT_NAMESPACE));
+        assert!(!terms.admits(11 * MIN_ESCROW_AMOUNT, MIN_TIME_LOCK, &DEFAULT_NAMESPACE));
+        assert!(!terms.admits(MIN_ESCROW_AMOUNT, MAX_TIME_LOCK, &DEFAULT_NAMESPACE));
+        assert!(!terms.admits(MIN_ESCROW_AMOUNT, MIN_TIME_LOCK, &Pubkey::new_unique()));
 
-#[derive(Accounts)]
-pub struct ResolveDisputeSwitchboard<'info> {
-    #[account(
-        mut,
-        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
-        bump = escrow.bump
-    )]
-    pub escrow: Account<'info, Escrow>,
+        let mut escrow = Escrow::from(legacy_escrow());
+        assert_eq!(escrow.refund_percentage_for(&config, 10), 100);
+        escrow.refund_curve = Some(terms.refund_curve);
+        assert_eq!(escrow.refund_percentage_for(&config, 10), 80);
+        assert_eq!(agreed_refund_percentage(&config, &escrow, None, 10).unwrap(), 80);
+    }
 
-    #[account(mut)]
-    pub agent: SystemAccount<'info>,
+    #[test]
+    fn refund_curve_maps_quality_to_refund() {
+        let mut config = default_config();
+        let refunds: Vec<u8> = [0, 20, 21, 40, 55, 80, 81, 100]
+            .iter()
+            .map(|&score| config.refund_percentage_for(score))
+            .collect();
+        assert_eq!(refunds, vec![100, 100, 75, 75, 50, 25, 0, 0]);
 
-    /// CHECK: API wallet address
-    #[account(mut)]
-    pub api: AccountInfo<'info>,
+        let mut curve = DEFAULT_REFUND_CURVE;
+        assert!(validate_refund_curve(&curve).is_ok());
+        curve[1].refund_percentage = 100;
+        curve[2].max_quality_score = 50;
+        assert!(validate_refund_curve(&curve).is_ok());
+        config.refund_curve = curve;
+        assert_eq!(config.refund_percentage_for(45), 50);
 
-    /// Switchboard Function pull feed containing quality score
-    /// CHECK: Validated via PullFeedAccountData::parse
-    pub switchboard_function: AccountInfo<'info>,
+        // Refunds may not rise with quality, and every score needs a step
+        curve[3].refund_percentage = 60;
+        assert!(validate_refund_curve(&curve).is_err());
+        curve[3].refund_percentage = 25;
+        curve[4].max_quality_score = 90;
+        assert!(validate_refund_curve(&curve).is_err());
+        curve[4].max_quality_score = 100;
+        curve[2].max_quality_score = curve[1].max_quality_score;
+        assert!(validate_refund_curve(&curve).is_err());
+    }
 
-    #[account(
-        mut,
-        seeds = [b"reputation", agent.key().as_ref()],
-        bump = agent_reputation.bump
-    )]
-    pub agent_reputation: Account<'info, EntityReputation>,
+    #[test]
+    fn escrow_is_closable_after_cooldown_from_settlement() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.set_status(EscrowStatus::Disputed);
+        assert_eq!(escrow.closable_at(), None);
 
-    #[account(
-        mut,
-        seeds = [b"reputation", api.key().as_ref()],
-        bump = api_reputation.bump
-    )]
-    pub api_reputation: Account<'info, EntityReputation>,
+        escrow.set_status(EscrowStatus::Resolved);
+        assert_eq!(escrow.closable_at(), Some(escrow.expires_at + ESCROW_CLOSE_COOLDOWN));
 
-    pub system_program: Program<'info, System>,
-}
+        escrow.settled_at = Some(1_700_000_100);
+        assert_eq!(escrow.closable_at(), Some(1_700_000_100 + ESCROW_CLOSE_COOLDOWN));
+    }
 
-#[derive(Accounts)]
-pub struct MarkDisputed<'info> {
-    #[account(
-        mut,
-        seeds = [b"escrow", escrow.transaction_id.as_bytes()],
-        bump = escrow.bump
-    )]
-    pub escrow: Account<'info, Escrow>,
+    #[test]
+    fn release_and_close_waits_out_the_close_cooldown() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.set_status(EscrowStatus::Active);
+        escrow.dispute_window = 3_600;
+        assert_eq!(escrow.sweepable_at(), escrow.expires_at + ESCROW_CLOSE_COOLDOWN);
 
-    #[account(
-        mut,
-        seeds = [b"reputation", agent.key().as_ref()],
-        bump = reputation.bump
-    )]
-    pub reputation: Account<'info, EntityReputation>,
+        // Released by hand at the earliest moment, then closed after the
+        // cooldown: the combined crank never closes sooner
+        escrow.dispute_window = 2 * 86_400;
+        let releasable_at = escrow.dispute_deadline();
+        assert!(releasable_at > escrow.expires_at);
+        assert_eq!(escrow.sweepable_at(), releasable_at + ESCROW_CLOSE_COOLDOWN);
+        escrow.set_status(EscrowStatus::Released);
+        escrow.settled_at = Some(releasable_at);
+        assert_eq!(escrow.closable_at(), Some(escrow.sweepable_at()));
+    }
 
-    #[account(mut)]
-    pub agent: Signer<'info>,
-}
+    fn verifier_set(size: usize, threshold: u8) -> VerifierSet {
+        VerifierSet {
+            namespace: DEFAULT_NAMESPACE,
+            verifiers: (0..size).map(|_| Pubkey::new_unique()).collect(),
+            threshold,
+            bump: 255,
+        }
+    }
 
-#[derive(Accounts)]
-pub struct InitReputation<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + EntityReputation::INIT_SPACE,
-        seeds = [b"reputation", entity.key().as_ref()],
-        bump
-    )]
-    pub reputation: Account<'info, EntityReputation>,
+    fn quorum_signature(verifier_index: u8) -> QuorumSignature {
+        QuorumSignature {
+            verifier_index,
+            signature: [0; 64],
+        }
+    }
 
-    /// CHECK: Entity being tracked
-    pub entity: AccountInfo<'info>,
+    #[test]
+    fn verifier_set_needs_distinct_members_and_reachable_threshold() {
+        let set = verifier_set(3, 2);
+        assert!(validate_verifier_set(&set.verifiers, 2).is_ok());
+        assert!(validate_verifier_set(&set.verifiers, 0).is_err());
+        assert!(validate_verifier_set(&set.verifiers, 4).is_err());
+        assert!(validate_verifier_set(&[], 1).is_err());
+        assert!(validate_verifier_set(&verifier_set(MAX_VERIFIER_SET_SIZE + 1, 1).verifiers, 1).is_err());
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        let duplicated = vec![set.verifiers[0], set.verifiers[1], set.verifiers[0]];
+        assert!(validate_verifier_set(&duplicated, 2).is_err());
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn quorum_counts_distinct_members_in_signature_order() {
+        let set = verifier_set(3, 2);
+        let signers = quorum_signers(&set, &[quorum_signature(2), quorum_signature(0)]).unwrap();
+        assert_eq!(signers, vec![set.verifiers[2], set.verifiers[0]]);
 
-#[derive(Accounts)]
-pub struct UpdateReputation<'info> {
-    #[account(
-        mut,
-        seeds = [b"reputation", reputation.entity.as_ref()],
-        bump = reputation.bump
-    )]
-    pub reputation: Account<'info, EntityReputation>,
+        assert!(quorum_signers(&set, &[quorum_signature(1)]).is_err());
+        assert!(quorum_signers(&set, &[quorum_signature(1), quorum_signature(1)]).is_err());
+        assert!(quorum_signers(&set, &[quorum_signature(0), quorum_signature(3)]).is_err());
+    }
 
-    /// Authority that can update reputation (restricted)
-    pub authority: Signer<'info>,
-}
+    fn provider_penalties() -> ProviderPenalties {
+        ProviderPenalties {
+            provider: Pubkey::new_unique(),
+            strike_count: 0,
+            suspended: false,
+            suspension_end: None,
+            total_refunds_issued: 0,
+            poor_quality_count: 0,
+            created_at: 0,
+            last_updated: 0,
+            bump: 255,
+        }
+    }
 
-#[derive(Accounts)]
-pub struct CheckRateLimit<'info> {
-    #[account(
-        mut,
-        seeds = [b"rate_limit", entity.key().as_ref()],
-        bump = rate_limiter.bump
-    )]
-    pub rate_limiter: Account<'info, RateLimiter>,
+    #[test]
+    fn suspension_claims_cover_escrows_resolved_around_the_suspension() {
+        let mut penalties = provider_penalties();
+        for now in [100, 200, 300] {
+            penalties.record_resolution(0, 0, now);
+        }
+        assert_eq!(penalties.suspended_at(), Some(300));
 
-    pub entity: Signer<'info>,
-}
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.amount = 1_000_000;
+        escrow.created_at = 250;
+        escrow.set_status(EscrowStatus::Resolved);
+        escrow.settled_at = Some(300);
+        escrow.refunded_to_agent = 800_000;
+        assert_eq!(suspension_claim_payout(&escrow, &penalties, 5_000).unwrap(), 200_000);
+        escrow.refunded_to_agent = 0;
+        assert_eq!(suspension_claim_payout(&escrow, &penalties, 5_000).unwrap(), 500_000);
 
-// ============================================================================
-// State
-// ============================================================================
+        // Fully refunded, or resolved before the suspension began
+        escrow.refunded_to_agent = 1_000_000;
+        assert!(suspension_claim_payout(&escrow, &penalties, 5_000).is_err());
+        escrow.refunded_to_agent = 0;
+        escrow.settled_at = Some(299);
+        assert!(suspension_claim_payout(&escrow, &penalties, 5_000).is_err());
 
-#[account]
-#[derive(InitSpace)]
-pub struct Escrow {
-    pub agent: Pubkey,                    // 32
-    pub api: Pubkey,                      // 32
-    pub amount: u64,                      // 8
-    pub status: EscrowStatus,             // 1 + 1
-    pub created_at: i64,                  // 8
-    pub expires_at: i64,                  // 8
-    #[max_len(64)]
-    pub transaction_id: String,           // 4 + 64
-    pub bump: u8,                         // 1
-    pub quality_score: Option<u8>,        // 1 + 1
-    pub refund_percentage: Option<u8>,    // 1 + 1
-}
+        // Created after the provider was already suspended
+        escrow.settled_at = Some(400);
+        escrow.created_at = 301;
+        assert!(suspension_claim_payout(&escrow, &penalties, 5_000).is_err());
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
-pub enum EscrowStatus {
-    Active,      // Payment locked, awaiting resolution
-    Released,    // Funds released to API (happy path)
-    Disputed,    // Agent disputed quality
-    Resolved,    // Dispute resolved with refund split
-}
+        escrow.created_at = 250;
+        penalties.suspended = false;
+        assert!(suspension_claim_payout(&escrow, &penalties, 5_000).is_err());
+    }
 
-/// Entity Reputation - tracks agent/provider performance on-chain
-#[account]
-#[derive(InitSpace)]
-pub struct EntityReputation {
-    pub entity: Pubkey,                   // 32
-    pub entity_type: EntityType,          // 1 + 1
-    pub total_transactions: u64,          // 8
-    pub disputes_filed: u64,              // 8
-    pub disputes_won: u64,                // 8 - Quality <50
-    pub disputes_partial: u64,            // 8 - Quality 50-79
-    pub disputes_lost: u64,               // 8 - Quality >=80
-    pub average_quality_received: u8,     // 1
-    pub reputation_score: u16,            // 2 - 0-1000 score
-    pub created_at: i64,                  // 8
-    pub last_updated: i64,                // 8
-    pub bump: u8,                         // 1
-}
+    #[test]
+    fn third_poor_resolution_suspends_provider() {
+        let mut penalties = provider_penalties();
+        assert!(!penalties.record_resolution(80, 0, 10));
+        assert!(!penalties.record_resolution(10, 500, 20));
+        assert!(!penalties.record_resolution(29, 500, 30));
+        assert_eq!(penalties.strike_count, 2);
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
-pub enum EntityType {
-    Agent,
-    Provider,
-}
+        assert!(penalties.record_resolution(0, 1_000, 40));
+        assert!(penalties.suspended);
+        assert_eq!(penalties.suspension_end, Some(40 + PROVIDER_SUSPENSION_DURATION));
+        assert_eq!(penalties.poor_quality_count, 3);
+        assert_eq!(penalties.total_refunds_issued, 2_000);
 
-/// Rate Limiter - prevents spam and abuse
-#[account]
-#[derive(InitSpace)]
-pub struct RateLimiter {
-    pub entity: Pubkey,                   // 32
-    pub verification_level: VerificationLevel, // 1 + 1
-    pub transactions_last_hour: u16,      // 2
-    pub transactions_last_day: u16,       // 2
-    pub disputes_last_day: u16,           // 2
-    pub last_hour_check: i64,             // 8
-    pub last_day_check: i64,              // 8
-    pub bump: u8,                         // 1
-}
+        // Further strikes do not push the end date out
+        assert!(!penalties.record_resolution(0, 0, 50));
+        assert_eq!(penalties.suspension_end, Some(40 + PROVIDER_SUSPENSION_DURATION));
+    }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
-pub enum VerificationLevel {
-    Basic,       // Just wallet (low limits)
-    Staked,      // 1+ SOL staked (medium limits)
-    Social,      // Twitter/GitHub linked (high limits)
-    KYC,         // Identity verified (unlimited)
-}
+    #[test]
+    fn verification_levels_only_rise_and_staked_is_self_serve() {
+        use VerificationLevel::*;
+        assert!(verification_upgrade_allowed(Basic, Staked, false));
+        assert!(!verification_upgrade_allowed(Basic, Social, false));
+        assert!(!verification_upgrade_allowed(Staked, KYC, false));
+        assert!(verification_upgrade_allowed(Staked, KYC, true));
+        assert!(!verification_upgrade_allowed(Social, Staked, true));
+        assert!(!verification_upgrade_allowed(Staked, Staked, true));
+    }
 
-/// Work Agreement - structured scope definition
-#[account]
-#[derive(InitSpace)]
-pub struct WorkAgreement {
-    pub escrow: Pubkey,                   // 32
-    #[max_len(128)]
-    pub query: String,                    // 4 + 128
-    pub required_fields: u8,              // 1 - bitmask or count
-    pub min_records: u32,                 // 4
-    pub max_age_days: u32,                // 4
-    pub min_quality_score: u8,            // 1
-    pub created_at: i64,                  // 8
-    pub bump: u8,                         // 1
-}
+    fn rate_limiter(verification_level: VerificationLevel) -> RateLimiter {
+        RateLimiter {
+            entity: Pubkey::new_unique(),
+            verification_level,
+            transactions_last_hour: 0,
+            transactions_last_day: 0,
+            disputes_last_day: 0,
+            last_hour_check: 0,
+            last_day_check: 0,
+            rate_limit_overridden_until: None,
+            staked_lamports: 0,
+            bump: 255,
+        }
+    }
 
-/// Provider Penalties - track strikes and suspensions
-#[account]
-#[derive(InitSpace)]
-pub struct ProviderPenalties {
-    pub provider: Pubkey,                 // 32
-    pub strike_count: u8,                 // 1
-    pub suspended: bool,                  // 1
-    pub suspension_end: Option<i64>,      // 1 + 8
-    pub total_refunds_issued: u64,        // 8
-    pub poor_quality_count: u32,          // 4 - Quality <30
-    pub created_at: i64,                  // 8
-    pub last_updated: i64,                // 8
-    pub bump: u8,                         // 1
-}
+    #[test]
+    fn rate_limit_counts_transactions_and_disputes_separately() {
+        let mut limiter = rate_limiter(VerificationLevel::Basic);
+        let now = 86_400 * 100;
 
-// ============================================================================
-// Errors
-// ============================================================================
+        // Basic allows one transaction an hour and three disputes a day
+        assert!(consume_rate_limit(&mut limiter, now, RateLimitedAction::Transaction).is_ok());
+        assert!(consume_rate_limit(&mut limiter, now, RateLimitedAction::Transaction).is_err());
+        for _ in 0..3 {
+            assert!(consume_rate_limit(&mut limiter, now, RateLimitedAction::Dispute).is_ok());
+        }
+        assert!(consume_rate_limit(&mut limiter, now, RateLimitedAction::Dispute).is_err());
 
-#[error_code]
-pub enum EscrowError {
-    #[msg("Invalid escrow status for this operation")]
-    InvalidStatus,
+        // Windows roll over with the clock
+        assert!(consume_rate_limit(&mut limiter, now + 3_600, RateLimitedAction::Transaction).is_ok());
+        assert!(consume_rate_limit(&mut limiter, now + 86_400, RateLimitedAction::Dispute).is_ok());
+        assert_eq!(limiter.disputes_last_day, 1);
+    }
 
-    #[msg("Unauthorized: Only agent or expired escrow can release")]
-    Unauthorized,
+    #[test]
+    fn attestations_age_out_at_the_namespace_window() {
+        let mut config = default_config();
+        assert_eq!(config.attestation_max_age_seconds, DEFAULT_ATTESTATION_MAX_AGE);
+        assert!(require_fresh_attestation(0, &config).is_ok());
+        assert!(require_fresh_attestation(300, &config).is_ok());
+        assert!(require_fresh_attestation(301, &config).is_err());
+        assert!(require_fresh_attestation(-1, &config).is_err());
 
-    #[msg("Invalid quality score (must be 0-100)")]
-    InvalidQualityScore,
+        // A high-latency namespace accepts what the default would not
+        config.attestation_max_age_seconds = MAX_ATTESTATION_MAX_AGE;
+        assert!(require_fresh_attestation(301, &config).is_ok());
+        assert!(require_fresh_attestation(MAX_ATTESTATION_MAX_AGE + 1, &config).is_err());
+    }
 
-    #[msg("Invalid refund percentage (must be 0-100)")]
-    InvalidRefundPercentage,
+    #[test]
+    fn payouts_keep_the_escrow_rent_exempt() {
+        let rent = Rent::default();
+        let min_balance = rent.minimum_balance(8 + Escrow::INIT_SPACE);
+        let amount = 10_000_000;
 
-    #[msg("Invalid verifier signature")]
-    InvalidSignature,
+        assert!(require_balance_after(min_balance + amount, amount, min_balance).is_ok());
+        assert!(require_balance_after(min_balance + amount - 1, amount, min_balance).is_err());
+        assert!(require_balance_after(amount - 1, amount, min_balance).is_err());
 
-    #[msg("Invalid time lock: must be between 1 hour and 30 days")]
-    InvalidTimeLock,
+        // Funding rent without the discriminator leaves the payout a deficit
+        let short = rent.minimum_balance(Escrow::INIT_SPACE) + amount;
+        assert!(short < min_balance + amount);
+        assert!(require_balance_after(short, amount, min_balance).is_err());
+    }
 
-    #[msg("Invalid amount: must be greater than 0")]
-    InvalidAmount,
+    #[test]
+    fn rate_limit_override_covers_disputes() {
+        let mut limiter = rate_limiter(VerificationLevel::Basic);
+        let now = 86_400 * 100;
+        limiter.rate_limit_overridden_until = Some(now + 3_600);
 
-    #[msg("Invalid transaction ID: must be non-empty and max 64 chars")]
-    InvalidTransactionId,
+        // Past the daily dispute limit while the override lasts
+        for _ in 0..10 {
+            assert!(consume_rate_limit(&mut limiter, now, RateLimitedAction::Dispute).is_ok());
+        }
+        assert_eq!(limiter.disputes_last_day, 10);
 
-    #[msg("Time lock not expired: cannot release funds yet")]
-    TimeLockNotExpired,
+        // Once it lapses the day's disputes count again, and it is cleared
+        assert!(consume_rate_limit(&mut limiter, now + 3_600, RateLimitedAction::Dispute).is_err());
+        assert!(limiter.rate_limit_overridden_until.is_none());
+    }
 
-    #[msg("Dispute window expired: cannot dispute after time lock")]
-    DisputeWindowExpired,
+    #[test]
+    fn dispute_window_restarts_at_delivery() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        escrow.dispute_window = DISPUTE_WINDOW;
+        assert_eq!(escrow.dispute_deadline(), escrow.created_at + DISPUTE_WINDOW);
 
-    #[msg("Amount too large: exceeds maximum escrow amount")]
-    AmountTooLarge,
+        // Delivery after expiry still leaves the full window to dispute
+        let delivered_at = escrow.expires_at + 60;
+        escrow.delivered_at = Some(delivered_at);
+        assert_eq!(escrow.dispute_deadline(), delivered_at + DISPUTE_WINDOW);
+    }
 
-    #[msg("Insufficient funds to pay dispute cost")]
-    InsufficientDisputeFunds,
+    #[test]
+    fn resolution_is_held_only_until_the_dispute_settles() {
+        let mut escrow = Escrow::from(legacy_escrow());
+        assert_eq!(escrow.status(), EscrowStatus::Disputed);
+        assert!(!escrow.resolution_held());
 
-    #[msg("Rate limit exceeded: too many transactions")]
-    RateLimitExceeded,
+        escrow.appeal_deadline = Some(escrow.expires_at);
+        escrow.first_verifier = Some(Pubkey::new_unique());
+        assert!(escrow.resolution_held());
 
-    #[msg("Provider is suspended")]
-    ProviderSuspended,
+        escrow.set_appealed(true);
+        assert!(escrow.resolution_held());
 
-    #[msg("Reputation score too low for this operation")]
-    ReputationTooLow,
+        escrow.set_status(EscrowStatus::Resolved);
+        assert!(!escrow.resolution_held());
+    }
 
-    #[msg("Arithmetic overflow in calculation")]
-    ArithmeticOverflow,
+    #[test]
+    fn milestone_amounts_must_cover_the_escrow() {
+        assert!(validate_milestone_amounts(&[3, 7], 10).is_ok());
+        assert!(validate_milestone_amounts(&[3, 6], 10).is_err());
+        assert!(validate_milestone_amounts(&[0, 10], 10).is_err());
+        assert!(validate_milestone_amounts(&[], 0).is_err());
+        assert!(validate_milestone_amounts(&[1; MAX_MILESTONES + 1], MAX_MILESTONES as u64 + 1).is_err());
+        assert!(validate_milestone_amounts(&[u64::MAX, 1], 0).is_err());
+    }
 
-    #[msg("Insufficient rent reserve in escrow account")]
-    InsufficientRentReserve,
+    #[test]
+    fn milestone_plan_settles_once_nothing_is_open() {
+        let mut plan = MilestonePlan {
+            escrow: Pubkey::new_unique(),
+            milestones: vec![
+                Milestone {
+                    amount: 4,
+                    status: MilestoneStatus::Pending,
+                    refund_percentage: None,
+                };
+                2
+            ],
+            bump: 255,
+        };
+        assert!(plan.milestone_mut(2, MilestoneStatus::Pending).is_err());
+        assert!(plan.milestone_mut(0, MilestoneStatus::Disputed).is_err());
 
-    #[msg("Invalid Switchboard attestation")]
-    InvalidSwitchboardAttestation,
+        plan.milestone_mut(0, MilestoneStatus::Pending).unwrap().status = MilestoneStatus::Released;
+        assert_eq!(plan.settled_status(), None);
+        plan.milestone_mut(1, MilestoneStatus::Pending).unwrap().status = MilestoneStatus::Released;
+        assert_eq!(plan.settled_status(), Some(EscrowStatus::Released));
 
-    #[msg("Switchboard attestation is stale (older than 60 seconds)")]
-    StaleAttestation,
+        plan.milestones[1].status = MilestoneStatus::Disputed;
+        assert_eq!(plan.settled_status(), None);
+        plan.milestones[1].status = MilestoneStatus::Resolved;
+        assert_eq!(plan.settled_status(), Some(EscrowStatus::Resolved));
+    }
 
-    #[msg("Quality score mismatch between Switchboard and submitted value")]
-    QualityScoreMismatch,
+    #[test]
+    fn dispute_bond_scales_with_the_escrowed_amount() {
+        let mut reputation = EntityReputation::from(legacy_reputation(0, 0));
+        let sol = 1_000_000_000;
+
+        // Small escrows and token escrows pay the flat cost
+        assert_eq!(calculate_dispute_cost(&reputation, 10_000_000, 100), BASE_DISPUTE_COST);
+        assert_eq!(calculate_dispute_cost(&reputation, 0, 100), BASE_DISPUTE_COST);
+        assert_eq!(calculate_dispute_cost(&reputation, 5 * sol, 100), 50_000_000);
+        assert_eq!(calculate_dispute_cost(&reputation, 5 * sol, 0), BASE_DISPUTE_COST);
+
+        // Frequent disputers pay a multiple of the proportional bond
+        reputation.total_transactions = 10;
+        reputation.disputes_filed = 5;
+        assert_eq!(calculate_dispute_cost(&reputation, 5 * sol, 100), 250_000_000);
+        assert_eq!(
+            calculate_dispute_cost(&reputation, MAX_ESCROW_AMOUNT, MAX_DISPUTE_BOND_BPS),
+            MAX_ESCROW_AMOUNT
+        );
+    }
+
+    #[test]
+    fn dispute_bond_returns_only_when_the_agent_wins() {
+        assert!(dispute_bond_returned(100));
+        assert!(dispute_bond_returned(DISPUTE_BOND_REFUND_THRESHOLD));
+        assert!(!dispute_bond_returned(DISPUTE_BOND_REFUND_THRESHOLD - 1));
+        assert!(!dispute_bond_returned(0));
+    }
+
+    #[test]
+    fn only_registered_keys_may_verify() {
+        let approved = Pubkey::new_unique();
+        let registry = VerifierRegistry {
+            namespace: DEFAULT_NAMESPACE,
+            verifiers: vec![approved],
+            bump: 255,
+        };
+        assert!(require_registered_verifier(&registry, &approved).is_ok());
+        assert!(require_registered_verifier(&registry, &Pubkey::new_unique()).is_err());
+    }
 }
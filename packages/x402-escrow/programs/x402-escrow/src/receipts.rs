@@ -0,0 +1,144 @@
+//! Compressed NFT receipts for settled escrows
+//!
+//! A receipt is a Bubblegum leaf minted to the agent. Its URI encodes the
+//! outcome as
+//! `x402:receipt:{transaction_id}:{quality_score}:{refund_percentage}:{paid_to_api}:{refunded_to_agent}`,
+//! with an empty quality score for escrows that were never assessed. The
+//! program's receipt authority PDA is the tree delegate and the receipt's
+//! only verified creator, so a receipt cannot be forged outside this program.
+//!
+//! Bubblegum is invoked directly rather than through `mpl-bubblegum`, whose
+//! releases are built against Solana 1.x; only `mint_v1` is needed.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke_signed};
+
+use crate::Escrow;
+
+pub const BUBBLEGUM_PROGRAM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+pub const SPL_NOOP_PROGRAM_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+pub const SPL_ACCOUNT_COMPRESSION_PROGRAM_ID: Pubkey =
+    pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+
+const MINT_V1_DISCRIMINATOR: [u8; 8] = [145, 98, 192, 118, 184, 147, 118, 104];
+const RECEIPT_NAME: &str = "x402 Receipt";
+const RECEIPT_SYMBOL: &str = "X402";
+
+/// Accounts Bubblegum's `mint_v1` takes, in its order
+pub struct ReceiptAccounts<'a, 'info> {
+    pub tree_config: &'a AccountInfo<'info>,
+    /// The escrow's agent, who owns the receipt
+    pub leaf_owner: &'a AccountInfo<'info>,
+    pub merkle_tree: &'a AccountInfo<'info>,
+    pub payer: &'a AccountInfo<'info>,
+    /// Tree delegate; signs with `authority_seeds`
+    pub receipt_authority: &'a AccountInfo<'info>,
+    pub log_wrapper: &'a AccountInfo<'info>,
+    pub compression_program: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub bubblegum_program: &'a AccountInfo<'info>,
+}
+
+/// URI recording a settled escrow's outcome
+pub fn receipt_uri(escrow: &Escrow) -> String {
+    format!(
+        "x402:receipt:{}:{}:{}:{}:{}",
+        escrow.transaction_id,
+        escrow.quality_score.map_or(String::new(), |score| score.to_string()),
+        escrow.refund_percentage.unwrap_or(0),
+        escrow.paid_to_api,
+        escrow.refunded_to_agent,
+    )
+}
+
+/// `mint_v1` instruction data: the discriminator then Bubblegum's `MetadataArgs`
+fn mint_v1_data(uri: &str, creator: &Pubkey) -> Result<Vec<u8>> {
+    let mut data = MINT_V1_DISCRIMINATOR.to_vec();
+    RECEIPT_NAME.serialize(&mut data)?;
+    RECEIPT_SYMBOL.serialize(&mut data)?;
+    uri.serialize(&mut data)?;
+    0u16.serialize(&mut data)?; // seller_fee_basis_points
+    false.serialize(&mut data)?; // primary_sale_happened
+    false.serialize(&mut data)?; // is_mutable
+    data.extend_from_slice(&[
+        0, // edition_nonce: None
+        1, 0, // token_standard: Some(NonFungible)
+        0, // collection: None
+        0, // uses: None
+        0, // token_program_version: Original
+    ]);
+    // creators: the receipt authority, verified by its signature, with the whole share
+    1u32.serialize(&mut data)?;
+    creator.serialize(&mut data)?;
+    true.serialize(&mut data)?;
+    100u8.serialize(&mut data)?;
+    Ok(data)
+}
+
+/// Mint the escrow's receipt to its agent
+pub fn mint_receipt(
+    accounts: &ReceiptAccounts,
+    escrow: &Escrow,
+    authority_seeds: &[&[u8]],
+) -> Result<()> {
+    let authority = accounts.receipt_authority.key();
+    let instruction = Instruction {
+        program_id: BUBBLEGUM_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(accounts.tree_config.key(), false),
+            AccountMeta::new_readonly(accounts.leaf_owner.key(), false),
+            // The agent is also the leaf delegate
+            AccountMeta::new_readonly(accounts.leaf_owner.key(), false),
+            AccountMeta::new(accounts.merkle_tree.key(), false),
+            AccountMeta::new_readonly(accounts.payer.key(), true),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new_readonly(accounts.log_wrapper.key(), false),
+            AccountMeta::new_readonly(accounts.compression_program.key(), false),
+            AccountMeta::new_readonly(accounts.system_program.key(), false),
+        ],
+        data: mint_v1_data(&receipt_uri(escrow), &authority)?,
+    };
+
+    invoke_signed(
+        &instruction,
+        &[
+            accounts.tree_config.clone(),
+            accounts.leaf_owner.clone(),
+            accounts.merkle_tree.clone(),
+            accounts.payer.clone(),
+            accounts.receipt_authority.clone(),
+            accounts.log_wrapper.clone(),
+            accounts.compression_program.clone(),
+            accounts.system_program.clone(),
+            accounts.bubblegum_program.clone(),
+        ],
+        &[authority_seeds],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mint_data_follows_bubblegum_metadata_args() {
+        let creator = Pubkey::new_unique();
+        let uri = "x402:receipt:tx:80:20:800:200";
+        let data = mint_v1_data(uri, &creator).unwrap();
+
+        assert_eq!(data[..8], MINT_V1_DISCRIMINATOR);
+        let strings = 4 + RECEIPT_NAME.len() + 4 + RECEIPT_SYMBOL.len() + 4 + uri.len();
+        assert_eq!(data.len(), 8 + strings + 2 + 1 + 1 + 6 + 4 + 32 + 1 + 1);
+
+        let uri_at = 8 + 4 + RECEIPT_NAME.len() + 4 + RECEIPT_SYMBOL.len();
+        assert_eq!(data[uri_at..uri_at + 4], (uri.len() as u32).to_le_bytes());
+        assert_eq!(&data[uri_at + 4..uri_at + 4 + uri.len()], uri.as_bytes());
+
+        let creators = &data[data.len() - 38..];
+        assert_eq!(creators[..4], 1u32.to_le_bytes());
+        assert_eq!(creators[4..36], creator.to_bytes());
+        assert_eq!(creators[36..], [1, 100]);
+    }
+}
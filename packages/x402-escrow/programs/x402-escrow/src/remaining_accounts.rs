@@ -0,0 +1,259 @@
+//! Declarative validation for `remaining_accounts`
+//!
+//! Instructions that take a variable number of accounts consume them through
+//! `RemainingAccountsParser`, declaring what each slot must look like. Failures
+//! name the offending slot so clients can tell which account was wrong.
+
+use anchor_lang::prelude::*;
+
+use crate::EscrowError;
+
+/// What a single remaining account slot must satisfy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountExpectation {
+    pub owner: Option<Pubkey>,
+    pub key: Option<Pubkey>,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+impl AccountExpectation {
+    /// No requirements; refine with the builder methods
+    pub const fn any() -> Self {
+        Self {
+            owner: None,
+            key: None,
+            writable: false,
+            signer: false,
+        }
+    }
+
+    pub const fn owned_by(mut self, owner: Pubkey) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub const fn key(mut self, key: Pubkey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub const fn writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+
+    pub const fn signer(mut self) -> Self {
+        self.signer = true;
+        self
+    }
+}
+
+/// Why a slot was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotErrorKind {
+    Missing,
+    KeyMismatch,
+    OwnerMismatch,
+    NotWritable,
+    NotSigner,
+    Unconsumed,
+}
+
+/// A rejected slot and the position it was found at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemainingAccountError {
+    pub slot: usize,
+    pub kind: SlotErrorKind,
+}
+
+impl From<RemainingAccountError> for anchor_lang::error::Error {
+    fn from(err: RemainingAccountError) -> Self {
+        msg!("Remaining account {} rejected: {:?}", err.slot, err.kind);
+        let code = match err.kind {
+            SlotErrorKind::Missing => EscrowError::RemainingAccountMissing,
+            SlotErrorKind::KeyMismatch => EscrowError::RemainingAccountKeyMismatch,
+            SlotErrorKind::OwnerMismatch => EscrowError::RemainingAccountOwnerMismatch,
+            SlotErrorKind::NotWritable => EscrowError::RemainingAccountNotWritable,
+            SlotErrorKind::NotSigner => EscrowError::RemainingAccountNotSigner,
+            SlotErrorKind::Unconsumed => EscrowError::RemainingAccountUnconsumed,
+        };
+        error!(code)
+    }
+}
+
+/// Consumes remaining accounts in declared order, validating each slot
+pub struct RemainingAccountsParser<'a, 'info> {
+    accounts: &'a [AccountInfo<'info>],
+    cursor: usize,
+}
+
+impl<'a, 'info> RemainingAccountsParser<'a, 'info> {
+    pub fn new(accounts: &'a [AccountInfo<'info>]) -> Self {
+        Self { accounts, cursor: 0 }
+    }
+
+    /// Take the next account, checking it against `expectation`
+    pub fn next(
+        &mut self,
+        expectation: &AccountExpectation,
+    ) -> core::result::Result<&'a AccountInfo<'info>, RemainingAccountError> {
+        let slot = self.cursor;
+        let fail = |kind| RemainingAccountError { slot, kind };
+
+        let account = self
+            .accounts
+            .get(slot)
+            .ok_or(fail(SlotErrorKind::Missing))?;
+
+        if expectation.key.is_some_and(|key| key != *account.key) {
+            return Err(fail(SlotErrorKind::KeyMismatch));
+        }
+        if expectation.owner.is_some_and(|owner| owner != *account.owner) {
+            return Err(fail(SlotErrorKind::OwnerMismatch));
+        }
+        if expectation.writable && !account.is_writable {
+            return Err(fail(SlotErrorKind::NotWritable));
+        }
+        if expectation.signer && !account.is_signer {
+            return Err(fail(SlotErrorKind::NotSigner));
+        }
+
+        self.cursor += 1;
+        Ok(account)
+    }
+
+    /// Accounts not yet consumed
+    pub fn remaining(&self) -> usize {
+        self.accounts.len() - self.cursor
+    }
+
+    /// Reject trailing accounts the instruction did not ask for
+    pub fn finish(self) -> core::result::Result<(), RemainingAccountError> {
+        if self.cursor < self.accounts.len() {
+            return Err(RemainingAccountError {
+                slot: self.cursor,
+                kind: SlotErrorKind::Unconsumed,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        signer: bool,
+        writable: bool,
+    }
+
+    impl TestAccount {
+        fn new(owner: Pubkey, signer: bool, writable: bool) -> Self {
+            Self {
+                key: Pubkey::new_unique(),
+                owner,
+                lamports: 0,
+                data: Vec::new(),
+                signer,
+                writable,
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.signer,
+                self.writable,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn consumes_slots_in_order() {
+        let owner = Pubkey::new_unique();
+        let mut first = TestAccount::new(owner, false, true);
+        let mut second = TestAccount::new(Pubkey::new_unique(), true, false);
+        let (first_key, second_key) = (first.key, second.key);
+        let infos = [first.info(), second.info()];
+
+        let mut parser = RemainingAccountsParser::new(&infos);
+        let a = parser
+            .next(&AccountExpectation::any().owned_by(owner).writable())
+            .unwrap();
+        let b = parser
+            .next(&AccountExpectation::any().key(second_key).signer())
+            .unwrap();
+
+        assert_eq!(*a.key, first_key);
+        assert_eq!(*b.key, second_key);
+        assert_eq!(parser.remaining(), 0);
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn reports_offending_slot() {
+        let owner = Pubkey::new_unique();
+        let mut good = TestAccount::new(owner, false, true);
+        let mut readonly = TestAccount::new(owner, false, false);
+        let infos = [good.info(), readonly.info()];
+        let expectation = AccountExpectation::any().owned_by(owner).writable();
+
+        let mut parser = RemainingAccountsParser::new(&infos);
+        parser.next(&expectation).unwrap();
+        assert_eq!(
+            parser.next(&expectation).unwrap_err(),
+            RemainingAccountError { slot: 1, kind: SlotErrorKind::NotWritable }
+        );
+    }
+
+    #[test]
+    fn rejects_each_failed_expectation() {
+        let owner = Pubkey::new_unique();
+        let mut account = TestAccount::new(owner, false, false);
+        let infos = [account.info()];
+
+        let cases = [
+            (AccountExpectation::any().key(Pubkey::new_unique()), SlotErrorKind::KeyMismatch),
+            (AccountExpectation::any().owned_by(Pubkey::new_unique()), SlotErrorKind::OwnerMismatch),
+            (AccountExpectation::any().writable(), SlotErrorKind::NotWritable),
+            (AccountExpectation::any().signer(), SlotErrorKind::NotSigner),
+        ];
+        for (expectation, kind) in cases {
+            let mut parser = RemainingAccountsParser::new(&infos);
+            assert_eq!(
+                parser.next(&expectation).unwrap_err(),
+                RemainingAccountError { slot: 0, kind }
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_unconsumed_accounts() {
+        let mut account = TestAccount::new(Pubkey::new_unique(), false, false);
+        let infos = [account.info()];
+
+        let mut parser = RemainingAccountsParser::new(&infos);
+        parser.next(&AccountExpectation::any()).unwrap();
+        assert_eq!(
+            parser.next(&AccountExpectation::any()).unwrap_err(),
+            RemainingAccountError { slot: 1, kind: SlotErrorKind::Missing }
+        );
+
+        let parser = RemainingAccountsParser::new(&infos);
+        assert_eq!(
+            parser.finish().unwrap_err(),
+            RemainingAccountError { slot: 0, kind: SlotErrorKind::Unconsumed }
+        );
+    }
+}
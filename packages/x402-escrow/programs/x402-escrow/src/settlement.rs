@@ -0,0 +1,152 @@
+//! Payout and bookkeeping shared by the dispute resolution paths
+//!
+//! Each resolution path verifies its attestation its own way: an Ed25519
+//! signature from a registered verifier, or a Switchboard or Pyth feed. Once
+//! it has a score and a split, `apply_resolution` moves the funds and records
+//! the outcome, so a new oracle backend only has to implement verification.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    apply_dispute_reputation, disburse_priority_fee, dispute_bond_returned, load_reputation,
+    pay_api_with_fee, record_provider_strike, record_score_snapshot, refund_agent, refund_destination,
+    release_assignment,
+    release_exposure, require_rent_exempt_after, settle_dispute_bond, slash_service_bond, store_reputation,
+    DisputeResolved, Escrow, EscrowStatus, GlobalConfig, ReputationHistory, ResolutionMethod, ServiceBond,
+    VerifierOracleAccount, EVENT_VERSION,
+};
+
+/// Accounts a resolution settles
+pub struct SettlementAccounts<'a, 'info> {
+    pub escrow: &'a mut Account<'info, Escrow>,
+    pub agent: &'a AccountInfo<'info>,
+    /// Required when the escrow names a refund recipient
+    pub refund_recipient: Option<&'a AccountInfo<'info>>,
+    pub api: &'a AccountInfo<'info>,
+    pub treasury: Option<&'a AccountInfo<'info>>,
+    pub config: &'a GlobalConfig,
+    /// Verifier that signed the ruling, or the agent when a feed attested it
+    pub priority_fee_recipient: &'a AccountInfo<'info>,
+    pub agent_reputation: &'a AccountInfo<'info>,
+    pub api_reputation: &'a AccountInfo<'info>,
+    pub agent_reputation_history: Option<&'a mut ReputationHistory>,
+    pub api_reputation_history: Option<&'a mut ReputationHistory>,
+    pub verifier_oracle: Option<&'a mut VerifierOracleAccount>,
+    pub service_bond: Option<&'a mut Account<'info, ServiceBond>>,
+    /// Strikes are recorded against the API when present
+    pub api_penalties: Option<&'a AccountInfo<'info>>,
+}
+
+/// A verified ruling on a disputed escrow
+pub struct Resolution {
+    pub quality_score: u8,
+    pub refund_percentage: u8,
+    /// Open amount split as (refund to agent, payment to API)
+    pub split: (u64, u64),
+    pub content_hash: Option<[u8; 32]>,
+    /// Verifier key or attestation account, as reported in `DisputeResolved`
+    pub resolver: Pubkey,
+    pub method: ResolutionMethod,
+}
+
+/// Pay out a verified resolution and record it on the escrow, the parties'
+/// reputations and the provider's penalties
+pub fn apply_resolution(accounts: SettlementAccounts, resolution: Resolution) -> Result<()> {
+    let SettlementAccounts {
+        escrow,
+        agent,
+        refund_recipient,
+        api,
+        treasury,
+        config,
+        priority_fee_recipient,
+        agent_reputation: agent_info,
+        api_reputation: api_info,
+        agent_reputation_history,
+        api_reputation_history,
+        verifier_oracle,
+        service_bond,
+        api_penalties,
+    } = accounts;
+    let Resolution {
+        quality_score,
+        refund_percentage,
+        split: (refund_amount, payment_amount),
+        content_hash,
+        resolver,
+        method,
+    } = resolution;
+    let clock = Clock::get()?;
+
+    msg!("Refund to Agent: {} SOL", refund_amount as f64 / 1_000_000_000.0);
+    msg!("Payment to API: {} SOL", payment_amount as f64 / 1_000_000_000.0);
+
+    let escrow_amount = escrow.open_amount();
+    disburse_priority_fee(escrow, priority_fee_recipient)?;
+    settle_dispute_bond(
+        escrow,
+        agent,
+        api,
+        treasury,
+        config,
+        dispute_bond_returned(refund_percentage),
+    )?;
+    require_rent_exempt_after(&escrow.to_account_info(), escrow_amount)?;
+
+    let refund_to = refund_destination(escrow, agent, refund_recipient)?;
+    refund_agent(escrow, refund_to, refund_amount)?;
+    // Payment to the API, less the namespace protocol fee
+    pay_api_with_fee(escrow, api, treasury, config, payment_amount)?;
+    slash_service_bond(escrow, service_bond, agent, config, quality_score, refund_percentage)?;
+    if let Some(api_penalties) = api_penalties {
+        record_provider_strike(escrow, api_penalties, quality_score, refund_amount)?;
+    }
+
+    escrow.set_status(EscrowStatus::Resolved);
+    escrow.settled_at = Some(clock.unix_timestamp);
+    escrow.quality_score = Some(quality_score);
+    escrow.refund_percentage = Some(refund_percentage);
+    if content_hash.is_some() {
+        escrow.assessed_content_hash = content_hash;
+    }
+    // Reputation is best-effort: corrupt analytics state never blocks settlement
+    let escrow_key = escrow.key();
+    let mut agent_reputation = load_reputation(agent_info, &escrow.agent, escrow_key);
+    let mut api_reputation = load_reputation(api_info, &escrow.api, escrow_key);
+
+    release_exposure(escrow, agent_reputation.as_mut(), api_reputation.as_mut());
+    release_assignment(escrow, verifier_oracle)?;
+
+    apply_dispute_reputation(
+        agent_reputation.as_mut(),
+        api_reputation.as_mut(),
+        escrow,
+        quality_score,
+        refund_percentage,
+        refund_amount,
+        payment_amount,
+        clock.unix_timestamp,
+    );
+    store_reputation(agent_info, agent_reputation.as_ref())?;
+    store_reputation(api_info, api_reputation.as_ref())?;
+    record_score_snapshot(agent_reputation_history, agent_reputation.as_ref(), clock.unix_timestamp);
+    record_score_snapshot(api_reputation_history, api_reputation.as_ref(), clock.unix_timestamp);
+
+    emit!(DisputeResolved {
+        version: EVENT_VERSION,
+        escrow: escrow_key,
+        transaction_id: escrow.transaction_id.clone(),
+        quality_score,
+        refund_percentage,
+        refund_amount,
+        payment_amount,
+        verifier: resolver,
+        content_hash,
+        agent: escrow.agent,
+        api: escrow.api,
+        method,
+        dispute_duration: escrow.dispute_duration(clock.unix_timestamp),
+    });
+
+    Ok(())
+}
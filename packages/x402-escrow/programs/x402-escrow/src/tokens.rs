@@ -0,0 +1,217 @@
+//! Token movements for SPL escrows
+//!
+//! The token path accepts classic SPL mints, including wrapped SOL, and
+//! Token-2022 mints. Two Token-2022 extensions change how a transfer behaves:
+//!
+//! - A transfer fee is withheld from what the destination receives. Deposits
+//!   are therefore recorded at the amount that reached the vault, payouts at
+//!   the amount that left it, and fees withheld in the vault are harvested to
+//!   the mint before the vault closes.
+//! - A transfer hook needs extra accounts on every transfer. Clients resolve
+//!   them from the mint's validation account and pass them as the
+//!   instruction's remaining accounts; they are forwarded to the token program
+//!   unchanged. An instruction making several transfers takes the union of
+//!   what each needs.
+//!
+//! Mints with extensions that let a third party move the vault's tokens or
+//! stop them moving at all are rejected when the escrow is created.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token_interface::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::{instruction::harvest_withheld_tokens_to_mint, TransferFeeAmount},
+        BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    },
+    state::{Account as TokenAccountState, Mint as MintState},
+};
+
+use crate::EscrowError;
+
+/// Mint extensions an escrow vault cannot safely hold
+const REJECTED_MINT_EXTENSIONS: [ExtensionType; 2] = [
+    // The delegate could drain the vault under a pending escrow
+    ExtensionType::PermanentDelegate,
+    // The vault could never pay out
+    ExtensionType::NonTransferable,
+];
+
+/// A `transfer_checked` through either token program
+pub struct TokenTransfer<'a, 'info> {
+    pub token_program: &'a AccountInfo<'info>,
+    pub from: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    /// Transfer hook accounts, appended to the instruction as given
+    pub hook_accounts: &'a [AccountInfo<'info>],
+}
+
+impl TokenTransfer<'_, '_> {
+    pub fn invoke(self, amount: u64, decimals: u8, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        let mut instruction = spl_token_2022::instruction::transfer_checked(
+            self.token_program.key,
+            self.from.key,
+            self.mint.key,
+            self.to.key,
+            self.authority.key,
+            &[],
+            amount,
+            decimals,
+        )?;
+
+        let mut account_infos = vec![self.from, self.mint, self.to, self.authority];
+        for account in self.hook_accounts {
+            instruction.accounts.push(AccountMeta {
+                pubkey: account.key(),
+                is_signer: false,
+                is_writable: account.is_writable,
+            });
+            account_infos.push(account.clone());
+        }
+
+        invoke_signed(&instruction, &account_infos, signer_seeds)?;
+        Ok(())
+    }
+}
+
+/// Reject mints whose extensions would undermine the vault's custody
+pub fn check_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint.try_borrow_data()?;
+    require!(
+        rejected_extension(&data)?.is_none(),
+        EscrowError::UnsupportedMintExtension
+    );
+    Ok(())
+}
+
+fn rejected_extension(mint_data: &[u8]) -> Result<Option<ExtensionType>> {
+    let mint = StateWithExtensions::<MintState>::unpack(mint_data)?;
+    Ok(mint
+        .get_extension_types()?
+        .into_iter()
+        .find(|extension| REJECTED_MINT_EXTENSIONS.contains(extension)))
+}
+
+/// Transfer fees withheld in a token account, awaiting harvest
+pub fn withheld_fees(token_account: &AccountInfo) -> Result<u64> {
+    if *token_account.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+
+    let data = token_account.try_borrow_data()?;
+    withheld_amount(&data)
+}
+
+fn withheld_amount(token_account_data: &[u8]) -> Result<u64> {
+    let account = StateWithExtensions::<TokenAccountState>::unpack(token_account_data)?;
+    Ok(account
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee| u64::from(fee.withheld_amount)))
+}
+
+/// Move the fees withheld in `token_account` to its mint, which a Token-2022
+/// account must be rid of before it can close. Permissionless.
+pub fn harvest_to_mint<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    token_account: &AccountInfo<'info>,
+) -> Result<()> {
+    let instruction =
+        harvest_withheld_tokens_to_mint(token_program.key, mint.key, &[token_account.key])?;
+    invoke_signed(
+        &instruction,
+        &[token_program.clone(), mint.clone(), token_account.clone()],
+        &[],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token_2022::extension::{
+        non_transferable::NonTransferable, transfer_fee::TransferFeeConfig, BaseStateWithExtensionsMut,
+        StateWithExtensionsMut,
+    };
+    use spl_token_2022::solana_program::program_pack::Pack;
+
+    fn mint_with(extensions: &[ExtensionType]) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<MintState>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut mint = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        for extension in extensions {
+            match extension {
+                ExtensionType::TransferFeeConfig => {
+                    mint.init_extension::<TransferFeeConfig>(true).unwrap();
+                }
+                ExtensionType::NonTransferable => {
+                    mint.init_extension::<NonTransferable>(true).unwrap();
+                }
+                _ => unreachable!(),
+            }
+        }
+        mint.base = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn only_custody_breaking_extensions_are_rejected() {
+        assert_eq!(rejected_extension(&mint_with(&[])).unwrap(), None);
+        assert_eq!(
+            rejected_extension(&mint_with(&[ExtensionType::TransferFeeConfig])).unwrap(),
+            None
+        );
+        assert_eq!(
+            rejected_extension(&mint_with(&[
+                ExtensionType::TransferFeeConfig,
+                ExtensionType::NonTransferable,
+            ]))
+            .unwrap(),
+            Some(ExtensionType::NonTransferable)
+        );
+
+        // Classic mints carry no extensions
+        let mut classic = vec![0u8; MintState::LEN];
+        MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut classic);
+        assert_eq!(rejected_extension(&classic).unwrap(), None);
+    }
+
+    #[test]
+    fn withheld_fees_read_from_the_account_extension() {
+        let extensions = [ExtensionType::TransferFeeAmount];
+        let len = ExtensionType::try_calculate_account_len::<TokenAccountState>(&extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut account =
+            StateWithExtensionsMut::<TokenAccountState>::unpack_uninitialized(&mut data).unwrap();
+        account
+            .init_extension::<TransferFeeAmount>(true)
+            .unwrap()
+            .withheld_amount = 250u64.into();
+        account.base = TokenAccountState {
+            amount: 9_750,
+            state: spl_token_2022::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        account.pack_base();
+        account.init_account_type().unwrap();
+
+        assert_eq!(withheld_amount(&data).unwrap(), 250);
+    }
+}
@@ -0,0 +1,178 @@
+//! Wormhole messages attesting dispute settlements
+//!
+//! A resolved escrow's outcome is posted through the Wormhole core bridge so
+//! facilitators on other chains can verify it from the signed VAA and unlock
+//! balances they mirrored. The program's `wormhole_emitter` PDA is the
+//! emitter; a VAA from any other emitter is not a settlement of this program.
+//!
+//! Payload, big-endian as Wormhole consumers expect:
+//!
+//! | bytes | field |
+//! |-------|-------|
+//! | 1 | payload id, `PAYLOAD_ID_SETTLEMENT` |
+//! | 32 | escrow |
+//! | 32 | agent |
+//! | 32 | api |
+//! | 32 | mint, zero for SOL |
+//! | 8 | amount |
+//! | 8 | refunded to agent |
+//! | 8 | paid to API |
+//! | 1 | quality score, `NO_QUALITY_SCORE` if never assessed |
+//! | 1 | refund percentage |
+//! | 8 | settled at, unix seconds |
+//! | 1 + n | transaction id, length-prefixed UTF-8 |
+//!
+//! The core bridge is invoked directly; its Rust crate pins Solana 1.x.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke_signed, sysvar};
+use anchor_lang::system_program;
+
+use crate::Escrow;
+
+pub const WORMHOLE_PROGRAM_ID: Pubkey = pubkey!("worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth");
+
+pub const PAYLOAD_ID_SETTLEMENT: u8 = 1;
+pub const NO_QUALITY_SCORE: u8 = u8::MAX;
+
+const POST_MESSAGE_INSTRUCTION: u8 = 1;
+const CONSISTENCY_FINALIZED: u8 = 1;
+/// `fee` in the bridge's config: after `guardian_set_index: u32`,
+/// `last_lamports: u64` and `guardian_set_expiration_time: u32`
+const BRIDGE_FEE_OFFSET: usize = 16;
+
+/// Accounts the core bridge's `post_message` takes, and the payer of its fee
+pub struct WormholeAccounts<'a, 'info> {
+    pub bridge: &'a AccountInfo<'info>,
+    /// Signs with `message_seeds`; one per escrow
+    pub message: &'a AccountInfo<'info>,
+    /// Signs with `emitter_seeds`
+    pub emitter: &'a AccountInfo<'info>,
+    pub sequence: &'a AccountInfo<'info>,
+    pub payer: &'a AccountInfo<'info>,
+    pub fee_collector: &'a AccountInfo<'info>,
+    pub clock: &'a AccountInfo<'info>,
+    pub rent: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub wormhole_program: &'a AccountInfo<'info>,
+}
+
+/// Settlement payload of a resolved escrow
+pub fn settlement_payload(escrow_key: &Pubkey, escrow: &Escrow) -> Vec<u8> {
+    let mut payload = vec![PAYLOAD_ID_SETTLEMENT];
+    payload.extend_from_slice(escrow_key.as_ref());
+    payload.extend_from_slice(escrow.agent.as_ref());
+    payload.extend_from_slice(escrow.api.as_ref());
+    payload.extend_from_slice(escrow.mint.unwrap_or_default().as_ref());
+    payload.extend_from_slice(&escrow.amount.to_be_bytes());
+    payload.extend_from_slice(&escrow.refunded_to_agent.to_be_bytes());
+    payload.extend_from_slice(&escrow.paid_to_api.to_be_bytes());
+    payload.push(escrow.quality_score.unwrap_or(NO_QUALITY_SCORE));
+    payload.push(escrow.refund_percentage.unwrap_or(0));
+    payload.extend_from_slice(&escrow.settled_at.unwrap_or(0).to_be_bytes());
+    // Transaction ids are at most 64 bytes
+    payload.push(escrow.transaction_id.len() as u8);
+    payload.extend_from_slice(escrow.transaction_id.as_bytes());
+    payload
+}
+
+/// `post_message` instruction data: nonce, payload, consistency level
+fn post_message_data(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut data = vec![POST_MESSAGE_INSTRUCTION];
+    0u32.serialize(&mut data)?; // nonce
+    payload.serialize(&mut data)?;
+    data.push(CONSISTENCY_FINALIZED);
+    Ok(data)
+}
+
+/// Message fee the bridge currently charges
+fn bridge_fee(bridge: &AccountInfo) -> Result<u64> {
+    let data = bridge.try_borrow_data()?;
+    let fee = data
+        .get(BRIDGE_FEE_OFFSET..BRIDGE_FEE_OFFSET + 8)
+        .ok_or(ErrorCode::AccountDidNotDeserialize)?;
+    Ok(u64::from_le_bytes(fee.try_into().unwrap()))
+}
+
+/// Sequence the emitter's next message will get
+pub fn next_sequence(sequence: &AccountInfo) -> Result<u64> {
+    let data = sequence.try_borrow_data()?;
+    Ok(data
+        .get(..8)
+        .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap())))
+}
+
+/// Pay the bridge fee and post `payload`
+pub fn post_message(
+    accounts: &WormholeAccounts,
+    payload: &[u8],
+    message_seeds: &[&[u8]],
+    emitter_seeds: &[&[u8]],
+) -> Result<()> {
+    let fee = bridge_fee(accounts.bridge)?;
+    if fee > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.clone(),
+                system_program::Transfer {
+                    from: accounts.payer.clone(),
+                    to: accounts.fee_collector.clone(),
+                },
+            ),
+            fee,
+        )?;
+    }
+
+    let instruction = Instruction {
+        program_id: WORMHOLE_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(accounts.bridge.key(), false),
+            AccountMeta::new(accounts.message.key(), true),
+            AccountMeta::new_readonly(accounts.emitter.key(), true),
+            AccountMeta::new(accounts.sequence.key(), false),
+            AccountMeta::new(accounts.payer.key(), true),
+            AccountMeta::new(accounts.fee_collector.key(), false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(accounts.system_program.key(), false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ],
+        data: post_message_data(payload)?,
+    };
+
+    invoke_signed(
+        &instruction,
+        &[
+            accounts.bridge.clone(),
+            accounts.message.clone(),
+            accounts.emitter.clone(),
+            accounts.sequence.clone(),
+            accounts.payer.clone(),
+            accounts.fee_collector.clone(),
+            accounts.clock.clone(),
+            accounts.system_program.clone(),
+            accounts.rent.clone(),
+            accounts.wormhole_program.clone(),
+        ],
+        &[message_seeds, emitter_seeds],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_message_data_follows_the_core_bridge_layout() {
+        let payload = [PAYLOAD_ID_SETTLEMENT, 0xAB, 0xCD];
+        let data = post_message_data(&payload).unwrap();
+
+        assert_eq!(data[0], POST_MESSAGE_INSTRUCTION);
+        assert_eq!(data[1..5], 0u32.to_le_bytes());
+        assert_eq!(data[5..9], (payload.len() as u32).to_le_bytes());
+        assert_eq!(data[9..12], payload);
+        assert_eq!(data[12], CONSISTENCY_FINALIZED);
+        assert_eq!(data.len(), 13);
+    }
+}
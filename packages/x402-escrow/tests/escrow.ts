@@ -2,7 +2,30 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { X402Escrow } from "../target/types/x402_escrow";
 import { expect } from "chai";
-import { PublicKey, SystemProgram, Keypair, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  PublicKey,
+  SystemProgram,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  Ed25519Program,
+  ComputeBudgetProgram,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import {
+  createAssociatedTokenAccount,
+  createInitializeMintInstruction,
+  createInitializeNonTransferableMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  ExtensionType,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import { createHash } from "crypto";
 
 describe("x402-escrow", () => {
   const provider = anchor.AnchorProvider.env();
@@ -16,12 +39,103 @@ describe("x402-escrow", () => {
 
   const ESCROW_AMOUNT = 0.01 * LAMPORTS_PER_SOL;
   const TIME_LOCK = 86400;
+  const DISPUTE_REASON = { lowQuality: {} };
+  const EVIDENCE_HASH = new Array(32).fill(7);
 
-  beforeEach(() => {
+  // The default namespace's registry slot rotates to each test's verifier
+  let registeredVerifier: PublicKey | null = null;
+
+  beforeEach(async () => {
     api = Keypair.generate();
     verifier = Keypair.generate();
+    await registerVerifier(verifier.publicKey, undefined, registeredVerifier);
+    registeredVerifier = verifier.publicKey;
+    await initializeNonces(verifier);
   });
 
+  // Resolutions consume a nonce from the verifier's registry. The wallet
+  // covers its rent so the verifier starts from an empty balance.
+  async function initializeNonces(signer: Keypair) {
+    const rent = await provider.connection.getMinimumBalanceForRentExemption(
+      program.account.usedNonces.size
+    );
+    await program.methods
+      .initializeUsedNonces()
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([
+        SystemProgram.transfer({
+          fromPubkey: agent.publicKey,
+          toPubkey: signer.publicKey,
+          lamports: rent,
+        }),
+      ])
+      .signers([signer])
+      .rpc();
+  }
+
+  // Verifier messages start "{transactionId}:{escrow}:{amount}:{resolutionNonce}:{nonce}"
+  let lastNonce = 0;
+  async function resolutionPrefix(escrowPda: PublicKey) {
+    const escrow = await program.account.escrow.fetch(escrowPda);
+    lastNonce += 1;
+    return {
+      nonce: new anchor.BN(lastNonce),
+      prefix:
+        `${escrow.transactionId}:${escrowPda.toBase58()}:${escrow.amount.toString()}:` +
+        `${escrow.resolutionNonce.toString()}:${lastNonce}`,
+    };
+  }
+
+  // Only keys in the namespace's verifier registry can resolve its disputes
+  async function registerVerifier(
+    key: PublicKey,
+    platform?: Keypair,
+    replaces: PublicKey | null = null
+  ) {
+    const namespace = platform?.publicKey ?? PublicKey.default;
+    const authority = platform?.publicKey ?? agent.publicKey;
+    const signers = platform ? [platform] : [];
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("global_config"), namespace.toBuffer()],
+      program.programId
+    );
+    const [verifierRegistry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("verifier_registry"), namespace.toBuffer()],
+      program.programId
+    );
+    if (!(await program.account.verifierRegistry.fetchNullable(verifierRegistry))) {
+      await program.methods
+        .initializeVerifierRegistry()
+        .accounts({ verifierRegistry, config, authority })
+        .signers(signers)
+        .rpc();
+    }
+    await program.methods
+      .registerVerifierKey(key, replaces)
+      .accounts({ verifierRegistry, config, authority })
+      .signers(signers)
+      .rpc();
+  }
+
+  // Status lives in the low two bits of `Escrow.flags`
+  function escrowStatus(flags: number): string {
+    return ["active", "released", "disputed", "resolved"][flags & 0b11];
+  }
+
+  async function ensureReputation(entity: PublicKey): Promise<PublicKey> {
+    const [reputationPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("reputation"), entity.toBuffer()],
+      program.programId
+    );
+    if (!(await program.account.entityReputation.fetchNullable(reputationPda))) {
+      await program.methods
+        .initReputation({ agent: {} })
+        .accounts({ reputation: reputationPda, entity, payer: agent.publicKey })
+        .rpc();
+    }
+    return reputationPda;
+  }
+
   describe("initialize_escrow", () => {
     it("creates escrow with correct parameters", async () => {
       const transactionId = `tx_${Date.now()}`;
@@ -34,7 +148,14 @@ describe("x402-escrow", () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -50,7 +171,7 @@ describe("x402-escrow", () => {
       expect(escrowAccount.api.toBase58()).to.equal(api.publicKey.toBase58());
       expect(escrowAccount.amount.toNumber()).to.equal(ESCROW_AMOUNT);
       expect(escrowAccount.transactionId).to.equal(transactionId);
-      expect(escrowAccount.status).to.deep.equal({ active: {} });
+      expect(escrowStatus(escrowAccount.flags)).to.equal("active");
     });
 
     it("transfers SOL to escrow PDA", async () => {
@@ -66,7 +187,14 @@ describe("x402-escrow", () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -81,6 +209,83 @@ describe("x402-escrow", () => {
 
       expect(transferred).to.be.greaterThan(ESCROW_AMOUNT * 0.99);
     });
+
+    it("rejects a USD escrow without a Pyth price update", async () => {
+      const transactionId = `tx_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .initializeEscrowUsd(
+            new anchor.BN(500),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            new anchor.BN(ESCROW_AMOUNT),
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required a price update");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidPriceFeed");
+      }
+    });
+
+    it("stores a metadata URI of up to 200 characters", async () => {
+      const open = async (metadataUri: string) => {
+        const transactionId = `tx_meta_${Date.now()}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            metadataUri,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        return escrowPda;
+      };
+
+      const metadataUri = "https://api.example.com/x402/calls/weather.json";
+      const escrowAccount = await program.account.escrow.fetch(await open(metadataUri));
+      expect(escrowAccount.metadataUri).to.equal(metadataUri);
+
+      try {
+        await open(`https://example.com/${"a".repeat(200)}`);
+        expect.fail("Should have rejected a metadata URI over 200 characters");
+      } catch (err) {
+        expect(err.toString()).to.include("MetadataUriTooLong");
+      }
+    });
   });
 
   describe("mark_disputed", () => {
@@ -98,7 +303,14 @@ describe("x402-escrow", () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -110,8 +322,9 @@ describe("x402-escrow", () => {
     });
 
     it("marks escrow as disputed", async () => {
+      const escrowBefore = await provider.connection.getBalance(escrowPda);
       await program.methods
-        .markDisputed()
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -119,7 +332,150 @@ describe("x402-escrow", () => {
         .rpc();
 
       const escrowAccount = await program.account.escrow.fetch(escrowPda);
-      expect(escrowAccount.status).to.deep.equal({ disputed: {} });
+      expect(escrowStatus(escrowAccount.flags)).to.equal("disputed");
+
+      // The dispute cost is held in the escrow until resolution
+      const bond = escrowAccount.disputeBond.toNumber();
+      expect(bond).to.be.greaterThan(0);
+      expect(await provider.connection.getBalance(escrowPda)).to.equal(escrowBefore + bond);
+    });
+
+    it("records the reason code and evidence hash", async () => {
+      const before = await program.account.escrow.fetch(escrowPda);
+      expect(before.disputeReason).to.be.null;
+      expect(before.evidenceHash).to.be.null;
+
+      const evidenceHash = Array.from(createHash("sha256").update("response was empty").digest());
+      let marked: any;
+      const listener = program.addEventListener("disputeMarked", (event) => {
+        marked = event;
+      });
+      try {
+        await program.methods
+          .markDisputed(new anchor.BN(0), { incomplete: {} }, evidenceHash)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey })
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowAccount.disputeReason).to.deep.equal({ incomplete: {} });
+      expect(escrowAccount.evidenceHash).to.deep.equal(evidenceHash);
+
+      // Verifiers read the claim category off the event
+      expect(marked.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(marked.transactionId).to.equal(transactionId);
+      expect(marked.reasonCode).to.deep.equal({ incomplete: {} });
+      expect(marked.evidenceHash).to.deep.equal(evidenceHash);
+    });
+
+    it("accepts one provider response while the dispute is open", async () => {
+      const evidenceHash = Array.from(createHash("sha256").update("full response log").digest());
+      const respond = () =>
+        program.methods
+          .submitProviderResponse(evidenceHash, "https://api.example.com/disputes/1")
+          .accounts({ escrow: escrowPda, api: api.publicKey })
+          .signers([api])
+          .rpc();
+
+      try {
+        await respond();
+        expect.fail("Should have rejected a response to an undisputed escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      // Only the escrow's API may answer, and the comment URI is bounded
+      try {
+        await program.methods
+          .submitProviderResponse(evidenceHash, null)
+          .accounts({ escrow: escrowPda, api: agent.publicKey })
+          .rpc();
+        expect.fail("Should have rejected a response from the agent");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+      try {
+        await program.methods
+          .submitProviderResponse(evidenceHash, `https://api.example.com/${"a".repeat(200)}`)
+          .accounts({ escrow: escrowPda, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected a comment URI over 200 characters");
+      } catch (err) {
+        expect(err.toString()).to.include("CommentUriTooLong");
+      }
+
+      let submitted: any;
+      const listener = program.addEventListener("providerResponseSubmitted", (event) => {
+        submitted = event;
+      });
+      try {
+        await respond();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+      expect(submitted.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(submitted.api.toBase58()).to.equal(api.publicKey.toBase58());
+      expect(submitted.evidenceHash).to.deep.equal(evidenceHash);
+      expect(submitted.commentUri).to.equal("https://api.example.com/disputes/1");
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowAccount.providerEvidenceHash).to.deep.equal(evidenceHash);
+      expect(escrowAccount.providerCommentUri).to.equal("https://api.example.com/disputes/1");
+
+      try {
+        await respond();
+        expect.fail("Should have rejected a second response");
+      } catch (err) {
+        expect(err.toString()).to.include("ProviderResponseAlreadySubmitted");
+      }
+    });
+
+    it("bonds a share of larger escrows", async () => {
+      const amount = 0.5 * LAMPORTS_PER_SOL;
+      const largeId = `tx_large_${Date.now()}`;
+      const [largePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(largeId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(amount),
+          new anchor.BN(TIME_LOCK),
+          largeId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: largePda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: largePda, agent: agent.publicKey })
+        .rpc();
+
+      // 1% by default, scaled up for agents that dispute often
+      const escrowAccount = await program.account.escrow.fetch(largePda);
+      expect(escrowAccount.disputeBond.toNumber()).to.be.at.least(amount / 100);
     });
 
     it("fails if not called by agent", async () => {
@@ -127,7 +483,7 @@ describe("x402-escrow", () => {
 
       try {
         await program.methods
-          .markDisputed()
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
           .accounts({
             escrow: escrowPda,
             agent: unauthorizedAgent.publicKey,
@@ -140,11 +496,50 @@ describe("x402-escrow", () => {
         expect(err.toString()).to.include("Unauthorized");
       }
     });
+
+    it("accepts the agent's delegate until revoked", async () => {
+      const hotKey = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(hotKey.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const dispute = () =>
+        program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: hotKey.publicKey })
+          .signers([hotKey])
+          .rpc();
+
+      await program.methods
+        .setDelegate(hotKey.publicKey)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await program.methods
+        .setDelegate(null)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      try {
+        await dispute();
+        expect.fail("Should have rejected a revoked delegate");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .setDelegate(hotKey.publicKey)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await dispute();
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("disputed");
+      expect(escrowAccount.delegate.toString()).to.equal(hotKey.publicKey.toString());
+    });
   });
 
   describe("resolve_dispute", () => {
     let transactionId: string;
     let escrowPda: PublicKey;
+    const contentHash = new Array(32).fill(0);
 
     beforeEach(async () => {
       transactionId = `tx_${Date.now()}`;
@@ -157,7 +552,14 @@ describe("x402-escrow", () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -168,7 +570,7 @@ describe("x402-escrow", () => {
         .rpc();
 
       await program.methods
-        .markDisputed()
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -176,6 +578,284 @@ describe("x402-escrow", () => {
         .rpc();
     });
 
+    it("rejects verifiers outside the registry", async () => {
+      try {
+        await program.methods
+          .resolveDispute(15, contentHash, new anchor.BN(0), new Array(64).fill(0))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: Keypair.generate().publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have rejected an unregistered verifier");
+      } catch (err) {
+        expect(err.toString()).to.include("VerifierNotRegistered");
+      }
+    });
+
+    it("reimburses the relayer that submits a verifier's ruling", async () => {
+      const RELAYER_FEE_LAMPORTS = 10_000;
+      const qualityScore = 15;
+      const relayer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(relayer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`),
+      });
+
+      let reimbursed: any;
+      const listener = program.addEventListener("relayerReimbursed", (event) => {
+        reimbursed = event;
+      });
+      try {
+        // The verifier only signed the message; the relayer signs the transaction
+        await program.methods
+          .resolveWithRelayer(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            relayer: relayer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .signers([relayer])
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      expect(await provider.connection.getBalance(relayer.publicKey)).to.equal(
+        LAMPORTS_PER_SOL + RELAYER_FEE_LAMPORTS
+      );
+      expect(reimbursed.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(reimbursed.relayer.toBase58()).to.equal(relayer.publicKey.toBase58());
+      expect(reimbursed.amount.toNumber()).to.equal(RELAYER_FEE_LAMPORTS);
+
+      // The fee comes out of the escrow before the split, like the oracle fee
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("resolved");
+      expect(escrow.oracleFeePaid.toNumber()).to.be.at.least(RELAYER_FEE_LAMPORTS);
+    });
+
+    it("rejects signatures over another amount or a spent nonce", async () => {
+      const contentHashHex = Buffer.from(contentHash).toString("hex");
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const resolveWith = (message: string) => {
+        const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: verifier.secretKey,
+          message: Buffer.from(message),
+        });
+        return program.methods
+          .resolveDispute(15, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+      };
+
+      const { resolutionNonce } = await program.account.escrow.fetch(escrowPda);
+      try {
+        await resolveWith(
+          `${transactionId}:${escrowPda.toBase58()}:${ESCROW_AMOUNT * 2}:${resolutionNonce}:${nonce}:15:${contentHashHex}`
+        );
+        expect.fail("Should have rejected a signature over another amount");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidSignature");
+      }
+
+      await program.methods
+        .invalidateNonce(nonce)
+        .accounts({ signer: verifier.publicKey })
+        .signers([verifier])
+        .rpc();
+      try {
+        await resolveWith(`${prefix}:15:${contentHashHex}`);
+        expect.fail("Should have rejected a spent nonce");
+      } catch (err) {
+        expect(err.toString()).to.include("NonceAlreadyUsed");
+      }
+    });
+
+    it("rejects a replayed signature or a reused nonce", async () => {
+      const qualityScore = 15;
+      const contentHashHex = Buffer.from(contentHash).toString("hex");
+      const resolve = (escrow: PublicKey, nonce: anchor.BN, signatureIx: TransactionInstruction) =>
+        program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+      await resolve(escrowPda, nonce, signatureIx);
+
+      // A second disputed escrow between the same parties
+      const otherId = `replay_${Date.now()}`;
+      const [otherPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(otherId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          otherId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: otherPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: otherPda, agent: agent.publicKey })
+        .rpc();
+
+      // The signed message names the first escrow and its creation slot
+      try {
+        await resolve(otherPda, nonce, signatureIx);
+        expect.fail("Should have rejected a signature for another escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidSignature");
+      }
+
+      // A fresh signature cannot spend the same nonce twice
+      const other = await program.account.escrow.fetch(otherPda);
+      const reused = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${other.transactionId}:${otherPda.toBase58()}:${other.amount.toString()}:` +
+            `${other.resolutionNonce.toString()}:${nonce.toString()}:${qualityScore}:${contentHashHex}`
+        ),
+      });
+      try {
+        await resolve(otherPda, nonce, reused);
+        expect.fail("Should have rejected a reused nonce");
+      } catch (err) {
+        expect(err.toString()).to.include("NonceAlreadyUsed");
+      }
+
+      const escrow = await program.account.escrow.fetch(otherPda);
+      expect(escrowStatus(escrow.flags)).to.equal("disputed");
+    });
+
+    it("emits a versioned DisputeResolved with the parties and dispute duration", async () => {
+      const qualityScore = 30;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+      const { disputedAt } = await program.account.escrow.fetch(escrowPda);
+      expect(disputedAt).to.not.be.null;
+
+      let resolved: any;
+      const listener = program.addEventListener("disputeResolved", (event) => {
+        resolved = event;
+      });
+      try {
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      expect(resolved.version).to.equal(4);
+      expect(resolved.agent.toBase58()).to.equal(agent.publicKey.toBase58());
+      expect(resolved.api.toBase58()).to.equal(api.publicKey.toBase58());
+      expect(resolved.method).to.deep.equal({ ed25519: {} });
+      expect(resolved.disputeDuration.toNumber()).to.be.at.least(0);
+    });
+
+    it("reports each party's old and new score in ReputationUpdated", async () => {
+      await ensureReputation(agent.publicKey);
+      const apiReputation = await ensureReputation(api.publicKey);
+      const before = await program.account.entityReputation.fetch(apiReputation);
+      const qualityScore = 15;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+
+      const updates: any[] = [];
+      const listener = program.addEventListener("reputationUpdated", (event) => {
+        updates.push(event);
+      });
+      try {
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const agentUpdate = updates.find((e) => e.entity.equals(agent.publicKey));
+      const apiUpdate = updates.find((e) => e.entity.equals(api.publicKey));
+      // A full refund is a win for the agent and a loss for the API
+      expect(agentUpdate.reason).to.deep.equal({ disputeWon: {} });
+      expect(apiUpdate.reason).to.deep.equal({ disputeLost: {} });
+      expect(apiUpdate.oldScore).to.equal(before.reputationScore);
+      const after = await program.account.entityReputation.fetch(apiReputation);
+      expect(apiUpdate.newScore).to.equal(after.reputationScore);
+    });
+
     it("resolves with 100% refund for score 0-20", async () => {
       const qualityScore = 15;
       const refundPercentage = 100;
@@ -185,7 +865,7 @@ describe("x402-escrow", () => {
       const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -205,21 +885,20 @@ describe("x402-escrow", () => {
       expect(apiGain).to.equal(0);
 
       const escrowAccount = await program.account.escrow.fetch(escrowPda);
-      expect(escrowAccount.status).to.deep.equal({ resolved: {} });
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
       expect(escrowAccount.qualityScore).to.equal(qualityScore);
       expect(escrowAccount.refundPercentage).to.equal(refundPercentage);
     });
 
     it("resolves with 75% refund for score 21-40", async () => {
       const qualityScore = 35;
-      const refundPercentage = 75;
       const signature = new Array(64).fill(0);
 
       const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
       const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -241,14 +920,13 @@ describe("x402-escrow", () => {
 
     it("resolves with 50% refund for score 41-60", async () => {
       const qualityScore = 55;
-      const refundPercentage = 50;
       const signature = new Array(64).fill(0);
 
       const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
       const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -270,14 +948,13 @@ describe("x402-escrow", () => {
 
     it("resolves with 25% refund for score 61-80", async () => {
       const qualityScore = 75;
-      const refundPercentage = 25;
       const signature = new Array(64).fill(0);
 
       const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
       const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -299,14 +976,13 @@ describe("x402-escrow", () => {
 
     it("resolves with 0% refund for score 81-100", async () => {
       const qualityScore = 95;
-      const refundPercentage = 0;
       const signature = new Array(64).fill(0);
 
       const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
       const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -328,12 +1004,11 @@ describe("x402-escrow", () => {
 
     it("rejects invalid quality score > 100", async () => {
       const qualityScore = 150;
-      const refundPercentage = 0;
       const signature = new Array(64).fill(0);
 
       try {
         await program.methods
-          .resolveDispute(qualityScore, refundPercentage, signature)
+          .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
           .accounts({
             escrow: escrowPda,
             agent: agent.publicKey,
@@ -349,39 +1024,39 @@ describe("x402-escrow", () => {
       }
     });
 
-    it("rejects invalid refund percentage > 100", async () => {
-      const qualityScore = 50;
-      const refundPercentage = 150;
+    it("accepts valid oracle signature format", async () => {
+      const qualityScore = 75;
       const signature = new Array(64).fill(0);
 
-      try {
-        await program.methods
-          .resolveDispute(qualityScore, refundPercentage, signature)
-          .accounts({
-            escrow: escrowPda,
-            agent: agent.publicKey,
-            api: api.publicKey,
-            verifier: verifier.publicKey,
-            systemProgram: SystemProgram.programId,
-          })
-          .rpc();
+      const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
 
-        expect.fail("Should have thrown error");
-      } catch (err) {
-        expect(err.toString()).to.include("InvalidRefundPercentage");
-      }
-    });
-
-    it("accepts valid oracle signature format", async () => {
-      const qualityScore = 75;
-      const refundPercentage = 25;
-      const signature = new Array(64).fill(0);
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, new anchor.BN(0), signature)
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
 
-      const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
-      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+    });
 
+    it("leaves the escrow rent-exempt after a full refund", async () => {
+      const qualityScore = 10;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
       await program.methods
-        .resolveDispute(qualityScore, refundPercentage, signature)
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -389,19 +1064,34 @@ describe("x402-escrow", () => {
           verifier: verifier.publicKey,
           systemProgram: SystemProgram.programId,
         })
+        .preInstructions([signatureIx])
         .rpc();
 
-      const escrowAccount = await program.account.escrow.fetch(escrowPda);
-      expect(escrowAccount.status).to.deep.equal({ resolved: {} });
+      // The minimum covers the whole account, discriminator included
+      const info = await provider.connection.getAccountInfo(escrowPda);
+      const minimum = await provider.connection.getMinimumBalanceForRentExemption(info.data.length);
+      expect(info.data.length).to.equal(program.account.escrow.size);
+      expect(info.lamports).to.be.at.least(minimum);
+
+      try {
+        await program.methods
+          .topUpEscrowRent()
+          .accounts({ escrow: escrowPda, payer: agent.publicKey })
+          .rpc();
+        expect.fail("Should have found no deficit to cover");
+      } catch (err) {
+        expect(err.toString()).to.include("EscrowAlreadyRentExempt");
+      }
     });
   });
 
-  describe("release_funds", () => {
+  describe("acknowledge_delivery", () => {
     let transactionId: string;
     let escrowPda: PublicKey;
+    const responseHash = Array.from(createHash("sha256").update("delivered").digest());
 
     beforeEach(async () => {
-      transactionId = `tx_${Date.now()}`;
+      transactionId = `ack_${Date.now()}`;
       [escrowPda] = PublicKey.findProgramAddressSync(
         [Buffer.from("escrow"), Buffer.from(transactionId)],
         program.programId
@@ -411,7 +1101,14 @@ describe("x402-escrow", () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -422,11 +1119,291 @@ describe("x402-escrow", () => {
         .rpc();
     });
 
-    it("releases funds to API after time-lock", async () => {
-      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+    const acknowledge = () =>
+      program.methods
+        .acknowledgeDelivery(responseHash, null, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+    const dispute = () =>
+      program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+    async function resolveAssessing(contentHash: number[]) {
+      const qualityScore = 60;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+      return program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+    }
+
+    it("rejects a commitment made after the dispute", async () => {
+      await dispute();
+      try {
+        await acknowledge();
+        expect.fail("Should have rejected acknowledging a disputed escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.responseHash).to.be.null;
+    });
+
+    it("resolves when the verifier assessed the committed response", async () => {
+      await acknowledge();
+      await dispute();
+      await resolveAssessing(responseHash);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("resolved");
+      expect(escrow.assessedContentHash).to.deep.equal(responseHash);
+    });
+
+    it("rejects a verifier that assessed other content", async () => {
+      await acknowledge();
+      await dispute();
+      try {
+        await resolveAssessing(new Array(32).fill(9));
+        expect.fail("Should have rejected an assessment of other content");
+      } catch (err) {
+        expect(err.toString()).to.include("AssessedWrongContent");
+      }
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("disputed");
+    });
+  });
+
+  describe("batch_resolve_disputes_ed25519", () => {
+    const contentHash = new Array(32).fill(0);
+    const contentHashHex = Buffer.from(contentHash).toString("hex");
+
+    // Merge single-signature Ed25519 instructions into one multi-signature one.
+    // web3.js lays out each as [header(16) | pubkey(32) | signature(64) | message].
+    function mergeEd25519Instructions(ixs: TransactionInstruction[]): TransactionInstruction {
+      const entries = ixs.map((ix) => ({
+        publicKey: ix.data.subarray(16, 48),
+        signature: ix.data.subarray(48, 112),
+        message: ix.data.subarray(112),
+      }));
+      const headerSize = 2 + 14 * entries.length;
+      const bodySize = entries.reduce((n, e) => n + 96 + e.message.length, 0);
+      const data = Buffer.alloc(headerSize + bodySize);
+      data.writeUInt8(entries.length, 0);
+
+      let cursor = headerSize;
+      entries.forEach((entry, i) => {
+        const base = 2 + 14 * i;
+        data.writeUInt16LE(cursor + 32, base); // signature
+        data.writeUInt16LE(0xffff, base + 2);
+        data.writeUInt16LE(cursor, base + 4); // public key
+        data.writeUInt16LE(0xffff, base + 6);
+        data.writeUInt16LE(cursor + 96, base + 8); // message
+        data.writeUInt16LE(entry.message.length, base + 10);
+        data.writeUInt16LE(0xffff, base + 12);
+
+        data.set(entry.publicKey, cursor);
+        data.set(entry.signature, cursor + 32);
+        data.set(entry.message, cursor + 96);
+        cursor += 96 + entry.message.length;
+      });
+
+      return new TransactionInstruction({ programId: Ed25519Program.programId, keys: [], data });
+    }
+
+    it("resolves three disputes from one 3-signature Ed25519 instruction", async () => {
+      const agentReputation = await ensureReputation(agent.publicKey);
+      const apiReputation = await ensureReputation(api.publicKey);
+
+      const qualityScores = [15, 50, 90];
+      const refundPercentages = [100, 50, 0];
+      const escrows: PublicKey[] = [];
+      const signatureIxs: TransactionInstruction[] = [];
+      const nonces: anchor.BN[] = [];
+
+      for (let i = 0; i < 3; i++) {
+        const transactionId = `batch_${Date.now()}_${i}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey })
+          .rpc();
+
+        const { nonce, prefix } = await resolutionPrefix(escrowPda);
+        nonces.push(nonce);
+        const message = Buffer.from(`${prefix}:${qualityScores[i]}:${contentHashHex}`);
+        signatureIxs.push(
+          Ed25519Program.createInstructionWithPrivateKey({
+            privateKey: verifier.secretKey,
+            message,
+          })
+        );
+        escrows.push(escrowPda);
+      }
+
+      const batchIx = mergeEd25519Instructions(signatureIxs);
+      const resolutions = signatureIxs.map((ix, i) => ({
+        qualityScore: qualityScores[i],
+        contentHash,
+        nonce: nonces[i],
+        signature: Array.from(ix.data.subarray(48, 112)),
+      }));
+      const remainingAccounts = escrows.flatMap((escrow) =>
+        [escrow, agent.publicKey, api.publicKey, agentReputation, apiReputation].map(
+          (pubkey) => ({ pubkey, isSigner: false, isWritable: true })
+        )
+      );
 
       await program.methods
-        .releaseFunds()
+        .batchResolveDisputesEd25519(resolutions)
+        .accounts({
+          verifier: verifier.publicKey,
+          instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(remainingAccounts)
+        .preInstructions([batchIx])
+        .rpc();
+
+      for (let i = 0; i < 3; i++) {
+        const escrowAccount = await program.account.escrow.fetch(escrows[i]);
+        expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+        expect(escrowAccount.qualityScore).to.equal(qualityScores[i]);
+        expect(escrowAccount.refundPercentage).to.equal(refundPercentages[i]);
+      }
+    });
+  });
+
+  describe("namespaces", () => {
+    const contentHash = new Array(32).fill(0);
+    const contentHashHex = Buffer.from(contentHash).toString("hex");
+
+    async function fund(pubkey: PublicKey) {
+      const sig = await provider.connection.requestAirdrop(pubkey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+    }
+
+    async function setupNamespace(feeBps: number, appealWindowSeconds = 0, oracleFeeBps = 0) {
+      const platform = Keypair.generate();
+      const treasury = Keypair.generate();
+      await fund(platform.publicKey);
+      await fund(treasury.publicKey);
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      await program.methods
+        .updateGlobalConfig({
+          strictSigAdjacency: null,
+          spamProtectionDeposit: null,
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps,
+          treasury: treasury.publicKey,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: null,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: null,
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: new anchor.BN(appealWindowSeconds),
+          pythSolUsdFeedId: null,
+          amountTiers: null,
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: null,
+          oracleFeeBps,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({
+          config: PublicKey.findProgramAddressSync(
+            [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          authority: platform.publicKey,
+        })
+        .signers([platform])
+        .rpc();
+
+      await registerVerifier(verifier.publicKey, platform);
+
+      return { namespace: platform.publicKey, treasury: treasury.publicKey, platform };
+    }
+
+    async function resolveInNamespace(namespace: PublicKey, treasury: PublicKey, twoPhase = false) {
+      const transactionId = `ns_${Date.now()}_${namespace.toBase58().slice(0, 8)}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          namespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -435,32 +1412,6605 @@ describe("x402-escrow", () => {
         })
         .rpc();
 
-      const apiBalanceAfter = await provider.connection.getBalance(api.publicKey);
-      const apiGain = apiBalanceAfter - apiBalanceBefore;
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
 
-      expect(apiGain).to.be.greaterThan(ESCROW_AMOUNT * 0.99);
+      const qualityScore = 50;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      const treasuryBefore = await provider.connection.getBalance(treasury);
+      const verifierBefore = await provider.connection.getBalance(verifier.publicKey);
+      const signature = Array.from(signatureIx.data.subarray(48, 112));
+
+      if (twoPhase) {
+        await program.methods
+          .submitResolution(qualityScore, contentHash, nonce, signature)
+          .accounts({ escrow: escrowPda, verifier: verifier.publicKey })
+          .preInstructions([signatureIx])
+          .rpc();
+      } else {
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, signature)
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+      }
 
       const escrowAccount = await program.account.escrow.fetch(escrowPda);
-      expect(escrowAccount.status).to.deep.equal({ released: {} });
+      expect(escrowAccount.namespace.toBase58()).to.equal(namespace.toBase58());
+
+      return {
+        escrowPda,
+        apiGain: (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore,
+        feeCollected: (await provider.connection.getBalance(treasury)) - treasuryBefore,
+        verifierGain: (await provider.connection.getBalance(verifier.publicKey)) - verifierBefore,
+      };
+    }
+
+    it("applies each namespace's fee_bps to otherwise identical escrows", async () => {
+      const feeFree = await setupNamespace(0);
+      const feeCharging = await setupNamespace(500);
+
+      const freeOutcome = await resolveInNamespace(feeFree.namespace, feeFree.treasury);
+      const chargedOutcome = await resolveInNamespace(feeCharging.namespace, feeCharging.treasury);
+
+      const payment = ESCROW_AMOUNT / 2;
+      expect(freeOutcome.apiGain).to.equal(payment);
+      expect(freeOutcome.feeCollected).to.equal(0);
+      expect(chargedOutcome.feeCollected).to.equal(payment * 0.05);
+      expect(chargedOutcome.apiGain).to.equal(payment - payment * 0.05);
     });
 
-    it("allows agent to release explicitly", async () => {
-      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+    it("bounds each namespace's attestation staleness window", async () => {
+      const { namespace, platform } = await setupNamespace(0);
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const setMaxAge = (seconds: number) =>
+        program.methods
+          .updateGlobalConfig({
+            strictSigAdjacency: null,
+            spamProtectionDeposit: null,
+            solUsdPriceFeed: null,
+            maxOverrideDurationSeconds: null,
+            feeBps: null,
+            treasury: null,
+            minVerifierBondLamports: null,
+            bondGracePeriodDays: null,
+            maxDisputeWindowExtensions: null,
+            insuranceFundBps: null,
+            maxInsurancePayoutBps: null,
+            maxPriorityFeeLamports: null,
+            usdStablecoinMint: null,
+            cancelGracePeriodSeconds: null,
+            refundCurve: null,
+            appealWindowSeconds: null,
+            pythSolUsdFeedId: null,
+            amountTiers: null,
+            disputeBondBps: null,
+            disputeBondToTreasury: null,
+            severeRefundSlashBps: null,
+            releaseInsuranceBps: null,
+            oracleFeeBps: null,
+            switchboardQueue: null,
+            attestationMaxAgeSeconds: new anchor.BN(seconds),
+            pythQualityFeedId: null,
+          })
+          .accounts({ config: configPda, authority: platform.publicKey })
+          .signers([platform])
+          .rpc();
+
+      let config = await program.account.globalConfig.fetch(configPda);
+      expect(config.attestationMaxAgeSeconds.toNumber()).to.equal(300);
+
+      // Between 30 seconds and an hour
+      for (const seconds of [29, 3601]) {
+        try {
+          await setMaxAge(seconds);
+          expect.fail(`Should have rejected a ${seconds}s window`);
+        } catch (err) {
+          expect(err.toString()).to.include("InvalidAttestationMaxAge");
+        }
+      }
 
+      await setMaxAge(1800);
+      config = await program.account.globalConfig.fetch(configPda);
+      expect(config.attestationMaxAgeSeconds.toNumber()).to.equal(1800);
+    });
+
+    it("checks only the instruction before a resolution in strict mode", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(0);
       await program.methods
-        .releaseFunds()
+        .updateGlobalConfig({
+          strictSigAdjacency: true,
+          spamProtectionDeposit: null,
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps: null,
+          treasury: null,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: null,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: null,
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: null,
+          pythSolUsdFeedId: null,
+          amountTiers: null,
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: null,
+          oracleFeeBps: null,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({
+          config: PublicKey.findProgramAddressSync(
+            [Buffer.from("global_config"), namespace.toBuffer()],
+            program.programId
+          )[0],
+          authority: platform.publicKey,
+        })
+        .signers([platform])
+        .rpc();
+
+      const resolveAfter = async (
+        preInstructions: (signatureIx: TransactionInstruction) => TransactionInstruction[]
+      ) => {
+        const transactionId = `strict_${Date.now()}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            namespace,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey })
+          .rpc();
+
+        const { nonce, prefix } = await resolutionPrefix(escrowPda);
+        const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: verifier.secretKey,
+          message: Buffer.from(`${prefix}:50:${contentHashHex}`),
+        });
+        // The account list predates the namespace config, which is derived
+        await program.methods
+          .resolveDispute(50, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions(preInstructions(signatureIx))
+          .rpc();
+        return escrowPda;
+      };
+
+      // A duplicate of the signature two instructions back is harmless
+      const escrowPda = await resolveAfter((signatureIx) => [signatureIx, signatureIx]);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("resolved");
+
+      try {
+        await resolveAfter((signatureIx) => [
+          signatureIx,
+          ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 }),
+        ]);
+        expect.fail("Should have required the signature right before the resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("SignatureInstructionMisplaced");
+      }
+    });
+
+    it("pays the resolving verifier the namespace's oracle fee before the split", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 0, 200);
+      const outcome = await resolveInNamespace(namespace, treasury);
+
+      const oracleFee = ESCROW_AMOUNT * 0.02;
+      expect(outcome.verifierGain).to.equal(oracleFee);
+      expect(outcome.apiGain).to.equal((ESCROW_AMOUNT - oracleFee) / 2);
+      const escrow = await program.account.escrow.fetch(outcome.escrowPda);
+      expect(escrow.oracleFeePaid.toNumber()).to.equal(oracleFee);
+    });
+
+    it("splits disputes by the namespace's refund curve", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(0);
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const noChanges = {
+        strictSigAdjacency: null,
+        spamProtectionDeposit: null,
+        solUsdPriceFeed: null,
+        maxOverrideDurationSeconds: null,
+        feeBps: null,
+        treasury: null,
+        minVerifierBondLamports: null,
+        bondGracePeriodDays: null,
+        maxDisputeWindowExtensions: null,
+        insuranceFundBps: null,
+        maxInsurancePayoutBps: null,
+        maxPriorityFeeLamports: null,
+        usdStablecoinMint: null,
+        cancelGracePeriodSeconds: null,
+        appealWindowSeconds: null,
+        pythSolUsdFeedId: null,
+        amountTiers: null,
+        disputeBondBps: null,
+        disputeBondToTreasury: null,
+        severeRefundSlashBps: null,
+        releaseInsuranceBps: null,
+        oracleFeeBps: null,
+        switchboardQueue: null,
+        attestationMaxAgeSeconds: null,
+        pythQualityFeedId: null,
+      };
+      const step = (maxQualityScore: number, refundPercentage: number) => ({
+        maxQualityScore,
+        refundPercentage,
+      });
+
+      try {
+        await program.methods
+          .updateGlobalConfig({
+            ...noChanges,
+            refundCurve: [step(20, 50), step(40, 75), step(60, 50), step(80, 25), step(100, 0)],
+          })
+          .accounts({ config: configPda, authority: platform.publicKey })
+          .signers([platform])
+          .rpc();
+        expect.fail("Should have rejected refunds that rise with quality");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidRefundCurve");
+      }
+
+      await program.methods
+        .updateGlobalConfig({
+          ...noChanges,
+          refundCurve: [step(20, 100), step(40, 100), step(60, 100), step(80, 50), step(100, 0)],
+        })
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      // A score of 50 refunds half under the default curve, all of it here
+      const { apiGain } = await resolveInNamespace(namespace, treasury);
+      expect(apiGain).to.equal(0);
+    });
+
+    it("caps escrow amounts by the agent's reputation tier", async () => {
+      const { namespace, platform } = await setupNamespace(0);
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const cap = new anchor.BN(ESCROW_AMOUNT / 2);
+      await program.methods
+        .updateGlobalConfig({
+          strictSigAdjacency: null,
+          spamProtectionDeposit: null,
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps: null,
+          treasury: null,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: null,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: null,
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: null,
+          pythSolUsdFeedId: null,
+          // Every score is capped, whatever the test agent has built up
+          amountTiers: [
+            { minReputationScore: 0, maxAmount: cap },
+            { minReputationScore: 999, maxAmount: cap },
+            { minReputationScore: 1000, maxAmount: cap },
+          ],
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: null,
+          oracleFeeBps: null,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      await ensureReputation(agent.publicKey);
+      const open = async (amount: number) => {
+        const transactionId = `tier_${Date.now()}_${amount}`;
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(amount),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            namespace,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: PublicKey.findProgramAddressSync(
+              [Buffer.from("escrow"), Buffer.from(transactionId)],
+              program.programId
+            )[0],
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      };
+
+      try {
+        await open(ESCROW_AMOUNT);
+        expect.fail("Should have capped the escrow at the agent's tier");
+      } catch (err) {
+        expect(err.toString()).to.include("AmountExceedsReputationTier");
+      }
+      await open(ESCROW_AMOUNT / 2);
+    });
+
+    async function settleResolution(escrowPda: PublicKey, treasury: PublicKey) {
+      await program.methods
+        .settleResolution()
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
           api: api.publicKey,
+          verifier: verifier.publicKey,
+          treasury,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
+    }
 
-      const apiBalanceAfter = await provider.connection.getBalance(api.publicKey);
-      const apiGain = apiBalanceAfter - apiBalanceBefore;
+    it("holds submitted resolutions through the appeal window", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 3600);
 
-      expect(apiGain).to.be.greaterThan(ESCROW_AMOUNT * 0.99);
+      try {
+        await resolveInNamespace(namespace, treasury);
+        expect.fail("Should have required a two-phase resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("TwoPhaseResolutionRequired");
+      }
+
+      const { escrowPda, apiGain } = await resolveInNamespace(namespace, treasury, true);
+      expect(apiGain).to.equal(0);
+
+      const held = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(held.flags)).to.equal("disputed");
+      expect(held.refundPercentage).to.equal(50);
+      expect(held.firstVerifier.toBase58()).to.equal(verifier.publicKey.toBase58());
+      expect(held.resolutionSignature).to.have.lengthOf(64);
+
+      try {
+        await settleResolution(escrowPda, treasury);
+        expect.fail("Should have waited for the appeal window");
+      } catch (err) {
+        expect(err.toString()).to.include("AppealWindowOpen");
+      }
+
+      await program.methods
+        .appealResolution()
+        .accounts({ escrow: escrowPda, appellant: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const appealed = await program.account.escrow.fetch(escrowPda);
+      expect(appealed.flags & (1 << 10)).to.not.equal(0);
+
+      try {
+        await program.methods
+          .appealResolution()
+          .accounts({ escrow: escrowPda, appellant: agent.publicKey })
+          .rpc();
+        expect.fail("Should have rejected a second appeal");
+      } catch (err) {
+        expect(err.toString()).to.include("AlreadyAppealed");
+      }
+    });
+
+    it("settles a submitted resolution once the appeal window closes", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 1);
+      const { escrowPda } = await resolveInNamespace(namespace, treasury, true);
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      await settleResolution(escrowPda, treasury);
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+      const apiGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+      expect(apiGain).to.equal(ESCROW_AMOUNT * 0.5);
+    });
+
+    it("settles a direct and a two-phase resolution the same way", async () => {
+      const direct = await setupNamespace(0);
+      const twoPhase = await setupNamespace(0, 1);
+
+      const resolved: any[] = [];
+      const listener = program.addEventListener("disputeResolved", (event) => {
+        resolved.push(event);
+      });
+      let directEscrow: PublicKey;
+      let heldEscrow: PublicKey;
+      let settledGain: number;
+      try {
+        ({ escrowPda: directEscrow } = await resolveInNamespace(direct.namespace, direct.treasury));
+        ({ escrowPda: heldEscrow } = await resolveInNamespace(twoPhase.namespace, twoPhase.treasury, true));
+        await new Promise((resolve) => setTimeout(resolve, 2000));
+        const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+        await settleResolution(heldEscrow, twoPhase.treasury);
+        settledGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      expect(settledGain).to.equal(ESCROW_AMOUNT * 0.5);
+      const [first, second] = [directEscrow, heldEscrow].map((escrow) =>
+        resolved.find((event) => event.escrow.toBase58() === escrow.toBase58())
+      );
+      for (const field of ["qualityScore", "refundPercentage"]) {
+        expect(second[field]).to.equal(first[field]);
+      }
+      expect(second.refundAmount.toNumber()).to.equal(first.refundAmount.toNumber());
+      expect(second.paymentAmount.toNumber()).to.equal(first.paymentAmount.toNumber());
+      expect(second.method).to.deep.equal(first.method);
+
+      for (const escrowPda of [directEscrow, heldEscrow]) {
+        const escrow = await program.account.escrow.fetch(escrowPda);
+        expect(escrowStatus(escrow.flags)).to.equal("resolved");
+        expect(escrow.qualityScore).to.equal(50);
+        expect(escrow.settledAt).to.not.be.null;
+      }
+    });
+
+    it("never settles an appealed resolution, and only the parties may appeal", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 3);
+      const { escrowPda } = await resolveInNamespace(namespace, treasury, true);
+
+      const stranger = Keypair.generate();
+      try {
+        await program.methods
+          .appealResolution()
+          .accounts({ escrow: escrowPda, appellant: stranger.publicKey })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Should have rejected an outsider's appeal");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .appealResolution()
+        .accounts({ escrow: escrowPda, appellant: agent.publicKey })
+        .rpc();
+      await new Promise((resolve) => setTimeout(resolve, 4000));
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      try {
+        await settleResolution(escrowPda, treasury);
+        expect.fail("Should have left the appealed ruling unsettled");
+      } catch (err) {
+        expect(err.toString()).to.include("AlreadyAppealed");
+      }
+      expect(await provider.connection.getBalance(api.publicKey)).to.equal(apiBalanceBefore);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("disputed");
+    });
+
+    it("closes every single-step resolution path once the appeal window is set", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 3600);
+      const [config] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const [verifierRegistry] = PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier_registry"), namespace.toBuffer()],
+        program.programId
+      );
+      const [verifierNonces] = PublicKey.findProgramAddressSync(
+        [Buffer.from("used_nonces"), verifier.publicKey.toBuffer()],
+        program.programId
+      );
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+
+      const transactionId = `two_phase_paths_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          namespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const expectTwoPhase = async (attempt: Promise<string>) => {
+        try {
+          await attempt;
+          expect.fail("Should have required a two-phase resolution");
+        } catch (err) {
+          expect(err.toString()).to.include("TwoPhaseResolutionRequired");
+        }
+      };
+
+      // Each path is rejected before any signature or attestation is read
+      await expectTwoPhase(
+        program.methods
+          .batchResolveDisputesEd25519([
+            { qualityScore: 50, contentHash, nonce: new anchor.BN(0), signature: new Array(64).fill(0) },
+          ])
+          .accounts({
+            verifier: verifier.publicKey,
+            instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+            verifierRegistry,
+            verifierNonces,
+            config,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc()
+      );
+      await expectTwoPhase(
+        program.methods
+          .resolveDisputeSwitchboard(50)
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            switchboardFunction: Keypair.generate().publicKey,
+            // The namespace trusts no queue
+            switchboardQueue: PublicKey.default,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc()
+      );
+
+      const mint = await createMint(provider.connection, agent.payer, agent.publicKey, null, 6);
+      const agentTokens = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, agent.payer, mint, agent.publicKey)
+      ).address;
+      const apiTokens = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, agent.payer, mint, api.publicKey)
+      ).address;
+      await mintTo(provider.connection, agent.payer, mint, agentTokens, agent.publicKey, 10_000_000);
+      const tokenId = `two_phase_spl_${Date.now()}`;
+      const [tokenEscrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(tokenId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrowSpl(new anchor.BN(10_000_000), new anchor.BN(TIME_LOCK), tokenId, namespace)
+        .accounts({
+          escrow: tokenEscrowPda,
+          mint,
+          agent: agent.publicKey,
+          agentTokenAccount: agentTokens,
+          api: api.publicKey,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: tokenEscrowPda, agent: agent.publicKey })
+        .rpc();
+      await expectTwoPhase(
+        program.methods
+          .resolveDisputeSpl(50, contentHash, new anchor.BN(0), new Array(64).fill(0))
+          .accounts({
+            escrow: tokenEscrowPda,
+            mint,
+            agentTokenAccount: agentTokens,
+            apiTokenAccount: apiTokens,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+          })
+          .rpc()
+      );
+
+      for (const escrow of [escrowPda, tokenEscrowPda]) {
+        const account = await program.account.escrow.fetch(escrow);
+        expect(escrowStatus(account.flags)).to.equal("disputed");
+      }
+    });
+
+    it("lets a quorum decide only appealed rulings once the appeal window is set", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(0, 3600);
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const [verifierSetPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier_set"), namespace.toBuffer()],
+        program.programId
+      );
+      const members = [Keypair.generate(), Keypair.generate()];
+      await program.methods
+        .configureVerifierSet(members.map((m) => m.publicKey), 2)
+        .accounts({ verifierSet: verifierSetPda, config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      const { escrowPda } = await resolveInNamespace(namespace, treasury, true);
+      const decide = () =>
+        program.methods
+          .resolveDisputeQuorum(50, contentHash, new anchor.BN(0), [
+            { verifierIndex: 0, signature: new Array(64).fill(0) },
+          ])
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: members[0].publicKey,
+            verifierSet: verifierSetPda,
+            config: configPda,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      // The held ruling runs its appeal window first
+      try {
+        await decide();
+        expect.fail("Should have left the held ruling to its appeal window");
+      } catch (err) {
+        expect(err.toString()).to.include("ResolutionHeld");
+      }
+
+      // A fresh dispute cannot skip the attest-then-appeal flow through a quorum
+      const transactionId = `quorum_two_phase_${Date.now()}`;
+      const [freshPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          namespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: freshPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: freshPda, agent: agent.publicKey })
+        .rpc();
+      try {
+        await program.methods
+          .resolveDisputeQuorum(50, contentHash, new anchor.BN(0), [
+            { verifierIndex: 0, signature: new Array(64).fill(0) },
+          ])
+          .accounts({
+            escrow: freshPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: members[0].publicKey,
+            verifierSet: verifierSetPda,
+            config: configPda,
+            treasury,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required a two-phase resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("TwoPhaseResolutionRequired");
+      }
+
+      // Once appealed, the quorum takes the case and only needs its signatures
+      await program.methods
+        .appealResolution()
+        .accounts({ escrow: escrowPda, appellant: api.publicKey })
+        .signers([api])
+        .rpc();
+      try {
+        await decide();
+        expect.fail("Should have required a quorum");
+      } catch (err) {
+        expect(err.toString()).to.include("QuorumNotReached");
+      }
+    });
+
+    it("closes the appeal window when it lapses", async () => {
+      const { namespace, treasury } = await setupNamespace(0, 1);
+      const { escrowPda } = await resolveInNamespace(namespace, treasury, true);
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+
+      try {
+        await program.methods
+          .appealResolution()
+          .accounts({ escrow: escrowPda, appellant: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected a late appeal");
+      } catch (err) {
+        expect(err.toString()).to.include("AppealWindowClosed");
+      }
+    });
+
+    it("lets an arbitrator adjust an escalated resolution within bounds", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(0, 3600);
+      const arbitrator = Keypair.generate();
+      const [arbitratorPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("arbitrator"), namespace.toBuffer(), arbitrator.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .registerArbitrator(arbitrator.publicKey)
+        .accounts({ arbitrator: arbitratorPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      const { escrowPda } = await resolveInNamespace(namespace, treasury, true);
+      await program.methods
+        .escalateToArbitrator()
+        .accounts({ escrow: escrowPda, arbitrator: arbitratorPda, escalator: agent.publicKey })
+        .rpc();
+
+      const escalated = await program.account.escrow.fetch(escrowPda);
+      expect(escalated.flags & (1 << 12)).to.not.equal(0);
+      expect(escalated.escalatedBy.toBase58()).to.equal(agent.publicKey.toBase58());
+
+      try {
+        await program.methods
+          .appealResolution()
+          .accounts({ escrow: escrowPda, appellant: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected an appeal of an escalated resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("AlreadyEscalated");
+      }
+
+      const rule = (refundPercentage: number) =>
+        program.methods
+          .arbitratorRule(refundPercentage)
+          .accounts({
+            escrow: escrowPda,
+            arbitrator: arbitratorPda,
+            authority: arbitrator.publicKey,
+            agent: agent.publicKey,
+            api: api.publicKey,
+          })
+          .signers([arbitrator])
+          .rpc();
+
+      try {
+        await rule(90);
+        expect.fail("Should have bounded the deviation from the oracle");
+      } catch (err) {
+        expect(err.toString()).to.include("ArbitratorDeviationExceeded");
+      }
+
+      const apiBeforeRuling = await provider.connection.getBalance(api.publicKey);
+      await rule(70);
+      // Moved toward the agent, so its bond comes back rather than going to the API
+      const ruled = await program.account.escrow.fetch(escrowPda);
+      expect(ruled.refundPercentage).to.equal(70);
+      expect(ruled.arbitrationBond.toNumber()).to.equal(0);
+      expect(await provider.connection.getBalance(api.publicKey)).to.equal(apiBeforeRuling);
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      await settleResolution(escrowPda, treasury);
+      const apiGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+      expect(apiGain).to.equal(ESCROW_AMOUNT * 0.3);
+    });
+
+    it("holds arbitrator rulings to a work agreement's quality minimum", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(0, 3600);
+      const arbitrator = Keypair.generate();
+      const [arbitratorPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("arbitrator"), namespace.toBuffer(), arbitrator.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .registerArbitrator(arbitrator.publicKey)
+        .accounts({ arbitrator: arbitratorPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      const transactionId = `sla_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const [workAgreement] = PublicKey.findProgramAddressSync(
+        [Buffer.from("work_agreement"), escrowPda.toBuffer()],
+        program.programId
+      );
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          namespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      const minQualityScore = 80;
+      await program.methods
+        .createWorkAgreement("btc ohlcv 2024", 0b111, 100, 7, minQualityScore, null, [0, 0, 0, 0])
+        .accounts({
+          workAgreement,
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      // The verifier scores just under the agreed minimum
+      const qualityScore = minQualityScore - 1;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}:100:7:${minQualityScore}`),
+      });
+      await program.methods
+        .submitResolution(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({ escrow: escrowPda, verifier: verifier.publicKey, workAgreement })
+        .preInstructions([signatureIx])
+        .rpc();
+      expect((await program.account.escrow.fetch(escrowPda)).refundPercentage).to.equal(100);
+
+      await program.methods
+        .escalateToArbitrator()
+        .accounts({ escrow: escrowPda, arbitrator: arbitratorPda, escalator: agent.publicKey })
+        .rpc();
+
+      const rule = (refundPercentage: number, agreement: PublicKey | null) =>
+        program.methods
+          .arbitratorRule(refundPercentage)
+          .accounts({
+            escrow: escrowPda,
+            arbitrator: arbitratorPda,
+            authority: arbitrator.publicKey,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            workAgreement: agreement,
+          })
+          .signers([arbitrator])
+          .rpc();
+
+      try {
+        await rule(80, null);
+        expect.fail("Should have required the work agreement");
+      } catch (err) {
+        expect(err.toString()).to.include("WorkAgreementRequired");
+      }
+
+      // A ruling within the deviation bound still cannot waive the minimum
+      await rule(80, workAgreement);
+      const ruled = await program.account.escrow.fetch(escrowPda);
+      expect(ruled.refundPercentage).to.equal(100);
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+      await settleResolution(escrowPda, treasury);
+      expect(await provider.connection.getBalance(api.publicKey)).to.equal(apiBalanceBefore);
+    });
+
+    it("collects fees in the treasury PDA for the authority to withdraw", async () => {
+      const { namespace, platform } = await setupNamespace(500);
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const [treasuryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("treasury"), namespace.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeTreasury()
+        .accounts({ treasury: treasuryPda, config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      const config = await program.account.globalConfig.fetch(configPda);
+      expect(config.treasury.toBase58()).to.equal(treasuryPda.toBase58());
+
+      const { feeCollected } = await resolveInNamespace(namespace, treasuryPda);
+      expect(feeCollected).to.equal((ESCROW_AMOUNT / 2) * 0.05);
+
+      const destination = Keypair.generate().publicKey;
+      await program.methods
+        .withdrawFees(new anchor.BN(feeCollected))
+        .accounts({
+          treasury: treasuryPda,
+          config: configPda,
+          authority: platform.publicKey,
+          destination,
+        })
+        .signers([platform])
+        .rpc();
+
+      expect(await provider.connection.getBalance(destination)).to.equal(feeCollected);
+      const treasury = await program.account.protocolTreasury.fetch(treasuryPda);
+      expect(treasury.totalWithdrawn.toNumber()).to.equal(feeCollected);
+
+      try {
+        await program.methods
+          .withdrawFees(new anchor.BN(1))
+          .accounts({
+            treasury: treasuryPda,
+            config: configPda,
+            authority: platform.publicKey,
+            destination,
+          })
+          .signers([platform])
+          .rpc();
+        expect.fail("Should have kept the treasury rent-exempt");
+      } catch (err) {
+        expect(err.toString()).to.include("EscrowBelowRentExempt");
+      }
+    });
+
+    it("takes the fee on release and lets only the authority withdraw", async () => {
+      const { namespace, treasury, platform } = await setupNamespace(500);
+      const transactionId = `fee_release_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          namespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const apiBefore = await provider.connection.getBalance(api.publicKey);
+      const treasuryBefore = await provider.connection.getBalance(treasury);
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          treasury,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      expect((await provider.connection.getBalance(treasury)) - treasuryBefore).to.equal(
+        ESCROW_AMOUNT * 0.05
+      );
+      expect((await provider.connection.getBalance(api.publicKey)) - apiBefore).to.equal(
+        ESCROW_AMOUNT * 0.95
+      );
+
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), namespace.toBuffer()],
+        program.programId
+      );
+      const [treasuryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("treasury"), namespace.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeTreasury()
+        .accounts({ treasury: treasuryPda, config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      const stranger = Keypair.generate();
+      try {
+        await program.methods
+          .withdrawFees(new anchor.BN(0))
+          .accounts({
+            treasury: treasuryPda,
+            config: configPda,
+            authority: stranger.publicKey,
+            destination: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Should have required the namespace authority");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+    });
+  });
+
+  describe("verifier bonds", () => {
+    let platform: Keypair;
+    let configPda: PublicKey;
+
+    const noChanges = {
+      strictSigAdjacency: null,
+      spamProtectionDeposit: null,
+      solUsdPriceFeed: null,
+      maxOverrideDurationSeconds: null,
+      feeBps: null,
+      treasury: null,
+      minVerifierBondLamports: null,
+      bondGracePeriodDays: null,
+      maxDisputeWindowExtensions: null,
+      insuranceFundBps: null,
+      maxInsurancePayoutBps: null,
+      maxPriorityFeeLamports: null,
+      usdStablecoinMint: null,
+      cancelGracePeriodSeconds: null,
+      refundCurve: null,
+      appealWindowSeconds: null,
+      pythSolUsdFeedId: null,
+      amountTiers: null,
+      disputeBondBps: null,
+      disputeBondToTreasury: null,
+      severeRefundSlashBps: null,
+      releaseInsuranceBps: null,
+      oracleFeeBps: null,
+      switchboardQueue: null,
+      attestationMaxAgeSeconds: null,
+      pythQualityFeedId: null,
+    };
+
+    beforeEach(async () => {
+      platform = Keypair.generate();
+      for (const key of [platform.publicKey, verifier.publicKey]) {
+        const sig = await provider.connection.requestAirdrop(key, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+      [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await program.methods
+        .initializeAssignmentQueue()
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await program.methods
+        .updateGlobalConfig({ ...noChanges, minVerifierBondLamports: new anchor.BN(LAMPORTS_PER_SOL / 10) })
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+    });
+
+    async function postBond(lamports: number) {
+      await program.methods
+        .postVerifierBond(new anchor.BN(lamports))
+        .accounts({ verifier: verifier.publicKey })
+        .signers([verifier])
+        .rpc();
+    }
+
+    async function register() {
+      await program.methods
+        .registerVerifierOracle(3)
+        .accounts({ verifier: verifier.publicKey, config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+    }
+
+    async function crank() {
+      await program.methods
+        .checkVerifierBondCompliance(verifier.publicKey)
+        .accounts({ config: configPda })
+        .rpc();
+    }
+
+    it("rejects registration below the minimum bond", async () => {
+      await postBond(LAMPORTS_PER_SOL / 20);
+      try {
+        await register();
+        expect.fail("Should have rejected an underbonded verifier");
+      } catch (err) {
+        expect(err.toString()).to.include("InsufficientVerifierBond");
+      }
+    });
+
+    it("deregisters underbonded verifiers only after the grace period", async () => {
+      await postBond(LAMPORTS_PER_SOL / 10);
+      await register();
+
+      try {
+        await crank();
+        expect.fail("Compliant verifier should not be deregistered");
+      } catch (err) {
+        expect(err.toString()).to.include("VerifierBondCompliant");
+      }
+
+      // Governance raises the minimum with a one day grace period
+      await program.methods
+        .updateGlobalConfig({
+          ...noChanges,
+          minVerifierBondLamports: new anchor.BN(LAMPORTS_PER_SOL),
+          bondGracePeriodDays: 1,
+        })
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      try {
+        await crank();
+        expect.fail("Should respect the grace period");
+      } catch (err) {
+        expect(err.toString()).to.include("BondGracePeriodActive");
+      }
+
+      // Grace period shortened to zero: the crank now deregisters
+      await program.methods
+        .updateGlobalConfig({ ...noChanges, bondGracePeriodDays: 0 })
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await crank();
+
+      const [oraclePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier_oracle"), platform.publicKey.toBuffer(), verifier.publicKey.toBuffer()],
+        program.programId
+      );
+      const oracle = await program.account.verifierOracleAccount.fetch(oraclePda);
+      expect(oracle.active).to.equal(false);
+
+      const [queuePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("assign_queue"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+      const queue = await program.account.assignmentQueue.fetch(queuePda);
+      expect(queue.verifierPubkeys.map((k) => k.toBase58())).to.not.include(
+        verifier.publicKey.toBase58()
+      );
+    });
+  });
+
+  describe("priority fees", () => {
+    const PRIORITY_FEE = 5_000_000;
+    const contentHash = new Array(32).fill(0);
+    const contentHashHex = Buffer.from(contentHash).toString("hex");
+    let platform: Keypair;
+
+    beforeEach(async () => {
+      platform = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(platform.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await program.methods
+        .updateGlobalConfig({
+          strictSigAdjacency: null,
+          spamProtectionDeposit: null,
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps: null,
+          treasury: null,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: null,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: new anchor.BN(2 * PRIORITY_FEE),
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: null,
+          pythSolUsdFeedId: null,
+          amountTiers: null,
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: null,
+          oracleFeeBps: null,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({
+          config: PublicKey.findProgramAddressSync(
+            [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          authority: platform.publicKey,
+        })
+        .signers([platform])
+        .rpc();
+      await registerVerifier(verifier.publicKey, platform);
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+    });
+
+    async function disputeWithFee(priorityFee: number, refundRecipient: PublicKey | null = null) {
+      const transactionId = `priority_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          refundRecipient,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(priorityFee), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      return { transactionId, escrowPda };
+    }
+
+    it("escrows the fee up to the namespace cap", async () => {
+      try {
+        await disputeWithFee(3 * PRIORITY_FEE);
+        expect.fail("Should have rejected a fee above the cap");
+      } catch (err) {
+        expect(err.toString()).to.include("PriorityFeeTooLarge");
+      }
+
+      const { escrowPda } = await disputeWithFee(PRIORITY_FEE);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.priorityFee.toNumber()).to.equal(PRIORITY_FEE);
+
+      const info = await provider.connection.getAccountInfo(escrowPda);
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(info.data.length);
+      expect(info.lamports).to.equal(rent + ESCROW_AMOUNT + PRIORITY_FEE);
+    });
+
+    it("pays the fee to the verifier on resolution", async () => {
+      const { transactionId, escrowPda } = await disputeWithFee(PRIORITY_FEE);
+      const qualityScore = 50;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      expect(await provider.connection.getBalance(verifier.publicKey)).to.equal(PRIORITY_FEE);
+    });
+
+    it("pays the refund to the escrow's refund recipient", async () => {
+      const coldWallet = Keypair.generate().publicKey;
+      const { escrowPda } = await disputeWithFee(0, coldWallet);
+      const qualityScore = 20;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+      const resolve = (refundRecipient: PublicKey | null) =>
+        program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            refundRecipient,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+
+      try {
+        await resolve(null);
+        expect.fail("Should have required the refund recipient");
+      } catch (err) {
+        expect(err.toString()).to.include("RefundRecipientMismatch");
+      }
+
+      await resolve(coldWallet);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.refundedToAgent.toNumber()).to.be.greaterThan(0);
+      expect(await provider.connection.getBalance(coldWallet)).to.equal(escrow.refundedToAgent.toNumber());
+    });
+
+    it("refunds the fee to the agent on mutual settlement", async () => {
+      const { escrowPda } = await disputeWithFee(PRIORITY_FEE);
+      const agentBefore = await provider.connection.getBalance(agent.publicKey);
+
+      await program.methods
+        .settleMutual(40)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const agentGain = (await provider.connection.getBalance(agent.publicKey)) - agentBefore;
+      // Less the transaction fee the agent paid
+      expect(agentGain).to.be.closeTo(ESCROW_AMOUNT * 0.4 + PRIORITY_FEE, 10_000);
+      expect(await provider.connection.getBalance(api.publicKey)).to.equal(ESCROW_AMOUNT * 0.6);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.refundPercentage).to.equal(40);
+    });
+  });
+
+  describe("payment references", () => {
+    const reference = {
+      methodAndPathHash: new Array(32).fill(1),
+      challengeNonce: new Array(16).fill(9),
+      clientIdHash: new Array(32).fill(2),
+    };
+
+    async function referencedEscrow(
+      paymentReference: typeof reference | null,
+      requestHash: number[] | null = null
+    ) {
+      const transactionId = `reference_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          paymentReference,
+          requestHash,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return escrowPda;
+    }
+
+    it("stores the 402 exchange on the escrow", async () => {
+      const escrowPda = await referencedEscrow(reference);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.paymentReference).to.deep.equal(reference);
+
+      const unreferenced = await program.account.escrow.fetch(await referencedEscrow(null));
+      expect(unreferenced.paymentReference).to.be.null;
+    });
+
+    it("rejects a zeroed challenge nonce", async () => {
+      try {
+        await referencedEscrow({ ...reference, challengeNonce: new Array(16).fill(0) });
+        expect.fail("Should have rejected a zeroed nonce");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidChallengeNonce");
+      }
+    });
+
+    it("requires the API to echo the challenge nonce on delivery", async () => {
+      const escrowPda = await referencedEscrow(reference);
+      const responseHash = new Array(32).fill(3);
+
+      for (const nonce of [null, new Array(16).fill(8)]) {
+        try {
+          await program.methods
+            .acknowledgeDelivery(responseHash, nonce, false)
+            .accounts({ escrow: escrowPda, api: api.publicKey })
+            .signers([api])
+            .rpc();
+          expect.fail("Should have rejected a mismatched nonce");
+        } catch (err) {
+          expect(err.toString()).to.include("ChallengeNonceMismatch");
+        }
+      }
+
+      await program.methods
+        .acknowledgeDelivery(responseHash, reference.challengeNonce, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.responseHash).to.deep.equal(responseHash);
+    });
+
+    it("brings the time lock forward to the dispute deadline on request", async () => {
+      const escrowPda = await referencedEscrow(null);
+      const before = await program.account.escrow.fetch(escrowPda);
+
+      await program.methods
+        .acknowledgeDelivery(new Array(32).fill(3), null, true)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      const deadline = escrow.deliveredAt.add(escrow.disputeWindow);
+      expect(escrow.expiresAt.toNumber()).to.equal(
+        Math.min(before.expiresAt.toNumber(), deadline.toNumber())
+      );
+    });
+
+    it("restarts a delivery-clocked time lock at delivery", async () => {
+      const escrowPda = await referencedEscrow(null);
+      await program.methods
+        .enableDeliveryClock()
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      await program.methods
+        .acknowledgeDelivery(new Array(32).fill(3), null, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.flags & (1 << 13)).to.not.equal(0);
+      expect(escrow.expiresAt.sub(escrow.deliveredAt).toNumber()).to.equal(TIME_LOCK);
+
+      try {
+        await program.methods
+          .enableDeliveryClock()
+          .accounts({ escrow: escrowPda, agent: agent.publicKey })
+          .rpc();
+        expect.fail("Should not enable the delivery clock after delivery");
+      } catch (err) {
+        expect(err.toString()).to.include("DeliveryAlreadyAcknowledged");
+      }
+    });
+
+    it("stores the request the agent committed to", async () => {
+      const requestHash = new Array(32).fill(4);
+      const escrowPda = await referencedEscrow(reference, requestHash);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.requestHash).to.deep.equal(requestHash);
+      expect(escrow.responseHash).to.equal(null);
+    });
+  });
+
+  describe("work agreements", () => {
+    const terms = {
+      query: "btc ohlcv 2024",
+      requiredFields: 0b111,
+      minRecords: 100,
+      maxAgeDays: 7,
+      minQualityScore: 80,
+    };
+
+    async function agreedEscrow() {
+      const transactionId = `agreement_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const [workAgreement] = PublicKey.findProgramAddressSync(
+        [Buffer.from("work_agreement"), escrowPda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { transactionId, escrowPda, workAgreement };
+    }
+
+    async function createAgreement(
+      escrowPda: PublicKey,
+      workAgreement: PublicKey,
+      refundCurve = null,
+      scoreWeights = [0, 0, 0, 0]
+    ) {
+      await program.methods
+        .createWorkAgreement(
+          terms.query,
+          terms.requiredFields,
+          terms.minRecords,
+          terms.maxAgeDays,
+          terms.minQualityScore,
+          refundCurve,
+          scoreWeights
+        )
+        .accounts({
+          workAgreement,
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    it("binds the agreement to the escrow", async () => {
+      const { escrowPda, workAgreement } = await agreedEscrow();
+      await createAgreement(escrowPda, workAgreement);
+
+      const agreement = await program.account.workAgreement.fetch(workAgreement);
+      expect(agreement.escrow.toString()).to.equal(escrowPda.toString());
+      expect(agreement.minRecords).to.equal(terms.minRecords);
+      expect(agreement.minQualityScore).to.equal(terms.minQualityScore);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.flags & (1 << 8)).to.not.equal(0);
+    });
+
+    it("rejects agreements after delivery is acknowledged", async () => {
+      const { escrowPda, workAgreement } = await agreedEscrow();
+      await program.methods
+        .acknowledgeDelivery(new Array(32).fill(3), null, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      try {
+        await createAgreement(escrowPda, workAgreement);
+        expect.fail("Should have rejected a late agreement");
+      } catch (err) {
+        expect(err.toString()).to.include("WorkAgreementTooLate");
+      }
+    });
+
+    it("enforces the agreement on resolution", async () => {
+      const { transactionId, escrowPda, workAgreement } = await agreedEscrow();
+      await createAgreement(escrowPda, workAgreement);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const accounts = {
+        escrow: escrowPda,
+        agent: agent.publicKey,
+        api: api.publicKey,
+        verifier: verifier.publicKey,
+        systemProgram: SystemProgram.programId,
+      };
+      const signature = new Array(64).fill(0);
+
+      try {
+        await program.methods
+          .resolveDispute(90, new Array(32).fill(0), new anchor.BN(0), signature)
+          .accounts(accounts)
+          .rpc();
+        expect.fail("Should have required the work agreement");
+      } catch (err) {
+        expect(err.toString()).to.include("WorkAgreementRequired");
+      }
+
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      const qualityScore = terms.minQualityScore - 1;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${"00".repeat(32)}:` +
+            `${terms.minRecords}:${terms.maxAgeDays}:${terms.minQualityScore}`
+        ),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, new Array(32).fill(0), nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({ ...accounts, workAgreement })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      // The curve alone refunds 25% at this score; missing the agreed minimum refunds it all
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.refundPercentage).to.equal(100);
+    });
+
+    it("settles on the agreement's refund curve", async () => {
+      const { escrowPda, workAgreement } = await agreedEscrow();
+      const refundCurve = [
+        { maxQualityScore: 59, refundPercentage: 80 },
+        { maxQualityScore: 69, refundPercentage: 60 },
+        { maxQualityScore: 89, refundPercentage: 20 },
+        { maxQualityScore: 99, refundPercentage: 5 },
+        { maxQualityScore: 100, refundPercentage: 0 },
+      ];
+      await createAgreement(escrowPda, workAgreement, refundCurve);
+      expect((await program.account.workAgreement.fetch(workAgreement)).refundCurve).to.deep.equal(refundCurve);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+
+      const qualityScore = 85;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${"00".repeat(32)}:` +
+            `${terms.minRecords}:${terms.maxAgeDays}:${terms.minQualityScore}`
+        ),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, new Array(32).fill(0), nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          workAgreement,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      // The namespace's curve would refund nothing at 85
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.refundPercentage).to.equal(20);
+    });
+
+    it("aggregates weighted sub-scores on chain", async () => {
+      const { escrowPda, workAgreement } = await agreedEscrow();
+      await createAgreement(escrowPda, workAgreement, null, [20, 50, 30, 0]);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+
+      const accounts = {
+        escrow: escrowPda,
+        agent: agent.publicKey,
+        api: api.publicKey,
+        verifier: verifier.publicKey,
+        workAgreement,
+        systemProgram: SystemProgram.programId,
+      };
+      try {
+        await program.methods
+          .resolveDispute(90, new Array(32).fill(0), new anchor.BN(0), new Array(64).fill(0))
+          .accounts(accounts)
+          .rpc();
+        expect.fail("Should have required per-dimension scores");
+      } catch (err) {
+        expect(err.toString()).to.include("SubScoresRequired");
+      }
+
+      // 0.2 * 100 + 0.5 * 90 + 0.3 * 85 = 90.5, rounded down
+      const subScores = [100, 90, 85, 0];
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:90:${"00".repeat(32)}:` +
+            `${terms.minRecords}:${terms.maxAgeDays}:${terms.minQualityScore}:` +
+            subScores.join(":")
+        ),
+      });
+      await program.methods
+        .resolveDisputeScored(subScores, new Array(32).fill(0), nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts(accounts)
+        .preInstructions([signatureIx])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.qualityScore).to.equal(90);
+      expect(escrow.qualitySubScores).to.deep.equal(subScores);
+    });
+  });
+
+  describe("service bonds", () => {
+    const BOND = 2 * LAMPORTS_PER_SOL;
+    const terms = {
+      qualityThreshold: 60,
+      slashBps: 2_500,
+      maxSlashPerIncident: new anchor.BN(LAMPORTS_PER_SOL / 4),
+      minActiveBond: new anchor.BN(LAMPORTS_PER_SOL),
+    };
+    const termsHash = new Array(32).fill(7);
+    const contentHash = new Array(32).fill(0);
+    const contentHashHex = Buffer.from(contentHash).toString("hex");
+    let bondedApi: Keypair;
+    let bondPda: PublicKey;
+
+    beforeEach(async () => {
+      bondedApi = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(bondedApi.publicKey, 3 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [bondPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("service_bond"), bondedApi.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .postServiceBond(new anchor.BN(BOND), termsHash, terms)
+        .accounts({ provider: bondedApi.publicKey })
+        .signers([bondedApi])
+        .rpc();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(bondedApi.publicKey);
+    });
+
+    async function coveredEscrow() {
+      const transactionId = `bonded_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: bondedApi.publicKey,
+          serviceBond: bondPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { transactionId, escrowPda };
+    }
+
+    async function resolve(transactionId: string, escrowPda: PublicKey, qualityScore: number) {
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: bondedApi.publicKey,
+          verifier: verifier.publicKey,
+          serviceBond: bondPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+    }
+
+    it("marks escrows covered by the bond", async () => {
+      const { escrowPda } = await coveredEscrow();
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.flags & (1 << 7)).to.not.equal(0);
+
+      const bond = await program.account.serviceBond.fetch(bondPda);
+      expect(bond.bondedLamports.toNumber()).to.equal(BOND);
+      expect(bond.terms.qualityThreshold).to.equal(60);
+    });
+
+    it("rejects top-ups that change the terms", async () => {
+      try {
+        await program.methods
+          .postServiceBond(new anchor.BN(LAMPORTS_PER_SOL / 10), termsHash, { ...terms, slashBps: 5_000 })
+          .accounts({ provider: bondedApi.publicKey })
+          .signers([bondedApi])
+          .rpc();
+        expect.fail("Should have rejected changed terms");
+      } catch (err) {
+        expect(err.toString()).to.include("ServiceBondTermsMismatch");
+      }
+    });
+
+    it("slashes the bond to the agent below the quality threshold", async () => {
+      const { transactionId, escrowPda } = await coveredEscrow();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const agentBefore = await provider.connection.getBalance(agent.publicKey);
+      await resolve(transactionId, escrowPda, 30);
+
+      // 25% of the bond, capped at a quarter SOL
+      const slash = LAMPORTS_PER_SOL / 4;
+      const bond = await program.account.serviceBond.fetch(bondPda);
+      expect(bond.bondedLamports.toNumber()).to.equal(BOND - slash);
+      expect(bond.totalSlashed.toNumber()).to.equal(slash);
+      const agentGain = (await provider.connection.getBalance(agent.publicKey)) - agentBefore;
+      expect(agentGain).to.be.closeTo(ESCROW_AMOUNT * 0.75 + slash, 10_000);
+    });
+
+    it("leaves the bond alone at or above the threshold", async () => {
+      const { transactionId, escrowPda } = await coveredEscrow();
+      await resolve(transactionId, escrowPda, 60);
+
+      const bond = await program.account.serviceBond.fetch(bondPda);
+      expect(bond.bondedLamports.toNumber()).to.equal(BOND);
+    });
+
+    it("slashes on near-total refunds even under lenient terms", async () => {
+      // A provider whose own terms never slash
+      bondedApi = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(bondedApi.publicKey, 3 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [bondPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("service_bond"), bondedApi.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .postServiceBond(new anchor.BN(BOND), termsHash, { ...terms, qualityThreshold: 0, slashBps: 0 })
+        .accounts({ provider: bondedApi.publicKey })
+        .signers([bondedApi])
+        .rpc();
+      await ensureReputation(bondedApi.publicKey);
+
+      const { transactionId, escrowPda } = await coveredEscrow();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await resolve(transactionId, escrowPda, 10);
+
+      // Full refund: the namespace's default 10% of the bond
+      const bond = await program.account.serviceBond.fetch(bondPda);
+      expect(bond.totalSlashed.toNumber()).to.equal(BOND / 10);
+    });
+
+    it("stays slashable for in-flight disputes during the withdrawal cooldown", async () => {
+      const { transactionId, escrowPda } = await coveredEscrow();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      // Provider tries to pull the whole bond ahead of the ruling
+      await program.methods
+        .requestServiceBondWithdrawal(new anchor.BN(BOND))
+        .accounts({ provider: bondedApi.publicKey })
+        .signers([bondedApi])
+        .rpc();
+
+      try {
+        await program.methods
+          .withdrawServiceBond()
+          .accounts({ provider: bondedApi.publicKey })
+          .signers([bondedApi])
+          .rpc();
+        expect.fail("Should have enforced the cooldown");
+      } catch (err) {
+        expect(err.toString()).to.include("ServiceBondCooldownActive");
+      }
+
+      try {
+        await coveredEscrow();
+        expect.fail("Should not cover new escrows while withdrawing");
+      } catch (err) {
+        expect(err.toString()).to.include("ServiceBondWithdrawalPending");
+      }
+
+      await resolve(transactionId, escrowPda, 10);
+
+      const slash = LAMPORTS_PER_SOL / 4;
+      const bond = await program.account.serviceBond.fetch(bondPda);
+      expect(bond.bondedLamports.toNumber()).to.equal(BOND - slash);
+      // The slash comes out of what the provider gets back
+      expect(bond.pendingWithdrawal.toNumber()).to.equal(BOND - slash);
+    });
+
+    it("requires the bond to settle a covered escrow", async () => {
+      const { transactionId, escrowPda } = await coveredEscrow();
+      const qualityScore = 20;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+
+      try {
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: bondedApi.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+        expect.fail("Should have required the service bond");
+      } catch (err) {
+        expect(err.toString()).to.include("ServiceBondMissing");
+      }
+    });
+  });
+
+  describe("verifier pauses", () => {
+    let platform: Keypair;
+    let backup: Keypair;
+    let configPda: PublicKey;
+    let queuePda: PublicKey;
+
+    function oracleAddress(key: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier_oracle"), platform.publicKey.toBuffer(), key.toBuffer()],
+        program.programId
+      )[0];
+    }
+
+    beforeEach(async () => {
+      platform = Keypair.generate();
+      backup = Keypair.generate();
+      for (const key of [platform.publicKey, verifier.publicKey, backup.publicKey]) {
+        const sig = await provider.connection.requestAirdrop(key, LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+      [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+      [queuePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("assign_queue"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await program.methods
+        .initializeAssignmentQueue()
+        .accounts({ config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      for (const operator of [verifier, backup]) {
+        await program.methods
+          .postVerifierBond(new anchor.BN(LAMPORTS_PER_SOL / 100))
+          .accounts({ verifier: operator.publicKey })
+          .signers([operator])
+          .rpc();
+        await program.methods
+          .registerVerifierOracle(3)
+          .accounts({ verifier: operator.publicKey, config: configPda, authority: platform.publicKey })
+          .signers([platform])
+          .rpc();
+      }
+      await ensureReputation(agent.publicKey);
+    });
+
+    async function disputedEscrow(): Promise<PublicKey> {
+      const transactionId = `pause_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, assignmentQueue: queuePda })
+        .remainingAccounts(
+          [verifier, backup].map((operator) => ({
+            pubkey: oracleAddress(operator.publicKey),
+            isWritable: true,
+            isSigner: false,
+          }))
+        )
+        .rpc();
+      return escrowPda;
+    }
+
+    async function pause(escrows: PublicKey[]) {
+      const pauseUntil = Math.floor(Date.now() / 1000) + 3600;
+      await program.methods
+        .pauseSelfAsVerifier(new Array(32).fill(1), new anchor.BN(pauseUntil))
+        .accounts({ verifierOracle: oracleAddress(verifier.publicKey), verifier: verifier.publicKey })
+        .remainingAccounts(escrows.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })))
+        .signers([verifier])
+        .rpc();
+      return pauseUntil;
+    }
+
+    it("releases the verifier's assignments when it pauses", async () => {
+      const escrowPda = await disputedEscrow();
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.assignedVerifier.toBase58()).to.equal(verifier.publicKey.toBase58());
+
+      try {
+        await pause([]);
+        expect.fail("Assigned disputes must be handed back");
+      } catch (err) {
+        expect(err.toString()).to.include("VerifierHasAssignments");
+      }
+
+      const pauseUntil = await pause([escrowPda]);
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.assignedVerifier).to.be.null;
+      const oracle = await program.account.verifierOracleAccount.fetch(oracleAddress(verifier.publicKey));
+      expect(oracle.active).to.equal(false);
+      expect(oracle.activeAssignments).to.equal(0);
+      expect(oracle.pausedUntil.toNumber()).to.equal(pauseUntil);
+    });
+
+    it("skips paused verifiers in the assignment queue", async () => {
+      await pause([]);
+
+      const escrowPda = await disputedEscrow();
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.assignedVerifier.toBase58()).to.equal(backup.publicKey.toBase58());
+    });
+
+    it("cannot dispute around the assignment queue", async () => {
+      const transactionId = `unassigned_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      // Another namespace's queue address fails the seeds check
+      const [otherQueue] = PublicKey.findProgramAddressSync(
+        [Buffer.from("assign_queue"), PublicKey.default.toBuffer()],
+        program.programId
+      );
+      try {
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, assignmentQueue: otherQueue })
+          .rpc();
+        expect.fail("Should have required the namespace's assignment queue");
+      } catch (err) {
+        expect(err.toString()).to.include("ConstraintSeeds");
+      }
+
+      // The queue without its verifiers' oracles cannot assign anyone
+      try {
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, assignmentQueue: queuePda })
+          .rpc();
+        expect.fail("Should have required the queued verifiers");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidVerifierAccount");
+      }
+    });
+
+    it("keeps the verifier paused until pause_until", async () => {
+      await pause([]);
+
+      try {
+        await program.methods
+          .resumeVerifier()
+          .accounts({ verifierOracle: oracleAddress(verifier.publicKey), verifier: verifier.publicKey })
+          .signers([verifier])
+          .rpc();
+        expect.fail("Pause has not ended");
+      } catch (err) {
+        expect(err.toString()).to.include("VerifierPauseActive");
+      }
+    });
+  });
+
+  describe("used nonces", () => {
+    let signer: Keypair;
+    let usedNoncesPda: PublicKey;
+
+    beforeEach(async () => {
+      signer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(signer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [usedNoncesPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("used_nonces"), signer.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeUsedNonces()
+        .accounts({ signer: signer.publicKey })
+        .signers([signer])
+        .rpc();
+    });
+
+    async function invalidate(nonce: number) {
+      await program.methods
+        .invalidateNonce(new anchor.BN(nonce))
+        .accounts({ signer: signer.publicKey })
+        .signers([signer])
+        .rpc();
+    }
+
+    async function expectReused(nonce: number) {
+      try {
+        await invalidate(nonce);
+        expect.fail(`Nonce ${nonce} should be rejected`);
+      } catch (err) {
+        expect(err.toString()).to.include("NonceAlreadyUsed");
+      }
+    }
+
+    it("accepts nonces out of order and rejects reuse", async () => {
+      await invalidate(7);
+      await invalidate(2);
+      await invalidate(200);
+      await invalidate(3);
+
+      await expectReused(7);
+      await expectReused(2);
+      await expectReused(200);
+    });
+
+    it("advances the window and treats nonces below it as used", async () => {
+      await invalidate(10);
+      await invalidate(300);
+
+      // 300 sits in the last word of the window, which now starts at 64
+      const registry = await program.account.usedNonces.fetch(usedNoncesPda);
+      expect(registry.windowStart.toNumber()).to.equal(64);
+
+      await expectReused(10);
+      await expectReused(300);
+      await expectReused(63);
+      await invalidate(64);
+      await invalidate(250);
+
+      // A far jump clears the whole bitmap
+      await invalidate(10_000);
+      const advanced = await program.account.usedNonces.fetch(usedNoncesPda);
+      expect(advanced.windowStart.toNumber()).to.equal(9_792);
+      await invalidate(9_800);
+      await expectReused(250);
+    });
+  });
+
+  describe("spl escrows", () => {
+    const TOKEN_AMOUNT = 25_000_000; // 25 USDC at 6 decimals
+    const contentHash = new Array(32).fill(0);
+    let mint: PublicKey;
+    let agentTokens: PublicKey;
+    let apiTokens: PublicKey;
+
+    beforeEach(async () => {
+      mint = await createMint(provider.connection, agent.payer, agent.publicKey, null, 6);
+      agentTokens = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, agent.payer, mint, agent.publicKey)
+      ).address;
+      apiTokens = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, agent.payer, mint, api.publicKey)
+      ).address;
+      await mintTo(provider.connection, agent.payer, mint, agentTokens, agent.publicKey, 100_000_000);
+    });
+
+    async function tokenEscrow() {
+      const transactionId = `spl_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow_vault"), escrowPda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrowSpl(
+          new anchor.BN(TOKEN_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          anchor.web3.PublicKey.default
+        )
+        .accounts({
+          escrow: escrowPda,
+          mint,
+          agent: agent.publicKey,
+          agentTokenAccount: agentTokens,
+          api: api.publicKey,
+        })
+        .rpc();
+      return { transactionId, escrowPda, vaultPda };
+    }
+
+    async function tokenBalance(account: PublicKey): Promise<number> {
+      return Number((await getAccount(provider.connection, account)).amount);
+    }
+
+    it("locks tokens in a vault owned by the escrow", async () => {
+      const { escrowPda, vaultPda } = await tokenEscrow();
+
+      const vault = await getAccount(provider.connection, vaultPda);
+      expect(vault.owner.toBase58()).to.equal(escrowPda.toBase58());
+      expect(Number(vault.amount)).to.equal(TOKEN_AMOUNT);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.mint.toBase58()).to.equal(mint.toBase58());
+      expect(escrow.amount.toNumber()).to.equal(TOKEN_AMOUNT);
+    });
+
+    it("releases tokens to the API", async () => {
+      const { escrowPda, vaultPda } = await tokenEscrow();
+
+      await program.methods
+        .releaseFundsSpl()
+        .accounts({
+          escrow: escrowPda,
+          mint,
+          agent: agent.publicKey,
+          apiTokenAccount: apiTokens,
+        })
+        .rpc();
+
+      expect(await tokenBalance(apiTokens)).to.equal(TOKEN_AMOUNT);
+      expect(await tokenBalance(vaultPda)).to.equal(0);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("released");
+    });
+
+    it("splits tokens on dispute resolution", async () => {
+      const { transactionId, escrowPda } = await tokenEscrow();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      const agentBefore = await tokenBalance(agentTokens);
+      const qualityScore = 35;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+
+      await program.methods
+        .resolveDisputeSpl(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          mint,
+          agentTokenAccount: agentTokens,
+          apiTokenAccount: apiTokens,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      expect((await tokenBalance(agentTokens)) - agentBefore).to.equal(TOKEN_AMOUNT * 0.75);
+      expect(await tokenBalance(apiTokens)).to.equal(TOKEN_AMOUNT * 0.25);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("resolved");
+      expect(escrow.refundedToAgent.toNumber()).to.equal(TOKEN_AMOUNT * 0.75);
+    });
+
+    it("keeps token escrows out of the SOL settlement paths", async () => {
+      const { escrowPda } = await tokenEscrow();
+
+      try {
+        await program.methods
+          .releaseFunds()
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have rejected a token escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("TokenEscrowRequiresSplPath");
+      }
+    });
+
+    it("holds disputed token escrows until the dispute goes stale", async () => {
+      const { escrowPda } = await tokenEscrow();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      const settle = () =>
+        program.methods
+          .settleStaleDisputeSpl()
+          .accounts({
+            escrow: escrowPda,
+            mint,
+            agentTokenAccount: agentTokens,
+            apiTokenAccount: apiTokens,
+            agent: agent.publicKey,
+            api: api.publicKey,
+          })
+          .rpc();
+
+      // Undisputed escrows release through release_funds_spl instead
+      try {
+        await settle();
+        expect.fail("Escrow is not disputed");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      try {
+        await settle();
+        expect.fail("Dispute is not stale yet");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputeNotStale");
+      }
+
+      // Nothing moved while the dispute waits
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("disputed");
+      expect(escrowAccount.settledAt).to.be.null;
+    });
+  });
+
+  describe("token-2022 escrows", () => {
+    const TOKEN_AMOUNT = 25_000_000;
+    const FEE_BPS = 100;
+
+    async function token2022Mint(
+      extension: ExtensionType,
+      initExtension: (mint: PublicKey) => TransactionInstruction
+    ): Promise<PublicKey> {
+      const mint = Keypair.generate();
+      const space = getMintLen([extension]);
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: agent.publicKey,
+            newAccountPubkey: mint.publicKey,
+            space,
+            lamports: await provider.connection.getMinimumBalanceForRentExemption(space),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          initExtension(mint.publicKey),
+          createInitializeMintInstruction(mint.publicKey, 6, agent.publicKey, null, TOKEN_2022_PROGRAM_ID)
+        ),
+        [mint]
+      );
+      return mint.publicKey;
+    }
+
+    async function fundedTokenAccount(mint: PublicKey): Promise<PublicKey> {
+      const account = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          agent.payer,
+          mint,
+          agent.publicKey,
+          false,
+          undefined,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ).address;
+      await mintTo(
+        provider.connection,
+        agent.payer,
+        mint,
+        account,
+        agent.publicKey,
+        100_000_000,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return account;
+    }
+
+    async function openEscrow(mint: PublicKey, agentTokenAccount: PublicKey) {
+      const transactionId = `t22_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow_vault"), escrowPda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrowSpl(
+          new anchor.BN(TOKEN_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          anchor.web3.PublicKey.default
+        )
+        .accounts({
+          escrow: escrowPda,
+          mint,
+          agent: agent.publicKey,
+          agentTokenAccount,
+          api: api.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .rpc();
+      return { escrowPda, vaultPda };
+    }
+
+    it("holds a fee-bearing deposit at the amount that reached the vault", async () => {
+      const mint = await token2022Mint(ExtensionType.TransferFeeConfig, (mint) =>
+        createInitializeTransferFeeConfigInstruction(
+          mint,
+          agent.publicKey,
+          agent.publicKey,
+          FEE_BPS,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        )
+      );
+      const { escrowPda, vaultPda } = await openEscrow(mint, await fundedTokenAccount(mint));
+
+      const received = TOKEN_AMOUNT - (TOKEN_AMOUNT * FEE_BPS) / 10_000;
+      const vault = await getAccount(provider.connection, vaultPda, undefined, TOKEN_2022_PROGRAM_ID);
+      expect(Number(vault.amount)).to.equal(received);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.amount.toNumber()).to.equal(received);
+
+      const apiTokens = getAssociatedTokenAddressSync(mint, api.publicKey, false, TOKEN_2022_PROGRAM_ID);
+      await createAssociatedTokenAccount(
+        provider.connection,
+        agent.payer,
+        mint,
+        api.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await program.methods
+        .releaseFundsSpl()
+        .accounts({
+          escrow: escrowPda,
+          mint,
+          agent: agent.publicKey,
+          apiTokenAccount: apiTokens,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .rpc();
+
+      const emptied = await getAccount(provider.connection, vaultPda, undefined, TOKEN_2022_PROGRAM_ID);
+      expect(Number(emptied.amount)).to.equal(0);
+      const api2022 = await getAccount(provider.connection, apiTokens, undefined, TOKEN_2022_PROGRAM_ID);
+      expect(Number(api2022.amount)).to.equal(received - (received * FEE_BPS) / 10_000);
+    });
+
+    it("rejects non-transferable mints", async () => {
+      const mint = await token2022Mint(ExtensionType.NonTransferable, (mint) =>
+        createInitializeNonTransferableMintInstruction(mint, TOKEN_2022_PROGRAM_ID)
+      );
+
+      try {
+        await openEscrow(mint, await fundedTokenAccount(mint));
+        expect.fail("Should have rejected a non-transferable mint");
+      } catch (err) {
+        expect(err.toString()).to.include("UnsupportedMintExtension");
+      }
+    });
+  });
+
+  describe("escrow audits", () => {
+    const contentHash = new Array(32).fill(0);
+
+    async function openEscrow() {
+      const transactionId = `audit_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { transactionId, escrowPda };
+    }
+
+    async function audit(escrowPda: PublicKey) {
+      const events: { name: string; data: any }[] = [];
+      const listeners = await Promise.all(
+        ["auditPassed", "auditFailed"].map((name) =>
+          program.addEventListener(name as any, (data) => events.push({ name, data }))
+        )
+      );
+      try {
+        await program.methods.auditEscrow().accounts({ escrow: escrowPda }).rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        for (const listener of listeners) {
+          await program.removeEventListener(listener);
+        }
+      }
+      return events;
+    }
+
+    it("passes a released escrow", async () => {
+      const { escrowPda } = await openEscrow();
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.paidToApi.toNumber() + escrow.feesPaid.toNumber()).to.equal(ESCROW_AMOUNT);
+      expect(escrow.refundedToAgent.toNumber()).to.equal(0);
+
+      const events = await audit(escrowPda);
+      expect(events.map((e) => e.name)).to.deep.equal(["auditPassed"]);
+    });
+
+    it("passes a resolved escrow", async () => {
+      const { transactionId, escrowPda } = await openEscrow();
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      const qualityScore = 30;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.refundedToAgent.toNumber()).to.equal(ESCROW_AMOUNT * 0.75);
+      expect(escrow.paidToApi.toNumber() + escrow.feesPaid.toNumber()).to.equal(ESCROW_AMOUNT * 0.25);
+
+      const events = await audit(escrowPda);
+      expect(events.map((e) => e.name)).to.deep.equal(["auditPassed"]);
+      expect(events[0].data.accounting.refundedToAgent.toNumber()).to.equal(ESCROW_AMOUNT * 0.75);
+    });
+
+    it("refuses escrows that have not settled", async () => {
+      const { escrowPda } = await openEscrow();
+      try {
+        await program.methods.auditEscrow().accounts({ escrow: escrowPda }).rpc();
+        expect.fail("Should have rejected an active escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+  });
+
+  describe("reputation degradation", () => {
+    async function openEscrow(
+      noDispute: boolean,
+      agentReputation: PublicKey | null,
+      apiReputation: PublicKey | null
+    ): Promise<PublicKey> {
+      const transactionId = `volume_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          noDispute,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          agentReputation,
+          apiReputation,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return escrowPda;
+    }
+
+    it("records volume without tracking exposure", async () => {
+      const agentReputation = await ensureReputation(agent.publicKey);
+      const apiReputation = await ensureReputation(api.publicKey);
+      const before = await program.account.entityReputation.fetch(agentReputation);
+
+      // A lone reputation cannot carry exposure but still counts the volume
+      await openEscrow(false, agentReputation, null);
+      let after = await program.account.entityReputation.fetch(agentReputation);
+      expect(after.totalVolumeLamports.sub(before.totalVolumeLamports).toNumber()).to.equal(
+        ESCROW_AMOUNT
+      );
+      expect(after.lockedValue.toString()).to.equal(before.lockedValue.toString());
+
+      // No-dispute escrows carry no exposure either
+      const escrowPda = await openEscrow(true, agentReputation, apiReputation);
+      after = await program.account.entityReputation.fetch(agentReputation);
+      expect(after.totalVolumeLamports.sub(before.totalVolumeLamports).toNumber()).to.equal(
+        2 * ESCROW_AMOUNT
+      );
+      expect(after.lockedValue.toString()).to.equal(before.lockedValue.toString());
+      const apiAfter = await program.account.entityReputation.fetch(apiReputation);
+      expect(apiAfter.totalVolumeLamports.toNumber()).to.equal(ESCROW_AMOUNT);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.flags & (1 << 4)).to.equal(0);
+    });
+
+    it("tracks win streaks across a win, loss, win, win", async () => {
+      const disputer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(disputer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const reputationPda = await ensureReputation(disputer.publicKey);
+
+      // Scores up to 40 refund at least 75%, a win for the agent
+      const resolve = async (qualityScore: number) => {
+        const transactionId = `streak_${Date.now()}_${qualityScore}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: disputer.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([disputer])
+          .rpc();
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: disputer.publicKey })
+          .signers([disputer])
+          .rpc();
+
+        const contentHash = new Array(32).fill(0);
+        const { nonce, prefix } = await resolutionPrefix(escrowPda);
+        const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: verifier.secretKey,
+          message: Buffer.from(
+            `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+          ),
+        });
+        await program.methods
+          .resolveDispute(
+            qualityScore,
+            contentHash,
+            nonce,
+            Array.from(signatureIx.data.subarray(48, 112))
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: disputer.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+        return program.account.entityReputation.fetch(reputationPda);
+      };
+
+      let reputation = await resolve(10);
+      expect(reputation.currentWinStreak).to.equal(1);
+      expect(reputation.longestWinStreak).to.equal(1);
+
+      reputation = await resolve(95);
+      expect(reputation.currentWinStreak).to.equal(0);
+      expect(reputation.longestWinStreak).to.equal(1);
+
+      await resolve(30);
+      reputation = await resolve(20);
+      expect(reputation.currentWinStreak).to.equal(2);
+      expect(reputation.longestWinStreak).to.equal(2);
+      expect(reputation.disputesWon.toNumber()).to.equal(3);
+      expect(reputation.disputesLost.toNumber()).to.equal(1);
+      expect(reputation.worstQualityReceived).to.equal(10);
+      expect(reputation.bestQualityReceived).to.equal(95);
+      // 100% refunds for scores up to 20, 75% up to 40
+      expect(reputation.totalRefundsReceivedLamports.toNumber()).to.equal(
+        ESCROW_AMOUNT * 2 + ESCROW_AMOUNT * 0.75
+      );
+    });
+
+    it("settles and emits ReputationUpdateSkipped when a reputation is unusable", async () => {
+      const transactionId = `degraded_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const contentHash = new Array(32).fill(0);
+      const qualityScore = 40;
+
+      // The API's reputation PDA is never initialized
+      await ensureReputation(agent.publicKey);
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+
+      const skipped: any[] = [];
+      const listener = program.addEventListener("reputationUpdateSkipped", (event) => {
+        skipped.push(event);
+      });
+
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      try {
+        await program.methods
+          .resolveDispute(
+            qualityScore,
+            contentHash,
+            nonce,
+            Array.from(signatureIx.data.subarray(48, 112))
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+
+      const apiGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+      expect(apiGain).to.equal(ESCROW_AMOUNT * 0.25);
+
+      expect(skipped.map((e) => e.entity.toBase58())).to.include(api.publicKey.toBase58());
+    });
+
+    it("lets only the provider itself create a provider reputation", async () => {
+      const apiProvider = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(apiProvider.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const [reputationPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reputation"), apiProvider.publicKey.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .initReputation({ provider: {} })
+          .accounts({ reputation: reputationPda, entity: apiProvider.publicKey, payer: agent.publicKey })
+          .rpc();
+        expect.fail("Should have required the provider to pay for its own account");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .initReputation({ provider: {} })
+        .accounts({
+          reputation: reputationPda,
+          entity: apiProvider.publicKey,
+          payer: apiProvider.publicKey,
+        })
+        .signers([apiProvider])
+        .rpc();
+
+      const reputation = await program.account.entityReputation.fetch(reputationPda);
+      expect(reputation.entityType).to.deep.equal({ provider: {} });
+    });
+
+    it("lets only the authority retype a reputation", async () => {
+      const apiProvider = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(apiProvider.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      await program.methods
+        .initReputation({ provider: {} })
+        .accounts({ entity: apiProvider.publicKey, payer: apiProvider.publicKey })
+        .signers([apiProvider])
+        .rpc();
+      const [reputationPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reputation"), apiProvider.publicKey.toBuffer()],
+        program.programId
+      );
+
+      // Retyping itself would take a provider out of the provider formula
+      try {
+        await program.methods
+          .setReputationEntityType(apiProvider.publicKey, { agent: {} })
+          .accounts({ authority: apiProvider.publicKey })
+          .signers([apiProvider])
+          .rpc();
+        expect.fail("Should have required the authority");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+      let reputation = await program.account.entityReputation.fetch(reputationPda);
+      expect(reputation.entityType).to.deep.equal({ provider: {} });
+
+      await program.methods
+        .setReputationEntityType(apiProvider.publicKey, { agent: {} })
+        .accounts({ authority: agent.publicKey })
+        .rpc();
+      reputation = await program.account.entityReputation.fetch(reputationPda);
+      expect(reputation.entityType).to.deep.equal({ agent: {} });
+    });
+
+    it("keeps one reputation per wallet, so there is nothing to merge", async () => {
+      const wallet = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(wallet.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const reputationPda = await ensureReputation(wallet.publicKey);
+
+      // A provider account for the same wallet lands on the same PDA
+      try {
+        await program.methods
+          .initReputation({ provider: {} })
+          .accounts({ entity: wallet.publicKey, payer: wallet.publicKey })
+          .signers([wallet])
+          .rpc();
+        expect.fail("Should not create a second reputation");
+      } catch (err) {
+        expect((err.logs ?? []).join("\n")).to.include("already in use");
+      }
+
+      const reputation = await program.account.entityReputation.fetch(reputationPda);
+      expect(reputation.entityType).to.deep.equal({ agent: {} });
+      expect(reputation.entity.toBase58()).to.equal(wallet.publicKey.toBase58());
+    });
+
+    it("reports reputations through return data", async () => {
+      const reputationPda = await ensureReputation(agent.publicKey);
+      const stored = await program.account.entityReputation.fetch(reputationPda);
+      const view = await program.methods
+        .getReputation()
+        .accounts({ entity: agent.publicKey, reputation: reputationPda })
+        .view();
+      expect(view.initialized).to.be.true;
+      expect(view.reputationScore).to.be.at.most(stored.reputationScore);
+      expect(view.totalTransactions.toNumber()).to.equal(stored.totalTransactions.toNumber());
+
+      const stranger = Keypair.generate().publicKey;
+      const [strangerPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reputation"), stranger.toBuffer()],
+        program.programId
+      );
+      const missing = await program.methods
+        .getReputation()
+        .accounts({ entity: stranger, reputation: strangerPda })
+        .view();
+      expect(missing.initialized).to.be.false;
+      expect(missing.reputationScore).to.equal(500);
+    });
+
+    it("snapshots a reputation once per score update", async () => {
+      const reputationPda = await ensureReputation(agent.publicKey);
+      const [historyPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reputation_history"), agent.publicKey.toBuffer()],
+        program.programId
+      );
+      if (!(await program.account.reputationHistory.fetchNullable(historyPda))) {
+        await program.methods
+          .initReputationHistory()
+          .accounts({ reputationHistory: historyPda, reputation: reputationPda, payer: agent.publicKey })
+          .rpc();
+      }
+
+      const snapshot = () =>
+        program.methods
+          .snapshotReputation()
+          .accounts({ reputationHistory: historyPda, reputation: reputationPda })
+          .rpc();
+
+      const before = await program.account.reputationHistory.fetch(historyPda);
+      const reputation = await program.account.entityReputation.fetch(reputationPda);
+      const latestIndex = (before.nextIndex + before.snapshots.length - 1) % before.snapshots.length;
+      if (
+        before.count === 0 ||
+        before.snapshots[latestIndex].timestamp.lt(reputation.lastUpdated)
+      ) {
+        await snapshot();
+      }
+
+      const history = await program.account.reputationHistory.fetch(historyPda);
+      const latest = history.snapshots[(history.nextIndex + history.snapshots.length - 1) % history.snapshots.length];
+      expect(latest.score).to.equal(reputation.reputationScore);
+
+      try {
+        await snapshot();
+        expect.fail("Should not record the same score update twice");
+      } catch (err) {
+        expect(err.toString()).to.include("SnapshotUpToDate");
+      }
+    });
+
+    it("refreshes a reputation without resetting its decay clock", async () => {
+      await ensureReputation(agent.publicKey);
+      const [reputationPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reputation"), agent.publicKey.toBuffer()],
+        program.programId
+      );
+      const before = await program.account.entityReputation.fetch(reputationPda);
+
+      await program.methods
+        .refreshReputation()
+        .accounts({ reputation: reputationPda })
+        .rpc();
+
+      const after = await program.account.entityReputation.fetch(reputationPda);
+      expect(after.reputationScore).to.be.at.most(before.reputationScore);
+      expect(after.lastUpdated.toNumber()).to.equal(before.lastUpdated.toNumber());
+    });
+  });
+
+  describe("open exposure", () => {
+    let escrowPda: PublicKey;
+    let agentReputation: PublicKey;
+    let apiReputation: PublicKey;
+    let agentLockedBefore: number;
+
+    const locked = async () => ({
+      agent:
+        (await program.account.entityReputation.fetch(agentReputation)).lockedValue.toNumber() -
+        agentLockedBefore,
+      api: (await program.account.entityReputation.fetch(apiReputation)).lockedValue.toNumber(),
+    });
+
+    beforeEach(async () => {
+      agentReputation = await ensureReputation(agent.publicKey);
+      apiReputation = await ensureReputation(api.publicKey);
+      agentLockedBefore = (
+        await program.account.entityReputation.fetch(agentReputation)
+      ).lockedValue.toNumber();
+
+      const transactionId = `exposure_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          agentReputation,
+          apiReputation,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      expect(await locked()).to.deep.equal({ agent: ESCROW_AMOUNT, api: ESCROW_AMOUNT });
+    });
+
+    // Each terminal transition releases the exposure once and clears the
+    // escrow's tracking flag, so any later exit leaves the totals alone
+    const expectReleased = async () => {
+      expect(await locked()).to.deep.equal({ agent: 0, api: 0 });
+      const escrow = await program.account.escrow.fetchNullable(escrowPda);
+      if (escrow) {
+        expect(escrow.flags & (1 << 4)).to.equal(0);
+      }
+    };
+
+    it("releases on release_funds", async () => {
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          agentReputation,
+          apiReputation,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await expectReleased();
+    });
+
+    it("requires both reputations to release a tracked escrow", async () => {
+      try {
+        await program.methods
+          .releaseFunds()
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            agentReputation,
+            apiReputation: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required the API's reputation");
+      } catch (err) {
+        expect(err.toString()).to.include("ExposureAccountsMissing");
+      }
+      expect(await locked()).to.deep.equal({ agent: ESCROW_AMOUNT, api: ESCROW_AMOUNT });
+    });
+
+    it("releases on settle_mutual", async () => {
+      await program.methods
+        .settleMutual(50)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+      await expectReleased();
+    });
+
+    it("releases on cancel_escrow", async () => {
+      await program.methods
+        .cancelEscrow()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          agentReputation,
+          apiReputation,
+        })
+        .signers([api])
+        .rpc();
+      await expectReleased();
+    });
+
+    it("releases once on resolve_dispute, not again on close", async () => {
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      expect(await locked()).to.deep.equal({ agent: ESCROW_AMOUNT, api: ESCROW_AMOUNT });
+
+      const qualityScore = 50;
+      const contentHash = new Array(32).fill(0);
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+      await expectReleased();
+
+      // Closing a settled escrow never touches the reputations; the cleared
+      // flag is what keeps every other exit from releasing it again
+      try {
+        await program.methods
+          .closeEscrow()
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            escrowVault: null,
+            tokenProgram: null,
+            cranker: provider.wallet.publicKey,
+          })
+          .rpc();
+      } catch (err) {
+        expect(err.toString()).to.include("CloseCooldownActive");
+      }
+      await expectReleased();
+    });
+
+    // crank_release_expired, settle_stale_dispute and the final
+    // withdraw_streamed need the clock past the time lock, which localnet
+    // cannot warp. They release through the same helpers as the paths above.
+    // release_and_close is compared with them in release-and-close.ts.
+  });
+
+  describe("no-dispute escrows", () => {
+    let escrowPda: PublicKey;
+
+    beforeEach(async () => {
+      const transactionId = `no_dispute_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          true,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("cannot be marked disputed", async () => {
+      try {
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey })
+          .rpc();
+        expect.fail("Should have rejected the dispute");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputesDisabledForEscrow");
+      }
+    });
+
+    it("cannot be resolved by a verifier signature", async () => {
+      const qualityScore = 10;
+      const contentHash = new Array(32).fill(0);
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(
+          `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+        ),
+      });
+      try {
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+        expect.fail("Should have rejected the resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputesDisabledForEscrow");
+      }
+    });
+
+    it("cannot be resolved by a Switchboard attestation", async () => {
+      try {
+        await program.methods
+          .resolveDisputeSwitchboard(10)
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            switchboardFunction: Keypair.generate().publicKey,
+            // The default namespace trusts no queue
+            switchboardQueue: PublicKey.default,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have rejected the resolution");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputesDisabledForEscrow");
+      }
+    });
+
+    it("releases with only the escrow, parties and config", async () => {
+      const [config] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), PublicKey.default.toBuffer()],
+        program.programId
+      );
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      await program.methods
+        .releaseFunds()
+        .accountsStrict({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          agentReputation: null,
+          apiReputation: null,
+          config,
+          treasury: null,
+          insuranceFund: null,
+          pythPriceUpdate: null,
+          agentWallet: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const apiGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+      expect(apiGain).to.equal(ESCROW_AMOUNT);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("released");
+    });
+  });
+
+  describe("dispute window extensions", () => {
+    const LONG_TIME_LOCK = 7 * 86400;
+    let escrowPda: PublicKey;
+    let extensionPda: PublicKey;
+
+    beforeEach(async () => {
+      const transactionId = `window_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      [extensionPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("window_extension"), escrowPda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(LONG_TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    async function requestExtension(seconds: number) {
+      await program.methods
+        .requestDisputeWindowExtension(new anchor.BN(seconds))
+        .accounts({ escrow: escrowPda, requester: agent.publicKey })
+        .rpc();
+    }
+
+    async function approve(approver: Keypair) {
+      await program.methods
+        .approveDisputeWindowExtension()
+        .accounts({
+          escrow: escrowPda,
+          extensionRequest: extensionPda,
+          requester: agent.publicKey,
+          approver: approver.publicKey,
+        })
+        .signers([approver])
+        .rpc();
+    }
+
+    it("extends the window once the counterparty approves", async () => {
+      const before = await program.account.escrow.fetch(escrowPda);
+      expect(before.disputeWindow.toNumber()).to.equal(2 * 86400);
+
+      await requestExtension(3600);
+
+      try {
+        await approve(Keypair.generate());
+        expect.fail("Only the counterparty may approve");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await approve(api);
+
+      const after = await program.account.escrow.fetch(escrowPda);
+      expect(after.disputeWindow.toNumber()).to.equal(2 * 86400 + 3600);
+      expect(after.disputeWindowExtensionCount).to.equal(1);
+      expect(await provider.connection.getAccountInfo(extensionPda)).to.be.null;
+    });
+
+    it("caps the number of extensions", async () => {
+      await requestExtension(3600);
+      await approve(api);
+      await requestExtension(3600);
+      await approve(api);
+      await requestExtension(3600);
+
+      try {
+        await approve(api);
+        expect.fail("Should have hit the extension cap");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputeWindowExtensionLimit");
+      }
+    });
+
+    it("keeps the window open past a short time lock", async () => {
+      const transactionId = `short_${Date.now()}`;
+      const [shortPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(3600),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: shortPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(shortPda);
+      expect(escrow.disputeWindow.toNumber()).to.equal(2 * 86400);
+      expect(escrow.disputeWindow.toNumber()).to.be.greaterThan(escrow.timeLock.toNumber());
+    });
+
+    it("rejects extending past the original time lock", async () => {
+      await requestExtension(LONG_TIME_LOCK);
+
+      try {
+        await approve(api);
+        expect.fail("Window may not exceed the time lock");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidDisputeWindowExtension");
+      }
+    });
+  });
+
+  describe("extend_time_lock", () => {
+    const MAX_TIME_LOCK = 30 * 86400;
+    let escrowPda: PublicKey;
+
+    beforeEach(async () => {
+      const transactionId = `extend_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("lets the agent push back the expiry", async () => {
+      const before = await program.account.escrow.fetch(escrowPda);
+
+      await program.methods
+        .extendTimeLock(new anchor.BN(3600))
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: null })
+        .rpc();
+
+      const after = await program.account.escrow.fetch(escrowPda);
+      expect(after.expiresAt.toNumber()).to.equal(before.expiresAt.toNumber() + 3600);
+      expect(after.timeLock.toNumber()).to.equal(before.timeLock.toNumber());
+    });
+
+    it("rejects extensions from anyone but the agent", async () => {
+      const stranger = Keypair.generate();
+
+      try {
+        await program.methods
+          .extendTimeLock(new anchor.BN(3600))
+          .accounts({ escrow: escrowPda, agent: stranger.publicKey, api: null })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Only the agent may extend the time lock");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+    });
+
+    it("keeps the total lock within 30 days", async () => {
+      try {
+        await program.methods
+          .extendTimeLock(new anchor.BN(MAX_TIME_LOCK))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have exceeded the maximum time lock");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidTimeLock");
+      }
+    });
+
+    it("records the API's consent in TimeLockExtended", async () => {
+      let extended: any;
+      const listener = program.addEventListener("timeLockExtended", (event) => {
+        extended = event;
+      });
+      try {
+        await program.methods
+          .extendTimeLock(new anchor.BN(3600))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(extended.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(extended.newExpiresAt.toNumber()).to.equal(escrow.expiresAt.toNumber());
+      expect(extended.apiConsented).to.be.true;
+    });
+
+    it("only extends active escrows by a positive amount", async () => {
+      try {
+        await program.methods
+          .extendTimeLock(new anchor.BN(0))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: null })
+          .rpc();
+        expect.fail("Should have rejected an empty extension");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidTimeLock");
+      }
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      try {
+        await program.methods
+          .extendTimeLock(new anchor.BN(3600))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: null })
+          .rpc();
+        expect.fail("Should have rejected a released escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+  });
+
+  describe("insurance fund", () => {
+    const contentHash = new Array(32).fill(0);
+    const contentHashHex = Buffer.from(contentHash).toString("hex");
+    const [insuranceFundPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("insurance_fund")],
+      program.programId
+    );
+    let platform: Keypair;
+
+    before(async () => {
+      if (!(await program.account.insuranceFund.fetchNullable(insuranceFundPda))) {
+        await program.methods
+          .initializeInsuranceFund()
+          .accounts({ authority: agent.publicKey })
+          .rpc();
+      }
+    });
+
+    beforeEach(async () => {
+      platform = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(platform.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      await program.methods
+        .updateGlobalConfig({
+          strictSigAdjacency: null,
+          spamProtectionDeposit: null,
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps: null,
+          treasury: null,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: 100,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: null,
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: null,
+          pythSolUsdFeedId: null,
+          amountTiers: null,
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: 100,
+          oracleFeeBps: null,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({
+          config: PublicKey.findProgramAddressSync(
+            [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          authority: platform.publicKey,
+        })
+        .signers([platform])
+        .rpc();
+      await registerVerifier(verifier.publicKey, platform);
+    });
+
+    async function createEscrow(withFund: boolean, apiKey: PublicKey = api.publicKey) {
+      const transactionId = `insured_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: apiKey,
+          insuranceFund: withFund ? insuranceFundPda : null,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { transactionId, escrowPda };
+    }
+
+    it("pools insurance_fund_bps of each escrow into the fund", async () => {
+      const before = await program.account.insuranceFund.fetch(insuranceFundPda);
+      const lamportsBefore = await provider.connection.getBalance(insuranceFundPda);
+
+      await createEscrow(true);
+
+      const after = await program.account.insuranceFund.fetch(insuranceFundPda);
+      const contribution = ESCROW_AMOUNT / 100;
+      expect(after.balance.sub(before.balance).toNumber()).to.equal(contribution);
+      expect(after.totalContributed.sub(before.totalContributed).toNumber()).to.equal(contribution);
+      expect((await provider.connection.getBalance(insuranceFundPda)) - lamportsBefore).to.equal(contribution);
+    });
+
+    it("requires the fund when the namespace pools contributions", async () => {
+      try {
+        await createEscrow(false);
+        expect.fail("Should have required the insurance fund");
+      } catch (err) {
+        expect(err.toString()).to.include("InsuranceFundMissing");
+      }
+    });
+
+    it("pools release_insurance_bps of each release", async () => {
+      const { escrowPda } = await createEscrow(true);
+      const before = await program.account.insuranceFund.fetch(insuranceFundPda);
+      const apiBefore = await provider.connection.getBalance(api.publicKey);
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          insuranceFund: insuranceFundPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const cut = ESCROW_AMOUNT / 100;
+      const after = await program.account.insuranceFund.fetch(insuranceFundPda);
+      expect(after.balance.sub(before.balance).toNumber()).to.equal(cut);
+      expect((await provider.connection.getBalance(api.publicKey)) - apiBefore).to.equal(ESCROW_AMOUNT - cut);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.feesPaid.toNumber()).to.equal(cut);
+    });
+
+    it("only pays suspension claims when the provider was suspended mid-engagement", async () => {
+      const freshApi = Keypair.generate();
+      const [penaltiesPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("provider_penalties"), freshApi.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initProviderPenalties()
+        .accounts({
+          providerPenalties: penaltiesPda,
+          provider: freshApi.publicKey,
+          payer: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const { escrowPda } = await createEscrow(true, freshApi.publicKey);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      const qualityScore = 50;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: freshApi.publicKey,
+          verifier: verifier.publicKey,
+          apiPenalties: penaltiesPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      // Half refunded, but the provider was never suspended
+      try {
+        await program.methods
+          .claimInsurance()
+          .accounts({
+            escrow: escrowPda,
+            providerPenalties: penaltiesPda,
+            claimant: agent.publicKey,
+          })
+          .rpc();
+        expect.fail("Provider was not suspended");
+      } catch (err) {
+        expect(err.toString()).to.include("InsuranceClaimNotEligible");
+      }
+    });
+
+    it("only accepts claims for stale-settled escrows", async () => {
+      const { transactionId, escrowPda } = await createEscrow(true);
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      try {
+        await program.methods
+          .settleStaleDispute()
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .rpc();
+        expect.fail("Dispute is not stale yet");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputeNotStale");
+      }
+
+      const qualityScore = 20;
+      const { nonce, prefix } = await resolutionPrefix(escrowPda);
+      const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: verifier.secretKey,
+        message: Buffer.from(`${prefix}:${qualityScore}:${contentHashHex}`),
+      });
+      await program.methods
+        .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([signatureIx])
+        .rpc();
+
+      try {
+        await program.methods
+          .fileInsuranceClaim(escrowPda, new Array(32).fill(7))
+          .accounts({ escrow: escrowPda, claimant: agent.publicKey })
+          .rpc();
+        expect.fail("Oracle-resolved escrows are not insurable");
+      } catch (err) {
+        expect(err.toString()).to.include("InsuranceClaimNotEligible");
+      }
+    });
+  });
+
+  describe("release_funds", () => {
+    let transactionId: string;
+    let escrowPda: PublicKey;
+
+    beforeEach(async () => {
+      transactionId = `tx_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("releases funds to API after time-lock", async () => {
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const apiBalanceAfter = await provider.connection.getBalance(api.publicKey);
+      const apiGain = apiBalanceAfter - apiBalanceBefore;
+
+      expect(apiGain).to.be.greaterThan(ESCROW_AMOUNT * 0.99);
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("released");
+    });
+
+    it("lets the agent's delegate release before the time lock", async () => {
+      const hotKey = Keypair.generate();
+      await program.methods
+        .setDelegate(hotKey.publicKey)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: hotKey.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([hotKey])
+        .rpc();
+
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("released");
+    });
+
+    it("allows agent to release explicitly", async () => {
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const apiBalanceAfter = await provider.connection.getBalance(api.publicKey);
+      const apiGain = apiBalanceAfter - apiBalanceBefore;
+
+      expect(apiGain).to.be.greaterThan(ESCROW_AMOUNT * 0.99);
+    });
+
+    it("settles an undisputed escrow on agreed terms", async () => {
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      await program.methods
+        .settleMutual(25)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const apiGain = (await provider.connection.getBalance(api.publicKey)) - apiBalanceBefore;
+      expect(apiGain).to.equal(ESCROW_AMOUNT * 0.75);
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+      expect(escrowAccount.refundPercentage).to.equal(25);
+    });
+
+    it("settles a disputed escrow on agreed terms without a verifier", async () => {
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      const { disputeBond } = await program.account.escrow.fetch(escrowPda);
+      const apiBalanceBefore = await provider.connection.getBalance(api.publicKey);
+
+      try {
+        await program.methods
+          .settleMutual(101)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected a refund above 100%");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidRefundPercentage");
+      }
+
+      // A full refund hands the dispute bond back to the agent
+      await program.methods
+        .settleMutual(100)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+      expect(disputeBond.toNumber()).to.be.greaterThan(0);
+      expect(await provider.connection.getBalance(api.publicKey)).to.equal(apiBalanceBefore);
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrowAccount.flags)).to.equal("resolved");
+      expect(escrowAccount.refundPercentage).to.equal(100);
+
+      try {
+        await program.methods
+          .settleMutual(0)
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected a settled escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+
+    it("adds funds within the escrow bounds", async () => {
+      await program.methods
+        .addFunds(new anchor.BN(ESCROW_AMOUNT))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      const escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowAccount.amount.toNumber()).to.equal(2 * ESCROW_AMOUNT);
+
+      try {
+        await program.methods
+          .addFunds(new anchor.BN(1_000 * LAMPORTS_PER_SOL))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have exceeded the maximum amount");
+      } catch (err) {
+        expect(err.toString()).to.include("AmountTooLarge");
+      }
+    });
+
+    it("moves the top-up into the escrow and emits EscrowToppedUp", async () => {
+      const balanceBefore = await provider.connection.getBalance(escrowPda);
+      let toppedUp: any;
+      const listener = program.addEventListener("escrowToppedUp", (event) => {
+        toppedUp = event;
+      });
+      try {
+        await program.methods
+          .addFunds(new anchor.BN(ESCROW_AMOUNT))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, systemProgram: SystemProgram.programId })
+          .rpc();
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      expect((await provider.connection.getBalance(escrowPda)) - balanceBefore).to.equal(ESCROW_AMOUNT);
+      expect(toppedUp.agent.toBase58()).to.equal(agent.publicKey.toBase58());
+      expect(toppedUp.amount.toNumber()).to.equal(ESCROW_AMOUNT);
+      expect(toppedUp.newAmount.toNumber()).to.equal(2 * ESCROW_AMOUNT);
+    });
+
+    it("only tops up active escrows, from the agent, by at least the minimum", async () => {
+      const stranger = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(stranger.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      try {
+        await program.methods
+          .addFunds(new anchor.BN(ESCROW_AMOUNT))
+          .accounts({ escrow: escrowPda, agent: stranger.publicKey, systemProgram: SystemProgram.programId })
+          .signers([stranger])
+          .rpc();
+        expect.fail("Should have required the agent");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      // Below the 0.001 SOL minimum escrow
+      try {
+        await program.methods
+          .addFunds(new anchor.BN(999_999))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, systemProgram: SystemProgram.programId })
+          .rpc();
+        expect.fail("Should have rejected a top-up below the minimum");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidAmount");
+      }
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      try {
+        await program.methods
+          .addFunds(new anchor.BN(ESCROW_AMOUNT))
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, systemProgram: SystemProgram.programId })
+          .rpc();
+        expect.fail("Should have rejected a released escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+
+    it("streams vested funds and freezes the rest on dispute", async () => {
+      await program.methods
+        .enableStreaming()
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+
+      await program.methods
+        .withdrawStreamed()
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+      let escrowAccount = await program.account.escrow.fetch(escrowPda);
+      const streamed = escrowAccount.streamedAmount.toNumber();
+      expect(streamed).to.be.greaterThan(0);
+      expect(streamed).to.be.lessThan(ESCROW_AMOUNT);
+
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+        })
+        .rpc();
+      try {
+        await program.methods
+          .withdrawStreamed()
+          .accounts({ escrow: escrowPda, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have frozen the stream");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+      escrowAccount = await program.account.escrow.fetch(escrowPda);
+      expect(escrowAccount.streamedAmount.toNumber()).to.equal(streamed);
+    });
+
+    it("refuses to crank an escrow before its time lock expires", async () => {
+      const [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), anchor.web3.PublicKey.default.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .crankReleaseExpired()
+          .accounts({ config: configPda, cranker: provider.wallet.publicKey })
+          .remainingAccounts([
+            { pubkey: escrowPda, isSigner: false, isWritable: true },
+            { pubkey: api.publicKey, isSigner: false, isWritable: true },
+            { pubkey: await ensureReputation(agent.publicKey), isSigner: false, isWritable: true },
+            { pubkey: await ensureReputation(api.publicKey), isSigner: false, isWritable: true },
+          ])
+          .rpc();
+        expect.fail("Should have rejected an unexpired escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("TimeLockNotExpired");
+      }
+    });
+  });
+  describe("cancel_escrow", () => {
+    let transactionId: string;
+    let escrowPda: PublicKey;
+
+    beforeEach(async () => {
+      transactionId = `cancel_${Date.now()}`;
+      [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("refunds and closes the escrow when the API co-signs", async () => {
+      const agentBalanceBefore = await provider.connection.getBalance(agent.publicKey);
+
+      await program.methods
+        .cancelEscrow()
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const agentBalanceAfter = await provider.connection.getBalance(agent.publicKey);
+      expect(agentBalanceAfter - agentBalanceBefore).to.be.greaterThan(ESCROW_AMOUNT);
+      expect(await provider.connection.getAccountInfo(escrowPda)).to.be.null;
+    });
+
+    it("requires the API's signature during the grace period", async () => {
+      try {
+        await program.methods
+          .cancelEscrow()
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: null })
+          .rpc();
+        expect.fail("Should have required the API's signature");
+      } catch (err) {
+        expect(err.toString()).to.include("CancelGracePeriodActive");
+      }
+    });
+
+    it("rejects cancellation after delivery", async () => {
+      await program.methods
+        .acknowledgeDelivery(new Array(32).fill(3), null, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      try {
+        await program.methods
+          .cancelEscrow()
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have rejected a delivered escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("DeliveryAlreadyAcknowledged");
+      }
+    });
+  });
+
+  describe("close_escrow", () => {
+    it("keeps settled escrows open through the cooldown", async () => {
+      const transactionId = `close_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const accounts = {
+        escrow: escrowPda,
+        agent: agent.publicKey,
+        escrowVault: null,
+        tokenProgram: null,
+        cranker: provider.wallet.publicKey,
+      };
+      try {
+        await program.methods.closeEscrow().accounts(accounts).rpc();
+        expect.fail("Should have rejected an active escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.settledAt).to.not.be.null;
+
+      try {
+        await program.methods.closeEscrow().accounts(accounts).rpc();
+        expect.fail("Should have enforced the close cooldown");
+      } catch (err) {
+        expect(err.toString()).to.include("CloseCooldownActive");
+      }
+    });
+
+    it("only releases and closes escrows past the close cooldown", async () => {
+      const transactionId = `sweep_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const accounts = {
+        escrow: escrowPda,
+        agent: agent.publicKey,
+        api: api.publicKey,
+        spamVault: null,
+        cranker: provider.wallet.publicKey,
+      };
+      try {
+        await program.methods.releaseAndClose().accounts(accounts).rpc();
+        expect.fail("Should have left the escrow for release_funds");
+      } catch (err) {
+        expect(err.toString()).to.include("CloseCooldownActive");
+      }
+
+      await program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      try {
+        await program.methods.releaseAndClose().accounts(accounts).rpc();
+        expect.fail("Should have rejected a released escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("released");
+    });
+  });
+
+  describe("escrow receipts", () => {
+    const BUBBLEGUM = new PublicKey("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+
+    it("only mints receipts for settled escrows", async () => {
+      const transactionId = `receipt_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const merkleTree = Keypair.generate().publicKey;
+      const [treeConfig] = PublicKey.findProgramAddressSync([merkleTree.toBuffer()], BUBBLEGUM);
+      try {
+        await program.methods
+          .mintEscrowReceipt()
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            treeConfig,
+            merkleTree,
+            payer: provider.wallet.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have rejected an active escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+  });
+
+  describe("wormhole settlements", () => {
+    const WORMHOLE = new PublicKey("worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth");
+
+    it("only publishes resolved escrows", async () => {
+      const transactionId = `wormhole_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const [emitter] = PublicKey.findProgramAddressSync(
+        [Buffer.from("wormhole_emitter")],
+        program.programId
+      );
+      const wormholePda = (...seeds: Buffer[]) =>
+        PublicKey.findProgramAddressSync(seeds, WORMHOLE)[0];
+      try {
+        await program.methods
+          .publishSettlement()
+          .accounts({
+            escrow: escrowPda,
+            wormholeBridge: wormholePda(Buffer.from("Bridge")),
+            wormholeSequence: wormholePda(Buffer.from("Sequence"), emitter.toBuffer()),
+            wormholeFeeCollector: wormholePda(Buffer.from("fee_collector")),
+            payer: provider.wallet.publicKey,
+            clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .rpc();
+        expect.fail("Should have rejected an active escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+  });
+
+  describe("cpi escrow creation", () => {
+    it("lets a separate payer fund an escrow the agent only signs for", async () => {
+      // Stands in for a PDA: signs, holds no lamports
+      const programAgent = Keypair.generate();
+      const transactionId = `cpi_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      const open = () =>
+        program.methods
+          .initializeEscrowCpi(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: programAgent.publicKey,
+            payer: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          });
+
+      // The agent still has to sign, whoever pays
+      try {
+        await open().rpc();
+        expect.fail("Should have required the agent's signature");
+      } catch (err) {
+        expect(err.toString()).to.match(/signature/i);
+      }
+
+      const payerBefore = await provider.connection.getBalance(agent.publicKey);
+      await open().signers([programAgent]).rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.agent.toBase58()).to.equal(programAgent.publicKey.toBase58());
+      expect(escrow.amount.toNumber()).to.equal(ESCROW_AMOUNT);
+      expect(await provider.connection.getBalance(programAgent.publicKey)).to.equal(0);
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(
+        (await provider.connection.getAccountInfo(escrowPda)).data.length
+      );
+      expect(payerBefore - (await provider.connection.getBalance(agent.publicKey))).to.be.at.least(
+        ESCROW_AMOUNT + rent
+      );
+
+      // Once open the escrow is the agent's: a cancellation refunds it, not the payer
+      const payerAfterOpen = await provider.connection.getBalance(agent.publicKey);
+      await program.methods
+        .cancelEscrow()
+        .accounts({ escrow: escrowPda, agent: programAgent.publicKey, api: api.publicKey })
+        .signers([programAgent, api])
+        .rpc();
+      expect(await provider.connection.getBalance(programAgent.publicKey)).to.be.at.least(
+        ESCROW_AMOUNT + rent
+      );
+      expect(await provider.connection.getBalance(agent.publicKey)).to.be.at.most(payerAfterOpen);
+    });
+  });
+
+  describe("sponsored escrows", () => {
+    it("lets a sponsor pay the rent while the agent funds the escrow", async () => {
+      const sponsor = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(sponsor.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const transactionId = `sponsored_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      const agentBefore = await provider.connection.getBalance(agent.publicKey);
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          payer: sponsor.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([sponsor])
+        .rpc();
+
+      const escrowInfo = await provider.connection.getAccountInfo(escrowPda);
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(escrowInfo.data.length);
+      expect(escrowInfo.lamports).to.equal(rent + ESCROW_AMOUNT);
+      expect(await provider.connection.getBalance(sponsor.publicKey)).to.be.at.most(
+        LAMPORTS_PER_SOL - rent
+      );
+      // The agent paid the escrowed amount and its transaction fee, not the rent
+      const agentSpent = agentBefore - (await provider.connection.getBalance(agent.publicKey));
+      expect(agentSpent).to.be.at.least(ESCROW_AMOUNT);
+      expect(agentSpent).to.be.lessThan(ESCROW_AMOUNT + rent);
+
+      // Closing returns the rent to the agent; the sponsor gets nothing back
+      const sponsorBeforeClose = await provider.connection.getBalance(sponsor.publicKey);
+      const agentBeforeClose = await provider.connection.getBalance(agent.publicKey);
+      await program.methods
+        .cancelEscrow()
+        .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+        .signers([api])
+        .rpc();
+      expect(await provider.connection.getBalance(sponsor.publicKey)).to.equal(sponsorBeforeClose);
+      expect(
+        (await provider.connection.getBalance(agent.publicKey)) - agentBeforeClose
+      ).to.be.greaterThan(ESCROW_AMOUNT + rent - 20_000);
+    });
+  });
+
+  describe("relayed resolutions", () => {
+    it("requires a relayer to reimburse", async () => {
+      const transactionId = `relayed_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .resolveWithRelayer(80, new Array(32).fill(0), new anchor.BN(0), new Array(64).fill(0))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            relayer: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required a relayer");
+      } catch (err) {
+        expect(err.toString()).to.include("RelayerMissing");
+      }
+    });
+  });
+
+  describe("release tips", () => {
+    const openEscrow = async (label: string) => {
+      const transactionId = `release_tip_${label}_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return escrowPda;
+    };
+    const setTip = (escrowPda: PublicKey, lamports: number) =>
+      program.methods
+        .setReleaseTip(new anchor.BN(lamports))
+        .accounts({ escrow: escrowPda, api: api.publicKey })
+        .signers([api])
+        .rpc();
+    const release = (escrowPda: PublicKey) =>
+      program.methods
+        .releaseFunds()
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    it("lets only the API offer a tip for releasing after expiry", async () => {
+      const escrowPda = await openEscrow("offer");
+
+      try {
+        await program.methods
+          .setReleaseTip(new anchor.BN(50_000))
+          .accounts({ escrow: escrowPda, api: agent.publicKey })
+          .rpc();
+        expect.fail("Should have rejected the agent");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      let offered: any;
+      const listener = program.addEventListener("releaseTipSet", (event) => {
+        offered = event;
+      });
+      try {
+        await setTip(escrowPda, 50_000);
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.releaseTipLamports.toNumber()).to.equal(50_000);
+      expect(offered.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(offered.releaseTipLamports.toNumber()).to.equal(50_000);
+    });
+
+    it("pays no tip when the agent releases and closes the offer", async () => {
+      const tipped = await openEscrow("tipped");
+      const untipped = await openEscrow("untipped");
+      await setTip(tipped, 50_000);
+
+      // The agent's own release before expiry pays the API as if no tip were offered
+      await release(tipped);
+      await release(untipped);
+      const [tippedEscrow, untippedEscrow] = await program.account.escrow.fetchMultiple([
+        tipped,
+        untipped,
+      ]);
+      expect(tippedEscrow.paidToApi.toNumber()).to.equal(untippedEscrow.paidToApi.toNumber());
+      expect(tippedEscrow.feesPaid.toNumber()).to.equal(untippedEscrow.feesPaid.toNumber());
+
+      try {
+        await setTip(tipped, 0);
+        expect.fail("Should have rejected a tip on a released escrow");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStatus");
+      }
+    });
+  });
+
+  describe("provider terms", () => {
+    it("opens escrows on the API's published terms", async () => {
+      const sig = await provider.connection.requestAirdrop(api.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const [providerTerms] = PublicKey.findProgramAddressSync(
+        [Buffer.from("provider_terms"), api.publicKey.toBuffer(), Buffer.from([0])],
+        program.programId
+      );
+      const refundCurve = [
+        { maxQualityScore: 30, refundPercentage: 100 },
+        { maxQualityScore: 50, refundPercentage: 60 },
+        { maxQualityScore: 70, refundPercentage: 30 },
+        { maxQualityScore: 90, refundPercentage: 10 },
+        { maxQualityScore: 100, refundPercentage: 0 },
+      ];
+      await program.methods
+        .publishProviderTerms(
+          0,
+          anchor.web3.PublicKey.default,
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(2 * ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          new anchor.BN(3600),
+          refundCurve
+        )
+        .accounts({ providerTerms, api: api.publicKey })
+        .signers([api])
+        .rpc();
+
+      const open = (transactionId: string, timeLock: number) => {
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        return program.methods
+          .initializeEscrowFromTerms(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(timeLock),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            providerTerms,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc()
+          .then(() => escrowPda);
+      };
+
+      try {
+        await open(`terms_off_${Date.now()}`, 2 * TIME_LOCK);
+        expect.fail("Should have rejected a time lock off the terms");
+      } catch (err) {
+        expect(err.toString()).to.include("ProviderTermsMismatch");
+      }
+
+      const escrowPda = await open(`terms_${Date.now()}`, TIME_LOCK);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.disputeWindow.toNumber()).to.equal(3600);
+      expect(escrow.refundCurve).to.deep.equal(refundCurve);
+    });
+  });
+
+  describe("stale dispute terms", () => {
+    const open = (transactionId: string, terms: any) =>
+      program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          terms
+        )
+        .accounts({
+          escrow: PublicKey.findProgramAddressSync(
+            [Buffer.from("escrow"), Buffer.from(transactionId)],
+            program.programId
+          )[0],
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    it("rejects a verifier timeout under a day", async () => {
+      try {
+        await open(`stale_short_${Date.now()}`, {
+          verifierTimeout: new anchor.BN(3600),
+          refundPercentage: 100,
+        });
+        expect.fail("Should have rejected a one-hour verifier timeout");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidStaleDisputeTerms");
+      }
+    });
+
+    it("stores the terms and waits out the verifier timeout", async () => {
+      const transactionId = `stale_terms_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await open(transactionId, { verifierTimeout: new anchor.BN(86_400), refundPercentage: 100 });
+      await ensureReputation(agent.publicKey);
+      await ensureReputation(api.publicKey);
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.staleDisputeTerms.verifierTimeout.toNumber()).to.equal(86_400);
+      expect(escrow.staleDisputeTerms.refundPercentage).to.equal(100);
+
+      try {
+        await program.methods
+          .settleStaleDispute()
+          .accounts({ escrow: escrowPda, agent: agent.publicKey, api: api.publicKey })
+          .rpc();
+        expect.fail("Verifier timeout has not passed");
+      } catch (err) {
+        expect(err.toString()).to.include("DisputeNotStale");
+      }
+    });
+  });
+
+  describe("escrow indexes", () => {
+    const indexAddress = (role: string, party: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from(`${role}_index`), party.toBuffer()],
+        program.programId
+      )[0];
+    const entryAddress = (role: string, party: PublicKey, position: number) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from(`${role}_escrow`), party.toBuffer(), new anchor.BN(position).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+    const count = async (role: "agent" | "api", party: PublicKey) => {
+      const account = role === "agent" ? program.account.agentIndex : program.account.apiIndex;
+      const index = await account.fetchNullable(indexAddress(role, party));
+      return index ? index.count.toNumber() : 0;
+    };
+
+    it("appends an escrow once to both parties' indexes", async () => {
+      const transactionId = `indexed_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const indexEscrow = async () => {
+        const [agentPosition, apiPosition] = [
+          await count("agent", agent.publicKey),
+          await count("api", api.publicKey),
+        ];
+        await program.methods
+          .indexEscrow()
+          .accounts({
+            escrow: escrowPda,
+            agentIndex: indexAddress("agent", agent.publicKey),
+            agentEntry: entryAddress("agent", agent.publicKey, agentPosition),
+            apiIndex: indexAddress("api", api.publicKey),
+            apiEntry: entryAddress("api", api.publicKey, apiPosition),
+            payer: provider.wallet.publicKey,
+          })
+          .rpc();
+        return [agentPosition, apiPosition];
+      };
+
+      const [agentPosition, apiPosition] = await indexEscrow();
+      expect(await count("agent", agent.publicKey)).to.equal(agentPosition + 1);
+      expect(await count("api", api.publicKey)).to.equal(apiPosition + 1);
+      const agentEntry = await program.account.escrowIndexEntry.fetch(
+        entryAddress("agent", agent.publicKey, agentPosition)
+      );
+      const apiEntry = await program.account.escrowIndexEntry.fetch(
+        entryAddress("api", api.publicKey, apiPosition)
+      );
+      expect(agentEntry.escrow.toBase58()).to.equal(escrowPda.toBase58());
+      expect(apiEntry.escrow.toBase58()).to.equal(escrowPda.toBase58());
+
+      try {
+        await indexEscrow();
+        expect.fail("Should have rejected a second index");
+      } catch (err) {
+        expect(err.toString()).to.include("EscrowAlreadyIndexed");
+      }
+    });
+
+    it("lets clients page through a party's escrows by position", async () => {
+      const opened: PublicKey[] = [];
+      for (let i = 0; i < 3; i++) {
+        const transactionId = `paged_${Date.now()}_${i}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        const agentPosition = await count("agent", agent.publicKey);
+        const apiPosition = await count("api", api.publicKey);
+        if (i === 0) {
+          // Entries must land at the index's next position
+          try {
+            await program.methods
+              .indexEscrow()
+              .accounts({
+                escrow: escrowPda,
+                agentIndex: indexAddress("agent", agent.publicKey),
+                agentEntry: entryAddress("agent", agent.publicKey, agentPosition),
+                apiIndex: indexAddress("api", api.publicKey),
+                apiEntry: entryAddress("api", api.publicKey, apiPosition + 1),
+                payer: provider.wallet.publicKey,
+              })
+              .rpc();
+            expect.fail("Should have rejected an entry past the end");
+          } catch (err) {
+            expect(err.toString()).to.include("ConstraintSeeds");
+          }
+        }
+        await program.methods
+          .indexEscrow()
+          .accounts({
+            escrow: escrowPda,
+            agentIndex: indexAddress("agent", agent.publicKey),
+            agentEntry: entryAddress("agent", agent.publicKey, agentPosition),
+            apiIndex: indexAddress("api", api.publicKey),
+            apiEntry: entryAddress("api", api.publicKey, apiPosition),
+            payer: provider.wallet.publicKey,
+          })
+          .rpc();
+        opened.push(escrowPda);
+      }
+
+      // A fresh API's escrows sit at positions 0..count, in indexing order
+      const total = await count("api", api.publicKey);
+      expect(total).to.equal(3);
+      const pageSize = 2;
+      const listed: string[] = [];
+      for (let start = 0; start < total; start += pageSize) {
+        const positions = [...Array(Math.min(pageSize, total - start)).keys()].map((i) => start + i);
+        const page = await program.account.escrowIndexEntry.fetchMultiple(
+          positions.map((position) => entryAddress("api", api.publicKey, position))
+        );
+        listed.push(...page.map((entry) => entry.escrow.toBase58()));
+      }
+      expect(listed).to.deep.equal(opened.map((escrow) => escrow.toBase58()));
+    });
+  });
+
+  describe("verifier quorum", () => {
+    let platform: Keypair;
+    let configPda: PublicKey;
+    let verifierSetPda: PublicKey;
+    let members: Keypair[];
+
+    beforeEach(async () => {
+      platform = Keypair.generate();
+      members = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+      const sig = await provider.connection.requestAirdrop(platform.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+      [verifierSetPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier_set"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+    });
+
+    async function configure(threshold: number) {
+      await program.methods
+        .configureVerifierSet(members.map((m) => m.publicKey), threshold)
+        .accounts({ verifierSet: verifierSetPda, config: configPda, authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+    }
+
+    it("stores an M-of-N verifier set", async () => {
+      await configure(2);
+
+      const set = await program.account.verifierSet.fetch(verifierSetPda);
+      expect(set.threshold).to.equal(2);
+      expect(set.verifiers.map((v) => v.toString())).to.deep.equal(
+        members.map((m) => m.publicKey.toString())
+      );
+    });
+
+    it("rejects a threshold above the set size", async () => {
+      try {
+        await configure(4);
+        expect.fail("Should have rejected an unreachable threshold");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidVerifierSet");
+      }
+    });
+
+    it("requires threshold signatures to resolve", async () => {
+      await configure(2);
+
+      const transactionId = `quorum_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      try {
+        await program.methods
+          .resolveDisputeQuorum(50, new Array(32).fill(0), new anchor.BN(0), [
+            { verifierIndex: 0, signature: new Array(64).fill(0) },
+          ])
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: members[0].publicKey,
+            verifierSet: verifierSetPda,
+            config: configPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required a quorum");
+      } catch (err) {
+        expect(err.toString()).to.include("QuorumNotReached");
+      }
+    });
+  });
+
+  describe("provider penalties", () => {
+    function penaltiesAddress(provider: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync(
+        [Buffer.from("provider_penalties"), provider.toBuffer()],
+        program.programId
+      )[0];
+    }
+
+    async function trackPenalties(provider: PublicKey): Promise<PublicKey> {
+      const penaltiesPda = penaltiesAddress(provider);
+      await program.methods
+        .initProviderPenalties()
+        .accounts({
+          providerPenalties: penaltiesPda,
+          provider,
+          payer: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return penaltiesPda;
+    }
+
+    it("records a strike for a poor-quality resolution", async () => {
+      const penaltiesPda = await trackPenalties(api.publicKey);
+
+      const transactionId = `penalty_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+      await program.methods
+        .resolveDispute(10, new Array(32).fill(0), new anchor.BN(0), new Array(64).fill(0))
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          verifier: verifier.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const penalties = await program.account.providerPenalties.fetch(penaltiesPda);
+      expect(penalties.strikeCount).to.equal(1);
+      expect(penalties.poorQualityCount).to.equal(1);
+      expect(penalties.suspended).to.be.false;
+      expect(penalties.totalRefundsIssued.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("only lifts an existing suspension", async () => {
+      const penaltiesPda = await trackPenalties(api.publicKey);
+
+      try {
+        await program.methods
+          .liftSuspension()
+          .accounts({ providerPenalties: penaltiesPda })
+          .rpc();
+        expect.fail("Should have rejected an unsuspended provider");
+      } catch (err) {
+        expect(err.toString()).to.include("ProviderNotSuspended");
+      }
+    });
+  });
+
+  describe("rate limiter", () => {
+    let entity: Keypair;
+    let rateLimiterPda: PublicKey;
+
+    beforeEach(async () => {
+      entity = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(entity.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [rateLimiterPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rate_limit"), entity.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initRateLimiter()
+        .accounts({
+          rateLimiter: rateLimiterPda,
+          entity: entity.publicKey,
+          payer: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("starts at Basic", async () => {
+      const limiter = await program.account.rateLimiter.fetch(rateLimiterPda);
+      expect(limiter.verificationLevel).to.deep.equal({ basic: {} });
+      expect(limiter.stakedLamports.toNumber()).to.equal(0);
+    });
+
+    it("stakes into Staked and withdraws back to Basic", async () => {
+      await program.methods
+        .upgradeVerificationLevel({ staked: {} })
+        .accounts({ rateLimiter: rateLimiterPda, entity: entity.publicKey, authority: null })
+        .signers([entity])
+        .rpc();
+
+      let limiter = await program.account.rateLimiter.fetch(rateLimiterPda);
+      expect(limiter.verificationLevel).to.deep.equal({ staked: {} });
+      expect(limiter.stakedLamports.toNumber()).to.equal(LAMPORTS_PER_SOL);
+
+      await program.methods
+        .withdrawVerificationStake()
+        .accounts({ rateLimiter: rateLimiterPda, entity: entity.publicKey })
+        .signers([entity])
+        .rpc();
+
+      limiter = await program.account.rateLimiter.fetch(rateLimiterPda);
+      expect(limiter.verificationLevel).to.deep.equal({ basic: {} });
+      expect(limiter.stakedLamports.toNumber()).to.equal(0);
+    });
+
+    it("requires the authority above Staked", async () => {
+      try {
+        await program.methods
+          .upgradeVerificationLevel({ kyc: {} })
+          .accounts({ rateLimiter: rateLimiterPda, entity: entity.publicKey, authority: null })
+          .signers([entity])
+          .rpc();
+        expect.fail("Should have required the authority");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidVerificationUpgrade");
+      }
+    });
+
+    it("charges the limiter when the entity opens escrows", async () => {
+      const open = async (transactionId: string) => {
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: entity.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([entity])
+          .rpc();
+      };
+
+      // Basic allows one escrow an hour
+      await open(`limited_${Date.now()}`);
+      try {
+        await open(`limited_${Date.now()}_2`);
+        expect.fail("Should have hit the hourly limit");
+      } catch (err) {
+        expect(err.toString()).to.include("RateLimitExceeded");
+      }
+
+      const limiter = await program.account.rateLimiter.fetch(rateLimiterPda);
+      expect(limiter.transactionsLastHour).to.equal(1);
+    });
+
+    it("lets an override carry disputes past the daily limit", async () => {
+      const until = Math.floor(Date.now() / 1000) + 3600;
+      await program.methods
+        .setRateLimitOverride(entity.publicKey, new anchor.BN(until))
+        .accounts({ rateLimiter: rateLimiterPda, authority: agent.publicKey })
+        .rpc();
+
+      // Basic allows three disputes a day, and one escrow an hour
+      for (let i = 0; i < 4; i++) {
+        const transactionId = `override_${Date.now()}_${i}`;
+        const [escrowPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), Buffer.from(transactionId)],
+          program.programId
+        );
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: entity.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([entity])
+          .rpc();
+        await program.methods
+          .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+          .accounts({ escrow: escrowPda, agent: entity.publicKey })
+          .signers([entity])
+          .rpc();
+      }
+
+      const limiter = await program.account.rateLimiter.fetch(rateLimiterPda);
+      expect(limiter.disputesLastDay).to.equal(4);
+    });
+  });
+
+  describe("milestones", () => {
+    async function milestoneEscrow(amounts: number[]) {
+      const transactionId = `milestones_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      const [milestonePlan] = PublicKey.findProgramAddressSync(
+        [Buffer.from("milestones"), escrowPda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await program.methods
+        .createMilestonePlan(amounts.map((amount) => new anchor.BN(amount)))
+        .accounts({
+          milestonePlan,
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { escrowPda, milestonePlan };
+    }
+
+    it("rejects plans that do not cover the escrow", async () => {
+      try {
+        await milestoneEscrow([ESCROW_AMOUNT / 2]);
+        expect.fail("Should have rejected a partial plan");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidMilestonePlan");
+      }
+    });
+
+    it("releases and disputes milestones independently", async () => {
+      const half = ESCROW_AMOUNT / 2;
+      const { escrowPda, milestonePlan } = await milestoneEscrow([half, half]);
+
+      await program.methods
+        .releaseMilestone(0)
+        .accounts({
+          escrow: escrowPda,
+          milestonePlan,
+          caller: agent.publicKey,
+          api: api.publicKey,
+        })
+        .rpc();
+      await program.methods
+        .disputeMilestone(1)
+        .accounts({ escrow: escrowPda, milestonePlan, agent: agent.publicKey })
+        .rpc();
+
+      const plan = await program.account.milestonePlan.fetch(milestonePlan);
+      expect(plan.milestones[0].status).to.deep.equal({ released: {} });
+      expect(plan.milestones[1].status).to.deep.equal({ disputed: {} });
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("active");
+      expect(escrow.paidToApi.toNumber() + escrow.feesPaid.toNumber()).to.equal(half);
+    });
+
+    it("blocks whole-escrow settlement", async () => {
+      const { escrowPda } = await milestoneEscrow([ESCROW_AMOUNT]);
+      try {
+        await program.methods
+          .releaseFunds()
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required milestone releases");
+      } catch (err) {
+        expect(err.toString()).to.include("MilestonePlanActive");
+      }
+    });
+  });
+
+  describe("subscriptions", () => {
+    const PERIOD = 86_400;
+
+    async function subscribe(periods: number) {
+      const subscribedApi = Keypair.generate();
+      const [subscriptionPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("subscription"), agent.publicKey.toBuffer(), subscribedApi.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .createSubscription(
+          anchor.web3.PublicKey.default,
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(PERIOD),
+          periods
+        )
+        .accounts({
+          subscription: subscriptionPda,
+          agent: agent.publicKey,
+          api: subscribedApi.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { subscriptionPda, subscribedApi };
+    }
+
+    it("holds the current period until its dispute window closes", async () => {
+      const { subscriptionPda, subscribedApi } = await subscribe(3);
+
+      try {
+        await program.methods
+          .claimPeriod()
+          .accounts({
+            subscription: subscriptionPda,
+            api: subscribedApi.publicKey,
+            agent: agent.publicKey,
+          })
+          .signers([subscribedApi])
+          .rpc();
+        expect.fail("Should not claim a running period");
+      } catch (err) {
+        expect(err.toString()).to.include("PeriodNotClaimable");
+      }
+
+      await program.methods
+        .disputePeriod()
+        .accounts({ subscription: subscriptionPda, agent: agent.publicKey })
+        .rpc();
+      const subscription = await program.account.subscriptionEscrow.fetch(subscriptionPda);
+      expect(subscription.disputed).to.equal(true);
+      expect(subscription.periodsSettled).to.equal(0);
+    });
+
+    it("refunds unstarted periods on cancellation", async () => {
+      const { subscriptionPda } = await subscribe(3);
+      const before = await provider.connection.getBalance(subscriptionPda);
+
+      await program.methods
+        .cancelSubscription()
+        .accounts({ subscription: subscriptionPda, agent: agent.publicKey })
+        .rpc();
+
+      const after = await provider.connection.getBalance(subscriptionPda);
+      expect(before - after).to.equal(2 * ESCROW_AMOUNT);
+      const subscription = await program.account.subscriptionEscrow.fetch(subscriptionPda);
+      expect(subscription.fundedPeriods).to.equal(1);
+    });
+  });
+
+  describe("payment channels", () => {
+    let channelApi: Keypair;
+    let channelPda: PublicKey;
+
+    beforeEach(async () => {
+      channelApi = Keypair.generate();
+      [channelPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("payment_channel"), agent.publicKey.toBuffer(), channelApi.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .openPaymentChannel(anchor.web3.PublicKey.default, new anchor.BN(4 * ESCROW_AMOUNT))
+        .accounts({
+          channel: channelPda,
+          agent: agent.publicKey,
+          api: channelApi.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    async function recordDebit(amount: number) {
+      await program.methods
+        .recordChannelDebit(new anchor.BN(amount))
+        .accounts({ channel: channelPda, agent: agent.publicKey })
+        .rpc();
+    }
+
+    it("numbers debits and caps them at the channel balance", async () => {
+      await recordDebit(ESCROW_AMOUNT);
+      await recordDebit(2 * ESCROW_AMOUNT);
+
+      const channel = await program.account.paymentChannel.fetch(channelPda);
+      expect(channel.entries.map((e) => e.id.toNumber())).to.deep.equal([0, 1]);
+      expect(channel.nextEntryId.toNumber()).to.equal(2);
+
+      try {
+        await recordDebit(2 * ESCROW_AMOUNT);
+        expect.fail("Should not record debits beyond the balance");
+      } catch (err) {
+        expect(err.toString()).to.include("ChannelBalanceExceeded");
+      }
+    });
+
+    it("holds entries inside their dispute window out of settlement", async () => {
+      await recordDebit(ESCROW_AMOUNT);
+      await recordDebit(ESCROW_AMOUNT);
+      await program.methods
+        .disputeChannelEntry(new anchor.BN(1))
+        .accounts({ channel: channelPda, agent: agent.publicKey })
+        .rpc();
+
+      await program.methods
+        .settleChannel()
+        .accounts({ channel: channelPda, api: channelApi.publicKey, agent: agent.publicKey })
+        .signers([channelApi])
+        .rpc();
+
+      const channel = await program.account.paymentChannel.fetch(channelPda);
+      expect(channel.entries.length).to.equal(2);
+      expect(channel.entries[1].disputed).to.equal(true);
+      expect(channel.totalSettled.toNumber()).to.equal(0);
+    });
+
+    it("only closes a channel without unsettled entries", async () => {
+      await recordDebit(ESCROW_AMOUNT);
+
+      try {
+        await program.methods
+          .closePaymentChannel()
+          .accounts({ channel: channelPda, agent: agent.publicKey })
+          .rpc();
+        expect.fail("Should not close over unsettled entries");
+      } catch (err) {
+        expect(err.toString()).to.include("ChannelEntriesPending");
+      }
+    });
+  });
+
+  describe("circuit breaker", () => {
+    const platform = Keypair.generate();
+    let configPda: PublicKey;
+
+    before(async () => {
+      const sig = await provider.connection.requestAirdrop(platform.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+      [configPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    async function openEscrow() {
+      const transactionId = `paused_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          platform.publicKey,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return escrowPda;
+    }
+
+    async function setPaused(paused: boolean, authority: Keypair) {
+      await program.methods
+        .setPaused(paused)
+        .accounts({ config: configPda, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    }
+
+    it("only lets an authority pause", async () => {
+      try {
+        await setPaused(true, Keypair.generate());
+        expect.fail("Should have rejected an outsider");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+    });
+
+    it("rejects new escrows while paused", async () => {
+      await setPaused(true, platform);
+      expect((await program.account.globalConfig.fetch(configPda)).paused).to.be.true;
+
+      try {
+        await openEscrow();
+        expect.fail("Should have rejected an escrow in a paused namespace");
+      } catch (err) {
+        expect(err.toString()).to.include("ProtocolPaused");
+      }
+
+      await setPaused(false, platform);
+      await openEscrow();
+    });
+
+    it("holds resolutions while paused", async () => {
+      await registerVerifier(verifier.publicKey, platform);
+      const escrowPda = await openEscrow();
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: agent.publicKey })
+        .rpc();
+
+      const qualityScore = 50;
+      const contentHash = new Array(32).fill(0);
+      const resolve = async () => {
+        const { nonce, prefix } = await resolutionPrefix(escrowPda);
+        const signatureIx = Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: verifier.secretKey,
+          message: Buffer.from(
+            `${prefix}:${qualityScore}:${Buffer.from(contentHash).toString("hex")}`
+          ),
+        });
+        await program.methods
+          .resolveDispute(qualityScore, contentHash, nonce, Array.from(signatureIx.data.subarray(48, 112)))
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: api.publicKey,
+            verifier: verifier.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([signatureIx])
+          .rpc();
+      };
+
+      // A compromised verifier key cannot move funds while the namespace is paused
+      await setPaused(true, platform);
+      try {
+        await resolve();
+        expect.fail("Should have rejected a resolution in a paused namespace");
+      } catch (err) {
+        expect(err.toString()).to.include("ProtocolPaused");
+      }
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("disputed");
+
+      await setPaused(false, platform);
+      await resolve();
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(escrow.flags)).to.equal("resolved");
+    });
+  });
+
+  describe("blocklist", () => {
+    let owner: Keypair;
+    let blocklistPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [blocklistPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("blocklist"), owner.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initializeBlocklist()
+        .accounts({
+          blocklist: blocklistPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("keeps entries sorted and rejects duplicates", async () => {
+      const entries = [Keypair.generate(), Keypair.generate(), Keypair.generate()].map(
+        (k) => k.publicKey
+      );
+
+      for (const entry of entries) {
+        await program.methods
+          .addToBlocklist(entry)
+          .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+      }
+
+      const blocklist = await program.account.blocklist.fetch(blocklistPda);
+      const stored = blocklist.blocked.map((k) => k.toBuffer());
+      const sorted = [...stored].sort(Buffer.compare);
+      expect(stored).to.deep.equal(sorted);
+
+      try {
+        await program.methods
+          .addToBlocklist(entries[0])
+          .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("AlreadyBlocked");
+      }
+    });
+
+    it("removes entries and rejects unknown ones", async () => {
+      const entry = Keypair.generate().publicKey;
+
+      await program.methods
+        .addToBlocklist(entry)
+        .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .removeFromBlocklist(entry)
+        .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const blocklist = await program.account.blocklist.fetch(blocklistPda);
+      expect(blocklist.blocked).to.have.length(0);
+
+      try {
+        await program.methods
+          .removeFromBlocklist(entry)
+          .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("NotBlocked");
+      }
+    });
+
+    it("rejects entries beyond capacity", async () => {
+      for (let i = 0; i < 32; i++) {
+        await program.methods
+          .addToBlocklist(Keypair.generate().publicKey)
+          .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+      }
+
+      try {
+        await program.methods
+          .addToBlocklist(Keypair.generate().publicKey)
+          .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("BlocklistFull");
+      }
+    });
+
+    async function block(counterparty: PublicKey) {
+      await program.methods
+        .addToBlocklist(counterparty)
+        .accounts({ blocklist: blocklistPda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    }
+
+    async function openEscrow(payer: Keypair | null, escrowApi: PublicKey) {
+      const escrowAgent = payer?.publicKey ?? agent.publicKey;
+      const transactionId = `blocked_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: escrowAgent,
+          api: escrowApi,
+          agentBlocklist: PublicKey.findProgramAddressSync(
+            [Buffer.from("blocklist"), escrowAgent.toBuffer()],
+            program.programId
+          )[0],
+          apiBlocklist: PublicKey.findProgramAddressSync(
+            [Buffer.from("blocklist"), escrowApi.toBuffer()],
+            program.programId
+          )[0],
+          systemProgram: SystemProgram.programId,
+        })
+        .signers(payer ? [payer] : [])
+        .rpc();
+    }
+
+    it("stops agents the API has blocked", async () => {
+      await block(agent.publicKey);
+
+      try {
+        await openEscrow(null, owner.publicKey);
+        expect.fail("Should have honored the API's blocklist");
+      } catch (err) {
+        expect(err.toString()).to.include("CounterpartyBlocked");
+      }
+    });
+
+    it("stops agents paying an API they blocked", async () => {
+      await block(api.publicKey);
+
+      try {
+        await openEscrow(owner, api.publicKey);
+        expect.fail("Should have honored the agent's blocklist");
+      } catch (err) {
+        expect(err.toString()).to.include("CounterpartyBlocked");
+      }
+    });
+
+    it("rejects a blocklist that is not the party's own", async () => {
+      const transactionId = `blocked_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .initializeEscrow(
+            new anchor.BN(ESCROW_AMOUNT),
+            new anchor.BN(TIME_LOCK),
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            escrow: escrowPda,
+            agent: agent.publicKey,
+            api: owner.publicKey,
+            agentBlocklist: PublicKey.findProgramAddressSync(
+              [Buffer.from("blocklist"), agent.publicKey.toBuffer()],
+              program.programId
+            )[0],
+            apiBlocklist: Keypair.generate().publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have required the API's blocklist PDA");
+      } catch (err) {
+        expect(err.toString()).to.include("ConstraintSeeds");
+      }
+    });
+  });
+
+  describe("allowlist", () => {
+    let organization: Keypair;
+    let restrictedAgent: Keypair;
+    let allowlistPda: PublicKey;
+
+    beforeEach(async () => {
+      organization = Keypair.generate();
+      restrictedAgent = Keypair.generate();
+      for (const key of [organization.publicKey, restrictedAgent.publicKey]) {
+        const sig = await provider.connection.requestAirdrop(key, LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [allowlistPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("allowlist"), organization.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeAllowlist()
+        .accounts({ allowlist: allowlistPda, organization: organization.publicKey })
+        .signers([organization])
+        .rpc();
+      await program.methods
+        .addToAllowlist(api.publicKey)
+        .accounts({ allowlist: allowlistPda, organization: organization.publicKey })
+        .signers([organization])
+        .rpc();
+      await program.methods
+        .restrictAgent()
+        .accounts({ organization: organization.publicKey, agent: restrictedAgent.publicKey })
+        .signers([organization, restrictedAgent])
+        .rpc();
+    });
+
+    async function openEscrow(apiKey: PublicKey, allowlist: PublicKey | null) {
+      const transactionId = `allowlist_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: restrictedAgent.publicKey,
+          api: apiKey,
+          allowlist,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([restrictedAgent])
+        .rpc();
+    }
+
+    it("lets a restricted agent pay only allowlisted APIs", async () => {
+      await openEscrow(api.publicKey, allowlistPda);
+
+      for (const [apiKey, allowlist, error] of [
+        [api.publicKey, null, "AllowlistRequired"],
+        [Keypair.generate().publicKey, allowlistPda, "ApiNotAllowlisted"],
+      ] as const) {
+        try {
+          await openEscrow(apiKey, allowlist);
+          expect.fail("Should have rejected the escrow");
+        } catch (err) {
+          expect(err.toString()).to.include(error);
+        }
+      }
+    });
+
+    it("frees the agent once the organization lifts the restriction", async () => {
+      const [restrictionPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("agent_restriction"), restrictedAgent.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .unrestrictAgent()
+        .accounts({ restriction: restrictionPda, organization: organization.publicKey })
+        .signers([organization])
+        .rpc();
+
+      await openEscrow(Keypair.generate().publicKey, null);
+    });
+  });
+
+  describe("organizations", () => {
+    let organization: Keypair;
+    let teamAgent: Keypair;
+    let memberPda: PublicKey;
+
+    before(async () => {
+      organization = Keypair.generate();
+      teamAgent = Keypair.generate();
+      for (const key of [organization.publicKey, teamAgent.publicKey]) {
+        const sig = await provider.connection.requestAirdrop(key, LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      await program.methods
+        .initializeOrganization()
+        .accounts({ organization: organization.publicKey })
+        .signers([organization])
+        .rpc();
+      await program.methods
+        .addOrganizationAgent(new anchor.BN(ESCROW_AMOUNT))
+        .accounts({ organization: organization.publicKey, agent: teamAgent.publicKey })
+        .signers([organization, teamAgent])
+        .rpc();
+      [memberPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("org_member"), teamAgent.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    async function openEscrow() {
+      const transactionId = `team_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: teamAgent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([teamAgent])
+        .rpc();
+    }
+
+    it("holds an agent to its daily spend limit", async () => {
+      await openEscrow();
+      const member = await program.account.organizationMember.fetch(memberPda);
+      expect(member.spentToday.toNumber()).to.equal(ESCROW_AMOUNT);
+
+      try {
+        await openEscrow();
+        expect.fail("Should have exceeded the daily limit");
+      } catch (err) {
+        expect(err.toString()).to.include("DailySpendLimitExceeded");
+      }
+    });
+
+    it("lets the organization raise the limit", async () => {
+      await program.methods
+        .setAgentSpendLimit(new anchor.BN(3 * ESCROW_AMOUNT))
+        .accounts({ member: memberPda, organization: organization.publicKey })
+        .signers([organization])
+        .rpc();
+
+      await openEscrow();
+    });
+  });
+
+  describe("session keys", () => {
+    const SCOPE_ESCROW = 1;
+    const SCOPE_DISPUTE = 2;
+
+    async function createSession(scope: number) {
+      const sessionKey = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(sessionKey.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const expiresAt = Math.floor(Date.now() / 1000) + 3600;
+      await program.methods
+        .createSessionKey(sessionKey.publicKey, scope, new anchor.BN(expiresAt))
+        .accounts({ agent: agent.publicKey })
+        .rpc();
+      return sessionKey;
+    }
+
+    async function openEscrow(sessionKey: Keypair) {
+      const transactionId = `session_${Date.now()}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrowWithSession(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          sessionSigner: sessionKey.publicKey,
+          agent: agent.publicKey,
+          api: api.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([sessionKey])
+        .rpc();
+      return escrowPda;
+    }
+
+    it("opens and disputes escrows for the agent", async () => {
+      const sessionKey = await createSession(SCOPE_ESCROW | SCOPE_DISPUTE);
+      const escrowPda = await openEscrow(sessionKey);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.agent.toString()).to.equal(agent.publicKey.toString());
+
+      const [session] = PublicKey.findProgramAddressSync(
+        [Buffer.from("session_key"), agent.publicKey.toBuffer(), sessionKey.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .markDisputed(new anchor.BN(0), DISPUTE_REASON, EVIDENCE_HASH)
+        .accounts({ escrow: escrowPda, agent: sessionKey.publicKey, session })
+        .signers([sessionKey])
+        .rpc();
+
+      const disputed = await program.account.escrow.fetch(escrowPda);
+      expect(escrowStatus(disputed.flags)).to.equal("disputed");
+    });
+
+    it("stays within its scope", async () => {
+      const sessionKey = await createSession(SCOPE_DISPUTE);
+
+      try {
+        await openEscrow(sessionKey);
+        expect.fail("Should have rejected an escrow outside the session's scope");
+      } catch (err) {
+        expect(err.toString()).to.include("SessionScopeMissing");
+      }
+    });
+
+    it("stops working once revoked", async () => {
+      const sessionKey = await createSession(SCOPE_ESCROW);
+      const [session] = PublicKey.findProgramAddressSync(
+        [Buffer.from("session_key"), agent.publicKey.toBuffer(), sessionKey.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .revokeSessionKey()
+        .accounts({ session, agent: agent.publicKey })
+        .rpc();
+
+      try {
+        await openEscrow(sessionKey);
+        expect.fail("Should have rejected a revoked session key");
+      } catch (err) {
+        expect(err.toString()).to.include("AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("spam protection deposits", () => {
+    const DEPOSIT = 0.005 * LAMPORTS_PER_SOL;
+    let spammer: Keypair;
+    let namespace: PublicKey;
+    let spamVault: PublicKey;
+
+    before(async () => {
+      const platform = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(platform.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      await program.methods
+        .initializeNamespaceConfig()
+        .accounts({ authority: platform.publicKey })
+        .signers([platform])
+        .rpc();
+
+      await program.methods
+        .updateGlobalConfig({
+          strictSigAdjacency: null,
+          spamProtectionDeposit: new anchor.BN(DEPOSIT),
+          solUsdPriceFeed: null,
+          maxOverrideDurationSeconds: null,
+          feeBps: null,
+          treasury: null,
+          minVerifierBondLamports: null,
+          bondGracePeriodDays: null,
+          maxDisputeWindowExtensions: null,
+          insuranceFundBps: null,
+          maxInsurancePayoutBps: null,
+          maxPriorityFeeLamports: null,
+          usdStablecoinMint: null,
+          cancelGracePeriodSeconds: null,
+          refundCurve: null,
+          appealWindowSeconds: null,
+          pythSolUsdFeedId: null,
+          amountTiers: null,
+          disputeBondBps: null,
+          disputeBondToTreasury: null,
+          severeRefundSlashBps: null,
+          releaseInsuranceBps: null,
+          oracleFeeBps: null,
+          switchboardQueue: null,
+          attestationMaxAgeSeconds: null,
+          pythQualityFeedId: null,
+        })
+        .accounts({
+          config: PublicKey.findProgramAddressSync(
+            [Buffer.from("global_config"), platform.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          authority: platform.publicKey,
+        })
+        .signers([platform])
+        .rpc();
+
+      namespace = platform.publicKey;
+    });
+
+    // Each test starts a fresh agent, so its window holds only its own escrows
+    beforeEach(async () => {
+      spammer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(spammer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [spamVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("spam_vault"), spammer.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    async function openEscrow(
+      escrowNamespace: PublicKey,
+      vault: PublicKey | null
+    ): Promise<PublicKey> {
+      const transactionId = `spam_${Date.now()}_${Math.random().toString(36).slice(2, 8)}`;
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), Buffer.from(transactionId)],
+        program.programId
+      );
+      await program.methods
+        .initializeEscrow(
+          new anchor.BN(ESCROW_AMOUNT),
+          new anchor.BN(TIME_LOCK),
+          transactionId,
+          false,
+          escrowNamespace,
+          null,
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          escrow: escrowPda,
+          agent: spammer.publicKey,
+          api: api.publicKey,
+          spamVault: vault,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([spammer])
+        .rpc();
+      return escrowPda;
+    }
+
+    // The third escrow in an hour is the first to pay the deposit
+    async function chargedEscrow(): Promise<PublicKey> {
+      await openEscrow(namespace, spamVault);
+      await openEscrow(namespace, spamVault);
+      const escrowPda = await openEscrow(namespace, spamVault);
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.spamDeposit.toNumber()).to.equal(DEPOSIT);
+      return escrowPda;
+    }
+
+    it("creates no vault where the namespace charges no deposit", async () => {
+      await openEscrow(anchor.web3.PublicKey.default, null);
+      expect(await provider.connection.getAccountInfo(spamVault)).to.be.null;
+    });
+
+    it("requires the vault where the namespace charges a deposit", async () => {
+      try {
+        await openEscrow(namespace, null);
+        expect.fail("Should have required the spam vault");
+      } catch (err) {
+        expect(err.toString()).to.include("SpamVaultMissing");
+      }
+    });
+
+    it("refunds the deposit when the API accepts the work", async () => {
+      const escrowPda = await chargedEscrow();
+      const agentBalanceBefore = await provider.connection.getBalance(spammer.publicKey);
+
+      await program.methods
+        .acknowledgeDelivery(new Array(32).fill(3), null, false)
+        .accounts({ escrow: escrowPda, api: api.publicKey, spamVault, agent: spammer.publicKey })
+        .signers([api])
+        .rpc();
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.spamDeposit.toNumber()).to.equal(0);
+      const vault = await program.account.spamProtectionVault.fetch(spamVault);
+      expect(vault.heldLamports.toNumber()).to.equal(0);
+      const agentBalanceAfter = await provider.connection.getBalance(spammer.publicKey);
+      expect(agentBalanceAfter - agentBalanceBefore).to.equal(DEPOSIT);
+    });
+
+    it("needs the vault to accept work that holds a deposit", async () => {
+      const escrowPda = await chargedEscrow();
+
+      try {
+        await program.methods
+          .acknowledgeDelivery(new Array(32).fill(3), null, false)
+          .accounts({ escrow: escrowPda, api: api.publicKey, spamVault: null, agent: null })
+          .signers([api])
+          .rpc();
+        expect.fail("Should have required the spam vault");
+      } catch (err) {
+        expect(err.toString()).to.include("SpamVaultMissing");
+      }
+    });
+
+    it("holds an unaccepted cancel's deposit for a day", async () => {
+      const escrowPda = await chargedEscrow();
+
+      await program.methods
+        .cancelEscrow()
+        .accounts({ escrow: escrowPda, agent: spammer.publicKey, api: api.publicKey, spamVault })
+        .signers([spammer, api])
+        .rpc();
+
+      const vault = await program.account.spamProtectionVault.fetch(spamVault);
+      expect(vault.retainedLamports.toNumber()).to.equal(DEPOSIT);
+      expect(vault.heldLamports.toNumber()).to.equal(DEPOSIT);
+      const now = Math.floor(Date.now() / 1000);
+      expect(vault.retainedUntil.toNumber()).to.be.greaterThan(now + 86400 - 120);
+
+      try {
+        await program.methods
+          .refundRetainedSpamDeposit()
+          .accounts({ spamVault, agent: spammer.publicKey })
+          .rpc();
+        expect.fail("Should have held the deposit for a day");
+      } catch (err) {
+        expect(err.toString()).to.include("SpamDepositLocked");
+      }
     });
   });
 });
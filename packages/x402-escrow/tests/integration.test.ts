@@ -43,6 +43,11 @@ describe('x402Resolve Integration Tests', () => {
     await new Promise(resolve => setTimeout(resolve, 1000)); // Wait for confirmation
   });
 
+  // Status lives in the low two bits of `Escrow.flags`
+  function escrowStatus(flags: number): string {
+    return ['active', 'released', 'disputed', 'resolved'][flags & 0b11];
+  }
+
   function deriveEscrowPDA(transactionId: string): [anchor.web3.PublicKey, number] {
     return anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from('escrow'), Buffer.from(transactionId)],
@@ -59,7 +64,14 @@ describe('x402Resolve Integration Tests', () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -78,7 +90,7 @@ describe('x402Resolve Integration Tests', () => {
       assert.equal(escrow.transactionId, transactionId);
 
       // Verify status is Active
-      assert.ok('active' in escrow.status);
+      assert.equal(escrowStatus(escrow.flags), 'active');
     });
 
     it('rejects amount below minimum', async () => {
@@ -91,7 +103,14 @@ describe('x402Resolve Integration Tests', () => {
           .initializeEscrow(
             new anchor.BN(tooSmall),
             new anchor.BN(TIME_LOCK),
-            transactionId
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
           )
           .accounts({
             escrow: escrowPda,
@@ -118,7 +137,14 @@ describe('x402Resolve Integration Tests', () => {
           .initializeEscrow(
             new anchor.BN(tooLarge),
             new anchor.BN(TIME_LOCK),
-            transactionId
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
           )
           .accounts({
             escrow: escrowPda,
@@ -145,7 +171,14 @@ describe('x402Resolve Integration Tests', () => {
           .initializeEscrow(
             new anchor.BN(ESCROW_AMOUNT),
             new anchor.BN(invalidTimeLock),
-            transactionId
+            transactionId,
+            false,
+            anchor.web3.PublicKey.default,
+            null,
+            null,
+            null,
+            null,
+            null
           )
           .accounts({
             escrow: escrowPda,
@@ -176,7 +209,14 @@ describe('x402Resolve Integration Tests', () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -190,7 +230,7 @@ describe('x402Resolve Integration Tests', () => {
 
     it('allows agent to file dispute', async () => {
       await program.methods
-        .markDisputed()
+        .markDisputed(new anchor.BN(0), { lowQuality: {} }, new Array(32).fill(0))
         .accounts({
           escrow: escrowPda,
           agent: agent.publicKey,
@@ -199,7 +239,7 @@ describe('x402Resolve Integration Tests', () => {
         .rpc();
 
       const escrow = await program.account.escrow.fetch(escrowPda);
-      assert.ok('disputed' in escrow.status);
+      assert.equal(escrowStatus(escrow.flags), 'disputed');
     });
 
     it('rejects dispute from non-agent', async () => {
@@ -209,7 +249,7 @@ describe('x402Resolve Integration Tests', () => {
 
       try {
         await program.methods
-          .markDisputed()
+          .markDisputed(new anchor.BN(0), { lowQuality: {} }, new Array(32).fill(0))
           .accounts({
             escrow: escrowPda,
             agent: nonAgent.publicKey,
@@ -236,7 +276,14 @@ describe('x402Resolve Integration Tests', () => {
         .initializeEscrow(
           new anchor.BN(ESCROW_AMOUNT),
           new anchor.BN(TIME_LOCK),
-          transactionId
+          transactionId,
+          false,
+          anchor.web3.PublicKey.default,
+          null,
+          null,
+          null,
+          null,
+          null
         )
         .accounts({
           escrow: escrowPda,
@@ -266,7 +313,7 @@ describe('x402Resolve Integration Tests', () => {
       assert.ok(apiBalanceAfter > apiBalanceBefore);
 
       const escrow = await program.account.escrow.fetch(escrowPda);
-      assert.ok('released' in escrow.status);
+      assert.equal(escrowStatus(escrow.flags), 'released');
     });
   });
 
@@ -0,0 +1,385 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { X402Escrow } from "../target/types/x402_escrow";
+import { expect } from "chai";
+import {
+  PublicKey,
+  SystemProgram,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  Transaction,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import { BankrunProvider } from "anchor-bankrun";
+import { AddedProgram, Clock, ProgramTestContext, start } from "solana-bankrun";
+import { existsSync } from "fs";
+
+// release_and_close only accepts escrows past `Escrow::sweepable_at`, a week
+// after expiry, and close_escrow a week after release. Localnet cannot warp
+// its clock, so these tests run the program in bankrun instead.
+describe("release_and_close", () => {
+  const ESCROW_AMOUNT = 0.01 * LAMPORTS_PER_SOL;
+  const TIME_LOCK = 86400;
+  const RELEASE_TIP = 50_000;
+  const CLOSE_COOLDOWN = 604_800;
+
+  const BUBBLEGUM = new PublicKey("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+  const COMPRESSION = new PublicKey("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+  const NOOP = new PublicKey("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+  // Receipts need Bubblegum and its programs, dumped from mainnet with
+  // `solana program dump -u m <program id> tests/fixtures/<name>.so`
+  const RECEIPT_PROGRAMS: AddedProgram[] = [
+    { name: "bubblegum", programId: BUBBLEGUM },
+    { name: "spl_account_compression", programId: COMPRESSION },
+    { name: "spl_noop", programId: NOOP },
+  ];
+  const receiptsLoaded = RECEIPT_PROGRAMS.every(({ name }) =>
+    existsSync(`tests/fixtures/${name}.so`)
+  );
+
+  let context: ProgramTestContext;
+  let program: Program<X402Escrow>;
+  let namespace: PublicKey;
+  let config: PublicKey;
+
+  before(async () => {
+    const { idl } = anchor.workspace.X402Escrow as Program<X402Escrow>;
+    process.env.SBF_OUT_DIR ??= "target/deploy";
+    context = await start(
+      [
+        { name: "x402_escrow", programId: new PublicKey(idl.address) },
+        ...(receiptsLoaded ? RECEIPT_PROGRAMS : []),
+      ],
+      []
+    );
+    program = new Program<X402Escrow>(idl, new BankrunProvider(context));
+
+    const platform = await funded();
+    await program.methods
+      .initializeNamespaceConfig()
+      .accounts({ authority: platform.publicKey })
+      .signers([platform])
+      .rpc();
+    namespace = platform.publicKey;
+    [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("global_config"), namespace.toBuffer()],
+      program.programId
+    );
+  });
+
+  async function funded(): Promise<Keypair> {
+    const keypair = Keypair.generate();
+    context.setAccount(keypair.publicKey, {
+      lamports: LAMPORTS_PER_SOL,
+      data: Buffer.alloc(0),
+      owner: SystemProgram.programId,
+      executable: false,
+    });
+    return keypair;
+  }
+
+  async function warpTo(unixTimestamp: number) {
+    const clock = await context.banksClient.getClock();
+    context.setClock(
+      new Clock(
+        clock.slot,
+        clock.epochStartTimestamp,
+        clock.epoch,
+        clock.leaderScheduleEpoch,
+        BigInt(unixTimestamp)
+      )
+    );
+  }
+
+  async function balance(key: PublicKey): Promise<number> {
+    return Number(await context.banksClient.getBalance(key));
+  }
+
+  // The context payer pays every fee, so the parties' balances only move
+  // with the escrow
+  async function send(instructions: TransactionInstruction[], signers: Keypair[]) {
+    const tx = new Transaction().add(...instructions);
+    tx.recentBlockhash = context.lastBlockhash;
+    tx.feePayer = context.payer.publicKey;
+    tx.sign(context.payer, ...signers);
+
+    const simulated = await context.banksClient.simulateTransaction(tx);
+    expect(simulated.result).to.be.null;
+    const result = await context.banksClient.processTransaction(tx);
+    const events = [
+      ...new anchor.EventParser(program.programId, program.coder).parseLogs(result.logMessages),
+    ];
+    return { unitsConsumed: Number(simulated.meta.computeUnitsConsumed), events };
+  }
+
+  // Each path gets its own parties, so their final balances can be compared
+  async function openEscrow(label: string) {
+    const [agent, api, cranker] = [await funded(), await funded(), await funded()];
+    const transactionId = `sweep_${label}_${Date.now()}`;
+    const [escrow] = PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), Buffer.from(transactionId)],
+      program.programId
+    );
+    await program.methods
+      .initializeEscrow(
+        new anchor.BN(ESCROW_AMOUNT),
+        new anchor.BN(TIME_LOCK),
+        transactionId,
+        false,
+        namespace,
+        null,
+        null,
+        null,
+        null,
+        null
+      )
+      .accounts({
+        escrow,
+        agent: agent.publicKey,
+        api: api.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([agent])
+      .rpc();
+    await program.methods
+      .setReleaseTip(new anchor.BN(RELEASE_TIP))
+      .accounts({ escrow, api: api.publicKey })
+      .signers([api])
+      .rpc();
+
+    // Escrow::sweepable_at: the cooldown counts from the later of expiry
+    // and the dispute deadline
+    const { expiresAt, createdAt, disputeWindow } = await program.account.escrow.fetch(escrow);
+    const sweepableAt =
+      Math.max(expiresAt.toNumber(), createdAt.toNumber() + disputeWindow.toNumber()) +
+      CLOSE_COOLDOWN;
+    return { transactionId, escrow, agent, api, cranker, sweepableAt };
+  }
+
+  type Escrow = Awaited<ReturnType<typeof openEscrow>>;
+
+  const balances = async ({ escrow, agent, api, cranker }: Escrow) => ({
+    escrow: await balance(escrow),
+    agent: await balance(agent.publicKey),
+    api: await balance(api.publicKey),
+    cranker: await balance(cranker.publicKey),
+  });
+
+  // A depth 3 tree delegated to the receipt authority: the header, then
+  // eight change logs and the rightmost proof, each of 136 bytes
+  async function receiptTree(): Promise<PublicKey> {
+    const creator = await funded();
+    const merkleTree = Keypair.generate();
+    const [treeConfig] = PublicKey.findProgramAddressSync([merkleTree.publicKey.toBuffer()], BUBBLEGUM);
+    const [receiptAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_authority")],
+      program.programId
+    );
+    const space = 56 + 24 + 8 * 136 + 136;
+    const createTreeData = Buffer.alloc(17);
+    Buffer.from([165, 83, 136, 142, 89, 202, 47, 220]).copy(createTreeData);
+    createTreeData.writeUInt32LE(3, 8);
+    createTreeData.writeUInt32LE(8, 12);
+    createTreeData.writeUInt8(0, 16); // public: None
+
+    await send(
+      [
+        SystemProgram.createAccount({
+          fromPubkey: creator.publicKey,
+          newAccountPubkey: merkleTree.publicKey,
+          lamports: 0.1 * LAMPORTS_PER_SOL,
+          space,
+          programId: COMPRESSION,
+        }),
+        new TransactionInstruction({
+          programId: BUBBLEGUM,
+          keys: [
+            { pubkey: treeConfig, isSigner: false, isWritable: true },
+            { pubkey: merkleTree.publicKey, isSigner: false, isWritable: true },
+            { pubkey: creator.publicKey, isSigner: true, isWritable: true },
+            { pubkey: creator.publicKey, isSigner: true, isWritable: false },
+            { pubkey: NOOP, isSigner: false, isWritable: false },
+            { pubkey: COMPRESSION, isSigner: false, isWritable: false },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+          ],
+          data: createTreeData,
+        }),
+        new TransactionInstruction({
+          programId: BUBBLEGUM,
+          keys: [
+            { pubkey: treeConfig, isSigner: false, isWritable: true },
+            { pubkey: creator.publicKey, isSigner: true, isWritable: false },
+            { pubkey: receiptAuthority, isSigner: false, isWritable: false },
+            { pubkey: merkleTree.publicKey, isSigner: false, isWritable: false },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+          ],
+          data: Buffer.from([253, 118, 66, 37, 190, 49, 154, 102]),
+        }),
+      ],
+      [creator, merkleTree]
+    );
+    return merkleTree.publicKey;
+  }
+
+  const receiptAccounts = (merkleTree: PublicKey) => ({
+    treeConfig: PublicKey.findProgramAddressSync([merkleTree.toBuffer()], BUBBLEGUM)[0],
+    merkleTree,
+    receiptAuthority: PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_authority")],
+      program.programId
+    )[0],
+    logWrapper: NOOP,
+    compressionProgram: COMPRESSION,
+    bubblegumProgram: BUBBLEGUM,
+    systemProgram: SystemProgram.programId,
+  });
+
+  // release_funds by a cranker, an optional receipt, then close_escrow once
+  // the cooldown has passed
+  async function twoStep(escrow: Escrow, merkleTree?: PublicKey) {
+    const release = await program.methods
+      .releaseFunds()
+      .accounts({
+        escrow: escrow.escrow,
+        agent: escrow.cranker.publicKey,
+        api: escrow.api.publicKey,
+        config,
+        systemProgram: SystemProgram.programId,
+      })
+      .instruction();
+    const released = await send([release], [escrow.cranker]);
+
+    let minted: Awaited<ReturnType<typeof send>> = { unitsConsumed: 0, events: [] };
+    if (merkleTree) {
+      const mint = await program.methods
+        .mintEscrowReceipt()
+        .accounts({
+          escrow: escrow.escrow,
+          agent: escrow.agent.publicKey,
+          payer: escrow.cranker.publicKey,
+          ...receiptAccounts(merkleTree),
+        })
+        .instruction();
+      minted = await send([mint], [escrow.cranker]);
+    }
+
+    const { settledAt } = await program.account.escrow.fetch(escrow.escrow);
+    await warpTo(settledAt.toNumber() + CLOSE_COOLDOWN);
+    const close = await program.methods
+      .closeEscrow()
+      .accounts({
+        escrow: escrow.escrow,
+        agent: escrow.agent.publicKey,
+        spamVault: null,
+        escrowVault: null,
+        tokenProgram: null,
+        mint: null,
+        cranker: escrow.cranker.publicKey,
+      })
+      .instruction();
+    const closed = await send([close], [escrow.cranker]);
+
+    return {
+      unitsConsumed: released.unitsConsumed + minted.unitsConsumed + closed.unitsConsumed,
+      events: [...released.events, ...minted.events, ...closed.events],
+    };
+  }
+
+  async function crank(escrow: Escrow, merkleTree?: PublicKey) {
+    const ix = await program.methods
+      .releaseAndClose()
+      .accounts({
+        escrow: escrow.escrow,
+        agent: escrow.agent.publicKey,
+        api: escrow.api.publicKey,
+        spamVault: null,
+        config,
+        cranker: escrow.cranker.publicKey,
+        ...(merkleTree ? receiptAccounts(merkleTree) : {}),
+      })
+      .instruction();
+    return send([ix], [escrow.cranker]);
+  }
+
+  const eventNames = (events: { name: string }[]) => events.map((event) => event.name);
+
+  it("leaves the same balances and events as release_funds then close_escrow", async () => {
+    const stepped = await openEscrow("stepped");
+    const cranked = await openEscrow("cranked");
+    const before = await balances(cranked);
+    expect(await balances(stepped)).to.deep.equal(before);
+
+    await warpTo(cranked.sweepableAt);
+    const steps = await twoStep(stepped);
+    const sweep = await crank(cranked);
+
+    const after = await balances(cranked);
+    expect(await balances(stepped)).to.deep.equal(after);
+    expect(after.escrow).to.equal(0);
+    expect(after.cranker - before.cranker).to.equal(RELEASE_TIP);
+    expect(after.api - before.api).to.equal(ESCROW_AMOUNT - RELEASE_TIP);
+    expect(after.agent - before.agent).to.equal(before.escrow - ESCROW_AMOUNT);
+    expect(eventNames(sweep.events)).to.deep.equal(eventNames(steps.events));
+
+    // One instruction instead of two, and fewer units than both together
+    console.log(
+      `release_funds + close_escrow: ${steps.unitsConsumed} CU, release_and_close: ${sweep.unitsConsumed} CU`
+    );
+    expect(sweep.unitsConsumed).to.be.lessThan(steps.unitsConsumed);
+  });
+
+  it("mints the same receipt as the two-step path when opted in", async function () {
+    if (!receiptsLoaded) this.skip();
+
+    const merkleTree = await receiptTree();
+    const stepped = await openEscrow("stepped_receipt");
+    const cranked = await openEscrow("cranked_receipt");
+    const before = await balances(cranked);
+
+    await warpTo(cranked.sweepableAt);
+    const steps = await twoStep(stepped, merkleTree);
+    const sweep = await crank(cranked, merkleTree);
+
+    const after = await balances(cranked);
+    expect(await balances(stepped)).to.deep.equal(after);
+    expect(after.cranker - before.cranker).to.equal(RELEASE_TIP);
+
+    // The receipt URI starts with the transaction ID and records the same outcome
+    const receipt = (events: { name: string; data: any }[], transactionId: string) =>
+      events
+        .find((event) => event.name === "receiptMinted")
+        .data.uri.replace(transactionId, "");
+    expect(receipt(sweep.events, cranked.transactionId)).to.equal(
+      receipt(steps.events, stepped.transactionId)
+    );
+    expect(eventNames(sweep.events)).to.deep.equal(eventNames(steps.events));
+    console.log(
+      `with receipt: release_funds + mint_escrow_receipt + close_escrow: ${steps.unitsConsumed} CU, release_and_close: ${sweep.unitsConsumed} CU`
+    );
+  });
+
+  it("waits for the close cooldown after expiry", async () => {
+    const escrow = await openEscrow("fresh");
+    await warpTo(escrow.sweepableAt - 1);
+
+    const ix = await program.methods
+      .releaseAndClose()
+      .accounts({
+        escrow: escrow.escrow,
+        agent: escrow.agent.publicKey,
+        api: escrow.api.publicKey,
+        spamVault: null,
+        config,
+        cranker: escrow.cranker.publicKey,
+      })
+      .instruction();
+    const tx = new Transaction().add(ix);
+    tx.recentBlockhash = context.lastBlockhash;
+    tx.feePayer = context.payer.publicKey;
+    tx.sign(context.payer, escrow.cranker);
+    const { result, meta } = await context.banksClient.tryProcessTransaction(tx);
+    expect(result).to.not.be.null;
+    expect(meta.logMessages.join("\n")).to.include("CloseCooldownActive");
+    expect(await balance(escrow.escrow)).to.be.greaterThan(ESCROW_AMOUNT);
+  });
+});
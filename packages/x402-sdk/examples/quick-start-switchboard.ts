@@ -61,7 +61,6 @@ async function quickStart() {
   await escrowClient.resolveDisputeSwitchboard(
     txId,
     assessment.qualityScore,
-    assessment.refundPercentage,
     assessment.attestation
   );
 
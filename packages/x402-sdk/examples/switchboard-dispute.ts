@@ -117,7 +117,6 @@ async function main() {
   const resolveTx = await escrowClient.resolveDisputeSwitchboard(
     transactionId,
     assessment.qualityScore,
-    assessment.refundPercentage,
     assessment.attestation
   );
 
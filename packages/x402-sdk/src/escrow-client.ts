@@ -5,7 +5,21 @@
 
 import * as anchor from '@coral-xyz/anchor';
 import { Program, AnchorProvider, Idl } from '@coral-xyz/anchor';
-import { PublicKey, Keypair, SystemProgram, Connection, Transaction } from '@solana/web3.js';
+import {
+  PublicKey,
+  Keypair,
+  SystemProgram,
+  Connection,
+  Transaction,
+  AccountMeta,
+} from '@solana/web3.js';
+import {
+  createAssociatedTokenAccountIdempotentInstruction,
+  createSyncNativeInstruction,
+  getAssociatedTokenAddressSync,
+  NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
+} from '@solana/spl-token';
 import IDL from '../types/x402_escrow.json';
 
 type X402Escrow = any; // Type will be inferred from IDL
@@ -16,72 +30,2334 @@ export interface EscrowConfig {
   wallet: anchor.Wallet;
 }
 
+/**
+ * HTTP 402 exchange an escrow pays for
+ */
+export interface PaymentReference {
+  /** SHA-256 of "{METHOD} {path}" */
+  methodAndPathHash: number[];
+  /** Nonce the server issued in its 402 response (16 bytes, non-zero) */
+  challengeNonce: number[];
+  clientIdHash: number[];
+}
+
+/**
+ * Scope an agent binds to an escrow with `createWorkAgreement`
+ */
+export interface WorkAgreementTerms {
+  /** Scope of the request (max 128 bytes) */
+  query: string;
+  requiredFields: number;
+  minRecords: number;
+  maxAgeDays: number;
+  /** Quality below this only settles as a full refund (0-100) */
+  minQualityScore: number;
+  /** Five steps with ascending scores ending at 100, in place of the namespace's curve */
+  refundCurve?: RefundStep[];
+  /**
+   * Percentage weight of up to four score dimensions (e.g. latency,
+   * accuracy, completeness), summing to 100; resolved with
+   * `resolveDisputeScored`. Omit for a single score.
+   */
+  scoreWeights?: number[];
+}
+
+/**
+ * One step of a refund curve: scores up to `maxQualityScore` refund
+ * `refundPercentage` of the escrow
+ */
+export interface RefundStep {
+  maxQualityScore: number;
+  refundPercentage: number;
+}
+
+/**
+ * One verifier's signature for `resolveDisputeQuorum`
+ */
+export interface QuorumSignature {
+  /** Position of the signer in the namespace's verifier set */
+  verifierIndex: number;
+  signature: number[];
+}
+
+/**
+ * Split `settleStaleDispute` applies to a dispute no verifier resolves
+ */
+export interface StaleDisputeTerms {
+  /** Seconds from the dispute, 1 to 30 days */
+  verifierTimeout: anchor.BN;
+  /** Refund to the agent (0-100) */
+  refundPercentage: number;
+}
+
+/**
+ * Standard escrow terms an API publishes with `publishProviderTerms`
+ */
+export interface ProviderTermsParams {
+  /** Namespace escrows on these terms must belong to (defaults to the global one) */
+  namespace?: PublicKey;
+  minAmount: anchor.BN;
+  maxAmount: anchor.BN;
+  /** Time lock every escrow on these terms takes */
+  timeLock: anchor.BN;
+  /** Seconds disputes are accepted; at most the time lock or 48 hours */
+  disputeWindow: anchor.BN;
+  /** Five steps with ascending scores ending at 100, in place of the namespace's curve */
+  refundCurve: RefundStep[];
+}
+
 export interface CreateEscrowParams {
   amount: anchor.BN;
   timeLock: anchor.BN;
   transactionId: string;
   apiPublicKey: PublicKey;
+  /** Opt out of arbitration: the escrow can only be released */
+  noDispute?: boolean;
+  /** Platform namespace whose config governs the escrow (defaults to the global one) */
+  namespace?: PublicKey;
+  /** Require cover from the API's service bond; fails if it is below the provider's minimum */
+  requireServiceBond?: boolean;
+  /** Record the 402 challenge being paid so both sides commit to it */
+  paymentReference?: PaymentReference;
+  /** SHA-256 of the request payload; verifiers sign resolutions over it */
+  requestHash?: number[];
+  /** Wallet dispute refunds are paid to instead of the agent, e.g. a cold wallet */
+  refundRecipient?: PublicKey;
+  /** Link, up to 200 characters, to JSON describing the call (endpoint, SLA, pricing) */
+  metadataUri?: string;
+  /** Settle unresolved disputes sooner and at this refund instead of 50% */
+  staleDisputeTerms?: StaleDisputeTerms;
+  /**
+   * Organization whose API allowlist the escrow must satisfy; required for
+   * agents the organization restricts
+   */
+  allowlistOrganization?: PublicKey;
+  /**
+   * Agent the escrow is opened for when the wallet is one of its session
+   * keys; the session key funds the escrow
+   */
+  sessionAgent?: PublicKey;
+}
+
+/**
+ * Byte layout of escrow accounts, mirroring the program's `layout` module
+ */
+export const ESCROW_LAYOUT = {
+  version: 1,
+  agentOffset: 8,
+  apiOffset: 40,
+  namespaceOffset: 72,
+  versionOffset: 104,
+  flagsOffset: 105,
+  statusMask: 0b11,
+  flags: {
+    exposureTracked: 1 << 4,
+    noDispute: 1 << 5,
+    settledStale: 1 << 6,
+    serviceBonded: 1 << 7,
+    workAgreement: 1 << 8,
+    milestones: 1 << 9,
+    appealed: 1 << 10,
+    streaming: 1 << 11,
+    escalated: 1 << 12,
+    deliveryClock: 1 << 13,
+    receiptMinted: 1 << 14,
+    indexed: 1 << 15,
+  },
+} as const;
+
+const BUBBLEGUM_PROGRAM_ID = new PublicKey('BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY');
+const SPL_NOOP_PROGRAM_ID = new PublicKey('noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV');
+const SPL_ACCOUNT_COMPRESSION_PROGRAM_ID = new PublicKey(
+  'cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK'
+);
+const WORMHOLE_PROGRAM_ID = new PublicKey('worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth');
+const SETTLEMENT_PAYLOAD_ID = 1;
+
+/**
+ * Outcome of a resolved escrow, as carried by its Wormhole message
+ */
+export interface SettlementPayload {
+  escrow: PublicKey;
+  agent: PublicKey;
+  api: PublicKey;
+  /** Null for SOL escrows */
+  mint: PublicKey | null;
+  amount: bigint;
+  refundedToAgent: bigint;
+  paidToApi: bigint;
+  /** Null when the escrow was never assessed */
+  qualityScore: number | null;
+  refundPercentage: number;
+  settledAt: number;
+  transactionId: string;
 }
 
+/**
+ * Scope bits of a session key, combined with `|`
+ */
+export const SESSION_SCOPES = {
+  escrow: 1 << 0,
+  dispute: 1 << 1,
+} as const;
+
+/**
+ * Schema version of the program's events, carried in each event's `version`
+ */
+export const EVENT_VERSION = 4;
+
+const ESCROW_STATUSES = ['Active', 'Released', 'Disputed', 'Resolved'] as const;
+
+export type EscrowStatusName = (typeof ESCROW_STATUSES)[number];
+
 export interface EscrowAccount {
   agent: PublicKey;
   api: PublicKey;
+  namespace: PublicKey;
+  version: number;
+  /** Status and boolean flags, decode with `EscrowUtils` */
+  flags: number;
   amount: anchor.BN;
-  status: any;
   createdAt: anchor.BN;
   expiresAt: anchor.BN;
   transactionId: string;
   bump: number;
   qualityScore?: number;
   refundPercentage?: number;
+  deliveredAt: anchor.BN | null;
+  /** Seconds disputes stay open, from delivery or from creation until then */
+  disputeWindow: anchor.BN;
+  paymentReference: PaymentReference | null;
+  /** Request payload the agent committed to at creation */
+  requestHash: number[] | null;
+  /** Wallet dispute refunds are paid to, when not the agent */
+  refundRecipient: PublicKey | null;
+  /** Key that may release or dispute for the agent */
+  delegate: PublicKey | null;
+  /** Off-chain JSON describing the API call */
+  metadataUri: string | null;
+  /** Payout accumulators checked by the program's `audit_escrow` */
+  paidToApi: anchor.BN;
+  refundedToAgent: anchor.BN;
+  feesPaid: anchor.BN;
+  /** Token held by SPL escrows, null for SOL escrows */
+  mint: PublicKey | null;
+  /** Release or resolution time; `closeEscrow` opens 7 days later */
+  settledAt: anchor.BN | null;
+  /** Dispute cost held until resolution; returned at a 75%+ refund */
+  disputeBond: anchor.BN;
+  /** Creation slot, signed into every resolution of this escrow */
+  resolutionNonce: anchor.BN;
+  /** End of the appeal window of a held resolution */
+  appealDeadline: anchor.BN | null;
+  /** Verifier of a held resolution, excluded from its appeal */
+  firstVerifier: PublicKey | null;
+  /** Verifier signature stored by `submitResolution` */
+  resolutionSignature: number[] | null;
+  /** Dollar value of a USD-priced escrow, in cents */
+  usdTargetCents: anchor.BN | null;
+  /** Withdrawn by the API from a streaming escrow */
+  streamedAmount: anchor.BN;
+  /** Arbitrator a held resolution was escalated to */
+  arbitrator: PublicKey | null;
+  /** Party that posted the arbitration bond */
+  escalatedBy: PublicKey | null;
+  /** Held until the arbitrator rules or the arbitration period ends */
+  arbitrationBond: anchor.BN;
+  /** When the agent disputed the escrow */
+  disputedAt: anchor.BN | null;
+  /** Claim category given when disputing */
+  disputeReason: DisputeReason | null;
+  /** SHA-256 of the agent's off-chain dispute evidence */
+  evidenceHash: number[] | null;
+  /** SHA-256 of the API's counter-evidence */
+  providerEvidenceHash: number[] | null;
+  /** API's account of the exchange */
+  providerCommentUri: string | null;
+  /** Split agreed at creation for a dispute no verifier resolves */
+  staleDisputeTerms: StaleDisputeTerms | null;
+  /** Offered by the API to whoever releases the escrow after expiry */
+  releaseTipLamports: anchor.BN;
+  /** Copied from the API's published terms; replaces the namespace's curve */
+  refundCurve: RefundStep[] | null;
+  /** Per-dimension scores of a weighted work agreement's resolution */
+  qualitySubScores: number[] | null;
+  /** Spam protection deposit held in the agent's vault until acceptance */
+  spamDeposit: anchor.BN;
+}
+
+/**
+ * What an agent claims went wrong with a disputed escrow
+ */
+export type DisputeReason =
+  | { other: {} }
+  | { notDelivered: {} }
+  | { incomplete: {} }
+  | { invalidData: {} }
+  | { lowQuality: {} }
+  | { slaBreach: {} };
+
+export type ResolutionMethod =
+  | { ed25519: {} }
+  | { switchboard: {} }
+  | { pyth: {} }
+  | { quorum: {} }
+  | { arbitrator: {} };
+
+export interface DisputeResolvedEvent {
+  version: number;
+  escrow: PublicKey;
+  transactionId: string;
+  qualityScore: number;
+  refundPercentage: number;
+  refundAmount: anchor.BN;
+  paymentAmount: anchor.BN;
+  /** Verifier key, or the feed account for oracle resolutions */
+  verifier: PublicKey;
+  contentHash: number[] | null;
+  agent: PublicKey;
+  api: PublicKey;
+  method: ResolutionMethod;
+  /** Seconds from `markDisputed` to resolution, null if never marked */
+  disputeDuration: anchor.BN | null;
+}
+
+export type ReputationChangeReason =
+  | { disputeWon: {} }
+  | { disputePartial: {} }
+  | { disputeLost: {} }
+  | { decay: {} };
+
+export interface ReputationUpdatedEvent {
+  version: number;
+  entity: PublicKey;
+  oldScore: number;
+  newScore: number;
+  reason: ReputationChangeReason;
+  currentWinStreak: number;
+  longestWinStreak: number;
+  worstQualityReceived: number;
+  bestQualityReceived: number;
+  totalRefundsReceivedLamports: anchor.BN;
+  totalPaymentsMadeLamports: anchor.BN;
+  timestamp: anchor.BN;
+}
+
+export interface ScoreSnapshot {
+  score: number;
+  timestamp: anchor.BN;
+}
+
+export interface ReputationView {
+  entity: PublicKey;
+  initialized: boolean;
+  entityType: { agent: {} } | { provider: {} } | null;
+  reputationScore: number;
+  totalTransactions: anchor.BN;
+  disputesFiled: anchor.BN;
+  disputesLost: anchor.BN;
+  averageQualityBps: number;
+  /** Distinct counterparties among the entity's last 8 resolutions */
+  distinctRecentCounterparties: number;
+  lastUpdated: anchor.BN;
+}
+
+export interface SubscriptionAccount {
+  agent: PublicKey;
+  api: PublicKey;
+  namespace: PublicKey;
+  pricePerPeriod: anchor.BN;
+  periodSeconds: anchor.BN;
+  /** Start of the oldest unsettled period */
+  periodStart: anchor.BN;
+  /** When the API may claim that period, absent a dispute */
+  claimableAt: anchor.BN;
+  fundedPeriods: number;
+  /** Index of the oldest unsettled period */
+  periodsSettled: number;
+  disputed: boolean;
+  bump: number;
+}
+
+export interface OrganizationMemberAccount {
+  organization: PublicKey;
+  agent: PublicKey;
+  /** Lamports of SOL escrows the agent may open per UTC day */
+  dailyLimit: anchor.BN;
+  spentToday: anchor.BN;
+  /** Day `spentToday` counts, in days since the epoch */
+  spendDay: anchor.BN;
+  bump: number;
+}
+
+export interface ChannelEntry {
+  id: anchor.BN;
+  /** Cut to the API's share once resolved */
+  amount: anchor.BN;
+  recordedAt: anchor.BN;
+  disputed: boolean;
+  resolved: boolean;
+}
+
+export interface PaymentChannelAccount {
+  agent: PublicKey;
+  api: PublicKey;
+  namespace: PublicKey;
+  /** Deposited and not yet paid to the API */
+  balance: anchor.BN;
+  /** API credits not yet offset against debits */
+  carriedCredit: anchor.BN;
+  nextEntryId: anchor.BN;
+  totalSettled: anchor.BN;
+  /** Unsettled debits, oldest first */
+  entries: ChannelEntry[];
+  bump: number;
 }
 
 export class EscrowClient {
   private program: Program<X402Escrow>;
   private provider: AnchorProvider;
 
-  constructor(config: EscrowConfig, idl: Idl) {
-    this.provider = new AnchorProvider(
-      config.connection,
-      config.wallet,
-      AnchorProvider.defaultOptions()
-    );
-    this.program = new Program(idl as X402Escrow, this.provider);
-    // Note: programId is derived from IDL, or we can override if needed
+  constructor(config: EscrowConfig, idl: Idl) {
+    this.provider = new AnchorProvider(
+      config.connection,
+      config.wallet,
+      AnchorProvider.defaultOptions()
+    );
+    this.program = new Program(idl as X402Escrow, this.provider);
+    // Note: programId is derived from IDL, or we can override if needed
+  }
+
+  /**
+   * Derive escrow PDA from transaction ID
+   */
+  deriveEscrowAddress(transactionId: string): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('escrow'), Buffer.from(transactionId)],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive reputation PDA from entity address
+   */
+  deriveReputationAddress(entity: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('reputation'), entity.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a provider's service bond PDA
+   */
+  deriveServiceBondAddress(provider: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('service_bond'), provider.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a namespace's verifier assignment queue PDA
+   */
+  deriveAssignmentQueueAddress(namespace: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('assign_queue'), namespace.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a verifier's oracle account PDA within a namespace
+   */
+  deriveVerifierOracleAddress(namespace: PublicKey, verifier: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('verifier_oracle'), namespace.toBuffer(), verifier.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive an agent's spam protection vault PDA
+   */
+  deriveSpamVaultAddress(agent: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('spam_vault'), agent.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the work agreement PDA bound to an escrow
+   */
+  deriveWorkAgreementAddress(escrowPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('work_agreement'), escrowPda.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the milestone plan PDA of an escrow
+   */
+  deriveMilestonePlanAddress(escrowPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('milestones'), escrowPda.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a provider's penalties PDA
+   */
+  deriveProviderPenaltiesAddress(provider: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('provider_penalties'), provider.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a namespace's quorum verifier set PDA
+   */
+  deriveVerifierSetAddress(namespace: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('verifier_set'), namespace.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a signer's consumed-nonce registry PDA
+   */
+  deriveUsedNoncesAddress(signer: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('used_nonces'), signer.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the subscription PDA between an agent and an API
+   */
+  deriveSubscriptionAddress(agent: PublicKey, api: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('subscription'), agent.toBuffer(), api.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the PDA of a set of terms an API has published
+   */
+  deriveProviderTermsAddress(api: PublicKey, termsId: number): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('provider_terms'), api.toBuffer(), Buffer.from([termsId])],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the API allowlist PDA of an organization
+   */
+  deriveAllowlistAddress(organization: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('allowlist'), organization.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the organization membership PDA of an agent
+   */
+  deriveOrganizationMemberAddress(agent: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('org_member'), agent.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the PDA that must be the delegate of receipt Merkle trees
+   */
+  deriveReceiptAuthorityAddress(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('receipt_authority')],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the program's Wormhole emitter, the only emitter whose VAAs are
+   * settlements of this program
+   */
+  deriveWormholeEmitterAddress(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('wormhole_emitter')],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the escrow index PDA of an agent or an API
+   */
+  deriveEscrowIndexAddress(party: PublicKey, role: 'agent' | 'api'): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from(`${role}_index`), party.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the entry at `position` of an agent's or an API's escrow index
+   */
+  deriveEscrowIndexEntryAddress(
+    party: PublicKey,
+    role: 'agent' | 'api',
+    position: number | anchor.BN
+  ): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [
+        Buffer.from(`${role}_escrow`),
+        party.toBuffer(),
+        new anchor.BN(position).toArrayLike(Buffer, 'le', 8),
+      ],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the PDA authorizing a session key to act for an agent
+   */
+  deriveSessionKeyAddress(agent: PublicKey, sessionKey: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('session_key'), agent.toBuffer(), sessionKey.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the payment channel PDA between an agent and an API
+   */
+  derivePaymentChannelAddress(agent: PublicKey, api: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('payment_channel'), agent.toBuffer(), api.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive the protocol-wide insurance fund PDA
+   */
+  deriveInsuranceFundAddress(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync([Buffer.from('insurance_fund')], this.program.programId);
+  }
+
+  /**
+   * Derive an entity's reputation history PDA
+   */
+  deriveReputationHistoryAddress(entity: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('reputation_history'), entity.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Derive a namespace's registry entry for an arbitrator
+   */
+  deriveArbitratorAddress(namespace: PublicKey, authority: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('arbitrator'), namespace.toBuffer(), authority.toBuffer()],
+      this.program.programId
+    );
+  }
+
+  /**
+   * Work agreement the program expects alongside a resolution, if any
+   */
+  private workAgreementFor(escrowPda: PublicKey, escrow: EscrowAccount): PublicKey | null {
+    return EscrowUtils.hasFlag(escrow.flags, ESCROW_LAYOUT.flags.workAgreement)
+      ? this.deriveWorkAgreementAddress(escrowPda)[0]
+      : null;
+  }
+
+  /**
+   * Service bond the program expects alongside a resolution, if any
+   */
+  private serviceBondFor(escrow: EscrowAccount): PublicKey | null {
+    return EscrowUtils.hasFlag(escrow.flags, ESCROW_LAYOUT.flags.serviceBonded)
+      ? this.deriveServiceBondAddress(escrow.api)[0]
+      : null;
+  }
+
+  /**
+   * Reputation histories a resolution should append to; null where the
+   * entity keeps none
+   */
+  private async reputationHistoriesFor(
+    escrow: EscrowAccount
+  ): Promise<{ agentReputationHistory: PublicKey | null; apiReputationHistory: PublicKey | null }> {
+    const [agentHistory] = this.deriveReputationHistoryAddress(escrow.agent);
+    const [apiHistory] = this.deriveReputationHistoryAddress(escrow.api);
+    const [agentInfo, apiInfo] = await this.provider.connection.getMultipleAccountsInfo([
+      agentHistory,
+      apiHistory,
+    ]);
+    return {
+      agentReputationHistory: agentInfo ? agentHistory : null,
+      apiReputationHistory: apiInfo ? apiHistory : null,
+    };
+  }
+
+  /**
+   * Treasury a dispute settlement must pass: needed when the namespace
+   * charges a fee or forfeits dispute bonds to it
+   */
+  private async disputeTreasuryFor(escrow: EscrowAccount): Promise<PublicKey | null> {
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), escrow.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps, disputeBondToTreasury } = await (
+      this.program.account as any
+    ).globalConfig.fetch(config);
+    return feeBps > 0 || disputeBondToTreasury ? treasury : null;
+  }
+
+  /**
+   * Agent's spam protection vault, passed only when the namespace charges a
+   * deposit so agents elsewhere never pay its rent
+   */
+  private async spamVaultFor(namespace: PublicKey, agent: PublicKey): Promise<PublicKey | null> {
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), namespace.toBuffer()],
+      this.program.programId
+    );
+    const { spamProtectionDeposit } = await (this.program.account as any).globalConfig.fetch(config);
+    return spamProtectionDeposit.gtn(0) ? this.deriveSpamVaultAddress(agent)[0] : null;
+  }
+
+  /**
+   * Create a new escrow
+   */
+  async createEscrow(params: CreateEscrowParams): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+    const agent = params.sessionAgent ?? this.provider.wallet.publicKey;
+
+    const method = params.sessionAgent
+      ? 'initializeEscrowWithSession'
+      : params.allowlistOrganization
+        ? 'initializeEscrowRestricted'
+        : 'initializeEscrow';
+
+    const tx: string = await (this.program.methods as any)
+      [method](...this.initializeEscrowArgs(params))
+      .accounts({
+        escrow: escrowPda,
+        ...(params.sessionAgent
+          ? { agent: params.sessionAgent, sessionSigner: this.provider.wallet.publicKey }
+          : { agent: this.provider.wallet.publicKey }),
+        api: params.apiPublicKey,
+        serviceBond: params.requireServiceBond
+          ? this.deriveServiceBondAddress(params.apiPublicKey)[0]
+          : null,
+        allowlist: params.allowlistOrganization
+          ? this.deriveAllowlistAddress(params.allowlistOrganization)[0]
+          : null,
+        spamVault: await this.spamVaultFor(params.namespace ?? PublicKey.default, agent),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Build an escrow whose rent and transaction fee a sponsor, such as a
+   * relayer or paymaster, pays; the wallet still funds the escrow itself
+   *
+   * Returns the transaction signed by the wallet, for the sponsor to sign
+   * as fee payer and submit. Rent goes back to the agent on close.
+   */
+  async createSponsoredEscrowTransaction(
+    params: Omit<CreateEscrowParams, 'sessionAgent'>,
+    payer: PublicKey
+  ): Promise<Transaction> {
+    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+
+    const transaction: Transaction = await (this.program.methods as any)
+      [params.allowlistOrganization ? 'initializeEscrowRestricted' : 'initializeEscrow'](
+        ...this.initializeEscrowArgs(params)
+      )
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        payer,
+        api: params.apiPublicKey,
+        serviceBond: params.requireServiceBond
+          ? this.deriveServiceBondAddress(params.apiPublicKey)[0]
+          : null,
+        allowlist: params.allowlistOrganization
+          ? this.deriveAllowlistAddress(params.allowlistOrganization)[0]
+          : null,
+        spamVault: await this.spamVaultFor(
+          params.namespace ?? PublicKey.default,
+          this.provider.wallet.publicKey
+        ),
+        systemProgram: SystemProgram.programId,
+      })
+      .transaction();
+    transaction.feePayer = payer;
+    transaction.recentBlockhash = (await this.provider.connection.getLatestBlockhash()).blockhash;
+
+    return this.provider.wallet.signTransaction(transaction);
+  }
+
+  /**
+   * Open an escrow on a set of terms the API has published
+   *
+   * The time lock and namespace are taken from the terms, and the amount
+   * must fall within their range. The escrow resolves disputes on the
+   * terms' refund curve.
+   */
+  async createEscrowFromTerms(
+    params: Omit<
+      CreateEscrowParams,
+      'timeLock' | 'namespace' | 'allowlistOrganization' | 'sessionAgent'
+    >,
+    termsId: number
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+    const [providerTerms] = this.deriveProviderTermsAddress(params.apiPublicKey, termsId);
+    const terms = await (this.program.account as any).providerTerms.fetch(providerTerms);
+
+    const tx: string = await (this.program.methods as any)
+      .initializeEscrowFromTerms(
+        ...this.initializeEscrowArgs({
+          ...params,
+          timeLock: terms.timeLock,
+          namespace: terms.namespace,
+        })
+      )
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: params.apiPublicKey,
+        serviceBond: params.requireServiceBond
+          ? this.deriveServiceBondAddress(params.apiPublicKey)[0]
+          : null,
+        providerTerms,
+        spamVault: await this.spamVaultFor(terms.namespace, this.provider.wallet.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Publish standard terms agents can open escrows on; the wallet is the API
+   *
+   * Published terms cannot change; retire them and publish again instead.
+   */
+  async publishProviderTerms(termsId: number, params: ProviderTermsParams): Promise<string> {
+    const [providerTerms] = this.deriveProviderTermsAddress(
+      this.provider.wallet.publicKey,
+      termsId
+    );
+
+    const tx: string = await (this.program.methods as any)
+      .publishProviderTerms(
+        termsId,
+        params.namespace ?? PublicKey.default,
+        params.minAmount,
+        params.maxAmount,
+        params.timeLock,
+        params.disputeWindow,
+        params.refundCurve
+      )
+      .accounts({
+        providerTerms,
+        api: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Retire published terms and reclaim their rent; open escrows keep theirs
+   */
+  async retireProviderTerms(termsId: number): Promise<string> {
+    const [providerTerms] = this.deriveProviderTermsAddress(
+      this.provider.wallet.publicKey,
+      termsId
+    );
+
+    const tx: string = await (this.program.methods as any)
+      .retireProviderTerms()
+      .accounts({
+        providerTerms,
+        api: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Arguments `initialize_escrow` and its restricted and session variants take
+   */
+  private initializeEscrowArgs(params: CreateEscrowParams): unknown[] {
+    return [
+      params.amount,
+      params.timeLock,
+      params.transactionId,
+      params.noDispute ?? false,
+      params.namespace ?? PublicKey.default,
+      params.paymentReference ?? null,
+      params.requestHash ?? null,
+      params.refundRecipient ?? null,
+      params.metadataUri ?? null,
+      params.staleDisputeTerms ?? null,
+    ];
+  }
+
+  /**
+   * Create an escrow worth `usdTargetCents`, locking lamports at the
+   * namespace's Pyth SOL/USD price. Fails if that exceeds `maxAmount`.
+   */
+  async createEscrowUsd(
+    params: Omit<CreateEscrowParams, 'amount'> & {
+      usdTargetCents: anchor.BN;
+      maxAmount: anchor.BN;
+      pythPriceUpdate: PublicKey;
+    }
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .initializeEscrowUsd(
+        params.usdTargetCents,
+        params.timeLock,
+        params.transactionId,
+        params.noDispute ?? false,
+        params.namespace ?? PublicKey.default,
+        params.paymentReference ?? null,
+        params.maxAmount,
+        params.requestHash ?? null,
+        params.refundRecipient ?? null,
+        params.metadataUri ?? null,
+        params.staleDisputeTerms ?? null
+      )
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: params.apiPublicKey,
+        serviceBond: params.requireServiceBond
+          ? this.deriveServiceBondAddress(params.apiPublicKey)[0]
+          : null,
+        pythPriceUpdate: params.pythPriceUpdate,
+        allowlist: params.allowlistOrganization
+          ? this.deriveAllowlistAddress(params.allowlistOrganization)[0]
+          : null,
+        spamVault: await this.spamVaultFor(
+          params.namespace ?? PublicKey.default,
+          this.provider.wallet.publicKey
+        ),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Token program that owns a mint: SPL Token or Token-2022
+   */
+  async getMintTokenProgram(mint: PublicKey): Promise<PublicKey> {
+    const info = await this.provider.connection.getAccountInfo(mint);
+    if (!info) {
+      throw new Error(`Mint ${mint.toBase58()} not found`);
+    }
+    return info.owner;
+  }
+
+  /**
+   * Create an escrow holding SPL tokens (e.g. USDC) from the wallet's
+   * associated token account
+   *
+   * Token-2022 mints work too; with a transfer fee the escrow holds what
+   * reaches its vault. A mint with a transfer hook needs the hook's extra
+   * accounts, here and on every payout. Escrowing wrapped SOL wraps the
+   * amount from the wallet first.
+   */
+  async createEscrowSpl(
+    params: Omit<CreateEscrowParams, 'noDispute' | 'requireServiceBond' | 'paymentReference'> & {
+      mint: PublicKey;
+      transferHookAccounts?: AccountMeta[];
+    }
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+    const agent = this.provider.wallet.publicKey;
+    const tokenProgram = await this.getMintTokenProgram(params.mint);
+    const agentTokenAccount = getAssociatedTokenAddressSync(params.mint, agent, false, tokenProgram);
+
+    const preInstructions = params.mint.equals(NATIVE_MINT)
+      ? [
+          createAssociatedTokenAccountIdempotentInstruction(agent, agentTokenAccount, agent, NATIVE_MINT),
+          SystemProgram.transfer({
+            fromPubkey: agent,
+            toPubkey: agentTokenAccount,
+            lamports: BigInt(params.amount.toString()),
+          }),
+          createSyncNativeInstruction(agentTokenAccount),
+        ]
+      : [];
+
+    const tx: string = await (this.program.methods as any)
+      .initializeEscrowSpl(
+        params.amount,
+        params.timeLock,
+        params.transactionId,
+        params.namespace ?? PublicKey.default
+      )
+      .accounts({
+        escrow: escrowPda,
+        mint: params.mint,
+        agent,
+        agentTokenAccount,
+        api: params.apiPublicKey,
+        allowlist: params.allowlistOrganization
+          ? this.deriveAllowlistAddress(params.allowlistOrganization)[0]
+          : null,
+        spamVault: await this.spamVaultFor(params.namespace ?? PublicKey.default, agent),
+        tokenProgram,
+      })
+      .remainingAccounts(params.transferHookAccounts ?? [])
+      .preInstructions(preInstructions)
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Release expired escrows of one namespace in a single transaction
+   *
+   * Anyone may crank; the wallet earns a small tip per escrow out of the
+   * API's payment. Every escrow must be past its time lock and dispute window.
+   * Batches holding USD escrows need `pythPriceUpdate` for the namespace's
+   * SOL/USD feed.
+   */
+  async crankReleaseExpired(
+    transactionIds: string[],
+    namespace: PublicKey = PublicKey.default,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps, releaseInsuranceBps } = await (
+      this.program.account as any
+    ).globalConfig.fetch(config);
+
+    const remainingAccounts = [];
+    for (const transactionId of transactionIds) {
+      const [escrowPda] = this.deriveEscrowAddress(transactionId);
+      const escrow = await this.getEscrow(transactionId);
+      for (const pubkey of [
+        escrowPda,
+        escrow.api,
+        this.deriveReputationAddress(escrow.agent)[0],
+        this.deriveReputationAddress(escrow.api)[0],
+        // USD escrows refund their repriced surplus to the agent
+        ...(escrow.usdTargetCents ? [escrow.agent] : []),
+      ]) {
+        remainingAccounts.push({ pubkey, isSigner: false, isWritable: true });
+      }
+    }
+
+    const tx: string = await (this.program.methods as any)
+      .crankReleaseExpired()
+      .accounts({
+        config,
+        treasury: feeBps > 0 ? treasury : null,
+        insuranceFund: releaseInsuranceBps > 0 ? this.deriveInsuranceFundAddress()[0] : null,
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        cranker: this.provider.wallet.publicKey,
+      })
+      .remainingAccounts(remainingAccounts)
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Release an SPL escrow's tokens to the API's associated token account
+   */
+  async releaseFundsSpl(
+    transactionId: string,
+    transferHookAccounts: AccountMeta[] = []
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    if (!escrow.mint) {
+      throw new Error('Escrow holds SOL; use releaseFunds');
+    }
+    const tokenProgram = await this.getMintTokenProgram(escrow.mint);
+
+    const tx: string = await (this.program.methods as any)
+      .releaseFundsSpl()
+      .accounts({
+        escrow: escrowPda,
+        mint: escrow.mint,
+        agent: this.provider.wallet.publicKey,
+        apiTokenAccount: getAssociatedTokenAddressSync(escrow.mint, escrow.api, false, tokenProgram),
+        tokenProgram,
+      })
+      .remainingAccounts(transferHookAccounts)
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Split a disputed SPL escrow no verifier resolved (permissionless)
+   *
+   * Available once the dispute is stale: by default 7 days after the time
+   * lock expires, or after the escrow's own verifier timeout.
+   */
+  async settleStaleDisputeSpl(
+    transactionId: string,
+    transferHookAccounts: AccountMeta[] = []
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    if (!escrow.mint) {
+      throw new Error('Escrow holds SOL, not tokens');
+    }
+    const tokenProgram = await this.getMintTokenProgram(escrow.mint);
+
+    const tx: string = await (this.program.methods as any)
+      .settleStaleDisputeSpl()
+      .accounts({
+        escrow: escrowPda,
+        mint: escrow.mint,
+        agentTokenAccount: getAssociatedTokenAddressSync(escrow.mint, escrow.agent, false, tokenProgram),
+        apiTokenAccount: getAssociatedTokenAddressSync(escrow.mint, escrow.api, false, tokenProgram),
+        agent: escrow.agent,
+        api: escrow.api,
+        tokenProgram,
+      })
+      .remainingAccounts(transferHookAccounts)
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Return the spam protection deposits held back from an agent's cancelled
+   * escrows, once a day has passed since the latest such cancellation
+   */
+  async refundRetainedSpamDeposit(agent: PublicKey = this.provider.wallet.publicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .refundRetainedSpamDeposit()
+      .accounts({
+        spamVault: this.deriveSpamVaultAddress(agent)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Cancel an undelivered escrow and recover the funds and rent
+   *
+   * Pass the API's keypair to co-sign; without it the namespace's cancel
+   * grace period must have elapsed since the escrow was created. A spam
+   * protection deposit stays in the agent's vault for a day; see
+   * `refundRetainedSpamDeposit`.
+   */
+  async cancelEscrow(transactionId: string, apiSigner?: anchor.web3.Keypair): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .cancelEscrow()
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: apiSigner?.publicKey ?? null,
+      })
+      .signers(apiSigner ? [apiSigner] : [])
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Settle an active or disputed escrow on a refund both parties agree to,
+   * without a verifier. The wallet is the agent; the API co-signs.
+   */
+  async settleMutual(
+    transactionId: string,
+    refundPercentage: number,
+    apiSigner: anchor.web3.Keypair,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .settleMutual(refundPercentage)
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: apiSigner.publicKey,
+        agentReputation: this.deriveReputationAddress(escrow.agent)[0],
+        apiReputation: this.deriveReputationAddress(escrow.api)[0],
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
+      })
+      .signers([apiSigner])
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Close a released or resolved escrow once its cooldown has passed,
+   * returning the rent to the agent
+   */
+  async closeEscrow(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const [escrowVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from('escrow_vault'), escrowPda.toBuffer()],
+      this.program.programId
+    );
+    const tokenProgram = escrow.mint ? await this.getMintTokenProgram(escrow.mint) : null;
+
+    const tx: string = await (this.program.methods as any)
+      .closeEscrow()
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        escrowVault: escrow.mint ? escrowVault : null,
+        tokenProgram,
+        // Token-2022 vaults may hold withheld transfer fees to harvest first
+        mint: tokenProgram?.equals(TOKEN_2022_PROGRAM_ID) ? escrow.mint : null,
+        cranker: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Release an escrow to the API and close it in one transaction
+   *
+   * Anyone may crank; the wallet earns the escrow's release tip, and the
+   * agent gets the rent back as `closeEscrow` would return it. Unlike
+   * `releaseFunds` this does not open at expiry: the program waits for
+   * `Escrow::sweepable_at`, 7 days after the later of expiry and the dispute
+   * deadline. Fresher escrows take `releaseFunds` then `closeEscrow`. With
+   * `merkleTree` the wallet also pays for the receipt `mintEscrowReceipt`
+   * would mint.
+   */
+  async releaseAndClose(
+    transactionId: string,
+    merkleTree?: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), escrow.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps, releaseInsuranceBps } = await (
+      this.program.account as any
+    ).globalConfig.fetch(config);
+
+    const receiptAccounts = merkleTree
+      ? {
+          treeConfig: PublicKey.findProgramAddressSync([merkleTree.toBuffer()], BUBBLEGUM_PROGRAM_ID)[0],
+          merkleTree,
+          receiptAuthority: this.deriveReceiptAuthorityAddress()[0],
+          logWrapper: SPL_NOOP_PROGRAM_ID,
+          compressionProgram: SPL_ACCOUNT_COMPRESSION_PROGRAM_ID,
+          bubblegumProgram: BUBBLEGUM_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        }
+      : {};
+
+    const tx: string = await (this.program.methods as any)
+      .releaseAndClose()
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        api: escrow.api,
+        spamVault: escrow.spamDeposit.gtn(0) ? this.deriveSpamVaultAddress(escrow.agent)[0] : null,
+        config,
+        treasury: feeBps > 0 ? treasury : null,
+        insuranceFund: releaseInsuranceBps > 0 ? this.deriveInsuranceFundAddress()[0] : null,
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        cranker: this.provider.wallet.publicKey,
+        ...receiptAccounts,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Release funds to API (happy path)
+   *
+   * USD-priced escrows take a fresh Pyth price update, refunding the agent
+   * whatever exceeds the dollar target at that price.
+   */
+  async releaseFunds(transactionId: string, pythPriceUpdate?: PublicKey): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), escrow.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { releaseInsuranceBps } = await (this.program.account as any).globalConfig.fetch(config);
+
+    const tx: string = await (this.program.methods as any)
+      .releaseFunds()
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: escrow.api,
+        insuranceFund: releaseInsuranceBps > 0 ? this.deriveInsuranceFundAddress()[0] : null,
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        agentWallet:
+          escrow.usdTargetCents && !escrow.agent.equals(this.provider.wallet.publicKey)
+            ? escrow.agent
+            : null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Commit to the response delivered for an escrow (API only)
+   *
+   * `responseHash` is the SHA-256 of the response payload; escrows with a
+   * payment reference must echo its `challengeNonce`. With `releaseEarly`
+   * the time lock ends at the dispute deadline, so an undisputed escrow can
+   * be released as soon as the agent's dispute window closes.
+   */
+  async acknowledgeDelivery(
+    transactionId: string,
+    responseHash: number[],
+    challengeNonce: number[] | null = null,
+    releaseEarly = false
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const holdsDeposit = escrow.spamDeposit.gtn(0);
+
+    const tx: string = await (this.program.methods as any)
+      .acknowledgeDelivery(responseHash, challengeNonce, releaseEarly)
+      .accounts({
+        escrow: escrowPda,
+        api: this.provider.wallet.publicKey,
+        spamVault: holdsDeposit ? this.deriveSpamVaultAddress(escrow.agent)[0] : null,
+        agent: holdsDeposit ? escrow.agent : null,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Extend the escrow's time lock (agent only)
+   *
+   * Once the time lock has expired the API must co-sign; pass its keypair as
+   * `apiSigner`. The total lock may not exceed 30 days from creation.
+   */
+  async extendTimeLock(
+    transactionId: string,
+    additionalSeconds: number,
+    apiSigner?: anchor.web3.Keypair
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .extendTimeLock(new anchor.BN(additionalSeconds))
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        api: apiSigner?.publicKey ?? null,
+      })
+      .signers(apiSigner ? [apiSigner] : [])
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Mark escrow as disputed
+   *
+   * An optional priority fee (lamports, capped by the namespace config) is
+   * escrowed for the resolving verifier so it can prioritize the dispute.
+   * Pass `sessionAgent` when the wallet is one of the escrow agent's session
+   * keys. `reason` and the SHA-256 `evidenceHash` of the agent's evidence
+   * tell the verifier what to check; the hash defaults to zero.
+   */
+  async markDisputed(
+    transactionId: string,
+    priorityFee?: anchor.BN,
+    sessionAgent?: PublicKey,
+    reason: DisputeReason = { other: {} },
+    evidenceHash?: number[] | Uint8Array
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const wallet = this.provider.wallet.publicKey;
+    const escrow = await this.getEscrow(transactionId);
+
+    // Namespaces with an assignment queue need every queued verifier's oracle
+    const [assignmentQueue] = this.deriveAssignmentQueueAddress(escrow.namespace);
+    const queue = await (this.program.account as any).assignmentQueue.fetchNullable(
+      assignmentQueue
+    );
+    const oracles: AccountMeta[] = (queue?.verifierPubkeys ?? []).map((verifier: PublicKey) => ({
+      pubkey: this.deriveVerifierOracleAddress(escrow.namespace, verifier)[0],
+      isWritable: true,
+      isSigner: false,
+    }));
+
+    const tx: string = await (this.program.methods as any)
+      .markDisputed(
+        priorityFee ?? new anchor.BN(0),
+        reason,
+        Array.from(evidenceHash ?? new Uint8Array(32))
+      )
+      .accounts({
+        escrow: escrowPda,
+        agent: wallet,
+        session: sessionAgent ? this.deriveSessionKeyAddress(sessionAgent, wallet)[0] : null,
+        assignmentQueue,
+      })
+      .remainingAccounts(oracles)
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Answer a dispute with the API's own evidence (API only)
+   *
+   * Accepted once, within 24 hours of the dispute and before a verifier
+   * rules. `commentUri` may link to up to 200 characters of explanation.
+   */
+  async submitProviderResponse(
+    transactionId: string,
+    evidenceHash: number[] | Uint8Array,
+    commentUri: string | null = null
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .submitProviderResponse(Array.from(evidenceHash), commentUri)
+      .accounts({
+        escrow: escrowPda,
+        api: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Bind a work agreement to an escrow before the API acknowledges delivery
+   */
+  async createWorkAgreement(transactionId: string, terms: WorkAgreementTerms): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .createWorkAgreement(
+        terms.query,
+        terms.requiredFields,
+        terms.minRecords,
+        terms.maxAgeDays,
+        terms.minQualityScore,
+        terms.refundCurve ?? null,
+        [0, 1, 2, 3].map((dimension) => terms.scoreWeights?.[dimension] ?? 0)
+      )
+      .accounts({
+        workAgreement: this.deriveWorkAgreementAddress(escrowPda)[0],
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Add lamports to an active escrow; the total stays within `MAX_AMOUNT`
+   */
+  async addFunds(transactionId: string, amount: anchor.BN): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const tracked = EscrowUtils.hasFlag(escrow.flags, ESCROW_LAYOUT.flags.exposureTracked);
+
+    const tx: string = await (this.program.methods as any)
+      .addFunds(amount)
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        agentReputation: tracked ? this.deriveReputationAddress(escrow.agent)[0] : null,
+        apiReputation: tracked ? this.deriveReputationAddress(escrow.api)[0] : null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Authorize a session key to act for the wallet's agent until `expiresAt`
+   * (unix seconds, at most 30 days ahead)
+   *
+   * `scope` combines `SESSION_SCOPES` bits.
+   */
+  async createSessionKey(
+    sessionKey: PublicKey,
+    scope: number,
+    expiresAt: anchor.BN
+  ): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .createSessionKey(sessionKey, scope, expiresAt)
+      .accounts({
+        agent: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Revoke one of the wallet agent's session keys
+   */
+  async revokeSessionKey(sessionKey: PublicKey): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .revokeSessionKey()
+      .accounts({
+        session: this.deriveSessionKeyAddress(agent, sessionKey)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Mint a compressed NFT receipt of a released or resolved escrow to its agent
+   *
+   * `merkleTree` is a Bubblegum tree delegated to
+   * `deriveReceiptAuthorityAddress()`. The wallet pays.
+   */
+  async mintEscrowReceipt(transactionId: string, merkleTree: PublicKey): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const [treeConfig] = PublicKey.findProgramAddressSync(
+      [merkleTree.toBuffer()],
+      BUBBLEGUM_PROGRAM_ID
+    );
+
+    const tx: string = await (this.program.methods as any)
+      .mintEscrowReceipt()
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        treeConfig,
+        merkleTree,
+        receiptAuthority: this.deriveReceiptAuthorityAddress()[0],
+        payer: this.provider.wallet.publicKey,
+        logWrapper: SPL_NOOP_PROGRAM_ID,
+        compressionProgram: SPL_ACCOUNT_COMPRESSION_PROGRAM_ID,
+        bubblegumProgram: BUBBLEGUM_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Post a resolved escrow's outcome to Wormhole (permissionless, once)
+   *
+   * The wallet pays the message rent and the bridge fee. Facilitators on
+   * other chains verify the resulting VAA against
+   * `deriveWormholeEmitterAddress()`.
+   */
+  async publishSettlement(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const [emitter] = this.deriveWormholeEmitterAddress();
+    const wormholePda = (...seeds: Buffer[]) =>
+      PublicKey.findProgramAddressSync(seeds, WORMHOLE_PROGRAM_ID)[0];
+
+    const tx: string = await (this.program.methods as any)
+      .publishSettlement()
+      .accounts({
+        escrow: escrowPda,
+        wormholeBridge: wormholePda(Buffer.from('Bridge')),
+        wormholeMessage: PublicKey.findProgramAddressSync(
+          [Buffer.from('wormhole_message'), escrowPda.toBuffer()],
+          this.program.programId
+        )[0],
+        wormholeEmitter: emitter,
+        wormholeSequence: wormholePda(Buffer.from('Sequence'), emitter.toBuffer()),
+        wormholeFeeCollector: wormholePda(Buffer.from('fee_collector')),
+        payer: this.provider.wallet.publicKey,
+        clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        wormholeProgram: WORMHOLE_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Append an escrow to its agent's and API's escrow indexes
+   *
+   * Permissionless and once per escrow; the wallet pays for the entries.
+   */
+  async indexEscrow(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const [agentIndex, apiIndex] = await this.getEscrowIndexCounts(escrow.agent, escrow.api);
+
+    const tx: string = await (this.program.methods as any)
+      .indexEscrow()
+      .accounts({
+        escrow: escrowPda,
+        agentIndex: this.deriveEscrowIndexAddress(escrow.agent, 'agent')[0],
+        agentEntry: this.deriveEscrowIndexEntryAddress(escrow.agent, 'agent', agentIndex)[0],
+        apiIndex: this.deriveEscrowIndexAddress(escrow.api, 'api')[0],
+        apiEntry: this.deriveEscrowIndexEntryAddress(escrow.api, 'api', apiIndex)[0],
+        payer: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Let a second key release or dispute an escrow for the agent
+   *
+   * Refunds still go to the agent. Pass `null` to revoke the delegate.
+   */
+  async setDelegate(transactionId: string, delegate: PublicKey | null): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .setDelegate(delegate)
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Start an escrow's time lock at delivery instead of creation
+   *
+   * Until the API acknowledges delivery, only the agent can release the
+   * escrow. Must be called before delivery.
+   */
+  async enableDeliveryClock(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .enableDeliveryClock()
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Stream an escrow to the API over its time lock instead of paying it at once
+   */
+  async enableStreaming(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .enableStreaming()
+      .accounts({
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Offer a tip to whoever releases the escrow once it expires
+   *
+   * Signed by the API; the tip comes out of its payment, capped so that it
+   * and the protocol fee stay within the audited fee limit. Pass 0 to
+   * withdraw the offer.
+   */
+  async setReleaseTip(transactionId: string, releaseTipLamports: number): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .setReleaseTip(new anchor.BN(releaseTipLamports))
+      .accounts({
+        escrow: escrowPda,
+        api: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Withdraw the vested share of a streaming escrow to the wallet's API
+   */
+  async withdrawStreamed(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), escrow.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps } = await (this.program.account as any).globalConfig.fetch(config);
+
+    const tx: string = await (this.program.methods as any)
+      .withdrawStreamed()
+      .accounts({
+        escrow: escrowPda,
+        api: this.provider.wallet.publicKey,
+        agentReputation: this.deriveReputationAddress(escrow.agent)[0],
+        apiReputation: this.deriveReputationAddress(escrow.api)[0],
+        treasury: feeBps > 0 ? treasury : null,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Split an escrow into milestones; the amounts must sum to the escrowed amount
+   */
+  async createMilestonePlan(transactionId: string, amounts: anchor.BN[]): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .createMilestonePlan(amounts)
+      .accounts({
+        milestonePlan: this.deriveMilestonePlanAddress(escrowPda)[0],
+        escrow: escrowPda,
+        agent: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Release one pending milestone to the API
+   */
+  async releaseMilestone(transactionId: string, index: number): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .releaseMilestone(index)
+      .accounts({
+        escrow: escrowPda,
+        milestonePlan: this.deriveMilestonePlanAddress(escrowPda)[0],
+        caller: this.provider.wallet.publicKey,
+        api: escrow.api,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Dispute one pending milestone, leaving the others releasable
+   */
+  async disputeMilestone(transactionId: string, index: number): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .disputeMilestone(index)
+      .accounts({
+        escrow: escrowPda,
+        milestonePlan: this.deriveMilestonePlanAddress(escrowPda)[0],
+        agent: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Subscribe to an API, prepaying `periods` billing periods from the wallet
+   */
+  async createSubscription(
+    api: PublicKey,
+    pricePerPeriod: anchor.BN,
+    periodSeconds: anchor.BN,
+    periods: number,
+    namespace: PublicKey = PublicKey.default
+  ): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .createSubscription(namespace, pricePerPeriod, periodSeconds, periods)
+      .accounts({
+        subscription: this.deriveSubscriptionAddress(agent, api)[0],
+        agent,
+        api,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Prepay further billing periods of the wallet's subscription to `api`
+   */
+  async topUpSubscription(api: PublicKey, periods: number): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .topUpSubscription(periods)
+      .accounts({
+        subscription: this.deriveSubscriptionAddress(agent, api)[0],
+        agent,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Claim the oldest billing period of a subscription to the wallet's API
+   */
+  async claimPeriod(agent: PublicKey): Promise<string> {
+    const api = this.provider.wallet.publicKey;
+    const [subscriptionPda] = this.deriveSubscriptionAddress(agent, api);
+    const subscription = await this.getSubscription(agent, api);
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), subscription.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps } = await (this.program.account as any).globalConfig.fetch(config);
+
+    const tx: string = await (this.program.methods as any)
+      .claimPeriod()
+      .accounts({
+        subscription: subscriptionPda,
+        api,
+        agent,
+        treasury: feeBps > 0 ? treasury : null,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Dispute the oldest billing period of the wallet's subscription to `api`
+   */
+  async disputePeriod(api: PublicKey): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .disputePeriod()
+      .accounts({
+        subscription: this.deriveSubscriptionAddress(agent, api)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Resolve a disputed billing period with a verifier signature
+   *
+   * The verifier signs
+   * "subscription:{subscription}:{period}:{pricePerPeriod}:{nonce}:{qualityScore}",
+   * where period is the subscription's `periodsSettled`.
+   */
+  async resolvePeriodDispute(
+    agent: PublicKey,
+    api: PublicKey,
+    qualityScore: number,
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey
+  ): Promise<string> {
+    const [subscriptionPda] = this.deriveSubscriptionAddress(agent, api);
+
+    const tx: string = await (this.program.methods as any)
+      .resolvePeriodDispute(qualityScore, nonce, signature)
+      .accounts({
+        subscription: subscriptionPda,
+        agent,
+        api,
+        verifier: verifierPublicKey,
+        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        verifierNonces: this.deriveUsedNoncesAddress(verifierPublicKey)[0],
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Cancel the wallet's subscription to `api`, refunding unstarted periods
+   */
+  async cancelSubscription(api: PublicKey): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .cancelSubscription()
+      .accounts({
+        subscription: this.deriveSubscriptionAddress(agent, api)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Open a payment channel to `api`, depositing `deposit` lamports from the wallet
+   */
+  async openPaymentChannel(
+    api: PublicKey,
+    deposit: anchor.BN,
+    namespace: PublicKey = PublicKey.default
+  ): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .openPaymentChannel(namespace, deposit)
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        agent,
+        api,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Add to the balance of the wallet's payment channel to `api`
+   */
+  async fundPaymentChannel(api: PublicKey, amount: anchor.BN): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .fundPaymentChannel(amount)
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        agent,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Record a call the wallet owes `api` in their payment channel
+   */
+  async recordChannelDebit(api: PublicKey, amount: anchor.BN): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .recordChannelDebit(amount)
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Credit `agent` against their channel to the wallet's API
+   */
+  async recordChannelCredit(agent: PublicKey, amount: anchor.BN): Promise<string> {
+    const api = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .recordChannelCredit(amount)
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        api,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Dispute one entry of the wallet's payment channel to `api`
+   */
+  async disputeChannelEntry(api: PublicKey, entryId: anchor.BN): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .disputeChannelEntry(entryId)
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Resolve a disputed channel entry with a verifier signature
+   *
+   * The verifier signs
+   * "channel:{channel}:{entryId}:{amount}:{nonce}:{qualityScore}".
+   */
+  async resolveChannelEntry(
+    agent: PublicKey,
+    api: PublicKey,
+    entryId: anchor.BN,
+    qualityScore: number,
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey
+  ): Promise<string> {
+    const [channelPda] = this.derivePaymentChannelAddress(agent, api);
+    const channel = await this.getPaymentChannel(agent, api);
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), channel.namespace.toBuffer()],
+      this.program.programId
+    );
+
+    const tx: string = await (this.program.methods as any)
+      .resolveChannelEntry(entryId, qualityScore, nonce, signature)
+      .accounts({
+        channel: channelPda,
+        verifier: verifierPublicKey,
+        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        verifierNonces: this.deriveUsedNoncesAddress(verifierPublicKey)[0],
+        config,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Settle the net of `agent`'s channel to the wallet's API
+   */
+  async settleChannel(agent: PublicKey): Promise<string> {
+    const api = this.provider.wallet.publicKey;
+    const channel = await this.getPaymentChannel(agent, api);
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), channel.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { treasury, feeBps } = await (this.program.account as any).globalConfig.fetch(config);
+
+    const tx: string = await (this.program.methods as any)
+      .settleChannel()
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        api,
+        agent,
+        config,
+        treasury: feeBps > 0 ? treasury : null,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Close the wallet's settled payment channel to `api`, reclaiming its balance
+   */
+  async closePaymentChannel(api: PublicKey): Promise<string> {
+    const agent = this.provider.wallet.publicKey;
+
+    const tx: string = await (this.program.methods as any)
+      .closePaymentChannel()
+      .accounts({
+        channel: this.derivePaymentChannelAddress(agent, api)[0],
+        agent,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Resolve dispute with verifier signature (Python verifier)
+   *
+   * The verifier signs "{prefix}:{qualityScore}:{contentHashHex}", where
+   * prefix is `EscrowUtils.resolutionMessagePrefix` and contentHash is the
+   * SHA-256 of the assessed response payload. Escrows created with a request
+   * hash append ":{requestHashHex}", escrows with a payment reference then
+   * append ":{challengeNonceHex}"; escrows bound to a work agreement then
+   * append ":{minRecords}:{maxAgeDays}:{minQualityScore}"; agreements with
+   * score weights resolve through `resolveDisputeScored`. The nonce must be
+   * unused in the verifier's nonce registry. The program derives the refund
+   * from the score using the namespace's refund curve. Namespaces with an
+   * appeal window reject it; use `submitResolution` and `settleResolution`.
+   */
+  async resolveDispute(
+    transactionId: string,
+    qualityScore: number,
+    contentHash: number[],
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    return this.submitVerifierResolution(
+      'resolveDispute',
+      transactionId,
+      qualityScore,
+      contentHash,
+      nonce,
+      signature,
+      verifierPublicKey,
+      pythPriceUpdate
+    );
+  }
+
+  /**
+   * Submit a verifier's signed resolution on its behalf, as a relayer
+   *
+   * Takes the same message and arguments as `resolveDispute`, so the
+   * verifier only signs off-chain and needs no funded wallet. The wallet pays
+   * for the transaction and is reimbursed a fixed 10,000 lamports out of the
+   * escrow.
+   */
+  async resolveWithRelayer(
+    transactionId: string,
+    qualityScore: number,
+    contentHash: number[],
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    return this.submitVerifierResolution(
+      'resolveWithRelayer',
+      transactionId,
+      qualityScore,
+      contentHash,
+      nonce,
+      signature,
+      verifierPublicKey,
+      pythPriceUpdate
+    );
+  }
+
+  /**
+   * Resolve a dispute from per-dimension scores, for escrows whose work
+   * agreement weights score dimensions
+   *
+   * The program aggregates `subScores` by the agreement's weights, rounding
+   * down. The verifier signs the `resolveDispute` message with that
+   * aggregate as the quality score, followed by ":{score1}:{score2}:{score3}:{score4}".
+   */
+  async resolveDisputeScored(
+    transactionId: string,
+    subScores: number[],
+    contentHash: number[],
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    return this.submitVerifierResolution(
+      'resolveDisputeScored',
+      transactionId,
+      subScores,
+      contentHash,
+      nonce,
+      signature,
+      verifierPublicKey,
+      pythPriceUpdate
+    );
+  }
+
+  private async submitVerifierResolution(
+    method: 'resolveDispute' | 'resolveWithRelayer' | 'resolveDisputeScored',
+    transactionId: string,
+    score: number | number[],
+    contentHash: number[],
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    // Need to derive reputation PDAs
+    const [agentReputation] = this.deriveReputationAddress(escrow.agent);
+    const [apiReputation] = this.deriveReputationAddress(escrow.api);
+
+    const tx: string = await (this.program.methods as any)
+      [method](score, contentHash, nonce, signature)
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        api: escrow.api,
+        refundRecipient: escrow.refundRecipient,
+        verifier: verifierPublicKey,
+        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        verifierNonces: this.deriveUsedNoncesAddress(verifierPublicKey)[0],
+        agentReputation: agentReputation,
+        apiReputation: apiReputation,
+        serviceBond: this.serviceBondFor(escrow),
+        ...(await this.reputationHistoriesFor(escrow)),
+        workAgreement: this.workAgreementFor(escrowPda, escrow),
+        apiPenalties: this.deriveProviderPenaltiesAddress(escrow.api)[0],
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        relayer: method === 'resolveWithRelayer' ? this.provider.wallet.publicKey : null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Resolve dispute with an M-of-N quorum of the namespace's verifier set
+   *
+   * Every signer signs the same message as `resolveDispute`, and the
+   * signatures must sit in one Ed25519 instruction in the order given. The
+   * first signer receives any priority fee, and the nonce is consumed from
+   * every signer's nonce registry. Appealed resolutions are decided here, and
+   * their original verifier may not sign.
+   */
+  async resolveDisputeQuorum(
+    transactionId: string,
+    qualityScore: number,
+    contentHash: number[],
+    nonce: anchor.BN,
+    signatures: QuorumSignature[],
+    firstSigner: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+    const [verifierSetPda] = this.deriveVerifierSetAddress(escrow.namespace);
+    const verifierSet = await (this.program.account as any).verifierSet.fetch(verifierSetPda);
+    const signerNonces = signatures.map(({ verifierIndex }) => ({
+      pubkey: this.deriveUsedNoncesAddress(verifierSet.verifiers[verifierIndex])[0],
+      isSigner: false,
+      isWritable: true,
+    }));
+
+    const [agentReputation] = this.deriveReputationAddress(escrow.agent);
+    const [apiReputation] = this.deriveReputationAddress(escrow.api);
+
+    const tx: string = await (this.program.methods as any)
+      .resolveDisputeQuorum(qualityScore, contentHash, nonce, signatures)
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        api: escrow.api,
+        refundRecipient: escrow.refundRecipient,
+        verifier: firstSigner,
+        verifierSet: verifierSetPda,
+        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        agentReputation: agentReputation,
+        apiReputation: apiReputation,
+        serviceBond: this.serviceBondFor(escrow),
+        ...(await this.reputationHistoriesFor(escrow)),
+        workAgreement: this.workAgreementFor(escrowPda, escrow),
+        apiPenalties: this.deriveProviderPenaltiesAddress(escrow.api)[0],
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(signerNonces)
+      .rpc();
+
+    return tx;
   }
 
   /**
-   * Derive escrow PDA from transaction ID
+   * Appeal a held resolution before its appeal window closes
+   *
+   * The wallet must be the escrow's agent or API.
    */
-  deriveEscrowAddress(transactionId: string): [PublicKey, number] {
-    return PublicKey.findProgramAddressSync(
-      [Buffer.from('escrow'), Buffer.from(transactionId)],
-      this.program.programId
-    );
-  }
+  async appealResolution(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
 
-  /**
-   * Derive reputation PDA from entity address
-   */
-  deriveReputationAddress(entity: PublicKey): [PublicKey, number] {
-    return PublicKey.findProgramAddressSync(
-      [Buffer.from('reputation'), entity.toBuffer()],
-      this.program.programId
-    );
+    const tx: string = await (this.program.methods as any)
+      .appealResolution()
+      .accounts({
+        escrow: escrowPda,
+        appellant: this.provider.wallet.publicKey,
+      })
+      .rpc();
+
+    return tx;
   }
 
   /**
-   * Create a new escrow
+   * Escalate a held resolution to a registered arbitrator instead of appealing
+   *
+   * The wallet must be the escrow's agent or API and posts a 0.05 SOL bond,
+   * returned if the arbitrator moves the refund in its favour.
    */
-  async createEscrow(params: CreateEscrowParams): Promise<string> {
-    const [escrowPda] = this.deriveEscrowAddress(params.transactionId);
+  async escalateToArbitrator(transactionId: string, arbitrator: PublicKey): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
 
     const tx: string = await (this.program.methods as any)
-      .initializeEscrow(params.amount, params.timeLock, params.transactionId)
+      .escalateToArbitrator()
       .accounts({
         escrow: escrowPda,
-        agent: this.provider.wallet.publicKey,
-        api: params.apiPublicKey,
+        arbitrator: this.deriveArbitratorAddress(escrow.namespace, arbitrator)[0],
+        escalator: this.provider.wallet.publicKey,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -90,19 +2366,26 @@ export class EscrowClient {
   }
 
   /**
-   * Release funds to API (happy path)
+   * Rule on a resolution escalated to the wallet's arbitrator key
+   *
+   * The refund may differ from the oracle's by at most 25 points, and a
+   * score under a bound work agreement's minimum refunds in full whatever
+   * the ruling. `settleResolution` pays out the ruling afterwards.
    */
-  async releaseFunds(transactionId: string): Promise<string> {
+  async arbitratorRule(transactionId: string, refundPercentage: number): Promise<string> {
     const [escrowPda] = this.deriveEscrowAddress(transactionId);
     const escrow = await this.getEscrow(transactionId);
+    const authority = this.provider.wallet.publicKey;
 
     const tx: string = await (this.program.methods as any)
-      .releaseFunds()
+      .arbitratorRule(refundPercentage)
       .accounts({
         escrow: escrowPda,
-        agent: this.provider.wallet.publicKey,
+        arbitrator: this.deriveArbitratorAddress(escrow.namespace, authority)[0],
+        authority,
+        agent: escrow.agent,
         api: escrow.api,
-        systemProgram: SystemProgram.programId,
+        workAgreement: this.workAgreementFor(escrowPda, escrow),
       })
       .rpc();
 
@@ -110,16 +2393,30 @@ export class EscrowClient {
   }
 
   /**
-   * Mark escrow as disputed
+   * Record a verifier's signed ruling without moving funds
+   *
+   * Takes the same signature as `resolveDispute`. The namespace's appeal
+   * window starts, after which `settleResolution` pays out the split.
    */
-  async markDisputed(transactionId: string): Promise<string> {
+  async submitResolution(
+    transactionId: string,
+    qualityScore: number,
+    contentHash: number[],
+    nonce: anchor.BN,
+    signature: number[],
+    verifierPublicKey: PublicKey
+  ): Promise<string> {
     const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
 
     const tx: string = await (this.program.methods as any)
-      .markDisputed()
+      .submitResolution(qualityScore, contentHash, nonce, signature)
       .accounts({
         escrow: escrowPda,
-        agent: this.provider.wallet.publicKey,
+        verifier: verifierPublicKey,
+        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        verifierNonces: this.deriveUsedNoncesAddress(verifierPublicKey)[0],
+        workAgreement: this.workAgreementFor(escrowPda, escrow),
       })
       .rpc();
 
@@ -127,32 +2424,126 @@ export class EscrowClient {
   }
 
   /**
-   * Resolve dispute with verifier signature (Python verifier)
+   * Pay out a submitted resolution whose appeal window closed without an appeal
    */
-  async resolveDispute(
-    transactionId: string,
-    qualityScore: number,
-    refundPercentage: number,
-    signature: number[],
-    verifierPublicKey: PublicKey
-  ): Promise<string> {
+  async settleResolution(transactionId: string, pythPriceUpdate?: PublicKey): Promise<string> {
     const [escrowPda] = this.deriveEscrowAddress(transactionId);
     const escrow = await this.getEscrow(transactionId);
+    if (!escrow.firstVerifier) {
+      throw new Error('No resolution is held for this escrow');
+    }
 
-    // Need to derive reputation PDAs
     const [agentReputation] = this.deriveReputationAddress(escrow.agent);
     const [apiReputation] = this.deriveReputationAddress(escrow.api);
 
     const tx: string = await (this.program.methods as any)
-      .resolveDispute(qualityScore, refundPercentage, signature)
+      .settleResolution()
       .accounts({
         escrow: escrowPda,
         agent: escrow.agent,
         api: escrow.api,
-        verifier: verifierPublicKey,
-        instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        refundRecipient: escrow.refundRecipient,
+        verifier: escrow.firstVerifier,
         agentReputation: agentReputation,
         apiReputation: apiReputation,
+        serviceBond: this.serviceBondFor(escrow),
+        ...(await this.reputationHistoriesFor(escrow)),
+        apiPenalties: this.deriveProviderPenaltiesAddress(escrow.api)[0],
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Apply time decay to an entity's stored reputation score (permissionless)
+   */
+  async refreshReputation(entity: PublicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .refreshReputation()
+      .accounts({
+        reputation: this.deriveReputationAddress(entity)[0],
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Start keeping an entity's score history; the wallet pays the rent
+   */
+  async initReputationHistory(entity: PublicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .initReputationHistory()
+      .accounts({
+        reputationHistory: this.deriveReputationHistoryAddress(entity)[0],
+        reputation: this.deriveReputationAddress(entity)[0],
+        payer: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Claim insurance for a resolved escrow whose provider was suspended
+   * mid-engagement; pays out immediately. The wallet must be the agent.
+   */
+  async claimInsurance(transactionId: string): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const tx: string = await (this.program.methods as any)
+      .claimInsurance()
+      .accounts({
+        escrow: escrowPda,
+        claim: PublicKey.findProgramAddressSync(
+          [Buffer.from('insurance_claim'), escrowPda.toBuffer()],
+          this.program.programId
+        )[0],
+        providerPenalties: this.deriveProviderPenaltiesAddress(escrow.api)[0],
+        insuranceFund: this.deriveInsuranceFundAddress()[0],
+        config: PublicKey.findProgramAddressSync(
+          [Buffer.from('global_config'), PublicKey.default.toBuffer()],
+          this.program.programId
+        )[0],
+        claimant: this.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Record an entity's current score if it changed since the last snapshot
+   */
+  async snapshotReputation(entity: PublicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .snapshotReputation()
+      .accounts({
+        reputationHistory: this.deriveReputationHistoryAddress(entity)[0],
+        reputation: this.deriveReputationAddress(entity)[0],
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Start tracking strikes for a provider; the wallet pays the rent
+   */
+  async initProviderPenalties(provider: PublicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .initProviderPenalties()
+      .accounts({
+        providerPenalties: this.deriveProviderPenaltiesAddress(provider)[0],
+        provider,
+        payer: this.provider.wallet.publicKey,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -160,6 +2551,18 @@ export class EscrowClient {
     return tx;
   }
 
+  /**
+   * Lift a provider's suspension once it has run its course
+   */
+  async liftSuspension(provider: PublicKey): Promise<string> {
+    const tx: string = await (this.program.methods as any)
+      .liftSuspension()
+      .accounts({ providerPenalties: this.deriveProviderPenaltiesAddress(provider)[0] })
+      .rpc();
+
+    return tx;
+  }
+
   /**
    * Resolve dispute with Switchboard On-Demand oracle
    *
@@ -168,8 +2571,8 @@ export class EscrowClient {
   async resolveDisputeSwitchboard(
     transactionId: string,
     qualityScore: number,
-    refundPercentage: number,
-    switchboardAttestation: PublicKey
+    switchboardAttestation: PublicKey,
+    pythPriceUpdate?: PublicKey
   ): Promise<string> {
     const [escrowPda] = this.deriveEscrowAddress(transactionId);
     const escrow = await this.getEscrow(transactionId);
@@ -178,15 +2581,68 @@ export class EscrowClient {
     const [agentReputation] = this.deriveReputationAddress(escrow.agent);
     const [apiReputation] = this.deriveReputationAddress(escrow.api);
 
+    // The feed must report to the namespace's trusted queue
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from('global_config'), escrow.namespace.toBuffer()],
+      this.program.programId
+    );
+    const { switchboardQueue } = await (this.program.account as any).globalConfig.fetch(config);
+
     const tx: string = await (this.program.methods as any)
-      .resolveDisputeSwitchboard(qualityScore, refundPercentage)
+      .resolveDisputeSwitchboard(qualityScore)
       .accounts({
         escrow: escrowPda,
         agent: escrow.agent,
         api: escrow.api,
+        refundRecipient: escrow.refundRecipient,
         switchboardFunction: switchboardAttestation,
+        switchboardQueue,
         agentReputation: agentReputation,
         apiReputation: apiReputation,
+        serviceBond: this.serviceBondFor(escrow),
+        ...(await this.reputationHistoriesFor(escrow)),
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return tx;
+  }
+
+  /**
+   * Resolve dispute with a Pyth-published quality attestation
+   *
+   * `priceUpdate` is a fully verified update for the namespace's
+   * `pythQualityFeedId`, posted through the Pyth receiver beforehand.
+   * USD escrows also take `pythPriceUpdate` for its SOL/USD feed.
+   */
+  async resolveDisputePyth(
+    transactionId: string,
+    qualityScore: number,
+    priceUpdate: PublicKey,
+    pythPriceUpdate?: PublicKey
+  ): Promise<string> {
+    const [escrowPda] = this.deriveEscrowAddress(transactionId);
+    const escrow = await this.getEscrow(transactionId);
+
+    const [agentReputation] = this.deriveReputationAddress(escrow.agent);
+    const [apiReputation] = this.deriveReputationAddress(escrow.api);
+
+    const tx: string = await (this.program.methods as any)
+      .resolveDisputePyth(qualityScore)
+      .accounts({
+        escrow: escrowPda,
+        agent: escrow.agent,
+        api: escrow.api,
+        refundRecipient: escrow.refundRecipient,
+        priceUpdate,
+        agentReputation,
+        apiReputation,
+        serviceBond: this.serviceBondFor(escrow),
+        ...(await this.reputationHistoriesFor(escrow)),
+        treasury: await this.disputeTreasuryFor(escrow),
+        pythPriceUpdate: pythPriceUpdate ?? null,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -202,6 +2658,104 @@ export class EscrowClient {
     return await (this.program.account as any).escrow.fetch(escrowPda);
   }
 
+  /**
+   * Number of escrows indexed for an agent and for an API
+   */
+  async getEscrowIndexCounts(agent: PublicKey, api: PublicKey): Promise<[number, number]> {
+    const [agentIndex, apiIndex] = await Promise.all([
+      (this.program.account as any).agentIndex.fetchNullable(
+        this.deriveEscrowIndexAddress(agent, 'agent')[0]
+      ),
+      (this.program.account as any).apiIndex.fetchNullable(
+        this.deriveEscrowIndexAddress(api, 'api')[0]
+      ),
+    ]);
+    return [agentIndex?.count.toNumber() ?? 0, apiIndex?.count.toNumber() ?? 0];
+  }
+
+  /**
+   * Escrow addresses indexed for an agent or an API, in indexing order
+   *
+   * Pages through the index without scanning program accounts. Closed
+   * escrows keep their entries.
+   */
+  async listIndexedEscrows(
+    party: PublicKey,
+    role: 'agent' | 'api',
+    offset = 0,
+    limit = 100
+  ): Promise<PublicKey[]> {
+    const account = role === 'agent' ? 'agentIndex' : 'apiIndex';
+    const index = await (this.program.account as any)[account].fetchNullable(
+      this.deriveEscrowIndexAddress(party, role)[0]
+    );
+    const end = Math.min(index?.count.toNumber() ?? 0, offset + limit);
+    if (end <= offset) {
+      return [];
+    }
+
+    const entries = await (this.program.account as any).escrowIndexEntry.fetchMultiple(
+      Array.from(
+        { length: end - offset },
+        (_, i) => this.deriveEscrowIndexEntryAddress(party, role, offset + i)[0]
+      )
+    );
+    return entries.filter((entry: any) => entry !== null).map((entry: any) => entry.escrow);
+  }
+
+  /**
+   * Get subscription account data
+   */
+  async getSubscription(agent: PublicKey, api: PublicKey): Promise<SubscriptionAccount> {
+    const [subscriptionPda] = this.deriveSubscriptionAddress(agent, api);
+    return await (this.program.account as any).subscriptionEscrow.fetch(subscriptionPda);
+  }
+
+  /**
+   * Organization membership and daily spend of an agent, or null if it
+   * belongs to no organization
+   */
+  async getOrganizationMember(agent: PublicKey): Promise<OrganizationMemberAccount | null> {
+    const [memberPda] = this.deriveOrganizationMemberAddress(agent);
+    return await (this.program.account as any).organizationMember.fetchNullable(memberPda);
+  }
+
+  /**
+   * Get payment channel account data
+   */
+  async getPaymentChannel(agent: PublicKey, api: PublicKey): Promise<PaymentChannelAccount> {
+    const [channelPda] = this.derivePaymentChannelAddress(agent, api);
+    return await (this.program.account as any).paymentChannel.fetch(channelPda);
+  }
+
+  /**
+   * Recorded score snapshots of an entity, oldest first
+   */
+  async getReputationHistory(entity: PublicKey): Promise<ScoreSnapshot[]> {
+    const [historyPda] = this.deriveReputationHistoryAddress(entity);
+    const history = await (this.program.account as any).reputationHistory.fetch(historyPda);
+    const capacity = history.snapshots.length;
+    const start = (history.nextIndex + capacity - history.count) % capacity;
+    return Array.from(
+      { length: history.count },
+      (_, i) => history.snapshots[(start + i) % capacity] as ScoreSnapshot
+    );
+  }
+
+  /**
+   * Query an entity's decayed reputation score through the program;
+   * neutral and uninitialized if the entity has no reputation account
+   */
+  async getReputationView(entity: PublicKey): Promise<ReputationView> {
+    return (this.program.methods as any)
+      .getReputation()
+      .accounts({
+        entity,
+        reputation: this.deriveReputationAddress(entity)[0],
+      })
+      .view();
+  }
+
   /**
    * Check if escrow exists
    */
@@ -219,14 +2773,7 @@ export class EscrowClient {
    */
   async getStatus(transactionId: string): Promise<string> {
     const escrow = await this.getEscrow(transactionId);
-    const status = escrow.status;
-
-    if ('active' in status) return 'Active';
-    if ('released' in status) return 'Released';
-    if ('disputed' in status) return 'Disputed';
-    if ('resolved' in status) return 'Resolved';
-
-    return 'Unknown';
+    return EscrowUtils.escrowStatus(escrow.flags);
   }
 
   /**
@@ -255,8 +2802,10 @@ export class EscrowClient {
     callbacks: {
       onInitialized?: (event: any) => void;
       onDisputed?: (event: any) => void;
-      onResolved?: (event: any) => void;
+      onResolved?: (event: DisputeResolvedEvent) => void;
       onReleased?: (event: any) => void;
+      /** Score changes, e.g. to alert a provider whose score dropped */
+      onReputationUpdated?: (event: ReputationUpdatedEvent) => void;
     }
   ): number[] {
     const listeners: number[] = [];
@@ -285,6 +2834,12 @@ export class EscrowClient {
       );
     }
 
+    if (callbacks.onReputationUpdated) {
+      listeners.push(
+        this.program.addEventListener('ReputationUpdated', callbacks.onReputationUpdated)
+      );
+    }
+
     return listeners;
   }
 
@@ -304,7 +2859,7 @@ export class EscrowClient {
     const escrows = await (this.program.account as any).escrow.all([
       {
         memcmp: {
-          offset: 8, // Discriminator
+          offset: ESCROW_LAYOUT.agentOffset,
           bytes: agentPublicKey.toBase58(),
         },
       },
@@ -337,6 +2892,8 @@ export class EscrowValidator {
   static readonly MIN_TIME_LOCK = 3600; // 1 hour
   static readonly MAX_TIME_LOCK = 2_592_000; // 30 days
   static readonly MAX_TRANSACTION_ID_LENGTH = 64;
+  static readonly MAX_METADATA_URI_LENGTH = 200;
+  static readonly MIN_VERIFIER_TIMEOUT = 86_400; // 1 day
 
   /**
    * Validate escrow amount
@@ -398,6 +2955,38 @@ export class EscrowValidator {
     return { valid: true };
   }
 
+  /**
+   * Validate metadata URI
+   */
+  static validateMetadataUri(uri: string): { valid: boolean; error?: string } {
+    if (uri.length > this.MAX_METADATA_URI_LENGTH) {
+      return {
+        valid: false,
+        error: `Metadata URI cannot exceed ${this.MAX_METADATA_URI_LENGTH} characters`,
+      };
+    }
+
+    return { valid: true };
+  }
+
+  /**
+   * Validate the split for disputes no verifier resolves
+   */
+  static validateStaleDisputeTerms(terms: StaleDisputeTerms): { valid: boolean; error?: string } {
+    const timeout = terms.verifierTimeout.toNumber();
+    if (timeout < this.MIN_VERIFIER_TIMEOUT || timeout > this.MAX_TIME_LOCK) {
+      return {
+        valid: false,
+        error: 'Verifier timeout must be between 1 and 30 days',
+      };
+    }
+    if (terms.refundPercentage < 0 || terms.refundPercentage > 100) {
+      return { valid: false, error: 'Stale dispute refund must be between 0 and 100' };
+    }
+
+    return { valid: true };
+  }
+
   /**
    * Validate quality score
    */
@@ -438,6 +3027,16 @@ export class EscrowValidator {
     const txIdCheck = this.validateTransactionId(params.transactionId);
     if (!txIdCheck.valid) errors.push(txIdCheck.error!);
 
+    if (params.metadataUri !== undefined) {
+      const uriCheck = this.validateMetadataUri(params.metadataUri);
+      if (!uriCheck.valid) errors.push(uriCheck.error!);
+    }
+
+    if (params.staleDisputeTerms !== undefined) {
+      const termsCheck = this.validateStaleDisputeTerms(params.staleDisputeTerms);
+      if (!termsCheck.valid) errors.push(termsCheck.error!);
+    }
+
     return {
       valid: errors.length === 0,
       errors,
@@ -449,6 +3048,70 @@ export class EscrowValidator {
  * Utility functions
  */
 export class EscrowUtils {
+  /**
+   * Decode the status packed into an escrow's flags
+   */
+  static escrowStatus(flags: number): EscrowStatusName {
+    return ESCROW_STATUSES[flags & ESCROW_LAYOUT.statusMask];
+  }
+
+  /**
+   * Decode the payload of a settlement VAA posted by `publishSettlement`
+   */
+  static decodeSettlementPayload(payload: Buffer): SettlementPayload {
+    if (payload[0] !== SETTLEMENT_PAYLOAD_ID) {
+      throw new Error(`Not a settlement payload: id ${payload[0]}`);
+    }
+    const key = (offset: number) => new PublicKey(payload.subarray(offset, offset + 32));
+    const mint = key(97);
+    const qualityScore = payload[153];
+    const idLength = payload[163];
+    return {
+      escrow: key(1),
+      agent: key(33),
+      api: key(65),
+      mint: mint.equals(PublicKey.default) ? null : mint,
+      amount: payload.readBigUInt64BE(129),
+      refundedToAgent: payload.readBigUInt64BE(137),
+      paidToApi: payload.readBigUInt64BE(145),
+      qualityScore: qualityScore === 0xff ? null : qualityScore,
+      refundPercentage: payload[154],
+      settledAt: Number(payload.readBigInt64BE(155)),
+      transactionId: payload.subarray(164, 164 + idLength).toString('utf8'),
+    };
+  }
+
+  /**
+   * Whether a flag from `ESCROW_LAYOUT.flags` is set
+   */
+  static hasFlag(flags: number, flag: number): boolean {
+    return (flags & flag) !== 0;
+  }
+
+  /**
+   * Unix time after which `markDisputed` is rejected and anyone may
+   * release the escrow (once it has also expired)
+   */
+  static disputeDeadline(escrow: EscrowAccount): number {
+    const start = escrow.deliveredAt ?? escrow.createdAt;
+    return start.add(escrow.disputeWindow).toNumber();
+  }
+
+  /**
+   * Start of every message a verifier signs for an escrow:
+   * "{transactionId}:{escrow}:{amount}:{resolutionNonce}:{nonce}"
+   */
+  static resolutionMessagePrefix(
+    escrow: EscrowAccount,
+    escrowPda: PublicKey,
+    nonce: anchor.BN
+  ): string {
+    return (
+      `${escrow.transactionId}:${escrowPda.toBase58()}:${escrow.amount.toString()}:` +
+      `${escrow.resolutionNonce.toString()}:${nonce.toString()}`
+    );
+  }
+
   /**
    * Convert SOL to lamports
    */
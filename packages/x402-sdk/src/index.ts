@@ -5,7 +5,22 @@
 
 export { KamiyoClient } from './client';
 export { Hyoban } from './reputation';
-export { EscrowClient, EscrowValidator, EscrowUtils } from './escrow-client';
+export {
+  EscrowClient,
+  EscrowValidator,
+  EscrowUtils,
+  ESCROW_LAYOUT,
+  EVENT_VERSION,
+  SESSION_SCOPES,
+  type DisputeReason,
+  type DisputeResolvedEvent,
+  type ProviderTermsParams,
+  type RefundStep,
+  type ResolutionMethod,
+  type ReputationChangeReason,
+  type ReputationUpdatedEvent,
+  type SettlementPayload,
+} from './escrow-client';
 export {
   SwitchboardClient,
   MockSwitchboardClient,
@@ -13,6 +13,7 @@ export interface EntityReputation {
   disputesWon: number;
   disputesPartial: number;
   disputesLost: number;
+  /** Exact mean of the quality scores received, not rounded */
   averageQualityReceived: number;
   reputationScore: number; // 0-1000
   createdAt: number;
@@ -59,7 +60,8 @@ export class Hyoban {
     try {
       const [repPDA] = this.getReputationPDA(entity);
       const account = await (this.program.account as any).entityReputation.fetch(repPDA);
-      
+      const qualityCount = account.qualityCount.toNumber();
+
       return {
         entity: account.entity,
         entityType: account.entityType.agent ? 'Agent' : 'Provider',
@@ -68,7 +70,8 @@ export class Hyoban {
         disputesWon: account.disputesWon.toNumber(),
         disputesPartial: account.disputesPartial.toNumber(),
         disputesLost: account.disputesLost.toNumber(),
-        averageQualityReceived: account.averageQualityReceived,
+        averageQualityReceived:
+          qualityCount === 0 ? 0 : account.qualitySum.toNumber() / qualityCount,
         reputationScore: account.reputationScore,
         createdAt: account.createdAt.toNumber(),
         lastUpdated: account.lastUpdated.toNumber(),
@@ -131,6 +134,84 @@ export class Hyoban {
     }
   }
 
+  /**
+   * Build the instruction creating an entity's rate limiter at Basic
+   */
+  async initRateLimiterInstruction(
+    entity: PublicKey,
+    payer: PublicKey
+  ): Promise<TransactionInstruction> {
+    return (this.program.methods as any)
+      .initRateLimiter()
+      .accounts({
+        rateLimiter: this.getRateLimiterPDA(entity)[0],
+        entity,
+        payer,
+      })
+      .instruction();
+  }
+
+  /**
+   * Build the instruction raising an entity's verification level
+   *
+   * Without `authority` only Staked is reachable, and the entity locks
+   * 1 SOL in its rate limiter.
+   */
+  async upgradeVerificationLevelInstruction(
+    entity: PublicKey,
+    level: VerificationLevel['level'],
+    authority?: PublicKey
+  ): Promise<TransactionInstruction> {
+    const key = level === 'KYC' ? 'kyc' : level.toLowerCase();
+    return (this.program.methods as any)
+      .upgradeVerificationLevel({ [key]: {} })
+      .accounts({
+        rateLimiter: this.getRateLimiterPDA(entity)[0],
+        entity,
+        authority: authority ?? null,
+      })
+      .instruction();
+  }
+
+  /**
+   * Build the instruction creating an entity's reputation
+   *
+   * A provider reputation must be paid for by the provider itself.
+   */
+  async initReputationInstruction(
+    entity: PublicKey,
+    entityType: EntityReputation['entityType'],
+    payer: PublicKey
+  ): Promise<TransactionInstruction> {
+    return (this.program.methods as any)
+      .initReputation(entityType === 'Provider' ? { provider: {} } : { agent: {} })
+      .accounts({
+        reputation: this.getReputationPDA(entity)[0],
+        entity,
+        payer,
+      })
+      .instruction();
+  }
+
+  /**
+   * Build the instruction moving a reputation to the quality-sum layout
+   *
+   * Accounts created before the upgrade fail to load until migrated;
+   * `payer` covers the extra rent.
+   */
+  async migrateReputationInstruction(
+    entity: PublicKey,
+    payer: PublicKey
+  ): Promise<TransactionInstruction> {
+    return (this.program.methods as any)
+      .migrateReputation()
+      .accounts({
+        reputation: this.getReputationPDA(entity)[0],
+        payer,
+      })
+      .instruction();
+  }
+
   /**
    * Calculate dispute cost based on reputation
    */
@@ -12,8 +12,15 @@ export interface QualityScoringParams {
   expectedCriteria: string[];
   expectedRecordCount?: number;
   transactionId?: string;
+  /** Refund curve of the escrow's namespace, which the attested refund must match */
+  refundCurve?: { maxQualityScore: number; refundPercentage: number }[];
+  /** Refund curve the escrow carries itself, which takes precedence over the namespace's */
+  escrowRefundCurve?: { maxQualityScore: number; refundPercentage: number }[];
 }
 
+/** Quality feeds report quality_score * 1000 + refund_percentage */
+const FEED_SCORE_SCALE = 1000;
+
 export interface QualityScoringResult {
   qualityScore: number;
   refundPercentage: number;
@@ -78,12 +85,15 @@ export class SwitchboardClient {
           expectedCriteria: params.expectedCriteria,
           expectedRecordCount: params.expectedRecordCount || 0,
           transactionId: params.transactionId || '',
+          refundCurve: params.refundCurve,
+          escrowRefundCurve: params.escrowRefundCurve,
         },
       });
 
-      // Extract result from simulation
-      const qualityScore = simulationResult.result.value as number;
-      const refundPercentage = this.calculateRefundPercentage(qualityScore);
+      // The feed value packs the score and the refund it attests to
+      const value = simulationResult.result.value as number;
+      const qualityScore = Math.floor(value / FEED_SCORE_SCALE);
+      const refundPercentage = value % FEED_SCORE_SCALE;
 
       // Parse full result details
       const result: QualityScoringResult = {
@@ -110,29 +120,11 @@ export class SwitchboardClient {
     }
   }
 
-  /**
-   * Calculate refund percentage from quality score
-   * Matches the Anchor program logic
-   */
-  private calculateRefundPercentage(qualityScore: number): number {
-    if (qualityScore >= 80) {
-      return 0; // Good quality - no refund
-    }
-
-    if (qualityScore >= 50) {
-      // Sliding scale: ((80 - score) / 80) * 100
-      return Math.round(((80 - qualityScore) / 80) * 100);
-    }
-
-    // Poor quality - full refund
-    return 100;
-  }
-
   /**
    * Verify Switchboard attestation is valid and recent
    *
    * @param attestation Attestation account public key
-   * @returns True if attestation is valid and recent (< 60 seconds)
+   * @returns True if attestation is valid and recent (within the namespace's staleness window)
    */
   async verifyAttestation(attestation: PublicKey): Promise<{
     valid: boolean;
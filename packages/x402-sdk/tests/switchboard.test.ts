@@ -133,7 +133,6 @@ describe('Switchboard Dispute Resolution', () => {
       const tx = await escrowClient.resolveDisputeSwitchboard(
         transactionId,
         assessment.qualityScore,
-        assessment.refundPercentage,
         assessment.attestation
       );
 
@@ -143,7 +142,7 @@ describe('Switchboard Dispute Resolution', () => {
       // Verify escrow state
       const escrow = await escrowClient.getEscrow(transactionId);
       expect(escrow.qualityScore).toBe(45);
-      expect(escrow.refundPercentage).toBe(100);
+      expect(escrow.refundPercentage).toBe(50); // From the program's default refund curve
       expect(await escrowClient.getStatus(transactionId)).toBe('Resolved');
     });
 
@@ -171,7 +170,7 @@ describe('Switchboard Dispute Resolution', () => {
       await escrowClient.markDisputed(txId2);
 
       // Resolve with different quality scores
-      mockSwitchboard.setMockScore(80);
+      mockSwitchboard.setMockScore(90);
       const assessment1 = await mockSwitchboard.requestQualityAssessment({
         originalQuery: 'query1',
         dataReceived: {},
@@ -179,7 +178,7 @@ describe('Switchboard Dispute Resolution', () => {
         transactionId: txId1,
       });
 
-      mockSwitchboard.setMockScore(30);
+      mockSwitchboard.setMockScore(10);
       const assessment2 = await mockSwitchboard.requestQualityAssessment({
         originalQuery: 'query2',
         dataReceived: {},
@@ -190,14 +189,12 @@ describe('Switchboard Dispute Resolution', () => {
       await escrowClient.resolveDisputeSwitchboard(
         txId1,
         assessment1.qualityScore,
-        assessment1.refundPercentage,
         assessment1.attestation
       );
 
       await escrowClient.resolveDisputeSwitchboard(
         txId2,
         assessment2.qualityScore,
-        assessment2.refundPercentage,
         assessment2.attestation
       );
 
@@ -244,20 +241,6 @@ describe('Switchboard Dispute Resolution', () => {
         escrowClient.resolveDisputeSwitchboard(
           txId,
           150, // Invalid: > 100
-          0,
-          PublicKey.default
-        )
-      ).rejects.toThrow();
-    });
-
-    it('should reject invalid refund percentages', async () => {
-      const txId = EscrowUtils.generateTransactionId('invalid');
-
-      await expect(
-        escrowClient.resolveDisputeSwitchboard(
-          txId,
-          50,
-          150, // Invalid: > 100
           PublicKey.default
         )
       ).rejects.toThrow();
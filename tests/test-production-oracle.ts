@@ -164,7 +164,7 @@ async function initReputation(
     );
 
     return await program.methods
-        .initReputation()
+        .initReputation({ agent: {} })
         .accounts({
             reputation: reputationPda,
             entity: entity,